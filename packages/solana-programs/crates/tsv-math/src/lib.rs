@@ -0,0 +1,505 @@
+//! Share/asset conversion math.
+//!
+//! Conversions use a virtual offset (OpenZeppelin ERC-4626 style): the vault behaves as if it
+//! always held `10^VIRTUAL_DECIMALS_OFFSET` extra shares backed by 1 extra unit of assets.
+//! This makes the classic first-depositor inflation attack (donate to `asset_vault` to
+//! inflate the share price and round the next depositor down to zero shares) unprofitable.
+//!
+//! All arithmetic is checked and surfaces `MathError` instead of panicking.
+//!
+//! Shared by the on-chain program and off-chain clients so previews computed off-chain round
+//! exactly like the instructions they preview. `no_std` and dependency-free to build for BPF.
+
+#![no_std]
+
+use core::fmt;
+
+/// Failure of a checked operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathError {
+    Overflow,
+    DivisionByZero,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathError::Overflow => f.write_str("arithmetic overflow"),
+            MathError::DivisionByZero => f.write_str("division by zero"),
+        }
+    }
+}
+
+/// `Result` of the checked operations
+pub type Result<T> = core::result::Result<T, MathError>;
+
+/// Decimal offset between shares and assets (1 asset unit = 10^offset shares initially)
+pub const VIRTUAL_DECIMALS_OFFSET: u32 = 3;
+
+/// Fee denominator: 10_000 bps = 100%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Seconds in a (365-day) year, for annualized fees
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Rounding direction for conversions. Every instruction picks the direction that favors the
+/// vault (existing holders) over the caller:
+///
+/// | operation  | computes         | rounding |
+/// |------------|------------------|----------|
+/// | `deposit`  | shares out       | `Floor`  |
+/// | `mint`     | assets in        | `Ceil`   |
+/// | `redeem`   | assets out       | `Floor`  |
+/// | `withdraw` | shares in        | `Ceil`   |
+/// | fees       | fee charged      | `Ceil`   |
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+}
+
+/// `a * b / denominator` in u128 with explicit rounding
+pub fn mul_div(a: u64, b: u128, denominator: u128, rounding: Rounding) -> Result<u64> {
+    if denominator == 0 {
+        return Err(MathError::DivisionByZero);
+    }
+    let product = (a as u128).checked_mul(b).ok_or(MathError::Overflow)?;
+    let quotient = match rounding {
+        Rounding::Floor => product / denominator,
+        Rounding::Ceil => {
+            product
+                .checked_add(denominator - 1)
+                .ok_or(MathError::Overflow)?
+                / denominator
+        }
+    };
+    u64::try_from(quotient).map_err(|_| MathError::Overflow)
+}
+
+/// `a + b`, erroring on overflow
+pub fn add(a: u64, b: u64) -> Result<u64> {
+    a.checked_add(b).ok_or(MathError::Overflow)
+}
+
+/// `a - b`, erroring on underflow
+pub fn sub(a: u64, b: u64) -> Result<u64> {
+    a.checked_sub(b).ok_or(MathError::Overflow)
+}
+
+fn virtual_shares(total_shares: u64) -> u128 {
+    total_shares as u128 + 10u128.pow(VIRTUAL_DECIMALS_OFFSET)
+}
+
+fn virtual_assets(total_assets: u64) -> u128 {
+    total_assets as u128 + 1
+}
+
+/// Shares for `assets` at the vault exchange rate
+pub fn convert_to_shares(
+    assets: u64,
+    total_assets: u64,
+    total_shares: u64,
+    rounding: Rounding,
+) -> Result<u64> {
+    mul_div(
+        assets,
+        virtual_shares(total_shares),
+        virtual_assets(total_assets),
+        rounding,
+    )
+}
+
+/// Assets for `shares` at the vault exchange rate
+pub fn convert_to_assets(
+    shares: u64,
+    total_assets: u64,
+    total_shares: u64,
+    rounding: Rounding,
+) -> Result<u64> {
+    mul_div(
+        shares,
+        virtual_assets(total_assets),
+        virtual_shares(total_shares),
+        rounding,
+    )
+}
+
+/// Fee of `fee_bps` on `amount` (rounds up)
+pub fn fee_on_amount(amount: u64, fee_bps: u16) -> Result<u64> {
+    mul_div(
+        amount,
+        fee_bps as u128,
+        BPS_DENOMINATOR as u128,
+        Rounding::Ceil,
+    )
+}
+
+/// Gross amount such that `net` remains after a `fee_bps` fee (rounds up)
+pub fn gross_up_for_fee(net: u64, fee_bps: u16) -> Result<u64> {
+    let fee_denominator = (BPS_DENOMINATOR as u128)
+        .checked_sub(fee_bps as u128)
+        .ok_or(MathError::Overflow)?;
+    mul_div(
+        net,
+        BPS_DENOMINATOR as u128,
+        fee_denominator,
+        Rounding::Ceil,
+    )
+}
+
+/// Shares owed for an annualized `fee_bps` management fee over `elapsed` seconds (rounds down,
+/// so holders are never over-charged)
+pub fn management_fee_shares(total_shares: u64, fee_bps: u16, elapsed: u64) -> Result<u64> {
+    let rate = (fee_bps as u128)
+        .checked_mul(elapsed as u128)
+        .ok_or(MathError::Overflow)?;
+    mul_div(
+        total_shares,
+        rate,
+        BPS_DENOMINATOR as u128 * SECONDS_PER_YEAR as u128,
+        Rounding::Floor,
+    )
+}
+
+/// Redeem fee scaled linearly with utilization (share of `total_assets` not held idle), from
+/// `base_fee_bps` with everything idle to `max_fee_bps` with nothing idle (rounds up). Returns
+/// `base_fee_bps` when `max_fee_bps <= base_fee_bps`
+pub fn utilization_fee_bps(
+    base_fee_bps: u16,
+    max_fee_bps: u16,
+    idle_assets: u64,
+    total_assets: u64,
+) -> Result<u16> {
+    if max_fee_bps <= base_fee_bps || total_assets == 0 {
+        return Ok(base_fee_bps);
+    }
+    let deployed = total_assets.saturating_sub(idle_assets);
+    let extra = mul_div(
+        deployed,
+        (max_fee_bps - base_fee_bps) as u128,
+        total_assets as u128,
+        Rounding::Ceil,
+    )?;
+    // extra <= max_fee_bps - base_fee_bps, so this fits in u16
+    Ok(base_fee_bps + extra as u16)
+}
+
+/// Penalty of `penalty_bps` decaying linearly to zero over `window` seconds, `elapsed` seconds
+/// after the deposit (rounds up)
+pub fn decayed_penalty_bps(penalty_bps: u16, window: u32, elapsed: u64) -> Result<u16> {
+    if elapsed >= window as u64 {
+        return Ok(0);
+    }
+    let remaining = window as u64 - elapsed;
+    let penalty = mul_div(
+        remaining,
+        penalty_bps as u128,
+        window as u128,
+        Rounding::Ceil,
+    )?;
+    // remaining < window, so penalty <= penalty_bps
+    Ok(penalty as u16)
+}
+
+/// Part of `locked` still locked with `remaining` of `window` seconds left of its linear unlock
+/// (rounds up)
+pub fn still_locked(locked: u64, remaining: u64, window: u64) -> Result<u64> {
+    if remaining == 0 {
+        return Ok(0);
+    }
+    if remaining >= window {
+        return Ok(locked);
+    }
+    mul_div(locked, remaining as u128, window as u128, Rounding::Ceil)
+}
+
+/// Part of `locked_profit`, locked at `locked_profit_ts` to unlock linearly until
+/// `profit_unlock_end_ts`, still locked at `now`
+pub fn locked_profit_at(
+    locked_profit: u64,
+    locked_profit_ts: i64,
+    profit_unlock_end_ts: i64,
+    now: i64,
+) -> Result<u64> {
+    if now <= locked_profit_ts {
+        return Ok(locked_profit);
+    }
+    let remaining = profit_unlock_end_ts.saturating_sub(now).max(0) as u64;
+    let window = profit_unlock_end_ts.saturating_sub(locked_profit_ts).max(0) as u64;
+    still_locked(locked_profit, remaining, window)
+}
+
+/// Simple (not compounded) annualized return in bps of an exchange rate moving from
+/// `start_rate` to `end_rate` over `elapsed` seconds, negative for a loss (rounds toward zero)
+pub fn annualized_return_bps(start_rate: u64, end_rate: u64, elapsed: u64) -> Result<i64> {
+    let denominator = start_rate as i128 * elapsed as i128;
+    if denominator == 0 {
+        return Err(MathError::DivisionByZero);
+    }
+    // |change| < 2^64 and the factor < 2^39, so this can't overflow an i128
+    let change = end_rate as i128 - start_rate as i128;
+    let bps = change * (BPS_DENOMINATOR as i128 * SECONDS_PER_YEAR as i128) / denominator;
+    i64::try_from(bps).map_err(|_| MathError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const USDC: u64 = 1_000_000;
+
+    #[test]
+    fn mul_div_reports_errors() {
+        assert!(mul_div(1, 1, 0, Rounding::Floor).is_err());
+        assert!(mul_div(u64::MAX, u128::MAX, 1, Rounding::Floor).is_err());
+        assert!(mul_div(u64::MAX, 2, 1, Rounding::Floor).is_err());
+        assert!(sub(0, 1).is_err());
+        assert!(add(u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn first_deposit_uses_offset() {
+        assert_eq!(
+            convert_to_shares(USDC, 0, 0, Rounding::Floor).unwrap(),
+            USDC * 1_000
+        );
+        assert_eq!(
+            convert_to_assets(USDC * 1_000, USDC, USDC * 1_000, Rounding::Floor).unwrap(),
+            USDC
+        );
+    }
+
+    /// Attacker deposits 1 unit, donates a large amount (booked into total_assets, e.g. via
+    /// `sync`), then a victim deposits. Without the offset the victim would receive 0 shares
+    /// and the attacker would capture the whole deposit.
+    #[test]
+    fn inflation_attack_is_unprofitable() {
+        let (mut total_assets, mut total_shares) = (0u64, 0u64);
+
+        // Attacker deposits 1 unit
+        let attacker_in = 1;
+        let attacker_shares =
+            convert_to_shares(attacker_in, total_assets, total_shares, Rounding::Floor).unwrap();
+        total_assets += attacker_in;
+        total_shares += attacker_shares;
+
+        // Attacker donates 10,000 USDC
+        let donation = 10_000 * USDC;
+        total_assets += donation;
+
+        // Victim deposits 10,000 USDC
+        let victim_in = 10_000 * USDC;
+        let victim_shares =
+            convert_to_shares(victim_in, total_assets, total_shares, Rounding::Floor).unwrap();
+        assert!(victim_shares > 0);
+        total_assets += victim_in;
+        total_shares += victim_shares;
+
+        // Attacker redeems everything
+        let attacker_out =
+            convert_to_assets(attacker_shares, total_assets, total_shares, Rounding::Floor)
+                .unwrap();
+        assert!(attacker_out < attacker_in + donation);
+
+        // Victim keeps almost all of the deposit
+        let victim_out = convert_to_assets(
+            victim_shares,
+            total_assets - attacker_out,
+            victim_shares,
+            Rounding::Floor,
+        )
+        .unwrap();
+        assert!(victim_out >= victim_in / 100 * 99);
+    }
+
+    #[test]
+    fn small_donation_does_not_zero_out_deposit() {
+        // 1 unit deposited, 1 USDC donated: a 1 USDC deposit still receives shares
+        let total_assets = 1 + USDC;
+        let total_shares = convert_to_shares(1, 0, 0, Rounding::Floor).unwrap();
+        assert!(convert_to_shares(USDC, total_assets, total_shares, Rounding::Floor).unwrap() > 0);
+    }
+
+    #[test]
+    fn management_fee_accrues_pro_rata() {
+        let total_shares = 1_000_000 * USDC;
+        // 2% per year
+        assert_eq!(
+            management_fee_shares(total_shares, 200, SECONDS_PER_YEAR).unwrap(),
+            total_shares / 50
+        );
+        assert_eq!(
+            management_fee_shares(total_shares, 200, SECONDS_PER_YEAR / 2).unwrap(),
+            total_shares / 100
+        );
+        assert_eq!(
+            management_fee_shares(total_shares, 0, SECONDS_PER_YEAR).unwrap(),
+            0
+        );
+        assert_eq!(management_fee_shares(0, 200, SECONDS_PER_YEAR).unwrap(), 0);
+    }
+
+    #[test]
+    fn utilization_fee_scales_between_base_and_max() {
+        // Fully idle: base fee
+        assert_eq!(utilization_fee_bps(10, 50, USDC, USDC).unwrap(), 10);
+        // Half deployed: midpoint
+        assert_eq!(utilization_fee_bps(10, 50, USDC / 2, USDC).unwrap(), 30);
+        // Nothing idle: max fee
+        assert_eq!(utilization_fee_bps(10, 50, 0, USDC).unwrap(), 50);
+        // Curve disabled
+        assert_eq!(utilization_fee_bps(10, 0, 0, USDC).unwrap(), 10);
+    }
+
+    #[test]
+    fn early_exit_penalty_decays_linearly() {
+        let window = 30 * 24 * 3600;
+        assert_eq!(decayed_penalty_bps(100, window, 0).unwrap(), 100);
+        assert_eq!(
+            decayed_penalty_bps(100, window, window as u64 / 2).unwrap(),
+            50
+        );
+        assert_eq!(
+            decayed_penalty_bps(100, window, window as u64 - 1).unwrap(),
+            1
+        );
+        assert_eq!(decayed_penalty_bps(100, window, window as u64).unwrap(), 0);
+        // Disabled
+        assert_eq!(decayed_penalty_bps(100, 0, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn profit_unlocks_linearly() {
+        let window = 6 * 3600;
+        assert_eq!(
+            still_locked(1_000 * USDC, window, window).unwrap(),
+            1_000 * USDC
+        );
+        assert_eq!(
+            still_locked(1_000 * USDC, window / 4, window).unwrap(),
+            250 * USDC
+        );
+        assert_eq!(still_locked(1, 1, window).unwrap(), 1);
+        assert_eq!(still_locked(1_000 * USDC, 0, window).unwrap(), 0);
+        // Unlocked at once
+        assert_eq!(still_locked(1_000 * USDC, 0, 0).unwrap(), 0);
+
+        // Locked at 100 until 100 + window
+        assert_eq!(
+            locked_profit_at(1_000 * USDC, 100, 100 + window as i64, 100).unwrap(),
+            1_000 * USDC
+        );
+        assert_eq!(
+            locked_profit_at(
+                1_000 * USDC,
+                100,
+                100 + window as i64,
+                100 + window as i64 / 2
+            )
+            .unwrap(),
+            500 * USDC
+        );
+        assert_eq!(
+            locked_profit_at(1_000 * USDC, 100, 100 + window as i64, 200 + window as i64).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn annualized_return_scales_to_a_year() {
+        let rate = 1_000_000_000;
+        // +5% over a year, half a year and a day
+        assert_eq!(
+            annualized_return_bps(rate, rate / 100 * 105, SECONDS_PER_YEAR).unwrap(),
+            500
+        );
+        assert_eq!(
+            annualized_return_bps(rate, rate / 100 * 105, SECONDS_PER_YEAR / 2).unwrap(),
+            1_000
+        );
+        assert_eq!(
+            annualized_return_bps(rate, rate + rate / 10_000, 24 * 3600).unwrap(),
+            365
+        );
+        // -1% over a year
+        assert_eq!(
+            annualized_return_bps(rate, rate / 100 * 99, SECONDS_PER_YEAR).unwrap(),
+            -100
+        );
+        assert!(annualized_return_bps(rate, rate, 0).is_err());
+        assert!(annualized_return_bps(0, rate, SECONDS_PER_YEAR).is_err());
+    }
+
+    // Bounds keep every intermediate result within u64
+    fn totals() -> impl Strategy<Value = (u64, u64)> {
+        (0u64..1_000_000_000_000, 0u64..1_000_000_000_000)
+    }
+
+    proptest! {
+        #[test]
+        fn ceil_is_floor_or_floor_plus_one(
+            amount in 0u64..1_000_000,
+            (total_assets, total_shares) in totals(),
+        ) {
+            let floor = convert_to_shares(amount, total_assets, total_shares, Rounding::Floor).unwrap();
+            let ceil = convert_to_shares(amount, total_assets, total_shares, Rounding::Ceil).unwrap();
+            prop_assert!(ceil == floor || ceil == floor + 1);
+
+            let floor = convert_to_assets(amount, total_assets, total_shares, Rounding::Floor).unwrap();
+            let ceil = convert_to_assets(amount, total_assets, total_shares, Rounding::Ceil).unwrap();
+            prop_assert!(ceil == floor || ceil == floor + 1);
+        }
+
+        /// deposit then redeem never returns more than was deposited
+        #[test]
+        fn deposit_redeem_round_trip_favors_vault(
+            assets in 0u64..1_000_000,
+            (total_assets, total_shares) in totals(),
+        ) {
+            let shares = convert_to_shares(assets, total_assets, total_shares, Rounding::Floor).unwrap();
+            let back = convert_to_assets(
+                shares,
+                total_assets + assets,
+                total_shares + shares,
+                Rounding::Floor,
+            ).unwrap();
+            prop_assert!(back <= assets);
+        }
+
+        /// mint then redeem never returns more than was paid
+        #[test]
+        fn mint_redeem_round_trip_favors_vault(
+            shares in 0u64..1_000_000,
+            (total_assets, total_shares) in totals(),
+        ) {
+            let cost = convert_to_assets(shares, total_assets, total_shares, Rounding::Ceil).unwrap();
+            let back = convert_to_assets(
+                shares,
+                total_assets + cost,
+                total_shares + shares,
+                Rounding::Floor,
+            ).unwrap();
+            prop_assert!(back <= cost);
+        }
+
+        /// withdraw burns at least as many shares as redeem would need for the same assets
+        #[test]
+        fn withdraw_burns_at_least_redeem_shares(
+            assets in 0u64..1_000_000,
+            (total_assets, total_shares) in totals(),
+        ) {
+            let burned = convert_to_shares(assets, total_assets, total_shares, Rounding::Ceil).unwrap();
+            let redeemable = convert_to_assets(burned, total_assets, total_shares, Rounding::Floor).unwrap();
+            prop_assert!(redeemable >= assets);
+        }
+
+        /// the fee never rounds in the caller's favor
+        #[test]
+        fn gross_up_covers_fee(net in 0u64..1_000_000_000_000, fee_bps in 0u16..=100) {
+            let gross = gross_up_for_fee(net, fee_bps).unwrap();
+            prop_assert!(gross - fee_on_amount(gross, fee_bps).unwrap() >= net);
+            prop_assert!(gross >= net);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Share conversion invariants over random sequences of vault operations.
+//!
+//! `Model` keeps the books the way the vault's instructions update them: deposits and redeems
+//! convert at the vault rate and set their fees aside, the first deposit locks the dead shares,
+//! and profits and losses move `total_assets` alone (profit as if already unlocked). Operations
+//! the vault would reject, such as a deposit too small for the dead shares, are skipped.
+
+use proptest::prelude::*;
+use tsv_math::{
+    convert_to_assets, convert_to_shares, fee_on_amount, Rounding, VIRTUAL_DECIMALS_OFFSET,
+};
+
+const USERS: usize = 4;
+
+/// Shares the first deposit locks forever
+const DEAD_SHARES: u64 = 1_000;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit {
+        user: usize,
+        assets: u64,
+    },
+    /// Redeem `per_mille` thousandths of the user's shares
+    Redeem {
+        user: usize,
+        per_mille: u64,
+    },
+    /// Yield booked into `total_assets` (`harvest`, `sync`)
+    Profit(u64),
+    /// Loss written off `total_assets` (`report_loss`)
+    Loss(u64),
+}
+
+#[derive(Clone, Debug)]
+struct Model {
+    deposit_fee_bps: u16,
+    redeem_fee_bps: u16,
+    total_assets: u64,
+    total_shares: u64,
+    /// Shares of each user; the rest of `total_shares` are the dead shares
+    shares: [u64; USERS],
+}
+
+impl Model {
+    fn new(deposit_fee_bps: u16, redeem_fee_bps: u16) -> Self {
+        Self {
+            deposit_fee_bps,
+            redeem_fee_bps,
+            total_assets: 0,
+            total_shares: 0,
+            shares: [0; USERS],
+        }
+    }
+
+    /// Apply `op`, or leave the books as they are when the vault would reject it
+    fn apply(&mut self, op: &Op) {
+        match *op {
+            Op::Deposit { user, assets } => {
+                if let Some(shares) = self.deposit(assets) {
+                    self.shares[user] += shares;
+                }
+            }
+            Op::Redeem { user, per_mille } => {
+                let shares = (self.shares[user] as u128 * per_mille as u128 / 1_000) as u64;
+                if self.redeem(shares).is_some() {
+                    self.shares[user] -= shares;
+                }
+            }
+            Op::Profit(assets) => self.total_assets += assets,
+            Op::Loss(assets) => self.total_assets -= assets.min(self.total_assets),
+        }
+    }
+
+    /// Shares `deposit(assets)` mints to its receiver, like `deposit_for_shares`
+    fn deposit(&mut self, assets: u64) -> Option<u64> {
+        let net = assets - fee_on_amount(assets, self.deposit_fee_bps).ok()?;
+        let dead_shares = if self.total_shares == 0 {
+            DEAD_SHARES
+        } else {
+            0
+        };
+        let minted =
+            convert_to_shares(net, self.total_assets, self.total_shares, Rounding::Floor).ok()?;
+        if minted <= dead_shares {
+            return None;
+        }
+        self.total_assets = self.total_assets.checked_add(net)?;
+        self.total_shares = self.total_shares.checked_add(minted)?;
+        Some(minted - dead_shares)
+    }
+
+    /// Assets `redeem(shares)` pays out, like `burn_for_redeem`
+    fn redeem(&mut self, shares: u64) -> Option<u64> {
+        if shares == 0 {
+            return None;
+        }
+        let gross = convert_to_assets(
+            shares,
+            self.total_assets,
+            self.total_shares,
+            Rounding::Floor,
+        )
+        .ok()?;
+        let fee = fee_on_amount(gross, self.redeem_fee_bps).ok()?;
+        self.total_assets -= gross;
+        self.total_shares -= shares;
+        Some(gross - fee)
+    }
+
+    /// What `shares` redeem for before the redeem fee
+    fn claim(&self, shares: u64) -> u64 {
+        convert_to_assets(
+            shares,
+            self.total_assets,
+            self.total_shares,
+            Rounding::Floor,
+        )
+        .unwrap()
+    }
+
+    /// Whether the price of a share is at least `before`'s: (A + 1) / (S + 10^offset), compared
+    /// cross-multiplied
+    fn rate_at_least(&self, before: &Model) -> bool {
+        let offset = 10u128.pow(VIRTUAL_DECIMALS_OFFSET);
+        (self.total_assets as u128 + 1) * (before.total_shares as u128 + offset)
+            >= (before.total_assets as u128 + 1) * (self.total_shares as u128 + offset)
+    }
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    let op = prop_oneof![
+        4 => (0..USERS, 1u64..1_000_000_000_000)
+            .prop_map(|(user, assets)| Op::Deposit { user, assets }),
+        4 => (0..USERS, 1u64..=1_000)
+            .prop_map(|(user, per_mille)| Op::Redeem { user, per_mille }),
+        1 => (0u64..1_000_000_000).prop_map(Op::Profit),
+        1 => (0u64..1_000_000_000).prop_map(Op::Loss),
+    ];
+    prop::collection::vec(op, 1..48)
+}
+
+proptest! {
+    /// Depositing then redeeming everything right away never returns more than was deposited,
+    /// whatever state the vault is in
+    #[test]
+    fn deposit_then_redeem_never_profits(
+        fees in (0u16..=100, 0u16..=100),
+        ops in ops(),
+        assets in 1u64..1_000_000_000_000,
+    ) {
+        let mut model = Model::new(fees.0, fees.1);
+        for op in &ops {
+            model.apply(op);
+
+            let mut probe = model.clone();
+            if let Some(shares) = probe.deposit(assets) {
+                let back = probe.redeem(shares).unwrap_or(0);
+                prop_assert!(back <= assets, "{back} out for {assets} in after {op:?}");
+            }
+        }
+    }
+
+    /// What every holder, the dead shares included, can redeem never exceeds `total_assets`
+    #[test]
+    fn claims_never_exceed_total_assets(
+        fees in (0u16..=100, 0u16..=100),
+        ops in ops(),
+    ) {
+        let mut model = Model::new(fees.0, fees.1);
+        for op in &ops {
+            model.apply(op);
+
+            let dead_shares = model.total_shares - model.shares.iter().sum::<u64>();
+            let claims = model.shares.iter().map(|&shares| model.claim(shares)).sum::<u64>()
+                + model.claim(dead_shares);
+            prop_assert!(
+                claims <= model.total_assets,
+                "claims {claims} > total_assets {} after {op:?}",
+                model.total_assets,
+            );
+        }
+    }
+
+    /// Only losses lower the price of a share: conversions round in the vault's favor and fees
+    /// are only ever taken out of the caller's side
+    #[test]
+    fn exchange_rate_never_falls_without_losses(
+        fees in (0u16..=100, 0u16..=100),
+        ops in ops(),
+    ) {
+        let mut model = Model::new(fees.0, fees.1);
+        for op in &ops {
+            let before = model.clone();
+            model.apply(op);
+            if !matches!(op, Op::Loss(_)) {
+                prop_assert!(model.rate_at_least(&before), "rate fell on {op:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! Random instruction sequences against the vault program, with account substitutions and
+//! forged meta-redeem signatures, checking the vault's accounting after every step.
+//!
+//! Runs the BPF program in `solana-program-test` through the integration tests' fixtures, see
+//! `pnpm fuzz`. Invariants:
+//!
+//! - the share mint supply equals `total_shares` and what the holders and dead shares hold
+//! - `asset_vault` covers `total_assets` plus what is set aside (fees, insurance, reserved
+//!   withdrawals), and the holders' claims never exceed `total_assets`
+//! - a failed transaction leaves the vault untouched; pauses and roles are enforced
+//! - only a transaction's signers, or an owner through its meta-redeem signature, lose tokens
+//! - a forged meta-redeem never succeeds, and a genuine one moves the nonce by one
+
+#[allow(dead_code)]
+#[path = "../../programs/tsv-usdc-vault/tests/integration/fixtures.rs"]
+mod fixtures;
+
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token::spl_token;
+use arbitrary::{Arbitrary, Unstructured};
+use honggfuzz::fuzz;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use tsv_usdc_vault::math::Rounding;
+
+use crate::fixtures::{sign, User, VaultFixture, ONE};
+
+const USERS: usize = 3;
+
+/// Assets each user starts with
+const USER_ASSETS: u64 = 10_000 * ONE;
+
+/// Steps run per input, bounding the time one input takes
+const MAX_STEPS: usize = 24;
+
+/// Relayer fee owners sign off on in meta-redeems
+const MAX_RELAYER_FEE: u64 = ONE / 10;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    deposit_fee_bps: u8,
+    redeem_fee_bps: u8,
+    steps: Vec<Step>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Step {
+    op: Op,
+    /// Replaces a non-signer account of the step's vault instruction
+    substitution: Option<Substitution>,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Deposit {
+        user: u8,
+        assets: u64,
+    },
+    Redeem {
+        user: u8,
+        shares: u64,
+    },
+    /// Transfer assets straight into `asset_vault`
+    Donate {
+        user: u8,
+        assets: u64,
+    },
+    /// `set_pause_flags`, by the pauser or by a user
+    Pause {
+        by_user: Option<u8>,
+        deposits: bool,
+        redeems: bool,
+        meta: bool,
+    },
+    QueueFeeUpdate {
+        deposit_fee_bps: u8,
+        redeem_fee_bps: u8,
+    },
+    ExecuteFeeUpdate,
+    Warp {
+        seconds: u16,
+    },
+    MetaRedeem {
+        owner: u8,
+        relayer: u8,
+        shares: u64,
+        relayer_fee: u64,
+        forgery: Option<Forgery>,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Substitution {
+    /// Index among the instruction's accounts
+    account: u8,
+    /// Index in `Sequence::account_pool`
+    replacement: u8,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Forgery {
+    /// Signed by the relayer instead of the owner
+    OtherSigner,
+    /// Signed over the owner's next nonce
+    StaleNonce,
+    /// A byte of the signed arguments (shares, receiver, deadline, max fee) flipped
+    Tamper { offset: u8, mask: u8 },
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    loop {
+        fuzz!(|data: &[u8]| {
+            if let Ok(input) = Input::arbitrary(&mut Unstructured::new(data)) {
+                runtime.block_on(run(input));
+            }
+        });
+    }
+}
+
+async fn run(input: Input) {
+    let mut fixture = VaultFixture::new(
+        u16::from(input.deposit_fee_bps) % 101,
+        u16::from(input.redeem_fee_bps) % 101,
+    )
+    .await;
+    let mut users = Vec::with_capacity(USERS);
+    for _ in 0..USERS {
+        users.push(fixture.user(USER_ASSETS).await);
+    }
+
+    let mut sequence = Sequence { fixture, users };
+    for step in input.steps.iter().take(MAX_STEPS) {
+        sequence.step(step).await;
+        sequence.check().await;
+    }
+}
+
+struct Sequence {
+    fixture: VaultFixture,
+    users: Vec<User>,
+}
+
+/// A step's transaction and who authorized it
+struct Prepared {
+    instructions: Vec<Instruction>,
+    /// Users signing besides the payer
+    signers: Vec<usize>,
+    /// Wallets allowed to lose tokens: the signers, and a meta-redeem's owner
+    authorized: Vec<Pubkey>,
+    /// Whether the program must reject it
+    must_fail: bool,
+    /// Owner whose nonce a meta-redeem moves, when it is genuine
+    meta_owner: Option<(Pubkey, bool)>,
+}
+
+impl Sequence {
+    fn user(&self, index: u8) -> (usize, &User) {
+        let index = index as usize % USERS;
+        (index, &self.users[index])
+    }
+
+    async fn step(&mut self, step: &Step) {
+        let vault = self.fixture.vault;
+        let before = self.fixture.vault_state().await;
+        let raw_before = self.account_data(&vault).await;
+        let balances_before = self.balances().await;
+
+        let Some(mut prepared) = self.prepare(&step.op, &before).await else {
+            return;
+        };
+        if let Some(substitution) = &step.substitution {
+            self.substitute(&mut prepared.instructions, substitution);
+        }
+        let nonce_before = match prepared.meta_owner {
+            Some((owner, _)) => Some(self.fixture.nonce(&owner).await),
+            None => None,
+        };
+
+        let signers: Vec<&Keypair> = prepared
+            .signers
+            .iter()
+            .map(|&index| &self.users[index].keypair)
+            .collect();
+        let result = self.fixture.send(&prepared.instructions, &signers).await;
+
+        if prepared.must_fail {
+            assert!(result.is_err(), "{step:?} succeeded");
+        }
+        if result.is_err() {
+            assert_eq!(
+                raw_before,
+                self.account_data(&vault).await,
+                "failed {step:?} changed the vault"
+            );
+        }
+        if let (Some((owner, genuine)), Some(nonce)) = (prepared.meta_owner, nonce_before) {
+            let moved = u64::from(result.is_ok() && genuine);
+            assert_eq!(self.fixture.nonce(&owner).await, nonce + moved, "{step:?}");
+        }
+
+        let balances_after = self.balances().await;
+        for (user, (before, after)) in self
+            .users
+            .iter()
+            .zip(balances_before.iter().zip(&balances_after))
+        {
+            if !prepared.authorized.contains(&user.pubkey()) {
+                assert!(
+                    after.0 >= before.0 && after.1 >= before.1,
+                    "{step:?} took tokens from {}: {before:?} -> {after:?}",
+                    user.pubkey()
+                );
+            }
+        }
+    }
+
+    /// The step's transaction, or `None` when it only moves the clock
+    async fn prepare(&mut self, op: &Op, vault: &tsv_usdc_vault::Vault) -> Option<Prepared> {
+        let prepared = match *op {
+            Op::Deposit { user, assets } => {
+                let (index, user) = self.user(user);
+                Prepared {
+                    instructions: vec![self.fixture.deposit(
+                        user,
+                        assets % (USER_ASSETS + 1),
+                        None,
+                    )],
+                    signers: vec![index],
+                    authorized: vec![user.pubkey()],
+                    must_fail: vault.paused_deposits,
+                    meta_owner: None,
+                }
+            }
+            Op::Redeem { user, shares } => {
+                let index = user as usize % USERS;
+                let user_shares = self.fixture.share_account(&self.users[index].pubkey());
+                let held = self.fixture.balance(&user_shares).await;
+                let user = &self.users[index];
+                Prepared {
+                    instructions: vec![self.fixture.redeem(user, shares % (held + 1), None)],
+                    signers: vec![index],
+                    authorized: vec![user.pubkey()],
+                    must_fail: vault.paused_redeems,
+                    meta_owner: None,
+                }
+            }
+            Op::Donate { user, assets } => {
+                let (index, user) = self.user(user);
+                let transfer = spl_token::instruction::transfer(
+                    &spl_token::ID,
+                    &user.asset,
+                    &self.fixture.asset_vault,
+                    &user.pubkey(),
+                    &[],
+                    assets % (ONE + 1),
+                )
+                .unwrap();
+                Prepared {
+                    instructions: vec![transfer],
+                    signers: vec![index],
+                    authorized: vec![user.pubkey()],
+                    must_fail: false,
+                    meta_owner: None,
+                }
+            }
+            Op::Pause {
+                by_user,
+                deposits,
+                redeems,
+                meta,
+            } => {
+                let (pauser, signers) = match by_user {
+                    Some(user) => {
+                        let (index, user) = self.user(user);
+                        (user.pubkey(), vec![index])
+                    }
+                    None => (self.fixture.payer(), vec![]),
+                };
+                Prepared {
+                    instructions: vec![self
+                        .fixture
+                        .set_pause_flags(&pauser, deposits, redeems, meta)],
+                    signers,
+                    authorized: vec![pauser],
+                    must_fail: pauser != vault.pauser,
+                    meta_owner: None,
+                }
+            }
+            Op::QueueFeeUpdate {
+                deposit_fee_bps,
+                redeem_fee_bps,
+            } => Prepared {
+                instructions: vec![self.fixture.queue_fee_update(
+                    u16::from(deposit_fee_bps) % 101,
+                    u16::from(redeem_fee_bps) % 101,
+                )],
+                signers: vec![],
+                authorized: vec![],
+                must_fail: false,
+                meta_owner: None,
+            },
+            Op::ExecuteFeeUpdate => Prepared {
+                instructions: vec![self.fixture.execute_fee_update()],
+                signers: vec![],
+                authorized: vec![],
+                must_fail: vault.fee_update_eta == 0,
+                meta_owner: None,
+            },
+            Op::Warp { seconds } => {
+                self.fixture.warp(i64::from(seconds)).await;
+                return None;
+            }
+            Op::MetaRedeem {
+                owner,
+                relayer,
+                shares,
+                relayer_fee,
+                ref forgery,
+            } => {
+                return Some(
+                    self.prepare_meta_redeem(owner, relayer, shares, relayer_fee, forgery)
+                        .await,
+                )
+            }
+        };
+        Some(prepared)
+    }
+
+    /// A meta-redeem of `owner`'s shares, approved for the vault beforehand, relayed by
+    /// `relayer` and forged as `forgery` says
+    async fn prepare_meta_redeem(
+        &mut self,
+        owner: u8,
+        relayer: u8,
+        shares: u64,
+        relayer_fee: u64,
+        forgery: &Option<Forgery>,
+    ) -> Prepared {
+        let owner = owner as usize % USERS;
+        let mut relayer = relayer as usize % USERS;
+        if relayer == owner && matches!(forgery, Some(Forgery::OtherSigner)) {
+            relayer = (owner + 1) % USERS;
+        }
+        let owner_key = self.users[owner].pubkey();
+        let owner_shares = self.fixture.share_account(&owner_key);
+        let shares = shares % (self.fixture.balance(&owner_shares).await + 1);
+        let approve = self.fixture.approve_vault(&self.users[owner], shares);
+        self.fixture
+            .send(&[approve], &[&self.users[owner].keypair])
+            .await
+            .expect("approve the vault");
+
+        let deadline = self.fixture.now().await + 600;
+        let mut message = self
+            .fixture
+            .redeem_message(&owner_key, shares, &owner_key, MAX_RELAYER_FEE, deadline)
+            .await;
+        let relayer_index = relayer;
+        let (owner, relayer) = (&self.users[owner], &self.users[relayer]);
+        let relayer_fee = relayer_fee % (MAX_RELAYER_FEE + 1);
+
+        let instructions = match forgery {
+            None => {
+                let signature = sign(&owner.keypair, &message);
+                self.fixture
+                    .meta_redeem(relayer, &message, &signature, relayer_fee)
+            }
+            Some(Forgery::OtherSigner) => {
+                let signature = sign(&relayer.keypair, &message);
+                let mut instructions =
+                    self.fixture
+                        .meta_redeem(relayer, &message, &signature, relayer_fee);
+                // The Ed25519 instruction's public key follows its 16-byte header
+                instructions[0].data[16..48].copy_from_slice(relayer.pubkey().as_ref());
+                instructions
+            }
+            Some(Forgery::StaleNonce) => {
+                message.nonce += 1;
+                let signature = sign(&owner.keypair, &message);
+                self.fixture
+                    .meta_redeem(relayer, &message, &signature, relayer_fee)
+            }
+            Some(Forgery::Tamper { offset, mask }) => {
+                let signature = sign(&owner.keypair, &message);
+                let mut instructions =
+                    self.fixture
+                        .meta_redeem(relayer, &message, &signature, relayer_fee);
+                // After the discriminator: shares, receiver, deadline and max relayer fee
+                let index = 8 + *offset as usize % (8 + 32 + 8 + 8);
+                instructions[1].data[index] ^= mask | 1;
+                instructions
+            }
+        };
+
+        let genuine = forgery.is_none();
+        let mut authorized = vec![relayer.pubkey()];
+        if genuine {
+            authorized.push(owner.pubkey());
+        }
+        Prepared {
+            instructions,
+            signers: vec![relayer_index],
+            authorized,
+            must_fail: !genuine,
+            meta_owner: Some((owner.pubkey(), genuine)),
+        }
+    }
+
+    /// Replace a non-signer account of the last instruction, the vault's, with one of the pool
+    fn substitute(&self, instructions: &mut [Instruction], substitution: &Substitution) {
+        let pool = self.account_pool();
+        let Some(instruction) = instructions.last_mut() else {
+            return;
+        };
+        let candidates: Vec<usize> = (0..instruction.accounts.len())
+            .filter(|&index| !instruction.accounts[index].is_signer)
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+        let index = candidates[substitution.account as usize % candidates.len()];
+        instruction.accounts[index].pubkey = pool[substitution.replacement as usize % pool.len()];
+    }
+
+    /// Accounts substitutions draw from: the users' wallets and token accounts, and the
+    /// vault's own accounts
+    fn account_pool(&self) -> Vec<Pubkey> {
+        let fixture = &self.fixture;
+        let mut pool = vec![
+            fixture.payer(),
+            fixture.vault,
+            fixture.asset_mint,
+            fixture.share_mint,
+            fixture.asset_vault,
+            fixture.dead_shares_account,
+        ];
+        for user in &self.users {
+            pool.extend([
+                user.pubkey(),
+                user.asset,
+                fixture.share_account(&user.pubkey()),
+            ]);
+        }
+        pool
+    }
+
+    /// Asset and share balances of each user
+    async fn balances(&mut self) -> Vec<(u64, u64)> {
+        let mut balances = Vec::with_capacity(USERS);
+        for index in 0..USERS {
+            let asset = self.users[index].asset;
+            let shares = self.fixture.share_account(&self.users[index].pubkey());
+            balances.push((
+                self.fixture.balance(&asset).await,
+                self.fixture.balance(&shares).await,
+            ));
+        }
+        balances
+    }
+
+    async fn account_data(&mut self, address: &Pubkey) -> Vec<u8> {
+        self.fixture
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .map(|account| account.data)
+            .unwrap_or_default()
+    }
+
+    /// The vault's accounting invariants
+    async fn check(&mut self) {
+        let vault = self.fixture.vault_state().await;
+        let share_mint = self.fixture.share_mint;
+        let supply = spl_token::state::Mint::unpack(&self.account_data(&share_mint).await)
+            .unwrap()
+            .supply;
+        assert_eq!(supply, vault.total_shares, "share supply");
+
+        let dead_shares_account = self.fixture.dead_shares_account;
+        let dead_shares = self.fixture.balance(&dead_shares_account).await;
+        let held: Vec<u64> = self
+            .balances()
+            .await
+            .iter()
+            .map(|(_, shares)| *shares)
+            .collect();
+        assert_eq!(
+            held.iter().sum::<u64>() + dead_shares,
+            supply,
+            "shares held"
+        );
+
+        let asset_vault = self.fixture.asset_vault;
+        let idle = self.fixture.balance(&asset_vault).await;
+        let set_aside =
+            vault.accrued_fees + vault.insurance_assets + vault.reserved_withdraw_assets;
+        assert!(
+            idle >= vault.total_assets + set_aside,
+            "asset vault {idle} short of {} + {set_aside}",
+            vault.total_assets
+        );
+
+        let claims: u64 = held
+            .iter()
+            .chain([&dead_shares])
+            .map(|&shares| vault.convert_to_assets(shares, Rounding::Floor).unwrap())
+            .sum();
+        assert!(
+            claims <= vault.total_assets,
+            "claims {claims} > total_assets {}",
+            vault.total_assets
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Kamino Lend (klend) instructions and `Reserve` fields the adapter relies on.
+//!
+//! klend is an Anchor program, so instructions are called by their sighash and the few `Reserve`
+//! fields needed are read at their offsets in its zero-copy layout rather than through a
+//! dependency on the klend crate.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+use crate::AdapterError;
+
+/// Kamino Lend program
+pub const KLEND_PROGRAM_ID: Pubkey = pubkey!("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD");
+
+/// sha256("global:init_user_metadata")[..8]
+pub const INIT_USER_METADATA_DISCRIMINATOR: [u8; 8] = [117, 169, 176, 69, 197, 23, 15, 162];
+
+/// sha256("global:init_obligation")[..8]
+pub const INIT_OBLIGATION_DISCRIMINATOR: [u8; 8] = [251, 10, 231, 76, 27, 11, 159, 96];
+
+/// sha256("global:deposit_reserve_liquidity_and_obligation_collateral")[..8]
+pub const DEPOSIT_DISCRIMINATOR: [u8; 8] = [129, 199, 4, 2, 222, 39, 26, 46];
+
+/// sha256("global:withdraw_obligation_collateral_and_redeem_reserve_collateral")[..8]
+pub const WITHDRAW_DISCRIMINATOR: [u8; 8] = [75, 93, 93, 220, 34, 150, 218, 196];
+
+// `Reserve` offsets, discriminator included: version (u64) and last_update (16 bytes) precede
+// the lending market; farm_collateral and farm_debt precede the embedded `ReserveLiquidity`
+const LENDING_MARKET_OFFSET: usize = 32;
+const LIQUIDITY_MINT_OFFSET: usize = 128;
+const AVAILABLE_AMOUNT_OFFSET: usize = 224;
+const BORROWED_AMOUNT_SF_OFFSET: usize = 232;
+const ACCUMULATED_PROTOCOL_FEES_SF_OFFSET: usize = 344;
+const ACCUMULATED_REFERRER_FEES_SF_OFFSET: usize = 360;
+const PENDING_REFERRER_FEES_SF_OFFSET: usize = 376;
+const RESERVE_MIN_LEN: usize = PENDING_REFERRER_FEES_SF_OFFSET + 16;
+
+/// Fractional bits of klend's scaled-fraction (`_sf`) amounts
+const FRACTION_BITS: u32 = 60;
+
+/// The `Reserve` fields the adapter reads
+pub struct ReserveView {
+    pub lending_market: Pubkey,
+    pub liquidity_mint: Pubkey,
+    /// Liquidity owed to depositors: available plus borrowed, minus fees owed to the protocol
+    /// and referrers, as a scaled fraction
+    pub total_liquidity_sf: u128,
+}
+
+impl ReserveView {
+    /// Read a klend `Reserve` account
+    pub fn load(reserve: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(
+            *reserve.owner,
+            KLEND_PROGRAM_ID,
+            AdapterError::InvalidReserve
+        );
+        let data = reserve.try_borrow_data()?;
+        require!(data.len() >= RESERVE_MIN_LEN, AdapterError::InvalidReserve);
+
+        let pubkey = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let u128_at =
+            |offset: usize| u128::from_le_bytes(data[offset..offset + 16].try_into().unwrap());
+
+        let owed_fees = u128_at(ACCUMULATED_PROTOCOL_FEES_SF_OFFSET)
+            .checked_add(u128_at(ACCUMULATED_REFERRER_FEES_SF_OFFSET))
+            .and_then(|fees| fees.checked_add(u128_at(PENDING_REFERRER_FEES_SF_OFFSET)))
+            .ok_or(AdapterError::MathOverflow)?;
+        let total_liquidity_sf = (u128::from(u64_at(AVAILABLE_AMOUNT_OFFSET)) << FRACTION_BITS)
+            .checked_add(u128_at(BORROWED_AMOUNT_SF_OFFSET))
+            .ok_or(AdapterError::MathOverflow)?
+            .saturating_sub(owed_fees);
+
+        Ok(Self {
+            lending_market: pubkey(LENDING_MARKET_OFFSET),
+            liquidity_mint: pubkey(LIQUIDITY_MINT_OFFSET),
+            total_liquidity_sf,
+        })
+    }
+
+    /// Liquidity `collateral` kTokens redeem for, given `collateral_supply` outstanding,
+    /// rounded down
+    pub fn liquidity_for_collateral(&self, collateral: u64, collateral_supply: u64) -> Result<u64> {
+        if collateral_supply == 0 {
+            return Ok(0);
+        }
+        let liquidity_sf = self
+            .total_liquidity_sf
+            .checked_mul(u128::from(collateral))
+            .ok_or(AdapterError::MathOverflow)?
+            / u128::from(collateral_supply);
+        u64::try_from(liquidity_sf >> FRACTION_BITS).map_err(|_| error!(AdapterError::MathOverflow))
+    }
+
+    /// Collateral (kTokens) to redeem for at least `liquidity`, given `collateral_supply`
+    /// outstanding, rounded up
+    pub fn collateral_for_liquidity(&self, liquidity: u64, collateral_supply: u64) -> Result<u64> {
+        if self.total_liquidity_sf == 0 {
+            return Ok(0);
+        }
+        let numerator = (u128::from(liquidity) << FRACTION_BITS)
+            .checked_mul(u128::from(collateral_supply))
+            .ok_or(AdapterError::MathOverflow)?;
+        let collateral = numerator.div_ceil(self.total_liquidity_sf);
+        u64::try_from(collateral).map_err(|_| error!(AdapterError::MathOverflow))
+    }
+}
+
+/// Call the klend instruction `discriminator` with `args` over `accounts`, signed by `signer_seeds`.
+/// `accounts` pairs each account with whether it is writable; `signers` are marked as signers
+pub fn invoke<'info>(
+    klend_program: &AccountInfo<'info>,
+    discriminator: [u8; 8],
+    args: &[u8],
+    accounts: &[(&AccountInfo<'info>, bool)],
+    signers: &[Pubkey],
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let metas = accounts
+        .iter()
+        .map(|(account, writable)| AccountMeta {
+            pubkey: account.key(),
+            is_signer: signers.contains(account.key),
+            is_writable: *writable,
+        })
+        .collect();
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(args);
+    let ix = Instruction {
+        program_id: KLEND_PROGRAM_ID,
+        accounts: metas,
+        data,
+    };
+
+    let mut infos: Vec<AccountInfo<'info>> = accounts
+        .iter()
+        .map(|(account, _)| (*account).clone())
+        .collect();
+    infos.push(klend_program.clone());
+    invoke_signed(&ix, &infos, &[signer_seeds])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash::hash;
+
+    #[test]
+    fn discriminators_match_anchor_sighash() {
+        for (name, discriminator) in [
+            ("init_user_metadata", INIT_USER_METADATA_DISCRIMINATOR),
+            ("init_obligation", INIT_OBLIGATION_DISCRIMINATOR),
+            (
+                "deposit_reserve_liquidity_and_obligation_collateral",
+                DEPOSIT_DISCRIMINATOR,
+            ),
+            (
+                "withdraw_obligation_collateral_and_redeem_reserve_collateral",
+                WITHDRAW_DISCRIMINATOR,
+            ),
+        ] {
+            let sighash = hash(format!("global:{name}").as_bytes()).to_bytes();
+            assert_eq!(sighash[..8], discriminator, "{name}");
+        }
+    }
+
+    #[test]
+    fn rounds_collateral_up() {
+        let reserve = ReserveView {
+            lending_market: Pubkey::default(),
+            liquidity_mint: Pubkey::default(),
+            // 1,100 USDC of liquidity backing 1,000 kTokens
+            total_liquidity_sf: 1_100_000_000 << FRACTION_BITS,
+        };
+        assert_eq!(
+            reserve
+                .collateral_for_liquidity(110_000_000, 1_000_000_000)
+                .unwrap(),
+            100_000_000
+        );
+        assert_eq!(
+            reserve.collateral_for_liquidity(1, 1_000_000_000).unwrap(),
+            1
+        );
+        assert_eq!(
+            reserve.collateral_for_liquidity(0, 1_000_000_000).unwrap(),
+            0
+        );
+        assert_eq!(
+            reserve
+                .liquidity_for_collateral(100_000_000, 1_000_000_000)
+                .unwrap(),
+            110_000_000
+        );
+    }
+}
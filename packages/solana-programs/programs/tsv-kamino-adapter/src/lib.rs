@@ -0,0 +1,462 @@
+//! Strategy adapter supplying a Talken Stable Vault's USDC to a Kamino Lend reserve.
+//!
+//! The adapter keeps one klend obligation per vault strategy, owned by its `KaminoStrategy` PDA,
+//! which also owns the `reserve` USDC account the vault allocates into. `strategy_deposit`
+//! supplies that USDC as obligation collateral; `strategy_withdraw` redeems enough collateral
+//! for the requested amount and forwards the USDC to the vault's `asset_vault`;
+//! `strategy_total_value` values the collateral at the reserve's current exchange rate.
+//!
+//! klend requires its reserve and the obligation to be refreshed in the same slot, so keeper
+//! transactions calling `allocate`/`deallocate` start with klend's `refresh_reserve` and
+//! `refresh_obligation` instructions.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use tsv_usdc_vault::Vault;
+
+pub mod klend;
+
+use klend::{ReserveView, KLEND_PROGRAM_ID};
+
+declare_id!("FNgn5u8ecWJ6NugvwfpgWPcQHxaLyVKo2xqaKYTnyXHv");
+
+#[program]
+pub mod tsv_kamino_adapter {
+    use super::*;
+
+    /// Set up the adapter for the vault's strategy using this program: its USDC `reserve`
+    /// account and a klend obligation in `lending_market` (vault authority only).
+    /// The strategy is then registered by an `add_strategy` action queued in the vault's
+    /// timelock, passing `reserve`
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        let klend_reserve = ReserveView::load(&ctx.accounts.klend_reserve)?;
+        require_keys_eq!(
+            klend_reserve.lending_market,
+            ctx.accounts.lending_market.key(),
+            AdapterError::InvalidReserve
+        );
+        require_keys_eq!(
+            klend_reserve.liquidity_mint,
+            ctx.accounts.asset_mint.key(),
+            AdapterError::ReserveMintMismatch
+        );
+
+        let strategy_key = ctx.accounts.strategy.key();
+        let config = &mut ctx.accounts.config;
+        config.vault = ctx.accounts.vault.key();
+        config.strategy = strategy_key;
+        config.lending_market = ctx.accounts.lending_market.key();
+        config.klend_reserve = ctx.accounts.klend_reserve.key();
+        config.obligation = ctx.accounts.obligation.key();
+        config.reserve = ctx.accounts.reserve.key();
+        config.collateral = 0;
+        config.bump = ctx.bumps.config;
+
+        let seeds = &[
+            b"kamino".as_ref(),
+            strategy_key.as_ref(),
+            &[ctx.bumps.config],
+        ];
+        let config_info = ctx.accounts.config.to_account_info();
+        let signers = [config_info.key(), ctx.accounts.authority.key()];
+        let klend_program = ctx.accounts.klend_program.to_account_info();
+        // Optional accounts are passed as the klend program id
+        let none = klend_program.clone();
+
+        // init_user_metadata(user_lookup_table: Pubkey)
+        klend::invoke(
+            &klend_program,
+            klend::INIT_USER_METADATA_DISCRIMINATOR,
+            Pubkey::default().as_ref(),
+            &[
+                (&config_info, false),
+                (&ctx.accounts.authority.to_account_info(), true),
+                (&ctx.accounts.user_metadata.to_account_info(), true),
+                (&none, false),
+                (&ctx.accounts.rent.to_account_info(), false),
+                (&ctx.accounts.system_program.to_account_info(), false),
+            ],
+            &signers,
+            seeds,
+        )?;
+
+        // init_obligation(InitObligationArgs { tag: 0, id: 0 }): a vanilla obligation, seeded
+        // with the system program for both seed accounts
+        let system_program = ctx.accounts.system_program.to_account_info();
+        klend::invoke(
+            &klend_program,
+            klend::INIT_OBLIGATION_DISCRIMINATOR,
+            &[0, 0],
+            &[
+                (&config_info, false),
+                (&ctx.accounts.authority.to_account_info(), true),
+                (&ctx.accounts.obligation.to_account_info(), true),
+                (&ctx.accounts.lending_market.to_account_info(), false),
+                (&system_program, false),
+                (&system_program, false),
+                (&ctx.accounts.user_metadata.to_account_info(), false),
+                (&ctx.accounts.rent.to_account_info(), false),
+                (&system_program, false),
+            ],
+            &signers,
+            seeds,
+        )?;
+
+        emit!(InitializeEvent {
+            vault: ctx.accounts.vault.key(),
+            strategy: strategy_key,
+            lending_market: ctx.accounts.lending_market.key(),
+            klend_reserve: ctx.accounts.klend_reserve.key(),
+            obligation: ctx.accounts.obligation.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Supply `amount` of USDC, already moved into `reserve` by the vault, to Kamino
+    pub fn strategy_deposit(ctx: Context<KaminoTransfer>, amount: u64) -> Result<()> {
+        let before = ctx.accounts.reserve_destination_deposit_collateral.amount;
+        invoke_klend_transfer(&ctx, klend::DEPOSIT_DISCRIMINATOR, amount)?;
+        ctx.accounts
+            .reserve_destination_deposit_collateral
+            .reload()?;
+        let collateral = ctx
+            .accounts
+            .reserve_destination_deposit_collateral
+            .amount
+            .checked_sub(before)
+            .ok_or(AdapterError::MathOverflow)?;
+
+        let config = &mut ctx.accounts.config;
+        config.collateral = config
+            .collateral
+            .checked_add(collateral)
+            .ok_or(AdapterError::MathOverflow)?;
+
+        emit!(KaminoDepositEvent {
+            strategy: config.strategy,
+            amount,
+            collateral,
+        });
+
+        Ok(())
+    }
+
+    /// Redeem collateral worth at least `amount` of USDC (at most all of it) and send what
+    /// comes out to the vault's `asset_vault`
+    pub fn strategy_withdraw(ctx: Context<KaminoTransfer>, amount: u64) -> Result<()> {
+        let klend_reserve = ReserveView::load(&ctx.accounts.klend_reserve)?;
+        let collateral = klend_reserve
+            .collateral_for_liquidity(amount, ctx.accounts.reserve_collateral_mint.supply)?
+            .min(ctx.accounts.config.collateral);
+
+        let before = ctx.accounts.reserve.amount;
+        invoke_klend_transfer(&ctx, klend::WITHDRAW_DISCRIMINATOR, collateral)?;
+        ctx.accounts.reserve.reload()?;
+        let received = ctx
+            .accounts
+            .reserve
+            .amount
+            .checked_sub(before)
+            .ok_or(AdapterError::MathOverflow)?;
+
+        let config = &ctx.accounts.config;
+        let seeds = &[b"kamino".as_ref(), config.strategy.as_ref(), &[config.bump]];
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.reserve.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.asset_vault.to_account_info(),
+            authority: config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            &[&seeds[..]],
+        );
+        token_interface::transfer_checked(cpi_ctx, received, ctx.accounts.asset_mint.decimals)?;
+
+        let config = &mut ctx.accounts.config;
+        config.collateral -= collateral;
+
+        emit!(KaminoWithdrawEvent {
+            strategy: config.strategy,
+            amount,
+            collateral,
+            received,
+        });
+
+        Ok(())
+    }
+
+    /// USDC the obligation's collateral redeems for at the reserve's last refreshed exchange
+    /// rate, plus any USDC waiting in `reserve`
+    pub fn strategy_total_value(ctx: Context<KaminoTransfer>) -> Result<u64> {
+        let klend_reserve = ReserveView::load(&ctx.accounts.klend_reserve)?;
+        let lent = klend_reserve.liquidity_for_collateral(
+            ctx.accounts.config.collateral,
+            ctx.accounts.reserve_collateral_mint.supply,
+        )?;
+        lent.checked_add(ctx.accounts.reserve.amount)
+            .ok_or_else(|| error!(AdapterError::MathOverflow))
+    }
+}
+
+/// Call klend's deposit (`amount` of liquidity) or withdraw (`amount` of collateral), which
+/// share their account list apart from the order, signed by the `KaminoStrategy` PDA
+fn invoke_klend_transfer(
+    ctx: &Context<KaminoTransfer>,
+    discriminator: [u8; 8],
+    amount: u64,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let config = &accounts.config;
+    let seeds = &[b"kamino".as_ref(), config.strategy.as_ref(), &[config.bump]];
+
+    let owner = config.to_account_info();
+    let obligation = accounts.obligation.to_account_info();
+    let lending_market = accounts.lending_market.to_account_info();
+    let lending_market_authority = accounts.lending_market_authority.to_account_info();
+    let klend_reserve = accounts.klend_reserve.to_account_info();
+    let liquidity_mint = accounts.asset_mint.to_account_info();
+    let liquidity_supply = accounts.reserve_liquidity_supply.to_account_info();
+    let collateral_mint = accounts.reserve_collateral_mint.to_account_info();
+    let collateral_supply = accounts
+        .reserve_destination_deposit_collateral
+        .to_account_info();
+    let user_liquidity = accounts.reserve.to_account_info();
+    let klend_program = accounts.klend_program.to_account_info();
+    let collateral_token_program = accounts.collateral_token_program.to_account_info();
+    let liquidity_token_program = accounts.token_program.to_account_info();
+    let instruction_sysvar = accounts.instruction_sysvar.to_account_info();
+
+    let account_list: Vec<(&AccountInfo, bool)> = if discriminator == klend::DEPOSIT_DISCRIMINATOR {
+        vec![
+            (&owner, true),
+            (&obligation, true),
+            (&lending_market, false),
+            (&lending_market_authority, false),
+            (&klend_reserve, true),
+            (&liquidity_mint, false),
+            (&liquidity_supply, true),
+            (&collateral_mint, true),
+            (&collateral_supply, true),
+            (&user_liquidity, true),
+            // placeholder_user_destination_collateral
+            (&klend_program, false),
+            (&collateral_token_program, false),
+            (&liquidity_token_program, false),
+            (&instruction_sysvar, false),
+        ]
+    } else {
+        vec![
+            (&owner, true),
+            (&obligation, true),
+            (&lending_market, false),
+            (&lending_market_authority, false),
+            (&klend_reserve, true),
+            (&liquidity_mint, false),
+            (&collateral_supply, true),
+            (&collateral_mint, true),
+            (&liquidity_supply, true),
+            (&user_liquidity, true),
+            // placeholder_user_destination_collateral
+            (&klend_program, false),
+            (&collateral_token_program, false),
+            (&liquidity_token_program, false),
+            (&instruction_sysvar, false),
+        ]
+    };
+
+    klend::invoke(
+        &klend_program,
+        discriminator,
+        &amount.to_le_bytes(),
+        &account_list,
+        &[owner.key()],
+        seeds,
+    )
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        seeds::program = tsv_usdc_vault::ID,
+        constraint = vault.authority == authority.key() @ AdapterError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: The vault's `Strategy` PDA for this adapter, created later by `add_strategy`
+    #[account(
+        seeds = [b"strategy", vault.key().as_ref(), crate::ID.as_ref()],
+        bump,
+        seeds::program = tsv_usdc_vault::ID
+    )]
+    pub strategy: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + KaminoStrategy::INIT_SPACE,
+        seeds = [b"kamino", strategy.key().as_ref()],
+        bump
+    )]
+    pub config: Account<'info, KaminoStrategy>,
+
+    #[account(address = vault.asset_mint, mint::token_program = token_program)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// USDC account the vault allocates into
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = asset_mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: klend lending market, matched against `klend_reserve`
+    #[account(owner = KLEND_PROGRAM_ID)]
+    pub lending_market: UncheckedAccount<'info>,
+
+    /// CHECK: klend USDC reserve, read by `ReserveView`
+    pub klend_reserve: UncheckedAccount<'info>,
+
+    /// CHECK: klend user metadata PDA of `config`, created and checked by klend
+    #[account(mut)]
+    pub user_metadata: UncheckedAccount<'info>,
+
+    /// CHECK: klend obligation PDA of `config`, created and checked by klend
+    #[account(mut)]
+    pub obligation: UncheckedAccount<'info>,
+
+    /// CHECK: Kamino Lend program
+    #[account(address = KLEND_PROGRAM_ID)]
+    pub klend_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Accounts of `strategy_deposit` and `strategy_withdraw`: the strategy interface accounts,
+/// then the Kamino accounts the keeper passes to `allocate`/`deallocate` as remaining accounts
+#[derive(Accounts)]
+pub struct KaminoTransfer<'info> {
+    /// The vault's `Strategy` PDA, signing through the vault program
+    #[account(address = config.strategy)]
+    pub strategy: Signer<'info>,
+
+    #[account(mut, token::mint = asset_mint)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = config.reserve)]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    #[account(mut, seeds = [b"kamino", strategy.key().as_ref()], bump = config.bump)]
+    pub config: Account<'info, KaminoStrategy>,
+
+    /// CHECK: Checked by klend against the owner `config`
+    #[account(mut, address = config.obligation)]
+    pub obligation: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by klend against the obligation and reserve
+    #[account(address = config.lending_market)]
+    pub lending_market: UncheckedAccount<'info>,
+
+    /// CHECK: PDA of the lending market, checked by klend
+    pub lending_market_authority: UncheckedAccount<'info>,
+
+    /// CHECK: klend USDC reserve, read by `ReserveView`
+    #[account(mut, address = config.klend_reserve)]
+    pub klend_reserve: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by klend against the reserve
+    #[account(mut)]
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+
+    /// Checked by klend against the reserve
+    #[account(mut)]
+    pub reserve_collateral_mint: InterfaceAccount<'info, Mint>,
+
+    /// Checked by klend against the reserve
+    #[account(mut)]
+    pub reserve_destination_deposit_collateral: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Instructions sysvar, used by klend to check for refresh instructions
+    #[account(address = sysvar::instructions::ID)]
+    pub instruction_sysvar: UncheckedAccount<'info>,
+
+    pub collateral_token_program: Program<'info, Token>,
+
+    /// CHECK: Kamino Lend program
+    #[account(address = KLEND_PROGRAM_ID)]
+    pub klend_program: UncheckedAccount<'info>,
+}
+
+/// Adapter state of one vault strategy, seeds = [b"kamino", strategy]. Owns `reserve` and the
+/// klend obligation
+#[account]
+#[derive(InitSpace)]
+pub struct KaminoStrategy {
+    pub vault: Pubkey,
+    /// The vault's `Strategy` PDA, the only caller of `strategy_deposit`/`strategy_withdraw`
+    pub strategy: Pubkey,
+    pub lending_market: Pubkey,
+    pub klend_reserve: Pubkey,
+    pub obligation: Pubkey,
+    /// USDC account the vault allocates into
+    pub reserve: Pubkey,
+    /// Collateral (kTokens) deposited in the obligation
+    pub collateral: u64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct InitializeEvent {
+    pub vault: Pubkey,
+    pub strategy: Pubkey,
+    pub lending_market: Pubkey,
+    pub klend_reserve: Pubkey,
+    pub obligation: Pubkey,
+}
+
+#[event]
+pub struct KaminoDepositEvent {
+    pub strategy: Pubkey,
+    pub amount: u64,
+    pub collateral: u64,
+}
+
+#[event]
+pub struct KaminoWithdrawEvent {
+    pub strategy: Pubkey,
+    pub amount: u64,
+    pub collateral: u64,
+    pub received: u64,
+}
+
+#[error_code]
+pub enum AdapterError {
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Not a klend reserve of the lending market")]
+    InvalidReserve,
+    #[msg("klend reserve does not lend the vault's asset")]
+    ReserveMintMismatch,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
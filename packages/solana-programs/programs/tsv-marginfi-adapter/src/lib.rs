@@ -0,0 +1,610 @@
+//! Strategy adapter lending a Talken Stable Vault's USDC on marginfi.
+//!
+//! The adapter keeps one marginfi account per vault strategy, owned by its `MarginfiStrategy`
+//! PDA, which also owns the `reserve` USDC account the vault allocates into. `strategy_deposit`
+//! lends that USDC in the configured bank once the bank passes the health checks;
+//! `strategy_withdraw` withdraws the requested amount and forwards it to the vault's
+//! `asset_vault`. `strategy_total_value` values the account's deposits at the bank's share
+//! value.
+//!
+//! `emergency_exit` pulls everything out of marginfi into `reserve` without going through the
+//! vault, for the vault authority or guardian to react to a bank in trouble. The USDC stays with
+//! the adapter, so the vault's accounting is unchanged, until `deallocate` returns it.
+
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use tsv_usdc_vault::Vault;
+
+pub mod marginfi;
+
+use marginfi::{BankView, MARGINFI_PROGRAM_ID};
+
+declare_id!("2M1JdXZgEe3Dzh6ssomKhMxNwcKdE9Ef3oAAyRVUG6GE");
+
+/// Default cap on the bank's utilization for new deposits (90%)
+pub const DEFAULT_MAX_UTILIZATION_BPS: u16 = 9_000;
+
+#[program]
+pub mod tsv_marginfi_adapter {
+    use super::*;
+
+    /// Set up the adapter for the vault's strategy using this program: its USDC `reserve`
+    /// account and a marginfi account in `marginfi_group` (vault authority only).
+    /// The strategy is then registered by an `add_strategy` action queued in the vault's
+    /// timelock, passing `reserve`
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        let bank = BankView::load(&ctx.accounts.bank)?;
+        require_keys_eq!(
+            bank.group,
+            ctx.accounts.marginfi_group.key(),
+            AdapterError::InvalidBank
+        );
+        require_keys_eq!(
+            bank.mint,
+            ctx.accounts.asset_mint.key(),
+            AdapterError::BankMintMismatch
+        );
+
+        let strategy_key = ctx.accounts.strategy.key();
+        let config = &mut ctx.accounts.config;
+        config.vault = ctx.accounts.vault.key();
+        config.strategy = strategy_key;
+        config.marginfi_group = ctx.accounts.marginfi_group.key();
+        config.bank = ctx.accounts.bank.key();
+        config.marginfi_account = ctx.accounts.marginfi_account.key();
+        config.reserve = ctx.accounts.reserve.key();
+        config.max_utilization_bps = DEFAULT_MAX_UTILIZATION_BPS;
+        config.exited = false;
+        config.bump = ctx.bumps.config;
+        config.account_bump = ctx.bumps.marginfi_account;
+
+        // marginfi_account_initialize: the marginfi account is created at our PDA, which signs
+        // as the new account alongside its owner `config`
+        let config_seeds = &[
+            b"marginfi".as_ref(),
+            strategy_key.as_ref(),
+            &[ctx.bumps.config],
+        ];
+        let account_seeds = &[
+            b"marginfi_account".as_ref(),
+            strategy_key.as_ref(),
+            &[ctx.bumps.marginfi_account],
+        ];
+        let config_info = ctx.accounts.config.to_account_info();
+        let marginfi_account = ctx.accounts.marginfi_account.to_account_info();
+        marginfi::invoke(
+            &ctx.accounts.marginfi_program.to_account_info(),
+            marginfi::ACCOUNT_INITIALIZE_DISCRIMINATOR,
+            &[],
+            &[
+                (&ctx.accounts.marginfi_group.to_account_info(), false),
+                (&marginfi_account, true),
+                (&config_info, false),
+                (&ctx.accounts.authority.to_account_info(), true),
+                (&ctx.accounts.system_program.to_account_info(), false),
+            ],
+            &[
+                config_info.key(),
+                marginfi_account.key(),
+                ctx.accounts.authority.key(),
+            ],
+            &[config_seeds, account_seeds],
+        )?;
+
+        emit!(InitializeEvent {
+            vault: ctx.accounts.vault.key(),
+            strategy: strategy_key,
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            bank: ctx.accounts.bank.key(),
+            marginfi_account: marginfi_account.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Change the bank utilization above which deposits are refused (vault authority only)
+    pub fn set_max_utilization(
+        ctx: Context<SetMaxUtilization>,
+        max_utilization_bps: u16,
+    ) -> Result<()> {
+        require!(
+            max_utilization_bps <= 10_000,
+            AdapterError::InvalidUtilization
+        );
+        ctx.accounts.config.max_utilization_bps = max_utilization_bps;
+        Ok(())
+    }
+
+    /// Lend `amount` of USDC, already moved into `reserve` by the vault, once the bank has room
+    /// under its deposit limit and isn't lent out beyond `max_utilization_bps`
+    pub fn strategy_deposit(ctx: Context<MarginfiTransfer>, amount: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        require!(!config.exited, AdapterError::StrategyExited);
+
+        let bank = BankView::load(&ctx.accounts.bank)?;
+        let deposits = bank
+            .total_deposits
+            .checked_add(amount)
+            .ok_or(AdapterError::MathOverflow)?;
+        require!(
+            deposits <= bank.deposit_limit,
+            AdapterError::DepositLimitExceeded
+        );
+        let utilization_bps = bank.utilization_bps_after(amount)?;
+        require!(
+            utilization_bps <= u64::from(config.max_utilization_bps),
+            AdapterError::UtilizationTooHigh
+        );
+
+        let config_info = config.to_account_info();
+        let seeds = &[
+            b"marginfi".as_ref(),
+            config.strategy.as_ref(),
+            &[config.bump],
+        ];
+        // lending_account_deposit(amount, deposit_up_to_limit: None)
+        let mut args = amount.to_le_bytes().to_vec();
+        args.push(0);
+        marginfi::invoke(
+            &ctx.accounts.marginfi_program.to_account_info(),
+            marginfi::DEPOSIT_DISCRIMINATOR,
+            &args,
+            &[
+                (&ctx.accounts.marginfi_group.to_account_info(), false),
+                (&ctx.accounts.marginfi_account.to_account_info(), true),
+                (&config_info, false),
+                (&ctx.accounts.bank.to_account_info(), true),
+                (&ctx.accounts.reserve.to_account_info(), true),
+                (&ctx.accounts.bank_liquidity_vault.to_account_info(), true),
+                (&ctx.accounts.token_program.to_account_info(), false),
+            ],
+            &[config_info.key()],
+            &[seeds],
+        )?;
+
+        emit!(MarginfiDepositEvent {
+            strategy: config.strategy,
+            amount,
+            utilization_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw `amount` of USDC from marginfi and send it to the vault's `asset_vault`. After
+    /// `emergency_exit`, sends up to `amount` of what `reserve` holds instead. marginfi's health
+    /// check accounts (bank and oracle of each remaining balance) follow as remaining accounts
+    pub fn strategy_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MarginfiTransfer<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let seeds = &[
+            b"marginfi".as_ref(),
+            config.strategy.as_ref(),
+            &[config.bump],
+        ];
+
+        let received = if config.exited {
+            amount.min(ctx.accounts.reserve.amount)
+        } else {
+            let before = ctx.accounts.reserve.amount;
+            let bank = BankView::load(&ctx.accounts.bank)?;
+            let deposits =
+                bank.account_deposits(&ctx.accounts.bank.key(), &ctx.accounts.marginfi_account)?;
+            // lending_account_withdraw(amount, withdraw_all: None), or withdraw_all: Some(true)
+            // when asked for at least the whole deposit (e.g. the vault's emergency exit)
+            let mut args = amount.to_le_bytes().to_vec();
+            if amount >= deposits {
+                args.extend_from_slice(&[1, 1]);
+            } else {
+                args.push(0);
+            }
+            withdraw(
+                &ctx.accounts.marginfi_program,
+                &args,
+                MarginfiWithdrawAccounts {
+                    marginfi_group: ctx.accounts.marginfi_group.to_account_info(),
+                    marginfi_account: ctx.accounts.marginfi_account.to_account_info(),
+                    config: config.to_account_info(),
+                    bank: ctx.accounts.bank.to_account_info(),
+                    destination: ctx.accounts.reserve.to_account_info(),
+                    bank_liquidity_vault_authority: ctx
+                        .accounts
+                        .bank_liquidity_vault_authority
+                        .to_account_info(),
+                    bank_liquidity_vault: ctx.accounts.bank_liquidity_vault.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                    remaining: ctx.remaining_accounts,
+                },
+                seeds,
+            )?;
+            ctx.accounts.reserve.reload()?;
+            ctx.accounts
+                .reserve
+                .amount
+                .checked_sub(before)
+                .ok_or(AdapterError::MathOverflow)?
+        };
+
+        let config = &ctx.accounts.config;
+        let seeds = &[
+            b"marginfi".as_ref(),
+            config.strategy.as_ref(),
+            &[config.bump],
+        ];
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.reserve.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.asset_vault.to_account_info(),
+            authority: config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            &[&seeds[..]],
+        );
+        token_interface::transfer_checked(cpi_ctx, received, ctx.accounts.asset_mint.decimals)?;
+
+        emit!(MarginfiWithdrawEvent {
+            strategy: config.strategy,
+            amount,
+            received,
+        });
+
+        Ok(())
+    }
+
+    /// USDC the marginfi account's deposits are worth at the bank's current share value, plus
+    /// any USDC waiting in `reserve`
+    pub fn strategy_total_value(ctx: Context<MarginfiTransfer>) -> Result<u64> {
+        let bank = BankView::load(&ctx.accounts.bank)?;
+        let lent =
+            bank.account_deposits(&ctx.accounts.bank.key(), &ctx.accounts.marginfi_account)?;
+        lent.checked_add(ctx.accounts.reserve.amount)
+            .ok_or_else(|| error!(AdapterError::MathOverflow))
+    }
+
+    /// Withdraw everything from marginfi into `reserve` and refuse further deposits (vault
+    /// authority or guardian). The vault gets the USDC back through `deallocate`
+    pub fn emergency_exit(ctx: Context<EmergencyExit>) -> Result<()> {
+        let before = ctx.accounts.reserve.amount;
+        let config = &ctx.accounts.config;
+        let seeds = &[
+            b"marginfi".as_ref(),
+            config.strategy.as_ref(),
+            &[config.bump],
+        ];
+        // lending_account_withdraw(0, withdraw_all: Some(true)); closing the only balance
+        // leaves nothing for the health check
+        let mut args = 0u64.to_le_bytes().to_vec();
+        args.extend_from_slice(&[1, 1]);
+        withdraw(
+            &ctx.accounts.marginfi_program,
+            &args,
+            MarginfiWithdrawAccounts {
+                marginfi_group: ctx.accounts.marginfi_group.to_account_info(),
+                marginfi_account: ctx.accounts.marginfi_account.to_account_info(),
+                config: config.to_account_info(),
+                bank: ctx.accounts.bank.to_account_info(),
+                destination: ctx.accounts.reserve.to_account_info(),
+                bank_liquidity_vault_authority: ctx
+                    .accounts
+                    .bank_liquidity_vault_authority
+                    .to_account_info(),
+                bank_liquidity_vault: ctx.accounts.bank_liquidity_vault.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+                remaining: &[],
+            },
+            seeds,
+        )?;
+        ctx.accounts.reserve.reload()?;
+        let withdrawn = ctx
+            .accounts
+            .reserve
+            .amount
+            .checked_sub(before)
+            .ok_or(AdapterError::MathOverflow)?;
+
+        let config = &mut ctx.accounts.config;
+        config.exited = true;
+
+        emit!(EmergencyExitEvent {
+            strategy: config.strategy,
+            actor: ctx.accounts.signer.key(),
+            withdrawn,
+        });
+
+        Ok(())
+    }
+}
+
+/// Accounts of marginfi's `lending_account_withdraw`
+struct MarginfiWithdrawAccounts<'a, 'info> {
+    marginfi_group: AccountInfo<'info>,
+    marginfi_account: AccountInfo<'info>,
+    config: AccountInfo<'info>,
+    bank: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    bank_liquidity_vault_authority: AccountInfo<'info>,
+    bank_liquidity_vault: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    /// Health check accounts
+    remaining: &'a [AccountInfo<'info>],
+}
+
+/// Call marginfi's `lending_account_withdraw` with `args`, signed by the `MarginfiStrategy` PDA
+fn withdraw<'info>(
+    marginfi_program: &UncheckedAccount<'info>,
+    args: &[u8],
+    accounts: MarginfiWithdrawAccounts<'_, 'info>,
+    config_seeds: &[&[u8]],
+) -> Result<()> {
+    let signer = accounts.config.key();
+    let mut list = vec![
+        (&accounts.marginfi_group, false),
+        (&accounts.marginfi_account, true),
+        (&accounts.config, false),
+        (&accounts.bank, true),
+        (&accounts.destination, true),
+        (&accounts.bank_liquidity_vault_authority, false),
+        (&accounts.bank_liquidity_vault, true),
+        (&accounts.token_program, false),
+    ];
+    list.extend(
+        accounts
+            .remaining
+            .iter()
+            .map(|account| (account, account.is_writable)),
+    );
+    marginfi::invoke(
+        &marginfi_program.to_account_info(),
+        marginfi::WITHDRAW_DISCRIMINATOR,
+        args,
+        &list,
+        &[signer],
+        &[config_seeds],
+    )
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        seeds::program = tsv_usdc_vault::ID,
+        constraint = vault.authority == authority.key() @ AdapterError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: The vault's `Strategy` PDA for this adapter, created later by `add_strategy`
+    #[account(
+        seeds = [b"strategy", vault.key().as_ref(), crate::ID.as_ref()],
+        bump,
+        seeds::program = tsv_usdc_vault::ID
+    )]
+    pub strategy: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MarginfiStrategy::INIT_SPACE,
+        seeds = [b"marginfi", strategy.key().as_ref()],
+        bump
+    )]
+    pub config: Account<'info, MarginfiStrategy>,
+
+    #[account(address = vault.asset_mint, mint::token_program = token_program)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// USDC account the vault allocates into
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = asset_mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: marginfi group, matched against `bank`
+    #[account(owner = MARGINFI_PROGRAM_ID)]
+    pub marginfi_group: UncheckedAccount<'info>,
+
+    /// CHECK: marginfi USDC bank, read by `BankView`
+    pub bank: UncheckedAccount<'info>,
+
+    /// CHECK: marginfi account, created by marginfi at this PDA
+    #[account(mut, seeds = [b"marginfi_account", strategy.key().as_ref()], bump)]
+    pub marginfi_account: UncheckedAccount<'info>,
+
+    /// CHECK: marginfi program
+    #[account(address = MARGINFI_PROGRAM_ID)]
+    pub marginfi_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxUtilization<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        seeds::program = tsv_usdc_vault::ID,
+        constraint = vault.authority == authority.key() @ AdapterError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut, has_one = vault)]
+    pub config: Account<'info, MarginfiStrategy>,
+}
+
+/// Accounts of `strategy_deposit` and `strategy_withdraw`: the strategy interface accounts,
+/// then the marginfi accounts the keeper passes to `allocate`/`deallocate` as remaining accounts
+#[derive(Accounts)]
+pub struct MarginfiTransfer<'info> {
+    /// The vault's `Strategy` PDA, signing through the vault program
+    #[account(address = config.strategy)]
+    pub strategy: Signer<'info>,
+
+    #[account(mut, token::mint = asset_mint)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = config.reserve)]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    #[account(seeds = [b"marginfi", strategy.key().as_ref()], bump = config.bump)]
+    pub config: Account<'info, MarginfiStrategy>,
+
+    /// CHECK: Checked by marginfi against the account and bank
+    #[account(address = config.marginfi_group)]
+    pub marginfi_group: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by marginfi against the owner `config`
+    #[account(mut, address = config.marginfi_account)]
+    pub marginfi_account: UncheckedAccount<'info>,
+
+    /// CHECK: marginfi USDC bank, read by `BankView`
+    #[account(mut, address = config.bank)]
+    pub bank: UncheckedAccount<'info>,
+
+    /// CHECK: PDA of the bank, checked by marginfi
+    pub bank_liquidity_vault_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by marginfi against the bank
+    #[account(mut)]
+    pub bank_liquidity_vault: UncheckedAccount<'info>,
+
+    /// CHECK: marginfi program
+    #[account(address = MARGINFI_PROGRAM_ID)]
+    pub marginfi_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyExit<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        seeds::program = tsv_usdc_vault::ID,
+        constraint = signer.key() == vault.authority
+            || signer.key() == vault.guardian @ AdapterError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub signer: Signer<'info>,
+
+    #[account(mut, has_one = vault)]
+    pub config: Account<'info, MarginfiStrategy>,
+
+    #[account(mut, address = config.reserve)]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Checked by marginfi against the account and bank
+    #[account(address = config.marginfi_group)]
+    pub marginfi_group: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by marginfi against the owner `config`
+    #[account(mut, address = config.marginfi_account)]
+    pub marginfi_account: UncheckedAccount<'info>,
+
+    /// CHECK: marginfi USDC bank
+    #[account(mut, address = config.bank)]
+    pub bank: UncheckedAccount<'info>,
+
+    /// CHECK: PDA of the bank, checked by marginfi
+    pub bank_liquidity_vault_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by marginfi against the bank
+    #[account(mut)]
+    pub bank_liquidity_vault: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: marginfi program
+    #[account(address = MARGINFI_PROGRAM_ID)]
+    pub marginfi_program: UncheckedAccount<'info>,
+}
+
+/// Adapter state of one vault strategy, seeds = [b"marginfi", strategy]. Owns `reserve` and
+/// the marginfi account
+#[account]
+#[derive(InitSpace)]
+pub struct MarginfiStrategy {
+    pub vault: Pubkey,
+    /// The vault's `Strategy` PDA, the only caller of `strategy_deposit`/`strategy_withdraw`
+    pub strategy: Pubkey,
+    pub marginfi_group: Pubkey,
+    pub bank: Pubkey,
+    /// marginfi account, seeds = [b"marginfi_account", strategy]
+    pub marginfi_account: Pubkey,
+    /// USDC account the vault allocates into
+    pub reserve: Pubkey,
+    /// Bank utilization above which deposits are refused
+    pub max_utilization_bps: u16,
+    /// Everything was pulled out by `emergency_exit`; deposits are refused
+    pub exited: bool,
+    pub bump: u8,
+    pub account_bump: u8,
+}
+
+#[event]
+pub struct InitializeEvent {
+    pub vault: Pubkey,
+    pub strategy: Pubkey,
+    pub marginfi_group: Pubkey,
+    pub bank: Pubkey,
+    pub marginfi_account: Pubkey,
+}
+
+#[event]
+pub struct MarginfiDepositEvent {
+    pub strategy: Pubkey,
+    pub amount: u64,
+    pub utilization_bps: u64,
+}
+
+#[event]
+pub struct MarginfiWithdrawEvent {
+    pub strategy: Pubkey,
+    pub amount: u64,
+    pub received: u64,
+}
+
+#[event]
+pub struct EmergencyExitEvent {
+    pub strategy: Pubkey,
+    pub actor: Pubkey,
+    pub withdrawn: u64,
+}
+
+#[error_code]
+pub enum AdapterError {
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Not a marginfi bank of the group")]
+    InvalidBank,
+    #[msg("marginfi bank does not lend the vault's asset")]
+    BankMintMismatch,
+    #[msg("Deposit would exceed the bank's deposit limit")]
+    DepositLimitExceeded,
+    #[msg("Bank utilization is above the adapter's maximum")]
+    UtilizationTooHigh,
+    #[msg("Utilization must be at most 10,000 bps")]
+    InvalidUtilization,
+    #[msg("Strategy was emergency-exited")]
+    StrategyExited,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
@@ -0,0 +1,223 @@
+//! marginfi v2 instructions and `Bank` fields the adapter relies on.
+//!
+//! marginfi is an Anchor program, so instructions are called by their sighash and the few `Bank`
+//! fields needed are read at their offsets in its zero-copy layout rather than through a
+//! dependency on the marginfi crate.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+use crate::AdapterError;
+
+/// marginfi v2 program
+pub const MARGINFI_PROGRAM_ID: Pubkey = pubkey!("MFv2hWf31Z9kbCa1snEPYctwafyhdvnV7FZnsebVacA");
+
+/// sha256("global:marginfi_account_initialize")[..8]
+pub const ACCOUNT_INITIALIZE_DISCRIMINATOR: [u8; 8] = [43, 78, 61, 255, 148, 52, 249, 154];
+
+/// sha256("global:lending_account_deposit")[..8]
+pub const DEPOSIT_DISCRIMINATOR: [u8; 8] = [171, 94, 235, 103, 82, 64, 212, 140];
+
+/// sha256("global:lending_account_withdraw")[..8]
+pub const WITHDRAW_DISCRIMINATOR: [u8; 8] = [36, 72, 74, 19, 210, 210, 192, 192];
+
+// `MarginfiAccount` offsets, discriminator included: group and authority precede the lending
+// account's `Balance`s, each with its active flag, bank and asset shares
+const BALANCES_OFFSET: usize = 72;
+const BALANCE_LEN: usize = 104;
+const MAX_BALANCES: usize = 16;
+const BALANCE_BANK_OFFSET: usize = 1;
+const BALANCE_ASSET_SHARES_OFFSET: usize = 40;
+
+// `Bank` offsets, discriminator included
+const MINT_OFFSET: usize = 8;
+const GROUP_OFFSET: usize = 41;
+const ASSET_SHARE_VALUE_OFFSET: usize = 80;
+const LIABILITY_SHARE_VALUE_OFFSET: usize = 96;
+const LIQUIDITY_VAULT_OFFSET: usize = 112;
+const TOTAL_LIABILITY_SHARES_OFFSET: usize = 256;
+const TOTAL_ASSET_SHARES_OFFSET: usize = 272;
+const DEPOSIT_LIMIT_OFFSET: usize = 360;
+const BANK_MIN_LEN: usize = DEPOSIT_LIMIT_OFFSET + 8;
+
+/// Fractional bits of marginfi's `I80F48` amounts
+const FRACTION_BITS: u32 = 48;
+
+/// The `Bank` fields the adapter reads
+pub struct BankView {
+    pub mint: Pubkey,
+    pub group: Pubkey,
+    pub liquidity_vault: Pubkey,
+    /// Liquidity deposited, in mint units
+    pub total_deposits: u64,
+    /// Liquidity borrowed, in mint units
+    pub total_borrows: u64,
+    /// Cap on `total_deposits`
+    pub deposit_limit: u64,
+    /// Value of one asset share, `I80F48`
+    pub asset_share_value: i128,
+}
+
+impl BankView {
+    /// Read a marginfi `Bank` account
+    pub fn load(bank: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*bank.owner, MARGINFI_PROGRAM_ID, AdapterError::InvalidBank);
+        let data = bank.try_borrow_data()?;
+        require!(data.len() >= BANK_MIN_LEN, AdapterError::InvalidBank);
+
+        let pubkey = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+        let fixed =
+            |offset: usize| i128::from_le_bytes(data[offset..offset + 16].try_into().unwrap());
+
+        Ok(Self {
+            mint: pubkey(MINT_OFFSET),
+            group: pubkey(GROUP_OFFSET),
+            liquidity_vault: pubkey(LIQUIDITY_VAULT_OFFSET),
+            total_deposits: shares_to_amount(
+                fixed(TOTAL_ASSET_SHARES_OFFSET),
+                fixed(ASSET_SHARE_VALUE_OFFSET),
+            )?,
+            total_borrows: shares_to_amount(
+                fixed(TOTAL_LIABILITY_SHARES_OFFSET),
+                fixed(LIABILITY_SHARE_VALUE_OFFSET),
+            )?,
+            deposit_limit: u64::from_le_bytes(
+                data[DEPOSIT_LIMIT_OFFSET..DEPOSIT_LIMIT_OFFSET + 8]
+                    .try_into()
+                    .unwrap(),
+            ),
+            asset_share_value: fixed(ASSET_SHARE_VALUE_OFFSET),
+        })
+    }
+
+    /// Value of the asset shares `marginfi_account` holds in this bank (at `bank`), in mint
+    /// units
+    pub fn account_deposits(&self, bank: &Pubkey, marginfi_account: &AccountInfo) -> Result<u64> {
+        require_keys_eq!(
+            *marginfi_account.owner,
+            MARGINFI_PROGRAM_ID,
+            AdapterError::InvalidBank
+        );
+        let data = marginfi_account.try_borrow_data()?;
+        require!(
+            data.len() >= BALANCES_OFFSET + MAX_BALANCES * BALANCE_LEN,
+            AdapterError::InvalidBank
+        );
+        for balance in data[BALANCES_OFFSET..]
+            .chunks_exact(BALANCE_LEN)
+            .take(MAX_BALANCES)
+        {
+            let active = balance[0] != 0;
+            let bank_pk = &balance[BALANCE_BANK_OFFSET..BALANCE_BANK_OFFSET + 32];
+            if active && bank_pk == bank.as_ref() {
+                let shares = i128::from_le_bytes(
+                    balance[BALANCE_ASSET_SHARES_OFFSET..BALANCE_ASSET_SHARES_OFFSET + 16]
+                        .try_into()
+                        .unwrap(),
+                );
+                return shares_to_amount(shares, self.asset_share_value);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Borrowed share of the deposits after depositing `amount` more, in bps
+    pub fn utilization_bps_after(&self, amount: u64) -> Result<u64> {
+        let deposits = u128::from(self.total_deposits) + u128::from(amount);
+        if deposits == 0 {
+            return Ok(0);
+        }
+        let bps = u128::from(self.total_borrows) * 10_000 / deposits;
+        u64::try_from(bps).map_err(|_| error!(AdapterError::MathOverflow))
+    }
+}
+
+/// `shares` at `share_value`, both `I80F48`, in whole mint units (rounded down). Whole shares
+/// are taken first so the product fits in 128 bits
+fn shares_to_amount(shares: i128, share_value: i128) -> Result<u64> {
+    let shares = u128::try_from(shares).map_err(|_| error!(AdapterError::InvalidBank))?;
+    let share_value = u128::try_from(share_value).map_err(|_| error!(AdapterError::InvalidBank))?;
+    let amount = (shares >> FRACTION_BITS)
+        .checked_mul(share_value)
+        .ok_or(AdapterError::MathOverflow)?
+        >> FRACTION_BITS;
+    u64::try_from(amount).map_err(|_| error!(AdapterError::MathOverflow))
+}
+
+/// Call the marginfi instruction `discriminator` with `args` over `accounts`, signed by
+/// `signer_seeds`. `accounts` pairs each account with whether it is writable; `signers` are
+/// marked as signers
+pub fn invoke<'info>(
+    marginfi_program: &AccountInfo<'info>,
+    discriminator: [u8; 8],
+    args: &[u8],
+    accounts: &[(&AccountInfo<'info>, bool)],
+    signers: &[Pubkey],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let metas = accounts
+        .iter()
+        .map(|(account, writable)| AccountMeta {
+            pubkey: account.key(),
+            is_signer: signers.contains(account.key),
+            is_writable: *writable,
+        })
+        .collect();
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(args);
+    let ix = Instruction {
+        program_id: MARGINFI_PROGRAM_ID,
+        accounts: metas,
+        data,
+    };
+
+    let mut infos: Vec<AccountInfo<'info>> = accounts
+        .iter()
+        .map(|(account, _)| (*account).clone())
+        .collect();
+    infos.push(marginfi_program.clone());
+    invoke_signed(&ix, &infos, signer_seeds)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash::hash;
+
+    #[test]
+    fn discriminators_match_anchor_sighash() {
+        for (name, discriminator) in [
+            (
+                "marginfi_account_initialize",
+                ACCOUNT_INITIALIZE_DISCRIMINATOR,
+            ),
+            ("lending_account_deposit", DEPOSIT_DISCRIMINATOR),
+            ("lending_account_withdraw", WITHDRAW_DISCRIMINATOR),
+        ] {
+            let sighash = hash(format!("global:{name}").as_bytes()).to_bytes();
+            assert_eq!(sighash[..8], discriminator, "{name}");
+        }
+    }
+
+    #[test]
+    fn converts_shares_and_utilization() {
+        let one = 1i128 << FRACTION_BITS;
+        // 1,000 USDC of shares worth 1.05 each
+        let deposits = shares_to_amount(1_000_000_000 * one, one + one / 20).unwrap();
+        assert_eq!(deposits, 1_050_000_000);
+
+        let bank = BankView {
+            mint: Pubkey::default(),
+            group: Pubkey::default(),
+            liquidity_vault: Pubkey::default(),
+            total_deposits: 900_000_000,
+            total_borrows: 800_000_000,
+            deposit_limit: u64::MAX,
+            asset_share_value: one,
+        };
+        assert_eq!(bank.utilization_bps_after(100_000_000).unwrap(), 8_000);
+        assert!(shares_to_amount(-one, one).is_err());
+    }
+}
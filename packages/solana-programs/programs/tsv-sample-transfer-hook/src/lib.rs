@@ -0,0 +1,114 @@
+//! Transfer hook for the vault's tests of a share mint with the TransferHook extension.
+//!
+//! Token-2022 calls `transfer_hook` on every `transfer_checked` of the mint, with the
+//! mint's `HookCounter` resolved from its extra account metas, and the hook counts the
+//! transfer. A test can then tell which instructions went through the hook: share transfers
+//! between holders do, the vault's `mint_to` and `burn` of shares don't.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use spl_tlv_account_resolution::account::ExtraAccountMeta;
+use spl_tlv_account_resolution::seeds::Seed;
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+declare_id!("6yHLE8p2YCW5xixBhZJ75copdN2VnXcDCqfgJa1TBc7p");
+
+#[program]
+pub mod tsv_sample_transfer_hook {
+    use super::*;
+
+    /// Create the extra account metas of `mint`, naming its `HookCounter`, and the counter
+    pub fn initialize_extra_account_meta_list(
+        ctx: Context<InitializeExtraAccountMetaList>,
+    ) -> Result<()> {
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &extra_account_metas()?)?;
+
+        ctx.accounts.counter.mint = ctx.accounts.mint.key();
+        Ok(())
+    }
+
+    /// Count a transfer of `amount`
+    #[interface(spl_transfer_hook_interface::execute)]
+    pub fn transfer_hook(ctx: Context<Execute>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.transfers += 1;
+        counter.last_amount = amount;
+        Ok(())
+    }
+}
+
+/// The `HookCounter` of the mint (account 1 of `Execute`), writable
+fn extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
+    Ok(vec![ExtraAccountMeta::new_with_seeds(
+        &[
+            Seed::Literal {
+                bytes: b"counter".to_vec(),
+            },
+            Seed::AccountKey { index: 1 },
+        ],
+        false,
+        true,
+    )?])
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: TLV list of extra account metas, written by `ExtraAccountMetaList::init`
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(extra_account_metas()?.len())?,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + HookCounter::INIT_SPACE,
+        seeds = [b"counter", mint.key().as_ref()],
+        bump
+    )]
+    pub counter: Account<'info, HookCounter>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of the transfer-hook interface's `Execute`, in its order
+#[derive(Accounts)]
+pub struct Execute<'info> {
+    #[account(token::mint = mint)]
+    pub source: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(token::mint = mint)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Owner or delegate of `source`, checked by Token-2022
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: Extra account metas of `mint`
+    #[account(seeds = [b"extra-account-metas", mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"counter", mint.key().as_ref()], bump, has_one = mint)]
+    pub counter: Account<'info, HookCounter>,
+}
+
+/// Transfers of `mint` seen by the hook, seeds = [b"counter", mint]
+#[account]
+#[derive(InitSpace)]
+pub struct HookCounter {
+    pub mint: Pubkey,
+    pub transfers: u64,
+    pub last_amount: u64,
+}
@@ -0,0 +1,107 @@
+//! Token-2022 mint extensions the vault's accounting and safety depend on.
+//!
+//! A mint with the TransferFee extension withholds part of every transfer in the destination
+//! account, so the vault receives less than the amount sent. Deposits book only what arrives,
+//! and exact-share mints gross the amount sent up so the vault still receives what it needs.
+//!
+//! Other extensions are checked once, when a vault is initialized for an asset mint: some make
+//! the vault unusable, others give a third party power over the vault's USDC.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::default_account_state::DefaultAccountState;
+use anchor_spl::token_2022::spl_token_2022::extension::mint_close_authority::MintCloseAuthority;
+use anchor_spl::token_2022::spl_token_2022::extension::permanent_delegate::PermanentDelegate;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_hook::TransferHook;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::{AccountState, Mint};
+
+use crate::{math, VaultError};
+
+/// `TransferFeeConfig` of `mint`, if it is a Token-2022 mint with that extension
+fn transfer_fee_config(mint: &AccountInfo) -> Result<Option<TransferFeeConfig>> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+    Ok(state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// Fee withheld from a transfer of `amount` of `mint` in the current epoch
+pub fn transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let Some(config) = transfer_fee_config(mint)? else {
+        return Ok(0);
+    };
+    config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or_else(|| error!(VaultError::MathOverflow))
+}
+
+/// Amount of `mint` to send so that `received` arrives after the current epoch's transfer fee
+pub fn gross_up_for_transfer_fee(mint: &AccountInfo, received: u64) -> Result<u64> {
+    let Some(config) = transfer_fee_config(mint)? else {
+        return Ok(received);
+    };
+    let fee = config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, received)
+        .ok_or_else(|| error!(VaultError::MathOverflow))?;
+    math::add(received, fee)
+}
+
+/// Reject an asset mint whose extensions would break the vault or let a third party move or
+/// destroy its USDC. PermanentDelegate and MintCloseAuthority with a set authority are allowed
+/// only with `allow_privileged`, for issuers (e.g. regulated stablecoins) that keep them
+pub fn check_asset_mint(mint: &AccountInfo, allow_privileged: bool) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+
+    for extension in state.get_extension_types()? {
+        match extension {
+            // Vault transfers would fail, or its token account would start frozen
+            ExtensionType::NonTransferable => {
+                return err!(VaultError::UnsupportedMintExtension);
+            }
+            ExtensionType::DefaultAccountState => {
+                let default = state.get_extension::<DefaultAccountState>()?;
+                require!(
+                    default.state != AccountState::Frozen as u8,
+                    VaultError::UnsupportedMintExtension
+                );
+            }
+            // The hook's extra accounts aren't forwarded, so only an unset hook works
+            ExtensionType::TransferHook => {
+                let hook = state.get_extension::<TransferHook>()?;
+                require!(
+                    Option::<Pubkey>::from(hook.program_id).is_none(),
+                    VaultError::UnsupportedMintExtension
+                );
+            }
+            // Can transfer or burn the vault's USDC at will
+            ExtensionType::PermanentDelegate => {
+                let delegate = state.get_extension::<PermanentDelegate>()?;
+                require!(
+                    allow_privileged || Option::<Pubkey>::from(delegate.delegate).is_none(),
+                    VaultError::PrivilegedMintExtension
+                );
+            }
+            // Can close the mint once its supply is zero and recreate it at the same address
+            // with different extensions
+            ExtensionType::MintCloseAuthority => {
+                let close = state.get_extension::<MintCloseAuthority>()?;
+                require!(
+                    allow_privileged || Option::<Pubkey>::from(close.close_authority).is_none(),
+                    VaultError::PrivilegedMintExtension
+                );
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
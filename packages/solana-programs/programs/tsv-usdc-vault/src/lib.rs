@@ -1,359 +1,10398 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::metadata::mpl_token_metadata::types::DataV2;
+use anchor_spl::metadata::{self, CreateMetadataAccountsV3, Metadata, UpdateMetadataAccountsV2};
+use anchor_spl::token_interface::{
+    self, Burn, CloseAccount, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
+use tsv_meta_message::{Domain, MetaAction, MetaMessage};
+
+pub mod extensions;
+pub mod math;
+pub mod migration;
+pub mod oracle;
+pub mod signature;
+pub mod strategy;
+
+use math::Rounding;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// Upper bound for penalty fees charged on top of the withdraw fee (10%)
+pub const MAX_PENALTY_BPS: u16 = 1_000;
+
+/// Upper bound for the annualized management fee (5% per year)
+pub const MAX_MANAGEMENT_FEE_BPS: u16 = 500;
+
+/// Upper bound for the performance fee taken on harvested strategy gains (20%)
+pub const MAX_PERFORMANCE_FEE_BPS: u16 = 2_000;
+
+/// Period harvested profit unlocks over by default (6 hours)
+pub const DEFAULT_PROFIT_UNLOCK_DURATION: u32 = 6 * 60 * 60;
+
+/// Upper bound for the profit unlock period (30 days)
+pub const MAX_PROFIT_UNLOCK_DURATION: u32 = 30 * 24 * 60 * 60;
+
+/// Upper bound for the premium `instant_redeem` charges on top of the redeem fee (5%)
+pub const MAX_INSTANT_REDEEM_PREMIUM_BPS: u16 = 500;
+
+/// Default maximum change of each fee per queued update
+pub const DEFAULT_MAX_FEE_STEP_BPS: u16 = 25;
+
+/// Delay of a queued fee update on a new vault (2 days)
+pub const DEFAULT_FEE_TIMELOCK_SECONDS: u32 = 2 * 24 * 60 * 60;
+
+/// Shortest fee-update delay `set_fee_timelock` accepts (1 day)
+pub const MIN_FEE_TIMELOCK_SECONDS: u32 = 24 * 60 * 60;
+
+/// Delay after which `emergency_withdraw` no longer needs the guardian co-signature (14 days)
+pub const EMERGENCY_ESCAPE_DELAY_SECONDS: i64 = 14 * 24 * 60 * 60;
+
+/// Time holders have to `claim_wind_down` after the wind-down rate is frozen, before the vault
+/// can be closed (90 days)
+pub const WIND_DOWN_GRACE_PERIOD_SECONDS: i64 = 90 * 24 * 60 * 60;
+
+/// Shortest delay of a queued timelock action (1 day), also applied to timelocks created
+/// before `init_timelock` enforced it
+pub const MIN_TIMELOCK_DELAY_SECONDS: u32 = 24 * 60 * 60;
+
+/// Window after a queued action's eta in which it can be executed before it expires (14 days)
+pub const ACTION_GRACE_PERIOD_SECONDS: i64 = 14 * 24 * 60 * 60;
+
+/// Length of the window a relayer's `daily_limit` applies to
+pub const RELAYER_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+/// Accounts per request in `batch_meta_redeem`'s `remaining_accounts`: owner, owner shares,
+/// receiver USDC account, owner position, fee exemption, allowlist entry, owner blocklist,
+/// receiver blocklist and user nonce
+pub const BATCH_ITEM_ACCOUNTS: usize = 9;
+
+/// Accounts per strategy in the `remaining_accounts` of `harvest` and of redemptions
+/// deallocating from strategies, before its adapter-specific ones: `Strategy`, adapter program
+/// and reserve
+pub const STRATEGY_ITEM_ACCOUNTS: usize = 3;
+
+/// Most strategies a vault can register at once
+pub const MAX_STRATEGIES: u8 = 8;
+
+/// Accounts per basket asset in `redeem_proportional`'s `remaining_accounts`: `BasketAsset`,
+/// its Pyth price update, mint, basket token account, the user's token account of the mint and
+/// the mint's token program
+pub const BASKET_ITEM_ACCOUNTS: usize = 6;
+
+/// Most basket assets a vault accepts besides its asset mint
+pub const MAX_BASKET_ASSETS: u8 = 4;
+
+/// Longest `VaultInfo` strings accepted, in bytes
+pub const MAX_VAULT_NAME_LEN: usize = 64;
+pub const MAX_VAULT_DESCRIPTION_LEN: usize = 256;
+pub const MAX_VAULT_URI_LEN: usize = 200;
+
+/// Fixed-point scale of `VaultSnapshot::exchange_rate` (assets per 1e9 share base units)
+pub const EXCHANGE_RATE_SCALE: u64 = 1_000_000_000;
+
+/// Daily exchange rates a `RateHistory` keeps (a year's worth)
+pub const RATE_HISTORY_LEN: usize = 365;
+
+/// Least time between two `RateHistory` snapshots (1 day)
+pub const RATE_SNAPSHOT_INTERVAL_SECONDS: i64 = 24 * 60 * 60;
+
 #[program]
 pub mod tsv_usdc_vault {
     use super::*;
 
-    /// Initialize the vault
-    pub fn initialize(ctx: Context<Initialize>, fee_bps: u16) -> Result<()> {
-        require!(fee_bps <= 100, VaultError::InvalidFee);
+    /// Initialize a vault outside the registry (see `create_vault`)
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        deposit_fee_bps: u16,
+        redeem_fee_bps: u16,
+        dead_shares: u64,
+        genesis_hash: [u8; 32],
+        allow_privileged_extensions: bool,
+        share_name: String,
+        share_symbol: String,
+        share_uri: String,
+        vault_info: VaultInfo,
+    ) -> Result<()> {
+        initialize_vault(
+            ctx.accounts,
+            ctx.bumps.vault,
+            ctx.bumps.vault_metadata,
+            ctx.bumps.event_authority,
+            deposit_fee_bps,
+            redeem_fee_bps,
+            dead_shares,
+            genesis_hash,
+            allow_privileged_extensions,
+            share_name,
+            share_symbol,
+            share_uri,
+            vault_info,
+        )
+    }
 
-        let vault = &mut ctx.accounts.vault;
-        vault.authority = ctx.accounts.authority.key();
-        vault.asset_mint = ctx.accounts.asset_mint.key();
-        vault.share_mint = ctx.accounts.share_mint.key();
-        vault.asset_vault = ctx.accounts.asset_vault.key();
-        vault.total_assets = 0;
-        vault.total_shares = 0;
-        vault.fee_bps = fee_bps;
-        vault.bump = ctx.bumps.vault;
+    /// Create the program's vault registry (upgrade authority only). `creator` is the only
+    /// account allowed to `create_vault`, or anyone with the default pubkey
+    pub fn init_registry(ctx: Context<InitRegistry>, creator: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.admin = ctx.accounts.admin.key();
+        registry.creator = creator;
+        registry.bump = ctx.bumps.registry;
+        registry.vaults = Vec::new();
+
+        emit_cpi!(RegistryCreatorUpdatedEvent { creator });
+
+        Ok(())
+    }
+
+    /// Change who may `create_vault` (registry admin only); the default pubkey opens it to
+    /// anyone
+    pub fn set_registry_creator(ctx: Context<SetRegistryCreator>, creator: Pubkey) -> Result<()> {
+        ctx.accounts.registry.creator = creator;
+
+        emit_cpi!(RegistryCreatorUpdatedEvent { creator });
 
         Ok(())
     }
 
-    /// Deposit USDC and mint shares
-    pub fn deposit(ctx: Context<Deposit>, assets: u64) -> Result<()> {
+    /// Initialize a vault with the arguments of `initialize` and list it in the registry. The
+    /// creator becomes the vault's authority and pays for the registry's growth
+    pub fn create_vault(
+        ctx: Context<CreateVault>,
+        deposit_fee_bps: u16,
+        redeem_fee_bps: u16,
+        dead_shares: u64,
+        genesis_hash: [u8; 32],
+        allow_privileged_extensions: bool,
+        share_name: String,
+        share_symbol: String,
+        share_uri: String,
+        vault_info: VaultInfo,
+    ) -> Result<()> {
+        let creator = ctx.accounts.vault_accounts.authority.key();
+        require!(
+            ctx.accounts.registry.can_create(&creator),
+            VaultError::Unauthorized
+        );
+
+        initialize_vault(
+            &mut ctx.accounts.vault_accounts,
+            ctx.bumps.vault_accounts.vault,
+            ctx.bumps.vault_accounts.vault_metadata,
+            ctx.bumps.vault_accounts.event_authority,
+            deposit_fee_bps,
+            redeem_fee_bps,
+            dead_shares,
+            genesis_hash,
+            allow_privileged_extensions,
+            share_name,
+            share_symbol,
+            share_uri,
+            vault_info,
+        )?;
+
+        let accounts = &ctx.accounts.vault_accounts;
+        register_vault_in(
+            &mut ctx.accounts.registry,
+            &accounts.vault,
+            accounts.authority.to_account_info(),
+            &accounts.system_program,
+            &EventCpi {
+                authority: accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.vault_accounts.event_authority,
+            },
+        )
+    }
+
+    /// List a vault created with `initialize`, such as the legacy `[b"vault"]` one, in the
+    /// registry (registry admin only)
+    pub fn register_vault(ctx: Context<RegisterVault>) -> Result<()> {
+        register_vault_in(
+            &mut ctx.accounts.registry,
+            &ctx.accounts.vault,
+            ctx.accounts.admin.to_account_info(),
+            &ctx.accounts.system_program,
+            &EventCpi {
+                authority: ctx.accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.event_authority,
+            },
+        )
+    }
+
+    /// Deposit USDC and mint shares to `receiver` (which may be another wallet)
+    pub fn deposit(ctx: Context<Deposit>, assets: u64, min_shares_out: Option<u64>) -> Result<()> {
         let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_deposits, VaultError::DepositsPaused);
+        vault.check_deposit_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.receiver_blocklist,
+            &vault.key(),
+            &ctx.accounts.receiver.key(),
+        )?;
+        require!(assets >= vault.min_deposit, VaultError::DepositTooSmall);
 
-        // Calculate shares to mint
-        let shares = if vault.total_shares == 0 {
-            assets // 1:1 for first deposit
-        } else {
-            (assets as u128)
-                .checked_mul(vault.total_shares as u128)
-                .unwrap()
-                .checked_div(vault.total_assets as u128)
-                .unwrap() as u64
-        };
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
 
-        // Transfer assets from user to vault
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_asset.to_account_info(),
-            to: ctx.accounts.asset_vault.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, assets)?;
+        let position = &mut ctx.accounts.receiver_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.receiver.key(),
+            ctx.bumps.receiver_position,
+        );
 
-        // Mint shares to user
-        let seeds = &[b"vault", &[vault.bump]];
-        let signer = &[&seeds[..]];
+        let (shares, fee) = deposit_for_shares(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            assets,
+            None,
+            now,
+            DepositTokenAccounts {
+                source_asset: &ctx.accounts.user_asset,
+                transfer_authority: ctx.accounts.user.to_account_info(),
+                transfer_seeds: &[],
+                asset_vault: &ctx.accounts.asset_vault,
+                asset_mint: &ctx.accounts.asset_mint,
+                share_mint: &ctx.accounts.share_mint,
+                receiver_shares: &ctx.accounts.receiver_shares,
+                dead_shares_account: &ctx.accounts.dead_shares_account,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(min_shares_out) = min_shares_out {
+            require!(shares >= min_shares_out, VaultError::SlippageExceeded);
+        }
+        if let Some(cost_basis) = &mut ctx.accounts.cost_basis {
+            cost_basis.record_deposit(assets, shares)?;
+        }
 
-        let cpi_accounts = token::MintTo {
-            mint: ctx.accounts.share_mint.to_account_info(),
-            to: ctx.accounts.user_shares.to_account_info(),
-            authority: vault.to_account_info(),
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(DepositEvent {
+            user: ctx.accounts.user.key(),
+            receiver: ctx.accounts.receiver.key(),
+            assets,
+            shares,
+            fee,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Mint an exact number of shares, pulling the required USDC, at most `max_assets_in` of it
+    /// when given
+    pub fn mint(ctx: Context<MintShares>, shares: u64, max_assets_in: Option<u64>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_deposits, VaultError::DepositsPaused);
+        vault.check_deposit_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        // Calculate assets required (round up, in favor of the vault), including the dead
+        // shares locked on the first deposit, then gross up for the deposit fee and so `assets`
+        // arrive after any Token-2022 transfer fee
+        let dead_shares = vault.pending_dead_shares();
+        let net_assets =
+            vault.convert_to_assets(math::add(shares, dead_shares)?, Rounding::Ceil)?;
+        let assets = if ctx.accounts.fee_exemption.is_some() {
+            net_assets
+        } else {
+            vault.gross_up_for_deposit_fee(net_assets)?
         };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        token::mint_to(cpi_ctx, shares)?;
+        require!(assets >= vault.min_deposit, VaultError::DepositTooSmall);
+        let sent = extensions::gross_up_for_transfer_fee(
+            &ctx.accounts.asset_mint.to_account_info(),
+            assets,
+        )?;
+        if let Some(max_assets_in) = max_assets_in {
+            require!(sent <= max_assets_in, VaultError::SlippageExceeded);
+        }
+
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
 
-        // Update vault state
-        vault.total_assets = vault.total_assets.checked_add(assets).unwrap();
-        vault.total_shares = vault.total_shares.checked_add(shares).unwrap();
+        let (shares, fee) = deposit_for_shares(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            sent,
+            Some(shares),
+            now,
+            DepositTokenAccounts {
+                source_asset: &ctx.accounts.user_asset,
+                transfer_authority: ctx.accounts.user.to_account_info(),
+                transfer_seeds: &[],
+                asset_vault: &ctx.accounts.asset_vault,
+                asset_mint: &ctx.accounts.asset_mint,
+                share_mint: &ctx.accounts.share_mint,
+                receiver_shares: &ctx.accounts.user_shares,
+                dead_shares_account: &ctx.accounts.dead_shares_account,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(cost_basis) = &mut ctx.accounts.cost_basis {
+            cost_basis.record_deposit(sent, shares)?;
+        }
 
-        emit!(DepositEvent {
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(DepositEvent {
             user: ctx.accounts.user.key(),
-            assets,
+            receiver: ctx.accounts.user.key(),
+            assets: sent,
             shares,
+            fee,
+            snapshot,
         });
 
         Ok(())
     }
 
     /// Redeem shares for USDC (with fee)
-    pub fn redeem(ctx: Context<Redeem>, shares: u64) -> Result<()> {
+    pub fn redeem<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Redeem<'info>>,
+        shares: u64,
+        min_assets_out: Option<u64>,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
         let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+        if let Some(receiver_asset) = &ctx.accounts.receiver_asset {
+            let receiver_blocklist = ctx
+                .accounts
+                .receiver_blocklist
+                .as_ref()
+                .ok_or(error!(ErrorCode::AccountNotEnoughKeys))?;
+            BlocklistEntry::check_not_blocked(
+                receiver_blocklist,
+                &vault.key(),
+                &receiver_asset.owner,
+            )?;
+        }
 
-        // Calculate gross assets
-        let gross_assets = (shares as u128)
-            .checked_mul(vault.total_assets as u128)
-            .unwrap()
-            .checked_div(vault.total_shares as u128)
-            .unwrap() as u64;
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
 
-        // Apply fee (10 bps = 0.1%)
-        let fee = (gross_assets as u128)
-            .checked_mul(vault.fee_bps as u128)
-            .unwrap()
-            .checked_div(10_000)
-            .unwrap() as u64;
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
+        position.check_unlocked(now)?;
 
-        let net_assets = gross_assets.checked_sub(fee).unwrap();
+        let (net_assets, fee) = burn_for_redeem(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.share_mint,
+                owner_shares: &ctx.accounts.user_shares,
+                burn_authority: ctx.accounts.user.to_account_info(),
+                asset_vault: &ctx.accounts.asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(min_assets_out) = min_assets_out {
+            require!(net_assets >= min_assets_out, VaultError::SlippageExceeded);
+        }
+        deallocate_for_redeem(
+            vault,
+            &mut ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.token_program,
+            StrategyItems::new(ctx.remaining_accounts, &adapter_accounts),
+            net_assets,
+            &EventCpi {
+                authority: ctx.accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.event_authority,
+            },
+        )?;
 
-        // Burn user shares
-        let cpi_accounts = token::Burn {
-            mint: ctx.accounts.share_mint.to_account_info(),
-            from: ctx.accounts.user_shares.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
+        // Transfer net assets to the receiver (defaults to the user's own account)
+        let receiver_asset = match &ctx.accounts.receiver_asset {
+            Some(receiver_asset) => receiver_asset,
+            None => &ctx.accounts.user_asset,
         };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::burn(cpi_ctx, shares)?;
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            receiver_asset,
+            &ctx.accounts.token_program,
+            net_assets,
+        )?;
+        if let Some(cost_basis) = &mut ctx.accounts.cost_basis {
+            cost_basis.record_redeem(shares, net_assets)?;
+        }
 
-        // Transfer net assets to user
-        let seeds = &[b"vault", &[vault.bump]];
-        let signer = &[&seeds[..]];
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(RedeemEvent {
+            user: ctx.accounts.user.key(),
+            receiver: receiver_asset.owner,
+            shares,
+            assets: net_assets,
+            fee,
+            snapshot,
+        });
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.asset_vault.to_account_info(),
-            to: ctx.accounts.user_asset.to_account_info(),
-            authority: vault.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        token::transfer(cpi_ctx, net_assets)?;
+        Ok(())
+    }
+
+    /// Redeem the caller's entire share balance, read on-chain at execution time
+    pub fn redeem_max<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Redeem<'info>>,
+        min_assets_out: Option<u64>,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        let shares = ctx.accounts.user_shares.amount;
+        redeem(ctx, shares, min_assets_out, adapter_accounts)
+    }
 
-        // Update vault state (fee remains in vault)
-        vault.total_assets = vault.total_assets.checked_sub(net_assets).unwrap();
-        vault.total_shares = vault.total_shares.checked_sub(shares).unwrap();
+    /// Redeem shares straight from the idle USDC, buffer included, without deallocating from
+    /// strategies or waiting for a withdrawal epoch. On top of the redeem fee it charges
+    /// `instant_redeem_premium_bps`, which stays in `total_assets` for the remaining and queued
+    /// shareholders whose liquidity it uses
+    pub fn instant_redeem(
+        ctx: Context<Redeem>,
+        shares: u64,
+        min_assets_out: Option<u64>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+        if let Some(receiver_asset) = &ctx.accounts.receiver_asset {
+            let receiver_blocklist = ctx
+                .accounts
+                .receiver_blocklist
+                .as_ref()
+                .ok_or(error!(ErrorCode::AccountNotEnoughKeys))?;
+            BlocklistEntry::check_not_blocked(
+                receiver_blocklist,
+                &vault.key(),
+                &receiver_asset.owner,
+            )?;
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
+        position.check_unlocked(now)?;
+
+        let (net_assets, fee) = burn_for_redeem(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.share_mint,
+                owner_shares: &ctx.accounts.user_shares,
+                burn_authority: ctx.accounts.user.to_account_info(),
+                asset_vault: &ctx.accounts.asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        // The premium applies to fee-exempt owners too: it pays the other shareholders, not
+        // the protocol
+        let premium = math::fee_on_amount(
+            math::add(net_assets, fee)?,
+            vault.instant_redeem_premium_bps,
+        )?;
+        let net_assets = math::sub(net_assets, premium)?;
+        vault.total_assets = math::add(vault.total_assets, premium)?;
+        if let Some(min_assets_out) = min_assets_out {
+            require!(net_assets >= min_assets_out, VaultError::SlippageExceeded);
+        }
+        require!(
+            net_assets <= vault.idle_assets(&ctx.accounts.asset_vault),
+            VaultError::InsufficientIdleAssets
+        );
+
+        let receiver_asset = match &ctx.accounts.receiver_asset {
+            Some(receiver_asset) => receiver_asset,
+            None => &ctx.accounts.user_asset,
+        };
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            receiver_asset,
+            &ctx.accounts.token_program,
+            net_assets,
+        )?;
+        if let Some(cost_basis) = &mut ctx.accounts.cost_basis {
+            cost_basis.record_redeem(shares, net_assets)?;
+        }
 
-        emit!(RedeemEvent {
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(InstantRedeemEvent {
             user: ctx.accounts.user.key(),
+            receiver: receiver_asset.owner,
             shares,
             assets: net_assets,
             fee,
+            premium,
+            snapshot,
         });
 
         Ok(())
     }
 
-    /// Meta-redeem: gasless redeem using off-chain signature
-    pub fn meta_redeem(
-        ctx: Context<MetaRedeem>,
-        shares: u64,
-        deadline: i64,
-        signature: [u8; 64],
+    /// Withdraw an exact amount of USDC (net of fee): burn, like `redeem`, the shares
+    /// `preview_withdraw` quotes for it, at most `max_shares_in` of them when given
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        assets: u64,
+        max_shares_in: Option<u64>,
+        adapter_accounts: Vec<u8>,
     ) -> Result<()> {
-        let vault = &ctx.accounts.vault;
-        let clock = Clock::get()?;
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
 
-        require!(clock.unix_timestamp <= deadline, VaultError::DeadlineExpired);
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
 
-        // Verify signature (simplified - production would use ed25519 verify)
-        // In production, verify that signature is valid for:
-        // sign(owner_pubkey, shares, receiver, nonce, deadline)
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
+        position.check_unlocked(now)?;
 
-        let user_nonce = &mut ctx.accounts.user_nonce;
-        user_nonce.nonce = user_nonce.nonce.checked_add(1).unwrap();
+        // Shares grossed up for the fee `burn_for_redeem` charges (round up, in favor of the vault)
+        let fee_exempt = ctx.accounts.fee_exemption.is_some();
+        let shares = vault.preview_withdraw(
+            assets,
+            position,
+            fee_exempt,
+            now,
+            vault.idle_assets(&ctx.accounts.asset_vault),
+        )?;
+        if let Some(max_shares_in) = max_shares_in {
+            require!(shares <= max_shares_in, VaultError::SlippageExceeded);
+        }
 
-        // Call regular redeem logic
-        // (Would need to restructure to share logic)
+        let (net_assets, fee) = burn_for_redeem(
+            vault,
+            position,
+            fee_exempt,
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.share_mint,
+                owner_shares: &ctx.accounts.user_shares,
+                burn_authority: ctx.accounts.user.to_account_info(),
+                asset_vault: &ctx.accounts.asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        // The rounded-up shares are worth at least `assets`; the rest stays in the vault
+        let surplus = math::sub(net_assets, assets)?;
+        vault.total_assets = math::add(vault.total_assets, surplus)?;
+        deallocate_for_redeem(
+            vault,
+            &mut ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.token_program,
+            StrategyItems::new(ctx.remaining_accounts, &adapter_accounts),
+            assets,
+            &EventCpi {
+                authority: ctx.accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.event_authority,
+            },
+        )?;
+
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.user_asset,
+            &ctx.accounts.token_program,
+            assets,
+        )?;
+        if let Some(cost_basis) = &mut ctx.accounts.cost_basis {
+            cost_basis.record_redeem(shares, assets)?;
+        }
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(RedeemEvent {
+            user: ctx.accounts.user.key(),
+            receiver: ctx.accounts.user_asset.owner,
+            shares,
+            assets,
+            fee,
+            snapshot,
+        });
 
         Ok(())
     }
 
-    /// Update fee (governance only)
-    pub fn update_fee(ctx: Context<UpdateFee>, new_fee_bps: u16) -> Result<()> {
-        require!(new_fee_bps <= 100, VaultError::InvalidFee);
-
+    /// Queue `shares` for withdrawal from the current epoch on, for redemptions too large for
+    /// the idle USDC on hand. The shares are burned right away, so they can't be moved while
+    /// queued, but stay in `total_shares` and keep earning until `process_epoch` fills them
+    /// (burning rather than escrowing them keeps the share mint's transfer hook out of it).
+    /// Requesting again in the same epoch adds to the request
+    pub fn request_withdraw(ctx: Context<RequestWithdraw>, shares: u64) -> Result<()> {
         let vault = &mut ctx.accounts.vault;
-        vault.fee_bps = new_fee_bps;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        require!(
+            vault.authority != Pubkey::default(),
+            VaultError::AuthorityRenounced
+        );
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.owner_blocklist,
+            &vault.key(),
+            &ctx.accounts.owner.key(),
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+        require!(
+            shares > 0 && vault.convert_to_assets(shares, Rounding::Floor)? >= vault.min_redeem,
+            VaultError::RedeemTooSmall
+        );
+
+        let position = &mut ctx.accounts.owner_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.owner.key(),
+            ctx.bumps.owner_position,
+        );
+        position.check_unlocked(now)?;
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            from: ctx.accounts.owner_shares.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token_interface::burn(cpi_ctx, shares)?;
 
-        emit!(FeeUpdatedEvent { new_fee_bps });
+        vault.queued_withdraw_shares = math::add(vault.queued_withdraw_shares, shares)?;
+        let request = &mut ctx.accounts.withdraw_request;
+        if request.owner == Pubkey::default() {
+            request.vault = vault.key();
+            request.owner = ctx.accounts.owner.key();
+            request.epoch = vault.withdraw_epoch;
+            request.next_epoch = vault.withdraw_epoch;
+            request.bump = ctx.bumps.withdraw_request;
+        }
+        request.shares = math::add(request.shares, shares)?;
+        request.remaining_shares = math::add(request.remaining_shares, shares)?;
+
+        emit_cpi!(WithdrawRequestedEvent {
+            owner: request.owner,
+            epoch: request.epoch,
+            shares,
+            remaining_shares: request.remaining_shares,
+        });
 
         Ok(())
     }
 
-    /// Emergency withdraw (admin only)
-    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>, amount: u64) -> Result<()> {
-        let vault = &ctx.accounts.vault;
-        let seeds = &[b"vault", &[vault.bump]];
+    /// Take `shares` of a withdrawal request's unfilled shares back out of the queue, minting
+    /// them back to `owner_shares`. Fills of processed epochs have to be claimed first.
+    /// Cancelling all of them closes the request, refunding its rent; cancelling part of them
+    /// leaves the rest queued
+    pub fn cancel_withdraw_request(ctx: Context<CancelWithdrawRequest>, shares: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let request = &mut ctx.accounts.withdraw_request;
+        require!(
+            request.next_epoch == vault.withdraw_epoch,
+            VaultError::WithdrawRequestProcessed
+        );
+        require!(
+            shares > 0 && shares <= request.remaining_shares,
+            VaultError::InvalidCancelAmount
+        );
+
+        let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
         let signer = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.asset_vault.to_account_info(),
-            to: ctx.accounts.admin_asset.to_account_info(),
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            to: ctx.accounts.owner_shares.to_account_info(),
             authority: vault.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        token::transfer(cpi_ctx, amount)?;
+        token_interface::mint_to(cpi_ctx, shares)?;
+
+        // The shares never left `total_shares`, only the queue, which the requests add up to
+        // exactly once their fills are claimed
+        vault.queued_withdraw_shares = math::sub(vault.queued_withdraw_shares, shares)?;
+        request.remaining_shares -= shares;
+
+        emit_cpi!(WithdrawRequestCancelledEvent {
+            owner: request.owner,
+            epoch: request.epoch,
+            shares,
+            remaining_shares: request.remaining_shares,
+        });
+
+        if request.remaining_shares == 0 {
+            request.close(ctx.accounts.owner.to_account_info())?;
+        }
 
         Ok(())
     }
-}
 
-// Accounts
+    /// Settle the current withdrawal epoch (keeper only): the queued shares are redeemed at the
+    /// current exchange rate, less the flat `redeem_fee_bps`, and the USDC is set aside for
+    /// `claim_withdraw`. Strategies are deallocated as in `redeem`, each strategy's
+    /// `STRATEGY_ITEM_ACCOUNTS` accounts, then its `adapter_accounts[i]` adapter-specific
+    /// accounts, following in `remaining_accounts`. When the idle USDC can't pay the whole
+    /// queue, every request is filled in the same proportion and the rest stays queued for the
+    /// next epoch
+    pub fn process_epoch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessEpoch<'info>>,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        let queued = vault.queued_withdraw_shares;
+        require!(queued > 0, VaultError::NoWithdrawRequests);
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + Vault::INIT_SPACE,
-        seeds = [b"vault"],
-        bump
-    )]
-    pub vault: Account<'info, Vault>,
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        // Source what the whole queue needs, keeping the buffer of what would remain
+        let queued_assets = vault.convert_to_assets(queued, Rounding::Floor)?;
+        let payout = math::sub(queued_assets, vault.withdraw_fee(queued_assets)?)?;
+        let target = math::add(payout, vault.liquidity_buffer_after(queued_assets)?)?;
+        source_liquidity(
+            vault,
+            &mut ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.token_program,
+            StrategyItems::new(ctx.remaining_accounts, &adapter_accounts),
+            target,
+            &EventCpi {
+                authority: ctx.accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.event_authority,
+            },
+        )?;
 
-    pub asset_mint: Account<'info, Mint>,
-    pub share_mint: Account<'info, Mint>,
+        // Then fill as much of it as the idle USDC pays for
+        let idle = vault.idle_assets(&ctx.accounts.asset_vault);
+        let (filled_shares, gross_assets) = if payout <= idle {
+            (queued, queued_assets)
+        } else {
+            let filled_shares =
+                math::mul_div(queued, idle as u128, payout as u128, Rounding::Floor)?;
+            (
+                filled_shares,
+                vault.convert_to_assets(filled_shares, Rounding::Floor)?,
+            )
+        };
+        require!(filled_shares > 0, VaultError::InsufficientIdleAssets);
+        // The fee takes any rounding that would pay out more than is idle
+        let assets = math::sub(gross_assets, vault.withdraw_fee(gross_assets)?)?.min(idle);
+        let fee = gross_assets - assets;
 
-    #[account(
-        init,
-        payer = authority,
-        token::mint = asset_mint,
-        token::authority = vault,
-    )]
-    pub asset_vault: Account<'info, TokenAccount>,
+        vault.total_assets = math::sub(vault.total_assets, gross_assets)?;
+        vault.total_shares = math::sub(vault.total_shares, filled_shares)?;
+        vault.accrued_fees = math::add(vault.accrued_fees, fee)?;
+        vault.reserved_withdraw_assets = math::add(vault.reserved_withdraw_assets, assets)?;
+        vault.queued_withdraw_shares = queued - filled_shares;
+        let epoch = vault.withdraw_epoch;
+        vault.withdraw_epoch = math::add(epoch, 1)?;
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        let settlement = &mut ctx.accounts.withdraw_epoch;
+        settlement.vault = vault.key();
+        settlement.epoch = epoch;
+        settlement.shares = queued;
+        settlement.filled_shares = filled_shares;
+        settlement.assets = assets;
+        settlement.unclaimed_assets = assets;
+        settlement.unclaimed_shares = queued;
+        settlement.unclaimed_filled_shares = filled_shares;
+        settlement.processed_ts = now;
+        settlement.bump = ctx.bumps.withdraw_epoch;
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut, seeds = [b"vault"], bump = vault.bump)]
-    pub vault: Account<'info, Vault>,
+        emit_cpi!(EpochProcessedEvent {
+            epoch,
+            shares: queued,
+            filled_shares,
+            assets,
+            fee,
+        });
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::ProcessEpoch {
+                epoch,
+                filled_shares,
+                assets,
+            },
+        )?);
 
-    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
-    pub user_asset: Account<'info, TokenAccount>,
+        Ok(())
+    }
 
-    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
-    pub user_shares: Account<'info, TokenAccount>,
+    /// Pay out a withdrawal request's fills in the processed epochs passed as writable
+    /// `remaining_accounts`, in order from the request's `next_epoch`; any number of them can
+    /// be claimed at a time. The request closes once fully filled. Claims aren't subject to
+    /// the pause flags: the USDC is already outside `total_assets`
+    pub fn claim_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimWithdraw<'info>>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.owner_blocklist,
+            &vault.key(),
+            &ctx.accounts.owner.key(),
+        )?;
+        require!(
+            !ctx.remaining_accounts.is_empty(),
+            VaultError::InvalidWithdrawEpoch
+        );
 
-    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
-    pub asset_vault: Account<'info, TokenAccount>,
+        let request = &mut ctx.accounts.withdraw_request;
+        let (mut shares, mut assets) = (0u64, 0u64);
+        for info in ctx.remaining_accounts {
+            let mut settlement: Account<WithdrawEpoch> = Account::try_from(info)?;
+            require_keys_eq!(settlement.vault, vault.key(), ErrorCode::ConstraintSeeds);
+            require!(
+                settlement.epoch == request.next_epoch,
+                VaultError::InvalidWithdrawEpoch
+            );
+            let (filled, paid) = settlement.claim(request.remaining_shares)?;
+            settlement.exit(&crate::ID)?;
 
-    #[account(mut, address = vault.share_mint)]
-    pub share_mint: Account<'info, Mint>,
+            request.remaining_shares -= filled;
+            request.next_epoch += 1;
+            shares = math::add(shares, filled)?;
+            assets = math::add(assets, paid)?;
+            if request.remaining_shares == 0 {
+                break;
+            }
+        }
+        request.filled_assets = math::add(request.filled_assets, assets)?;
+        vault.reserved_withdraw_assets = math::sub(vault.reserved_withdraw_assets, assets)?;
 
-    pub token_program: Program<'info, Token>,
-}
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.owner_asset,
+            &ctx.accounts.token_program,
+            assets,
+        )?;
 
-#[derive(Accounts)]
-pub struct Redeem<'info> {
-    #[account(mut, seeds = [b"vault"], bump = vault.bump)]
-    pub vault: Account<'info, Vault>,
+        emit_cpi!(WithdrawClaimedEvent {
+            owner: request.owner,
+            epoch: request.epoch,
+            shares,
+            assets,
+            remaining_shares: request.remaining_shares,
+            filled_assets: request.filled_assets,
+        });
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+        if request.remaining_shares == 0 {
+            request.close(ctx.accounts.owner.to_account_info())?;
+        }
 
-    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
-    pub user_asset: Account<'info, TokenAccount>,
+        Ok(())
+    }
 
-    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
-    pub user_shares: Account<'info, TokenAccount>,
+    /// Accept `mint` as a basket asset: a stablecoin deposited alongside USDC, valued with the
+    /// Pyth `price_update` account of the feed `feed_id` and held in its own vault-owned token
+    /// account. Prices older than `max_price_age` or with a confidence interval wider than
+    /// `max_conf_bps` of the price are refused, and the vault holds at most `cap` of it, in its
+    /// base units (governance only)
+    pub fn add_basket_asset(
+        ctx: Context<AddBasketAsset>,
+        feed_id: [u8; 32],
+        max_price_age: u32,
+        max_conf_bps: u16,
+        cap: u64,
+    ) -> Result<()> {
+        require!(
+            max_price_age > 0 && (1..=10_000).contains(&max_conf_bps),
+            VaultError::InvalidBasketAsset
+        );
+        extensions::check_asset_mint(&ctx.accounts.mint.to_account_info(), false)?;
+        // Refuse a price account of another feed up front
+        oracle::UsdPrice::load_feed(&ctx.accounts.price_update, &feed_id)?;
 
-    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
-    pub asset_vault: Account<'info, TokenAccount>,
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            vault.basket_asset_count < MAX_BASKET_ASSETS,
+            VaultError::TooManyBasketAssets
+        );
+        vault.basket_asset_count += 1;
 
-    #[account(mut, address = vault.share_mint)]
-    pub share_mint: Account<'info, Mint>,
+        let basket_asset = &mut ctx.accounts.basket_asset;
+        basket_asset.vault = vault.key();
+        basket_asset.mint = ctx.accounts.mint.key();
+        basket_asset.token_account = ctx.accounts.basket_vault.key();
+        basket_asset.price_feed = ctx.accounts.price_update.key();
+        basket_asset.feed_id = feed_id;
+        basket_asset.max_price_age = max_price_age;
+        basket_asset.max_conf_bps = max_conf_bps;
+        basket_asset.decimals = ctx.accounts.mint.decimals;
+        basket_asset.value_decimals = ctx.accounts.asset_mint.decimals;
+        basket_asset.cap = cap;
+        basket_asset.balance = 0;
+        basket_asset.value = 0;
+        basket_asset.bump = ctx.bumps.basket_asset;
 
-    pub token_program: Program<'info, Token>,
-}
+        emit_cpi!(BasketAssetAddedEvent {
+            mint: basket_asset.mint,
+            price_feed: basket_asset.price_feed,
+            max_price_age,
+            cap,
+            max_conf_bps,
+        });
 
-#[derive(Accounts)]
-pub struct MetaRedeem<'info> {
-    #[account(seeds = [b"vault"], bump = vault.bump)]
-    pub vault: Account<'info, Vault>,
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::AddBasketAsset {
+                mint: basket_asset.mint,
+                max_price_age,
+                cap,
+                max_conf_bps,
+            },
+        )?);
 
-    /// CHECK: Owner of shares (verified by signature)
-    pub owner: UncheckedAccount<'info>,
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub relayer: Signer<'info>,
+    /// Change a basket asset's oldest accepted price, widest accepted confidence interval and
+    /// its cap; lowering the cap below the balance only stops deposits (governance only)
+    pub fn update_basket_asset(
+        ctx: Context<UpdateBasketAsset>,
+        max_price_age: u32,
+        max_conf_bps: u16,
+        cap: u64,
+    ) -> Result<()> {
+        require!(
+            max_price_age > 0 && (1..=10_000).contains(&max_conf_bps),
+            VaultError::InvalidBasketAsset
+        );
+        let basket_asset = &mut ctx.accounts.basket_asset;
+        basket_asset.max_price_age = max_price_age;
+        basket_asset.max_conf_bps = max_conf_bps;
+        basket_asset.cap = cap;
 
-    #[account(
-        init_if_needed,
-        payer = relayer,
-        space = 8 + UserNonce::INIT_SPACE,
-        seeds = [b"nonce", owner.key().as_ref()],
-        bump
-    )]
-    pub user_nonce: Account<'info, UserNonce>,
+        emit_cpi!(BasketAssetUpdatedEvent {
+            mint: basket_asset.mint,
+            max_price_age,
+            cap,
+            max_conf_bps,
+        });
 
-    pub system_program: Program<'info, System>,
-}
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::UpdateBasketAsset {
+                mint: basket_asset.mint,
+                max_price_age,
+                cap,
+                max_conf_bps,
+            },
+        )?);
 
-#[derive(Accounts)]
-pub struct UpdateFee<'info> {
-    #[account(mut, seeds = [b"vault"], bump = vault.bump, has_one = authority)]
-    pub vault: Account<'info, Vault>,
+        Ok(())
+    }
 
-    pub authority: Signer<'info>,
-}
+    /// Book a basket asset's balance in `total_assets` at its current price (permissionless).
+    /// Basket deposits and redemptions do this first too
+    pub fn revalue_basket_asset(ctx: Context<RevalueBasketAsset>) -> Result<()> {
+        let basket_asset = &mut ctx.accounts.basket_asset;
+        let price =
+            basket_asset.load_price(&ctx.accounts.price_update, Clock::get()?.unix_timestamp)?;
+        ctx.accounts
+            .vault
+            .revalue_basket_asset(basket_asset, &price)?;
 
-#[derive(Accounts)]
-pub struct EmergencyWithdraw<'info> {
-    #[account(seeds = [b"vault"], bump = vault.bump, has_one = authority)]
-    pub vault: Account<'info, Vault>,
+        emit_cpi!(BasketAssetRevaluedEvent {
+            mint: basket_asset.mint,
+            price: price.price,
+            exponent: price.exponent,
+            balance: basket_asset.balance,
+            value: basket_asset.value,
+        });
 
-    pub authority: Signer<'info>,
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub asset_vault: Account<'info, TokenAccount>,
+    /// Deposit `amount` of a basket asset and mint shares to the user for its value at the
+    /// current Pyth price, net of the deposit fee. The fee stays in the basket, and so in
+    /// `total_assets`, since `collect_fees` only pays out USDC
+    pub fn deposit_basket(
+        ctx: Context<DepositBasket>,
+        amount: u64,
+        min_shares_out: Option<u64>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_deposits, VaultError::DepositsPaused);
+        vault.check_deposit_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
 
-    #[account(mut)]
-    pub admin_asset: Account<'info, TokenAccount>,
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+        let basket_asset = &mut ctx.accounts.basket_asset;
+        let price = basket_asset.load_price(&ctx.accounts.price_update, now)?;
+        vault.revalue_basket_asset(basket_asset, &price)?;
 
-    pub token_program: Program<'info, Token>,
-}
+        let received = math::sub(
+            amount,
+            extensions::transfer_fee(&ctx.accounts.mint.to_account_info(), amount)?,
+        )?;
+        let balance = math::add(basket_asset.balance, received)?;
+        require!(
+            balance <= basket_asset.cap,
+            VaultError::BasketAssetCapExceeded
+        );
+        let value = basket_asset.value_of(&price, received)?;
+        require!(value >= vault.min_deposit, VaultError::DepositTooSmall);
 
-// State
+        let fee = vault.deposit_fee(value)?;
+        let dead_shares = vault.pending_dead_shares();
+        let minted = vault.convert_to_shares(math::sub(value, fee)?, Rounding::Floor)?;
+        require!(minted > dead_shares, VaultError::DepositTooSmall);
+        let shares = math::sub(minted, dead_shares)?;
+        if let Some(min_shares_out) = min_shares_out {
+            require!(shares >= min_shares_out, VaultError::SlippageExceeded);
+        }
+        require!(
+            math::add(vault.total_assets, value)? <= vault.deposit_cap,
+            VaultError::DepositCapExceeded
+        );
 
-#[account]
-#[derive(InitSpace)]
-pub struct Vault {
-    pub authority: Pubkey,
-    pub asset_mint: Pubkey,
-    pub share_mint: Pubkey,
-    pub asset_vault: Pubkey,
-    pub total_assets: u64,
-    pub total_shares: u64,
-    pub fee_bps: u16,
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
+        position.deposited_assets = math::add(position.deposited_assets, value)?;
+        require!(
+            position.deposited_assets <= vault.max_per_user,
+            VaultError::UserCapExceeded
+        );
+        position.extend_lockup(now, vault.lockup_seconds)?;
+        position.last_deposit_ts = now;
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.user_token.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.basket_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.basket_token_program.to_account_info();
+        token_interface::transfer_checked(
+            CpiContext::new(cpi_program, cpi_accounts),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+        let signer = &[&seeds[..]];
+        for (to, amount) in [
+            (ctx.accounts.user_shares.to_account_info(), shares),
+            (
+                ctx.accounts.dead_shares_account.to_account_info(),
+                dead_shares,
+            ),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            let cpi_accounts = MintTo {
+                mint: ctx.accounts.share_mint.to_account_info(),
+                to,
+                authority: vault.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::mint_to(cpi_ctx, amount)?;
+        }
+
+        basket_asset.balance = balance;
+        basket_asset.value = math::add(basket_asset.value, value)?;
+        vault.basket_value = math::add(vault.basket_value, value)?;
+        vault.total_assets = math::add(vault.total_assets, value)?;
+        vault.total_shares = math::add(vault.total_shares, minted)?;
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(BasketDepositEvent {
+            user: ctx.accounts.user.key(),
+            mint: basket_asset.mint,
+            amount,
+            value,
+            shares,
+            fee,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Redeem `shares` for a basket asset of the user's choice, at its current Pyth price and
+    /// with the fees of `redeem` (the fee is set aside in USDC)
+    pub fn redeem_basket(
+        ctx: Context<RedeemBasket>,
+        shares: u64,
+        min_amount_out: Option<u64>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+        let basket_asset = &mut ctx.accounts.basket_asset;
+        let price = basket_asset.load_price(&ctx.accounts.price_update, now)?;
+        vault.revalue_basket_asset(basket_asset, &price)?;
+
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
+        position.check_unlocked(now)?;
+
+        let (value, fee) = burn_for_redeem(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.share_mint,
+                owner_shares: &ctx.accounts.user_shares,
+                burn_authority: ctx.accounts.user.to_account_info(),
+                asset_vault: &ctx.accounts.asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        let amount = pay_basket_asset(
+            vault,
+            basket_asset,
+            &price,
+            value,
+            BasketTokenAccounts {
+                mint: &ctx.accounts.mint,
+                basket_vault: &ctx.accounts.basket_vault,
+                receiver: &ctx.accounts.user_token,
+                token_program: &ctx.accounts.basket_token_program,
+            },
+        )?;
+        if let Some(min_amount_out) = min_amount_out {
+            require!(amount >= min_amount_out, VaultError::SlippageExceeded);
+        }
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(BasketRedeemEvent {
+            user: ctx.accounts.user.key(),
+            mint: basket_asset.mint,
+            shares,
+            amount,
+            value,
+            fee,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Redeem `shares` for a slice of every asset the vault holds, in proportion to their part
+    /// of `total_assets`: the basket assets at their current Pyth prices and the rest in idle
+    /// USDC, with the fees of `redeem`. Each basket asset's `BASKET_ITEM_ACCOUNTS` accounts
+    /// follow in `remaining_accounts`, all of them, in ascending `BasketAsset` address order
+    pub fn redeem_proportional<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemProportional<'info>>,
+        shares: u64,
+        min_value_out: Option<u64>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+        require!(
+            ctx.remaining_accounts.len()
+                == usize::from(vault.basket_asset_count) * BASKET_ITEM_ACCOUNTS,
+            VaultError::MissingBasketAssets
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        // Book every basket asset at its current price before pricing the shares
+        let mut items = Vec::with_capacity(usize::from(vault.basket_asset_count));
+        let mut previous = Pubkey::default();
+        for item in ctx.remaining_accounts.chunks(BASKET_ITEM_ACCOUNTS) {
+            let mut basket_asset: Account<BasketAsset> = Account::try_from(&item[0])?;
+            require_keys_eq!(basket_asset.vault, vault.key(), ErrorCode::ConstraintHasOne);
+            require!(
+                basket_asset.key() > previous,
+                VaultError::MissingBasketAssets
+            );
+            previous = basket_asset.key();
+            require_keys_eq!(
+                item[1].key(),
+                basket_asset.price_feed,
+                ErrorCode::ConstraintAddress
+            );
+            let price = basket_asset.load_price(&item[1], now)?;
+            vault.revalue_basket_asset(&mut basket_asset, &price)?;
+            items.push((basket_asset, price, item));
+        }
+        let total_assets = vault.total_assets;
+
+        let position = &mut ctx.accounts.user_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+        );
+        position.check_unlocked(now)?;
+
+        let (value, fee) = burn_for_redeem(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.share_mint,
+                owner_shares: &ctx.accounts.user_shares,
+                burn_authority: ctx.accounts.user.to_account_info(),
+                asset_vault: &ctx.accounts.asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(min_value_out) = min_value_out {
+            require!(value >= min_value_out, VaultError::SlippageExceeded);
+        }
+
+        // Each basket asset pays its part of the value; USDC pays the rest
+        let mut basket_amounts = Vec::with_capacity(items.len());
+        let mut assets = value;
+        for (mut basket_asset, price, item) in items {
+            let part = math::mul_div(
+                value,
+                basket_asset.value as u128,
+                total_assets as u128,
+                Rounding::Floor,
+            )?;
+            let mint = InterfaceAccount::<Mint>::try_from(&item[2])?;
+            let basket_vault = InterfaceAccount::<TokenAccount>::try_from(&item[3])?;
+            let receiver = InterfaceAccount::<TokenAccount>::try_from(&item[4])?;
+            let token_program = Interface::<TokenInterface>::try_from(&item[5])?;
+            require_keys_eq!(mint.key(), basket_asset.mint, ErrorCode::ConstraintAddress);
+            require_keys_eq!(
+                basket_vault.key(),
+                basket_asset.token_account,
+                ErrorCode::ConstraintAddress
+            );
+            let amount = pay_basket_asset(
+                vault,
+                &mut basket_asset,
+                &price,
+                part,
+                BasketTokenAccounts {
+                    mint: &mint,
+                    basket_vault: &basket_vault,
+                    receiver: &receiver,
+                    token_program: &token_program,
+                },
+            )?;
+            basket_asset.exit(&crate::ID)?;
+            basket_amounts.push(amount);
+            assets -= part;
+        }
+        require!(
+            assets <= vault.idle_assets(&ctx.accounts.asset_vault),
+            VaultError::InsufficientIdleAssets
+        );
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.user_asset,
+            &ctx.accounts.token_program,
+            assets,
+        )?;
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(ProportionalRedeemEvent {
+            user: ctx.accounts.user.key(),
+            shares,
+            assets,
+            basket_amounts,
+            value,
+            fee,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Attach the junior tranche's share mint and set the waterfall (governance only). The
+    /// senior tranche (`share_mint`) keeps `senior_yield_bps` of the yield on its assets and
+    /// passes the rest to the junior tranche, which also earns its own pro-rata yield and absorbs
+    /// losses first. Junior redemptions must leave it at `min_subordination_bps` of
+    /// `total_assets`. The mint is fixed once set
+    pub fn set_junior_tranche(
+        ctx: Context<SetJuniorTranche>,
+        senior_yield_bps: u16,
+        min_subordination_bps: u16,
+    ) -> Result<()> {
+        require!(
+            senior_yield_bps <= 10_000 && min_subordination_bps <= 10_000,
+            VaultError::InvalidTrancheConfig
+        );
+        let vault = &mut ctx.accounts.vault;
+        let junior_share_mint = &ctx.accounts.junior_share_mint;
+        if vault.junior_share_mint == Pubkey::default() {
+            require!(
+                junior_share_mint.supply == 0 && junior_share_mint.key() != vault.share_mint,
+                VaultError::InvalidJuniorShareMint
+            );
+            vault.junior_share_mint = junior_share_mint.key();
+        }
+        vault.senior_yield_bps = senior_yield_bps;
+        vault.min_subordination_bps = min_subordination_bps;
+
+        emit_cpi!(JuniorTrancheUpdatedEvent {
+            junior_share_mint: vault.junior_share_mint,
+            senior_yield_bps,
+            min_subordination_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetJuniorTranche {
+                junior_share_mint: vault.junior_share_mint,
+                senior_yield_bps,
+                min_subordination_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Deposit USDC into the junior tranche, minting junior shares priced on `junior_assets`.
+    /// No deposit fee, position or lockup applies
+    pub fn deposit_junior(
+        ctx: Context<DepositJunior>,
+        assets: u64,
+        min_shares_out: Option<u64>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_deposits, VaultError::DepositsPaused);
+        vault.check_deposit_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+        require!(assets >= vault.min_deposit, VaultError::DepositTooSmall);
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        let received = math::sub(
+            assets,
+            extensions::transfer_fee(&ctx.accounts.asset_mint.to_account_info(), assets)?,
+        )?;
+        let shares = vault.convert_junior_to_shares(received, Rounding::Floor)?;
+        require!(shares > 0, VaultError::DepositTooSmall);
+        if let Some(min_shares_out) = min_shares_out {
+            require!(shares >= min_shares_out, VaultError::SlippageExceeded);
+        }
+        require!(
+            math::add(vault.total_assets, received)? <= vault.deposit_cap,
+            VaultError::DepositCapExceeded
+        );
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.user_asset.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.asset_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token_interface::transfer_checked(
+            CpiContext::new(cpi_program, cpi_accounts),
+            assets,
+            ctx.accounts.asset_mint.decimals,
+        )?;
+
+        let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.junior_share_mint.to_account_info(),
+            to: ctx.accounts.user_junior_shares.to_account_info(),
+            authority: vault.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::mint_to(cpi_ctx, shares)?;
+
+        vault.total_assets = math::add(vault.total_assets, received)?;
+        vault.junior_assets = math::add(vault.junior_assets, received)?;
+        vault.junior_shares = math::add(vault.junior_shares, shares)?;
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(JuniorDepositEvent {
+            user: ctx.accounts.user.key(),
+            assets,
+            shares,
+            junior_assets: vault.junior_assets,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Burn junior shares for their part of `junior_assets`, paid from idle USDC. Refused when
+    /// it would leave the junior tranche below `min_subordination_bps` of `total_assets`
+    pub fn redeem_junior(
+        ctx: Context<RedeemJunior>,
+        shares: u64,
+        min_assets_out: Option<u64>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        let assets = vault.convert_junior_to_assets(shares, Rounding::Floor)?;
+        require!(assets >= vault.min_redeem, VaultError::RedeemTooSmall);
+        if let Some(min_assets_out) = min_assets_out {
+            require!(assets >= min_assets_out, VaultError::SlippageExceeded);
+        }
+        require!(
+            assets <= vault.idle_assets(&ctx.accounts.asset_vault),
+            VaultError::InsufficientIdleAssets
+        );
+
+        vault.total_assets = math::sub(vault.total_assets, assets)?;
+        vault.junior_assets = math::sub(vault.junior_assets, assets)?;
+        vault.junior_shares = math::sub(vault.junior_shares, shares)?;
+        vault.check_subordination()?;
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.junior_share_mint.to_account_info(),
+            from: ctx.accounts.user_junior_shares.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token_interface::burn(CpiContext::new(cpi_program, cpi_accounts), shares)?;
+
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.user_asset,
+            &ctx.accounts.token_program,
+            assets,
+        )?;
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(JuniorRedeemEvent {
+            user: ctx.accounts.user.key(),
+            shares,
+            assets,
+            junior_assets: vault.junior_assets,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Name the vault positions may move to with `migrate_position`, such as its v2 successor
+    /// holding the same asset (governance only). Without `target_vault`, migration is closed
+    pub fn set_migration_target(ctx: Context<SetMigrationTarget>) -> Result<()> {
+        let target = ctx
+            .accounts
+            .target_vault
+            .as_ref()
+            .map_or(Pubkey::default(), |target_vault| target_vault.key());
+        ctx.accounts.vault.migration_target = target;
+
+        emit_cpi!(MigrationTargetUpdatedEvent { target });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetMigrationTarget { target },
+        )?);
+
+        Ok(())
+    }
+
+    /// Move `shares` of the caller's position to the source vault's `migration_target` in one
+    /// instruction: the shares are burned, their USDC moves straight from the source vault's
+    /// `asset_vault` to the target's, and the target vault mints its shares for it. Both vaults'
+    /// fees are waived, since governance opted into the move, but the target position takes
+    /// over the source lockup and deposit time, so its lockup, cooldown and early-exit penalty
+    /// still run from the original deposit; strategies are deallocated as for `redeem`
+    pub fn migrate_position<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigratePosition<'info>>,
+        shares: u64,
+        min_shares_out: Option<u64>,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        let user = ctx.accounts.user.key();
+        let source = &mut ctx.accounts.source_vault;
+        require!(!source.paused_redeems, VaultError::RedeemsPaused);
+        source.check_redeem_allowed(ctx.accounts.source_allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(&ctx.accounts.source_blocklist, &source.key(), &user)?;
+        let target = &ctx.accounts.target_vault;
+        require!(!target.paused_deposits, VaultError::DepositsPaused);
+        target.check_deposit_allowed(ctx.accounts.target_allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(&ctx.accounts.target_blocklist, &target.key(), &user)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        source.accrue_management_fee(now)?;
+        source.unlock_profit(now)?;
+
+        let position = &mut ctx.accounts.source_position;
+        position.init_if_new(source.key(), user, ctx.bumps.source_position);
+
+        let (assets, _) = burn_for_redeem(
+            source,
+            position,
+            true,
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.source_share_mint,
+                owner_shares: &ctx.accounts.user_source_shares,
+                burn_authority: ctx.accounts.user.to_account_info(),
+                asset_vault: &ctx.accounts.source_asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        deallocate_for_redeem(
+            source,
+            &mut ctx.accounts.source_asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.token_program,
+            StrategyItems::new(ctx.remaining_accounts, &adapter_accounts),
+            assets,
+            &EventCpi {
+                authority: ctx.accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.event_authority,
+            },
+        )?;
+
+        let target = &mut ctx.accounts.target_vault;
+        target.accrue_management_fee(now)?;
+        target.unlock_profit(now)?;
+
+        let position = &mut ctx.accounts.target_position;
+        position.init_if_new(target.key(), user, ctx.bumps.target_position);
+        let (locked_until, last_deposit_ts) = (position.locked_until, position.last_deposit_ts);
+
+        let source = &ctx.accounts.source_vault;
+        let source_seeds = &[b"vault".as_ref(), source.address_seed(), &[source.bump]];
+        let (target_shares, _) = deposit_for_shares(
+            target,
+            position,
+            true,
+            assets,
+            None,
+            now,
+            DepositTokenAccounts {
+                source_asset: &ctx.accounts.source_asset_vault,
+                transfer_authority: source.to_account_info(),
+                transfer_seeds: source_seeds,
+                asset_vault: &ctx.accounts.target_asset_vault,
+                asset_mint: &ctx.accounts.asset_mint,
+                share_mint: &ctx.accounts.target_share_mint,
+                receiver_shares: &ctx.accounts.user_target_shares,
+                dead_shares_account: &ctx.accounts.target_dead_shares_account,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(min_shares_out) = min_shares_out {
+            require!(
+                target_shares >= min_shares_out,
+                VaultError::SlippageExceeded
+            );
+        }
+        // Rather than restarting at the migration, as for a deposit
+        let source_position = &ctx.accounts.source_position;
+        let position = &mut ctx.accounts.target_position;
+        position.locked_until = locked_until.max(source_position.locked_until);
+        position.last_deposit_ts = last_deposit_ts.max(source_position.last_deposit_ts);
+
+        let source_snapshot = ctx.accounts.source_vault.snapshot()?;
+        let target_snapshot = ctx.accounts.target_vault.snapshot()?;
+        emit_cpi!(PositionMigratedEvent {
+            user,
+            source_vault: ctx.accounts.source_vault.key(),
+            target_vault: ctx.accounts.target_vault.key(),
+            shares,
+            assets,
+            target_shares,
+            source_snapshot,
+            target_snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Gasless deposit: a relayer submits an owner-signed deposit, pulling the owner's USDC
+    /// through the vault PDA (pre-approved as delegate) and minting shares to `receiver`. The
+    /// relayer pays the transaction fees and any account rent
+    pub fn meta_deposit(
+        ctx: Context<MetaDeposit>,
+        assets: u64,
+        deadline: i64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!vault.paused_meta, VaultError::MetaPaused);
+        require!(!vault.paused_deposits, VaultError::DepositsPaused);
+        vault.check_deposit_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        vault.check_relayer_allowed(ctx.accounts.relayer_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.owner_blocklist,
+            &vault.key(),
+            &ctx.accounts.owner.key(),
+        )?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.relayer_blocklist,
+            &vault.key(),
+            &ctx.accounts.relayer.key(),
+        )?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.receiver_blocklist,
+            &vault.key(),
+            &ctx.accounts.receiver.key(),
+        )?;
+        require!(now <= deadline, VaultError::DeadlineExpired);
+        require!(assets >= vault.min_deposit, VaultError::DepositTooSmall);
+
+        // The owner's signature is checked by a preceding Ed25519 program instruction
+        let user_nonce = &mut ctx.accounts.user_nonce;
+        let nonce = user_nonce.nonce;
+        let message = MetaMessage {
+            domain: vault.domain(vault.key()),
+            owner: ctx.accounts.owner.key(),
+            nonce,
+            deadline,
+            action: MetaAction::Deposit {
+                assets,
+                receiver: ctx.accounts.receiver.key(),
+            },
+        }
+        .signing_bytes();
+        signature::verify_ed25519_ix(
+            &ctx.accounts.instructions,
+            &ctx.accounts.owner.key(),
+            &message,
+            &signature,
+        )?;
+        user_nonce.nonce = math::add(user_nonce.nonce, 1)?;
+
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        let position = &mut ctx.accounts.receiver_position;
+        position.init_if_new(
+            vault.key(),
+            ctx.accounts.receiver.key(),
+            ctx.bumps.receiver_position,
+        );
+
+        let vault_info = vault.to_account_info();
+        let (shares, fee) = deposit_for_shares(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            assets,
+            None,
+            now,
+            DepositTokenAccounts {
+                source_asset: &ctx.accounts.owner_asset,
+                transfer_authority: vault_info,
+                transfer_seeds: &[],
+                asset_vault: &ctx.accounts.asset_vault,
+                asset_mint: &ctx.accounts.asset_mint,
+                share_mint: &ctx.accounts.share_mint,
+                receiver_shares: &ctx.accounts.receiver_shares,
+                dead_shares_account: &ctx.accounts.dead_shares_account,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(relayer_entry) = &mut ctx.accounts.relayer_entry {
+            relayer_entry.record_volume(now, assets)?;
+        }
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(MetaDepositEvent {
+            owner: ctx.accounts.owner.key(),
+            receiver: ctx.accounts.receiver.key(),
+            relayer: ctx.accounts.relayer.key(),
+            assets,
+            shares,
+            fee,
+            nonce,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Meta-redeem: gasless redeem using off-chain signature. Strategies are deallocated as for
+    /// `redeem`
+    #[allow(clippy::too_many_arguments)]
+    pub fn meta_redeem<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MetaRedeem<'info>>,
+        shares: u64,
+        receiver: Pubkey,
+        deadline: i64,
+        max_relayer_fee: u64,
+        relayer_fee: u64,
+        signature: [u8; 64],
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        process_meta_redeem(
+            ctx,
+            shares,
+            receiver,
+            deadline,
+            max_relayer_fee,
+            relayer_fee,
+            OwnerSignature::Ed25519(signature),
+            adapter_accounts,
+        )
+    }
+
+    /// Meta-redeem signed by the owner's linked Ethereum address: like `meta_redeem`, but
+    /// authorized by a preceding secp256k1 program instruction over the EIP-191 message
+    pub fn meta_redeem_evm<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MetaRedeem<'info>>,
+        shares: u64,
+        receiver: Pubkey,
+        deadline: i64,
+        max_relayer_fee: u64,
+        relayer_fee: u64,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        process_meta_redeem(
+            ctx,
+            shares,
+            receiver,
+            deadline,
+            max_relayer_fee,
+            relayer_fee,
+            OwnerSignature::Secp256k1,
+            adapter_accounts,
+        )
+    }
+
+    /// Process several owner-signed meta-redeems in one transaction, all or nothing. Each
+    /// request's accounts follow in `remaining_accounts` (`BATCH_ITEM_ACCOUNTS` per request, the
+    /// program id standing for an absent fee exemption or allowlist entry), and its Ed25519
+    /// verify instruction precedes ours, in request order. Relayer fees are paid out once. Without
+    /// strategy accounts of its own, the batch only pays out of idle USDC
+    pub fn batch_meta_redeem<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchMetaRedeem<'info>>,
+        requests: Vec<MetaRedeemRequest>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!vault.paused_meta, VaultError::MetaPaused);
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        require!(
+            !requests.is_empty()
+                && ctx.remaining_accounts.len() == requests.len() * BATCH_ITEM_ACCOUNTS,
+            ErrorCode::AccountNotEnoughKeys
+        );
+        vault.check_relayer_allowed(ctx.accounts.relayer_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.relayer_blocklist,
+            &vault.key(),
+            &ctx.accounts.relayer.key(),
+        )?;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        // Request i is verified by the instruction `requests.len() - i` before this one
+        let current = sysvar::instructions::load_current_index_checked(&ctx.accounts.instructions)?;
+        let first_verify_ix = current
+            .checked_sub(requests.len() as u16)
+            .ok_or(VaultError::InvalidSignature)?;
+
+        let events = EventCpi {
+            authority: ctx.accounts.event_authority.to_account_info(),
+            bump: ctx.bumps.event_authority,
+        };
+        let mut volume = 0;
+        let mut relayer_fee = 0;
+        for (i, (request, item)) in requests
+            .iter()
+            .zip(ctx.remaining_accounts.chunks(BATCH_ITEM_ACCOUNTS))
+            .enumerate()
+        {
+            let net_assets = batch_meta_redeem_item(
+                ctx.accounts,
+                item,
+                request,
+                first_verify_ix + i as u16,
+                relayer_fee,
+                now,
+                &events,
+            )?;
+            volume = math::add(volume, net_assets)?;
+            relayer_fee = math::add(relayer_fee, request.relayer_fee)?;
+        }
+
+        if let Some(relayer_entry) = &mut ctx.accounts.relayer_entry {
+            relayer_entry.record_volume(now, volume)?;
+        }
+        transfer_from_vault(
+            &ctx.accounts.vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.relayer_asset,
+            &ctx.accounts.token_program,
+            relayer_fee,
+        )?;
+
+        emit_cpi!(BatchMetaRedeemEvent {
+            relayer: ctx.accounts.relayer.key(),
+            count: requests.len() as u32,
+            relayer_fee,
+        });
+
+        Ok(())
+    }
+
+    /// Link an Ethereum address whose EIP-191 signatures may authorize the caller's
+    /// meta-transactions (`meta_redeem_evm`)
+    pub fn link_evm_address(ctx: Context<LinkEvmAddress>, eth_address: [u8; 20]) -> Result<()> {
+        let evm_owner = &mut ctx.accounts.evm_owner;
+        evm_owner.vault = ctx.accounts.vault.key();
+        evm_owner.owner = ctx.accounts.owner.key();
+        evm_owner.eth_address = eth_address;
+        evm_owner.bump = ctx.bumps.evm_owner;
+
+        emit_cpi!(EvmAddressLinkedEvent {
+            owner: ctx.accounts.owner.key(),
+            eth_address,
+            linked: true,
+        });
+
+        Ok(())
+    }
+
+    /// Remove the caller's linked Ethereum address, closing its PDA
+    pub fn unlink_evm_address(ctx: Context<UnlinkEvmAddress>) -> Result<()> {
+        emit_cpi!(EvmAddressLinkedEvent {
+            owner: ctx.accounts.owner.key(),
+            eth_address: ctx.accounts.evm_owner.eth_address,
+            linked: false,
+        });
+
+        Ok(())
+    }
+
+    /// Invalidate every outstanding meta-transaction signature of the owner by bumping its nonce
+    pub fn cancel_nonce(ctx: Context<CancelNonce>) -> Result<()> {
+        let user_nonce = &mut ctx.accounts.user_nonce;
+        let cancelled_nonce = user_nonce.nonce;
+        user_nonce.nonce = math::add(user_nonce.nonce, 1)?;
+
+        emit_cpi!(NonceCancelledEvent {
+            owner: ctx.accounts.owner.key(),
+            cancelled_nonce,
+        });
+
+        Ok(())
+    }
+
+    /// Register a temporary key that may redeem the owner's shares until `expires_at`, paying
+    /// out at most `max_assets` of USDC in total, and only to the owner's own USDC account
+    pub fn create_session_key(
+        ctx: Context<CreateSessionKey>,
+        session_key: Pubkey,
+        max_assets: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(
+            expires_at > Clock::get()?.unix_timestamp,
+            VaultError::SessionExpired
+        );
+
+        let session = &mut ctx.accounts.session;
+        session.vault = ctx.accounts.vault.key();
+        session.owner = ctx.accounts.owner.key();
+        session.session_key = session_key;
+        session.remaining_assets = max_assets;
+        session.expires_at = expires_at;
+        session.bump = ctx.bumps.session;
+
+        emit_cpi!(SessionKeyCreatedEvent {
+            owner: ctx.accounts.owner.key(),
+            session_key,
+            max_assets,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a session key before it expires, closing its PDA
+    pub fn revoke_session_key(ctx: Context<RevokeSessionKey>) -> Result<()> {
+        emit_cpi!(SessionKeyRevokedEvent {
+            owner: ctx.accounts.owner.key(),
+            session_key: ctx.accounts.session.session_key,
+        });
+
+        Ok(())
+    }
+
+    /// Redeem signed by a session key on the owner's behalf: the vault PDA burns the owner's
+    /// shares as approved delegate and pays the USDC to the owner, drawing down the session limit.
+    /// Strategies are deallocated as for `redeem`
+    pub fn session_redeem<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SessionRedeem<'info>>,
+        shares: u64,
+        min_assets_out: Option<u64>,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+        vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.owner_blocklist,
+            &vault.key(),
+            &ctx.accounts.owner.key(),
+        )?;
+        let session = &mut ctx.accounts.session;
+        require!(now <= session.expires_at, VaultError::SessionExpired);
+
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        let position = &ctx.accounts.owner_position;
+        position.check_unlocked(now)?;
+
+        let vault_info = vault.to_account_info();
+        let (net_assets, fee) = burn_for_redeem(
+            vault,
+            position,
+            ctx.accounts.fee_exemption.is_some(),
+            shares,
+            now,
+            RedeemTokenAccounts {
+                share_mint: &ctx.accounts.share_mint,
+                owner_shares: &ctx.accounts.owner_shares,
+                burn_authority: vault_info,
+                asset_vault: &ctx.accounts.asset_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+        )?;
+        if let Some(min_assets_out) = min_assets_out {
+            require!(net_assets >= min_assets_out, VaultError::SlippageExceeded);
+        }
+        session.remaining_assets = session
+            .remaining_assets
+            .checked_sub(net_assets)
+            .ok_or(VaultError::SessionLimitExceeded)?;
+        deallocate_for_redeem(
+            vault,
+            &mut ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.token_program,
+            StrategyItems::new(ctx.remaining_accounts, &adapter_accounts),
+            net_assets,
+            &EventCpi {
+                authority: ctx.accounts.event_authority.to_account_info(),
+                bump: ctx.bumps.event_authority,
+            },
+        )?;
+
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.owner_asset,
+            &ctx.accounts.token_program,
+            net_assets,
+        )?;
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(SessionRedeemEvent {
+            owner: ctx.accounts.owner.key(),
+            session_key: ctx.accounts.session_key.key(),
+            shares,
+            assets: net_assets,
+            fee,
+            remaining_assets: session.remaining_assets,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Start tracking the caller's cost basis in the vault. `deposit`, `mint`, `redeem`,
+    /// `instant_redeem` and `withdraw` update it when passed the `CostBasis` PDA; shares held
+    /// before opening it carry no cost
+    pub fn open_cost_basis(ctx: Context<OpenCostBasis>) -> Result<()> {
+        let cost_basis = &mut ctx.accounts.cost_basis;
+        cost_basis.vault = ctx.accounts.vault.key();
+        cost_basis.owner = ctx.accounts.owner.key();
+        cost_basis.opened_at = Clock::get()?.unix_timestamp;
+        cost_basis.bump = ctx.bumps.cost_basis;
+
+        emit_cpi!(CostBasisTrackingEvent {
+            owner: ctx.accounts.owner.key(),
+            tracking: true,
+        });
+
+        Ok(())
+    }
+
+    /// Stop tracking the caller's cost basis, closing its PDA
+    pub fn close_cost_basis(ctx: Context<CloseCostBasis>) -> Result<()> {
+        emit_cpi!(CostBasisTrackingEvent {
+            owner: ctx.accounts.owner.key(),
+            tracking: false,
+        });
+
+        Ok(())
+    }
+
+    /// Preview shares minted for a deposit of `assets`
+    pub fn preview_deposit(ctx: Context<ViewVault>, assets: u64) -> Result<u64> {
+        let vault = ctx.accounts.vault.accrued(Clock::get()?.unix_timestamp)?;
+        vault.preview_deposit(assets)
+    }
+
+    /// Preview USDC `owner` receives (net of the fee `redeem` charges them) for redeeming
+    /// `shares`
+    pub fn preview_redeem(ctx: Context<ViewRedeem>, shares: u64) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = ctx.accounts.vault.accrued(now)?;
+        vault.preview_redeem(
+            shares,
+            &ctx.accounts.owner_position(),
+            ctx.accounts.fee_exemption.is_some(),
+            now,
+            vault.idle_assets(&ctx.accounts.asset_vault),
+        )
+    }
+
+    /// Preview the shares `withdraw` burns from `owner` (fee included) to pay out `assets`
+    pub fn preview_withdraw(ctx: Context<ViewRedeem>, assets: u64) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = ctx.accounts.vault.accrued(now)?;
+        vault.preview_withdraw(
+            assets,
+            &ctx.accounts.owner_position(),
+            ctx.accounts.fee_exemption.is_some(),
+            now,
+            vault.idle_assets(&ctx.accounts.asset_vault),
+        )
+    }
+
+    /// Convert an asset amount to shares at the current exchange rate (no fee)
+    pub fn convert_to_shares(ctx: Context<ViewVault>, assets: u64) -> Result<u64> {
+        let vault = ctx.accounts.vault.accrued(Clock::get()?.unix_timestamp)?;
+        vault.convert_to_shares(assets, Rounding::Floor)
+    }
+
+    /// Convert a share amount to assets at the current exchange rate (no fee)
+    pub fn convert_to_assets(ctx: Context<ViewVault>, shares: u64) -> Result<u64> {
+        let vault = ctx.accounts.vault.accrued(Clock::get()?.unix_timestamp)?;
+        vault.convert_to_assets(shares, Rounding::Floor)
+    }
+
+    /// Create the vault's `RateHistory` with its first snapshot (permissionless; the payer
+    /// funds the rent)
+    pub fn init_rate_history(ctx: Context<InitRateHistory>) -> Result<()> {
+        let rate_history = &mut ctx.accounts.rate_history;
+        rate_history.vault = ctx.accounts.vault.key();
+        rate_history.bump = ctx.bumps.rate_history;
+        rate_history.head = 0;
+        rate_history.snapshots = Vec::new();
+
+        emit_cpi!(rate_history.record(&ctx.accounts.vault, Clock::get()?.unix_timestamp)?);
+
+        Ok(())
+    }
+
+    /// Snapshot the vault's exchange rate into its `RateHistory`, at most once per
+    /// `RATE_SNAPSHOT_INTERVAL_SECONDS` (permissionless keeper crank)
+    pub fn record_rate(ctx: Context<RecordRate>) -> Result<()> {
+        emit_cpi!(ctx
+            .accounts
+            .rate_history
+            .record(&ctx.accounts.vault, Clock::get()?.unix_timestamp)?);
+
+        Ok(())
+    }
+
+    /// Annualized return in bps over the last `days` snapshots of a `RateHistory` (simple, not
+    /// compounded; negative after a loss)
+    pub fn rate_apy(ctx: Context<ViewRateHistory>, days: u16) -> Result<i64> {
+        ctx.accounts.rate_history.apy_bps(days as usize)
+    }
+
+    /// Queue new deposit and redeem fees, executable after `fee_timelock_seconds` (fee manager
+    /// only). Each fee may move by at most `max_fee_step_bps`; queuing replaces any pending update
+    pub fn queue_fee_update(
+        ctx: Context<UpdateFee>,
+        new_deposit_fee_bps: u16,
+        new_redeem_fee_bps: u16,
+    ) -> Result<()> {
+        require!(new_deposit_fee_bps <= 100, VaultError::InvalidFee);
+        require!(new_redeem_fee_bps <= 100, VaultError::InvalidFee);
+
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            new_deposit_fee_bps.abs_diff(vault.deposit_fee_bps) <= vault.max_fee_step_bps
+                && new_redeem_fee_bps.abs_diff(vault.redeem_fee_bps) <= vault.max_fee_step_bps,
+            VaultError::FeeStepTooLarge
+        );
+
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(vault.fee_timelock_seconds as i64)
+            .ok_or(VaultError::MathOverflow)?;
+        vault.pending_deposit_fee_bps = new_deposit_fee_bps;
+        vault.pending_redeem_fee_bps = new_redeem_fee_bps;
+        vault.fee_update_eta = eta;
+
+        emit_cpi!(FeeQueuedEvent {
+            new_deposit_fee_bps,
+            new_redeem_fee_bps,
+            eta,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.fee_manager.key(),
+            AdminAction::QueueFeeUpdate {
+                new_deposit_fee_bps,
+                new_redeem_fee_bps,
+                eta,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Apply the queued fee update once its timelock has elapsed (fee manager only)
+    pub fn execute_fee_update(ctx: Context<UpdateFee>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.fee_update_eta > 0, VaultError::NoPendingFeeUpdate);
+        require!(
+            Clock::get()?.unix_timestamp >= vault.fee_update_eta,
+            VaultError::TimelockNotExpired
+        );
+
+        vault.deposit_fee_bps = vault.pending_deposit_fee_bps;
+        vault.redeem_fee_bps = vault.pending_redeem_fee_bps;
+        vault.fee_update_eta = 0;
+
+        emit_cpi!(FeeExecutedEvent {
+            new_deposit_fee_bps: vault.deposit_fee_bps,
+            new_redeem_fee_bps: vault.redeem_fee_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.fee_manager.key(),
+            AdminAction::ExecuteFeeUpdate {
+                new_deposit_fee_bps: vault.deposit_fee_bps,
+                new_redeem_fee_bps: vault.redeem_fee_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Configure the fee-update timelock and maximum step size (governance only). Tightening
+    /// both (no shorter delay, no larger step) applies right away and drops any queued change;
+    /// loosening either is queued behind the current delay for `execute_fee_timelock`
+    pub fn set_fee_timelock(
+        ctx: Context<UpdateConfig>,
+        fee_timelock_seconds: u32,
+        max_fee_step_bps: u16,
+    ) -> Result<()> {
+        require!(
+            fee_timelock_seconds >= MIN_FEE_TIMELOCK_SECONDS,
+            VaultError::InvalidFeeTimelock
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        if fee_timelock_seconds >= vault.fee_timelock_seconds
+            && max_fee_step_bps <= vault.max_fee_step_bps
+        {
+            vault.fee_timelock_seconds = fee_timelock_seconds;
+            vault.max_fee_step_bps = max_fee_step_bps;
+            vault.fee_timelock_eta = 0;
+
+            emit_cpi!(FeeTimelockUpdatedEvent {
+                fee_timelock_seconds,
+                max_fee_step_bps,
+            });
+        } else {
+            let eta = Clock::get()?
+                .unix_timestamp
+                .checked_add(vault.fee_timelock_seconds as i64)
+                .ok_or(VaultError::MathOverflow)?;
+            vault.pending_fee_timelock_seconds = fee_timelock_seconds;
+            vault.pending_max_fee_step_bps = max_fee_step_bps;
+            vault.fee_timelock_eta = eta;
+
+            emit_cpi!(FeeTimelockQueuedEvent {
+                fee_timelock_seconds,
+                max_fee_step_bps,
+                eta,
+            });
+        }
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetFeeTimelock {
+                fee_timelock_seconds,
+                max_fee_step_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Apply the fee timelock loosened by `set_fee_timelock` once the delay it was queued
+    /// behind has elapsed (governance only)
+    pub fn execute_fee_timelock(ctx: Context<UpdateConfig>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.fee_timelock_eta > 0, VaultError::NoPendingFeeUpdate);
+        require!(
+            Clock::get()?.unix_timestamp >= vault.fee_timelock_eta,
+            VaultError::TimelockNotExpired
+        );
+
+        vault.fee_timelock_seconds = vault.pending_fee_timelock_seconds;
+        vault.max_fee_step_bps = vault.pending_max_fee_step_bps;
+        vault.fee_timelock_eta = 0;
+
+        emit_cpi!(FeeTimelockUpdatedEvent {
+            fee_timelock_seconds: vault.fee_timelock_seconds,
+            max_fee_step_bps: vault.max_fee_step_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::ExecuteFeeTimelock {
+                fee_timelock_seconds: vault.fee_timelock_seconds,
+                max_fee_step_bps: vault.max_fee_step_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set per-instruction pause flags (pauser only)
+    pub fn set_pause_flags(
+        ctx: Context<SetPauseFlags>,
+        paused_deposits: bool,
+        paused_redeems: bool,
+        paused_meta: bool,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        // A pause set by the depeg breaker is only lifted by the guardian resetting it
+        require!(
+            !vault.depeg_tripped
+                || (paused_deposits && (paused_redeems || !vault.depeg_pauses_redeems)),
+            VaultError::DepegBreakerTripped
+        );
+        // Winding down keeps deposits paused, and redemptions too once the rate is frozen
+        require!(
+            vault.wind_down_ts == 0
+                || (paused_deposits && (paused_redeems || !vault.wind_down_frozen())),
+            VaultError::WindingDown
+        );
+        vault.paused_deposits = paused_deposits;
+        vault.paused_redeems = paused_redeems;
+        vault.paused_meta = paused_meta;
+
+        emit_cpi!(PauseFlagsUpdatedEvent {
+            paused_deposits,
+            paused_redeems,
+            paused_meta,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.pauser.key(),
+            AdminAction::SetPauseFlags {
+                paused_deposits,
+                paused_redeems,
+                paused_meta,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Propose a new authority, which must accept before taking over (governance only).
+    /// Proposing `Pubkey::default()` cancels a pending transfer
+    pub fn propose_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.pending_authority = new_authority;
+
+        emit_cpi!(AuthorityProposedEvent {
+            authority: vault.authority,
+            pending_authority: new_authority,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::ProposeAuthority { new_authority },
+        )?);
+
+        Ok(())
+    }
+
+    /// Complete an authority transfer (pending authority only)
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let previous_authority = vault.authority;
+        vault.authority = vault.pending_authority;
+        vault.pending_authority = Pubkey::default();
+
+        emit_cpi!(AuthorityTransferredEvent {
+            previous_authority,
+            new_authority: vault.authority,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.pending_authority.key(),
+            AdminAction::AcceptAuthority,
+        )?);
+
+        Ok(())
+    }
+
+    /// Kill switch: pause deposits, redemptions and meta-transactions at once (guardian only).
+    /// The guardian cannot unpause, move funds or change parameters
+    pub fn guardian_pause(ctx: Context<GuardianPause>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.paused_deposits = true;
+        vault.paused_redeems = true;
+        vault.paused_meta = true;
+
+        emit_cpi!(GuardianPauseEvent {
+            guardian: ctx.accounts.guardian.key(),
+        });
+        emit_cpi!(PauseFlagsUpdatedEvent {
+            paused_deposits: true,
+            paused_redeems: true,
+            paused_meta: true,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.guardian.key(),
+            AdminAction::GuardianPause
+        )?);
+
+        Ok(())
+    }
+
+    /// Configure the depeg circuit breaker: the Pyth USDC/USD `price_feed` account (default =
+    /// off), the largest deviation from $1 and the oldest price tolerated, and whether tripping
+    /// also pauses redemptions (governance only)
+    pub fn set_depeg_breaker(
+        ctx: Context<UpdateConfig>,
+        price_feed: Pubkey,
+        max_price_deviation_bps: u16,
+        max_price_age: u32,
+        pauses_redeems: bool,
+    ) -> Result<()> {
+        require!(
+            price_feed == Pubkey::default()
+                || ((1..=10_000).contains(&max_price_deviation_bps) && max_price_age > 0),
+            VaultError::InvalidDepegBreaker
+        );
+        let vault = &mut ctx.accounts.vault;
+        vault.price_feed = price_feed;
+        vault.max_price_deviation_bps = max_price_deviation_bps;
+        vault.max_price_age = max_price_age;
+        vault.depeg_pauses_redeems = pauses_redeems;
+
+        emit_cpi!(DepegBreakerUpdatedEvent {
+            price_feed,
+            max_price_deviation_bps,
+            max_price_age,
+            pauses_redeems,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetDepegBreaker {
+                price_feed,
+                max_price_deviation_bps,
+                max_price_age,
+                pauses_redeems,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Check the USDC/USD price against the depeg breaker (permissionless). A price off $1 by
+    /// more than `max_price_deviation_bps`, or older than `max_price_age`, trips it: deposits
+    /// (and redemptions, if configured) pause until the guardian resets it
+    pub fn check_price(ctx: Context<CheckPrice>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        if vault.depeg_tripped {
+            return Ok(());
+        }
+        let price = oracle::UsdPrice::load(&ctx.accounts.price_update)?;
+        let deviation_bps = price.deviation_bps()?;
+        let stale = price.is_stale(Clock::get()?.unix_timestamp, vault.max_price_age);
+        if !stale && deviation_bps <= u64::from(vault.max_price_deviation_bps) {
+            return Ok(());
+        }
+
+        vault.depeg_tripped = true;
+        vault.paused_deposits = true;
+        if vault.depeg_pauses_redeems {
+            vault.paused_redeems = true;
+        }
+
+        emit_cpi!(DepegBreakerTrippedEvent {
+            price: price.price,
+            exponent: price.exponent,
+            publish_time: price.publish_time,
+            deviation_bps,
+            stale,
+        });
+        emit_cpi!(PauseFlagsUpdatedEvent {
+            paused_deposits: true,
+            paused_redeems: vault.paused_redeems,
+            paused_meta: vault.paused_meta,
+        });
+
+        Ok(())
+    }
+
+    /// Lift the pause set by the depeg breaker once the price is fresh and back within bounds
+    /// (guardian only). With the breaker since turned off, no price is checked
+    pub fn reset_depeg_breaker(ctx: Context<ResetDepegBreaker>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.depeg_tripped, VaultError::DepegBreakerNotTripped);
+        let mut deviation_bps = 0;
+        if vault.price_feed != Pubkey::default() {
+            let price = oracle::UsdPrice::load(&ctx.accounts.price_update)?;
+            deviation_bps = price.deviation_bps()?;
+            require!(
+                !price.is_stale(Clock::get()?.unix_timestamp, vault.max_price_age)
+                    && deviation_bps <= u64::from(vault.max_price_deviation_bps),
+                VaultError::PriceOutOfBounds
+            );
+        }
+
+        vault.depeg_tripped = false;
+        vault.paused_deposits = vault.wind_down_ts > 0;
+        if vault.depeg_pauses_redeems && !vault.wind_down_frozen() {
+            vault.paused_redeems = false;
+        }
+
+        emit_cpi!(DepegBreakerResetEvent {
+            guardian: ctx.accounts.guardian.key(),
+            deviation_bps,
+        });
+        emit_cpi!(PauseFlagsUpdatedEvent {
+            paused_deposits: vault.paused_deposits,
+            paused_redeems: vault.paused_redeems,
+            paused_meta: vault.paused_meta,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.guardian.key(),
+            AdminAction::ResetDepegBreaker
+        )?);
+
+        Ok(())
+    }
+
+    /// Irreversibly give up all admin control: clears the authority, every role and the
+    /// timelock admin, if the vault has a timelock, so neither a new nor an already queued
+    /// action can run and no admin instruction can ever succeed again (governance only).
+    /// Without a keeper no epoch is processed, so withdrawals can no longer be queued; queued
+    /// ones can still be cancelled
+    pub fn renounce_authority(ctx: Context<RenounceAuthority>) -> Result<()> {
+        // Not closed: the timelock may be the authority, renouncing from its own queued action
+        let timelock_info = &ctx.accounts.timelock;
+        if timelock_info.owner == &crate::ID {
+            let mut timelock = Timelock::try_deserialize(&mut &timelock_info.data.borrow()[..])?;
+            timelock.admin = Pubkey::default();
+            timelock.try_serialize(&mut &mut timelock_info.data.borrow_mut()[..])?;
+        }
+
+        let vault = &mut ctx.accounts.vault;
+        let previous_authority = vault.authority;
+        vault.authority = Pubkey::default();
+        vault.pending_authority = Pubkey::default();
+        vault.pauser = Pubkey::default();
+        vault.fee_manager = Pubkey::default();
+        vault.guardian = Pubkey::default();
+        vault.keeper = Pubkey::default();
+        vault.compliance = Pubkey::default();
+
+        msg!("Vault authority renounced; parameters are now immutable");
+        emit_cpi!(AuthorityRenouncedEvent {
+            previous_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::RenounceAuthority
+        )?);
+
+        Ok(())
+    }
+
+    /// Assign `role` to `new_holder` (governance only)
+    pub fn set_role(ctx: Context<UpdateConfig>, role: Role, new_holder: Pubkey) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let holder = vault.role_holder_mut(role);
+        let previous_holder = *holder;
+        *holder = new_holder;
+
+        emit_cpi!(RoleUpdatedEvent {
+            role,
+            previous_holder,
+            new_holder,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetRole { role, new_holder },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the global TVL cap (governance only)
+    pub fn set_deposit_cap(ctx: Context<UpdateConfig>, new_deposit_cap: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.deposit_cap = new_deposit_cap;
+
+        emit_cpi!(DepositCapUpdatedEvent { new_deposit_cap });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetDepositCap { new_deposit_cap },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the per-user cumulative deposit limit (governance only)
+    pub fn set_max_per_user(ctx: Context<UpdateConfig>, new_max_per_user: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.max_per_user = new_max_per_user;
+
+        emit_cpi!(MaxPerUserUpdatedEvent { new_max_per_user });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetMaxPerUser { new_max_per_user },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set minimum deposit and redeem amounts in asset units (governance only)
+    pub fn set_min_amounts(
+        ctx: Context<UpdateConfig>,
+        min_deposit: u64,
+        min_redeem: u64,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.min_deposit = min_deposit;
+        vault.min_redeem = min_redeem;
+
+        emit_cpi!(MinAmountsUpdatedEvent {
+            min_deposit,
+            min_redeem,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetMinAmounts {
+                min_deposit,
+                min_redeem,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the lockup applied to each deposit before it can be redeemed (governance only)
+    pub fn set_lockup_seconds(ctx: Context<UpdateConfig>, lockup_seconds: u32) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.lockup_seconds = lockup_seconds;
+
+        emit_cpi!(LockupUpdatedEvent { lockup_seconds });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetLockupSeconds { lockup_seconds },
+        )?);
+
+        Ok(())
+    }
+
+    /// Configure the early-exit penalty (timelock only, i.e. via a queued action):
+    /// `early_exit_penalty_bps` right after a deposit, decaying linearly to zero over
+    /// `early_exit_window_seconds`. May raise the highest redeem fee by at most `max_fee_step_bps`
+    pub fn set_early_exit_penalty(
+        ctx: Context<TimelockedConfig>,
+        early_exit_window_seconds: u32,
+        early_exit_penalty_bps: u16,
+    ) -> Result<()> {
+        require!(
+            early_exit_penalty_bps <= MAX_PENALTY_BPS,
+            VaultError::InvalidFee
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        let before = vault.max_effective_redeem_fee_bps();
+        vault.early_exit_window_seconds = early_exit_window_seconds;
+        vault.early_exit_penalty_bps = early_exit_penalty_bps;
+        vault.check_redeem_fee_step(before)?;
+
+        emit_cpi!(EarlyExitPenaltyUpdatedEvent {
+            early_exit_window_seconds,
+            early_exit_penalty_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetEarlyExitPenalty {
+                early_exit_window_seconds,
+                early_exit_penalty_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the redeem fee charged at full utilization (timelock only, i.e. via a queued action).
+    /// The fee scales linearly from `redeem_fee_bps` with an empty idle buffer up to this value;
+    /// 0 disables the curve. May raise the highest redeem fee by at most `max_fee_step_bps`
+    pub fn set_redeem_fee_curve(
+        ctx: Context<TimelockedConfig>,
+        max_redeem_fee_bps: u16,
+    ) -> Result<()> {
+        require!(max_redeem_fee_bps <= 100, VaultError::InvalidFee);
+
+        let vault = &mut ctx.accounts.vault;
+        let before = vault.max_effective_redeem_fee_bps();
+        vault.max_redeem_fee_bps = max_redeem_fee_bps;
+        vault.check_redeem_fee_step(before)?;
+
+        emit_cpi!(RedeemFeeCurveUpdatedEvent { max_redeem_fee_bps });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetRedeemFeeCurve { max_redeem_fee_bps },
+        )?);
+
+        Ok(())
+    }
+
+    /// Configure the deposit-to-redeem cooldown (timelock only, i.e. via a queued action).
+    /// Redemptions inside the window are rejected when `cooldown_penalty_bps` is 0, otherwise
+    /// charged the penalty on top of the fee. May raise the highest redeem fee by at most
+    /// `max_fee_step_bps`
+    pub fn set_cooldown(
+        ctx: Context<TimelockedConfig>,
+        cooldown_seconds: u32,
+        cooldown_penalty_bps: u16,
+    ) -> Result<()> {
+        require!(
+            cooldown_penalty_bps <= MAX_PENALTY_BPS,
+            VaultError::InvalidFee
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        let before = vault.max_effective_redeem_fee_bps();
+        vault.cooldown_seconds = cooldown_seconds;
+        vault.cooldown_penalty_bps = cooldown_penalty_bps;
+        vault.check_redeem_fee_step(before)?;
+
+        emit_cpi!(CooldownUpdatedEvent {
+            cooldown_seconds,
+            cooldown_penalty_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetCooldown {
+                cooldown_seconds,
+                cooldown_penalty_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the annualized management fee and the treasury share account it is minted to
+    /// (timelock only, i.e. via a queued action). Fees owed at the previous rate are accrued
+    /// first
+    pub fn set_management_fee(
+        ctx: Context<TimelockedConfig>,
+        management_fee_bps: u16,
+        treasury_shares: Pubkey,
+    ) -> Result<()> {
+        require!(
+            management_fee_bps <= MAX_MANAGEMENT_FEE_BPS,
+            VaultError::InvalidFee
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        vault.accrue_management_fee(Clock::get()?.unix_timestamp)?;
+        vault.management_fee_bps = management_fee_bps;
+        vault.treasury_shares = treasury_shares;
+
+        emit_cpi!(ManagementFeeUpdatedEvent {
+            management_fee_bps,
+            treasury_shares,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetManagementFee {
+                management_fee_bps,
+                treasury_shares,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the share of `total_assets` kept idle in `asset_vault` for redemptions (governance
+    /// only)
+    pub fn set_buffer_bps(ctx: Context<UpdateConfig>, buffer_bps: u16) -> Result<()> {
+        require!(buffer_bps <= 10_000, VaultError::InvalidBuffer);
+        let vault = &mut ctx.accounts.vault;
+        vault.buffer_bps = buffer_bps;
+
+        emit_cpi!(BufferUpdatedEvent { buffer_bps });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetBufferBps { buffer_bps },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the premium `instant_redeem` charges on top of the redeem fee, at most
+    /// `MAX_INSTANT_REDEEM_PREMIUM_BPS` (timelock only, i.e. via a queued action)
+    pub fn set_instant_redeem_premium(
+        ctx: Context<TimelockedConfig>,
+        instant_redeem_premium_bps: u16,
+    ) -> Result<()> {
+        require!(
+            instant_redeem_premium_bps <= MAX_INSTANT_REDEEM_PREMIUM_BPS,
+            VaultError::InvalidFee
+        );
+        let vault = &mut ctx.accounts.vault;
+        vault.instant_redeem_premium_bps = instant_redeem_premium_bps;
+
+        emit_cpi!(InstantRedeemPremiumUpdatedEvent {
+            instant_redeem_premium_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetInstantRedeemPremium {
+                instant_redeem_premium_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the fee taken on strategy gains booked by `harvest`, minted as shares to the
+    /// management fee's treasury share account (timelock only, i.e. via a queued action)
+    pub fn set_performance_fee(
+        ctx: Context<TimelockedConfig>,
+        performance_fee_bps: u16,
+    ) -> Result<()> {
+        require!(
+            performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS,
+            VaultError::InvalidFee
+        );
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            performance_fee_bps == 0 || vault.treasury_shares != Pubkey::default(),
+            VaultError::TreasuryNotSet
+        );
+        vault.performance_fee_bps = performance_fee_bps;
+
+        emit_cpi!(PerformanceFeeUpdatedEvent {
+            performance_fee_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetPerformanceFee {
+                performance_fee_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set the period over which harvested profit unlocks into the share price (governance
+    /// only). Applies from the next harvest
+    pub fn set_profit_unlock_duration(
+        ctx: Context<UpdateConfig>,
+        profit_unlock_duration: u32,
+    ) -> Result<()> {
+        require!(
+            profit_unlock_duration <= MAX_PROFIT_UNLOCK_DURATION,
+            VaultError::InvalidProfitUnlockDuration
+        );
+        let vault = &mut ctx.accounts.vault;
+        vault.profit_unlock_duration = profit_unlock_duration;
+
+        emit_cpi!(ProfitUnlockDurationUpdatedEvent {
+            profit_unlock_duration,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetProfitUnlockDuration {
+                profit_unlock_duration,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Accrue the management fee and mint all owed fee shares to the treasury (permissionless)
+    pub fn accrue_fees(ctx: Context<AccrueFees>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.accrue_management_fee(Clock::get()?.unix_timestamp)?;
+
+        let shares = vault.unminted_fee_shares;
+        if shares > 0 {
+            let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = MintTo {
+                mint: ctx.accounts.share_mint.to_account_info(),
+                to: ctx.accounts.treasury_shares.to_account_info(),
+                authority: vault.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::mint_to(cpi_ctx, shares)?;
+
+            vault.unminted_fee_shares = 0;
+        }
+
+        emit_cpi!(ManagementFeeAccruedEvent {
+            shares,
+            total_shares: vault.total_shares,
+        });
+
+        Ok(())
+    }
+
+    /// Set the USDC token account that receives collected fees (timelock only, i.e. via a queued
+    /// action)
+    pub fn set_fee_recipient(ctx: Context<TimelockedConfig>, fee_recipient: Pubkey) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.fee_recipient = fee_recipient;
+
+        emit_cpi!(FeeRecipientUpdatedEvent { fee_recipient });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetFeeRecipient { fee_recipient },
+        )?);
+
+        Ok(())
+    }
+
+    /// Transfer all accrued deposit/redeem fees to `fee_recipient` (permissionless)
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let amount = vault.accrued_fees;
+
+        if amount > 0 {
+            let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.asset_vault.to_account_info(),
+                mint: ctx.accounts.asset_mint.to_account_info(),
+                to: ctx.accounts.fee_recipient.to_account_info(),
+                authority: vault.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.asset_mint.decimals)?;
+
+            vault.accrued_fees = 0;
+        }
+
+        emit_cpi!(FeesCollectedEvent {
+            fee_recipient: vault.fee_recipient,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Turn KYC-gated mode on/off (governance only). When `permissioned`, depositors must hold
+    /// an allowlist entry, and so must redeemers if `permissioned_redeems` is also set
+    pub fn set_permissioned(
+        ctx: Context<UpdateConfig>,
+        permissioned: bool,
+        permissioned_redeems: bool,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.permissioned = permissioned;
+        vault.permissioned_redeems = permissioned_redeems;
+
+        emit_cpi!(PermissionedUpdatedEvent {
+            permissioned,
+            permissioned_redeems,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetPermissioned {
+                permissioned,
+                permissioned_redeems,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Allow `wallet` into a permissioned vault (governance only)
+    pub fn add_to_allowlist(ctx: Context<AddToAllowlist>, wallet: Pubkey) -> Result<()> {
+        let allowlist_entry = &mut ctx.accounts.allowlist_entry;
+        allowlist_entry.vault = ctx.accounts.vault.key();
+        allowlist_entry.wallet = wallet;
+        allowlist_entry.bump = ctx.bumps.allowlist_entry;
+
+        emit_cpi!(AllowlistUpdatedEvent {
+            wallet,
+            allowed: true,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::AddToAllowlist { wallet },
+        )?);
+
+        Ok(())
+    }
+
+    /// Remove `wallet` from the allowlist, closing its PDA (governance only)
+    pub fn remove_from_allowlist(ctx: Context<RemoveFromAllowlist>, wallet: Pubkey) -> Result<()> {
+        emit_cpi!(AllowlistUpdatedEvent {
+            wallet,
+            allowed: false,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::RemoveFromAllowlist { wallet },
+        )?);
+
+        Ok(())
+    }
+
+    /// Only let registered relayers submit meta-transactions (governance only)
+    pub fn set_relayers_restricted(
+        ctx: Context<UpdateConfig>,
+        relayers_restricted: bool,
+    ) -> Result<()> {
+        ctx.accounts.vault.relayers_restricted = relayers_restricted;
+
+        emit_cpi!(RelayersRestrictedUpdatedEvent {
+            relayers_restricted,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetRelayersRestricted {
+                relayers_restricted,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Register `relayer`, capping the USDC it may move through meta-transactions per day
+    /// (governance only). Re-registering updates the limit and keeps the current window
+    pub fn set_relayer(ctx: Context<SetRelayer>, relayer: Pubkey, daily_limit: u64) -> Result<()> {
+        let relayer_entry = &mut ctx.accounts.relayer_entry;
+        relayer_entry.vault = ctx.accounts.vault.key();
+        relayer_entry.relayer = relayer;
+        relayer_entry.daily_limit = daily_limit;
+        relayer_entry.bump = ctx.bumps.relayer_entry;
+
+        emit_cpi!(RelayerUpdatedEvent {
+            relayer,
+            daily_limit,
+            registered: true,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetRelayer {
+                relayer,
+                daily_limit,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Deregister `relayer`, closing its PDA (governance only)
+    pub fn remove_relayer(ctx: Context<RemoveRelayer>, relayer: Pubkey) -> Result<()> {
+        emit_cpi!(RelayerUpdatedEvent {
+            relayer,
+            daily_limit: 0,
+            registered: false,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::RemoveRelayer { relayer },
+        )?);
+
+        Ok(())
+    }
+
+    /// Update the share token's name, symbol and metadata URI (governance only)
+    pub fn update_share_metadata(
+        ctx: Context<UpdateShareMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = UpdateMetadataAccountsV2 {
+            metadata: ctx.accounts.share_metadata.to_account_info(),
+            update_authority: ctx.accounts.vault.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_metadata_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        metadata::update_metadata_accounts_v2(
+            cpi_ctx,
+            None,
+            Some(share_metadata(name.clone(), symbol.clone(), uri.clone())),
+            None,
+            None,
+        )?;
+
+        emit_cpi!(ShareMetadataUpdatedEvent {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::UpdateShareMetadata { name, symbol, uri },
+        )?);
+
+        Ok(())
+    }
+
+    /// Replace the vault's display name, description, URI and risk tier (governance only).
+    /// Creates the metadata account of vaults initialized before it existed
+    pub fn update_vault_metadata(
+        ctx: Context<UpdateVaultMetadata>,
+        vault_info: VaultInfo,
+    ) -> Result<()> {
+        emit_cpi!(ctx.accounts.vault_metadata.set(
+            ctx.accounts.vault.key(),
+            ctx.bumps.vault_metadata,
+            vault_info.clone(),
+        )?);
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::UpdateVaultMetadata { vault_info },
+        )?);
+
+        Ok(())
+    }
+
+    /// Block a sanctioned `wallet` from every user-facing instruction (compliance only)
+    pub fn block_address(ctx: Context<BlockAddress>, wallet: Pubkey) -> Result<()> {
+        let blocklist_entry = &mut ctx.accounts.blocklist_entry;
+        blocklist_entry.vault = ctx.accounts.vault.key();
+        blocklist_entry.wallet = wallet;
+        blocklist_entry.bump = ctx.bumps.blocklist_entry;
+
+        emit_cpi!(BlocklistUpdatedEvent {
+            wallet,
+            blocked: true,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.compliance.key(),
+            AdminAction::BlockAddress { wallet },
+        )?);
+
+        Ok(())
+    }
+
+    /// Lift the block on `wallet`, closing its PDA (compliance only)
+    pub fn unblock_address(ctx: Context<UnblockAddress>, wallet: Pubkey) -> Result<()> {
+        emit_cpi!(BlocklistUpdatedEvent {
+            wallet,
+            blocked: false,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.compliance.key(),
+            AdminAction::UnblockAddress { wallet },
+        )?);
+
+        Ok(())
+    }
+
+    /// Exempt `wallet` from deposit and redeem fees (fee manager only)
+    pub fn add_fee_exemption(ctx: Context<AddFeeExemption>, wallet: Pubkey) -> Result<()> {
+        let fee_exemption = &mut ctx.accounts.fee_exemption;
+        fee_exemption.vault = ctx.accounts.vault.key();
+        fee_exemption.wallet = wallet;
+        fee_exemption.bump = ctx.bumps.fee_exemption;
+
+        emit_cpi!(FeeExemptionUpdatedEvent {
+            wallet,
+            exempt: true,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.fee_manager.key(),
+            AdminAction::AddFeeExemption { wallet },
+        )?);
+
+        Ok(())
+    }
+
+    /// Revoke the fee exemption of `wallet`, closing its PDA (fee manager only)
+    pub fn remove_fee_exemption(ctx: Context<RemoveFeeExemption>, wallet: Pubkey) -> Result<()> {
+        emit_cpi!(FeeExemptionUpdatedEvent {
+            wallet,
+            exempt: false,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.fee_manager.key(),
+            AdminAction::RemoveFeeExemption { wallet },
+        )?);
+
+        Ok(())
+    }
+
+    /// Set how `sync` books unaccounted USDC (governance only)
+    pub fn set_surplus_policy(ctx: Context<UpdateConfig>, policy: SurplusPolicy) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.surplus_policy = policy;
+
+        emit_cpi!(SurplusPolicyUpdatedEvent { policy });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetSurplusPolicy { policy },
+        )?);
+
+        Ok(())
+    }
+
+    /// Reconcile `total_assets` with the actual `asset_vault` balance (keeper only)
+    pub fn sync(ctx: Context<SyncAssets>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &mut ctx.accounts.vault;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+
+        // USDC allocated to strategies and the basket assets are still part of `total_assets`
+        let balance = ctx.accounts.asset_vault.amount;
+        let booked_elsewhere = math::add(vault.allocated_assets, vault.basket_value)?;
+        let surplus = math::add(balance, booked_elsewhere)?.saturating_sub(math::add(
+            math::add(vault.total_assets, vault.accrued_fees)?,
+            math::add(vault.insurance_assets, vault.reserved_withdraw_assets)?,
+        )?);
+
+        if surplus > 0 {
+            match vault.surplus_policy {
+                // Direct transfers accrue to all shareholders, the senior part locked like
+                // harvested profit so a deposit right before the sync can't capture it
+                SurplusPolicy::Yield => {
+                    let junior_gain = vault.junior_gain(surplus)?;
+                    vault.junior_assets = math::add(vault.junior_assets, junior_gain)?;
+                    vault.total_assets = math::add(vault.total_assets, surplus)?;
+                    let locked_profit = math::add(vault.locked_profit, surplus - junior_gain)?;
+                    vault.lock_profit(locked_profit, now)?;
+                }
+                // Direct transfers are swept out as protocol fees
+                SurplusPolicy::Fees => {
+                    let fee_destination = ctx
+                        .accounts
+                        .fee_destination
+                        .as_ref()
+                        .ok_or(VaultError::MissingFeeDestination)?;
+
+                    let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+                    let signer = &[&seeds[..]];
+
+                    let cpi_accounts = TransferChecked {
+                        from: ctx.accounts.asset_vault.to_account_info(),
+                        mint: ctx.accounts.asset_mint.to_account_info(),
+                        to: fee_destination.to_account_info(),
+                        authority: vault.to_account_info(),
+                    };
+                    let cpi_program = ctx.accounts.token_program.to_account_info();
+                    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+                    token_interface::transfer_checked(
+                        cpi_ctx,
+                        surplus,
+                        ctx.accounts.asset_mint.decimals,
+                    )?;
+                }
+            }
+        }
+
+        emit_cpi!(SyncEvent {
+            balance,
+            total_assets: vault.total_assets,
+            surplus,
+            policy: vault.surplus_policy,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::Sync { surplus }
+        )?);
+
+        Ok(())
+    }
+
+    /// Register the strategy adapter `adapter_program`, which receives allocations in its
+    /// `reserve` USDC account, up to `max_debt`, aiming for `target_weight_bps` of
+    /// `total_assets` (timelock only, i.e. via a queued action). See `strategy`
+    pub fn add_strategy(
+        ctx: Context<AddStrategy>,
+        max_debt: u64,
+        target_weight_bps: u16,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(
+            vault.strategy_count < MAX_STRATEGIES,
+            VaultError::TooManyStrategies
+        );
+        vault.strategy_count += 1;
+        vault.set_target_weight(0, target_weight_bps)?;
+
+        let adapter_program = ctx.accounts.adapter_program.key();
+        let strategy = &mut ctx.accounts.strategy;
+        strategy.vault = vault.key();
+        strategy.adapter_program = adapter_program;
+        strategy.reserve = ctx.accounts.reserve.key();
+        strategy.allocated = 0;
+        strategy.max_debt = max_debt;
+        strategy.target_weight_bps = target_weight_bps;
+        strategy.enabled = true;
+        strategy.bump = ctx.bumps.strategy;
+
+        emit_cpi!(StrategyUpdatedEvent {
+            adapter_program,
+            max_debt,
+            target_weight_bps,
+            enabled: true,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::AddStrategy {
+                adapter_program,
+                max_debt,
+                target_weight_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Change a strategy's allocation cap and target weight, or stop new allocations to it
+    /// (timelock only, i.e. via a queued action). A disabled strategy can still be deallocated
+    pub fn update_strategy(
+        ctx: Context<UpdateStrategy>,
+        max_debt: u64,
+        target_weight_bps: u16,
+        enabled: bool,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        ctx.accounts
+            .vault
+            .set_target_weight(strategy.target_weight_bps, target_weight_bps)?;
+        strategy.max_debt = max_debt;
+        strategy.target_weight_bps = target_weight_bps;
+        strategy.enabled = enabled;
+
+        emit_cpi!(StrategyUpdatedEvent {
+            adapter_program: strategy.adapter_program,
+            max_debt,
+            target_weight_bps,
+            enabled,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::UpdateStrategy {
+                adapter_program: strategy.adapter_program,
+                max_debt,
+                target_weight_bps,
+                enabled,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Unregister a fully deallocated strategy, closing its PDA to the timelock admin (timelock
+    /// only, i.e. via a queued action)
+    pub fn remove_strategy(ctx: Context<RemoveStrategy>) -> Result<()> {
+        let strategy = &ctx.accounts.strategy;
+        require!(strategy.allocated == 0, VaultError::StrategyNotEmpty);
+        let vault = &mut ctx.accounts.vault;
+        vault.strategy_count -= 1;
+        vault.set_target_weight(strategy.target_weight_bps, 0)?;
+
+        emit_cpi!(StrategyRemovedEvent {
+            adapter_program: strategy.adapter_program,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::RemoveStrategy {
+                adapter_program: strategy.adapter_program,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Move `amount` of idle USDC into a strategy and have its adapter deploy it (keeper only).
+    /// Adapter-specific accounts are passed as remaining accounts
+    pub fn allocate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StrategyTransfer<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.wind_down_ts == 0, VaultError::WindingDown);
+        let strategy = &mut ctx.accounts.strategy;
+        require!(strategy.enabled, VaultError::StrategyDisabled);
+        require!(
+            math::add(amount, vault.liquidity_buffer()?)?
+                <= vault.idle_assets(&ctx.accounts.asset_vault),
+            VaultError::InsufficientIdleAssets
+        );
+        strategy.allocated = math::add(strategy.allocated, amount)?;
+        require!(
+            strategy.allocated <= strategy.max_debt,
+            VaultError::StrategyAllocationExceeded
+        );
+        vault.allocated_assets = math::add(vault.allocated_assets, amount)?;
+
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.reserve,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        invoke_strategy(
+            &ctx.accounts.vault.key(),
+            &ctx.accounts.strategy,
+            ctx.accounts.adapter_accounts(ctx.remaining_accounts),
+            strategy::STRATEGY_DEPOSIT_DISCRIMINATOR,
+            amount,
+        )?;
+
+        emit_cpi!(AllocateEvent {
+            adapter_program: ctx.accounts.strategy.adapter_program,
+            amount,
+            allocated: ctx.accounts.strategy.allocated,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::Allocate {
+                adapter_program: ctx.accounts.strategy.adapter_program,
+                amount,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Have a strategy's adapter return `amount` of USDC to the vault (keeper only). What
+    /// arrives first repays the allocated principal; anything beyond it is yield, booked by
+    /// `sync` like any other surplus
+    pub fn deallocate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StrategyTransfer<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let before = ctx.accounts.asset_vault.amount;
+        invoke_strategy(
+            &ctx.accounts.vault.key(),
+            &ctx.accounts.strategy,
+            ctx.accounts.adapter_accounts(ctx.remaining_accounts),
+            strategy::STRATEGY_WITHDRAW_DISCRIMINATOR,
+            amount,
+        )?;
+        ctx.accounts.asset_vault.reload()?;
+        let received = math::sub(ctx.accounts.asset_vault.amount, before)?;
+
+        let strategy = &mut ctx.accounts.strategy;
+        let repaid = received.min(strategy.allocated);
+        strategy.allocated -= repaid;
+        let vault = &mut ctx.accounts.vault;
+        vault.allocated_assets = math::sub(vault.allocated_assets, repaid)?;
+
+        emit_cpi!(DeallocateEvent {
+            adapter_program: strategy.adapter_program,
+            amount,
+            received,
+            allocated: strategy.allocated,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::Deallocate {
+                adapter_program: ctx.accounts.strategy.adapter_program,
+                amount,
+                received,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Move `amount` from `from_strategy`, above its target allocation, to `to_strategy`, below
+    /// its target, without going through idle USDC (keeper only). Neither may cross its target.
+    /// The first `from_accounts` remaining accounts go to the `from` adapter, the rest to the
+    /// `to` adapter
+    pub fn rebalance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Rebalance<'info>>,
+        amount: u64,
+        from_accounts: u8,
+    ) -> Result<()> {
+        let split = usize::from(from_accounts);
+        require!(
+            split <= ctx.remaining_accounts.len(),
+            ErrorCode::AccountNotEnoughKeys
+        );
+        let (from_remaining, to_remaining) = ctx.remaining_accounts.split_at(split);
+        let vault_key = ctx.accounts.vault.key();
+        let total_assets = ctx.accounts.vault.total_assets;
+
+        let from_target = ctx.accounts.from_strategy.target_allocation(total_assets)?;
+        require!(
+            math::sub(ctx.accounts.from_strategy.allocated, amount)? >= from_target,
+            VaultError::RebalanceCrossesTarget
+        );
+
+        let before = ctx.accounts.asset_vault.amount;
+        invoke_strategy(
+            &vault_key,
+            &ctx.accounts.from_strategy,
+            ctx.accounts.from_adapter_accounts(from_remaining),
+            strategy::STRATEGY_WITHDRAW_DISCRIMINATOR,
+            amount,
+        )?;
+        ctx.accounts.asset_vault.reload()?;
+        let received = math::sub(ctx.accounts.asset_vault.amount, before)?;
+
+        // As in `deallocate`, anything beyond the principal stays idle for `sync`
+        let from = &mut ctx.accounts.from_strategy;
+        let repaid = received.min(from.allocated);
+        from.allocated -= repaid;
+        let moved = received.min(amount);
+
+        let to_target = ctx.accounts.to_strategy.target_allocation(total_assets)?;
+        let to = &mut ctx.accounts.to_strategy;
+        require!(to.enabled, VaultError::StrategyDisabled);
+        to.allocated = math::add(to.allocated, moved)?;
+        require!(
+            to.allocated <= to_target,
+            VaultError::RebalanceCrossesTarget
+        );
+        require!(
+            to.allocated <= to.max_debt,
+            VaultError::StrategyAllocationExceeded
+        );
+        let vault = &mut ctx.accounts.vault;
+        vault.allocated_assets = math::add(math::sub(vault.allocated_assets, repaid)?, moved)?;
+
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.to_reserve,
+            &ctx.accounts.token_program,
+            moved,
+        )?;
+        invoke_strategy(
+            &vault_key,
+            &ctx.accounts.to_strategy,
+            ctx.accounts.to_adapter_accounts(to_remaining),
+            strategy::STRATEGY_DEPOSIT_DISCRIMINATOR,
+            moved,
+        )?;
+
+        let from = &ctx.accounts.from_strategy;
+        let to = &ctx.accounts.to_strategy;
+        emit_cpi!(DeallocateEvent {
+            adapter_program: from.adapter_program,
+            amount,
+            received,
+            allocated: from.allocated,
+        });
+        emit_cpi!(AllocateEvent {
+            adapter_program: to.adapter_program,
+            amount: moved,
+            allocated: to.allocated,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::Rebalance {
+                from: from.adapter_program,
+                to: to.adapter_program,
+                amount,
+                moved,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Pull everything out of a compromised strategy back into `asset_vault` and disable it
+    /// (guardian only). Withdraws the adapter's whole `strategy_total_value`; whatever can't be
+    /// recovered stays allocated for `report_loss`. Adapter-specific accounts follow as remaining
+    /// accounts, as for `deallocate`
+    pub fn emergency_exit_strategy<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EmergencyExitStrategy<'info>>,
+    ) -> Result<()> {
+        let vault_key = ctx.accounts.vault.key();
+        let strategy_seeds = &[
+            b"strategy".as_ref(),
+            vault_key.as_ref(),
+            ctx.accounts.strategy.adapter_program.as_ref(),
+            &[ctx.accounts.strategy.bump],
+        ];
+        let value = strategy::total_value(
+            ctx.accounts.adapter_accounts(ctx.remaining_accounts),
+            strategy_seeds,
+        )?;
+
+        let before = ctx.accounts.asset_vault.amount;
+        if value > 0 {
+            invoke_strategy(
+                &vault_key,
+                &ctx.accounts.strategy,
+                ctx.accounts.adapter_accounts(ctx.remaining_accounts),
+                strategy::STRATEGY_WITHDRAW_DISCRIMINATOR,
+                value,
+            )?;
+            ctx.accounts.asset_vault.reload()?;
+        }
+        let received = math::sub(ctx.accounts.asset_vault.amount, before)?;
+
+        let strategy = &mut ctx.accounts.strategy;
+        let repaid = received.min(strategy.allocated);
+        strategy.allocated -= repaid;
+        strategy.enabled = false;
+        let vault = &mut ctx.accounts.vault;
+        vault.allocated_assets = math::sub(vault.allocated_assets, repaid)?;
+
+        emit_cpi!(StrategyEmergencyExitEvent {
+            adapter_program: strategy.adapter_program,
+            guardian: ctx.accounts.guardian.key(),
+            value,
+            received,
+            allocated: strategy.allocated,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.guardian.key(),
+            AdminAction::EmergencyExitStrategy {
+                adapter_program: ctx.accounts.strategy.adapter_program,
+                received,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Book each strategy's gain or loss since its last harvest, as reported by its adapter's
+    /// `strategy_total_value`, into `total_assets`, taking the performance fee on gains as fee
+    /// shares (keeper only). Each strategy's `STRATEGY_ITEM_ACCOUNTS` accounts, then its
+    /// `adapter_accounts[i]` adapter-specific accounts, follow in `remaining_accounts`
+    pub fn harvest<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Harvest<'info>>,
+        adapter_accounts: Vec<u8>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.vault.accrue_management_fee(now)?;
+        ctx.accounts.vault.unlock_profit(now)?;
+
+        let mut strategies = Vec::with_capacity(adapter_accounts.len());
+        for item in StrategyItems::new(ctx.remaining_accounts, &adapter_accounts) {
+            strategies.push(harvest_strategy(&ctx.accounts, item?)?);
+        }
+        let gain = strategies
+            .iter()
+            .try_fold(0u64, |sum, pnl| math::add(sum, pnl.gain))?;
+        let loss = strategies
+            .iter()
+            .try_fold(0u64, |sum, pnl| math::add(sum, pnl.loss))?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.allocated_assets = math::sub(math::add(vault.allocated_assets, gain)?, loss)?;
+        let performance_fee = math::fee_on_amount(gain, vault.performance_fee_bps)?;
+        let profit = gain - performance_fee;
+        // The junior tranche takes its cut of the profit at once and absorbs the loss first
+        let junior_profit = vault.junior_gain(profit)?;
+        vault.junior_assets = math::add(vault.junior_assets, junior_profit)?;
+        let senior_loss = vault.absorb_loss(loss);
+        vault.total_assets = math::sub(math::add(vault.total_assets, profit)?, loss)?;
+        // The senior profit is locked, losses eating into it first, so it streams into the
+        // share price instead of going to whoever deposits right before the harvest
+        let locked_profit =
+            math::add(vault.locked_profit, profit - junior_profit)?.saturating_sub(senior_loss);
+        if profit > 0 {
+            vault.lock_profit(locked_profit, now)?;
+        } else {
+            vault.locked_profit = locked_profit;
+        }
+        // The fee's shares are priced before it is added, so they are worth exactly the fee
+        let fee_shares = vault.convert_to_shares(performance_fee, Rounding::Floor)?;
+        vault.total_assets = math::add(vault.total_assets, performance_fee)?;
+        vault.total_shares = math::add(vault.total_shares, fee_shares)?;
+        vault.unminted_fee_shares = math::add(vault.unminted_fee_shares, fee_shares)?;
+
+        emit_cpi!(HarvestEvent {
+            strategies,
+            gain,
+            loss,
+            performance_fee,
+            fee_shares,
+            total_assets: vault.total_assets,
+            locked_profit: vault.locked_profit,
+            junior_assets: vault.junior_assets,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::Harvest {
+                gain,
+                loss,
+                fee_shares,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Write off `loss` of a strategy's allocated principal that can't be recovered (keeper
+    /// only). With `use_insurance`, the insurance buffer covers what it can; the rest lowers the
+    /// share price, and pauses deposits when it reaches `loss_pause_threshold_bps` of
+    /// `total_assets`
+    pub fn report_loss(ctx: Context<ReportLoss>, loss: u64, use_insurance: bool) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        strategy.allocated = math::sub(strategy.allocated, loss)?;
+        let vault = &mut ctx.accounts.vault;
+        // Book the fee accrued up to the loss first, like every instruction moving the share price
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+        vault.allocated_assets = math::sub(vault.allocated_assets, loss)?;
+
+        // Insurance USDC already sits in `asset_vault`; covering moves it into `total_assets`
+        let covered = if use_insurance {
+            loss.min(vault.insurance_assets)
+        } else {
+            0
+        };
+        vault.insurance_assets -= covered;
+        let socialized = loss - covered;
+        let loss_bps = if vault.total_assets == 0 {
+            0
+        } else {
+            math::mul_div(
+                socialized,
+                10_000,
+                vault.total_assets as u128,
+                Rounding::Ceil,
+            )?
+        };
+        vault.total_assets = math::sub(vault.total_assets, socialized)?;
+        let junior_loss = socialized - vault.absorb_loss(socialized);
+        vault.locked_profit = vault.locked_profit.saturating_sub(socialized - junior_loss);
+
+        let threshold = vault.loss_pause_threshold_bps;
+        let pause = socialized > 0 && threshold > 0 && loss_bps >= u64::from(threshold);
+        if pause && !vault.paused_deposits {
+            vault.paused_deposits = true;
+            emit_cpi!(PauseFlagsUpdatedEvent {
+                paused_deposits: true,
+                paused_redeems: vault.paused_redeems,
+                paused_meta: vault.paused_meta,
+            });
+        }
+
+        emit_cpi!(LossEvent {
+            adapter_program: strategy.adapter_program,
+            loss,
+            covered,
+            socialized,
+            junior_loss,
+            loss_bps,
+            allocated: strategy.allocated,
+            total_assets: vault.total_assets,
+            insurance_assets: vault.insurance_assets,
+            deposits_paused: vault.paused_deposits,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.keeper.key(),
+            AdminAction::ReportLoss {
+                adapter_program: strategy.adapter_program,
+                loss,
+                covered,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Add `amount` of USDC to the insurance buffer, held in `asset_vault` outside
+    /// `total_assets` until `report_loss` draws on it (permissionless)
+    pub fn fund_insurance(ctx: Context<FundInsurance>, amount: u64) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.funder_asset.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.asset_vault.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.asset_mint.decimals)?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.insurance_assets = math::add(vault.insurance_assets, amount)?;
+
+        emit_cpi!(InsuranceFundedEvent {
+            funder: ctx.accounts.funder.key(),
+            amount,
+            insurance_assets: vault.insurance_assets,
+        });
+
+        Ok(())
+    }
+
+    /// Set the share of `total_assets` a single socialized loss may reach before `report_loss`
+    /// pauses deposits (governance only, 0 = never)
+    pub fn set_loss_pause_threshold(
+        ctx: Context<UpdateConfig>,
+        loss_pause_threshold_bps: u16,
+    ) -> Result<()> {
+        require!(
+            loss_pause_threshold_bps <= 10_000,
+            VaultError::InvalidLossThreshold
+        );
+        let vault = &mut ctx.accounts.vault;
+        vault.loss_pause_threshold_bps = loss_pause_threshold_bps;
+
+        emit_cpi!(LossPauseThresholdUpdatedEvent {
+            loss_pause_threshold_bps,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetLossPauseThreshold {
+                loss_pause_threshold_bps,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Upgrade a vault written by an older program version to the current layout, reallocating
+    /// it (the authority tops up rent) and filling in defaults for fields added since (governance
+    /// only). Takes the vault unchecked since older layouts don't deserialize as `Vault`.
+    /// `genesis_hash` (of this cluster) is only used by vaults predating meta-message domains.
+    /// Pre-versioning vaults then need `init_legacy_accounts` before taking deposits
+    pub fn migrate_state(ctx: Context<MigrateState>, genesis_hash: [u8; 32]) -> Result<()> {
+        let vault_info = ctx.accounts.vault.to_account_info();
+        require_keys_eq!(
+            *vault_info.owner,
+            crate::ID,
+            ErrorCode::AccountOwnedByWrongProgram
+        );
+
+        let original_len = vault_info.data_len();
+        if original_len < migration::VAULT_ACCOUNT_SIZE {
+            let rent = Rent::get()?.minimum_balance(migration::VAULT_ACCOUNT_SIZE);
+            let top_up = rent.saturating_sub(vault_info.lamports());
+            if top_up > 0 {
+                let cpi_accounts = system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: vault_info.clone(),
+                };
+                let cpi_program = ctx.accounts.system_program.to_account_info();
+                system_program::transfer(CpiContext::new(cpi_program, cpi_accounts), top_up)?;
+            }
+            vault_info.realloc(migration::VAULT_ACCOUNT_SIZE, true)?;
+        }
+
+        let mut vault = migration::decode(&vault_info.data.borrow()[..original_len])?;
+        require_keys_eq!(
+            vault.authority,
+            ctx.accounts.authority.key(),
+            VaultError::Unauthorized
+        );
+        let from_version = vault.version;
+        require!(
+            from_version < migration::CURRENT_VAULT_VERSION,
+            VaultError::AlreadyMigrated
+        );
+
+        migration::upgrade(&mut vault, Clock::get()?.unix_timestamp, genesis_hash)?;
+        let address = Pubkey::create_program_address(
+            &[b"vault", vault.address_seed(), &[vault.bump]],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::ConstraintSeeds)?;
+        require_keys_eq!(address, vault_info.key(), ErrorCode::ConstraintSeeds);
+        vault.try_serialize(&mut &mut vault_info.data.borrow_mut()[..])?;
+
+        emit_cpi!(StateMigratedEvent {
+            from_version,
+            to_version: vault.version,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::MigrateState {
+                from_version,
+                to_version: vault.version,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Create the dead shares account of a vault migrated from before layout versions, which
+    /// the first deposit into an empty vault locks `dead_shares` in, and record the only
+    /// `emergency_withdraw` destination (governance only, once)
+    pub fn init_legacy_accounts(ctx: Context<InitLegacyAccounts>, dead_shares: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require_keys_eq!(
+            vault.dead_shares_account,
+            Pubkey::default(),
+            VaultError::AlreadyMigrated
+        );
+        vault.dead_shares = dead_shares;
+        vault.dead_shares_account = ctx.accounts.dead_shares_account.key();
+        vault.recovery_account = ctx.accounts.recovery_account.key();
+
+        emit_cpi!(RecoveryAccountUpdatedEvent {
+            recovery_account: vault.recovery_account,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::InitLegacyAccounts {
+                dead_shares,
+                dead_shares_account: vault.dead_shares_account,
+                recovery_account: vault.recovery_account,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Create the vault's timelock controller (governance only). Admin operations are routed
+    /// through it once the vault authority is transferred to the timelock PDA; fee, strategy
+    /// and emergency withdrawal changes only ever run through it
+    pub fn init_timelock(ctx: Context<InitTimelock>, delay_seconds: u32) -> Result<()> {
+        require!(
+            delay_seconds >= MIN_TIMELOCK_DELAY_SECONDS,
+            VaultError::TimelockDelayTooShort
+        );
+        let timelock = &mut ctx.accounts.timelock;
+        timelock.vault = ctx.accounts.vault.key();
+        timelock.admin = ctx.accounts.authority.key();
+        timelock.delay_seconds = delay_seconds;
+        timelock.next_action_id = 0;
+        timelock.bump = ctx.bumps.timelock;
+
+        emit_cpi!(TimelockDelayUpdatedEvent { delay_seconds });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::InitTimelock { delay_seconds },
+        )?);
+
+        Ok(())
+    }
+
+    /// Queue a call into this program, signed by the timelock PDA, executable after the delay
+    /// (timelock admin only)
+    pub fn queue_action(
+        ctx: Context<QueueAction>,
+        action_accounts: Vec<ActionAccount>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let timelock = &mut ctx.accounts.timelock;
+        let id = timelock.next_action_id;
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(timelock.delay_seconds.max(MIN_TIMELOCK_DELAY_SECONDS) as i64)
+            .ok_or(VaultError::MathOverflow)?;
+        let expires_at = eta
+            .checked_add(ACTION_GRACE_PERIOD_SECONDS)
+            .ok_or(VaultError::MathOverflow)?;
+        timelock.next_action_id = math::add(id, 1)?;
+
+        let action = &mut ctx.accounts.action;
+        action.timelock = timelock.key();
+        action.id = id;
+        action.eta = eta;
+        action.accounts = action_accounts.clone();
+        action.data = data.clone();
+        action.bump = ctx.bumps.action;
+        action.expires_at = expires_at;
+
+        // Depositors get the whole delay to review a new strategy before it can receive funds
+        if let Some(event) = strategy_queued_event(id, eta, &action_accounts, &data)? {
+            emit_cpi!(event);
+        }
+        emit_cpi!(ActionQueuedEvent {
+            id,
+            eta,
+            expires_at,
+            accounts: action_accounts,
+            data,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.admin.key(),
+            AdminAction::QueueAction { id, eta },
+        )?);
+
+        Ok(())
+    }
+
+    /// Drop a queued action, reclaiming its rent (timelock admin only)
+    pub fn cancel_action(ctx: Context<CancelAction>) -> Result<()> {
+        emit_cpi!(ActionCancelledEvent {
+            id: ctx.accounts.action.id,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.admin.key(),
+            AdminAction::CancelAction {
+                id: ctx.accounts.action.id,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Execute a queued action once its delay has elapsed and before it expires (timelock admin
+    /// only). The action's accounts are passed as remaining accounts
+    pub fn execute_action<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteAction<'info>>,
+    ) -> Result<()> {
+        let action = &ctx.accounts.action;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= action.eta, VaultError::TimelockNotExpired);
+        require!(now < action.expires_at, VaultError::ActionExpired);
+
+        let timelock = &ctx.accounts.timelock;
+        let timelock_key = timelock.key();
+        let ix = Instruction {
+            program_id: crate::ID,
+            accounts: action
+                .accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.pubkey,
+                    is_signer: account.is_signer || account.pubkey == timelock_key,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: action.data.clone(),
+        };
+
+        let seeds = &[
+            b"timelock".as_ref(),
+            timelock.vault.as_ref(),
+            &[timelock.bump],
+        ];
+        let signer = &[&seeds[..]];
+        invoke_signed(&ix, ctx.remaining_accounts, signer)?;
+
+        emit_cpi!(ActionExecutedEvent { id: action.id });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.admin.key(),
+            AdminAction::ExecuteAction { id: action.id },
+        )?);
+
+        Ok(())
+    }
+
+    /// Garbage-collect an expired action, refunding its rent to the timelock admin (permissionless)
+    pub fn prune_expired_action(ctx: Context<PruneExpiredAction>) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp >= ctx.accounts.action.expires_at,
+            VaultError::ActionNotExpired
+        );
+
+        emit_cpi!(ActionExpiredEvent {
+            id: ctx.accounts.action.id,
+        });
+
+        Ok(())
+    }
+
+    /// Change the timelock delay (timelock only, i.e. via a queued action)
+    pub fn set_timelock_delay(ctx: Context<UpdateTimelock>, delay_seconds: u32) -> Result<()> {
+        require!(
+            delay_seconds >= MIN_TIMELOCK_DELAY_SECONDS,
+            VaultError::TimelockDelayTooShort
+        );
+        let timelock = &mut ctx.accounts.timelock;
+        timelock.delay_seconds = delay_seconds;
+
+        emit_cpi!(TimelockDelayUpdatedEvent { delay_seconds });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetTimelockDelay { delay_seconds },
+        )?);
+
+        Ok(())
+    }
+
+    /// Change the key allowed to queue/cancel/execute actions (timelock only)
+    pub fn set_timelock_admin(ctx: Context<UpdateTimelock>, new_admin: Pubkey) -> Result<()> {
+        let timelock = &mut ctx.accounts.timelock;
+        timelock.admin = new_admin;
+
+        emit_cpi!(TimelockAdminUpdatedEvent { new_admin });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::SetTimelockAdmin { new_admin },
+        )?);
+
+        Ok(())
+    }
+
+    /// Change the emergency withdrawal destination (timelock only, i.e. via a queued action)
+    pub fn set_recovery_account(ctx: Context<SetRecoveryAccount>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.recovery_account = ctx.accounts.recovery_account.key();
+
+        emit_cpi!(RecoveryAccountUpdatedEvent {
+            recovery_account: vault.recovery_account,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::SetRecoveryAccount {
+                recovery_account: vault.recovery_account,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Start the escape hatch for a lost guardian key: `EMERGENCY_ESCAPE_DELAY_SECONDS` from now,
+    /// `emergency_withdraw` can run without the guardian (governance only)
+    pub fn request_emergency_escape(ctx: Context<UpdateConfig>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let escape_ts = Clock::get()?
+            .unix_timestamp
+            .checked_add(EMERGENCY_ESCAPE_DELAY_SECONDS)
+            .ok_or(VaultError::MathOverflow)?;
+        vault.emergency_escape_ts = escape_ts;
+
+        emit_cpi!(EmergencyEscapeRequestedEvent { escape_ts });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::RequestEmergencyEscape { escape_ts },
+        )?);
+
+        Ok(())
+    }
+
+    /// Call off a requested escape hatch (guardian only): a guardian able to sign shows its key
+    /// isn't lost, so the timelock alone must not reach the funds
+    pub fn cancel_emergency_escape(ctx: Context<GuardianPause>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.emergency_escape_ts > 0, VaultError::NoEmergencyEscape);
+        vault.emergency_escape_ts = 0;
+
+        emit_cpi!(EmergencyEscapeCancelledEvent {
+            guardian: ctx.accounts.guardian.key(),
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.guardian.key(),
+            AdminAction::CancelEmergencyEscape
+        )?);
+
+        Ok(())
+    }
+
+    /// Emergency withdraw to the pre-registered recovery account (timelock plus guardian
+    /// co-signature, or timelock alone once a requested escape hatch has matured). The escape
+    /// hatch is used up by the withdraw; another needs a new request and delay
+    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>, amount: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        if ctx.accounts.guardian.is_none() {
+            require!(
+                vault.emergency_escape_ts > 0
+                    && Clock::get()?.unix_timestamp >= vault.emergency_escape_ts,
+                VaultError::GuardianSignatureRequired
+            );
+            vault.emergency_escape_ts = 0;
+        }
+
+        let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.asset_vault.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.recovery_account.to_account_info(),
+            authority: vault.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.asset_mint.decimals)?;
+
+        emit_cpi!(EmergencyWithdrawEvent {
+            authority: ctx.accounts.timelock.key(),
+            recovery_account: ctx.accounts.recovery_account.key(),
+            amount,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.timelock.key(),
+            AdminAction::EmergencyWithdraw { amount },
+        )?);
+
+        Ok(())
+    }
+
+    /// Begin winding the vault down (governance only): deposits are paused for good and no
+    /// more USDC goes to strategies. Holders may still redeem until `freeze_wind_down`
+    pub fn start_wind_down(ctx: Context<UpdateConfig>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.wind_down_ts == 0, VaultError::WindingDown);
+        vault.wind_down_ts = Clock::get()?.unix_timestamp;
+        vault.paused_deposits = true;
+
+        emit_cpi!(WindDownStartedEvent {
+            timestamp: vault.wind_down_ts,
+        });
+        emit_cpi!(PauseFlagsUpdatedEvent {
+            paused_deposits: true,
+            paused_redeems: vault.paused_redeems,
+            paused_meta: vault.paused_meta,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::StartWindDown
+        )?);
+
+        Ok(())
+    }
+
+    /// Freeze the final exchange rate once every strategy and basket asset is exited and the
+    /// withdrawal queue and junior tranche are settled (governance only). Locked profit is
+    /// released into it; from then on holders only leave through `claim_wind_down`, for
+    /// `WIND_DOWN_GRACE_PERIOD_SECONDS` before `close_vault`
+    pub fn freeze_wind_down(ctx: Context<UpdateConfig>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.wind_down_ts > 0, VaultError::NotWindingDown);
+        require!(!vault.wind_down_frozen(), VaultError::WindingDown);
+        require!(
+            vault.allocated_assets == 0
+                && vault.basket_value == 0
+                && vault.queued_withdraw_shares == 0
+                && vault.junior_assets == 0,
+            VaultError::WindDownNotReady
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        vault.accrue_management_fee(now)?;
+        vault.locked_profit = 0;
+        vault.wind_down_assets = vault.free_assets();
+        vault.wind_down_shares = vault.total_shares;
+        vault.wind_down_close_ts = now
+            .checked_add(WIND_DOWN_GRACE_PERIOD_SECONDS)
+            .ok_or(VaultError::MathOverflow)?;
+        vault.paused_redeems = true;
+
+        emit_cpi!(WindDownFrozenEvent {
+            assets: vault.wind_down_assets,
+            shares: vault.wind_down_shares,
+            close_ts: vault.wind_down_close_ts,
+        });
+        emit_cpi!(PauseFlagsUpdatedEvent {
+            paused_deposits: true,
+            paused_redeems: true,
+            paused_meta: vault.paused_meta,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::FreezeWindDown {
+                assets: vault.wind_down_assets,
+                shares: vault.wind_down_shares,
+            },
+        )?);
+
+        Ok(())
+    }
+
+    /// Burn `shares` for their pro-rata USDC at the frozen wind-down rate, without fees, until
+    /// the vault is closed
+    pub fn claim_wind_down(ctx: Context<ClaimWindDown>, shares: u64) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.wind_down_frozen(), VaultError::NotWindingDown);
+        BlocklistEntry::check_not_blocked(
+            &ctx.accounts.user_blocklist,
+            &vault.key(),
+            &ctx.accounts.user.key(),
+        )?;
+
+        let assets = math::mul_div(
+            shares,
+            vault.wind_down_assets as u128,
+            vault.wind_down_shares as u128,
+            Rounding::Floor,
+        )?;
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            from: ctx.accounts.user_shares.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token_interface::burn(CpiContext::new(cpi_program, cpi_accounts), shares)?;
+
+        vault.total_assets = math::sub(vault.total_assets, assets)?;
+        vault.total_shares = math::sub(vault.total_shares, shares)?;
+
+        transfer_from_vault(
+            vault,
+            &ctx.accounts.asset_vault,
+            &ctx.accounts.asset_mint,
+            &ctx.accounts.user_asset,
+            &ctx.accounts.token_program,
+            assets,
+        )?;
+
+        let snapshot = vault.snapshot()?;
+        emit_cpi!(WindDownClaimEvent {
+            user: ctx.accounts.user.key(),
+            shares,
+            assets,
+            snapshot,
+        });
+
+        Ok(())
+    }
+
+    /// Close a wound-down vault after its grace period (governance only): the USDC left
+    /// (unclaimed, dead-share and fee USDC) is swept to `fee_recipient`, the dead shares are
+    /// burned, and the vault, its `asset_vault`, dead-share account and metadata are closed with
+    /// their rent going to the `fee_recipient` owner
+    pub fn close_vault(ctx: Context<CloseVault>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        require!(vault.wind_down_frozen(), VaultError::NotWindingDown);
+        require!(
+            Clock::get()?.unix_timestamp >= vault.wind_down_close_ts,
+            VaultError::GracePeriodActive
+        );
+
+        let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+
+        let swept = ctx.accounts.asset_vault.amount;
+        if swept > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.asset_vault.to_account_info(),
+                mint: ctx.accounts.asset_mint.to_account_info(),
+                to: ctx.accounts.fee_recipient.to_account_info(),
+                authority: vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, signer);
+            token_interface::transfer_checked(cpi_ctx, swept, ctx.accounts.asset_mint.decimals)?;
+        }
+
+        let dead_shares = ctx.accounts.dead_shares_account.amount;
+        if dead_shares > 0 {
+            let cpi_accounts = Burn {
+                mint: ctx.accounts.share_mint.to_account_info(),
+                from: ctx.accounts.dead_shares_account.to_account_info(),
+                authority: vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, signer);
+            token_interface::burn(cpi_ctx, dead_shares)?;
+        }
+
+        for account in [
+            ctx.accounts.asset_vault.to_account_info(),
+            ctx.accounts.dead_shares_account.to_account_info(),
+        ] {
+            let cpi_accounts = CloseAccount {
+                account,
+                destination: ctx.accounts.treasury.to_account_info(),
+                authority: vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, signer);
+            token_interface::close_account(cpi_ctx)?;
+        }
+
+        emit_cpi!(VaultClosedEvent {
+            treasury: ctx.accounts.treasury.key(),
+            swept,
+        });
+
+        emit_cpi!(admin_action_event(
+            ctx.accounts.authority.key(),
+            AdminAction::CloseVault { swept },
+        )?);
+
+        Ok(())
+    }
+}
+
+/// How the owner authorized a meta-transaction
+enum OwnerSignature {
+    /// ed25519 signature by the owner's Solana key
+    Ed25519([u8; 64]),
+    /// secp256k1 signature by the owner's linked Ethereum address (carried by the instruction)
+    Secp256k1,
+}
+
+/// Shared by `initialize` and `create_vault`
+#[allow(clippy::too_many_arguments)]
+fn initialize_vault(
+    accounts: &mut Initialize<'_>,
+    vault_bump: u8,
+    vault_metadata_bump: u8,
+    event_authority_bump: u8,
+    deposit_fee_bps: u16,
+    redeem_fee_bps: u16,
+    dead_shares: u64,
+    genesis_hash: [u8; 32],
+    allow_privileged_extensions: bool,
+    share_name: String,
+    share_symbol: String,
+    share_uri: String,
+    vault_info: VaultInfo,
+) -> Result<()> {
+    require!(deposit_fee_bps <= 100, VaultError::InvalidFee);
+    require!(redeem_fee_bps <= 100, VaultError::InvalidFee);
+    extensions::check_asset_mint(
+        &accounts.asset_mint.to_account_info(),
+        allow_privileged_extensions,
+    )?;
+
+    let vault = &mut accounts.vault;
+    vault.authority = accounts.authority.key();
+    vault.asset_mint = accounts.asset_mint.key();
+    vault.share_mint = accounts.share_mint.key();
+    vault.asset_vault = accounts.asset_vault.key();
+    vault.total_assets = 0;
+    vault.total_shares = 0;
+    vault.deposit_fee_bps = deposit_fee_bps;
+    vault.redeem_fee_bps = redeem_fee_bps;
+    vault.bump = vault_bump;
+    vault.pauser = accounts.authority.key();
+    vault.paused_deposits = false;
+    vault.paused_redeems = false;
+    vault.paused_meta = false;
+    vault.deposit_cap = u64::MAX;
+    vault.max_per_user = u64::MAX;
+    vault.min_deposit = 0;
+    vault.min_redeem = 0;
+    vault.surplus_policy = SurplusPolicy::Yield;
+    vault.dead_shares = dead_shares;
+    vault.dead_shares_account = accounts.dead_shares_account.key();
+    vault.lockup_seconds = 0;
+    vault.cooldown_seconds = 0;
+    vault.cooldown_penalty_bps = 0;
+    vault.management_fee_bps = 0;
+    vault.last_accrual_ts = Clock::get()?.unix_timestamp;
+    vault.treasury_shares = Pubkey::default();
+    vault.unminted_fee_shares = 0;
+    vault.accrued_fees = 0;
+    vault.fee_recipient = Pubkey::default();
+    vault.fee_manager = accounts.authority.key();
+    vault.fee_timelock_seconds = DEFAULT_FEE_TIMELOCK_SECONDS;
+    vault.max_fee_step_bps = DEFAULT_MAX_FEE_STEP_BPS;
+    vault.pending_deposit_fee_bps = 0;
+    vault.pending_redeem_fee_bps = 0;
+    vault.fee_update_eta = 0;
+    vault.max_redeem_fee_bps = 0;
+    vault.early_exit_window_seconds = 0;
+    vault.early_exit_penalty_bps = 0;
+    vault.pending_authority = Pubkey::default();
+    vault.guardian = accounts.authority.key();
+    vault.keeper = accounts.authority.key();
+    vault.compliance = accounts.authority.key();
+    vault.recovery_account = accounts.recovery_account.key();
+    vault.emergency_escape_ts = 0;
+    vault.permissioned = false;
+    vault.permissioned_redeems = false;
+    vault.allocated_assets = 0;
+    vault.strategy_count = 0;
+    vault.total_target_weight_bps = 0;
+    vault.performance_fee_bps = 0;
+    vault.locked_profit = 0;
+    vault.profit_unlock_duration = DEFAULT_PROFIT_UNLOCK_DURATION;
+    vault.locked_profit_ts = 0;
+    vault.profit_unlock_end_ts = 0;
+    vault.insurance_assets = 0;
+    vault.loss_pause_threshold_bps = 0;
+    vault.buffer_bps = 0;
+    vault.price_feed = Pubkey::default();
+    vault.max_price_deviation_bps = 0;
+    vault.max_price_age = 0;
+    vault.depeg_pauses_redeems = false;
+    vault.depeg_tripped = false;
+    vault.withdraw_epoch = 0;
+    vault.queued_withdraw_shares = 0;
+    vault.reserved_withdraw_assets = 0;
+    vault.instant_redeem_premium_bps = 0;
+    vault.legacy_address = false;
+    vault.basket_asset_count = 0;
+    vault.basket_value = 0;
+    vault.junior_share_mint = Pubkey::default();
+    vault.junior_assets = 0;
+    vault.junior_shares = 0;
+    vault.senior_yield_bps = 0;
+    vault.min_subordination_bps = 0;
+    vault.migration_target = Pubkey::default();
+    vault.wind_down_ts = 0;
+    vault.wind_down_close_ts = 0;
+    vault.wind_down_assets = 0;
+    vault.wind_down_shares = 0;
+    vault.event_sequence = 0;
+    vault.pending_fee_timelock_seconds = 0;
+    vault.pending_max_fee_step_bps = 0;
+    vault.fee_timelock_eta = 0;
+    vault.version = migration::CURRENT_VAULT_VERSION;
+    vault.genesis_hash = genesis_hash;
+
+    // Name the share token for wallets; the vault PDA is its mint and update authority
+    let seeds = &[
+        b"vault".as_ref(),
+        accounts.vault.address_seed(),
+        &[vault_bump],
+    ];
+    let signer = &[&seeds[..]];
+    let cpi_accounts = CreateMetadataAccountsV3 {
+        metadata: accounts.share_metadata.to_account_info(),
+        mint: accounts.share_mint.to_account_info(),
+        mint_authority: accounts.vault.to_account_info(),
+        payer: accounts.authority.to_account_info(),
+        update_authority: accounts.vault.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+        rent: accounts.rent.to_account_info(),
+    };
+    let cpi_program = accounts.token_metadata_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    metadata::create_metadata_accounts_v3(
+        cpi_ctx,
+        share_metadata(share_name, share_symbol, share_uri),
+        true,
+        true,
+        None,
+    )?;
+
+    let events = EventCpi {
+        authority: accounts.event_authority.to_account_info(),
+        bump: event_authority_bump,
+    };
+    events.emit(accounts.vault_metadata.set(
+        accounts.vault.key(),
+        vault_metadata_bump,
+        vault_info,
+    )?)?;
+
+    events.emit(admin_action_event(
+        accounts.authority.key(),
+        AdminAction::Initialize {
+            deposit_fee_bps,
+            redeem_fee_bps,
+            dead_shares,
+            allow_privileged_extensions,
+        },
+    )?)?;
+
+    Ok(())
+}
+
+/// Append `vault` to `registry`, growing the account by one entry paid by `payer`
+fn register_vault_in<'info>(
+    registry: &mut Account<'info, Registry>,
+    vault: &Account<'info, Vault>,
+    payer: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    events: &EventCpi<'info>,
+) -> Result<()> {
+    let vault_key = vault.key();
+    require!(
+        !registry.vaults.contains(&vault_key),
+        VaultError::VaultAlreadyRegistered
+    );
+
+    let registry_info = registry.to_account_info();
+    let size = Registry::space(registry.vaults.len() + 1);
+    let rent = Rent::get()?.minimum_balance(size);
+    let top_up = rent.saturating_sub(registry_info.lamports());
+    if top_up > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer,
+            to: registry_info.clone(),
+        };
+        let cpi_program = system_program.to_account_info();
+        system_program::transfer(CpiContext::new(cpi_program, cpi_accounts), top_up)?;
+    }
+    registry_info.realloc(size, false)?;
+
+    let index = registry.vaults.len() as u32;
+    registry.vaults.push(vault_key);
+
+    events.emit(VaultRegisteredEvent {
+        vault: vault_key,
+        asset_mint: vault.asset_mint,
+        authority: vault.authority,
+        index,
+    })
+}
+
+/// Shared by `meta_redeem` and `meta_redeem_evm`, which only differ in how the owner signs
+#[allow(clippy::too_many_arguments)]
+fn process_meta_redeem<'info>(
+    ctx: Context<'_, '_, 'info, 'info, MetaRedeem<'info>>,
+    shares: u64,
+    receiver: Pubkey,
+    deadline: i64,
+    max_relayer_fee: u64,
+    relayer_fee: u64,
+    owner_signature: OwnerSignature,
+    adapter_accounts: Vec<u8>,
+) -> Result<()> {
+    let vault = &mut ctx.accounts.vault;
+    let clock = Clock::get()?;
+
+    require!(!vault.paused_meta, VaultError::MetaPaused);
+    require!(!vault.paused_redeems, VaultError::RedeemsPaused);
+    vault.check_redeem_allowed(ctx.accounts.allowlist_entry.is_some())?;
+    vault.check_relayer_allowed(ctx.accounts.relayer_entry.is_some())?;
+    BlocklistEntry::check_not_blocked(
+        &ctx.accounts.owner_blocklist,
+        &vault.key(),
+        &ctx.accounts.owner.key(),
+    )?;
+    BlocklistEntry::check_not_blocked(
+        &ctx.accounts.relayer_blocklist,
+        &vault.key(),
+        &ctx.accounts.relayer.key(),
+    )?;
+    BlocklistEntry::check_not_blocked(&ctx.accounts.receiver_blocklist, &vault.key(), &receiver)?;
+    require!(
+        clock.unix_timestamp <= deadline,
+        VaultError::DeadlineExpired
+    );
+    // The relayer picks its fee, bounded by the amount the owner signed off on
+    require!(
+        relayer_fee <= max_relayer_fee,
+        VaultError::RelayerFeeExceeded
+    );
+
+    // The owner's signature is checked by a preceding signature program instruction
+    let user_nonce = &mut ctx.accounts.user_nonce;
+    let nonce = user_nonce.nonce;
+    let message = MetaMessage {
+        domain: vault.domain(vault.key()),
+        owner: ctx.accounts.owner.key(),
+        nonce,
+        deadline,
+        action: MetaAction::Redeem {
+            shares,
+            receiver,
+            max_relayer_fee,
+        },
+    };
+    match owner_signature {
+        OwnerSignature::Ed25519(signature) => signature::verify_ed25519_ix(
+            &ctx.accounts.instructions,
+            &ctx.accounts.owner.key(),
+            &message.signing_bytes(),
+            &signature,
+        )?,
+        OwnerSignature::Secp256k1 => {
+            let evm_owner = ctx
+                .accounts
+                .evm_owner
+                .as_ref()
+                .ok_or(VaultError::InvalidSignature)?;
+            signature::verify_secp256k1_ix(
+                &ctx.accounts.instructions,
+                &evm_owner.eth_address,
+                &message.eip191_signing_bytes(),
+            )?
+        }
+    }
+    user_nonce.nonce = math::add(user_nonce.nonce, 1)?;
+
+    let now = clock.unix_timestamp;
+    vault.accrue_management_fee(now)?;
+    vault.unlock_profit(now)?;
+
+    let position = &mut ctx.accounts.owner_position;
+    position.init_if_new(
+        vault.key(),
+        ctx.accounts.owner.key(),
+        ctx.bumps.owner_position,
+    );
+    position.check_unlocked(now)?;
+
+    // The owner approved the vault PDA as delegate of `owner_shares`, so the vault burns
+    let vault_info = vault.to_account_info();
+    let (net_assets, fee) = burn_for_redeem(
+        vault,
+        position,
+        ctx.accounts.fee_exemption.is_some(),
+        shares,
+        now,
+        RedeemTokenAccounts {
+            share_mint: &ctx.accounts.share_mint,
+            owner_shares: &ctx.accounts.owner_shares,
+            burn_authority: vault_info,
+            asset_vault: &ctx.accounts.asset_vault,
+            token_program: &ctx.accounts.token_program,
+        },
+    )?;
+
+    if let Some(relayer_entry) = &mut ctx.accounts.relayer_entry {
+        relayer_entry.record_volume(now, net_assets)?;
+    }
+    deallocate_for_redeem(
+        vault,
+        &mut ctx.accounts.asset_vault,
+        &ctx.accounts.asset_mint,
+        &ctx.accounts.token_program,
+        StrategyItems::new(ctx.remaining_accounts, &adapter_accounts),
+        net_assets,
+        &EventCpi {
+            authority: ctx.accounts.event_authority.to_account_info(),
+            bump: ctx.bumps.event_authority,
+        },
+    )?;
+
+    // The relayer is paid out of the redeemed USDC, the rest goes to the receiver
+    let assets = math::sub(net_assets, relayer_fee)?;
+    transfer_from_vault(
+        vault,
+        &ctx.accounts.asset_vault,
+        &ctx.accounts.asset_mint,
+        &ctx.accounts.relayer_asset,
+        &ctx.accounts.token_program,
+        relayer_fee,
+    )?;
+    transfer_from_vault(
+        vault,
+        &ctx.accounts.asset_vault,
+        &ctx.accounts.asset_mint,
+        &ctx.accounts.receiver_asset,
+        &ctx.accounts.token_program,
+        assets,
+    )?;
+
+    let snapshot = vault.snapshot()?;
+    emit_cpi!(MetaRedeemEvent {
+        owner: ctx.accounts.owner.key(),
+        receiver,
+        relayer: ctx.accounts.relayer.key(),
+        shares,
+        assets,
+        fee,
+        nonce,
+        relayer_fee,
+        snapshot,
+    });
+
+    Ok(())
+}
+
+/// Redeem one request of `batch_meta_redeem` from its `remaining_accounts` chunk, emitting its
+/// `MetaRedeemEvent`. Returns the net assets before the relayer fee, which the caller pays
+/// along with `owed_relayer_fee`, the fees of the requests before. The batch has no strategy
+/// accounts to deallocate from, so it is paid from idle USDC only
+#[allow(clippy::too_many_arguments)]
+fn batch_meta_redeem_item<'info>(
+    accounts: &mut BatchMetaRedeem<'info>,
+    item: &'info [AccountInfo<'info>],
+    request: &MetaRedeemRequest,
+    verify_ix_index: u16,
+    owed_relayer_fee: u64,
+    now: i64,
+    events: &EventCpi<'info>,
+) -> Result<u64> {
+    // In `BATCH_ITEM_ACCOUNTS` order (the caller checked the length)
+    let (owner, owner_shares, receiver_asset) = (&item[0], &item[1], &item[2]);
+    let (owner_position, fee_exemption, allowlist_entry) = (&item[3], &item[4], &item[5]);
+    let (owner_blocklist, receiver_blocklist, user_nonce) = (&item[6], &item[7], &item[8]);
+    let vault = &mut accounts.vault;
+    let vault_key = vault.key();
+    let owner_key = owner.key();
+
+    // Same constraints as the `MetaRedeem` accounts, checked by hand
+    let owner_shares: InterfaceAccount<TokenAccount> = InterfaceAccount::try_from(owner_shares)?;
+    require!(
+        owner_shares.mint == vault.share_mint && owner_shares.owner == owner_key,
+        ErrorCode::ConstraintRaw
+    );
+    let receiver_asset: InterfaceAccount<TokenAccount> =
+        InterfaceAccount::try_from(receiver_asset)?;
+    require!(
+        receiver_asset.mint == vault.asset_mint && receiver_asset.owner == request.receiver,
+        ErrorCode::ConstraintRaw
+    );
+    let position: Account<UserPosition> = Account::try_from(owner_position)?;
+    require!(
+        position.vault == vault_key && position.owner == owner_key,
+        ErrorCode::ConstraintSeeds
+    );
+    let fee_exemption = optional_account::<FeeExemption>(fee_exemption)?;
+    if let Some(fee_exemption) = &fee_exemption {
+        require!(
+            fee_exemption.vault == vault_key && fee_exemption.wallet == owner_key,
+            ErrorCode::ConstraintSeeds
+        );
+    }
+    let allowlist_entry = optional_account::<AllowlistEntry>(allowlist_entry)?;
+    if let Some(allowlist_entry) = &allowlist_entry {
+        require!(
+            allowlist_entry.vault == vault_key && allowlist_entry.wallet == owner_key,
+            ErrorCode::ConstraintSeeds
+        );
+    }
+    let mut user_nonce: Account<UserNonce> = Account::try_from(user_nonce)?;
+    let (expected_nonce, _) =
+        Pubkey::find_program_address(&[b"nonce", owner_key.as_ref()], &crate::ID);
+    require_keys_eq!(user_nonce.key(), expected_nonce, ErrorCode::ConstraintSeeds);
+
+    vault.check_redeem_allowed(allowlist_entry.is_some())?;
+    BlocklistEntry::check_not_blocked(owner_blocklist, &vault_key, &owner_key)?;
+    BlocklistEntry::check_not_blocked(receiver_blocklist, &vault_key, &request.receiver)?;
+    require!(now <= request.deadline, VaultError::DeadlineExpired);
+    require!(
+        request.relayer_fee <= request.max_relayer_fee,
+        VaultError::RelayerFeeExceeded
+    );
+
+    let nonce = user_nonce.nonce;
+    let message = MetaMessage {
+        domain: vault.domain(vault_key),
+        owner: owner_key,
+        nonce,
+        deadline: request.deadline,
+        action: MetaAction::Redeem {
+            shares: request.shares,
+            receiver: request.receiver,
+            max_relayer_fee: request.max_relayer_fee,
+        },
+    }
+    .signing_bytes();
+    signature::verify_ed25519_ix_at(
+        &accounts.instructions,
+        verify_ix_index,
+        &owner_key,
+        &message,
+        &request.signature,
+    )?;
+    // Written back right away so a later request of the same owner sees the new nonce
+    user_nonce.nonce = math::add(nonce, 1)?;
+    user_nonce.exit(&crate::ID)?;
+
+    position.check_unlocked(now)?;
+
+    // The requests before paid out of `asset_vault` already
+    accounts.asset_vault.reload()?;
+    let vault_info = vault.to_account_info();
+    let (net_assets, fee) = burn_for_redeem(
+        vault,
+        &position,
+        fee_exemption.is_some(),
+        request.shares,
+        now,
+        RedeemTokenAccounts {
+            share_mint: &accounts.share_mint,
+            owner_shares: &owner_shares,
+            burn_authority: vault_info,
+            asset_vault: &accounts.asset_vault,
+            token_program: &accounts.token_program,
+        },
+    )?;
+    require!(
+        math::add(net_assets, owed_relayer_fee)? <= vault.idle_assets(&accounts.asset_vault),
+        VaultError::InsufficientIdleAssets
+    );
+
+    let assets = math::sub(net_assets, request.relayer_fee)?;
+    transfer_from_vault(
+        vault,
+        &accounts.asset_vault,
+        &accounts.asset_mint,
+        &receiver_asset,
+        &accounts.token_program,
+        assets,
+    )?;
+
+    let snapshot = vault.snapshot()?;
+    events.emit(MetaRedeemEvent {
+        owner: owner_key,
+        receiver: request.receiver,
+        relayer: accounts.relayer.key(),
+        shares: request.shares,
+        assets,
+        fee,
+        nonce,
+        relayer_fee: request.relayer_fee,
+        snapshot,
+    })?;
+
+    Ok(net_assets)
+}
+
+/// Deserialize an optional account passed in `remaining_accounts`, where (as for Anchor's
+/// optional accounts) the program id stands for `None`
+fn optional_account<'info, T: AccountSerialize + AccountDeserialize + Owner + Clone>(
+    info: &'info AccountInfo<'info>,
+) -> Result<Option<Account<'info, T>>> {
+    if info.key() == crate::ID {
+        return Ok(None);
+    }
+    Account::try_from(info).map(Some)
+}
+
+/// Token accounts `deposit_for_shares` moves USDC and mints shares with
+struct DepositTokenAccounts<'a, 'info> {
+    source_asset: &'a InterfaceAccount<'info, TokenAccount>,
+    /// The source owner, or the vault PDA acting as the owner's approved delegate, or another
+    /// vault PDA owning `source_asset`
+    transfer_authority: AccountInfo<'info>,
+    /// Signer seeds of `transfer_authority` when it is another vault, empty otherwise
+    transfer_seeds: &'a [&'a [u8]],
+    asset_vault: &'a InterfaceAccount<'info, TokenAccount>,
+    asset_mint: &'a InterfaceAccount<'info, Mint>,
+    share_mint: &'a InterfaceAccount<'info, Mint>,
+    receiver_shares: &'a InterfaceAccount<'info, TokenAccount>,
+    dead_shares_account: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+}
+
+/// Pull `assets` into the vault and mint shares for them to the receiver, returning
+/// (shares, fee), or exactly `exact_shares` when given, the assets having to pay for at least
+/// that many. Only the assets received after any Token-2022 transfer fee are booked.
+/// Shared by `deposit`, `mint`, `meta_deposit` and `migrate_position`
+fn deposit_for_shares<'info>(
+    vault: &mut Account<'info, Vault>,
+    position: &mut UserPosition,
+    fee_exempt: bool,
+    assets: u64,
+    exact_shares: Option<u64>,
+    now: i64,
+    accounts: DepositTokenAccounts<'_, 'info>,
+) -> Result<(u64, u64)> {
+    let received = math::sub(
+        assets,
+        extensions::transfer_fee(&accounts.asset_mint.to_account_info(), assets)?,
+    )?;
+
+    // Calculate shares to mint on the received assets net of the deposit fee (waived for exempt
+    // wallets), locking the dead shares on the first deposit
+    let fee = if fee_exempt {
+        0
+    } else {
+        vault.deposit_fee(received)?
+    };
+    let dead_shares = vault.pending_dead_shares();
+    let minted = vault.convert_to_shares(math::sub(received, fee)?, Rounding::Floor)?;
+    require!(minted > dead_shares, VaultError::DepositTooSmall);
+    let shares = match exact_shares {
+        // Any rounding surplus of the assets stays in the vault
+        Some(exact_shares) => {
+            require!(
+                exact_shares > 0 && math::add(exact_shares, dead_shares)? <= minted,
+                VaultError::DepositTooSmall
+            );
+            exact_shares
+        }
+        None => math::sub(minted, dead_shares)?,
+    };
+
+    require!(
+        math::add(vault.total_assets, received)? <= vault.deposit_cap,
+        VaultError::DepositCapExceeded
+    );
+
+    // Track cumulative deposits of the receiver against the per-user limit
+    position.deposited_assets = math::add(position.deposited_assets, received)?;
+    require!(
+        position.deposited_assets <= vault.max_per_user,
+        VaultError::UserCapExceeded
+    );
+    position.extend_lockup(now, vault.lockup_seconds)?;
+    position.last_deposit_ts = now;
+
+    // Transfer assets into the vault (the vault seeds only matter when it moves them as delegate)
+    let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+    let signer = &[&seeds[..]];
+    let signers = [&seeds[..], accounts.transfer_seeds];
+    let transfer_signer = if accounts.transfer_seeds.is_empty() {
+        &signers[..1]
+    } else {
+        &signers[..]
+    };
+
+    let cpi_accounts = TransferChecked {
+        from: accounts.source_asset.to_account_info(),
+        mint: accounts.asset_mint.to_account_info(),
+        to: accounts.asset_vault.to_account_info(),
+        authority: accounts.transfer_authority,
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, transfer_signer);
+    token_interface::transfer_checked(cpi_ctx, assets, accounts.asset_mint.decimals)?;
+
+    // Mint shares to receiver
+    let cpi_accounts = MintTo {
+        mint: accounts.share_mint.to_account_info(),
+        to: accounts.receiver_shares.to_account_info(),
+        authority: vault.to_account_info(),
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::mint_to(cpi_ctx, shares)?;
+
+    if dead_shares > 0 {
+        let cpi_accounts = MintTo {
+            mint: accounts.share_mint.to_account_info(),
+            to: accounts.dead_shares_account.to_account_info(),
+            authority: vault.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token_interface::mint_to(cpi_ctx, dead_shares)?;
+    }
+
+    // Update vault state (fee is set aside for `collect_fees`)
+    vault.total_assets = math::add(vault.total_assets, math::sub(received, fee)?)?;
+    vault.total_shares = math::add(vault.total_shares, math::add(shares, dead_shares)?)?;
+    vault.accrued_fees = math::add(vault.accrued_fees, fee)?;
+
+    Ok((shares, fee))
+}
+
+/// Token accounts `burn_for_redeem` burns shares with
+struct RedeemTokenAccounts<'a, 'info> {
+    share_mint: &'a InterfaceAccount<'info, Mint>,
+    owner_shares: &'a InterfaceAccount<'info, TokenAccount>,
+    /// The share owner, or the vault PDA acting as the owner's approved delegate
+    burn_authority: AccountInfo<'info>,
+    asset_vault: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+}
+
+/// Burn `shares` and book the redemption, returning (net assets, fee). Shared by `redeem`,
+/// `withdraw` and `meta_redeem`; the caller pays the net assets out with `transfer_from_vault`
+fn burn_for_redeem<'info>(
+    vault: &mut Account<'info, Vault>,
+    position: &UserPosition,
+    fee_exempt: bool,
+    shares: u64,
+    now: i64,
+    accounts: RedeemTokenAccounts<'_, 'info>,
+) -> Result<(u64, u64)> {
+    // Calculate gross assets
+    let gross_assets = vault.convert_to_assets(shares, Rounding::Floor)?;
+    require!(gross_assets >= vault.min_redeem, VaultError::RedeemTooSmall);
+
+    // Apply redeem fee (10 bps = 0.1%, scaled up by utilization, plus any cooldown penalty)
+    // unless the owner is exempt
+    let idle_assets = vault.idle_assets(accounts.asset_vault);
+    let fee_bps = vault.redeem_fee_bps(position, fee_exempt, now, idle_assets)?;
+    let fee = math::fee_on_amount(gross_assets, fee_bps)?;
+    let net_assets = math::sub(gross_assets, fee)?;
+
+    // Burn owner shares (the vault seeds only matter when it burns as delegate)
+    let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = Burn {
+        mint: accounts.share_mint.to_account_info(),
+        from: accounts.owner_shares.to_account_info(),
+        authority: accounts.burn_authority,
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::burn(cpi_ctx, shares)?;
+
+    // Update vault state (fee is set aside for `collect_fees`)
+    vault.total_assets = math::sub(vault.total_assets, gross_assets)?;
+    vault.total_shares = math::sub(vault.total_shares, shares)?;
+    vault.accrued_fees = math::add(vault.accrued_fees, fee)?;
+
+    Ok((net_assets, fee))
+}
+
+/// Token accounts `pay_basket_asset` pays a basket asset out of
+struct BasketTokenAccounts<'a, 'info> {
+    mint: &'a InterfaceAccount<'info, Mint>,
+    basket_vault: &'a InterfaceAccount<'info, TokenAccount>,
+    receiver: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+}
+
+/// Pay `value` worth of a basket asset, already booked out of `total_assets` by
+/// `burn_for_redeem`, at `price`, returning the amount paid. The asset's value is rebooked for
+/// its new balance; the rounding left in the basket goes back into `total_assets`
+fn pay_basket_asset<'info>(
+    vault: &mut Account<'info, Vault>,
+    basket_asset: &mut BasketAsset,
+    price: &oracle::UsdPrice,
+    value: u64,
+    accounts: BasketTokenAccounts<'_, 'info>,
+) -> Result<u64> {
+    let amount = price.amount(
+        value,
+        basket_asset.decimals,
+        basket_asset.value_decimals,
+        Rounding::Floor,
+    )?;
+    require!(
+        amount <= basket_asset.balance,
+        VaultError::InsufficientBasketBalance
+    );
+    transfer_from_vault(
+        vault,
+        accounts.basket_vault,
+        accounts.mint,
+        accounts.receiver,
+        accounts.token_program,
+        amount,
+    )?;
+
+    basket_asset.balance -= amount;
+    vault.total_assets = math::add(vault.total_assets, value)?;
+    vault.revalue_basket_asset(basket_asset, price)?;
+    Ok(amount)
+}
+
+/// Pay `amount` of USDC out of `asset_vault`, signed by the vault PDA
+fn transfer_from_vault<'info>(
+    vault: &Account<'info, Vault>,
+    asset_vault: &InterfaceAccount<'info, TokenAccount>,
+    asset_mint: &InterfaceAccount<'info, Mint>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[b"vault".as_ref(), vault.address_seed(), &[vault.bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: asset_vault.to_account_info(),
+        mint: asset_mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: vault.to_account_info(),
+    };
+    let cpi_program = token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::transfer_checked(cpi_ctx, amount, asset_mint.decimals)
+}
+
+/// Value one strategy of `harvest` from its `remaining_accounts` chunk and set its
+/// `allocated` to that value, returning the change
+fn harvest_strategy<'info>(
+    accounts: &Harvest<'info>,
+    item: &'info [AccountInfo<'info>],
+) -> Result<StrategyPnl> {
+    let vault_key = accounts.vault.key();
+    let mut strategy = load_strategy_item(&vault_key, item)?;
+    let (strategy_info, adapter_program, reserve) = (&item[0], &item[1], &item[2]);
+
+    let seeds = &[
+        b"strategy".as_ref(),
+        vault_key.as_ref(),
+        strategy.adapter_program.as_ref(),
+        &[strategy.bump],
+    ];
+    let value = strategy::total_value(
+        strategy::AdapterAccounts {
+            adapter_program,
+            strategy: strategy_info.clone(),
+            asset_vault: accounts.asset_vault.to_account_info(),
+            reserve: reserve.clone(),
+            asset_mint: accounts.asset_mint.to_account_info(),
+            token_program: accounts.token_program.to_account_info(),
+            remaining: &item[STRATEGY_ITEM_ACCOUNTS..],
+        },
+        seeds,
+    )?;
+
+    let pnl = StrategyPnl {
+        adapter_program: strategy.adapter_program,
+        value,
+        gain: value.saturating_sub(strategy.allocated),
+        loss: strategy.allocated.saturating_sub(value),
+    };
+    strategy.allocated = value;
+    strategy.exit(&crate::ID)?;
+    Ok(pnl)
+}
+
+/// Iterator over the strategies in `remaining_accounts`, each `STRATEGY_ITEM_ACCOUNTS` followed
+/// by as many adapter accounts as its entry in `adapter_accounts`
+struct StrategyItems<'a, 'info> {
+    remaining: &'info [AccountInfo<'info>],
+    adapter_accounts: std::slice::Iter<'a, u8>,
+}
+
+impl<'a, 'info> StrategyItems<'a, 'info> {
+    fn new(remaining: &'info [AccountInfo<'info>], adapter_accounts: &'a [u8]) -> Self {
+        Self {
+            remaining,
+            adapter_accounts: adapter_accounts.iter(),
+        }
+    }
+}
+
+impl<'info> Iterator for StrategyItems<'_, 'info> {
+    type Item = Result<&'info [AccountInfo<'info>]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let count = self.adapter_accounts.next()?;
+        let len = STRATEGY_ITEM_ACCOUNTS + usize::from(*count);
+        if self.remaining.len() < len {
+            return Some(Err(ErrorCode::AccountNotEnoughKeys.into()));
+        }
+        let (item, rest) = self.remaining.split_at(len);
+        self.remaining = rest;
+        Some(Ok(item))
+    }
+}
+
+/// The `Strategy` of a remaining-accounts `item`, checked to belong to the vault at `vault` and
+/// to match the item's adapter program and reserve
+fn load_strategy_item<'info>(
+    vault: &Pubkey,
+    item: &'info [AccountInfo<'info>],
+) -> Result<Account<'info, Strategy>> {
+    // In `STRATEGY_ITEM_ACCOUNTS` order (`StrategyItems` checked the length)
+    let (strategy_info, adapter_program, reserve) = (&item[0], &item[1], &item[2]);
+    let strategy: Account<Strategy> = Account::try_from(strategy_info)?;
+    require_keys_eq!(strategy.vault, *vault, ErrorCode::ConstraintSeeds);
+    require_keys_eq!(
+        adapter_program.key(),
+        strategy.adapter_program,
+        ErrorCode::ConstraintAddress
+    );
+    require_keys_eq!(
+        reserve.key(),
+        strategy.reserve,
+        ErrorCode::ConstraintAddress
+    );
+    Ok(strategy)
+}
+
+/// `StrategyQueuedEvent` of action `id` if it calls `add_strategy`, `None` for any other call
+fn strategy_queued_event(
+    id: u64,
+    eta: i64,
+    accounts: &[ActionAccount],
+    data: &[u8],
+) -> Result<Option<StrategyQueuedEvent>> {
+    if data.get(..8) != Some(&crate::instruction::AddStrategy::DISCRIMINATOR[..]) {
+        return Ok(None);
+    }
+    let args = crate::instruction::AddStrategy::try_from_slice(&data[8..])
+        .map_err(|_| ErrorCode::InstructionDidNotDeserialize)?;
+    // In `AddStrategy` order: vault, timelock, payer, adapter program, reserve, ...
+    require!(accounts.len() >= 5, ErrorCode::AccountNotEnoughKeys);
+    Ok(Some(StrategyQueuedEvent {
+        id,
+        adapter_program: accounts[3].pubkey,
+        reserve: accounts[4].pubkey,
+        max_debt: args.max_debt,
+        target_weight_bps: args.target_weight_bps,
+        eta,
+    }))
+}
+
+/// Make sure `asset_vault` can pay out `payout`, withdrawing from `strategies` in the order
+/// given until it also holds the liquidity buffer again. Without strategies (or once they run
+/// dry) only the payout itself has to be covered
+fn deallocate_for_redeem<'info>(
+    vault: &mut Account<'info, Vault>,
+    asset_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    asset_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    strategies: StrategyItems<'_, 'info>,
+    payout: u64,
+    events: &EventCpi<'info>,
+) -> Result<()> {
+    let target = math::add(payout, vault.liquidity_buffer()?)?;
+    source_liquidity(
+        vault,
+        asset_vault,
+        asset_mint,
+        token_program,
+        strategies,
+        target,
+        events,
+    )?;
+    require!(
+        payout <= vault.idle_assets(asset_vault),
+        VaultError::InsufficientIdleAssets
+    );
+    Ok(())
+}
+
+/// Withdraw from `strategies` in the order given until `asset_vault` holds `target` idle USDC
+/// or they run dry
+fn source_liquidity<'info>(
+    vault: &mut Account<'info, Vault>,
+    asset_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    asset_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    strategies: StrategyItems<'_, 'info>,
+    target: u64,
+    events: &EventCpi<'info>,
+) -> Result<()> {
+    let vault_key = vault.key();
+    for item in strategies {
+        let needed = target.saturating_sub(vault.idle_assets(asset_vault));
+        if needed == 0 {
+            break;
+        }
+        let item = item?;
+        let mut strategy = load_strategy_item(&vault_key, item)?;
+        let amount = needed.min(strategy.allocated);
+        if amount == 0 {
+            continue;
+        }
+
+        let before = asset_vault.amount;
+        invoke_strategy(
+            &vault_key,
+            &strategy,
+            strategy::AdapterAccounts {
+                adapter_program: &item[1],
+                strategy: item[0].clone(),
+                asset_vault: asset_vault.to_account_info(),
+                reserve: item[2].clone(),
+                asset_mint: asset_mint.to_account_info(),
+                token_program: token_program.to_account_info(),
+                remaining: &item[STRATEGY_ITEM_ACCOUNTS..],
+            },
+            strategy::STRATEGY_WITHDRAW_DISCRIMINATOR,
+            amount,
+        )?;
+        asset_vault.reload()?;
+        let received = math::sub(asset_vault.amount, before)?;
+
+        let repaid = received.min(strategy.allocated);
+        strategy.allocated -= repaid;
+        vault.allocated_assets = math::sub(vault.allocated_assets, repaid)?;
+        strategy.exit(&crate::ID)?;
+
+        events.emit(DeallocateEvent {
+            adapter_program: strategy.adapter_program,
+            amount,
+            received,
+            allocated: strategy.allocated,
+        })?;
+    }
+    Ok(())
+}
+
+/// Call the adapter of `strategy` (of the vault at `vault`) with `amount`, signed by the
+/// `Strategy` PDA
+fn invoke_strategy<'info>(
+    vault: &Pubkey,
+    strategy: &Account<'info, Strategy>,
+    accounts: strategy::AdapterAccounts<'_, 'info>,
+    discriminator: [u8; 8],
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[
+        b"strategy".as_ref(),
+        vault.as_ref(),
+        strategy.adapter_program.as_ref(),
+        &[strategy.bump],
+    ];
+    strategy::invoke_adapter(discriminator, amount, accounts, seeds)
+}
+
+/// Metaplex metadata of the share token: fungible, no royalties, creators or collection
+fn share_metadata(name: String, symbol: String, uri: String) -> DataV2 {
+    DataV2 {
+        name,
+        symbol,
+        uri,
+        seller_fee_basis_points: 0,
+        creators: None,
+        collection: None,
+        uses: None,
+    }
+}
+
+/// Audit-log record of a privileged instruction, for it to emit
+fn admin_action_event(actor: Pubkey, action: AdminAction) -> Result<AdminActionEvent> {
+    Ok(AdminActionEvent {
+        actor,
+        action,
+        timestamp: Clock::get()?.unix_timestamp,
+    })
+}
+
+/// The `#[event_cpi]` accounts of an instruction, for the helpers emitting its events
+/// (`emit_cpi!` needs the instruction's `ctx` in scope)
+struct EventCpi<'info> {
+    authority: AccountInfo<'info>,
+    bump: u8,
+}
+
+impl EventCpi<'_> {
+    /// Emit `event` the way `emit_cpi!` does: as the data of a self-CPI signed by the event
+    /// authority, which indexers read back from the transaction's inner instructions
+    fn emit<E: anchor_lang::Event>(&self, event: E) -> Result<()> {
+        let data: Vec<u8> = anchor_lang::event::EVENT_IX_TAG_LE
+            .iter()
+            .copied()
+            .chain(event.data())
+            .collect();
+        let ix = Instruction::new_with_bytes(
+            crate::ID,
+            &data,
+            vec![AccountMeta::new_readonly(*self.authority.key, true)],
+        );
+        invoke_signed(
+            &ix,
+            &[self.authority.clone()],
+            &[&[b"__event_authority", &[self.bump]]],
+        )?;
+        Ok(())
+    }
+}
+
+// Accounts
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Vault::INIT_SPACE,
+        seeds = [b"vault", asset_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Both mints must belong to `token_program` (SPL Token or Token-2022)
+    #[account(mint::token_program = token_program)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+    /// May have a transfer hook: the vault only mints and burns shares, which don't invoke it
+    #[account(mint::token_program = token_program)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = asset_mint,
+        token::authority = vault,
+        token::token_program = token_program,
+    )]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Permanently locked first-deposit shares (Uniswap MINIMUM_LIQUIDITY style)
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"dead_shares", vault.key().as_ref()],
+        bump,
+        token::mint = share_mint,
+        token::authority = vault,
+        token::token_program = token_program,
+    )]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Only destination allowed for `emergency_withdraw`
+    #[account(constraint = recovery_account.mint == asset_mint.key())]
+    pub recovery_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Metaplex metadata PDA of `share_mint`, created by the metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), share_mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub share_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VaultMetadata::INIT_SPACE,
+        seeds = [b"vault_metadata", vault.key().as_ref()],
+        bump
+    )]
+    pub vault_metadata: Account<'info, VaultMetadata>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitRegistry<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = Registry::space(0),
+        seeds = [b"registry"],
+        bump
+    )]
+    pub registry: Account<'info, Registry>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// This program's program data account (its address is derived from the program id)
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = anchor_lang::solana_program::bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ VaultError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetRegistryCreator<'info> {
+    #[account(mut, seeds = [b"registry"], bump = registry.bump, has_one = admin)]
+    pub registry: Account<'info, Registry>,
+
+    pub admin: Signer<'info>,
+}
+
+/// The accounts of `initialize`, plus the registry the new vault is listed in
+#[derive(Accounts)]
+pub struct CreateVault<'info> {
+    #[account(mut, seeds = [b"registry"], bump = registry.bump)]
+    pub registry: Account<'info, Registry>,
+
+    pub vault_accounts: Initialize<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RegisterVault<'info> {
+    #[account(mut, seeds = [b"registry"], bump = registry.bump, has_one = admin)]
+    pub registry: Account<'info, Registry>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Wallet credited with the minted shares (may differ from `user`)
+    pub receiver: UncheckedAccount<'info>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receiver's share ATA, created on first deposit (paid by `user`)
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = share_mint,
+        associated_token::authority = receiver,
+        associated_token::token_program = token_program,
+    )]
+    pub receiver_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Vault-owned account holding the shares locked on the first deposit
+    #[account(mut, address = vault.dead_shares_account)]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), receiver.key().as_ref()],
+        bump
+    )]
+    pub receiver_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), user.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `receiver` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), receiver.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Present to update `receiver`'s cost basis (see `open_cost_basis`)
+    #[account(
+        mut,
+        seeds = [b"cost_basis", vault.key().as_ref(), receiver.key().as_ref()],
+        bump = cost_basis.bump
+    )]
+    pub cost_basis: Option<Account<'info, CostBasis>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of `receiver`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub receiver_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MintShares<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's share ATA, created on first mint
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = share_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Vault-owned account holding the shares locked on the first deposit
+    #[account(mut, address = vault.dead_shares_account)]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), user.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Present to update `user`'s cost basis (see `open_cost_basis`)
+    #[account(
+        mut,
+        seeds = [b"cost_basis", vault.key().as_ref(), user.key().as_ref()],
+        bump = cost_basis.bump
+    )]
+    pub cost_basis: Option<Account<'info, CostBasis>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's USDC ATA, created if missing
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = asset_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    /// Optional destination for the redeemed USDC (e.g. an exchange deposit address)
+    #[account(mut, constraint = receiver_asset.mint == vault.asset_mint)]
+    pub receiver_asset: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), user.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Present to update `user`'s cost basis (see `open_cost_basis`)
+    #[account(
+        mut,
+        seeds = [b"cost_basis", vault.key().as_ref(), user.key().as_ref()],
+        bump = cost_basis.bump
+    )]
+    pub cost_basis: Option<Account<'info, CostBasis>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of the `receiver_asset` owner, required with `receiver_asset`
+    pub receiver_blocklist: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's USDC ATA, created if missing
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = asset_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), user.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Present to update `user`'s cost basis (see `open_cost_basis`)
+    #[account(
+        mut,
+        seeds = [b"cost_basis", vault.key().as_ref(), user.key().as_ref()],
+        bump = cost_basis.bump
+    )]
+    pub cost_basis: Option<Account<'info, CostBasis>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RequestWithdraw<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, constraint = owner_shares.mint == vault.share_mint)]
+    pub owner_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// The owner's request in the current epoch, created by its first request
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + WithdrawRequest::INIT_SPACE,
+        seeds = [
+            b"withdraw_request",
+            vault.key().as_ref(),
+            owner.key().as_ref(),
+            &vault.withdraw_epoch.to_le_bytes()
+        ],
+        bump
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub owner_position: Account<'info, UserPosition>,
+
+    /// Present when `owner` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), owner.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `owner`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub owner_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelWithdrawRequest<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [
+            b"withdraw_request",
+            vault.key().as_ref(),
+            owner.key().as_ref(),
+            &withdraw_request.epoch.to_le_bytes()
+        ],
+        bump = withdraw_request.bump
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+
+    /// Receives the cancelled shares
+    #[account(mut, constraint = owner_shares.mint == vault.share_mint)]
+    pub owner_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts of `process_epoch`; the strategies to deallocate from follow as remaining accounts
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ProcessEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = keeper.key() == vault.keeper @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    /// Settlement of the epoch being processed, paid for by the keeper
+    #[account(
+        init,
+        payer = keeper,
+        space = 8 + WithdrawEpoch::INIT_SPACE,
+        seeds = [
+            b"withdraw_epoch",
+            vault.key().as_ref(),
+            &vault.withdraw_epoch.to_le_bytes()
+        ],
+        bump
+    )]
+    pub withdraw_epoch: Account<'info, WithdrawEpoch>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of `claim_withdraw`; the processed `WithdrawEpoch`s to claim follow as remaining
+/// accounts
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimWithdraw<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [
+            b"withdraw_request",
+            vault.key().as_ref(),
+            owner.key().as_ref(),
+            &withdraw_request.epoch.to_le_bytes()
+        ],
+        bump = withdraw_request.bump
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Owner's USDC ATA, created if missing
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = asset_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Blocklist PDA of `owner`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub owner_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddBasketAsset<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// The stablecoin accepted, on SPL Token or Token-2022
+    #[account(
+        mint::token_program = token_program,
+        constraint = mint.key() != vault.asset_mint @ VaultError::InvalidBasketAsset
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + BasketAsset::INIT_SPACE,
+        seeds = [b"basket_asset", vault.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub basket_asset: Account<'info, BasketAsset>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"basket_vault", vault.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault,
+        token::token_program = token_program,
+    )]
+    pub basket_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Pyth `PriceUpdateV2` account of `feed_id`, decoded by `oracle::UsdPrice::load_feed`
+    pub price_update: UncheckedAccount<'info>,
+
+    /// Token program of `mint`
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateBasketAsset<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut, has_one = vault)]
+    pub basket_asset: Account<'info, BasketAsset>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevalueBasketAsset<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, has_one = vault)]
+    pub basket_asset: Account<'info, BasketAsset>,
+
+    /// CHECK: Pyth `PriceUpdateV2` account, decoded by `BasketAsset::load_price`
+    #[account(address = basket_asset.price_feed)]
+    pub price_update: UncheckedAccount<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositBasket<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut, has_one = vault, has_one = mint)]
+    pub basket_asset: Account<'info, BasketAsset>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, token::mint = mint)]
+    pub user_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = basket_asset.token_account)]
+    pub basket_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Pyth `PriceUpdateV2` account, decoded by `BasketAsset::load_price`
+    #[account(address = basket_asset.price_feed)]
+    pub price_update: UncheckedAccount<'info>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's share ATA, created on first deposit
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = share_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault-owned account holding the shares locked on the first deposit
+    #[account(mut, address = vault.dead_shares_account)]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    /// Token program of the shares
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of `mint`
+    pub basket_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RedeemBasket<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut, has_one = vault, has_one = mint)]
+    pub basket_asset: Account<'info, BasketAsset>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the basket asset
+    #[account(mut, token::mint = mint)]
+    pub user_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = basket_asset.token_account)]
+    pub basket_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Pyth `PriceUpdateV2` account, decoded by `BasketAsset::load_price`
+    #[account(address = basket_asset.price_feed)]
+    pub price_update: UncheckedAccount<'info>,
+
+    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// Prices the utilization-scaled redeem fee
+    #[account(constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), user.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    /// Token program of the shares
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of `mint`
+    pub basket_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RedeemProportional<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the USDC part
+    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), user.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetJuniorTranche<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    /// Minted by the vault PDA; must be the vault's junior share mint once one is set
+    #[account(
+        mint::authority = vault,
+        mint::token_program = token_program,
+        constraint = vault.junior_share_mint == Pubkey::default()
+            || vault.junior_share_mint == junior_share_mint.key()
+            @ VaultError::InvalidJuniorShareMint
+    )]
+    pub junior_share_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositJunior<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.junior_share_mint @ VaultError::InvalidJuniorShareMint)]
+    pub junior_share_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's junior share ATA, created on first deposit
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = junior_share_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_junior_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RedeemJunior<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    pub user: Signer<'info>,
+
+    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.junior_share_mint @ VaultError::InvalidJuniorShareMint)]
+    pub junior_share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, constraint = user_junior_shares.mint == vault.junior_share_mint)]
+    pub user_junior_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Present when `user` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetMigrationTarget<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    /// Another vault of the same asset mint; omit to close migration
+    #[account(
+        seeds = [b"vault", target_vault.address_seed()],
+        bump = target_vault.bump,
+        constraint = target_vault.asset_mint == vault.asset_mint
+            && target_vault.key() != vault.key()
+            @ VaultError::InvalidMigrationTarget
+    )]
+    pub target_vault: Option<Account<'info, Vault>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    #[account(mut, seeds = [b"vault", source_vault.address_seed()], bump = source_vault.bump)]
+    pub source_vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", target_vault.address_seed()],
+        bump = target_vault.bump,
+        constraint = target_vault.key() == source_vault.migration_target
+            @ VaultError::InvalidMigrationTarget
+    )]
+    pub target_vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Shared by both vaults
+    #[account(address = source_vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = source_vault.share_mint)]
+    pub source_share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, constraint = user_source_shares.mint == source_vault.share_mint)]
+    pub user_source_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = source_asset_vault.key() == source_vault.asset_vault)]
+    pub source_asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", source_vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub source_position: Account<'info, UserPosition>,
+
+    #[account(mut, address = target_vault.share_mint)]
+    pub target_share_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's target share ATA, created if missing
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = target_share_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_target_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = target_asset_vault.key() == target_vault.asset_vault)]
+    pub target_asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = target_vault.dead_shares_account)]
+    pub target_dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", target_vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub target_position: Account<'info, UserPosition>,
+
+    /// Present when `user` is on the source vault's allowlist
+    #[account(
+        seeds = [b"allowlist", source_vault.key().as_ref(), user.key().as_ref()],
+        bump = source_allowlist_entry.bump
+    )]
+    pub source_allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Present when `user` is on the target vault's allowlist
+    #[account(
+        seeds = [b"allowlist", target_vault.key().as_ref(), user.key().as_ref()],
+        bump = target_allowlist_entry.bump
+    )]
+    pub target_allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `user` in the source vault, must be empty (see
+    /// `BlocklistEntry::check_not_blocked`)
+    pub source_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of `user` in the target vault, must be empty
+    pub target_blocklist: UncheckedAccount<'info>,
+
+    /// Token program of both vaults' asset and share mints
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MetaDeposit<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Owner of the deposited USDC (verified by signature)
+    pub owner: UncheckedAccount<'info>,
+
+    /// Owner's USDC, with the vault PDA approved as delegate for at least `assets`
+    #[account(
+        mut,
+        constraint = owner_asset.mint == vault.asset_mint,
+        constraint = owner_asset.owner == owner.key()
+    )]
+    pub owner_asset: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Wallet credited with the minted shares (signed off on by the owner)
+    pub receiver: UncheckedAccount<'info>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receiver's share ATA, created on first deposit (paid by the relayer)
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        associated_token::mint = share_mint,
+        associated_token::authority = receiver,
+        associated_token::token_program = token_program,
+    )]
+    pub receiver_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Vault-owned account holding the shares locked on the first deposit
+    #[account(mut, address = vault.dead_shares_account)]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), receiver.key().as_ref()],
+        bump
+    )]
+    pub receiver_position: Account<'info, UserPosition>,
+
+    /// Present when `owner` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), owner.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `receiver` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), receiver.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    /// Registry entry of `relayer`, required while `relayers_restricted`; counts its daily volume
+    #[account(
+        mut,
+        seeds = [b"relayer", vault.key().as_ref(), relayer.key().as_ref()],
+        bump = relayer_entry.bump
+    )]
+    pub relayer_entry: Option<Account<'info, RelayerEntry>>,
+
+    /// CHECK: Blocklist PDA of `owner`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub owner_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of `relayer`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub relayer_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of `receiver`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub receiver_blocklist: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + UserNonce::INIT_SPACE,
+        seeds = [b"nonce", owner.key().as_ref()],
+        bump
+    )]
+    pub user_nonce: Account<'info, UserNonce>,
+
+    /// CHECK: Instructions sysvar, to find the Ed25519 verify instruction
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(shares: u64, receiver: Pubkey)]
+pub struct MetaRedeem<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Owner of shares (verified by signature)
+    pub owner: UncheckedAccount<'info>,
+
+    /// Owner's shares, with the vault PDA approved as delegate for at least `shares`
+    #[account(
+        mut,
+        constraint = owner_shares.mint == vault.share_mint,
+        constraint = owner_shares.owner == owner.key()
+    )]
+    pub owner_shares: InterfaceAccount<'info, TokenAccount>,
+
+    /// USDC destination signed off on by the owner
+    #[account(
+        mut,
+        constraint = receiver_asset.mint == vault.asset_mint,
+        constraint = receiver_asset.owner == receiver
+    )]
+    pub receiver_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"position", vault.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub owner_position: Account<'info, UserPosition>,
+
+    /// Present when `owner` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), owner.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `owner` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), owner.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Owner's linked Ethereum address, required by `meta_redeem_evm`
+    #[account(
+        seeds = [b"evm_owner", vault.key().as_ref(), owner.key().as_ref()],
+        bump = evm_owner.bump
+    )]
+    pub evm_owner: Option<Account<'info, EvmOwner>>,
+
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    /// Relayer's USDC account receiving the relayer fee
+    #[account(
+        mut,
+        constraint = relayer_asset.mint == vault.asset_mint,
+        constraint = relayer_asset.owner == relayer.key()
+    )]
+    pub relayer_asset: InterfaceAccount<'info, TokenAccount>,
+
+    /// Registry entry of `relayer`, required while `relayers_restricted`; counts its daily volume
+    #[account(
+        mut,
+        seeds = [b"relayer", vault.key().as_ref(), relayer.key().as_ref()],
+        bump = relayer_entry.bump
+    )]
+    pub relayer_entry: Option<Account<'info, RelayerEntry>>,
+
+    /// CHECK: Blocklist PDA of `owner`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub owner_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of `relayer`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub relayer_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Blocklist PDA of `receiver`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub receiver_blocklist: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + UserNonce::INIT_SPACE,
+        seeds = [b"nonce", owner.key().as_ref()],
+        bump
+    )]
+    pub user_nonce: Account<'info, UserNonce>,
+
+    /// CHECK: Instructions sysvar, to find the Ed25519 verify instruction
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct BatchMetaRedeem<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    pub relayer: Signer<'info>,
+
+    /// Relayer's USDC account receiving the summed relayer fees
+    #[account(
+        mut,
+        constraint = relayer_asset.mint == vault.asset_mint,
+        constraint = relayer_asset.owner == relayer.key()
+    )]
+    pub relayer_asset: InterfaceAccount<'info, TokenAccount>,
+
+    /// Registry entry of `relayer`, required while `relayers_restricted`; counts its daily volume
+    #[account(
+        mut,
+        seeds = [b"relayer", vault.key().as_ref(), relayer.key().as_ref()],
+        bump = relayer_entry.bump
+    )]
+    pub relayer_entry: Option<Account<'info, RelayerEntry>>,
+
+    /// CHECK: Blocklist PDA of `relayer`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub relayer_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: Instructions sysvar, to find the Ed25519 verify instructions
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct LinkEvmAddress<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + EvmOwner::INIT_SPACE,
+        seeds = [b"evm_owner", vault.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub evm_owner: Account<'info, EvmOwner>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UnlinkEvmAddress<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"evm_owner", vault.key().as_ref(), owner.key().as_ref()],
+        bump = evm_owner.bump
+    )]
+    pub evm_owner: Account<'info, EvmOwner>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct OpenCostBasis<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + CostBasis::INIT_SPACE,
+        seeds = [b"cost_basis", vault.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub cost_basis: Account<'info, CostBasis>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CloseCostBasis<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"cost_basis", vault.key().as_ref(), owner.key().as_ref()],
+        bump = cost_basis.bump
+    )]
+    pub cost_basis: Account<'info, CostBasis>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSessionKey<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + SessionKey::INIT_SPACE,
+        seeds = [b"session", vault.key().as_ref(), owner.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeSessionKey<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [
+            b"session",
+            vault.key().as_ref(),
+            owner.key().as_ref(),
+            session.session_key.as_ref()
+        ],
+        bump = session.bump
+    )]
+    pub session: Account<'info, SessionKey>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SessionRedeem<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    pub session_key: Signer<'info>,
+
+    /// CHECK: Owner of shares, bound to `session_key` by the session PDA seeds
+    pub owner: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"session",
+            vault.key().as_ref(),
+            owner.key().as_ref(),
+            session_key.key().as_ref()
+        ],
+        bump = session.bump
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    /// Owner's shares, with the vault PDA approved as delegate for at least `shares`
+    #[account(
+        mut,
+        constraint = owner_shares.mint == vault.share_mint,
+        constraint = owner_shares.owner == owner.key()
+    )]
+    pub owner_shares: InterfaceAccount<'info, TokenAccount>,
+
+    /// Owner's USDC account, the only destination a session key can redeem to
+    #[account(
+        mut,
+        constraint = owner_asset.mint == vault.asset_mint,
+        constraint = owner_asset.owner == owner.key()
+    )]
+    pub owner_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"position", vault.key().as_ref(), owner.key().as_ref()],
+        bump = owner_position.bump
+    )]
+    pub owner_position: Account<'info, UserPosition>,
+
+    /// Present when `owner` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), owner.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// Present when `owner` is on the allowlist (required in permissioned mode)
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), owner.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// CHECK: Blocklist PDA of `owner`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub owner_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelNonce<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + UserNonce::INIT_SPACE,
+        seeds = [b"nonce", owner.key().as_ref()],
+        bump
+    )]
+    pub user_nonce: Account<'info, UserNonce>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Read-only vault access for preview/view instructions (result is written via return data)
+#[derive(Accounts)]
+pub struct ViewVault<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+}
+
+/// Read-only accounts for the redeem previews, which charge `owner` the fee `redeem` would
+#[derive(Accounts)]
+pub struct ViewRedeem<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Owner of the previewed shares, only used to derive its PDAs
+    pub owner: UncheckedAccount<'info>,
+
+    /// `owner`'s position, absent before their first deposit
+    #[account(
+        seeds = [b"position", vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Option<Account<'info, UserPosition>>,
+
+    /// Present when `owner` is exempt from fees
+    #[account(
+        seeds = [b"fee_exempt", vault.key().as_ref(), owner.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+}
+
+impl ViewRedeem<'_> {
+    /// `owner`'s position, empty (no lockup, cooldown or early-exit penalty) before their first
+    /// deposit, as `redeem` creates it
+    fn owner_position(&self) -> UserPosition {
+        self.user_position.as_deref().cloned().unwrap_or_default()
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitRateHistory<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + RateHistory::INIT_SPACE,
+        seeds = [b"rate_history", vault.key().as_ref()],
+        bump
+    )]
+    pub rate_history: Account<'info, RateHistory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RecordRate<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"rate_history", vault.key().as_ref()],
+        bump = rate_history.bump
+    )]
+    pub rate_history: Account<'info, RateHistory>,
+}
+
+/// Read-only `RateHistory` access for `rate_apy` (result is written via return data)
+#[derive(Accounts)]
+pub struct ViewRateHistory<'info> {
+    #[account(seeds = [b"rate_history", rate_history.vault.as_ref()], bump = rate_history.bump)]
+    pub rate_history: Account<'info, RateHistory>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = fee_manager.key() == vault.fee_manager @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub fee_manager: Signer<'info>,
+}
+
+/// Vault configuration changes depositors must see coming: `timelock` must sign, so they only
+/// ever run through a queued action
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TimelockedConfig<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"timelock", vault.key().as_ref()],
+        bump = timelock.bump,
+        constraint = timelock.to_account_info().is_signer @ VaultError::Unauthorized
+    )]
+    pub timelock: Account<'info, Timelock>,
+}
+
+/// Authority-gated vault configuration changes. Like every role signer in this program,
+/// `authority` only has to sign, so it may be a PDA signing via CPI (e.g. a Squads vault)
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+}
+
+/// `timelock` is the vault's timelock PDA whether or not it exists, so a vault with one can't
+/// renounce while leaving it able to run queued actions
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RenounceAuthority<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Timelock PDA of `vault`, disabled if it exists
+    #[account(mut, seeds = [b"timelock", vault.key().as_ref()], bump)]
+    pub timelock: UncheckedAccount<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = pending_authority.key() == vault.pending_authority @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub pending_authority: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetPauseFlags<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = pauser.key() == vault.pauser @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub pauser: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct GuardianPause<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = guardian.key() == vault.guardian @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub guardian: Signer<'info>,
+}
+
+/// `price_update` must be the vault's configured Pyth price feed
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CheckPrice<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Pyth `PriceUpdateV2` account, decoded by `oracle::UsdPrice::load`
+    #[account(address = vault.price_feed)]
+    pub price_update: UncheckedAccount<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ResetDepegBreaker<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = guardian.key() == vault.guardian @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub guardian: Signer<'info>,
+
+    /// CHECK: Pyth `PriceUpdateV2` account, decoded by `oracle::UsdPrice::load`; unused while
+    /// the breaker is off
+    #[account(address = vault.price_feed)]
+    pub price_update: UncheckedAccount<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SyncAssets<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = keeper.key() == vault.keeper @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub keeper: Signer<'info>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the surplus under `SurplusPolicy::Fees`: the vault's `fee_recipient`
+    #[account(
+        mut,
+        address = vault.fee_recipient,
+        constraint = fee_destination.mint == vault.asset_mint
+    )]
+    pub fee_destination: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// `timelock` must sign, so strategies are only ever added through a queued action
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddStrategy<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"timelock", vault.key().as_ref()],
+        bump = timelock.bump,
+        constraint = timelock.to_account_info().is_signer @ VaultError::Unauthorized
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    /// Pays the strategy's rent, usually the timelock admin executing the action
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Strategy adapter program implementing the `strategy` interface
+    #[account(executable)]
+    pub adapter_program: UncheckedAccount<'info>,
+
+    /// Adapter-owned USDC account receiving allocations
+    #[account(
+        constraint = reserve.mint == vault.asset_mint,
+        constraint = reserve.key() != vault.asset_vault
+    )]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Strategy::INIT_SPACE,
+        seeds = [b"strategy", vault.key().as_ref(), adapter_program.key().as_ref()],
+        bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// `timelock` must sign, so strategy limits only change through a queued action
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateStrategy<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"timelock", vault.key().as_ref()],
+        bump = timelock.bump,
+        constraint = timelock.to_account_info().is_signer @ VaultError::Unauthorized
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref(), strategy.adapter_program.as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+}
+
+/// `timelock` must sign, so a strategy is only removed through a queued action. The rent goes to
+/// the timelock admin, like that of pruned actions, whoever executes it
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RemoveStrategy<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"timelock", vault.key().as_ref()],
+        bump = timelock.bump,
+        constraint = timelock.to_account_info().is_signer @ VaultError::Unauthorized
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    /// CHECK: Rent destination, the timelock admin
+    #[account(mut, address = timelock.admin)]
+    pub admin: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"strategy", vault.key().as_ref(), strategy.adapter_program.as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+}
+
+/// Accounts of `allocate` and `deallocate`; adapter-specific accounts follow as remaining
+/// accounts
+#[event_cpi]
+#[derive(Accounts)]
+pub struct StrategyTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = keeper.key() == vault.keeper @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref(), strategy.adapter_program.as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// CHECK: The strategy's adapter program
+    #[account(address = strategy.adapter_program, executable)]
+    pub adapter_program: UncheckedAccount<'info>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = strategy.reserve)]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> StrategyTransfer<'info> {
+    /// Interface accounts of the strategy's adapter, followed by `remaining`
+    fn adapter_accounts<'a>(
+        &'a self,
+        remaining: &'a [AccountInfo<'info>],
+    ) -> strategy::AdapterAccounts<'a, 'info> {
+        strategy::AdapterAccounts {
+            adapter_program: &self.adapter_program,
+            strategy: self.strategy.to_account_info(),
+            asset_vault: self.asset_vault.to_account_info(),
+            reserve: self.reserve.to_account_info(),
+            asset_mint: self.asset_mint.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            remaining,
+        }
+    }
+}
+
+/// Accounts of `emergency_exit_strategy`; adapter-specific accounts follow as remaining accounts
+#[event_cpi]
+#[derive(Accounts)]
+pub struct EmergencyExitStrategy<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = guardian.key() == vault.guardian @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub guardian: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref(), strategy.adapter_program.as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// CHECK: The strategy's adapter program
+    #[account(address = strategy.adapter_program, executable)]
+    pub adapter_program: UncheckedAccount<'info>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = strategy.reserve)]
+    pub reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> EmergencyExitStrategy<'info> {
+    /// Interface accounts of the strategy's adapter, followed by `remaining`
+    fn adapter_accounts<'a>(
+        &'a self,
+        remaining: &'a [AccountInfo<'info>],
+    ) -> strategy::AdapterAccounts<'a, 'info> {
+        strategy::AdapterAccounts {
+            adapter_program: &self.adapter_program,
+            strategy: self.strategy.to_account_info(),
+            asset_vault: self.asset_vault.to_account_info(),
+            reserve: self.reserve.to_account_info(),
+            asset_mint: self.asset_mint.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            remaining,
+        }
+    }
+}
+
+/// Accounts of `harvest`; each strategy's accounts follow as remaining accounts
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Harvest<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = keeper.key() == vault.keeper @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub keeper: Signer<'info>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ReportLoss<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = keeper.key() == vault.keeper @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref(), strategy.adapter_program.as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FundInsurance<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    pub funder: Signer<'info>,
+
+    #[account(mut, constraint = funder_asset.mint == vault.asset_mint)]
+    pub funder_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts of `rebalance`; each adapter's accounts follow as remaining accounts, `from` first
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Rebalance<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = keeper.key() == vault.keeper @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref(), from_strategy.adapter_program.as_ref()],
+        bump = from_strategy.bump
+    )]
+    pub from_strategy: Account<'info, Strategy>,
+
+    /// CHECK: `from_strategy`'s adapter program
+    #[account(address = from_strategy.adapter_program, executable)]
+    pub from_adapter_program: UncheckedAccount<'info>,
+
+    #[account(mut, address = from_strategy.reserve)]
+    pub from_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref(), to_strategy.adapter_program.as_ref()],
+        bump = to_strategy.bump,
+        constraint = to_strategy.key() != from_strategy.key()
+    )]
+    pub to_strategy: Account<'info, Strategy>,
+
+    /// CHECK: `to_strategy`'s adapter program
+    #[account(address = to_strategy.adapter_program, executable)]
+    pub to_adapter_program: UncheckedAccount<'info>,
+
+    #[account(mut, address = to_strategy.reserve)]
+    pub to_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Rebalance<'info> {
+    /// Interface accounts of the `from` adapter, followed by `remaining`
+    fn from_adapter_accounts<'a>(
+        &'a self,
+        remaining: &'a [AccountInfo<'info>],
+    ) -> strategy::AdapterAccounts<'a, 'info> {
+        strategy::AdapterAccounts {
+            adapter_program: &self.from_adapter_program,
+            strategy: self.from_strategy.to_account_info(),
+            asset_vault: self.asset_vault.to_account_info(),
+            reserve: self.from_reserve.to_account_info(),
+            asset_mint: self.asset_mint.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            remaining,
+        }
+    }
+
+    /// Interface accounts of the `to` adapter, followed by `remaining`
+    fn to_adapter_accounts<'a>(
+        &'a self,
+        remaining: &'a [AccountInfo<'info>],
+    ) -> strategy::AdapterAccounts<'a, 'info> {
+        strategy::AdapterAccounts {
+            adapter_program: &self.to_adapter_program,
+            strategy: self.to_strategy.to_account_info(),
+            asset_vault: self.asset_vault.to_account_info(),
+            reserve: self.to_reserve.to_account_info(),
+            asset_mint: self.asset_mint.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            remaining,
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AccrueFees<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = vault.treasury_shares,
+        constraint = treasury_shares.mint == vault.share_mint
+    )]
+    pub treasury_shares: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = vault.fee_recipient,
+        constraint = fee_recipient.mint == vault.asset_mint
+    )]
+    pub fee_recipient: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddToAllowlist<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AllowlistEntry::INIT_SPACE,
+        seeds = [b"allowlist", vault.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct RemoveFromAllowlist<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"allowlist", vault.key().as_ref(), wallet.as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(relayer: Pubkey)]
+pub struct SetRelayer<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + RelayerEntry::INIT_SPACE,
+        seeds = [b"relayer", vault.key().as_ref(), relayer.as_ref()],
+        bump
+    )]
+    pub relayer_entry: Account<'info, RelayerEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateShareMetadata<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Metaplex metadata PDA of the vault's share mint, updated by the metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), vault.share_mint.as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub share_metadata: UncheckedAccount<'info>,
+
+    pub token_metadata_program: Program<'info, Metadata>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateVaultMetadata<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + VaultMetadata::INIT_SPACE,
+        seeds = [b"vault_metadata", vault.key().as_ref()],
+        bump
+    )]
+    pub vault_metadata: Account<'info, VaultMetadata>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(relayer: Pubkey)]
+pub struct RemoveRelayer<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"relayer", vault.key().as_ref(), relayer.as_ref()],
+        bump = relayer_entry.bump
+    )]
+    pub relayer_entry: Account<'info, RelayerEntry>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct BlockAddress<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = compliance.key() == vault.compliance @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub compliance: Signer<'info>,
+
+    #[account(
+        init,
+        payer = compliance,
+        space = 8 + BlocklistEntry::INIT_SPACE,
+        seeds = [b"blocklist", vault.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub blocklist_entry: Account<'info, BlocklistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct UnblockAddress<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = compliance.key() == vault.compliance @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub compliance: Signer<'info>,
+
+    #[account(
+        mut,
+        close = compliance,
+        seeds = [b"blocklist", vault.key().as_ref(), wallet.as_ref()],
+        bump = blocklist_entry.bump
+    )]
+    pub blocklist_entry: Account<'info, BlocklistEntry>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddFeeExemption<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = fee_manager.key() == vault.fee_manager @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub fee_manager: Signer<'info>,
+
+    #[account(
+        init,
+        payer = fee_manager,
+        space = 8 + FeeExemption::INIT_SPACE,
+        seeds = [b"fee_exempt", vault.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub fee_exemption: Account<'info, FeeExemption>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct RemoveFeeExemption<'info> {
+    #[account(
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        constraint = fee_manager.key() == vault.fee_manager @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub fee_manager: Signer<'info>,
+
+    #[account(
+        mut,
+        close = fee_manager,
+        seeds = [b"fee_exempt", vault.key().as_ref(), wallet.as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Account<'info, FeeExemption>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    /// CHECK: Vault in any layout version; owner, discriminator and authority are checked in
+    /// `migrate_state`, which also makes it one of this program's vault PDAs
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitLegacyAccounts<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        has_one = authority,
+        has_one = share_mint
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mint::token_program = token_program)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    /// Same PDA `initialize` creates for new vaults
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"dead_shares", vault.key().as_ref()],
+        bump,
+        token::mint = share_mint,
+        token::authority = vault,
+        token::token_program = token_program,
+    )]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(constraint = recovery_account.mint == vault.asset_mint)]
+    pub recovery_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitTimelock<'info> {
+    #[account(seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Timelock::INIT_SPACE,
+        seeds = [b"timelock", vault.key().as_ref()],
+        bump
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(action_accounts: Vec<ActionAccount>, data: Vec<u8>)]
+pub struct QueueAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"timelock", timelock.vault.as_ref()],
+        bump = timelock.bump,
+        has_one = admin
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = QueuedAction::space(action_accounts.len(), data.len()),
+        seeds = [
+            b"action",
+            timelock.key().as_ref(),
+            &timelock.next_action_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub action: Account<'info, QueuedAction>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelAction<'info> {
+    #[account(seeds = [b"timelock", timelock.vault.as_ref()], bump = timelock.bump, has_one = admin)]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(mut, close = admin, has_one = timelock)]
+    pub action: Account<'info, QueuedAction>,
+}
+
+/// `timelock` is read-only here: the executed instruction may modify it (or the vault) through
+/// the CPI, and a `mut` account would be written back over those changes on exit
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    #[account(seeds = [b"timelock", timelock.vault.as_ref()], bump = timelock.bump, has_one = admin)]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(mut, close = admin, has_one = timelock)]
+    pub action: Account<'info, QueuedAction>,
+}
+
+/// Closes a queued action past its grace period, refunding the timelock admin (anyone)
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PruneExpiredAction<'info> {
+    #[account(seeds = [b"timelock", timelock.vault.as_ref()], bump = timelock.bump)]
+    pub timelock: Account<'info, Timelock>,
+
+    /// CHECK: Rent destination, the timelock admin
+    #[account(mut, address = timelock.admin)]
+    pub admin: UncheckedAccount<'info>,
+
+    #[account(mut, close = admin, has_one = timelock)]
+    pub action: Account<'info, QueuedAction>,
+}
+
+/// Timelock configuration changes, only reachable through `execute_action` (the timelock PDA
+/// must sign)
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateTimelock<'info> {
+    #[account(
+        mut,
+        seeds = [b"timelock", timelock.vault.as_ref()],
+        bump = timelock.bump,
+        constraint = timelock.to_account_info().is_signer @ VaultError::Unauthorized
+    )]
+    pub timelock: Account<'info, Timelock>,
+}
+
+/// `authority` must be the vault's timelock PDA, so the change is always delayed
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetRecoveryAccount<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump, has_one = authority)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(constraint = authority.key() == timelock.key() @ VaultError::Unauthorized)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"timelock", vault.key().as_ref()], bump = timelock.bump)]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(constraint = recovery_account.mint == vault.asset_mint)]
+    pub recovery_account: InterfaceAccount<'info, TokenAccount>,
+}
+
+/// `timelock` must sign, so funds only leave through a queued action, and only to the recovery
+/// account the timelock also controls
+#[event_cpi]
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"timelock", vault.key().as_ref()],
+        bump = timelock.bump,
+        constraint = timelock.to_account_info().is_signer @ VaultError::Unauthorized
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = vault.recovery_account)]
+    pub recovery_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mandatory co-signer unless the escape hatch has matured; must be a different key
+    #[account(
+        address = vault.guardian @ VaultError::Unauthorized,
+        constraint = guardian.key() != timelock.key() @ VaultError::GuardianSignatureRequired
+    )]
+    pub guardian: Option<Signer<'info>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimWindDown<'info> {
+    #[account(mut, seeds = [b"vault", vault.address_seed()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+
+    pub user: Signer<'info>,
+
+    #[account(mut, constraint = user_asset.mint == vault.asset_mint)]
+    pub user_asset: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, constraint = user_shares.mint == vault.share_mint)]
+    pub user_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Blocklist PDA of `user`, must be empty (see `BlocklistEntry::check_not_blocked`)
+    pub user_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CloseVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.address_seed()],
+        bump = vault.bump,
+        has_one = authority,
+        close = treasury
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut, constraint = asset_vault.key() == vault.asset_vault)]
+    pub asset_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.asset_mint)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = vault.dead_shares_account)]
+    pub dead_shares_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = vault.fee_recipient,
+        constraint = fee_recipient.mint == vault.asset_mint
+    )]
+    pub fee_recipient: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Owner of `fee_recipient`, credited with the closed accounts' rent
+    #[account(mut, address = fee_recipient.owner)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// Closed too when the vault has one
+    #[account(
+        mut,
+        seeds = [b"vault_metadata", vault.key().as_ref()],
+        bump = vault_metadata.bump,
+        close = treasury
+    )]
+    pub vault_metadata: Option<Account<'info, VaultMetadata>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// State
+
+/// Every vault of the program, seeds = [b"registry"]; off-chain tools enumerate the vaults
+/// from this one account
+#[account]
+pub struct Registry {
+    pub admin: Pubkey,
+    /// Only account allowed to `create_vault`; the default pubkey lets anyone create vaults
+    pub creator: Pubkey,
+    pub bump: u8,
+    /// Registered vaults, in order of registration; the account grows with each one
+    pub vaults: Vec<Pubkey>,
+}
+
+impl Registry {
+    /// Size of a registry holding `vaults` vaults, discriminator included
+    pub const fn space(vaults: usize) -> usize {
+        8 + 32 + 32 + 1 + 4 + 32 * vaults
+    }
+
+    pub fn can_create(&self, creator: &Pubkey) -> bool {
+        self.creator == Pubkey::default() || self.creator == *creator
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Vault {
+    pub authority: Pubkey,
+    pub asset_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub asset_vault: Pubkey,
+    pub total_assets: u64,
+    pub total_shares: u64,
+    pub deposit_fee_bps: u16,
+    pub redeem_fee_bps: u16,
+    pub bump: u8,
+    pub pauser: Pubkey,
+    pub paused_deposits: bool,
+    pub paused_redeems: bool,
+    pub paused_meta: bool,
+    pub deposit_cap: u64,
+    pub max_per_user: u64,
+    pub min_deposit: u64,
+    pub min_redeem: u64,
+    pub surplus_policy: SurplusPolicy,
+    pub dead_shares: u64,
+    pub dead_shares_account: Pubkey,
+    pub lockup_seconds: u32,
+    pub cooldown_seconds: u32,
+    pub cooldown_penalty_bps: u16,
+    pub management_fee_bps: u16,
+    pub last_accrual_ts: i64,
+    pub treasury_shares: Pubkey,
+    /// Fee shares already counted in `total_shares` but not yet minted to `treasury_shares`
+    pub unminted_fee_shares: u64,
+    /// Deposit/redeem fees held in `asset_vault` but excluded from `total_assets`
+    pub accrued_fees: u64,
+    pub fee_recipient: Pubkey,
+    pub fee_manager: Pubkey,
+    pub fee_timelock_seconds: u32,
+    pub max_fee_step_bps: u16,
+    pub pending_deposit_fee_bps: u16,
+    pub pending_redeem_fee_bps: u16,
+    /// Earliest execution time of the queued fee update (0 = none queued)
+    pub fee_update_eta: i64,
+    /// Redeem fee at 100% utilization (0 = flat `redeem_fee_bps`)
+    pub max_redeem_fee_bps: u16,
+    pub early_exit_window_seconds: u32,
+    pub early_exit_penalty_bps: u16,
+    /// Proposed authority awaiting `accept_authority` (default = none)
+    pub pending_authority: Pubkey,
+    /// Security role: may only pause everything via `guardian_pause`
+    pub guardian: Pubkey,
+    /// Operations role: runs cranks such as `sync`
+    pub keeper: Pubkey,
+    /// Only destination allowed for `emergency_withdraw`
+    pub recovery_account: Pubkey,
+    /// Time after which `emergency_withdraw` may skip the guardian (0 = not requested)
+    pub emergency_escape_ts: i64,
+    /// Deposits require an allowlist entry
+    pub permissioned: bool,
+    /// Redemptions also require an allowlist entry (only while `permissioned`)
+    pub permissioned_redeems: bool,
+    /// Manages the blocklist
+    pub compliance: Pubkey,
+    /// Layout version, see `migration`
+    pub version: u8,
+    /// Genesis hash of the cluster, binding meta-transaction signatures to it
+    pub genesis_hash: [u8; 32],
+    /// Meta-transactions require the relayer to have a `RelayerEntry`
+    pub relayers_restricted: bool,
+    /// USDC allocated to strategies, part of `total_assets` but not held in `asset_vault`
+    pub allocated_assets: u64,
+    /// Registered `Strategy` accounts, at most `MAX_STRATEGIES`
+    pub strategy_count: u8,
+    /// Sum of the strategies' `target_weight_bps`, at most 10,000
+    pub total_target_weight_bps: u16,
+    /// Fee on harvested strategy gains, minted as shares to `treasury_shares`
+    pub performance_fee_bps: u16,
+    /// Harvested profit, part of `total_assets` but not yet of the share price, as of
+    /// `locked_profit_ts`
+    pub locked_profit: u64,
+    /// Seconds harvested profit takes to unlock
+    pub profit_unlock_duration: u32,
+    pub locked_profit_ts: i64,
+    /// When `locked_profit` is fully unlocked
+    pub profit_unlock_end_ts: i64,
+    /// USDC held in `asset_vault` outside `total_assets` to cover strategy losses
+    pub insurance_assets: u64,
+    /// Socialized loss, as a share of `total_assets`, at which deposits pause (0 = never)
+    pub loss_pause_threshold_bps: u16,
+    /// Share of `total_assets` kept idle for redemptions: `allocate` leaves it in place and
+    /// redemptions passing strategies deallocate to restore it
+    pub buffer_bps: u16,
+    /// Pyth USDC/USD `PriceUpdateV2` account watched by the depeg breaker (default = off)
+    pub price_feed: Pubkey,
+    /// Deviation of the USDC price from $1 that trips the depeg breaker
+    pub max_price_deviation_bps: u16,
+    /// Seconds after which a price is stale, which also trips the breaker
+    pub max_price_age: u32,
+    /// Tripping the breaker pauses redemptions as well as deposits
+    pub depeg_pauses_redeems: bool,
+    /// The depeg breaker paused the vault and awaits `reset_depeg_breaker`
+    pub depeg_tripped: bool,
+    /// Epoch taking withdrawal requests, settled by `process_epoch`
+    pub withdraw_epoch: u64,
+    /// Shares requested for withdrawal and not filled yet: burned already, but part of
+    /// `total_shares` until `process_epoch` fills them
+    pub queued_withdraw_shares: u64,
+    /// USDC held in `asset_vault` for settled but unclaimed withdrawal requests, outside
+    /// `total_assets`
+    pub reserved_withdraw_assets: u64,
+    /// Premium `instant_redeem` charges on top of the redeem fee, kept in `total_assets`
+    pub instant_redeem_premium_bps: u16,
+    /// Created at the single-vault `[b"vault"]` address, before vault PDAs were derived from
+    /// `asset_mint`
+    pub legacy_address: bool,
+    /// Registered `BasketAsset` accounts, at most `MAX_BASKET_ASSETS`
+    pub basket_asset_count: u8,
+    /// Part of `total_assets` held in basket assets, at their last booked prices
+    pub basket_value: u64,
+    /// Mint of the junior tranche's shares, the default pubkey without one; `share_mint` is
+    /// the senior tranche
+    pub junior_share_mint: Pubkey,
+    /// Part of `total_assets` owned by the junior tranche, left out of the senior share price
+    pub junior_assets: u64,
+    /// Junior shares outstanding
+    pub junior_shares: u64,
+    /// Part of the yield on senior assets the senior tranche keeps; the junior tranche gets
+    /// the rest for its first-loss cover
+    pub senior_yield_bps: u16,
+    /// Share of `total_assets` junior redemptions must leave in the junior tranche
+    pub min_subordination_bps: u16,
+    /// Vault `migrate_position` moves positions to, the default pubkey when closed
+    pub migration_target: Pubkey,
+    /// When `start_wind_down` ran, 0 for a running vault
+    pub wind_down_ts: i64,
+    /// When `close_vault` may run, set by `freeze_wind_down` (0 until the rate is frozen)
+    pub wind_down_close_ts: i64,
+    /// Frozen wind-down exchange rate: `claim_wind_down` pays
+    /// `shares * wind_down_assets / wind_down_shares`
+    pub wind_down_assets: u64,
+    pub wind_down_shares: u64,
+    /// Sequence number of the last `VaultSnapshot` emitted, increasing by one per event
+    pub event_sequence: u64,
+    /// Fee timelock queued by a `set_fee_timelock` that loosens it
+    pub pending_fee_timelock_seconds: u32,
+    pub pending_max_fee_step_bps: u16,
+    /// Earliest execution time of the queued fee timelock change (0 = none queued)
+    pub fee_timelock_eta: i64,
+}
+
+/// Vault totals right after a deposit or redeem, carried by its event so indexers can track
+/// the share price without fetching the account
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct VaultSnapshot {
+    /// Per-vault event sequence number, starting at 1 with no gaps
+    pub sequence: u64,
+    pub total_assets: u64,
+    pub total_shares: u64,
+    /// Assets redeemable for `EXCHANGE_RATE_SCALE` shares at the exchange rate after the event
+    pub exchange_rate: u64,
+}
+
+/// Human-readable description of a vault for frontends, stored in its `VaultMetadata`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct VaultInfo {
+    /// Display name, at most `MAX_VAULT_NAME_LEN` bytes
+    #[max_len(MAX_VAULT_NAME_LEN)]
+    pub name: String,
+    /// At most `MAX_VAULT_DESCRIPTION_LEN` bytes
+    #[max_len(MAX_VAULT_DESCRIPTION_LEN)]
+    pub description: String,
+    /// Off-chain details such as documentation or an audit report, at most
+    /// `MAX_VAULT_URI_LEN` bytes
+    #[max_len(MAX_VAULT_URI_LEN)]
+    pub uri: String,
+    pub risk_tier: RiskTier,
+}
+
+/// Risk rating shown to depositors, set by the vault's governance
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum RiskTier {
+    /// Idle stablecoins and overcollateralized lending only
+    Conservative,
+    Moderate,
+    Aggressive,
+}
+
+/// One owner-signed redemption of `batch_meta_redeem`, with the arguments of `meta_redeem`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MetaRedeemRequest {
+    pub shares: u64,
+    pub receiver: Pubkey,
+    pub deadline: i64,
+    pub max_relayer_fee: u64,
+    pub relayer_fee: u64,
+    pub signature: [u8; 64],
+}
+
+/// How `sync` books USDC held by `asset_vault` beyond `total_assets`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum SurplusPolicy {
+    /// Add to `total_assets`, raising the share price
+    Yield,
+    /// Transfer out to a fee destination
+    Fees,
+}
+
+/// Rotatable roles below the vault `authority` (admin), assigned via `set_role`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Sets pause flags
+    Pauser,
+    /// Queues/executes fee updates and manages fee exemptions
+    FeeManager,
+    /// Pauses everything via `guardian_pause`, nothing else
+    Guardian,
+    /// Runs cranks (`sync`)
+    Keeper,
+    /// Blocks and unblocks sanctioned addresses
+    Compliance,
+}
+
+/// Privileged instruction, with its parameters, recorded by `AdminActionEvent`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum AdminAction {
+    Initialize {
+        deposit_fee_bps: u16,
+        redeem_fee_bps: u16,
+        dead_shares: u64,
+        allow_privileged_extensions: bool,
+    },
+    QueueFeeUpdate {
+        new_deposit_fee_bps: u16,
+        new_redeem_fee_bps: u16,
+        eta: i64,
+    },
+    ExecuteFeeUpdate {
+        new_deposit_fee_bps: u16,
+        new_redeem_fee_bps: u16,
+    },
+    SetFeeTimelock {
+        fee_timelock_seconds: u32,
+        max_fee_step_bps: u16,
+    },
+    SetPauseFlags {
+        paused_deposits: bool,
+        paused_redeems: bool,
+        paused_meta: bool,
+    },
+    ProposeAuthority {
+        new_authority: Pubkey,
+    },
+    AcceptAuthority,
+    GuardianPause,
+    SetDepegBreaker {
+        price_feed: Pubkey,
+        max_price_deviation_bps: u16,
+        max_price_age: u32,
+        pauses_redeems: bool,
+    },
+    ResetDepegBreaker,
+    RenounceAuthority,
+    SetRole {
+        role: Role,
+        new_holder: Pubkey,
+    },
+    SetDepositCap {
+        new_deposit_cap: u64,
+    },
+    SetMaxPerUser {
+        new_max_per_user: u64,
+    },
+    SetMinAmounts {
+        min_deposit: u64,
+        min_redeem: u64,
+    },
+    SetLockupSeconds {
+        lockup_seconds: u32,
+    },
+    SetEarlyExitPenalty {
+        early_exit_window_seconds: u32,
+        early_exit_penalty_bps: u16,
+    },
+    SetRedeemFeeCurve {
+        max_redeem_fee_bps: u16,
+    },
+    SetCooldown {
+        cooldown_seconds: u32,
+        cooldown_penalty_bps: u16,
+    },
+    SetManagementFee {
+        management_fee_bps: u16,
+        treasury_shares: Pubkey,
+    },
+    SetPerformanceFee {
+        performance_fee_bps: u16,
+    },
+    SetProfitUnlockDuration {
+        profit_unlock_duration: u32,
+    },
+    SetBufferBps {
+        buffer_bps: u16,
+    },
+    SetInstantRedeemPremium {
+        instant_redeem_premium_bps: u16,
+    },
+    SetFeeRecipient {
+        fee_recipient: Pubkey,
+    },
+    SetPermissioned {
+        permissioned: bool,
+        permissioned_redeems: bool,
+    },
+    AddToAllowlist {
+        wallet: Pubkey,
+    },
+    RemoveFromAllowlist {
+        wallet: Pubkey,
+    },
+    SetRelayersRestricted {
+        relayers_restricted: bool,
+    },
+    SetRelayer {
+        relayer: Pubkey,
+        daily_limit: u64,
+    },
+    RemoveRelayer {
+        relayer: Pubkey,
+    },
+    UpdateShareMetadata {
+        name: String,
+        symbol: String,
+        uri: String,
+    },
+    UpdateVaultMetadata {
+        vault_info: VaultInfo,
+    },
+    BlockAddress {
+        wallet: Pubkey,
+    },
+    UnblockAddress {
+        wallet: Pubkey,
+    },
+    AddFeeExemption {
+        wallet: Pubkey,
+    },
+    RemoveFeeExemption {
+        wallet: Pubkey,
+    },
+    SetSurplusPolicy {
+        policy: SurplusPolicy,
+    },
+    Sync {
+        surplus: u64,
+    },
+    AddStrategy {
+        adapter_program: Pubkey,
+        max_debt: u64,
+        target_weight_bps: u16,
+    },
+    UpdateStrategy {
+        adapter_program: Pubkey,
+        max_debt: u64,
+        target_weight_bps: u16,
+        enabled: bool,
+    },
+    RemoveStrategy {
+        adapter_program: Pubkey,
+    },
+    Allocate {
+        adapter_program: Pubkey,
+        amount: u64,
+    },
+    Deallocate {
+        adapter_program: Pubkey,
+        amount: u64,
+        received: u64,
+    },
+    EmergencyExitStrategy {
+        adapter_program: Pubkey,
+        received: u64,
+    },
+    Rebalance {
+        from: Pubkey,
+        to: Pubkey,
+        amount: u64,
+        moved: u64,
+    },
+    Harvest {
+        gain: u64,
+        loss: u64,
+        fee_shares: u64,
+    },
+    ReportLoss {
+        adapter_program: Pubkey,
+        loss: u64,
+        covered: u64,
+    },
+    ProcessEpoch {
+        epoch: u64,
+        filled_shares: u64,
+        assets: u64,
+    },
+    AddBasketAsset {
+        mint: Pubkey,
+        max_price_age: u32,
+        cap: u64,
+        max_conf_bps: u16,
+    },
+    UpdateBasketAsset {
+        mint: Pubkey,
+        max_price_age: u32,
+        cap: u64,
+        max_conf_bps: u16,
+    },
+    SetJuniorTranche {
+        junior_share_mint: Pubkey,
+        senior_yield_bps: u16,
+        min_subordination_bps: u16,
+    },
+    SetMigrationTarget {
+        target: Pubkey,
+    },
+    StartWindDown,
+    FreezeWindDown {
+        assets: u64,
+        shares: u64,
+    },
+    CloseVault {
+        swept: u64,
+    },
+    SetLossPauseThreshold {
+        loss_pause_threshold_bps: u16,
+    },
+    InitTimelock {
+        delay_seconds: u32,
+    },
+    QueueAction {
+        id: u64,
+        eta: i64,
+    },
+    CancelAction {
+        id: u64,
+    },
+    ExecuteAction {
+        id: u64,
+    },
+    SetTimelockDelay {
+        delay_seconds: u32,
+    },
+    SetTimelockAdmin {
+        new_admin: Pubkey,
+    },
+    SetRecoveryAccount {
+        recovery_account: Pubkey,
+    },
+    RequestEmergencyEscape {
+        escape_ts: i64,
+    },
+    EmergencyWithdraw {
+        amount: u64,
+    },
+    MigrateState {
+        from_version: u8,
+        to_version: u8,
+    },
+    InitLegacyAccounts {
+        dead_shares: u64,
+        dead_shares_account: Pubkey,
+        recovery_account: Pubkey,
+    },
+    ExecuteFeeTimelock {
+        fee_timelock_seconds: u32,
+        max_fee_step_bps: u16,
+    },
+    CancelEmergencyEscape,
+}
+
+impl Vault {
+    /// Seed following `b"vault"` in the vault PDA: `asset_mint`, one vault per asset, or
+    /// nothing for a legacy vault, which keeps its `[b"vault"]` address (an empty seed doesn't
+    /// change the derivation)
+    pub fn address_seed(&self) -> &[u8] {
+        if self.legacy_address {
+            &[]
+        } else {
+            self.asset_mint.as_ref()
+        }
+    }
+
+    /// Domain every meta-transaction signature for this vault (at address `vault`) must be
+    /// bound to
+    pub fn domain(&self, vault: Pubkey) -> Domain {
+        Domain {
+            schema_version: tsv_meta_message::SCHEMA_VERSION,
+            genesis_hash: self.genesis_hash,
+            program_id: crate::ID,
+            vault,
+        }
+    }
+
+    /// Fail with `NotAllowlisted` in permissioned mode unless an allowlist entry was passed
+    pub fn check_deposit_allowed(&self, allowlisted: bool) -> Result<()> {
+        require!(
+            !self.permissioned || allowlisted,
+            VaultError::NotAllowlisted
+        );
+        Ok(())
+    }
+
+    /// Like `check_deposit_allowed`, only enforced when redemptions are permissioned too
+    pub fn check_redeem_allowed(&self, allowlisted: bool) -> Result<()> {
+        require!(
+            !(self.permissioned && self.permissioned_redeems) || allowlisted,
+            VaultError::NotAllowlisted
+        );
+        Ok(())
+    }
+
+    /// Fail with `RelayerNotRegistered` if only registered relayers may submit meta-transactions
+    /// and this one has no `RelayerEntry`
+    pub fn check_relayer_allowed(&self, registered: bool) -> Result<()> {
+        require!(
+            !self.relayers_restricted || registered,
+            VaultError::RelayerNotRegistered
+        );
+        Ok(())
+    }
+
+    /// Key currently holding `role`
+    pub fn role_holder_mut(&mut self, role: Role) -> &mut Pubkey {
+        match role {
+            Role::Pauser => &mut self.pauser,
+            Role::FeeManager => &mut self.fee_manager,
+            Role::Guardian => &mut self.guardian,
+            Role::Keeper => &mut self.keeper,
+            Role::Compliance => &mut self.compliance,
+        }
+    }
+
+    /// Shares to lock in `dead_shares_account` on the next mint (only the first deposit)
+    pub fn pending_dead_shares(&self) -> u64 {
+        if self.total_shares == 0 {
+            self.dead_shares
+        } else {
+            0
+        }
+    }
+
+    /// Book the management fee accrued since `last_accrual_ts` as shares owed to the treasury.
+    /// They count towards `total_shares` right away (diluting holders) and are minted out by
+    /// `accrue_fees`
+    pub fn accrue_management_fee(&mut self, now: i64) -> Result<u64> {
+        // Claims pay out the frozen wind-down assets over the frozen shares, so no fee may
+        // dilute them after `freeze_wind_down`
+        if self.wind_down_frozen() {
+            return Ok(0);
+        }
+        let elapsed = now.saturating_sub(self.last_accrual_ts).max(0) as u64;
+        let shares =
+            math::management_fee_shares(self.total_shares, self.management_fee_bps, elapsed)?;
+        // Keep accruing from the same timestamp until at least one share is owed, so frequent
+        // instructions don't round the fee away
+        if shares == 0 && self.management_fee_bps > 0 && self.total_shares > 0 {
+            return Ok(0);
+        }
+
+        self.total_shares = math::add(self.total_shares, shares)?;
+        self.unminted_fee_shares = math::add(self.unminted_fee_shares, shares)?;
+        self.last_accrual_ts = self.last_accrual_ts.max(now);
+        Ok(shares)
+    }
+
+    /// Release the part of `locked_profit` unlocked since `locked_profit_ts`; the rest keeps
+    /// unlocking linearly until `profit_unlock_end_ts`
+    pub fn unlock_profit(&mut self, now: i64) -> Result<()> {
+        if now <= self.locked_profit_ts {
+            return Ok(());
+        }
+        self.locked_profit = math::locked_profit_at(
+            self.locked_profit,
+            self.locked_profit_ts,
+            self.profit_unlock_end_ts,
+            now,
+        )?;
+        self.locked_profit_ts = now;
+        Ok(())
+    }
+
+    /// Lock `locked_profit` from `now`, unlocking over `profit_unlock_duration`
+    pub fn lock_profit(&mut self, locked_profit: u64, now: i64) -> Result<()> {
+        self.locked_profit = if self.profit_unlock_duration > 0 {
+            locked_profit
+        } else {
+            0
+        };
+        self.locked_profit_ts = now;
+        self.profit_unlock_end_ts = now
+            .checked_add(self.profit_unlock_duration as i64)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Copy of the vault with the management fee accrued and profit unlocked up to `now` (for
+    /// previews)
+    pub fn accrued(&self, now: i64) -> Result<Vault> {
+        let mut vault = self.clone();
+        vault.accrue_management_fee(now)?;
+        vault.unlock_profit(now)?;
+        Ok(vault)
+    }
+
+    /// Take the next numbered `VaultSnapshot` of the vault's totals, for a deposit or redeem
+    /// event
+    pub fn snapshot(&mut self) -> Result<VaultSnapshot> {
+        self.event_sequence = math::add(self.event_sequence, 1)?;
+        Ok(VaultSnapshot {
+            sequence: self.event_sequence,
+            total_assets: self.total_assets,
+            total_shares: self.total_shares,
+            exchange_rate: self.convert_to_assets(EXCHANGE_RATE_SCALE, Rounding::Floor)?,
+        })
+    }
+
+    /// Whether `freeze_wind_down` fixed the final exchange rate
+    pub fn wind_down_frozen(&self) -> bool {
+        self.wind_down_close_ts > 0
+    }
+
+    /// `total_assets` backing the (senior) share price: all but the still locked profit and
+    /// the junior tranche's assets
+    pub fn free_assets(&self) -> u64 {
+        self.total_assets
+            .saturating_sub(self.locked_profit)
+            .saturating_sub(self.junior_assets)
+    }
+
+    /// Shares for `assets` at the current exchange rate
+    pub fn convert_to_shares(&self, assets: u64, rounding: Rounding) -> Result<u64> {
+        math::convert_to_shares(assets, self.free_assets(), self.total_shares, rounding)
+    }
+
+    /// Assets for `shares` at the current exchange rate
+    pub fn convert_to_assets(&self, shares: u64, rounding: Rounding) -> Result<u64> {
+        math::convert_to_assets(shares, self.free_assets(), self.total_shares, rounding)
+    }
+
+    /// Junior shares for `assets` at the junior tranche's exchange rate
+    pub fn convert_junior_to_shares(&self, assets: u64, rounding: Rounding) -> Result<u64> {
+        math::convert_to_shares(assets, self.junior_assets, self.junior_shares, rounding)
+    }
+
+    /// Assets for `shares` junior shares at the junior tranche's exchange rate
+    pub fn convert_junior_to_assets(&self, shares: u64, rounding: Rounding) -> Result<u64> {
+        math::convert_to_assets(shares, self.junior_assets, self.junior_shares, rounding)
+    }
+
+    /// Part of `gain`, about to be added to `total_assets`, owed to the junior tranche: its
+    /// pro-rata part plus what the senior tranche gives up of its own beyond `senior_yield_bps`
+    pub fn junior_gain(&self, gain: u64) -> Result<u64> {
+        if self.junior_assets == 0 {
+            return Ok(0);
+        }
+        let pro_rata = math::mul_div(
+            gain,
+            self.junior_assets as u128,
+            self.total_assets as u128,
+            Rounding::Floor,
+        )?;
+        let senior_kept = math::mul_div(
+            gain - pro_rata,
+            self.senior_yield_bps as u128,
+            10_000,
+            Rounding::Ceil,
+        )?;
+        Ok(gain - senior_kept)
+    }
+
+    /// Charge `loss`, about to leave `total_assets`, to the junior tranche first, returning the
+    /// part left to the senior tranche
+    pub fn absorb_loss(&mut self, loss: u64) -> u64 {
+        let junior_loss = loss.min(self.junior_assets);
+        self.junior_assets -= junior_loss;
+        loss - junior_loss
+    }
+
+    /// Fail unless the junior tranche holds `min_subordination_bps` of `total_assets`
+    pub fn check_subordination(&self) -> Result<()> {
+        let required = math::mul_div(
+            self.total_assets,
+            self.min_subordination_bps as u128,
+            10_000,
+            Rounding::Ceil,
+        )?;
+        require!(
+            self.junior_assets >= required,
+            VaultError::SubordinationTooLow
+        );
+        Ok(())
+    }
+
+    /// Deposit fee charged on `assets` (rounds up)
+    pub fn deposit_fee(&self, assets: u64) -> Result<u64> {
+        math::fee_on_amount(assets, self.deposit_fee_bps)
+    }
+
+    /// Assets to pull so that `net_assets` remain after the deposit fee (rounds up)
+    pub fn gross_up_for_deposit_fee(&self, net_assets: u64) -> Result<u64> {
+        math::gross_up_for_fee(net_assets, self.deposit_fee_bps)
+    }
+
+    /// Withdraw fee charged on `gross_assets` (rounds up)
+    pub fn withdraw_fee(&self, gross_assets: u64) -> Result<u64> {
+        math::fee_on_amount(gross_assets, self.redeem_fee_bps)
+    }
+
+    /// Gross assets needed so that `net_assets` remain after the fee (rounds up)
+    pub fn gross_up_for_fee(&self, net_assets: u64) -> Result<u64> {
+        math::gross_up_for_fee(net_assets, self.redeem_fee_bps)
+    }
+
+    /// Replace a strategy's target weight `old_bps` by `new_bps` in the total
+    pub fn set_target_weight(&mut self, old_bps: u16, new_bps: u16) -> Result<()> {
+        let total =
+            u32::from(self.total_target_weight_bps) - u32::from(old_bps) + u32::from(new_bps);
+        require!(total <= 10_000, VaultError::TargetWeightsExceeded);
+        self.total_target_weight_bps = total as u16;
+        Ok(())
+    }
+
+    /// USDC to keep idle in `asset_vault` for redemptions: `buffer_bps` of `total_assets`
+    pub fn liquidity_buffer(&self) -> Result<u64> {
+        math::mul_div(
+            self.total_assets,
+            self.buffer_bps as u128,
+            10_000,
+            Rounding::Floor,
+        )
+    }
+
+    /// `liquidity_buffer` once `outflow` has left `total_assets`
+    pub fn liquidity_buffer_after(&self, outflow: u64) -> Result<u64> {
+        math::mul_div(
+            self.total_assets.saturating_sub(outflow),
+            self.buffer_bps as u128,
+            10_000,
+            Rounding::Floor,
+        )
+    }
+
+    /// Book `basket_asset`'s balance at `price`, moving `total_assets` by the change in value
+    /// through the tranche waterfall
+    pub fn revalue_basket_asset(
+        &mut self,
+        basket_asset: &mut BasketAsset,
+        price: &oracle::UsdPrice,
+    ) -> Result<()> {
+        let value = basket_asset.value_of(price, basket_asset.balance)?;
+        if value >= basket_asset.value {
+            let junior_gain = self.junior_gain(value - basket_asset.value)?;
+            self.junior_assets = math::add(self.junior_assets, junior_gain)?;
+        } else {
+            self.absorb_loss(basket_asset.value - value);
+        }
+        self.total_assets = math::sub(math::add(self.total_assets, value)?, basket_asset.value)?;
+        self.basket_value = math::sub(math::add(self.basket_value, value)?, basket_asset.value)?;
+        basket_asset.value = value;
+        Ok(())
+    }
+
+    /// USDC in `asset_vault` available for instant redemptions (excludes accrued fees, the
+    /// insurance buffer and USDC reserved for settled withdrawal requests)
+    pub fn idle_assets(&self, asset_vault: &TokenAccount) -> u64 {
+        asset_vault
+            .amount
+            .saturating_sub(self.accrued_fees)
+            .saturating_sub(self.insurance_assets)
+            .saturating_sub(self.reserved_withdraw_assets)
+    }
+
+    /// Fee in bps charged when `position` redeems at `now` with `idle_assets` on hand: the
+    /// utilization-scaled redeem fee plus the cooldown penalty (or `CooldownActive` when no
+    /// penalty is configured) and the decaying early-exit penalty
+    pub fn effective_redeem_fee_bps(
+        &self,
+        position: &UserPosition,
+        now: i64,
+        idle_assets: u64,
+    ) -> Result<u16> {
+        let mut fee_bps = math::utilization_fee_bps(
+            self.redeem_fee_bps,
+            self.max_redeem_fee_bps,
+            idle_assets,
+            self.total_assets,
+        )?;
+
+        let cooldown_ends = position
+            .last_deposit_ts
+            .checked_add(self.cooldown_seconds as i64)
+            .ok_or(VaultError::MathOverflow)?;
+        if now < cooldown_ends {
+            require!(self.cooldown_penalty_bps > 0, VaultError::CooldownActive);
+            fee_bps = fee_bps
+                .checked_add(self.cooldown_penalty_bps)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+
+        let since_deposit = now.saturating_sub(position.last_deposit_ts).max(0) as u64;
+        let early_exit_bps = math::decayed_penalty_bps(
+            self.early_exit_penalty_bps,
+            self.early_exit_window_seconds,
+            since_deposit,
+        )?;
+        fee_bps = fee_bps
+            .checked_add(early_exit_bps)
+            .ok_or(VaultError::MathOverflow)?;
+
+        Ok(fee_bps)
+    }
+
+    /// Highest fee in bps `effective_redeem_fee_bps` can charge: the top of the utilization
+    /// curve plus both penalties, paid right after a deposit
+    pub fn max_effective_redeem_fee_bps(&self) -> u16 {
+        let mut fee_bps = self.redeem_fee_bps.max(self.max_redeem_fee_bps);
+        if self.cooldown_seconds > 0 {
+            fee_bps = fee_bps.saturating_add(self.cooldown_penalty_bps);
+        }
+        if self.early_exit_window_seconds > 0 {
+            fee_bps = fee_bps.saturating_add(self.early_exit_penalty_bps);
+        }
+        fee_bps
+    }
+
+    /// Check a redeem fee setting didn't raise `max_effective_redeem_fee_bps` from `before` by
+    /// more than `max_fee_step_bps`, the step `queue_fee_update` allows the flat fee
+    pub fn check_redeem_fee_step(&self, before: u16) -> Result<()> {
+        require!(
+            self.max_effective_redeem_fee_bps() <= before.saturating_add(self.max_fee_step_bps),
+            VaultError::FeeStepTooLarge
+        );
+        Ok(())
+    }
+
+    /// Fee in bps `redeem` and `withdraw` charge `position` at `now` with `idle_assets` on
+    /// hand: none if its owner is fee exempt, `effective_redeem_fee_bps` otherwise
+    pub fn redeem_fee_bps(
+        &self,
+        position: &UserPosition,
+        fee_exempt: bool,
+        now: i64,
+        idle_assets: u64,
+    ) -> Result<u16> {
+        if fee_exempt {
+            return Ok(0);
+        }
+        self.effective_redeem_fee_bps(position, now, idle_assets)
+    }
+
+    /// Shares minted by `deposit(assets)`, net of the deposit fee (rounds down)
+    pub fn preview_deposit(&self, assets: u64) -> Result<u64> {
+        let net_assets = math::sub(assets, self.deposit_fee(assets)?)?;
+        self.convert_to_shares(net_assets, Rounding::Floor)
+    }
+
+    /// Assets pulled by `mint(shares)`, including the deposit fee (rounds up)
+    pub fn preview_mint(&self, shares: u64) -> Result<u64> {
+        let net_assets = self.convert_to_assets(shares, Rounding::Ceil)?;
+        self.gross_up_for_deposit_fee(net_assets)
+    }
+
+    /// Net assets paid out by `redeem(shares)` to `position`'s owner at `now`, charged the
+    /// fee of `redeem_fee_bps` (rounds down)
+    pub fn preview_redeem(
+        &self,
+        shares: u64,
+        position: &UserPosition,
+        fee_exempt: bool,
+        now: i64,
+        idle_assets: u64,
+    ) -> Result<u64> {
+        let gross_assets = self.convert_to_assets(shares, Rounding::Floor)?;
+        let fee_bps = self.redeem_fee_bps(position, fee_exempt, now, idle_assets)?;
+        math::sub(gross_assets, math::fee_on_amount(gross_assets, fee_bps)?)
+    }
+
+    /// Shares burned by `withdraw(assets)` from `position`'s owner at `now`, charged the fee of
+    /// `redeem_fee_bps` (rounds up)
+    pub fn preview_withdraw(
+        &self,
+        assets: u64,
+        position: &UserPosition,
+        fee_exempt: bool,
+        now: i64,
+        idle_assets: u64,
+    ) -> Result<u64> {
+        let fee_bps = self.redeem_fee_bps(position, fee_exempt, now, idle_assets)?;
+        let gross_assets = math::gross_up_for_fee(assets, fee_bps)?;
+        self.convert_to_shares(gross_assets, Rounding::Ceil)
+    }
+}
+
+/// Per-user deposit tracking, seeds = [b"position", vault, user]
+#[account]
+#[derive(InitSpace, Default)]
+pub struct UserPosition {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub deposited_assets: u64,
+    pub bump: u8,
+    pub locked_until: i64,
+    pub last_deposit_ts: i64,
+}
+
+impl UserPosition {
+    /// Populate identity fields when the PDA was just created by `init_if_needed`
+    pub fn init_if_new(&mut self, vault: Pubkey, owner: Pubkey, bump: u8) {
+        if self.owner == Pubkey::default() {
+            self.vault = vault;
+            self.owner = owner;
+            self.bump = bump;
+        }
+    }
+
+    /// Push the unlock time out to `now + lockup_seconds` (never shortens an existing lockup)
+    pub fn extend_lockup(&mut self, now: i64, lockup_seconds: u32) -> Result<()> {
+        let locked_until = now
+            .checked_add(lockup_seconds as i64)
+            .ok_or(VaultError::MathOverflow)?;
+        self.locked_until = self.locked_until.max(locked_until);
+        Ok(())
+    }
+
+    /// Fail with `StillLocked` (logging the unlock timestamp) while the lockup is active
+    pub fn check_unlocked(&self, now: i64) -> Result<()> {
+        if now < self.locked_until {
+            msg!("Position locked until {}", self.locked_until);
+            return err!(VaultError::StillLocked);
+        }
+        Ok(())
+    }
+}
+
+/// Opt-in record of a user's cost basis in a vault, for PnL and tax reporting without an
+/// indexer, seeds = [b"cost_basis", vault, owner]
+#[account]
+#[derive(InitSpace)]
+pub struct CostBasis {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    /// Assets paid into tracked deposits, fees included
+    pub deposited_assets: u64,
+    /// Assets received from tracked redemptions, net of fees
+    pub redeemed_assets: u64,
+    /// Shares bought by tracked deposits and not yet redeemed
+    pub shares: u64,
+    /// What `shares` cost: the deposits' assets, less the share-weighted part redemptions took
+    pub cost_basis: u64,
+    /// Redemption proceeds less the cost basis they took, negative for a loss
+    pub realized_pnl: i64,
+    pub opened_at: i64,
+    pub bump: u8,
+}
+
+impl CostBasis {
+    /// Book `shares` bought for `assets`
+    pub fn record_deposit(&mut self, assets: u64, shares: u64) -> Result<()> {
+        self.deposited_assets = math::add(self.deposited_assets, assets)?;
+        self.shares = math::add(self.shares, shares)?;
+        self.cost_basis = math::add(self.cost_basis, assets)?;
+        Ok(())
+    }
+
+    /// Book `shares` redeemed for `assets`, taking their average cost out of the basis. Shares
+    /// beyond the tracked ones came in some other way (a transfer, or a flow that doesn't take
+    /// this account) and cost nothing
+    pub fn record_redeem(&mut self, shares: u64, assets: u64) -> Result<()> {
+        let tracked = shares.min(self.shares);
+        let cost = if tracked == 0 {
+            0
+        } else {
+            math::mul_div(
+                self.cost_basis,
+                tracked as u128,
+                self.shares as u128,
+                Rounding::Floor,
+            )?
+        };
+        self.shares -= tracked;
+        self.cost_basis -= cost;
+        self.redeemed_assets = math::add(self.redeemed_assets, assets)?;
+        let pnl =
+            i64::try_from(assets as i128 - cost as i128).map_err(|_| VaultError::MathOverflow)?;
+        self.realized_pnl = self
+            .realized_pnl
+            .checked_add(pnl)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// A stablecoin the vault accepts besides `asset_mint`, seeds = [b"basket_asset", vault, mint]
+#[account]
+#[derive(InitSpace)]
+pub struct BasketAsset {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    /// Vault-owned account holding it, seeds = [b"basket_vault", vault, mint]
+    pub token_account: Pubkey,
+    /// Pyth `PriceUpdateV2` account of its USD price, and the feed id it must carry
+    pub price_feed: Pubkey,
+    pub feed_id: [u8; 32],
+    /// Oldest price accepted, in seconds
+    pub max_price_age: u32,
+    pub decimals: u8,
+    /// Decimals of `asset_mint`, the unit of `value`
+    pub value_decimals: u8,
+    /// Most of it the vault holds, in its base units
+    pub cap: u64,
+    /// Amount held, in its base units
+    pub balance: u64,
+    /// Part of `total_assets` booked for `balance` at the last price
+    pub value: u64,
+    pub bump: u8,
+    /// Widest price confidence interval accepted, in bps of the price
+    pub max_conf_bps: u16,
+}
+
+impl BasketAsset {
+    /// Its price from `price_update`, refused once older than `max_price_age` at `now` or
+    /// less certain than `max_conf_bps` allows
+    pub fn load_price(&self, price_update: &AccountInfo, now: i64) -> Result<oracle::UsdPrice> {
+        let price = oracle::UsdPrice::load_feed(price_update, &self.feed_id)?;
+        require!(
+            !price.is_stale(now, self.max_price_age),
+            VaultError::StalePrice
+        );
+        require!(
+            price.is_confident(self.max_conf_bps),
+            VaultError::PriceConfidenceTooWide
+        );
+        Ok(price)
+    }
+
+    /// Value of `amount` of it at `price`, in `asset_mint` base units
+    pub fn value_of(&self, price: &oracle::UsdPrice, amount: u64) -> Result<u64> {
+        price.value(amount, self.decimals, self.value_decimals, Rounding::Floor)
+    }
+}
+
+/// Display information of a vault, seeds = [b"vault_metadata", vault]
+#[account]
+#[derive(InitSpace)]
+pub struct VaultMetadata {
+    pub vault: Pubkey,
+    pub info: VaultInfo,
+    pub bump: u8,
+}
+
+impl VaultMetadata {
+    /// Store `info` after checking its lengths, returning the event announcing it to indexers
+    pub fn set(
+        &mut self,
+        vault: Pubkey,
+        bump: u8,
+        info: VaultInfo,
+    ) -> Result<VaultMetadataUpdatedEvent> {
+        require!(
+            info.name.len() <= MAX_VAULT_NAME_LEN
+                && info.description.len() <= MAX_VAULT_DESCRIPTION_LEN
+                && info.uri.len() <= MAX_VAULT_URI_LEN,
+            VaultError::VaultInfoTooLong
+        );
+
+        self.vault = vault;
+        self.bump = bump;
+        self.info = info.clone();
+
+        Ok(VaultMetadataUpdatedEvent {
+            vault,
+            name: info.name,
+            description: info.description,
+            uri: info.uri,
+            risk_tier: info.risk_tier,
+        })
+    }
+}
+
+/// Ring buffer of a vault's last `RATE_HISTORY_LEN` daily exchange rates, written by the
+/// `record_rate` crank so APYs can be computed on-chain, seeds = [b"rate_history", vault]
+#[account]
+#[derive(InitSpace)]
+pub struct RateHistory {
+    pub vault: Pubkey,
+    pub bump: u8,
+    /// Slot the next snapshot goes to once `snapshots` is full (until then it is appended)
+    pub head: u16,
+    #[max_len(RATE_HISTORY_LEN)]
+    pub snapshots: Vec<RateSnapshot>,
+}
+
+/// Exchange rate of a vault at `timestamp`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct RateSnapshot {
+    pub timestamp: i64,
+    /// Assets redeemable for `EXCHANGE_RATE_SCALE` shares, with the management fee accrued and
+    /// profit unlocked up to `timestamp` (as for previews)
+    pub exchange_rate: u64,
+}
+
+impl RateHistory {
+    /// Snapshot `vault`'s exchange rate at `now`, overwriting the oldest snapshot once full,
+    /// and return the event announcing it. Refused within `RATE_SNAPSHOT_INTERVAL_SECONDS` of
+    /// the latest one
+    pub fn record(&mut self, vault: &Vault, now: i64) -> Result<RateRecordedEvent> {
+        if let Some(latest) = self.snapshot(0) {
+            require!(
+                now >= latest
+                    .timestamp
+                    .saturating_add(RATE_SNAPSHOT_INTERVAL_SECONDS),
+                VaultError::RateSnapshotTooEarly
+            );
+        }
+        let snapshot = RateSnapshot {
+            timestamp: now,
+            exchange_rate: vault
+                .accrued(now)?
+                .convert_to_assets(EXCHANGE_RATE_SCALE, Rounding::Floor)?,
+        };
+        if self.snapshots.len() < RATE_HISTORY_LEN {
+            self.snapshots.push(snapshot);
+        } else {
+            self.snapshots[self.head as usize] = snapshot;
+        }
+        self.head = ((self.head as usize + 1) % RATE_HISTORY_LEN) as u16;
+
+        Ok(RateRecordedEvent {
+            vault: self.vault,
+            timestamp: snapshot.timestamp,
+            exchange_rate: snapshot.exchange_rate,
+        })
+    }
+
+    /// Snapshot taken `age` snapshots before the latest one (`0` for the latest), if kept
+    pub fn snapshot(&self, age: usize) -> Option<&RateSnapshot> {
+        if age >= self.snapshots.len() {
+            return None;
+        }
+        // Until the buffer is full `head` is its length, so this also indexes from the end
+        let index = (self.head as usize + RATE_HISTORY_LEN - 1 - age) % RATE_HISTORY_LEN;
+        self.snapshots.get(index)
+    }
+
+    /// Annualized return in bps between the snapshot `days` snapshots back and the latest one
+    pub fn apy_bps(&self, days: usize) -> Result<i64> {
+        let (Some(end), Some(start)) = (self.snapshot(0), self.snapshot(days)) else {
+            return err!(VaultError::RateHistoryTooShort);
+        };
+        require!(days > 0, VaultError::RateHistoryTooShort);
+        math::annualized_return_bps(
+            start.exchange_rate,
+            end.exchange_rate,
+            end.timestamp.saturating_sub(start.timestamp) as u64,
+        )
+    }
+}
+
+/// Queued withdrawal of `owner`'s shares, seeds = [b"withdraw_request", vault, owner, epoch]
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawRequest {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    /// Epoch the request was made in
+    pub epoch: u64,
+    /// Shares requested
+    pub shares: u64,
+    /// Shares not filled yet, as of `next_epoch`
+    pub remaining_shares: u64,
+    /// First epoch whose fill hasn't been claimed
+    pub next_epoch: u64,
+    /// USDC claimed so far
+    pub filled_assets: u64,
+    pub bump: u8,
+}
+
+/// A processed withdrawal epoch, seeds = [b"withdraw_epoch", vault, epoch]
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawEpoch {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    /// Shares queued when the epoch was processed
+    pub shares: u64,
+    /// Part of `shares` filled, every request in the same proportion
+    pub filled_shares: u64,
+    /// USDC the filled shares were redeemed for, net of the redeem fee
+    pub assets: u64,
+    /// Part of `assets` not claimed yet
+    pub unclaimed_assets: u64,
+    /// Part of `shares` whose requests haven't claimed their fill yet
+    pub unclaimed_shares: u64,
+    /// Part of `filled_shares` not claimed yet
+    pub unclaimed_filled_shares: u64,
+    pub processed_ts: i64,
+    pub bump: u8,
+}
+
+impl WithdrawEpoch {
+    /// Fill of a request with `remaining_shares` queued in this epoch, as (shares, assets):
+    /// its proportional part of the filled shares not claimed yet, rounded down, and their
+    /// part of `unclaimed_assets`. The last request to claim takes what is left, so the fills
+    /// add up to exactly `filled_shares` and `assets`
+    pub fn claim(&mut self, remaining_shares: u64) -> Result<(u64, u64)> {
+        let shares = math::mul_div(
+            remaining_shares,
+            self.unclaimed_filled_shares as u128,
+            self.unclaimed_shares as u128,
+            Rounding::Floor,
+        )?;
+        let assets = if shares == 0 {
+            0
+        } else {
+            math::mul_div(
+                shares,
+                self.unclaimed_assets as u128,
+                self.unclaimed_filled_shares as u128,
+                Rounding::Floor,
+            )?
+        };
+        self.unclaimed_shares = math::sub(self.unclaimed_shares, remaining_shares)?;
+        self.unclaimed_filled_shares -= shares;
+        self.unclaimed_assets -= assets;
+        Ok((shares, assets))
+    }
+}
+
+/// Marks a wallet as allowed into a permissioned vault, seeds = [b"allowlist", vault, wallet]
+#[account]
+#[derive(InitSpace)]
+pub struct AllowlistEntry {
+    pub vault: Pubkey,
+    pub wallet: Pubkey,
+    pub bump: u8,
+}
+
+/// Ethereum address allowed to sign for `owner`, seeds = [b"evm_owner", vault, owner]
+#[account]
+#[derive(InitSpace)]
+pub struct EvmOwner {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub eth_address: [u8; 20],
+    pub bump: u8,
+}
+
+/// Temporary key allowed to redeem for `owner`, seeds = [b"session", vault, owner, session_key]
+#[account]
+#[derive(InitSpace)]
+pub struct SessionKey {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    /// USDC the session key may still redeem to the owner
+    pub remaining_assets: u64,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+/// External yield source USDC can be allocated to through its adapter program (see `strategy`),
+/// seeds = [b"strategy", vault, adapter_program]
+#[account]
+#[derive(InitSpace)]
+pub struct Strategy {
+    pub vault: Pubkey,
+    pub adapter_program: Pubkey,
+    /// Adapter-owned USDC account allocations are sent to
+    pub reserve: Pubkey,
+    /// Principal currently allocated, still counted in `Vault::total_assets`
+    pub allocated: u64,
+    /// Cap on `allocated`
+    pub max_debt: u64,
+    /// Share of `Vault::total_assets` `rebalance` moves `allocated` toward
+    pub target_weight_bps: u16,
+    /// New allocations are allowed (deallocating always is)
+    pub enabled: bool,
+    pub bump: u8,
+}
+
+impl Strategy {
+    /// Allocation aimed for when the vault holds `total_assets`
+    pub fn target_allocation(&self, total_assets: u64) -> Result<u64> {
+        math::mul_div(
+            total_assets,
+            u128::from(self.target_weight_bps),
+            10_000,
+            Rounding::Floor,
+        )
+    }
+}
+
+/// Relayer allowed to submit meta-transactions, seeds = [b"relayer", vault, relayer]
+#[account]
+#[derive(InitSpace)]
+pub struct RelayerEntry {
+    pub vault: Pubkey,
+    pub relayer: Pubkey,
+    /// USDC the relayer may move per `RELAYER_WINDOW_SECONDS`
+    pub daily_limit: u64,
+    pub window_start: i64,
+    /// USDC moved since `window_start`
+    pub window_volume: u64,
+    pub bump: u8,
+}
+
+impl RelayerEntry {
+    /// Count `volume` of USDC against the daily limit, starting a new window once the current
+    /// one is over
+    pub fn record_volume(&mut self, now: i64, volume: u64) -> Result<()> {
+        if now >= self.window_start.saturating_add(RELAYER_WINDOW_SECONDS) {
+            self.window_start = now;
+            self.window_volume = 0;
+        }
+        self.window_volume = math::add(self.window_volume, volume)?;
+        require!(
+            self.window_volume <= self.daily_limit,
+            VaultError::RelayerLimitExceeded
+        );
+        Ok(())
+    }
+}
+
+/// Marks a sanctioned wallet, seeds = [b"blocklist", vault, wallet]
+#[account]
+#[derive(InitSpace)]
+pub struct BlocklistEntry {
+    pub vault: Pubkey,
+    pub wallet: Pubkey,
+    pub bump: u8,
+}
+
+impl BlocklistEntry {
+    /// Fail with `AddressBlocked` if `entry`, which must be the blocklist PDA of `wallet`, exists.
+    /// Callers always pass the PDA (instead of an optional entry) so a block can't be skipped
+    pub fn check_not_blocked(entry: &AccountInfo, vault: &Pubkey, wallet: &Pubkey) -> Result<()> {
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"blocklist", vault.as_ref(), wallet.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(entry.key(), expected, ErrorCode::ConstraintSeeds);
+        require!(entry.data_is_empty(), VaultError::AddressBlocked);
+        Ok(())
+    }
+}
+
+/// Marks a wallet as exempt from deposit/redeem fees, seeds = [b"fee_exempt", vault, wallet]
+#[account]
+#[derive(InitSpace)]
+pub struct FeeExemption {
+    pub vault: Pubkey,
+    pub wallet: Pubkey,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct UserNonce {
+    pub nonce: u64,
+}
+
+/// Timelock controller, seeds = [b"timelock", vault]. Becomes the vault authority so that admin
+/// instructions can only run via queued actions
+#[account]
+#[derive(InitSpace)]
+pub struct Timelock {
+    pub vault: Pubkey,
+    /// Key allowed to queue, cancel and execute actions
+    pub admin: Pubkey,
+    pub delay_seconds: u32,
+    pub next_action_id: u64,
+    pub bump: u8,
+}
+
+/// Pending call into this program, seeds = [b"action", timelock, id]
+#[account]
+pub struct QueuedAction {
+    pub timelock: Pubkey,
+    pub id: u64,
+    pub eta: i64,
+    pub accounts: Vec<ActionAccount>,
+    /// Anchor instruction data (discriminator + args)
+    pub data: Vec<u8>,
     pub bump: u8,
+    /// No longer executable from here on, `eta + ACTION_GRACE_PERIOD_SECONDS`
+    pub expires_at: i64,
+}
+
+impl QueuedAction {
+    /// Account size for an action with `accounts` metas and `data_len` bytes of data
+    pub fn space(accounts: usize, data_len: usize) -> usize {
+        8 + 32 + 8 + 8 + 4 + accounts * ActionAccount::INIT_SPACE + 4 + data_len + 1 + 8
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct ActionAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+// Events
+
+/// Audit log: emitted by every privileged instruction alongside its specific event
+#[event]
+pub struct AdminActionEvent {
+    pub actor: Pubkey,
+    pub action: AdminAction,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositEvent {
+    pub user: Pubkey,
+    pub receiver: Pubkey,
+    pub assets: u64,
+    pub shares: u64,
+    pub fee: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct RedeemEvent {
+    pub user: Pubkey,
+    pub receiver: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    pub fee: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct InstantRedeemEvent {
+    pub user: Pubkey,
+    pub receiver: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    /// Redeem fee, set aside for `collect_fees`
+    pub fee: u64,
+    /// Premium left in `total_assets`
+    pub premium: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct WithdrawRequestedEvent {
+    pub owner: Pubkey,
+    pub epoch: u64,
+    pub shares: u64,
+    /// Unfilled shares of the owner's request made in `epoch`, this one's included
+    pub remaining_shares: u64,
+}
+
+#[event]
+pub struct WithdrawRequestCancelledEvent {
+    pub owner: Pubkey,
+    pub epoch: u64,
+    pub shares: u64,
+    /// Unfilled shares left in the request (0 = closed)
+    pub remaining_shares: u64,
+}
+
+#[event]
+pub struct EpochProcessedEvent {
+    pub epoch: u64,
+    /// Shares queued
+    pub shares: u64,
+    /// Part of `shares` filled; the rest stays queued
+    pub filled_shares: u64,
+    /// USDC reserved for the epoch's claims, net of `fee`
+    pub assets: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct WithdrawClaimedEvent {
+    pub owner: Pubkey,
+    /// Epoch the request was made in
+    pub epoch: u64,
+    /// Shares filled in the claimed epochs, and their USDC
+    pub shares: u64,
+    pub assets: u64,
+    /// Unfilled shares left in the request (0 = closed)
+    pub remaining_shares: u64,
+    /// USDC claimed by the request so far
+    pub filled_assets: u64,
+}
+
+#[event]
+pub struct BasketAssetAddedEvent {
+    pub mint: Pubkey,
+    pub price_feed: Pubkey,
+    pub max_price_age: u32,
+    pub cap: u64,
+    pub max_conf_bps: u16,
+}
+
+#[event]
+pub struct BasketAssetUpdatedEvent {
+    pub mint: Pubkey,
+    pub max_price_age: u32,
+    pub cap: u64,
+    pub max_conf_bps: u16,
+}
+
+#[event]
+pub struct BasketAssetRevaluedEvent {
+    pub mint: Pubkey,
+    /// Pyth price, `price * 10^exponent` dollars
+    pub price: i64,
+    pub exponent: i32,
+    pub balance: u64,
+    /// `balance` valued in `asset_mint` base units
+    pub value: u64,
+}
+
+#[event]
+pub struct BasketDepositEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// `amount`'s value in `asset_mint` base units, fee included
+    pub value: u64,
+    pub shares: u64,
+    pub fee: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct BasketRedeemEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+    /// `amount`'s value in `asset_mint` base units, net of `fee`
+    pub value: u64,
+    pub fee: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct ProportionalRedeemEvent {
+    pub user: Pubkey,
+    pub shares: u64,
+    /// USDC paid
+    pub assets: u64,
+    /// Amount of each basket asset paid, in `remaining_accounts` order
+    pub basket_amounts: Vec<u64>,
+    /// Value of everything paid in `asset_mint` base units, net of `fee`
+    pub value: u64,
+    pub fee: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct JuniorTrancheUpdatedEvent {
+    pub junior_share_mint: Pubkey,
+    pub senior_yield_bps: u16,
+    pub min_subordination_bps: u16,
+}
+
+#[event]
+pub struct JuniorDepositEvent {
+    pub user: Pubkey,
+    pub assets: u64,
+    pub shares: u64,
+    pub junior_assets: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct JuniorRedeemEvent {
+    pub user: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    pub junior_assets: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct MigrationTargetUpdatedEvent {
+    pub target: Pubkey,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct UserNonce {
+#[event]
+pub struct PositionMigratedEvent {
+    pub user: Pubkey,
+    pub source_vault: Pubkey,
+    pub target_vault: Pubkey,
+    /// Source vault shares burned
+    pub shares: u64,
+    /// USDC moved between the vaults
+    pub assets: u64,
+    /// Target vault shares minted
+    pub target_shares: u64,
+    pub source_snapshot: VaultSnapshot,
+    pub target_snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct MetaDepositEvent {
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    pub relayer: Pubkey,
+    pub assets: u64,
+    pub shares: u64,
+    pub fee: u64,
     pub nonce: u64,
+    pub snapshot: VaultSnapshot,
 }
 
-// Events
+#[event]
+pub struct MetaRedeemEvent {
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    pub relayer: Pubkey,
+    pub shares: u64,
+    /// USDC sent to the receiver, after the vault fee and the relayer fee
+    pub assets: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub relayer_fee: u64,
+    pub snapshot: VaultSnapshot,
+}
 
 #[event]
-pub struct DepositEvent {
-    pub user: Pubkey,
+pub struct BatchMetaRedeemEvent {
+    pub relayer: Pubkey,
+    /// Requests processed, each also emitting a `MetaRedeemEvent`
+    pub count: u32,
+    pub relayer_fee: u64,
+}
+
+#[event]
+pub struct EvmAddressLinkedEvent {
+    pub owner: Pubkey,
+    pub eth_address: [u8; 20],
+    pub linked: bool,
+}
+
+#[event]
+pub struct CostBasisTrackingEvent {
+    pub owner: Pubkey,
+    pub tracking: bool,
+}
+
+#[event]
+pub struct SessionKeyCreatedEvent {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub max_assets: u64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct SessionKeyRevokedEvent {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+}
+
+#[event]
+pub struct SessionRedeemEvent {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub shares: u64,
     pub assets: u64,
+    pub fee: u64,
+    pub remaining_assets: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct NonceCancelledEvent {
+    pub owner: Pubkey,
+    pub cancelled_nonce: u64,
+}
+
+#[event]
+pub struct FeeQueuedEvent {
+    pub new_deposit_fee_bps: u16,
+    pub new_redeem_fee_bps: u16,
+    pub eta: i64,
+}
+
+#[event]
+pub struct FeeExecutedEvent {
+    pub new_deposit_fee_bps: u16,
+    pub new_redeem_fee_bps: u16,
+}
+
+#[event]
+pub struct FeeTimelockUpdatedEvent {
+    pub fee_timelock_seconds: u32,
+    pub max_fee_step_bps: u16,
+}
+
+#[event]
+pub struct FeeTimelockQueuedEvent {
+    pub fee_timelock_seconds: u32,
+    pub max_fee_step_bps: u16,
+    pub eta: i64,
+}
+
+#[event]
+pub struct PauseFlagsUpdatedEvent {
+    pub paused_deposits: bool,
+    pub paused_redeems: bool,
+    pub paused_meta: bool,
+}
+
+#[event]
+pub struct AuthorityProposedEvent {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferredEvent {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct GuardianPauseEvent {
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct DepegBreakerUpdatedEvent {
+    pub price_feed: Pubkey,
+    pub max_price_deviation_bps: u16,
+    pub max_price_age: u32,
+    pub pauses_redeems: bool,
+}
+
+/// The USDC price left its bounds (or went stale) and the vault paused
+#[event]
+pub struct DepegBreakerTrippedEvent {
+    /// Price as `price * 10^exponent` dollars
+    pub price: i64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub deviation_bps: u64,
+    pub stale: bool,
+}
+
+#[event]
+pub struct DepegBreakerResetEvent {
+    pub guardian: Pubkey,
+    /// Deviation of the price the reset was allowed at
+    pub deviation_bps: u64,
+}
+
+#[event]
+pub struct AuthorityRenouncedEvent {
+    pub previous_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoleUpdatedEvent {
+    pub role: Role,
+    pub previous_holder: Pubkey,
+    pub new_holder: Pubkey,
+}
+
+#[event]
+pub struct DepositCapUpdatedEvent {
+    pub new_deposit_cap: u64,
+}
+
+#[event]
+pub struct MaxPerUserUpdatedEvent {
+    pub new_max_per_user: u64,
+}
+
+#[event]
+pub struct MinAmountsUpdatedEvent {
+    pub min_deposit: u64,
+    pub min_redeem: u64,
+}
+
+#[event]
+pub struct SurplusPolicyUpdatedEvent {
+    pub policy: SurplusPolicy,
+}
+
+#[event]
+pub struct LockupUpdatedEvent {
+    pub lockup_seconds: u32,
+}
+
+#[event]
+pub struct CooldownUpdatedEvent {
+    pub cooldown_seconds: u32,
+    pub cooldown_penalty_bps: u16,
+}
+
+#[event]
+pub struct ManagementFeeUpdatedEvent {
+    pub management_fee_bps: u16,
+    pub treasury_shares: Pubkey,
+}
+
+#[event]
+pub struct ManagementFeeAccruedEvent {
     pub shares: u64,
+    pub total_shares: u64,
 }
 
 #[event]
-pub struct RedeemEvent {
+pub struct PerformanceFeeUpdatedEvent {
+    pub performance_fee_bps: u16,
+}
+
+#[event]
+pub struct BufferUpdatedEvent {
+    pub buffer_bps: u16,
+}
+
+#[event]
+pub struct InstantRedeemPremiumUpdatedEvent {
+    pub instant_redeem_premium_bps: u16,
+}
+
+#[event]
+pub struct ProfitUnlockDurationUpdatedEvent {
+    pub profit_unlock_duration: u32,
+}
+
+#[event]
+pub struct FeeRecipientUpdatedEvent {
+    pub fee_recipient: Pubkey,
+}
+
+#[event]
+pub struct FeesCollectedEvent {
+    pub fee_recipient: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PermissionedUpdatedEvent {
+    pub permissioned: bool,
+    pub permissioned_redeems: bool,
+}
+
+#[event]
+pub struct AllowlistUpdatedEvent {
+    pub wallet: Pubkey,
+    pub allowed: bool,
+}
+
+#[event]
+pub struct RelayersRestrictedUpdatedEvent {
+    pub relayers_restricted: bool,
+}
+
+#[event]
+pub struct RelayerUpdatedEvent {
+    pub relayer: Pubkey,
+    pub daily_limit: u64,
+    pub registered: bool,
+}
+
+#[event]
+pub struct ShareMetadataUpdatedEvent {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+#[event]
+pub struct VaultMetadataUpdatedEvent {
+    pub vault: Pubkey,
+    pub name: String,
+    pub description: String,
+    pub uri: String,
+    pub risk_tier: RiskTier,
+}
+
+#[event]
+pub struct RateRecordedEvent {
+    pub vault: Pubkey,
+    pub timestamp: i64,
+    pub exchange_rate: u64,
+}
+
+#[event]
+pub struct BlocklistUpdatedEvent {
+    pub wallet: Pubkey,
+    pub blocked: bool,
+}
+
+#[event]
+pub struct FeeExemptionUpdatedEvent {
+    pub wallet: Pubkey,
+    pub exempt: bool,
+}
+
+#[event]
+pub struct RedeemFeeCurveUpdatedEvent {
+    pub max_redeem_fee_bps: u16,
+}
+
+#[event]
+pub struct EarlyExitPenaltyUpdatedEvent {
+    pub early_exit_window_seconds: u32,
+    pub early_exit_penalty_bps: u16,
+}
+
+#[event]
+pub struct TimelockDelayUpdatedEvent {
+    pub delay_seconds: u32,
+}
+
+#[event]
+pub struct TimelockAdminUpdatedEvent {
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct ActionQueuedEvent {
+    pub id: u64,
+    pub eta: i64,
+    pub expires_at: i64,
+    pub accounts: Vec<ActionAccount>,
+    pub data: Vec<u8>,
+}
+
+#[event]
+pub struct ActionCancelledEvent {
+    pub id: u64,
+}
+
+#[event]
+pub struct ActionExecutedEvent {
+    pub id: u64,
+}
+
+#[event]
+pub struct ActionExpiredEvent {
+    pub id: u64,
+}
+
+#[event]
+pub struct RecoveryAccountUpdatedEvent {
+    pub recovery_account: Pubkey,
+}
+
+#[event]
+pub struct EmergencyEscapeRequestedEvent {
+    pub escape_ts: i64,
+}
+
+#[event]
+pub struct EmergencyEscapeCancelledEvent {
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct EmergencyWithdrawEvent {
+    pub authority: Pubkey,
+    pub recovery_account: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct WindDownStartedEvent {
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WindDownFrozenEvent {
+    pub assets: u64,
+    pub shares: u64,
+    /// When `close_vault` may run
+    pub close_ts: i64,
+}
+
+#[event]
+pub struct WindDownClaimEvent {
     pub user: Pubkey,
     pub shares: u64,
     pub assets: u64,
-    pub fee: u64,
+    pub snapshot: VaultSnapshot,
+}
+
+#[event]
+pub struct VaultClosedEvent {
+    pub treasury: Pubkey,
+    /// USDC left in `asset_vault`, sent to `fee_recipient`
+    pub swept: u64,
+}
+
+#[event]
+pub struct RegistryCreatorUpdatedEvent {
+    /// Default pubkey: anyone may create vaults
+    pub creator: Pubkey,
+}
+
+#[event]
+pub struct VaultRegisteredEvent {
+    pub vault: Pubkey,
+    pub asset_mint: Pubkey,
+    pub authority: Pubkey,
+    /// Position in `Registry::vaults`
+    pub index: u32,
+}
+
+#[event]
+pub struct StateMigratedEvent {
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct SyncEvent {
+    pub balance: u64,
+    pub total_assets: u64,
+    pub surplus: u64,
+    pub policy: SurplusPolicy,
+}
+
+/// An `add_strategy` call was queued in the timelock; the strategy is registered no earlier
+/// than `eta`
+#[event]
+pub struct StrategyQueuedEvent {
+    /// Id of the queued action
+    pub id: u64,
+    pub adapter_program: Pubkey,
+    pub reserve: Pubkey,
+    pub max_debt: u64,
+    pub target_weight_bps: u16,
+    pub eta: i64,
+}
+
+#[event]
+pub struct StrategyUpdatedEvent {
+    pub adapter_program: Pubkey,
+    pub max_debt: u64,
+    pub target_weight_bps: u16,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct StrategyRemovedEvent {
+    pub adapter_program: Pubkey,
+}
+
+#[event]
+pub struct AllocateEvent {
+    pub adapter_program: Pubkey,
+    pub amount: u64,
+    pub allocated: u64,
+}
+
+#[event]
+pub struct StrategyEmergencyExitEvent {
+    pub adapter_program: Pubkey,
+    pub guardian: Pubkey,
+    /// Value the adapter reported and was asked to withdraw
+    pub value: u64,
+    pub received: u64,
+    /// Allocation left unrecovered
+    pub allocated: u64,
+}
+
+#[event]
+pub struct DeallocateEvent {
+    pub adapter_program: Pubkey,
+    pub amount: u64,
+    pub received: u64,
+    pub allocated: u64,
+}
+
+/// One strategy's result in a `HarvestEvent`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StrategyPnl {
+    pub adapter_program: Pubkey,
+    /// Value reported by the adapter, the strategy's new `allocated`
+    pub value: u64,
+    pub gain: u64,
+    pub loss: u64,
+}
+
+#[event]
+pub struct HarvestEvent {
+    pub strategies: Vec<StrategyPnl>,
+    pub gain: u64,
+    pub loss: u64,
+    /// Part of `gain` taken as `fee_shares`
+    pub performance_fee: u64,
+    pub fee_shares: u64,
+    pub total_assets: u64,
+    /// Profit still to unlock into the share price, this harvest's included
+    pub locked_profit: u64,
+    /// Junior tranche's assets after its cut of the profit and its part of the loss
+    pub junior_assets: u64,
+}
+
+#[event]
+pub struct LossEvent {
+    pub adapter_program: Pubkey,
+    pub loss: u64,
+    /// Part of `loss` drawn from the insurance buffer
+    pub covered: u64,
+    /// Part of `loss` taken out of `total_assets`
+    pub socialized: u64,
+    /// Part of `socialized` absorbed by the junior tranche
+    pub junior_loss: u64,
+    /// `socialized` as a share of `total_assets` before the loss
+    pub loss_bps: u64,
+    /// Strategy's `allocated` after the loss
+    pub allocated: u64,
+    pub total_assets: u64,
+    pub insurance_assets: u64,
+    pub deposits_paused: bool,
+}
+
+#[event]
+pub struct InsuranceFundedEvent {
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub insurance_assets: u64,
 }
 
 #[event]
-pub struct FeeUpdatedEvent {
-    pub new_fee_bps: u16,
+pub struct LossPauseThresholdUpdatedEvent {
+    pub loss_pause_threshold_bps: u16,
 }
 
 // Errors
@@ -366,4 +10405,184 @@ pub enum VaultError {
     DeadlineExpired,
     #[msg("Invalid signature")]
     InvalidSignature,
+    #[msg("Output amount below the requested minimum")]
+    SlippageExceeded,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Division by zero")]
+    DivisionByZero,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Deposits are paused")]
+    DepositsPaused,
+    #[msg("Redemptions are paused")]
+    RedeemsPaused,
+    #[msg("Meta-transactions are paused")]
+    MetaPaused,
+    #[msg("Deposit would exceed the vault cap")]
+    DepositCapExceeded,
+    #[msg("Deposit would exceed the per-user limit")]
+    UserCapExceeded,
+    #[msg("Deposit below the vault minimum")]
+    DepositTooSmall,
+    #[msg("Redemption below the vault minimum")]
+    RedeemTooSmall,
+    #[msg("Fee destination account required")]
+    MissingFeeDestination,
+    #[msg("Position is still locked")]
+    StillLocked,
+    #[msg("Redemption within the deposit cooldown window")]
+    CooldownActive,
+    #[msg("Fee change exceeds the maximum step")]
+    FeeStepTooLarge,
+    #[msg("No fee update queued")]
+    NoPendingFeeUpdate,
+    #[msg("Timelock has not expired")]
+    TimelockNotExpired,
+    #[msg("Relayer fee exceeds the signed maximum")]
+    RelayerFeeExceeded,
+    #[msg("Guardian co-signature required")]
+    GuardianSignatureRequired,
+    #[msg("Wallet is not on the allowlist")]
+    NotAllowlisted,
+    #[msg("Address is blocked")]
+    AddressBlocked,
+    #[msg("Vault state is already at the current version")]
+    AlreadyMigrated,
+    #[msg("Queued action has expired")]
+    ActionExpired,
+    #[msg("Queued action has not expired yet")]
+    ActionNotExpired,
+    #[msg("Session key has expired")]
+    SessionExpired,
+    #[msg("Redemption exceeds the session key's remaining limit")]
+    SessionLimitExceeded,
+    #[msg("Relayer is not registered")]
+    RelayerNotRegistered,
+    #[msg("Relayer daily volume limit exceeded")]
+    RelayerLimitExceeded,
+    #[msg("Asset mint has a Token-2022 extension the vault can't operate with")]
+    UnsupportedMintExtension,
+    #[msg("Asset mint has a privileged Token-2022 extension; initialize with allow_privileged_extensions to accept it")]
+    PrivilegedMintExtension,
+    #[msg("Too many strategies registered")]
+    TooManyStrategies,
+    #[msg("Strategy is disabled")]
+    StrategyDisabled,
+    #[msg("Allocation exceeds the strategy's maximum")]
+    StrategyAllocationExceeded,
+    #[msg("Strategy still has allocated assets")]
+    StrategyNotEmpty,
+    #[msg("Not enough idle assets in the vault")]
+    InsufficientIdleAssets,
+    #[msg("Strategy target weights exceed 100%")]
+    TargetWeightsExceeded,
+    #[msg("Rebalance would move a strategy past its target allocation")]
+    RebalanceCrossesTarget,
+    #[msg("Strategy adapter returned no valid value")]
+    InvalidStrategyValue,
+    #[msg("Set a treasury share account with set_management_fee first")]
+    TreasuryNotSet,
+    #[msg("Profit unlock duration over the maximum (30 days)")]
+    InvalidProfitUnlockDuration,
+    #[msg("Loss pause threshold over 100%")]
+    InvalidLossThreshold,
+    #[msg("Liquidity buffer over 100%")]
+    InvalidBuffer,
+    #[msg("Price feed is not a fully verified Pyth price update of the expected feed")]
+    InvalidPriceFeed,
+    #[msg("Depeg breaker needs a deviation of 1-10,000 bps and a positive max price age")]
+    InvalidDepegBreaker,
+    #[msg("The depeg breaker paused the vault; the guardian must reset it")]
+    DepegBreakerTripped,
+    #[msg("The depeg breaker is not tripped")]
+    DepegBreakerNotTripped,
+    #[msg("USDC price is stale or off its peg")]
+    PriceOutOfBounds,
+    #[msg("No withdrawal requests in the current epoch")]
+    NoWithdrawRequests,
+    #[msg("Claim the withdrawal request's processed epochs first")]
+    WithdrawRequestProcessed,
+    #[msg("Cancel between 1 share and the request's shares")]
+    InvalidCancelAmount,
+    #[msg("Pass processed withdrawal epochs in order from the request's next epoch")]
+    InvalidWithdrawEpoch,
+    #[msg("Vault is already in the registry")]
+    VaultAlreadyRegistered,
+    #[msg(
+        "Basket asset must differ from the vault's asset mint and have a positive max price age"
+    )]
+    InvalidBasketAsset,
+    #[msg("Too many basket assets")]
+    TooManyBasketAssets,
+    #[msg("Deposit would exceed the basket asset's cap")]
+    BasketAssetCapExceeded,
+    #[msg("Price is older than the basket asset's max price age")]
+    StalePrice,
+    #[msg("Not enough of the basket asset held")]
+    InsufficientBasketBalance,
+    #[msg("Pass the accounts of every basket asset, in ascending address order")]
+    MissingBasketAssets,
+    #[msg("Vault name, description or URI is too long")]
+    VaultInfoTooLong,
+    #[msg("Tranche bps must not exceed 10,000")]
+    InvalidTrancheConfig,
+    #[msg("Junior share mint must be a new mint of the vault, and can't be replaced")]
+    InvalidJuniorShareMint,
+    #[msg("Junior tranche would fall below its minimum subordination")]
+    SubordinationTooLow,
+    #[msg("Migration target must be another vault of the same asset, named by the source vault")]
+    InvalidMigrationTarget,
+    #[msg("Vault is winding down")]
+    WindingDown,
+    #[msg("Vault's wind-down has not reached this stage")]
+    NotWindingDown,
+    #[msg("Exit strategies and basket assets, and settle queued withdrawals and junior capital")]
+    WindDownNotReady,
+    #[msg("Wind-down grace period has not ended")]
+    GracePeriodActive,
+    #[msg("Exchange rate was already recorded within the last day")]
+    RateSnapshotTooEarly,
+    #[msg("Rate history doesn't reach back that many snapshots")]
+    RateHistoryTooShort,
+    #[msg("Fee timelock is shorter than the minimum")]
+    InvalidFeeTimelock,
+    #[msg("No emergency escape has been requested")]
+    NoEmergencyEscape,
+    #[msg("Timelock delay is shorter than the minimum")]
+    TimelockDelayTooShort,
+    #[msg("Vault authority was renounced, so withdrawal epochs are no longer processed")]
+    AuthorityRenounced,
+    #[msg("Price confidence interval is wider than the basket asset accepts")]
+    PriceConfidenceTooWide,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_fills_add_up_to_the_epoch_fill() {
+        // Three requests of one share each, two of them filled for 5 USDC base units
+        let mut settlement = WithdrawEpoch {
+            vault: Pubkey::default(),
+            epoch: 0,
+            shares: 3,
+            filled_shares: 2,
+            assets: 5,
+            unclaimed_assets: 5,
+            unclaimed_shares: 3,
+            unclaimed_filled_shares: 2,
+            processed_ts: 0,
+            bump: 0,
+        };
+        let fills: Vec<_> = (0..3).map(|_| settlement.claim(1).unwrap()).collect();
+        assert_eq!(fills, [(0, 0), (1, 2), (1, 3)]);
+        assert_eq!(settlement.unclaimed_shares, 0);
+        assert_eq!(settlement.unclaimed_filled_shares, 0);
+        assert_eq!(settlement.unclaimed_assets, 0);
+
+        // A request can't claim for more than is still queued in the epoch
+        assert!(settlement.claim(1).is_err());
+    }
 }
@@ -0,0 +1,159 @@
+//! Share/asset conversion math.
+//!
+//! The math itself lives in the `tsv-math` crate, shared with off-chain clients so their
+//! previews round exactly like the program. These wrappers surface its errors as
+//! `VaultError::MathOverflow` / `DivisionByZero`.
+
+use anchor_lang::prelude::*;
+use tsv_math::MathError;
+
+use crate::VaultError;
+
+pub use tsv_math::{Rounding, BPS_DENOMINATOR, SECONDS_PER_YEAR, VIRTUAL_DECIMALS_OFFSET};
+
+impl From<MathError> for VaultError {
+    fn from(err: MathError) -> Self {
+        match err {
+            MathError::Overflow => VaultError::MathOverflow,
+            MathError::DivisionByZero => VaultError::DivisionByZero,
+        }
+    }
+}
+
+fn checked<T>(result: tsv_math::Result<T>) -> Result<T> {
+    result.map_err(|err| VaultError::from(err).into())
+}
+
+/// `a * b / denominator` in u128 with explicit rounding
+pub fn mul_div(a: u64, b: u128, denominator: u128, rounding: Rounding) -> Result<u64> {
+    checked(tsv_math::mul_div(a, b, denominator, rounding))
+}
+
+/// `a + b`, erroring on overflow
+pub fn add(a: u64, b: u64) -> Result<u64> {
+    checked(tsv_math::add(a, b))
+}
+
+/// `a - b`, erroring on underflow
+pub fn sub(a: u64, b: u64) -> Result<u64> {
+    checked(tsv_math::sub(a, b))
+}
+
+/// Shares for `assets` at the vault exchange rate
+pub fn convert_to_shares(
+    assets: u64,
+    total_assets: u64,
+    total_shares: u64,
+    rounding: Rounding,
+) -> Result<u64> {
+    checked(tsv_math::convert_to_shares(
+        assets,
+        total_assets,
+        total_shares,
+        rounding,
+    ))
+}
+
+/// Assets for `shares` at the vault exchange rate
+pub fn convert_to_assets(
+    shares: u64,
+    total_assets: u64,
+    total_shares: u64,
+    rounding: Rounding,
+) -> Result<u64> {
+    checked(tsv_math::convert_to_assets(
+        shares,
+        total_assets,
+        total_shares,
+        rounding,
+    ))
+}
+
+/// Fee of `fee_bps` on `amount` (rounds up)
+pub fn fee_on_amount(amount: u64, fee_bps: u16) -> Result<u64> {
+    checked(tsv_math::fee_on_amount(amount, fee_bps))
+}
+
+/// Gross amount such that `net` remains after a `fee_bps` fee (rounds up)
+pub fn gross_up_for_fee(net: u64, fee_bps: u16) -> Result<u64> {
+    checked(tsv_math::gross_up_for_fee(net, fee_bps))
+}
+
+/// Shares owed for an annualized `fee_bps` management fee over `elapsed` seconds (rounds down)
+pub fn management_fee_shares(total_shares: u64, fee_bps: u16, elapsed: u64) -> Result<u64> {
+    checked(tsv_math::management_fee_shares(
+        total_shares,
+        fee_bps,
+        elapsed,
+    ))
+}
+
+/// Redeem fee scaled linearly with utilization, from `base_fee_bps` to `max_fee_bps` (rounds up)
+pub fn utilization_fee_bps(
+    base_fee_bps: u16,
+    max_fee_bps: u16,
+    idle_assets: u64,
+    total_assets: u64,
+) -> Result<u16> {
+    checked(tsv_math::utilization_fee_bps(
+        base_fee_bps,
+        max_fee_bps,
+        idle_assets,
+        total_assets,
+    ))
+}
+
+/// Penalty of `penalty_bps` decaying linearly to zero over `window` seconds (rounds up)
+pub fn decayed_penalty_bps(penalty_bps: u16, window: u32, elapsed: u64) -> Result<u16> {
+    checked(tsv_math::decayed_penalty_bps(penalty_bps, window, elapsed))
+}
+
+/// Part of `locked_profit` locked at `locked_profit_ts` still locked at `now` (rounds up)
+pub fn locked_profit_at(
+    locked_profit: u64,
+    locked_profit_ts: i64,
+    profit_unlock_end_ts: i64,
+    now: i64,
+) -> Result<u64> {
+    checked(tsv_math::locked_profit_at(
+        locked_profit,
+        locked_profit_ts,
+        profit_unlock_end_ts,
+        now,
+    ))
+}
+
+/// Simple annualized return in bps of an exchange rate moving from `start_rate` to `end_rate`
+/// over `elapsed` seconds (rounds toward zero)
+pub fn annualized_return_bps(start_rate: u64, end_rate: u64, elapsed: u64) -> Result<i64> {
+    checked(tsv_math::annualized_return_bps(
+        start_rate, end_rate, elapsed,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn math_errors_surface_as_vault_errors() {
+        assert_eq!(
+            mul_div(1, 1, 0, Rounding::Floor).unwrap_err(),
+            Error::from(VaultError::DivisionByZero)
+        );
+        assert_eq!(
+            add(u64::MAX, 1).unwrap_err(),
+            Error::from(VaultError::MathOverflow)
+        );
+        assert_eq!(
+            sub(0, 1).unwrap_err(),
+            Error::from(VaultError::MathOverflow)
+        );
+        assert_eq!(
+            convert_to_shares(1_000_000, 0, 0, Rounding::Floor).unwrap(),
+            1_000_000_000
+        );
+    }
+}
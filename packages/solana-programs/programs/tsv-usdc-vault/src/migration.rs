@@ -0,0 +1,139 @@
+//! `Vault` layout versioning.
+//!
+//! The first deployed program wrote `BaselineVault`, with one fee for deposits and redeems;
+//! splitting it moved `bump`, so those accounts are decoded field by field. Every field since
+//! has been appended, so an account of a later layout is a prefix of the current one, and
+//! zero-padding it yields a valid `Vault`. Either way `version` reads as the pre-versioning
+//! layout (0) until `upgrade` fills in the defaults of every field added since.
+
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use crate::{
+    Vault, DEFAULT_FEE_TIMELOCK_SECONDS, DEFAULT_MAX_FEE_STEP_BPS, DEFAULT_PROFIT_UNLOCK_DURATION,
+    MIN_FEE_TIMELOCK_SECONDS,
+};
+
+/// Layout version written by `initialize` and `migrate_state`
+pub const CURRENT_VAULT_VERSION: u8 = 6;
+
+/// Size of the current `Vault` account, discriminator included
+pub const VAULT_ACCOUNT_SIZE: usize = 8 + Vault::INIT_SPACE;
+
+/// `Vault` as the first deployed program wrote it
+#[derive(AnchorDeserialize)]
+pub struct BaselineVault {
+    pub authority: Pubkey,
+    pub asset_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub asset_vault: Pubkey,
+    pub total_assets: u64,
+    pub total_shares: u64,
+    /// Charged on deposits and redeems alike
+    pub fee_bps: u16,
+    pub bump: u8,
+}
+
+/// Size of a `BaselineVault` account, discriminator included
+pub const BASELINE_VAULT_SIZE: usize = 8 + 4 * 32 + 8 + 8 + 2 + 1;
+
+/// `Vault` account `data` of any layout version, in the current layout with every field it
+/// lacks zero. A `BaselineVault`'s fee becomes both the deposit and the redeem fee
+pub fn decode(data: &[u8]) -> Result<Vault> {
+    let mut padded = vec![0; VAULT_ACCOUNT_SIZE.max(data.len())];
+    if data.len() != BASELINE_VAULT_SIZE {
+        padded[..data.len()].copy_from_slice(data);
+        return Vault::try_deserialize(&mut padded.as_slice());
+    }
+
+    require!(
+        data[..8] == Vault::DISCRIMINATOR,
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    let baseline = BaselineVault::deserialize(&mut &data[8..])?;
+    let mut vault = Vault::try_deserialize_unchecked(&mut padded.as_slice())?;
+    vault.authority = baseline.authority;
+    vault.asset_mint = baseline.asset_mint;
+    vault.share_mint = baseline.share_mint;
+    vault.asset_vault = baseline.asset_vault;
+    vault.total_assets = baseline.total_assets;
+    vault.total_shares = baseline.total_shares;
+    vault.deposit_fee_bps = baseline.fee_bps;
+    vault.redeem_fee_bps = baseline.fee_bps;
+    vault.bump = baseline.bump;
+    Ok(vault)
+}
+
+/// Bring a decoded `vault` from `vault.version` up to `CURRENT_VAULT_VERSION`. The dead shares
+/// and recovery accounts a pre-versioning vault lacks are created by `init_legacy_accounts`
+pub fn upgrade(vault: &mut Vault, now: i64, genesis_hash: [u8; 32]) -> Result<()> {
+    if vault.version < 1 {
+        // Roles and limits that read as zero on pre-versioning vaults; the treasury and fee
+        // recipient stay unset, as on a new vault, until governance sets them
+        let authority = vault.authority;
+        for role in [
+            &mut vault.pauser,
+            &mut vault.fee_manager,
+            &mut vault.guardian,
+            &mut vault.keeper,
+            &mut vault.compliance,
+        ] {
+            if *role == Pubkey::default() {
+                *role = authority;
+            }
+        }
+        if vault.deposit_cap == 0 {
+            vault.deposit_cap = u64::MAX;
+        }
+        if vault.max_per_user == 0 {
+            vault.max_per_user = u64::MAX;
+        }
+        if vault.max_fee_step_bps == 0 {
+            vault.max_fee_step_bps = DEFAULT_MAX_FEE_STEP_BPS;
+        }
+        if vault.last_accrual_ts == 0 {
+            vault.last_accrual_ts = now;
+        }
+    }
+
+    if vault.version < 2 {
+        // Meta-transaction domain separation
+        vault.genesis_hash = genesis_hash;
+    }
+
+    // Version 3 appended `relayers_restricted`, whose zero default (open relaying) is kept
+
+    if vault.version < 4 {
+        // Strategy accounting: `allocated_assets`, `strategy_count`, `total_target_weight_bps`,
+        // `performance_fee_bps`, the locked profit, the insurance buffer, the loss pause
+        // threshold and `buffer_bps` stay zero until strategies are set up, as does the depeg
+        // breaker (off) until a price feed is configured; the withdrawal queue starts empty at
+        // epoch 0 and `instant_redeem` without a premium
+        vault.profit_unlock_duration = DEFAULT_PROFIT_UNLOCK_DURATION;
+    }
+
+    if vault.version < 5 {
+        // Every vault before per-asset addresses lives at the single-vault `[b"vault"]` PDA;
+        // there are no basket assets, junior tranche or migration target yet, and no vault is
+        // winding down or has numbered its events
+        vault.legacy_address = true;
+    }
+
+    if vault.version < 6 {
+        // Fee timelocks got a floor; a vault below it (0 by default until now) gets the
+        // default delay, with no loosening queued
+        if vault.fee_timelock_seconds < MIN_FEE_TIMELOCK_SECONDS {
+            vault.fee_timelock_seconds = DEFAULT_FEE_TIMELOCK_SECONDS;
+        }
+    }
+
+    vault.version = CURRENT_VAULT_VERSION;
+    Ok(())
+}
+
+/// Deserialize a `Vault` account of any layout version, zero-padding older (shorter) accounts.
+/// For off-chain readers of vaults that have not been migrated yet; `version` tells the layout
+#[cfg(feature = "legacy-state")]
+pub fn try_deserialize_any_version(data: &[u8]) -> Result<Vault> {
+    decode(data)
+}
@@ -0,0 +1,225 @@
+//! Pyth USD prices: USDC/USD for the depeg circuit breaker, and the price of each basket asset.
+//!
+//! Prices come from `PriceUpdateV2` accounts of the Pyth receiver program, such as the USDC/USD
+//! price feed account Pyth keeps updated. The receiver is an Anchor program, so the few fields
+//! needed are read at their offsets rather than through a dependency on the Pyth SDK.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::VaultError;
+
+/// Pyth Solana receiver program, owner of `PriceUpdateV2` accounts
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+/// Pyth USDC/USD feed id
+pub const USDC_USD_FEED_ID: [u8; 32] = [
+    234, 160, 32, 198, 28, 196, 121, 113, 40, 19, 70, 28, 225, 83, 137, 74, 150, 166, 192, 11, 33,
+    237, 12, 252, 39, 152, 209, 249, 169, 233, 201, 74,
+];
+
+/// sha256("account:PriceUpdateV2")[..8]
+pub const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+// `PriceUpdateV2` offsets, discriminator included: the write authority precedes the
+// verification level, a Borsh enum whose `Full` variant is a lone tag, then the price message
+const VERIFICATION_LEVEL_OFFSET: usize = 40;
+const VERIFICATION_LEVEL_FULL: u8 = 1;
+const FEED_ID_OFFSET: usize = 41;
+const PRICE_OFFSET: usize = 73;
+const CONF_OFFSET: usize = 81;
+const EXPONENT_OFFSET: usize = 89;
+const PUBLISH_TIME_OFFSET: usize = 93;
+const PRICE_UPDATE_MIN_LEN: usize = PUBLISH_TIME_OFFSET + 8;
+
+/// Most decimals a price is accepted with
+const MAX_EXPONENT_DECIMALS: u32 = 18;
+
+/// A USD price: `price * 10^exponent` dollars, with confidence interval `conf`
+pub struct UsdPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+impl UsdPrice {
+    /// Read a fully verified USDC/USD `PriceUpdateV2` account
+    pub fn load(price_update: &AccountInfo) -> Result<Self> {
+        Self::load_feed(price_update, &USDC_USD_FEED_ID)
+    }
+
+    /// Read a fully verified `PriceUpdateV2` account of the feed `feed_id`
+    pub fn load_feed(price_update: &AccountInfo, feed_id: &[u8; 32]) -> Result<Self> {
+        require_keys_eq!(
+            *price_update.owner,
+            PYTH_RECEIVER_PROGRAM_ID,
+            VaultError::InvalidPriceFeed
+        );
+        let data = price_update.try_borrow_data()?;
+        require!(
+            data.len() >= PRICE_UPDATE_MIN_LEN
+                && data[..8] == PRICE_UPDATE_V2_DISCRIMINATOR
+                && data[VERIFICATION_LEVEL_OFFSET] == VERIFICATION_LEVEL_FULL
+                && data[FEED_ID_OFFSET..FEED_ID_OFFSET + 32] == feed_id[..],
+            VaultError::InvalidPriceFeed
+        );
+
+        let i64_at =
+            |offset: usize| i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        Ok(Self {
+            price: i64_at(PRICE_OFFSET),
+            conf: u64::from_le_bytes(data[CONF_OFFSET..CONF_OFFSET + 8].try_into().unwrap()),
+            exponent: i32::from_le_bytes(
+                data[EXPONENT_OFFSET..EXPONENT_OFFSET + 4]
+                    .try_into()
+                    .unwrap(),
+            ),
+            publish_time: i64_at(PUBLISH_TIME_OFFSET),
+        })
+    }
+
+    /// Decimals of `price`
+    fn decimals(&self) -> Result<u32> {
+        Ok(u32::try_from(-i64::from(self.exponent))
+            .ok()
+            .filter(|decimals| *decimals <= MAX_EXPONENT_DECIMALS)
+            .ok_or(VaultError::InvalidPriceFeed)?)
+    }
+
+    /// Distance from $1 in bps, rounded up; a non-positive price is a full depeg
+    pub fn deviation_bps(&self) -> Result<u64> {
+        let decimals = self.decimals()?;
+        if self.price <= 0 {
+            return Ok(10_000);
+        }
+        let one = 10u128.pow(decimals);
+        let distance = (self.price as u128).abs_diff(one);
+        let bps = (distance * 10_000).div_ceil(one);
+        u64::try_from(bps).map_err(|_| error!(VaultError::MathOverflow))
+    }
+
+    /// Dollar value of `amount` base units of a token with `decimals` decimals, in base units
+    /// of a $1 token with `value_decimals` decimals
+    pub fn value(
+        &self,
+        amount: u64,
+        decimals: u8,
+        value_decimals: u8,
+        rounding: Rounding,
+    ) -> Result<u64> {
+        let (price, unit) = self.scale(decimals, value_decimals)?;
+        math::mul_div(amount, price, unit, rounding)
+    }
+
+    /// Base units of a token with `decimals` decimals worth `value` base units of a $1 token
+    /// with `value_decimals` decimals; the inverse of `value`
+    pub fn amount(
+        &self,
+        value: u64,
+        decimals: u8,
+        value_decimals: u8,
+        rounding: Rounding,
+    ) -> Result<u64> {
+        let (price, unit) = self.scale(decimals, value_decimals)?;
+        math::mul_div(value, unit, price, rounding)
+    }
+
+    /// The price as a ratio of value to token base units
+    fn scale(&self, decimals: u8, value_decimals: u8) -> Result<(u128, u128)> {
+        require!(self.price > 0, VaultError::PriceOutOfBounds);
+        let price = 10u128
+            .checked_pow(value_decimals.into())
+            .and_then(|scale| scale.checked_mul(self.price as u128))
+            .ok_or(VaultError::MathOverflow)?;
+        let unit = 10u128
+            .checked_pow(u32::from(decimals) + self.decimals()?)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok((price, unit))
+    }
+
+    /// Confidence interval within `max_conf_bps` of the price, i.e.
+    /// `conf * 10_000 <= price * max_conf_bps`; never for a non-positive price
+    pub fn is_confident(&self, max_conf_bps: u16) -> bool {
+        self.price > 0
+            && u128::from(self.conf) * 10_000 <= self.price as u128 * u128::from(max_conf_bps)
+    }
+
+    /// Published more than `max_age` seconds before `now`
+    pub fn is_stale(&self, now: i64, max_age: u32) -> bool {
+        now.saturating_sub(self.publish_time) > i64::from(max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash::hash;
+
+    fn price(price: i64, exponent: i32) -> UsdPrice {
+        UsdPrice {
+            price,
+            conf: 0,
+            exponent,
+            publish_time: 1_000,
+        }
+    }
+
+    #[test]
+    fn discriminator_matches_anchor_account_hash() {
+        assert_eq!(
+            hash(b"account:PriceUpdateV2").to_bytes()[..8],
+            PRICE_UPDATE_V2_DISCRIMINATOR
+        );
+    }
+
+    #[test]
+    fn measures_deviation_from_one_dollar() {
+        assert_eq!(price(100_000_000, -8).deviation_bps().unwrap(), 0);
+        assert_eq!(price(99_500_000, -8).deviation_bps().unwrap(), 50);
+        assert_eq!(price(100_500_000, -8).deviation_bps().unwrap(), 50);
+        // Rounds up
+        assert_eq!(price(99_999_999, -8).deviation_bps().unwrap(), 1);
+        assert_eq!(price(0, -8).deviation_bps().unwrap(), 10_000);
+        assert!(price(1, 2).deviation_bps().is_err());
+        assert!(price(1, -19).deviation_bps().is_err());
+    }
+
+    #[test]
+    fn values_tokens_in_dollar_units() {
+        // 1 USDT (6 decimals) at $0.999 is 999,000 USDC base units
+        let usdt = price(99_900_000, -8);
+        assert_eq!(
+            usdt.value(1_000_000, 6, 6, Rounding::Floor).unwrap(),
+            999_000
+        );
+        assert_eq!(
+            usdt.amount(999_000, 6, 6, Rounding::Floor).unwrap(),
+            1_000_000
+        );
+        // Rounds per `rounding` across decimals
+        assert_eq!(usdt.value(1, 9, 6, Rounding::Floor).unwrap(), 0);
+        assert_eq!(usdt.value(1, 9, 6, Rounding::Ceil).unwrap(), 1);
+        assert_eq!(usdt.amount(1, 6, 6, Rounding::Ceil).unwrap(), 2);
+        assert!(price(0, -8).value(1, 6, 6, Rounding::Floor).is_err());
+    }
+
+    #[test]
+    fn bounds_the_confidence_interval() {
+        // $1 with a $0.005 interval is 50 bps wide
+        let usdt = UsdPrice {
+            conf: 500_000,
+            ..price(100_000_000, -8)
+        };
+        assert!(usdt.is_confident(50));
+        assert!(!usdt.is_confident(49));
+        assert!(price(100_000_000, -8).is_confident(1));
+        assert!(!price(0, -8).is_confident(10_000));
+    }
+
+    #[test]
+    fn goes_stale_after_max_age() {
+        assert!(!price(100_000_000, -8).is_stale(1_060, 60));
+        assert!(price(100_000_000, -8).is_stale(1_061, 60));
+    }
+}
@@ -0,0 +1,227 @@
+//! Off-chain signature verification for meta-transactions.
+//!
+//! Programs can't verify ed25519 signatures cheaply themselves; instead the relayer puts an
+//! Ed25519 native program instruction right before ours in the same transaction. The runtime
+//! fails the whole transaction if that signature is invalid, so all we have to check (via the
+//! instructions sysvar) is that it covers the expected signer, message and signature.
+//!
+//! Messages themselves are defined in the `tsv-meta-message` crate, shared with clients.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::solana_program::{ed25519_program, secp256k1_program};
+
+use crate::VaultError;
+
+/// Size of the Ed25519 instruction header: signature count + padding
+const HEADER_LEN: usize = 2;
+
+/// Size of one `Ed25519SignatureOffsets` entry (7 x u16)
+const OFFSETS_LEN: usize = 14;
+
+/// Marks offsets pointing into the Ed25519 instruction's own data
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Size of the secp256k1 instruction header: signature count
+const SECP_HEADER_LEN: usize = 1;
+
+/// Size of one `SecpSignatureOffsets` entry (u16, u8, u16, u8, u16, u16, u8)
+const SECP_OFFSETS_LEN: usize = 11;
+
+/// Require that the instruction right before the current one is an Ed25519 program instruction
+/// verifying `signature` by `signer` over exactly `message`
+pub fn verify_ed25519_ix(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions_sysvar)?;
+    require!(current > 0, VaultError::InvalidSignature);
+    verify_ed25519_ix_at(instructions_sysvar, current - 1, signer, message, signature)
+}
+
+/// Like `verify_ed25519_ix`, for the Ed25519 program instruction at `index` in the transaction
+/// (batches carry one per request)
+pub fn verify_ed25519_ix_at(
+    instructions_sysvar: &AccountInfo,
+    index: u16,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<()> {
+    let ix = load_instruction_at_checked(index as usize, instructions_sysvar)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        VaultError::InvalidSignature
+    );
+    require!(ix.accounts.is_empty(), VaultError::InvalidSignature);
+
+    let (ix_signer, ix_signature, ix_message) =
+        parse_ed25519_data(&ix.data).ok_or(VaultError::InvalidSignature)?;
+    require!(
+        ix_signer == signer.as_ref() && ix_signature == signature.as_ref() && ix_message == message,
+        VaultError::InvalidSignature
+    );
+    Ok(())
+}
+
+/// Require that the instruction right before the current one is a secp256k1 program instruction
+/// recovering `eth_address` from a signature over exactly `message` (EIP-191 prefixed by the
+/// caller). The precompile itself hashes the message with keccak256 and checks the recovery
+pub fn verify_secp256k1_ix(
+    instructions_sysvar: &AccountInfo,
+    eth_address: &[u8; 20],
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions_sysvar)?;
+    require!(current > 0, VaultError::InvalidSignature);
+    let index = current - 1;
+    let ix = load_instruction_at_checked(index as usize, instructions_sysvar)?;
+    require_keys_eq!(
+        ix.program_id,
+        secp256k1_program::ID,
+        VaultError::InvalidSignature
+    );
+
+    let (ix_eth_address, ix_message) =
+        parse_secp256k1_data(&ix.data, index).ok_or(VaultError::InvalidSignature)?;
+    require!(
+        ix_eth_address == eth_address.as_ref() && ix_message == message,
+        VaultError::InvalidSignature
+    );
+    Ok(())
+}
+
+/// Split Ed25519 instruction data carrying a single signature into (public key, signature,
+/// message). `None` unless all three live in that same instruction
+fn parse_ed25519_data(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    if data.len() < HEADER_LEN + OFFSETS_LEN || data[0] != 1 {
+        return None;
+    }
+    let offset = |i: usize| {
+        let at = HEADER_LEN + 2 * i;
+        u16::from_le_bytes([data[at], data[at + 1]])
+    };
+    let (signature_offset, signature_ix) = (offset(0) as usize, offset(1));
+    let (public_key_offset, public_key_ix) = (offset(2) as usize, offset(3));
+    let (message_offset, message_len, message_ix) =
+        (offset(4) as usize, offset(5) as usize, offset(6));
+    if [signature_ix, public_key_ix, message_ix] != [CURRENT_INSTRUCTION; 3] {
+        return None;
+    }
+
+    Some((
+        data.get(public_key_offset..public_key_offset + 32)?,
+        data.get(signature_offset..signature_offset + 64)?,
+        data.get(message_offset..message_offset + message_len)?,
+    ))
+}
+
+/// Split secp256k1 instruction data carrying a single signature into (Ethereum address,
+/// message). `None` unless the signature, address and message all live in that same
+/// instruction, at `index` in the transaction
+fn parse_secp256k1_data(data: &[u8], index: u16) -> Option<(&[u8], &[u8])> {
+    if data.len() < SECP_HEADER_LEN + SECP_OFFSETS_LEN || data[0] != 1 {
+        return None;
+    }
+    let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+    let own = |at: usize| u16::from(data[at]) == index;
+    let offsets = SECP_HEADER_LEN;
+    if !(own(offsets + 2) && own(offsets + 5) && own(offsets + 10)) {
+        return None;
+    }
+    let eth_address_offset = u16_at(offsets + 3);
+    let (message_offset, message_len) = (u16_at(offsets + 6), u16_at(offsets + 8));
+
+    Some((
+        data.get(eth_address_offset..eth_address_offset + 20)?,
+        data.get(message_offset..message_offset + message_len)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Layout produced by `Ed25519Program.createInstructionWithPublicKey` in web3.js
+    fn ed25519_data(public_key: &[u8; 32], signature: &[u8; 64], message: &[u8]) -> Vec<u8> {
+        let public_key_offset = (HEADER_LEN + OFFSETS_LEN) as u16;
+        let signature_offset = public_key_offset + 32;
+        let message_offset = signature_offset + 64;
+
+        let mut data = vec![1, 0];
+        for value in [
+            signature_offset,
+            CURRENT_INSTRUCTION,
+            public_key_offset,
+            CURRENT_INSTRUCTION,
+            message_offset,
+            message.len() as u16,
+            CURRENT_INSTRUCTION,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(public_key);
+        data.extend_from_slice(signature);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn parses_single_signature() {
+        let data = ed25519_data(&[7; 32], &[9; 64], b"message");
+        let (public_key, signature, message) = parse_ed25519_data(&data).unwrap();
+        assert_eq!(public_key, &[7; 32]);
+        assert_eq!(signature, &[9; 64]);
+        assert_eq!(message, b"message");
+    }
+
+    /// Layout produced by `Secp256k1Program.createInstructionWithEthAddress` in web3.js
+    fn secp256k1_data(eth_address: &[u8; 20], message: &[u8], index: u8) -> Vec<u8> {
+        let eth_address_offset = (SECP_HEADER_LEN + SECP_OFFSETS_LEN) as u16;
+        let signature_offset = eth_address_offset + 20;
+        let message_offset = signature_offset + 65;
+
+        let mut data = vec![1];
+        data.extend_from_slice(&signature_offset.to_le_bytes());
+        data.push(index);
+        data.extend_from_slice(&eth_address_offset.to_le_bytes());
+        data.push(index);
+        data.extend_from_slice(&message_offset.to_le_bytes());
+        data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        data.push(index);
+        data.extend_from_slice(eth_address);
+        data.extend_from_slice(&[5; 65]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn parses_secp256k1_signature() {
+        let data = secp256k1_data(&[3; 20], b"message", 2);
+        let (eth_address, message) = parse_secp256k1_data(&data, 2).unwrap();
+        assert_eq!(eth_address, &[3; 20]);
+        assert_eq!(message, b"message");
+
+        // Offsets must point into the verify instruction itself
+        assert!(parse_secp256k1_data(&data, 1).is_none());
+        assert!(parse_secp256k1_data(&data[..data.len() - 1], 2).is_none());
+    }
+
+    #[test]
+    fn rejects_foreign_or_truncated_data() {
+        let mut data = ed25519_data(&[7; 32], &[9; 64], b"message");
+        // Message read from another instruction
+        data[HEADER_LEN + 12] = 0;
+        data[HEADER_LEN + 13] = 0;
+        assert!(parse_ed25519_data(&data).is_none());
+
+        let data = ed25519_data(&[7; 32], &[9; 64], b"message");
+        assert!(parse_ed25519_data(&data[..data.len() - 1]).is_none());
+        assert!(parse_ed25519_data(&[2, 0]).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+//! Interface between the vault and external strategy adapters.
+//!
+//! An adapter is a program that deploys idle USDC into a yield source. To allocate, the vault
+//! transfers USDC into the adapter's `reserve` token account itself, then calls the adapter's
+//! `strategy_deposit` to put it to work. To deallocate, it calls `strategy_withdraw`, which must
+//! send USDC back into the vault's `asset_vault`; the vault only counts what actually arrives.
+//!
+//! Both calls are signed by the `Strategy` PDA (seeds = [b"strategy", vault, adapter_program]),
+//! never by the vault PDA, which is the share mint authority and owns `asset_vault`. Adapters
+//! authenticate the vault by requiring that signer.
+//!
+//! Adapters implement three Anchor-style instructions:
+//!
+//! - `strategy_deposit(amount: u64)`: deploy `amount` of USDC now held in `reserve`
+//! - `strategy_withdraw(amount: u64)`: return `amount` of USDC into `asset_vault`
+//! - `strategy_total_value() -> u64`: USDC the strategy holds, deployed or in `reserve`, set as
+//!   return data; `harvest` books its change as gain or loss
+//!
+//! all with accounts, in order: `strategy` (signer), `asset_vault` (writable), `reserve`
+//! (writable), `asset_mint`, `token_program`, then any adapter-specific accounts, which the
+//! keeper passes to `allocate`/`deallocate` as remaining accounts.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{get_return_data, invoke_signed};
+
+use crate::VaultError;
+
+/// Anchor discriminator of `strategy_deposit`: sha256("global:strategy_deposit")[..8]
+pub const STRATEGY_DEPOSIT_DISCRIMINATOR: [u8; 8] = [231, 58, 49, 174, 119, 19, 230, 74];
+
+/// Anchor discriminator of `strategy_withdraw`: sha256("global:strategy_withdraw")[..8]
+pub const STRATEGY_WITHDRAW_DISCRIMINATOR: [u8; 8] = [17, 121, 13, 73, 154, 20, 116, 217];
+
+/// Anchor discriminator of `strategy_total_value`: sha256("global:strategy_total_value")[..8]
+pub const STRATEGY_TOTAL_VALUE_DISCRIMINATOR: [u8; 8] = [136, 147, 226, 226, 161, 166, 139, 78];
+
+/// Accounts of an adapter call, in interface order
+pub struct AdapterAccounts<'a, 'info> {
+    pub adapter_program: &'a AccountInfo<'info>,
+    pub strategy: AccountInfo<'info>,
+    pub asset_vault: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub asset_mint: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    /// Adapter-specific accounts, appended as passed
+    pub remaining: &'a [AccountInfo<'info>],
+}
+
+/// Call the adapter instruction `discriminator` with `amount`, signed by the `Strategy` PDA
+/// through `strategy_seeds`
+pub fn invoke_adapter(
+    discriminator: [u8; 8],
+    amount: u64,
+    accounts: AdapterAccounts,
+    strategy_seeds: &[&[u8]],
+) -> Result<()> {
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(&amount.to_le_bytes());
+    call(data, accounts, strategy_seeds)
+}
+
+/// Ask the adapter for the strategy's `strategy_total_value`, signed by the `Strategy` PDA
+/// through `strategy_seeds`
+pub fn total_value(accounts: AdapterAccounts, strategy_seeds: &[&[u8]]) -> Result<u64> {
+    let adapter_program = accounts.adapter_program.key();
+    call(
+        STRATEGY_TOTAL_VALUE_DISCRIMINATOR.to_vec(),
+        accounts,
+        strategy_seeds,
+    )?;
+    let (program_id, data) = get_return_data().ok_or(VaultError::InvalidStrategyValue)?;
+    require_keys_eq!(
+        program_id,
+        adapter_program,
+        VaultError::InvalidStrategyValue
+    );
+    let value = data
+        .get(..8)
+        .ok_or(VaultError::InvalidStrategyValue)?
+        .try_into()
+        .unwrap();
+    Ok(u64::from_le_bytes(value))
+}
+
+/// Call the adapter with instruction `data`, signed by the `Strategy` PDA
+fn call(data: Vec<u8>, accounts: AdapterAccounts, strategy_seeds: &[&[u8]]) -> Result<()> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.strategy.key(), true),
+        AccountMeta::new(accounts.asset_vault.key(), false),
+        AccountMeta::new(accounts.reserve.key(), false),
+        AccountMeta::new_readonly(accounts.asset_mint.key(), false),
+        AccountMeta::new_readonly(accounts.token_program.key(), false),
+    ];
+    metas.extend(accounts.remaining.iter().map(|account| AccountMeta {
+        pubkey: account.key(),
+        is_signer: account.is_signer,
+        is_writable: account.is_writable,
+    }));
+
+    let ix = Instruction {
+        program_id: accounts.adapter_program.key(),
+        accounts: metas,
+        data,
+    };
+
+    let mut infos = vec![
+        accounts.strategy,
+        accounts.asset_vault,
+        accounts.reserve,
+        accounts.asset_mint,
+        accounts.token_program,
+        accounts.adapter_program.clone(),
+    ];
+    infos.extend_from_slice(accounts.remaining);
+    invoke_signed(&ix, &infos, &[strategy_seeds])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash::hash;
+
+    #[test]
+    fn discriminators_match_anchor_sighash() {
+        assert_eq!(
+            hash(b"global:strategy_deposit").to_bytes()[..8],
+            STRATEGY_DEPOSIT_DISCRIMINATOR
+        );
+        assert_eq!(
+            hash(b"global:strategy_withdraw").to_bytes()[..8],
+            STRATEGY_WITHDRAW_DISCRIMINATOR
+        );
+        assert_eq!(
+            hash(b"global:strategy_total_value").to_bytes()[..8],
+            STRATEGY_TOTAL_VALUE_DISCRIMINATOR
+        );
+    }
+}
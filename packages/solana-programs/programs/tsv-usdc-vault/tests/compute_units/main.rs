@@ -0,0 +1,176 @@
+//! Compute units of the vault's hot instructions, in the scenarios that change what they
+//! touch (first deposit of the vault or of a holder, partial or full redeem, first or later
+//! meta-redeem nonce, harvest with or without a management fee to book), checked against
+//! `budgets.txt`. Run with `pnpm bench:cu`; `TSV_CU_BLESS=1` rewrites the budgets from the
+//! measured units plus `HEADROOM_PERCENT`.
+
+#[allow(dead_code)]
+#[path = "../integration/fixtures.rs"]
+mod fixtures;
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::Keypair;
+
+use crate::fixtures::{sign, VaultFixture, ONE};
+
+const BUDGETS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/compute_units/budgets.txt"
+);
+
+/// Margin blessed budgets leave over the measured units
+const HEADROOM_PERCENT: u64 = 10;
+
+/// Measured units of each scenario, in the order run
+struct Bench {
+    fixture: VaultFixture,
+    units: Vec<(&'static str, u64)>,
+}
+
+impl Bench {
+    async fn measure(
+        &mut self,
+        scenario: &'static str,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) {
+        let units = self
+            .fixture
+            .send_measured(instructions, signers)
+            .await
+            .unwrap_or_else(|err| panic!("{scenario} failed: {err}"));
+        self.units.push((scenario, units));
+    }
+}
+
+#[tokio::test]
+async fn compute_units_within_budget() {
+    let fixture = VaultFixture::new(10, 10).await;
+    let mut bench = Bench {
+        fixture,
+        units: Vec::new(),
+    };
+    let first = bench.fixture.user(1_000 * ONE).await;
+    let holder = bench.fixture.user(1_000 * ONE).await;
+    let relayer = bench.fixture.user(0).await;
+
+    let deposit = bench.fixture.deposit(&first, 500 * ONE, None);
+    bench
+        .measure("deposit/first_in_vault", &[deposit], &[&first.keypair])
+        .await;
+    let deposit = bench.fixture.deposit(&holder, 500 * ONE, None);
+    bench
+        .measure("deposit/new_holder", &[deposit], &[&holder.keypair])
+        .await;
+    let deposit = bench.fixture.deposit(&holder, 500 * ONE, None);
+    bench
+        .measure("deposit/existing_holder", &[deposit], &[&holder.keypair])
+        .await;
+
+    let holder_shares = bench.fixture.share_account(&holder.pubkey());
+    let shares = bench.fixture.balance(&holder_shares).await;
+    let redeem = bench.fixture.redeem(&holder, shares / 2, None);
+    bench
+        .measure("redeem/partial", &[redeem], &[&holder.keypair])
+        .await;
+    let redeem = bench.fixture.redeem(&holder, shares - shares / 2, None);
+    bench
+        .measure("redeem/full", &[redeem], &[&holder.keypair])
+        .await;
+
+    let first_shares = bench.fixture.share_account(&first.pubkey());
+    let shares = bench.fixture.balance(&first_shares).await;
+    let approve = bench.fixture.approve_vault(&first, shares);
+    bench
+        .fixture
+        .send(&[approve], &[&first.keypair])
+        .await
+        .unwrap();
+    for scenario in ["meta_redeem/first_nonce", "meta_redeem/later_nonce"] {
+        let deadline = bench.fixture.now().await + 600;
+        let message = bench
+            .fixture
+            .redeem_message(&first.pubkey(), shares / 4, &first.pubkey(), 0, deadline)
+            .await;
+        let signature = sign(&first.keypair, &message);
+        let instructions = bench.fixture.meta_redeem(&relayer, &message, &signature, 0);
+        bench
+            .measure(scenario, &instructions, &[&relayer.keypair])
+            .await;
+    }
+
+    let harvest = bench.fixture.harvest();
+    bench.measure("harvest/idle", &[harvest], &[]).await;
+    let set_fee = bench.fixture.set_management_fee(200);
+    bench.fixture.through_timelock(&set_fee).await.unwrap();
+    bench.fixture.warp(24 * 60 * 60).await;
+    let harvest = bench.fixture.harvest();
+    bench
+        .measure("harvest/management_fee", &[harvest], &[])
+        .await;
+
+    check(&bench.units);
+}
+
+/// Print the units against the budgets and fail on any over budget or without one, or
+/// rewrite the budgets when blessing
+fn check(measured: &[(&'static str, u64)]) {
+    if std::env::var_os("TSV_CU_BLESS").is_some() {
+        bless(measured);
+        return;
+    }
+
+    let budgets = budgets();
+    let mut regressions = Vec::new();
+    println!("{:<28} {:>8} {:>8}", "scenario", "units", "budget");
+    for &(scenario, units) in measured {
+        let budget = budgets.get(scenario).copied();
+        let shown = budget.map_or("none".to_string(), |budget| budget.to_string());
+        println!("{scenario:<28} {units:>8} {shown:>8}");
+        match budget {
+            Some(budget) if units <= budget => {}
+            Some(budget) => regressions.push(format!("{scenario}: {units} > {budget}")),
+            None => regressions.push(format!("{scenario}: no budget")),
+        }
+    }
+    assert!(
+        regressions.is_empty(),
+        "compute units over budget (TSV_CU_BLESS=1 rewrites budgets.txt):\n{}",
+        regressions.join("\n")
+    );
+}
+
+/// `scenario units` lines of `budgets.txt`, `#` starting a comment
+fn budgets() -> BTreeMap<String, u64> {
+    let text = fs::read_to_string(BUDGETS).expect("budgets.txt");
+    text.lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (scenario, units) = line
+                .split_once(char::is_whitespace)
+                .unwrap_or_else(|| panic!("budget line without units: {line}"));
+            let units = units
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("invalid units in budget line: {line}"));
+            (scenario.to_string(), units)
+        })
+        .collect()
+}
+
+fn bless(measured: &[(&'static str, u64)]) {
+    let mut text = String::from(
+        "# Compute units each scenario of tests/compute_units may use, measured plus headroom.\n\
+         # Rewritten by `TSV_CU_BLESS=1 pnpm bench:cu`; lower them as instructions get cheaper.\n",
+    );
+    for &(scenario, units) in measured {
+        // Rounded up to a thousand so small changes don't churn the file
+        let budget = (units * (100 + HEADROOM_PERCENT) / 100).div_ceil(1_000) * 1_000;
+        text.push_str(&format!("{scenario} {budget}\n"));
+    }
+    fs::write(BUDGETS, text).expect("write budgets.txt");
+}
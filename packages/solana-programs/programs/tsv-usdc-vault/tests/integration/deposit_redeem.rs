@@ -0,0 +1,201 @@
+use anchor_spl::token::spl_token;
+use tsv_usdc_vault::math::Rounding;
+use tsv_usdc_vault::{VaultError, DEFAULT_PROFIT_UNLOCK_DURATION};
+
+use crate::fixtures::{assert_vault_error, VaultFixture, DEAD_SHARES, ONE};
+
+#[tokio::test]
+async fn deposit_then_redeem_round_trips() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(1_000 * ONE).await;
+    let user_shares = fixture.share_account(&user.pubkey());
+
+    let vault = fixture.vault_state().await;
+    let minted = vault.preview_deposit(1_000 * ONE).unwrap();
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    // The first deposit locks the dead shares out of what it mints
+    let shares = fixture.balance(&user_shares).await;
+    assert_eq!(shares, minted - DEAD_SHARES);
+    let dead_shares_account = fixture.dead_shares_account;
+    assert_eq!(fixture.balance(&dead_shares_account).await, DEAD_SHARES);
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.total_assets, 1_000 * ONE);
+    assert_eq!(vault.total_shares, minted);
+
+    let redeemed = fixture.preview_redeem(&user.pubkey(), shares).await;
+    fixture
+        .send(&[fixture.redeem(&user, shares, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    assert_eq!(fixture.balance(&user_shares).await, 0);
+    assert_eq!(fixture.balance(&user.asset).await, redeemed);
+    // Only the dead shares' value stays behind
+    assert!(redeemed <= 1_000 * ONE && 1_000 * ONE - redeemed <= 1);
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.total_assets, 1_000 * ONE - redeemed);
+    assert_eq!(vault.total_shares, DEAD_SHARES);
+}
+
+#[tokio::test]
+async fn withdraw_pays_exactly_the_assets_for_the_previewed_shares() {
+    let mut fixture = VaultFixture::new(0, 30).await;
+    let user = fixture.user(1_000 * ONE).await;
+    let user_shares = fixture.share_account(&user.pubkey());
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+    let shares = fixture.balance(&user_shares).await;
+
+    let assets = 100 * ONE + 1;
+    let now = fixture.now().await;
+    let position = fixture.position_state(&user.pubkey()).await;
+    let vault = fixture.vault_state().await.accrued(now).unwrap();
+    let burned = vault
+        .preview_withdraw(assets, &position, false, now, vault.total_assets)
+        .unwrap();
+    let result = fixture
+        .send(
+            &[fixture.withdraw(&user, assets, Some(burned - 1))],
+            &[&user.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::SlippageExceeded);
+    fixture
+        .send(
+            &[fixture.withdraw(&user, assets, Some(burned))],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    // The fee is taken from the shares burned, and their rounding stays in the vault
+    assert_eq!(fixture.balance(&user.asset).await, assets);
+    assert_eq!(fixture.balance(&user_shares).await, shares - burned);
+    let after = fixture.vault_state().await;
+    let gross = vault.convert_to_assets(burned, Rounding::Floor).unwrap();
+    assert_eq!(after.accrued_fees, vault.withdraw_fee(gross).unwrap());
+    assert_eq!(
+        after.total_assets,
+        1_000 * ONE - assets - after.accrued_fees
+    );
+}
+
+#[tokio::test]
+async fn later_deposits_mint_at_the_current_price() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let first = fixture.user(500 * ONE).await;
+    let second = fixture.user(250 * ONE).await;
+    fixture
+        .send(
+            &[fixture.deposit(&first, 500 * ONE, None)],
+            &[&first.keypair],
+        )
+        .await
+        .unwrap();
+
+    let minted = fixture
+        .vault_state()
+        .await
+        .preview_deposit(250 * ONE)
+        .unwrap();
+    fixture
+        .send(
+            &[fixture.deposit(&second, 250 * ONE, None)],
+            &[&second.keypair],
+        )
+        .await
+        .unwrap();
+
+    let second_shares = fixture.share_account(&second.pubkey());
+    assert_eq!(fixture.balance(&second_shares).await, minted);
+    let first_shares = fixture.share_account(&first.pubkey());
+    let first_balance = fixture.balance(&first_shares).await;
+    // Half the assets of the first depositor, so half its shares plus the dead ones
+    assert_eq!(minted, (first_balance + DEAD_SHARES) / 2);
+}
+
+#[tokio::test]
+async fn slippage_floors_are_enforced() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+
+    let shares = fixture
+        .vault_state()
+        .await
+        .preview_deposit(100 * ONE)
+        .unwrap()
+        - DEAD_SHARES;
+    let result = fixture
+        .send(
+            &[fixture.deposit(&user, 100 * ONE, Some(shares + 1))],
+            &[&user.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::SlippageExceeded);
+    fixture
+        .send(
+            &[fixture.deposit(&user, 100 * ONE, Some(shares))],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    let assets = fixture.preview_redeem(&user.pubkey(), shares).await;
+    let result = fixture
+        .send(
+            &[fixture.redeem(&user, shares, Some(assets + 1))],
+            &[&user.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::SlippageExceeded);
+}
+
+#[tokio::test]
+async fn synced_surplus_unlocks_into_the_share_price() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(1_000 * ONE).await;
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    // A direct transfer into the vault, booked as yield by `sync`
+    let donation = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        &fixture.asset_mint,
+        &fixture.asset_vault,
+        &fixture.payer(),
+        &[],
+        100 * ONE,
+    )
+    .unwrap();
+    fixture.send(&[donation], &[]).await.unwrap();
+    fixture.send(&[fixture.sync()], &[]).await.unwrap();
+
+    // Locked at first, so a deposit right before the sync can't capture it
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.total_assets, 1_100 * ONE);
+    assert_eq!(vault.locked_profit, 100 * ONE);
+    assert_eq!(vault.free_assets(), 1_000 * ONE);
+
+    fixture.warp(DEFAULT_PROFIT_UNLOCK_DURATION as i64).await;
+    fixture.send(&[fixture.sync()], &[]).await.unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.locked_profit, 0);
+    assert_eq!(vault.free_assets(), 1_100 * ONE);
+}
@@ -0,0 +1,81 @@
+use tsv_usdc_vault::{VaultError, EMERGENCY_ESCAPE_DELAY_SECONDS, MIN_TIMELOCK_DELAY_SECONDS};
+
+use crate::fixtures::{assert_vault_error, VaultFixture, ONE};
+
+#[tokio::test]
+async fn escape_hatch_opens_after_the_delay_once() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    // Action 0: without a requested escape the guardian must co-sign
+    let withdraw = fixture.emergency_withdraw(ONE);
+    let result = fixture.through_timelock(&withdraw).await;
+    assert_vault_error(result, VaultError::GuardianSignatureRequired);
+    // Only ever through the timelock
+    let result = fixture.send(&[withdraw.clone()], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    fixture
+        .send(&[fixture.request_emergency_escape()], &[])
+        .await
+        .unwrap();
+    fixture
+        .send(&[fixture.queue_action(1, &withdraw)], &[])
+        .await
+        .unwrap();
+    fixture.warp(EMERGENCY_ESCAPE_DELAY_SECONDS - 1).await;
+    let result = fixture
+        .send(&[fixture.execute_action(1, &withdraw)], &[])
+        .await;
+    assert_vault_error(result, VaultError::GuardianSignatureRequired);
+
+    fixture.warp(1).await;
+    let recovery_account = fixture.recovery_account();
+    let before = fixture.balance(&recovery_account).await;
+    fixture
+        .send(&[fixture.execute_action(1, &withdraw)], &[])
+        .await
+        .unwrap();
+    assert_eq!(fixture.balance(&recovery_account).await, before + ONE);
+    assert_eq!(fixture.vault_state().await.emergency_escape_ts, 0);
+
+    // The matured escape is used up by the withdraw
+    let result = fixture.through_timelock(&withdraw).await;
+    assert_vault_error(result, VaultError::GuardianSignatureRequired);
+}
+
+#[tokio::test]
+async fn guardian_cancels_a_requested_escape() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    let result = fixture
+        .send(&[fixture.cancel_emergency_escape()], &[])
+        .await;
+    assert_vault_error(result, VaultError::NoEmergencyEscape);
+
+    fixture
+        .send(&[fixture.request_emergency_escape()], &[])
+        .await
+        .unwrap();
+    fixture
+        .send(&[fixture.cancel_emergency_escape()], &[])
+        .await
+        .unwrap();
+    assert_eq!(fixture.vault_state().await.emergency_escape_ts, 0);
+
+    fixture
+        .warp(EMERGENCY_ESCAPE_DELAY_SECONDS - MIN_TIMELOCK_DELAY_SECONDS as i64)
+        .await;
+    let withdraw = fixture.emergency_withdraw(ONE);
+    let result = fixture.through_timelock(&withdraw).await;
+    assert_vault_error(result, VaultError::GuardianSignatureRequired);
+}
@@ -0,0 +1,423 @@
+use anchor_lang::error::ErrorCode;
+use anchor_spl::token::spl_token;
+use tsv_usdc_vault::math::{self, Rounding};
+use tsv_usdc_vault::{
+    SurplusPolicy, VaultError, DEFAULT_FEE_TIMELOCK_SECONDS, EMERGENCY_ESCAPE_DELAY_SECONDS,
+    MIN_FEE_TIMELOCK_SECONDS,
+};
+
+use crate::fixtures::{assert_anchor_error, assert_vault_error, VaultFixture, DEAD_SHARES, ONE};
+
+#[tokio::test]
+async fn deposit_and_redeem_fees_accrue_to_the_vault() {
+    let mut fixture = VaultFixture::new(50, 30).await;
+    let user = fixture.user(1_000 * ONE).await;
+    let user_shares = fixture.share_account(&user.pubkey());
+
+    let vault = fixture.vault_state().await;
+    let deposit_fee = vault.deposit_fee(1_000 * ONE).unwrap();
+    assert_eq!(deposit_fee, 5 * ONE);
+    let minted = vault.preview_deposit(1_000 * ONE).unwrap();
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    // Shares are minted on the assets net of the fee, which stays in the vault set aside
+    let shares = fixture.balance(&user_shares).await;
+    assert_eq!(shares, minted - DEAD_SHARES);
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.total_assets, 995 * ONE);
+    assert_eq!(vault.accrued_fees, deposit_fee);
+
+    let redeemed_shares = shares / 2;
+    let gross = vault
+        .convert_to_assets(redeemed_shares, Rounding::Floor)
+        .unwrap();
+    let redeem_fee = vault.withdraw_fee(gross).unwrap();
+    let net = fixture
+        .preview_redeem(&user.pubkey(), redeemed_shares)
+        .await;
+    assert_eq!(net, gross - redeem_fee);
+    fixture
+        .send(
+            &[fixture.redeem(&user, redeemed_shares, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(fixture.balance(&user.asset).await, net);
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.accrued_fees, deposit_fee + redeem_fee);
+    let asset_vault = fixture.asset_vault;
+    assert_eq!(
+        fixture.balance(&asset_vault).await,
+        vault.total_assets + vault.accrued_fees
+    );
+}
+
+#[tokio::test]
+async fn fees_are_collected_to_the_recipient_set_through_the_timelock() {
+    let mut fixture = VaultFixture::new(50, 0).await;
+    let user = fixture.user(1_000 * ONE).await;
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+    let treasury = fixture.user(0).await;
+
+    let set = fixture.set_fee_recipient(&treasury.asset);
+    let result = fixture.send(&[set.clone()], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    fixture.through_timelock(&set).await.unwrap();
+
+    let accrued = fixture.vault_state().await.accrued_fees;
+    assert_eq!(accrued, 5 * ONE);
+    fixture
+        .send(&[fixture.collect_fees(&treasury.asset)], &[])
+        .await
+        .unwrap();
+    assert_eq!(fixture.balance(&treasury.asset).await, accrued);
+    assert_eq!(fixture.vault_state().await.accrued_fees, 0);
+}
+
+#[tokio::test]
+async fn fee_update_waits_for_its_timelock() {
+    let mut fixture = VaultFixture::new(50, 30).await;
+    fixture
+        .send(&[fixture.queue_fee_update(40, 20)], &[])
+        .await
+        .unwrap();
+
+    let result = fixture.send(&[fixture.execute_fee_update()], &[]).await;
+    assert_vault_error(result, VaultError::TimelockNotExpired);
+
+    fixture.warp(DEFAULT_FEE_TIMELOCK_SECONDS as i64).await;
+    fixture
+        .send(&[fixture.execute_fee_update()], &[])
+        .await
+        .unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!((vault.deposit_fee_bps, vault.redeem_fee_bps), (40, 20));
+    assert_eq!(vault.fee_update_eta, 0);
+}
+
+#[tokio::test]
+async fn loosening_the_fee_timelock_waits_for_the_current_delay() {
+    let mut fixture = VaultFixture::new(50, 30).await;
+
+    let result = fixture
+        .send(
+            &[fixture.set_fee_timelock(MIN_FEE_TIMELOCK_SECONDS - 1, 25)],
+            &[],
+        )
+        .await;
+    assert_vault_error(result, VaultError::InvalidFeeTimelock);
+
+    // A larger step is queued; the delay and step in force don't change
+    fixture
+        .send(
+            &[fixture.set_fee_timelock(MIN_FEE_TIMELOCK_SECONDS, 50)],
+            &[],
+        )
+        .await
+        .unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.fee_timelock_seconds, DEFAULT_FEE_TIMELOCK_SECONDS);
+    assert_eq!(vault.max_fee_step_bps, 25);
+    let result = fixture.send(&[fixture.queue_fee_update(80, 30)], &[]).await;
+    assert_vault_error(result, VaultError::FeeStepTooLarge);
+    let result = fixture.send(&[fixture.execute_fee_timelock()], &[]).await;
+    assert_vault_error(result, VaultError::TimelockNotExpired);
+
+    fixture.warp(DEFAULT_FEE_TIMELOCK_SECONDS as i64).await;
+    fixture
+        .send(&[fixture.execute_fee_timelock()], &[])
+        .await
+        .unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.fee_timelock_seconds, MIN_FEE_TIMELOCK_SECONDS);
+    assert_eq!(vault.max_fee_step_bps, 50);
+    assert_eq!(vault.fee_timelock_eta, 0);
+    fixture
+        .send(&[fixture.queue_fee_update(80, 30)], &[])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn tightening_the_fee_timelock_applies_at_once() {
+    let mut fixture = VaultFixture::new(50, 30).await;
+    fixture
+        .send(
+            &[fixture.set_fee_timelock(MIN_FEE_TIMELOCK_SECONDS, 50)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // Tightening drops the queued loosening
+    let delay = 2 * DEFAULT_FEE_TIMELOCK_SECONDS;
+    fixture
+        .send(&[fixture.set_fee_timelock(delay, 10)], &[])
+        .await
+        .unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(
+        (vault.fee_timelock_seconds, vault.max_fee_step_bps),
+        (delay, 10)
+    );
+    assert_eq!(vault.fee_timelock_eta, 0);
+    let result = fixture.send(&[fixture.execute_fee_timelock()], &[]).await;
+    assert_vault_error(result, VaultError::NoPendingFeeUpdate);
+}
+
+#[tokio::test]
+async fn fee_update_is_bounded_by_the_max_step() {
+    let mut fixture = VaultFixture::new(50, 30).await;
+
+    // The default step is 25 bps
+    let result = fixture.send(&[fixture.queue_fee_update(80, 30)], &[]).await;
+    assert_vault_error(result, VaultError::FeeStepTooLarge);
+    let result = fixture.send(&[fixture.execute_fee_update()], &[]).await;
+    assert_vault_error(result, VaultError::NoPendingFeeUpdate);
+}
+
+#[tokio::test]
+async fn redeem_surcharges_are_bounded_by_the_max_step() {
+    let mut fixture = VaultFixture::new(0, 30).await;
+
+    // The default step is 25 bps over the highest fee a redemption can pay, 30 bps so far
+    let result = fixture
+        .through_timelock(&fixture.set_redeem_fee_curve(56))
+        .await;
+    assert_vault_error(result, VaultError::FeeStepTooLarge);
+    fixture
+        .through_timelock(&fixture.set_redeem_fee_curve(55))
+        .await
+        .unwrap();
+
+    // Penalties stack on top of the curve's top
+    let result = fixture
+        .through_timelock(&fixture.set_early_exit_penalty(86_400, 26))
+        .await;
+    assert_vault_error(result, VaultError::FeeStepTooLarge);
+    fixture
+        .through_timelock(&fixture.set_cooldown(3_600, 25))
+        .await
+        .unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.max_effective_redeem_fee_bps(), 80);
+
+    // Lowering a surcharge isn't bounded
+    fixture
+        .through_timelock(&fixture.set_redeem_fee_curve(0))
+        .await
+        .unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.max_effective_redeem_fee_bps(), 55);
+}
+
+#[tokio::test]
+async fn redeem_preview_charges_the_fee_at_the_current_utilization() {
+    let mut fixture = VaultFixture::new(0, 10).await;
+    let user = fixture.user(1_000 * ONE).await;
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+    fixture
+        .through_timelock(&fixture.set_redeem_fee_curve(30))
+        .await
+        .unwrap();
+
+    // Half the USDC leaves the asset vault but stays in `total_assets`, as if deployed
+    fixture
+        .send(&[fixture.request_emergency_escape()], &[])
+        .await
+        .unwrap();
+    fixture.warp(EMERGENCY_ESCAPE_DELAY_SECONDS).await;
+    fixture
+        .through_timelock(&fixture.emergency_withdraw(500 * ONE))
+        .await
+        .unwrap();
+
+    // 10 bps plus half the curve's 20 bps spread
+    let user_shares = fixture.share_account(&user.pubkey());
+    let shares = fixture.balance(&user_shares).await / 10;
+    let vault = fixture.vault_state().await;
+    let gross = vault.convert_to_assets(shares, Rounding::Floor).unwrap();
+    let net = fixture.preview_redeem(&user.pubkey(), shares).await;
+    assert_eq!(net, gross - math::fee_on_amount(gross, 20).unwrap());
+
+    fixture
+        .send(&[fixture.redeem(&user, shares, None)], &[&user.keypair])
+        .await
+        .unwrap();
+    assert_eq!(fixture.balance(&user.asset).await, net);
+}
+
+#[tokio::test]
+async fn redeem_preview_charges_the_decaying_early_exit_penalty() {
+    const DAY: i64 = 24 * 60 * 60;
+    let mut fixture = VaultFixture::new(0, 10).await;
+    fixture
+        .through_timelock(&fixture.set_early_exit_penalty(10 * DAY as u32, 25))
+        .await
+        .unwrap();
+    let user = fixture.user(1_000 * ONE).await;
+    fixture
+        .send(
+            &[fixture.deposit(&user, 1_000 * ONE, None)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    // Four days into the window, 15 of the 25 bps are left on top of the 10 bps fee
+    fixture.warp(4 * DAY).await;
+    let user_shares = fixture.share_account(&user.pubkey());
+    let shares = fixture.balance(&user_shares).await / 10;
+    let vault = fixture.vault_state().await;
+    let gross = vault.convert_to_assets(shares, Rounding::Floor).unwrap();
+    let net = fixture.preview_redeem(&user.pubkey(), shares).await;
+    assert_eq!(net, gross - math::fee_on_amount(gross, 25).unwrap());
+
+    fixture
+        .send(&[fixture.redeem(&user, shares, None)], &[&user.keypair])
+        .await
+        .unwrap();
+    assert_eq!(fixture.balance(&user.asset).await, net);
+}
+
+#[tokio::test]
+async fn performance_fee_is_bounded_and_needs_a_treasury() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let result = fixture
+        .through_timelock(&fixture.set_performance_fee(2_001))
+        .await;
+    assert_vault_error(result, VaultError::InvalidFee);
+    let result = fixture
+        .through_timelock(&fixture.set_performance_fee(1_000))
+        .await;
+    assert_vault_error(result, VaultError::TreasuryNotSet);
+}
+
+#[tokio::test]
+async fn instant_redeem_charges_its_premium() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    let result = fixture
+        .through_timelock(&fixture.set_instant_redeem_premium(501))
+        .await;
+    assert_vault_error(result, VaultError::InvalidFee);
+    fixture
+        .through_timelock(&fixture.set_instant_redeem_premium(100))
+        .await
+        .unwrap();
+
+    let shares = fixture
+        .balance(&fixture.share_account(&user.pubkey()))
+        .await;
+    let gross = fixture
+        .vault_state()
+        .await
+        .convert_to_assets(shares, Rounding::Floor)
+        .unwrap();
+    fixture
+        .send(&[fixture.instant_redeem(&user, shares)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    // 1% of the gross assets, rounded up, stays with the vault
+    let premium = gross.div_ceil(100);
+    assert_eq!(fixture.balance(&user.asset).await, gross - premium);
+}
+
+#[tokio::test]
+async fn management_fee_stops_at_the_frozen_wind_down() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+    fixture
+        .through_timelock(&fixture.set_management_fee(500))
+        .await
+        .unwrap();
+    fixture.warp(30 * 24 * 60 * 60).await;
+
+    fixture
+        .send(
+            &[fixture.start_wind_down(), fixture.freeze_wind_down()],
+            &[],
+        )
+        .await
+        .unwrap();
+    let frozen = fixture.vault_state().await;
+    assert!(frozen.unminted_fee_shares > 0);
+    assert_eq!(frozen.total_shares, frozen.wind_down_shares);
+
+    fixture.warp(30 * 24 * 60 * 60).await;
+    fixture.send(&[fixture.harvest()], &[]).await.unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.total_shares, frozen.total_shares);
+    assert_eq!(vault.unminted_fee_shares, frozen.unminted_fee_shares);
+}
+
+#[tokio::test]
+async fn surplus_fees_only_go_to_the_fee_recipient() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(0).await;
+    let other = fixture.user(0).await;
+    fixture
+        .through_timelock(&fixture.set_fee_recipient(&user.asset))
+        .await
+        .unwrap();
+    fixture
+        .send(&[fixture.set_surplus_policy(SurplusPolicy::Fees)], &[])
+        .await
+        .unwrap();
+
+    // A direct transfer into the vault, swept out by `sync`
+    let donation = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        &fixture.asset_mint,
+        &fixture.asset_vault,
+        &fixture.payer(),
+        &[],
+        100 * ONE,
+    )
+    .unwrap();
+    fixture.send(&[donation], &[]).await.unwrap();
+
+    // The keeper can't pick where it goes
+    let result = fixture
+        .send(&[fixture.sync_fees(Some(other.asset))], &[])
+        .await;
+    assert_anchor_error(result, ErrorCode::ConstraintAddress);
+
+    fixture
+        .send(&[fixture.sync_fees(Some(user.asset))], &[])
+        .await
+        .unwrap();
+    assert_eq!(fixture.balance(&user.asset).await, 100 * ONE);
+    assert_eq!(fixture.balance(&other.asset).await, 0);
+    assert_eq!(fixture.vault_state().await.total_assets, 0);
+}
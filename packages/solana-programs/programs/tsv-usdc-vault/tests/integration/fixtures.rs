@@ -0,0 +1,1508 @@
+//! A vault over a fresh asset mint in `solana-program-test`, funded users, and the instructions
+//! the tests send, with accounts in the program's order.
+//!
+//! The program and the Metaplex Token Metadata program `initialize` calls are loaded as BPF:
+//! `tsv_usdc_vault.so` from `SBF_OUT_DIR` (`anchor build`) and `mpl_token_metadata.so` from
+//! `tests/fixtures` (`solana program dump`), see `pnpm test:rust`.
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::{
+    AccountDeserialize, AnchorSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_interface::TokenAccount;
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::{ed25519_program, system_instruction, system_program, sysvar};
+use tsv_meta_message::{MetaAction, MetaMessage};
+use tsv_usdc_vault::math::VIRTUAL_DECIMALS_OFFSET;
+use tsv_usdc_vault::migration;
+use tsv_usdc_vault::{
+    ActionAccount, RiskTier, Strategy, SurplusPolicy, Timelock, UserNonce, UserPosition, Vault,
+    VaultError, VaultInfo, MIN_TIMELOCK_DELAY_SECONDS,
+};
+
+pub const ASSET_DECIMALS: u8 = 6;
+
+/// One whole asset token
+pub const ONE: u64 = 10u64.pow(ASSET_DECIMALS as u32);
+
+/// Shares the first deposit locks in the dead shares account
+pub const DEAD_SHARES: u64 = 1_000;
+
+/// Executable standing in for a strategy adapter: any program registers, and calling it fails in
+/// the adapter, after the vault's own checks
+pub const ADAPTER_PROGRAM: Pubkey = anchor_spl::metadata::ID;
+
+/// Lamports each user starts with, for rent and fees
+const USER_LAMPORTS: u64 = 1_000_000_000;
+
+/// Size of the Ed25519 instruction header (signature count + padding) and its one offsets entry
+const ED25519_HEADER_LEN: u16 = 2 + 14;
+
+/// Offsets of an Ed25519 instruction pointing into its own data
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Wallet with an asset account, also the relayer of meta-transactions
+pub struct User {
+    pub keypair: Keypair,
+    /// Associated asset account
+    pub asset: Pubkey,
+}
+
+impl User {
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+}
+
+/// Accounts of a vault the fixture pointed at, such as the migration target of the vault it
+/// points at next
+#[derive(Clone, Copy)]
+pub struct VaultAddresses {
+    pub vault: Pubkey,
+    pub share_mint: Pubkey,
+    pub asset_vault: Pubkey,
+    pub dead_shares_account: Pubkey,
+}
+
+/// Vault initialized by the test payer, which holds every role and the asset mint authority
+pub struct VaultFixture {
+    pub context: ProgramTestContext,
+    pub asset_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub vault: Pubkey,
+    pub asset_vault: Pubkey,
+    pub dead_shares_account: Pubkey,
+    pub genesis_hash: [u8; 32],
+    /// Transactions sent so far
+    sent: u64,
+}
+
+impl VaultFixture {
+    /// Vault with the given fees over a new SPL Token asset mint
+    pub async fn new(deposit_fee_bps: u16, redeem_fee_bps: u16) -> Self {
+        let mut program_test = ProgramTest::new("tsv_usdc_vault", tsv_usdc_vault::ID, None);
+        program_test.add_program("mpl_token_metadata", anchor_spl::metadata::ID, None);
+        program_test.prefer_bpf(true);
+        let context = program_test.start_with_context().await;
+
+        let asset_mint = Keypair::new();
+        let share_mint = Keypair::new();
+        let asset_vault = Keypair::new();
+        let vault = pda(&[b"vault", asset_mint.pubkey().as_ref()]);
+        let mut fixture = Self {
+            genesis_hash: context.genesis_config().hash().to_bytes(),
+            context,
+            asset_mint: asset_mint.pubkey(),
+            share_mint: share_mint.pubkey(),
+            vault,
+            asset_vault: asset_vault.pubkey(),
+            dead_shares_account: pda(&[b"dead_shares", vault.as_ref()]),
+            sent: 0,
+        };
+
+        let authority = fixture.payer();
+        let share_decimals = ASSET_DECIMALS + VIRTUAL_DECIMALS_OFFSET as u8;
+        let mut ixs = fixture
+            .create_mint(&asset_mint.pubkey(), &authority, ASSET_DECIMALS)
+            .await;
+        ixs.extend(
+            fixture
+                .create_mint(&share_mint.pubkey(), &vault, share_decimals)
+                .await,
+        );
+        fixture
+            .send(&ixs, &[&asset_mint, &share_mint])
+            .await
+            .expect("create mints");
+
+        let recovery_account = fixture.create_asset_account(&authority).await;
+        let accounts = tsv_usdc_vault::accounts::Initialize {
+            vault,
+            authority,
+            asset_mint: fixture.asset_mint,
+            share_mint: fixture.share_mint,
+            asset_vault: fixture.asset_vault,
+            dead_shares_account: fixture.dead_shares_account,
+            recovery_account,
+            share_metadata: share_metadata(&fixture.share_mint),
+            vault_metadata: pda(&[b"vault_metadata", vault.as_ref()]),
+            token_program: spl_token::ID,
+            token_metadata_program: anchor_spl::metadata::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::Initialize {
+            deposit_fee_bps,
+            redeem_fee_bps,
+            dead_shares: DEAD_SHARES,
+            genesis_hash: fixture.genesis_hash,
+            allow_privileged_extensions: false,
+            share_name: "Talken Stable Vault USDC".into(),
+            share_symbol: "tsvUSDC".into(),
+            share_uri: String::new(),
+            vault_info: VaultInfo {
+                name: "Talken Stable Vault USDC".into(),
+                description: String::new(),
+                uri: String::new(),
+                risk_tier: RiskTier::Conservative,
+            },
+        };
+        let initialize = instruction(accounts, args);
+        fixture
+            .send(&[initialize], &[&asset_vault])
+            .await
+            .expect("initialize");
+        fixture
+    }
+
+    /// Point the fixture at a vault the first deployed program would have written at the
+    /// single-vault `[b"vault"]` PDA: a `BaselineVault` account charging `fee_bps` on deposits
+    /// and redeems, over a new share mint and asset vault, without a dead shares account
+    pub async fn baseline_vault(&mut self, fee_bps: u16) {
+        let (vault, bump) = Pubkey::find_program_address(&[b"vault"], &tsv_usdc_vault::ID);
+        let share_mint = Keypair::new();
+        let share_decimals = ASSET_DECIMALS + VIRTUAL_DECIMALS_OFFSET as u8;
+        let ixs = self
+            .create_mint(&share_mint.pubkey(), &vault, share_decimals)
+            .await;
+        self.send(&ixs, &[&share_mint])
+            .await
+            .expect("create share mint");
+        let asset_vault = self.create_asset_account(&vault).await;
+
+        let baseline = (
+            self.payer(),
+            self.asset_mint,
+            share_mint.pubkey(),
+            asset_vault,
+            0u64,
+            0u64,
+            fee_bps,
+            bump,
+        );
+        let mut data = Vault::DISCRIMINATOR.to_vec();
+        baseline.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), migration::BASELINE_VAULT_SIZE);
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let account = Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: tsv_usdc_vault::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.context.set_account(&vault, &account.into());
+
+        self.vault = vault;
+        self.share_mint = share_mint.pubkey();
+        self.asset_vault = asset_vault;
+        self.dead_shares_account = pda(&[b"dead_shares", vault.as_ref()]);
+    }
+
+    /// Accounts of the vault the fixture points at
+    pub fn addresses(&self) -> VaultAddresses {
+        VaultAddresses {
+            vault: self.vault,
+            share_mint: self.share_mint,
+            asset_vault: self.asset_vault,
+            dead_shares_account: self.dead_shares_account,
+        }
+    }
+
+    /// Point the fixture back at a vault of the same asset mint it pointed at before
+    pub fn point_at(&mut self, addresses: &VaultAddresses) {
+        self.vault = addresses.vault;
+        self.share_mint = addresses.share_mint;
+        self.asset_vault = addresses.asset_vault;
+        self.dead_shares_account = addresses.dead_shares_account;
+    }
+
+    /// Test payer: fee payer of every transaction, vault authority and asset mint authority
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /// New wallet with lamports and an asset account holding `assets`
+    pub async fn user(&mut self, assets: u64) -> User {
+        let keypair = Keypair::new();
+        let fund = system_instruction::transfer(&self.payer(), &keypair.pubkey(), USER_LAMPORTS);
+        self.send(&[fund], &[]).await.expect("fund user");
+
+        let asset = self.create_asset_account(&keypair.pubkey()).await;
+        if assets > 0 {
+            let mint_to = spl_token::instruction::mint_to(
+                &spl_token::ID,
+                &self.asset_mint,
+                &asset,
+                &self.payer(),
+                &[],
+                assets,
+            )
+            .unwrap();
+            self.send(&[mint_to], &[]).await.expect("mint assets");
+        }
+        User { keypair, asset }
+    }
+
+    /// Send `instructions` in one transaction paid by the test payer. A compute unit price
+    /// unique to each transaction keeps a repeated one from being deduplicated, without
+    /// waiting for a new blockhash
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let transaction = self.transaction(instructions, signers).await;
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|err| err.unwrap())
+    }
+
+    /// `send`, returning the compute units the vault program's last top-level instruction
+    /// consumed, CPIs included, as the runtime logs them
+    pub async fn send_measured(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<u64, TransactionError> {
+        let transaction = self.transaction(instructions, signers).await;
+        let outcome = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .expect("banks client");
+        outcome.result?;
+        let logs = outcome.metadata.expect("transaction metadata").log_messages;
+        // Self-CPIs emitting events log their own totals first; the top-level one comes last
+        let prefix = format!("Program {} consumed ", tsv_usdc_vault::ID);
+        let units = logs
+            .iter()
+            .rev()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .and_then(|rest| rest.split(' ').next())
+            .expect("vault program compute units in the logs");
+        Ok(units.parse().unwrap())
+    }
+
+    /// Transaction of `instructions` paid by the test payer, see `send`
+    async fn transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Transaction {
+        self.sent += 1;
+        let mut all_instructions =
+            vec![ComputeBudgetInstruction::set_compute_unit_price(self.sent)];
+        all_instructions.extend_from_slice(instructions);
+        let blockhash = self
+            .context
+            .banks_client
+            .get_latest_blockhash()
+            .await
+            .expect("blockhash");
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        Transaction::new_signed_with_payer(
+            &all_instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        )
+    }
+
+    pub async fn vault_state(&mut self) -> Vault {
+        let address = self.vault;
+        self.anchor_account(&address).await
+    }
+
+    pub async fn strategy_state(&mut self) -> Strategy {
+        let address = self.strategy();
+        self.anchor_account(&address).await
+    }
+
+    /// `owner`'s position, empty before their first deposit
+    pub async fn position_state(&mut self, owner: &Pubkey) -> UserPosition {
+        let address = self.position(owner);
+        if !self.exists(&address).await {
+            return UserPosition::default();
+        }
+        self.anchor_account(&address).await
+    }
+
+    /// What the `preview_redeem` view returns for `owner`'s `shares` now: the vault accrued to
+    /// the current clock, charging the fee `redeem` would on its idle USDC
+    pub async fn preview_redeem(&mut self, owner: &Pubkey, shares: u64) -> u64 {
+        let now = self.now().await;
+        let vault = self.vault_state().await.accrued(now).unwrap();
+        let position = self.position_state(owner).await;
+        let address = self.asset_vault;
+        let asset_vault: TokenAccount = self.anchor_account(&address).await;
+        vault
+            .preview_redeem(
+                shares,
+                &position,
+                false,
+                now,
+                vault.idle_assets(&asset_vault),
+            )
+            .unwrap()
+    }
+
+    /// Meta-transaction nonce `owner` must sign next
+    pub async fn nonce(&mut self, owner: &Pubkey) -> u64 {
+        let address = pda(&[b"nonce", owner.as_ref()]);
+        match self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+        {
+            Some(account) => {
+                UserNonce::try_deserialize(&mut account.data.as_slice())
+                    .unwrap()
+                    .nonce
+            }
+            None => 0,
+        }
+    }
+
+    /// Whether `address` holds an account
+    pub async fn exists(&mut self, address: &Pubkey) -> bool {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    /// Amount held by `token_account`, zero if it doesn't exist
+    pub async fn balance(&mut self, token_account: &Pubkey) -> u64 {
+        match self
+            .context
+            .banks_client
+            .get_account(*token_account)
+            .await
+            .unwrap()
+        {
+            Some(account) => {
+                spl_token::state::Account::unpack(&account.data)
+                    .unwrap()
+                    .amount
+            }
+            None => 0,
+        }
+    }
+
+    /// Move the clock `seconds` forward
+    pub async fn warp(&mut self, seconds: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp
+    }
+
+    pub fn share_account(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, &self.share_mint)
+    }
+
+    /// `deposit` of `assets` by `user` for itself
+    pub fn deposit(&self, user: &User, assets: u64, min_shares_out: Option<u64>) -> Instruction {
+        let user = user.pubkey();
+        let accounts = tsv_usdc_vault::accounts::Deposit {
+            vault: self.vault,
+            user,
+            user_asset: get_associated_token_address(&user, &self.asset_mint),
+            receiver: user,
+            share_mint: self.share_mint,
+            receiver_shares: self.share_account(&user),
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            dead_shares_account: self.dead_shares_account,
+            receiver_position: self.position(&user),
+            fee_exemption: None,
+            allowlist_entry: None,
+            cost_basis: None,
+            user_blocklist: self.blocklist_entry(&user),
+            receiver_blocklist: self.blocklist_entry(&user),
+            token_program: spl_token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::Deposit {
+            assets,
+            min_shares_out,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `redeem` of `user`'s `shares` to its own asset account
+    pub fn redeem(&self, user: &User, shares: u64, min_assets_out: Option<u64>) -> Instruction {
+        let user = user.pubkey();
+        let accounts = tsv_usdc_vault::accounts::Redeem {
+            vault: self.vault,
+            user,
+            asset_mint: self.asset_mint,
+            user_asset: get_associated_token_address(&user, &self.asset_mint),
+            receiver_asset: None,
+            user_shares: self.share_account(&user),
+            asset_vault: self.asset_vault,
+            share_mint: self.share_mint,
+            user_position: self.position(&user),
+            fee_exemption: None,
+            allowlist_entry: None,
+            cost_basis: None,
+            user_blocklist: self.blocklist_entry(&user),
+            receiver_blocklist: None,
+            token_program: spl_token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::Redeem {
+            shares,
+            min_assets_out,
+            adapter_accounts: Vec::new(),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `withdraw` of `assets` for `user` to its own asset account
+    pub fn withdraw(&self, user: &User, assets: u64, max_shares_in: Option<u64>) -> Instruction {
+        let user = user.pubkey();
+        let accounts = tsv_usdc_vault::accounts::Withdraw {
+            vault: self.vault,
+            user,
+            asset_mint: self.asset_mint,
+            user_asset: get_associated_token_address(&user, &self.asset_mint),
+            user_shares: self.share_account(&user),
+            asset_vault: self.asset_vault,
+            share_mint: self.share_mint,
+            user_position: self.position(&user),
+            fee_exemption: None,
+            allowlist_entry: None,
+            cost_basis: None,
+            user_blocklist: self.blocklist_entry(&user),
+            token_program: spl_token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::Withdraw {
+            assets,
+            max_shares_in,
+            adapter_accounts: Vec::new(),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `instant_redeem` of `user`'s `shares` to its own asset account
+    pub fn instant_redeem(&self, user: &User, shares: u64) -> Instruction {
+        let mut ix = self.redeem(user, shares, None);
+        ix.data = tsv_usdc_vault::instruction::InstantRedeem {
+            shares,
+            min_assets_out: None,
+        }
+        .data();
+        ix
+    }
+
+    /// `request_withdraw` of `user`'s `shares` in withdrawal epoch `epoch`, the vault's current
+    pub fn request_withdraw(&self, user: &User, shares: u64, epoch: u64) -> Instruction {
+        let owner = user.pubkey();
+        let accounts = tsv_usdc_vault::accounts::RequestWithdraw {
+            vault: self.vault,
+            owner,
+            owner_shares: self.share_account(&owner),
+            share_mint: self.share_mint,
+            withdraw_request: self.withdraw_request(&owner, epoch),
+            owner_position: self.position(&owner),
+            allowlist_entry: None,
+            owner_blocklist: self.blocklist_entry(&owner),
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::RequestWithdraw { shares };
+        instruction(accounts, args)
+    }
+
+    /// `process_epoch` of withdrawal epoch `epoch`, the vault's current, by the keeper
+    pub fn process_epoch(&self, epoch: u64) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::ProcessEpoch {
+            vault: self.vault,
+            keeper: self.payer(),
+            withdraw_epoch: self.withdraw_epoch(epoch),
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::ProcessEpoch {
+            adapter_accounts: Vec::new(),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `claim_withdraw` of `user`'s request made in epoch `epoch`, from that epoch's settlement
+    pub fn claim_withdraw(&self, user: &User, epoch: u64) -> Instruction {
+        let owner = user.pubkey();
+        let accounts = tsv_usdc_vault::accounts::ClaimWithdraw {
+            vault: self.vault,
+            owner,
+            withdraw_request: self.withdraw_request(&owner, epoch),
+            asset_mint: self.asset_mint,
+            owner_asset: user.asset,
+            asset_vault: self.asset_vault,
+            owner_blocklist: self.blocklist_entry(&owner),
+            token_program: spl_token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::ClaimWithdraw {};
+        let mut ix = instruction(accounts, args);
+        ix.accounts
+            .push(AccountMeta::new(self.withdraw_epoch(epoch), false));
+        ix
+    }
+
+    /// `set_pause_flags` signed by `pauser`
+    pub fn set_pause_flags(
+        &self,
+        pauser: &Pubkey,
+        paused_deposits: bool,
+        paused_redeems: bool,
+        paused_meta: bool,
+    ) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::SetPauseFlags {
+            vault: self.vault,
+            pauser: *pauser,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetPauseFlags {
+            paused_deposits,
+            paused_redeems,
+            paused_meta,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_fee_timelock` by the authority
+    pub fn set_fee_timelock(
+        &self,
+        fee_timelock_seconds: u32,
+        max_fee_step_bps: u16,
+    ) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetFeeTimelock {
+            fee_timelock_seconds,
+            max_fee_step_bps,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `execute_fee_timelock` by the authority
+    pub fn execute_fee_timelock(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::ExecuteFeeTimelock {})
+    }
+
+    /// `set_management_fee` minting to the payer's share account, for the timelock to sign
+    pub fn set_management_fee(&self, management_fee_bps: u16) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetManagementFee {
+            management_fee_bps,
+            treasury_shares: self.share_account(&self.payer()),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_performance_fee`, for the timelock to sign
+    pub fn set_performance_fee(&self, performance_fee_bps: u16) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetPerformanceFee {
+            performance_fee_bps,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_instant_redeem_premium`, for the timelock to sign
+    pub fn set_instant_redeem_premium(&self, instant_redeem_premium_bps: u16) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetInstantRedeemPremium {
+            instant_redeem_premium_bps,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_redeem_fee_curve`, for the timelock to sign
+    pub fn set_redeem_fee_curve(&self, max_redeem_fee_bps: u16) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetRedeemFeeCurve { max_redeem_fee_bps };
+        instruction(accounts, args)
+    }
+
+    /// `set_cooldown`, for the timelock to sign
+    pub fn set_cooldown(&self, cooldown_seconds: u32, cooldown_penalty_bps: u16) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetCooldown {
+            cooldown_seconds,
+            cooldown_penalty_bps,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_early_exit_penalty`, for the timelock to sign
+    pub fn set_early_exit_penalty(
+        &self,
+        early_exit_window_seconds: u32,
+        early_exit_penalty_bps: u16,
+    ) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetEarlyExitPenalty {
+            early_exit_window_seconds,
+            early_exit_penalty_bps,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_buffer_bps` by the authority
+    pub fn set_buffer_bps(&self, buffer_bps: u16) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::SetBufferBps { buffer_bps },
+        )
+    }
+
+    /// `set_surplus_policy` by the authority
+    pub fn set_surplus_policy(&self, policy: SurplusPolicy) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::SetSurplusPolicy { policy },
+        )
+    }
+
+    /// `set_fee_recipient`, for the timelock to sign
+    pub fn set_fee_recipient(&self, fee_recipient: &Pubkey) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::TimelockedConfig {
+            vault: self.vault,
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetFeeRecipient {
+            fee_recipient: *fee_recipient,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `collect_fees` to `fee_recipient`, sent by the payer
+    pub fn collect_fees(&self, fee_recipient: &Pubkey) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::CollectFees {
+            vault: self.vault,
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            fee_recipient: *fee_recipient,
+            token_program: spl_token::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::CollectFees {})
+    }
+
+    /// `start_wind_down` by the authority
+    pub fn start_wind_down(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::StartWindDown {})
+    }
+
+    /// `freeze_wind_down` by the authority
+    pub fn freeze_wind_down(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::FreezeWindDown {})
+    }
+
+    /// `migrate_state` by the authority
+    pub fn migrate_state(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::MigrateState {
+            vault: self.vault,
+            authority: self.payer(),
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::MigrateState {
+            genesis_hash: self.genesis_hash,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `set_lockup_seconds` by the authority
+    pub fn set_lockup_seconds(&self, lockup_seconds: u32) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetLockupSeconds { lockup_seconds };
+        instruction(accounts, args)
+    }
+
+    /// `set_migration_target` to `target` by the authority
+    pub fn set_migration_target(&self, target: &Pubkey) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::SetMigrationTarget {
+            vault: self.vault,
+            authority: self.payer(),
+            target_vault: Some(*target),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::SetMigrationTarget {};
+        instruction(accounts, args)
+    }
+
+    /// `migrate_position` of `user`'s `shares` to the `target` vault
+    pub fn migrate_position(
+        &self,
+        user: &User,
+        shares: u64,
+        target: &VaultAddresses,
+    ) -> Instruction {
+        let user = user.pubkey();
+        let accounts = tsv_usdc_vault::accounts::MigratePosition {
+            source_vault: self.vault,
+            target_vault: target.vault,
+            user,
+            asset_mint: self.asset_mint,
+            source_share_mint: self.share_mint,
+            user_source_shares: self.share_account(&user),
+            source_asset_vault: self.asset_vault,
+            source_position: self.position(&user),
+            target_share_mint: target.share_mint,
+            user_target_shares: get_associated_token_address(&user, &target.share_mint),
+            target_asset_vault: target.asset_vault,
+            target_dead_shares_account: target.dead_shares_account,
+            target_position: pda(&[b"position", target.vault.as_ref(), user.as_ref()]),
+            source_allowlist_entry: None,
+            target_allowlist_entry: None,
+            source_blocklist: self.blocklist_entry(&user),
+            target_blocklist: pda(&[b"blocklist", target.vault.as_ref(), user.as_ref()]),
+            token_program: spl_token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::MigratePosition {
+            shares,
+            min_shares_out: None,
+            adapter_accounts: Vec::new(),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `init_legacy_accounts` by the authority with `DEAD_SHARES`, recovering to
+    /// `recovery_account`
+    pub fn init_legacy_accounts(&self, recovery_account: &Pubkey) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::InitLegacyAccounts {
+            vault: self.vault,
+            authority: self.payer(),
+            share_mint: self.share_mint,
+            dead_shares_account: self.dead_shares_account,
+            recovery_account: *recovery_account,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::InitLegacyAccounts {
+            dead_shares: DEAD_SHARES,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `harvest` by the keeper over no strategies, booking only the management fee and
+    /// unlocking profit
+    pub fn harvest(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::Harvest {
+            vault: self.vault,
+            keeper: self.payer(),
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            token_program: spl_token::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::Harvest {
+            adapter_accounts: Vec::new(),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `request_emergency_escape` by the authority
+    pub fn request_emergency_escape(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateConfig {
+            vault: self.vault,
+            authority: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::RequestEmergencyEscape {},
+        )
+    }
+
+    /// `cancel_emergency_escape` by the guardian
+    pub fn cancel_emergency_escape(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::GuardianPause {
+            vault: self.vault,
+            guardian: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::CancelEmergencyEscape {},
+        )
+    }
+
+    /// The recovery account `initialize` registered: the payer's asset account
+    pub fn recovery_account(&self) -> Pubkey {
+        get_associated_token_address(&self.payer(), &self.asset_mint)
+    }
+
+    /// `emergency_withdraw` of `amount` without the guardian, through the escape hatch, for
+    /// the timelock to sign
+    pub fn emergency_withdraw(&self, amount: u64) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::EmergencyWithdraw {
+            vault: self.vault,
+            timelock: self.timelock(),
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            recovery_account: self.recovery_account(),
+            guardian: None,
+            token_program: spl_token::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::EmergencyWithdraw { amount },
+        )
+    }
+
+    /// Strategy of `ADAPTER_PROGRAM`
+    pub fn strategy(&self) -> Pubkey {
+        pda(&[b"strategy", self.vault.as_ref(), ADAPTER_PROGRAM.as_ref()])
+    }
+
+    /// New asset account for a strategy to lend into, owned by a fresh key
+    pub async fn reserve(&mut self) -> Pubkey {
+        self.create_asset_account(&Pubkey::new_unique()).await
+    }
+
+    /// `add_strategy` of `ADAPTER_PROGRAM` over `reserve`, paid by the payer, for the timelock
+    /// to sign
+    pub fn add_strategy(
+        &self,
+        reserve: &Pubkey,
+        max_debt: u64,
+        target_weight_bps: u16,
+    ) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::AddStrategy {
+            vault: self.vault,
+            timelock: self.timelock(),
+            payer: self.payer(),
+            adapter_program: ADAPTER_PROGRAM,
+            reserve: *reserve,
+            strategy: self.strategy(),
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::AddStrategy {
+            max_debt,
+            target_weight_bps,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `update_strategy` of `ADAPTER_PROGRAM`, for the timelock to sign
+    pub fn update_strategy(
+        &self,
+        max_debt: u64,
+        target_weight_bps: u16,
+        enabled: bool,
+    ) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateStrategy {
+            vault: self.vault,
+            timelock: self.timelock(),
+            strategy: self.strategy(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::UpdateStrategy {
+            max_debt,
+            target_weight_bps,
+            enabled,
+        };
+        instruction(accounts, args)
+    }
+
+    /// `remove_strategy` of `ADAPTER_PROGRAM`, for the timelock to sign; the payer is the timelock
+    /// admin
+    pub fn remove_strategy(&self) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::RemoveStrategy {
+            vault: self.vault,
+            timelock: self.timelock(),
+            admin: self.payer(),
+            strategy: self.strategy(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::RemoveStrategy {})
+    }
+
+    /// `allocate` of `amount` to `ADAPTER_PROGRAM`'s strategy over `reserve` by the keeper
+    pub fn allocate(&self, reserve: &Pubkey, amount: u64) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::StrategyTransfer {
+            vault: self.vault,
+            keeper: self.payer(),
+            strategy: self.strategy(),
+            adapter_program: ADAPTER_PROGRAM,
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            reserve: *reserve,
+            token_program: spl_token::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::Allocate { amount })
+    }
+
+    /// `emergency_exit_strategy` of `ADAPTER_PROGRAM`'s strategy over `reserve`, signed by
+    /// `guardian`
+    pub fn emergency_exit_strategy(&self, guardian: &Pubkey, reserve: &Pubkey) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::EmergencyExitStrategy {
+            vault: self.vault,
+            guardian: *guardian,
+            strategy: self.strategy(),
+            adapter_program: ADAPTER_PROGRAM,
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            reserve: *reserve,
+            token_program: spl_token::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::EmergencyExitStrategy {},
+        )
+    }
+
+    /// The vault's timelock PDA
+    pub fn timelock(&self) -> Pubkey {
+        pda(&[b"timelock", self.vault.as_ref()])
+    }
+
+    /// Queued action `id` of the vault's timelock
+    pub fn action(&self, id: u64) -> Pubkey {
+        pda(&[b"action", self.timelock().as_ref(), &id.to_le_bytes()])
+    }
+
+    /// `init_timelock` by the authority, which becomes the timelock admin
+    pub fn init_timelock(&self, delay_seconds: u32) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::InitTimelock {
+            vault: self.vault,
+            authority: self.payer(),
+            timelock: self.timelock(),
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::InitTimelock { delay_seconds },
+        )
+    }
+
+    /// `set_timelock_delay`, which only the timelock itself may sign
+    pub fn set_timelock_delay(&self, delay_seconds: u32) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::UpdateTimelock {
+            timelock: self.timelock(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(
+            accounts,
+            tsv_usdc_vault::instruction::SetTimelockDelay { delay_seconds },
+        )
+    }
+
+    /// `queue_action` of `ix` as action `id` by the timelock admin
+    pub fn queue_action(&self, id: u64, ix: &Instruction) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::QueueAction {
+            timelock: self.timelock(),
+            admin: self.payer(),
+            action: self.action(id),
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::QueueAction {
+            action_accounts: ix
+                .accounts
+                .iter()
+                .map(|meta| ActionAccount {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: ix.data.clone(),
+        };
+        instruction(accounts, args)
+    }
+
+    /// `execute_action` of action `id`, queued from `ix`, by the timelock admin
+    pub fn execute_action(&self, id: u64, ix: &Instruction) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::ExecuteAction {
+            timelock: self.timelock(),
+            admin: self.payer(),
+            action: self.action(id),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let mut execute = instruction(accounts, tsv_usdc_vault::instruction::ExecuteAction {});
+        execute
+            .accounts
+            .extend(ix.accounts.iter().map(|meta| AccountMeta {
+                is_signer: false,
+                ..meta.clone()
+            }));
+        execute
+    }
+
+    /// Run `ix` through the timelock, created with the minimum delay on first use: queue it,
+    /// move the clock past the delay and execute it
+    pub async fn through_timelock(&mut self, ix: &Instruction) -> Result<(), TransactionError> {
+        let timelock = self.timelock();
+        if !self.exists(&timelock).await {
+            let init = self.init_timelock(MIN_TIMELOCK_DELAY_SECONDS);
+            self.send(&[init], &[]).await?;
+        }
+        let id = self
+            .anchor_account::<Timelock>(&timelock)
+            .await
+            .next_action_id;
+        self.send(&[self.queue_action(id, ix)], &[]).await?;
+        self.warp(MIN_TIMELOCK_DELAY_SECONDS as i64).await;
+        self.send(&[self.execute_action(id, ix)], &[]).await
+    }
+
+    /// `prune_expired_action` of action `id`, sent by the payer
+    pub fn prune_expired_action(&self, id: u64) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::PruneExpiredAction {
+            timelock: self.timelock(),
+            admin: self.payer(),
+            action: self.action(id),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::PruneExpiredAction {})
+    }
+
+    /// `sync` by the keeper under the default yield surplus policy
+    pub fn sync(&self) -> Instruction {
+        self.sync_fees(None)
+    }
+
+    /// `sync` by the keeper, sweeping the surplus to `fee_destination` under
+    /// `SurplusPolicy::Fees`
+    pub fn sync_fees(&self, fee_destination: Option<Pubkey>) -> Instruction {
+        let accounts = tsv_usdc_vault::accounts::SyncAssets {
+            vault: self.vault,
+            keeper: self.payer(),
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            fee_destination,
+            token_program: spl_token::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        instruction(accounts, tsv_usdc_vault::instruction::Sync {})
+    }
+
+    /// `queue_fee_update` by the fee manager
+    pub fn queue_fee_update(&self, deposit_fee_bps: u16, redeem_fee_bps: u16) -> Instruction {
+        let args = tsv_usdc_vault::instruction::QueueFeeUpdate {
+            new_deposit_fee_bps: deposit_fee_bps,
+            new_redeem_fee_bps: redeem_fee_bps,
+        };
+        instruction(self.update_fee_accounts(), args)
+    }
+
+    /// `execute_fee_update` by the fee manager
+    pub fn execute_fee_update(&self) -> Instruction {
+        instruction(
+            self.update_fee_accounts(),
+            tsv_usdc_vault::instruction::ExecuteFeeUpdate {},
+        )
+    }
+
+    /// Redeem message of `owner` at its current nonce, paying `receiver`
+    pub async fn redeem_message(
+        &mut self,
+        owner: &Pubkey,
+        shares: u64,
+        receiver: &Pubkey,
+        max_relayer_fee: u64,
+        deadline: i64,
+    ) -> MetaMessage {
+        let domain = self.vault_state().await.domain(self.vault);
+        MetaMessage {
+            domain,
+            owner: *owner,
+            nonce: self.nonce(owner).await,
+            deadline,
+            action: MetaAction::Redeem {
+                shares,
+                receiver: *receiver,
+                max_relayer_fee,
+            },
+        }
+    }
+
+    /// Ed25519 verification of `signature` over `message`, then `meta_redeem` relayed by
+    /// `relayer`, paying the receiver's associated asset account
+    pub fn meta_redeem(
+        &self,
+        relayer: &User,
+        message: &MetaMessage,
+        signature: &[u8; 64],
+        relayer_fee: u64,
+    ) -> Vec<Instruction> {
+        let MetaAction::Redeem {
+            shares,
+            receiver,
+            max_relayer_fee,
+        } = message.action
+        else {
+            panic!("not a redeem message");
+        };
+        let owner = message.owner;
+        let relayer_key = relayer.pubkey();
+        let accounts = tsv_usdc_vault::accounts::MetaRedeem {
+            vault: self.vault,
+            owner,
+            owner_shares: self.share_account(&owner),
+            receiver_asset: get_associated_token_address(&receiver, &self.asset_mint),
+            asset_vault: self.asset_vault,
+            asset_mint: self.asset_mint,
+            share_mint: self.share_mint,
+            owner_position: self.position(&owner),
+            fee_exemption: None,
+            allowlist_entry: None,
+            evm_owner: None,
+            relayer: relayer_key,
+            relayer_asset: relayer.asset,
+            relayer_entry: None,
+            owner_blocklist: self.blocklist_entry(&owner),
+            relayer_blocklist: self.blocklist_entry(&relayer_key),
+            receiver_blocklist: self.blocklist_entry(&receiver),
+            user_nonce: pda(&[b"nonce", owner.as_ref()]),
+            instructions: sysvar::instructions::ID,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        };
+        let args = tsv_usdc_vault::instruction::MetaRedeem {
+            shares,
+            receiver,
+            deadline: message.deadline,
+            max_relayer_fee,
+            relayer_fee,
+            signature: *signature,
+            adapter_accounts: Vec::new(),
+        };
+        vec![
+            ed25519_verify(&owner, signature, &message.signing_bytes()),
+            instruction(accounts, args),
+        ]
+    }
+
+    /// Let the vault PDA burn `shares` of `owner`, as meta-redeems require
+    pub fn approve_vault(&self, owner: &User, shares: u64) -> Instruction {
+        spl_token::instruction::approve(
+            &spl_token::ID,
+            &self.share_account(&owner.pubkey()),
+            &self.vault,
+            &owner.pubkey(),
+            &[],
+            shares,
+        )
+        .unwrap()
+    }
+
+    fn update_fee_accounts(&self) -> tsv_usdc_vault::accounts::UpdateFee {
+        tsv_usdc_vault::accounts::UpdateFee {
+            vault: self.vault,
+            fee_manager: self.payer(),
+            event_authority: event_authority(),
+            program: tsv_usdc_vault::ID,
+        }
+    }
+
+    fn position(&self, owner: &Pubkey) -> Pubkey {
+        pda(&[b"position", self.vault.as_ref(), owner.as_ref()])
+    }
+
+    fn blocklist_entry(&self, wallet: &Pubkey) -> Pubkey {
+        pda(&[b"blocklist", self.vault.as_ref(), wallet.as_ref()])
+    }
+
+    fn withdraw_request(&self, owner: &Pubkey, epoch: u64) -> Pubkey {
+        pda(&[
+            b"withdraw_request",
+            self.vault.as_ref(),
+            owner.as_ref(),
+            &epoch.to_le_bytes(),
+        ])
+    }
+
+    fn withdraw_epoch(&self, epoch: u64) -> Pubkey {
+        pda(&[b"withdraw_epoch", self.vault.as_ref(), &epoch.to_le_bytes()])
+    }
+
+    async fn create_mint(
+        &mut self,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        decimals: u8,
+    ) -> Vec<Instruction> {
+        let len = spl_token::state::Mint::LEN;
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        vec![
+            system_instruction::create_account(
+                &self.payer(),
+                mint,
+                rent.minimum_balance(len),
+                len as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                mint,
+                authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ]
+    }
+
+    /// Associated asset account of `owner`
+    async fn create_asset_account(&mut self, owner: &Pubkey) -> Pubkey {
+        let create = spl_associated_token_account::instruction::create_associated_token_account(
+            &self.payer(),
+            owner,
+            &self.asset_mint,
+            &spl_token::ID,
+        );
+        self.send(&[create], &[])
+            .await
+            .expect("create asset account");
+        get_associated_token_address(owner, &self.asset_mint)
+    }
+
+    async fn anchor_account<T: AccountDeserialize>(&mut self, address: &Pubkey) -> T {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("missing account {address}"));
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+}
+
+/// Signature of `signer` over `message`'s signing bytes
+pub fn sign(signer: &Keypair, message: &MetaMessage) -> [u8; 64] {
+    let signature = signer.sign_message(&message.signing_bytes());
+    signature.as_ref().try_into().unwrap()
+}
+
+/// Require `result` to be the failure of an instruction with `error`
+pub fn assert_vault_error(result: Result<(), TransactionError>, error: VaultError) {
+    let code = u32::from(error);
+    match result {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(actual))) => {
+            assert_eq!(actual, code, "expected error {code}, got {actual}")
+        }
+        other => panic!("expected error {code}, got {other:?}"),
+    }
+}
+
+/// Require `result` to be the failure of an instruction with Anchor's `error`, e.g. a violated
+/// account constraint
+pub fn assert_anchor_error(result: Result<(), TransactionError>, error: ErrorCode) {
+    let code = u32::from(error);
+    match result {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(actual))) => {
+            assert_eq!(actual, code, "expected error {code}, got {actual}")
+        }
+        other => panic!("expected error {code}, got {other:?}"),
+    }
+}
+
+/// Ed25519 program instruction verifying `signature` by `signer` over `message`, in the
+/// single-signature layout the vault accepts: offsets, public key, signature, then message
+fn ed25519_verify(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let public_key_offset = ED25519_HEADER_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset,
+        CURRENT_INSTRUCTION,
+        public_key_offset,
+        CURRENT_INSTRUCTION,
+        message_offset,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+fn instruction(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+fn share_metadata(share_mint: &Pubkey) -> Pubkey {
+    let program = anchor_spl::metadata::ID;
+    Pubkey::find_program_address(
+        &[b"metadata", program.as_ref(), share_mint.as_ref()],
+        &program,
+    )
+    .0
+}
+
+fn event_authority() -> Pubkey {
+    pda(&[b"__event_authority"])
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0
+}
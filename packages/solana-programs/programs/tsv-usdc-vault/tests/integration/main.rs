@@ -0,0 +1,12 @@
+//! End-to-end tests of the vault program in `solana-program-test`, one module per area, sharing
+//! the fixtures in `fixtures`.
+
+mod deposit_redeem;
+mod emergency;
+mod fees;
+mod fixtures;
+mod meta_redeem;
+mod migration;
+mod pause;
+mod strategies;
+mod timelock;
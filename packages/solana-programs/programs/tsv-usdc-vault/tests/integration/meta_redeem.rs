@@ -0,0 +1,331 @@
+use tsv_usdc_vault::{VaultError, EMERGENCY_ESCAPE_DELAY_SECONDS};
+
+use crate::fixtures::{assert_vault_error, sign, User, VaultFixture, ONE};
+
+/// Relayer fee the owner signs off on, and the one charged
+const MAX_RELAYER_FEE: u64 = ONE / 10;
+const RELAYER_FEE: u64 = ONE / 100;
+
+/// Vault where the owner deposited 100 tokens and approved the vault PDA to burn all its
+/// shares, the owner, its shares and a relayer without assets
+async fn setup() -> (VaultFixture, User, u64, User) {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let owner = fixture.user(100 * ONE).await;
+    let relayer = fixture.user(0).await;
+    fixture
+        .send(
+            &[fixture.deposit(&owner, 100 * ONE, None)],
+            &[&owner.keypair],
+        )
+        .await
+        .unwrap();
+    let owner_shares = fixture.share_account(&owner.pubkey());
+    let shares = fixture.balance(&owner_shares).await;
+    fixture
+        .send(&[fixture.approve_vault(&owner, shares)], &[&owner.keypair])
+        .await
+        .unwrap();
+    (fixture, owner, shares, relayer)
+}
+
+#[tokio::test]
+async fn relayed_redeem_pays_receiver_and_relayer() {
+    let (mut fixture, owner, shares, relayer) = setup().await;
+    let deadline = fixture.now().await + 600;
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+
+    let redeemed = fixture.preview_redeem(&owner.pubkey(), shares).await;
+    fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await
+        .unwrap();
+
+    let owner_shares = fixture.share_account(&owner.pubkey());
+    assert_eq!(fixture.balance(&owner_shares).await, 0);
+    assert_eq!(fixture.balance(&owner.asset).await, redeemed - RELAYER_FEE);
+    assert_eq!(fixture.balance(&relayer.asset).await, RELAYER_FEE);
+    assert_eq!(fixture.nonce(&owner.pubkey()).await, 1);
+}
+
+#[tokio::test]
+async fn replayed_signature_is_rejected() {
+    let (mut fixture, owner, shares, relayer) = setup().await;
+    let deadline = fixture.now().await + 600;
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares / 2,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    let instructions = fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE);
+
+    fixture
+        .send(&instructions, &[&relayer.keypair])
+        .await
+        .unwrap();
+    // The nonce moved on, so the same signature no longer covers the vault's message
+    let result = fixture.send(&instructions, &[&relayer.keypair]).await;
+    assert_vault_error(result, VaultError::InvalidSignature);
+}
+
+#[tokio::test]
+async fn signature_must_cover_the_owner_and_arguments() {
+    let (mut fixture, owner, shares, relayer) = setup().await;
+    let deadline = fixture.now().await + 600;
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares / 2,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+
+    // Valid Ed25519 signature, but by the relayer rather than the owner
+    let forged = sign(&relayer.keypair, &message);
+    let mut instructions = fixture.meta_redeem(&relayer, &message, &forged, RELAYER_FEE);
+    instructions[0].data[16..48].copy_from_slice(relayer.pubkey().as_ref());
+    let result = fixture.send(&instructions, &[&relayer.keypair]).await;
+    assert_vault_error(result, VaultError::InvalidSignature);
+
+    // The owner's signature over half the shares, relayed as a redeem of all of them
+    let signature = sign(&owner.keypair, &message);
+    let mut tampered = message.clone();
+    tampered.action = tsv_meta_message::MetaAction::Redeem {
+        shares,
+        receiver: owner.pubkey(),
+        max_relayer_fee: MAX_RELAYER_FEE,
+    };
+    let mut instructions = fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE);
+    instructions[1] = fixture
+        .meta_redeem(&relayer, &tampered, &signature, RELAYER_FEE)
+        .remove(1);
+    let result = fixture.send(&instructions, &[&relayer.keypair]).await;
+    assert_vault_error(result, VaultError::InvalidSignature);
+
+    assert_eq!(fixture.nonce(&owner.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn expired_or_paused_meta_redeems_are_rejected() {
+    let (mut fixture, owner, shares, relayer) = setup().await;
+    let now = fixture.now().await;
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            now - 1,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    let result = fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::DeadlineExpired);
+
+    let pauser = fixture.payer();
+    fixture
+        .send(&[fixture.set_pause_flags(&pauser, false, false, true)], &[])
+        .await
+        .unwrap();
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            now + 600,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    let result = fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::MetaPaused);
+}
+
+#[tokio::test]
+async fn meta_redeem_is_paid_from_idle_assets_only() {
+    // A 1% deposit fee leaves 1 token of accrued fees in the asset vault
+    let mut fixture = VaultFixture::new(100, 0).await;
+    let owner = fixture.user(100 * ONE).await;
+    let relayer = fixture.user(0).await;
+    fixture
+        .send(
+            &[fixture.deposit(&owner, 100 * ONE, None)],
+            &[&owner.keypair],
+        )
+        .await
+        .unwrap();
+    let owner_shares = fixture.share_account(&owner.pubkey());
+    let shares = fixture.balance(&owner_shares).await;
+    fixture
+        .send(&[fixture.approve_vault(&owner, shares)], &[&owner.keypair])
+        .await
+        .unwrap();
+
+    // 10 tokens leave the asset vault but stay in `total_assets`, as if deployed
+    fixture
+        .send(&[fixture.request_emergency_escape()], &[])
+        .await
+        .unwrap();
+    fixture.warp(EMERGENCY_ESCAPE_DELAY_SECONDS).await;
+    fixture
+        .through_timelock(&fixture.emergency_withdraw(10 * ONE))
+        .await
+        .unwrap();
+
+    // Redeeming everything would dip into the accrued fees
+    let deadline = fixture.now().await + 600;
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    let result = fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::InsufficientIdleAssets);
+
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares / 2,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await
+        .unwrap();
+    let asset_vault = fixture.asset_vault;
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.accrued_fees, ONE);
+    assert!(fixture.balance(&asset_vault).await >= vault.accrued_fees);
+}
+
+#[tokio::test]
+async fn meta_redeem_leaves_settled_withdrawals_claimable() {
+    let (mut fixture, owner, shares, relayer) = setup().await;
+    let withdrawer = fixture.user(100 * ONE).await;
+    fixture
+        .send(
+            &[fixture.deposit(&withdrawer, 100 * ONE, None)],
+            &[&withdrawer.keypair],
+        )
+        .await
+        .unwrap();
+    let withdrawer_shares = fixture.share_account(&withdrawer.pubkey());
+    let queued = fixture.balance(&withdrawer_shares).await;
+    fixture
+        .send(
+            &[fixture.request_withdraw(&withdrawer, queued, 0)],
+            &[&withdrawer.keypair],
+        )
+        .await
+        .unwrap();
+    fixture
+        .send(&[fixture.process_epoch(0)], &[])
+        .await
+        .unwrap();
+    let reserved = fixture.vault_state().await.reserved_withdraw_assets;
+    assert!(reserved > 0);
+
+    // Half the owner's USDC leaves the asset vault but stays in `total_assets`, as if deployed
+    fixture
+        .send(&[fixture.request_emergency_escape()], &[])
+        .await
+        .unwrap();
+    fixture.warp(EMERGENCY_ESCAPE_DELAY_SECONDS).await;
+    fixture
+        .through_timelock(&fixture.emergency_withdraw(50 * ONE))
+        .await
+        .unwrap();
+
+    // Redeeming everything would spend the USDC set aside for the settled request
+    let deadline = fixture.now().await + 600;
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    let result = fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::InsufficientIdleAssets);
+
+    let message = fixture
+        .redeem_message(
+            &owner.pubkey(),
+            shares / 4,
+            &owner.pubkey(),
+            MAX_RELAYER_FEE,
+            deadline,
+        )
+        .await;
+    let signature = sign(&owner.keypair, &message);
+    fixture
+        .send(
+            &fixture.meta_redeem(&relayer, &message, &signature, RELAYER_FEE),
+            &[&relayer.keypair],
+        )
+        .await
+        .unwrap();
+
+    fixture
+        .send(
+            &[fixture.claim_withdraw(&withdrawer, 0)],
+            &[&withdrawer.keypair],
+        )
+        .await
+        .unwrap();
+    assert_eq!(fixture.balance(&withdrawer.asset).await, reserved);
+    assert_eq!(fixture.vault_state().await.reserved_withdraw_assets, 0);
+}
@@ -0,0 +1,122 @@
+use solana_sdk::pubkey::Pubkey;
+use tsv_usdc_vault::migration::CURRENT_VAULT_VERSION;
+use tsv_usdc_vault::{VaultError, DEFAULT_FEE_TIMELOCK_SECONDS};
+
+use crate::fixtures::{assert_vault_error, VaultFixture, DEAD_SHARES, ONE};
+
+#[tokio::test]
+async fn migrates_a_baseline_vault() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    fixture.baseline_vault(25).await;
+    let (_, bump) = Pubkey::find_program_address(&[b"vault"], &tsv_usdc_vault::ID);
+
+    let migrate = fixture.migrate_state();
+    fixture.send(&[migrate], &[]).await.unwrap();
+
+    let authority = fixture.payer();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.version, CURRENT_VAULT_VERSION);
+    assert_eq!(vault.bump, bump);
+    assert!(vault.legacy_address);
+    assert_eq!((vault.deposit_fee_bps, vault.redeem_fee_bps), (25, 25));
+    assert_eq!(vault.share_mint, fixture.share_mint);
+    assert_eq!(vault.asset_vault, fixture.asset_vault);
+    for role in [
+        vault.pauser,
+        vault.fee_manager,
+        vault.guardian,
+        vault.keeper,
+        vault.compliance,
+    ] {
+        assert_eq!(role, authority);
+    }
+    assert_eq!(vault.deposit_cap, u64::MAX);
+    assert_eq!(vault.fee_timelock_seconds, DEFAULT_FEE_TIMELOCK_SECONDS);
+    assert_eq!(vault.genesis_hash, fixture.genesis_hash);
+
+    let migrate = fixture.migrate_state();
+    let result = fixture.send(&[migrate], &[]).await;
+    assert_vault_error(result, VaultError::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn baseline_vault_takes_deposits_after_init_legacy_accounts() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    fixture.baseline_vault(25).await;
+    let migrate = fixture.migrate_state();
+    fixture.send(&[migrate], &[]).await.unwrap();
+
+    // No dead shares account to lock the first deposit's shares in yet
+    let user = fixture.user(100 * ONE).await;
+    let deposit = fixture.deposit(&user, 10 * ONE, None);
+    assert!(fixture.send(&[deposit], &[&user.keypair]).await.is_err());
+
+    let recovery = fixture.user(0).await;
+    let init = fixture.init_legacy_accounts(&recovery.asset);
+    fixture.send(&[init], &[]).await.unwrap();
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.dead_shares_account, fixture.dead_shares_account);
+    assert_eq!(vault.recovery_account, recovery.asset);
+
+    let deposit = fixture.deposit(&user, 10 * ONE, None);
+    fixture.send(&[deposit], &[&user.keypair]).await.unwrap();
+    let dead_shares_account = fixture.dead_shares_account;
+    assert_eq!(fixture.balance(&dead_shares_account).await, DEAD_SHARES);
+    assert_eq!(fixture.vault_state().await.total_assets, 10 * ONE);
+}
+
+#[tokio::test]
+async fn migrated_position_keeps_the_source_lockup_and_deposit_time() {
+    const DAY: i64 = 24 * 60 * 60;
+    // A baseline vault migrating to the `[b"vault", asset_mint]` vault, which has no lockup
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let target = fixture.addresses();
+    fixture.baseline_vault(0).await;
+    let migrate = fixture.migrate_state();
+    fixture.send(&[migrate], &[]).await.unwrap();
+    let recovery = fixture.user(0).await;
+    let init = fixture.init_legacy_accounts(&recovery.asset);
+    fixture.send(&[init], &[]).await.unwrap();
+    fixture
+        .send(&[fixture.set_lockup_seconds(DAY as u32)], &[])
+        .await
+        .unwrap();
+    fixture
+        .send(&[fixture.set_migration_target(&target.vault)], &[])
+        .await
+        .unwrap();
+
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+    let source_position = fixture.position_state(&user.pubkey()).await;
+    fixture.warp(DAY / 2).await;
+
+    let user_shares = fixture.share_account(&user.pubkey());
+    let shares = fixture.balance(&user_shares).await;
+    fixture
+        .send(
+            &[fixture.migrate_position(&user, shares, &target)],
+            &[&user.keypair],
+        )
+        .await
+        .unwrap();
+
+    fixture.point_at(&target);
+    let position = fixture.position_state(&user.pubkey()).await;
+    assert_eq!(position.locked_until, source_position.locked_until);
+    assert_eq!(position.last_deposit_ts, source_position.last_deposit_ts);
+
+    // The source lockup still runs in the target vault
+    let user_shares = fixture.share_account(&user.pubkey());
+    let shares = fixture.balance(&user_shares).await;
+    let redeem = fixture.redeem(&user, shares, None);
+    let result = fixture.send(&[redeem.clone()], &[&user.keypair]).await;
+    assert_vault_error(result, VaultError::StillLocked);
+
+    fixture.warp(DAY / 2).await;
+    fixture.send(&[redeem], &[&user.keypair]).await.unwrap();
+    assert_eq!(fixture.balance(&user_shares).await, 0);
+}
@@ -0,0 +1,80 @@
+use solana_sdk::signature::Signer;
+use tsv_usdc_vault::VaultError;
+
+use crate::fixtures::{assert_vault_error, VaultFixture, ONE};
+
+#[tokio::test]
+async fn paused_deposits_leave_redeems_open() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(200 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    let pauser = fixture.payer();
+    fixture
+        .send(&[fixture.set_pause_flags(&pauser, true, false, false)], &[])
+        .await
+        .unwrap();
+    let result = fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await;
+    assert_vault_error(result, VaultError::DepositsPaused);
+    fixture
+        .send(&[fixture.redeem(&user, 1_000, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    fixture
+        .send(
+            &[fixture.set_pause_flags(&pauser, false, false, false)],
+            &[],
+        )
+        .await
+        .unwrap();
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn paused_redeems_leave_deposits_open() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(200 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+
+    let pauser = fixture.payer();
+    fixture
+        .send(&[fixture.set_pause_flags(&pauser, false, true, false)], &[])
+        .await
+        .unwrap();
+    let result = fixture
+        .send(&[fixture.redeem(&user, 1_000, None)], &[&user.keypair])
+        .await;
+    assert_vault_error(result, VaultError::RedeemsPaused);
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn only_the_pauser_sets_pause_flags() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(0).await;
+
+    let result = fixture
+        .send(
+            &[fixture.set_pause_flags(&user.pubkey(), true, true, true)],
+            &[&user.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    let vault = fixture.vault_state().await;
+    assert!(!vault.paused_deposits && !vault.paused_redeems && !vault.paused_meta);
+}
@@ -0,0 +1,97 @@
+use solana_sdk::signature::Signer;
+use tsv_usdc_vault::{VaultError, MIN_TIMELOCK_DELAY_SECONDS};
+
+use crate::fixtures::{assert_vault_error, VaultFixture, ONE};
+
+#[tokio::test]
+async fn strategies_only_change_through_the_timelock() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+    let reserve = fixture.reserve().await;
+
+    fixture
+        .send(&[fixture.init_timelock(MIN_TIMELOCK_DELAY_SECONDS)], &[])
+        .await
+        .unwrap();
+    let add = fixture.add_strategy(&reserve, 1_000 * ONE, 6_000);
+    let result = fixture.send(&[add.clone()], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    fixture.through_timelock(&add).await.unwrap();
+
+    let update = fixture.update_strategy(500 * ONE, 4_000, false);
+    let result = fixture.send(&[update.clone()], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    fixture.through_timelock(&update).await.unwrap();
+    let strategy = fixture.strategy_state().await;
+    assert_eq!(strategy.max_debt, 500 * ONE);
+    assert_eq!(strategy.target_weight_bps, 4_000);
+    assert!(!strategy.enabled);
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.strategy_count, 1);
+    assert_eq!(vault.total_target_weight_bps, 4_000);
+
+    let result = fixture
+        .through_timelock(&fixture.update_strategy(500 * ONE, 10_001, false))
+        .await;
+    assert_vault_error(result, VaultError::TargetWeightsExceeded);
+
+    let result = fixture.send(&[fixture.allocate(&reserve, ONE)], &[]).await;
+    assert_vault_error(result, VaultError::StrategyDisabled);
+
+    let remove = fixture.remove_strategy();
+    let result = fixture.send(&[remove.clone()], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    fixture.through_timelock(&remove).await.unwrap();
+    let strategy = fixture.strategy();
+    assert!(!fixture.exists(&strategy).await);
+    let vault = fixture.vault_state().await;
+    assert_eq!(vault.strategy_count, 0);
+    assert_eq!(vault.total_target_weight_bps, 0);
+}
+
+#[tokio::test]
+async fn only_the_guardian_exits_a_strategy() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let reserve = fixture.reserve().await;
+    fixture
+        .through_timelock(&fixture.add_strategy(&reserve, 1_000 * ONE, 0))
+        .await
+        .unwrap();
+
+    let intruder = fixture.user(0).await;
+    let result = fixture
+        .send(
+            &[fixture.emergency_exit_strategy(&intruder.pubkey(), &reserve)],
+            &[&intruder.keypair],
+        )
+        .await;
+    assert_vault_error(result, VaultError::Unauthorized);
+}
+
+#[tokio::test]
+async fn liquidity_buffer_stays_out_of_allocations() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let user = fixture.user(100 * ONE).await;
+    fixture
+        .send(&[fixture.deposit(&user, 100 * ONE, None)], &[&user.keypair])
+        .await
+        .unwrap();
+    let reserve = fixture.reserve().await;
+    fixture
+        .through_timelock(&fixture.add_strategy(&reserve, 1_000 * ONE, 0))
+        .await
+        .unwrap();
+
+    // The whole vault is buffer, so nothing can be allocated; the failure comes before the
+    // adapter call
+    fixture
+        .send(&[fixture.set_buffer_bps(10_000)], &[])
+        .await
+        .unwrap();
+    let result = fixture.send(&[fixture.allocate(&reserve, ONE)], &[]).await;
+    assert_vault_error(result, VaultError::InsufficientIdleAssets);
+}
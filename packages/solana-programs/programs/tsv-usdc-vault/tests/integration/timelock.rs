@@ -0,0 +1,82 @@
+use tsv_usdc_vault::{VaultError, ACTION_GRACE_PERIOD_SECONDS, MIN_TIMELOCK_DELAY_SECONDS};
+
+use crate::fixtures::{assert_vault_error, VaultFixture};
+
+#[tokio::test]
+async fn expired_actions_cannot_run_and_are_pruned() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    fixture
+        .send(&[fixture.init_timelock(MIN_TIMELOCK_DELAY_SECONDS)], &[])
+        .await
+        .unwrap();
+    let set_delay = fixture.set_timelock_delay(2 * MIN_TIMELOCK_DELAY_SECONDS);
+    fixture
+        .send(&[fixture.queue_action(0, &set_delay)], &[])
+        .await
+        .unwrap();
+
+    // Still executable once the delay elapses, so not prunable
+    let result = fixture.send(&[fixture.prune_expired_action(0)], &[]).await;
+    assert_vault_error(result, VaultError::ActionNotExpired);
+
+    fixture
+        .warp(MIN_TIMELOCK_DELAY_SECONDS as i64 + ACTION_GRACE_PERIOD_SECONDS)
+        .await;
+    let result = fixture
+        .send(&[fixture.execute_action(0, &set_delay)], &[])
+        .await;
+    assert_vault_error(result, VaultError::ActionExpired);
+
+    let action = fixture.action(0);
+    fixture
+        .send(&[fixture.prune_expired_action(0)], &[])
+        .await
+        .unwrap();
+    assert!(!fixture.exists(&action).await);
+}
+
+#[tokio::test]
+async fn timelock_delay_has_a_minimum() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    let result = fixture
+        .send(
+            &[fixture.init_timelock(MIN_TIMELOCK_DELAY_SECONDS - 1)],
+            &[],
+        )
+        .await;
+    assert_vault_error(result, VaultError::TimelockDelayTooShort);
+
+    let set_delay = fixture.set_timelock_delay(MIN_TIMELOCK_DELAY_SECONDS - 1);
+    let result = fixture.through_timelock(&set_delay).await;
+    assert_vault_error(result, VaultError::TimelockDelayTooShort);
+}
+
+#[tokio::test]
+async fn fee_changes_only_run_through_the_timelock() {
+    let mut fixture = VaultFixture::new(0, 0).await;
+    fixture
+        .send(&[fixture.init_timelock(MIN_TIMELOCK_DELAY_SECONDS)], &[])
+        .await
+        .unwrap();
+
+    let set_fee = fixture.set_management_fee(200);
+    let result = fixture.send(&[set_fee.clone()], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    fixture
+        .send(&[fixture.queue_action(0, &set_fee)], &[])
+        .await
+        .unwrap();
+    fixture.warp(MIN_TIMELOCK_DELAY_SECONDS as i64 - 1).await;
+    let result = fixture
+        .send(&[fixture.execute_action(0, &set_fee)], &[])
+        .await;
+    assert_vault_error(result, VaultError::TimelockNotExpired);
+
+    fixture.warp(1).await;
+    fixture
+        .send(&[fixture.execute_action(0, &set_fee)], &[])
+        .await
+        .unwrap();
+    assert_eq!(fixture.vault_state().await.management_fee_bps, 200);
+}
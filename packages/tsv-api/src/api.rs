@@ -0,0 +1,388 @@
+//! HTTP API over the indexer database. Amounts and shares are decimal strings of base units,
+//! since they can exceed the integers JavaScript represents exactly.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use axum::extract::{Path, Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tsv_usdc_vault::EXCHANGE_RATE_SCALE;
+
+use crate::db::{Db, FlowTotals, HistoryKey, PricePoint, Transfer};
+use crate::error::ApiError;
+use crate::returns;
+
+/// Rows per page when the request doesn't say
+const DEFAULT_LIMIT: i64 = 100;
+/// Most rows per page
+const MAX_LIMIT: i64 = 500;
+/// Longest APY window, in days
+const MAX_DAYS: i64 = 366;
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Arc<Db>,
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/v1/vaults", get(vaults))
+        .route("/v1/vaults/:vault/stats", get(stats))
+        .route("/v1/vaults/:vault/apy", get(apy))
+        .route("/v1/vaults/:vault/apy/daily", get(daily_apy))
+        .route("/v1/vaults/:vault/share-prices", get(share_prices))
+        .route("/v1/vaults/:vault/users/:user/position", get(position))
+        .route(
+            "/v1/vaults/:vault/users/:user/transactions",
+            get(transactions),
+        )
+        .with_state(state)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SharePrice {
+    sequence: i64,
+    block_time: Option<i64>,
+    total_assets: String,
+    total_shares: String,
+    /// Assets per `EXCHANGE_RATE_SCALE` shares
+    exchange_rate: String,
+    /// Assets per share, for display
+    share_price: f64,
+}
+
+impl From<&PricePoint> for SharePrice {
+    fn from(point: &PricePoint) -> Self {
+        Self {
+            sequence: point.sequence,
+            block_time: point.block_time,
+            total_assets: point.total_assets.to_string(),
+            total_shares: point.total_shares.to_string(),
+            exchange_rate: point.exchange_rate.to_string(),
+            share_price: point.exchange_rate as f64 / EXCHANGE_RATE_SCALE as f64,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Totals {
+    deposits: i64,
+    deposited_assets: String,
+    redemptions: i64,
+    redeemed_assets: String,
+    fees_collected: String,
+    fee_shares: String,
+}
+
+impl From<FlowTotals> for Totals {
+    fn from(totals: FlowTotals) -> Self {
+        Self {
+            deposits: totals.deposits,
+            deposited_assets: totals.deposited_assets.to_string(),
+            redemptions: totals.redemptions,
+            redeemed_assets: totals.redeemed_assets.to_string(),
+            fees_collected: totals.fees_collected.to_string(),
+            fee_shares: totals.fee_shares.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Transaction {
+    signature: String,
+    event_index: i32,
+    slot: i64,
+    block_time: Option<i64>,
+    /// `deposit` or `redemption`
+    direction: String,
+    kind: String,
+    owner: String,
+    receiver: String,
+    assets: String,
+    shares: String,
+    fee: String,
+}
+
+impl From<Transfer> for Transaction {
+    fn from(transfer: Transfer) -> Self {
+        Self {
+            signature: transfer.key.signature,
+            event_index: transfer.key.event_index,
+            slot: transfer.key.slot,
+            block_time: transfer.block_time,
+            direction: transfer.direction,
+            kind: transfer.kind,
+            owner: transfer.owner,
+            receiver: transfer.receiver,
+            assets: transfer.assets.to_string(),
+            shares: transfer.shares.to_string(),
+            fee: transfer.fee.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WindowQuery {
+    days: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    limit: Option<i64>,
+    /// `next` of the previous page
+    before: Option<String>,
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn vaults(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    Ok(Json(json!({ "vaults": state.db.vaults().await? })))
+}
+
+async fn stats(
+    State(state): State<AppState>,
+    Path(vault): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let vault = indexed_vault(&state, &vault).await?;
+    let now = unix_now();
+    let latest = state.db.latest_price(&vault).await?;
+    let mut apy = serde_json::Map::new();
+    for days in [7, 30] {
+        let apy_bps = match &latest {
+            Some(latest) => trailing_apy(&state, &vault, latest, now, days).await?,
+            None => None,
+        };
+        apy.insert(format!("{days}d"), json!(apy_bps));
+    }
+
+    Ok(Json(json!({
+        "vault": vault,
+        "latest": latest.as_ref().map(SharePrice::from),
+        "apyBps": apy,
+        "depositors": state.db.depositors(&vault).await?,
+        "allTime": Totals::from(state.db.totals(&vault, None).await?),
+        "last24h": Totals::from(state.db.totals(&vault, Some(now - DAY)).await?),
+    })))
+}
+
+async fn apy(
+    State(state): State<AppState>,
+    Path(vault): Path<String>,
+    Query(query): Query<WindowQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let vault = indexed_vault(&state, &vault).await?;
+    let days = days(query.days, 7)?;
+    let Some(latest) = state.db.latest_price(&vault).await? else {
+        return Ok(Json(
+            json!({ "days": days, "from": null, "to": null, "apyBps": null }),
+        ));
+    };
+    let start = state.db.price_at(&vault, unix_now() - days * DAY).await?;
+
+    Ok(Json(json!({
+        "days": days,
+        "from": start.as_ref().map(SharePrice::from),
+        "to": SharePrice::from(&latest),
+        "apyBps": start.and_then(|start| returns::apy_bps(&start, &latest)),
+    })))
+}
+
+async fn daily_apy(
+    State(state): State<AppState>,
+    Path(vault): Path<String>,
+    Query(query): Query<WindowQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let vault = indexed_vault(&state, &vault).await?;
+    let days = days(query.days, 30)?;
+    // One more day, for the first day's return
+    let closes = state
+        .db
+        .daily_prices(&vault, unix_now() - (days + 1) * DAY)
+        .await?;
+    let returns: Vec<Value> = returns::daily_returns(&closes)
+        .into_iter()
+        .skip(closes.len().saturating_sub(days as usize))
+        .map(|daily| {
+            json!({
+                "day": daily.day,
+                "exchangeRate": daily.exchange_rate.to_string(),
+                "apyBps": daily.apy_bps,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "days": returns })))
+}
+
+async fn share_prices(
+    State(state): State<AppState>,
+    Path(vault): Path<String>,
+    Query(query): Query<RangeQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let vault = indexed_vault(&state, &vault).await?;
+    let prices = state
+        .db
+        .prices(&vault, query.from, query.to, limit(query.limit)?)
+        .await?;
+    let prices: Vec<SharePrice> = prices.iter().map(SharePrice::from).collect();
+
+    Ok(Json(json!({ "sharePrices": prices })))
+}
+
+async fn position(
+    State(state): State<AppState>,
+    Path((vault, user)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    let vault = indexed_vault(&state, &vault).await?;
+    let user = pubkey(&user, "user")?;
+    let flows = state.db.flows(&vault, &user).await?;
+    let exchange_rate = state
+        .db
+        .latest_price(&vault)
+        .await?
+        .map_or(0, |latest| latest.exchange_rate);
+    let position = returns::position(&flows, exchange_rate);
+
+    Ok(Json(json!({
+        "vault": vault,
+        "user": user,
+        "shares": position.shares.to_string(),
+        "value": position.value.to_string(),
+        "exchangeRate": exchange_rate.to_string(),
+        "depositedAssets": position.deposited_assets.to_string(),
+        "redeemedAssets": position.redeemed_assets.to_string(),
+        "earnings": position.earnings.to_string(),
+    })))
+}
+
+async fn transactions(
+    State(state): State<AppState>,
+    Path((vault, user)): Path<(String, String)>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let vault = indexed_vault(&state, &vault).await?;
+    let user = pubkey(&user, "user")?;
+    let limit = limit(query.limit)?;
+    let before = query.before.as_deref().map(parse_cursor).transpose()?;
+    let transfers = state
+        .db
+        .transfers(&vault, &user, before.as_ref(), limit)
+        .await?;
+    // A full page may have more after it
+    let next = (transfers.len() as i64 == limit)
+        .then(|| transfers.last().map(|last| cursor(&last.key)))
+        .flatten();
+    let transactions: Vec<Transaction> = transfers.into_iter().map(Transaction::from).collect();
+
+    Ok(Json(json!({ "transactions": transactions, "next": next })))
+}
+
+/// APY from the share price `days` before `now` to `latest`
+async fn trailing_apy(
+    state: &AppState,
+    vault: &str,
+    latest: &PricePoint,
+    now: i64,
+    days: i64,
+) -> Result<Option<i64>, ApiError> {
+    let start = state.db.price_at(vault, now - days * DAY).await?;
+    Ok(start.and_then(|start| returns::apy_bps(&start, latest)))
+}
+
+/// `vault` as base58, or not found when the indexer doesn't index it
+async fn indexed_vault(state: &AppState, vault: &str) -> Result<String, ApiError> {
+    let vault = pubkey(vault, "vault")?;
+    if !state.db.is_indexed(&vault).await? {
+        return Err(ApiError::NotFound(format!("vault {vault}")));
+    }
+    Ok(vault)
+}
+
+/// `value` normalized to base58, rejecting anything that isn't an address
+fn pubkey(value: &str, name: &str) -> Result<String, ApiError> {
+    Pubkey::from_str(value)
+        .map(|key| key.to_string())
+        .map_err(|_| ApiError::InvalidRequest(format!("invalid {name} {value}")))
+}
+
+fn days(days: Option<i64>, default: i64) -> Result<i64, ApiError> {
+    match days.unwrap_or(default) {
+        days @ 1..=MAX_DAYS => Ok(days),
+        _ => Err(ApiError::InvalidRequest(format!(
+            "days must be from 1 to {MAX_DAYS}"
+        ))),
+    }
+}
+
+fn limit(limit: Option<i64>) -> Result<i64, ApiError> {
+    match limit.unwrap_or(DEFAULT_LIMIT) {
+        limit @ 1..=MAX_LIMIT => Ok(limit),
+        _ => Err(ApiError::InvalidRequest(format!(
+            "limit must be from 1 to {MAX_LIMIT}"
+        ))),
+    }
+}
+
+/// Opaque page cursor: `slot.event_index.signature`
+fn cursor(key: &HistoryKey) -> String {
+    format!("{}.{}.{}", key.slot, key.event_index, key.signature)
+}
+
+fn parse_cursor(value: &str) -> Result<HistoryKey, ApiError> {
+    let mut parts = value.splitn(3, '.');
+    let slot = parts.next().and_then(|part| part.parse().ok());
+    let event_index = parts.next().and_then(|part| part.parse().ok());
+    match (slot, event_index, parts.next()) {
+        (Some(slot), Some(event_index), Some(signature)) if !signature.is_empty() => {
+            Ok(HistoryKey {
+                slot,
+                signature: signature.to_string(),
+                event_index,
+            })
+        }
+        _ => Err(ApiError::InvalidRequest(format!("invalid cursor {value}"))),
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let key = HistoryKey {
+            slot: 287_000_123,
+            signature: "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb".to_string(),
+            event_index: 2,
+        };
+
+        assert_eq!(parse_cursor(&cursor(&key)).unwrap(), key);
+        assert!(parse_cursor("287000123.2").is_err());
+        assert!(parse_cursor("slot.2.sig").is_err());
+    }
+}
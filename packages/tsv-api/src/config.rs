@@ -0,0 +1,40 @@
+//! API settings, read from the environment.
+
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use crate::error::ApiError;
+
+pub struct Config {
+    /// HTTP API listen address (`BIND_ADDR`, default 0.0.0.0:8080)
+    pub bind_addr: SocketAddr,
+    /// PostgreSQL connection string of the indexer's database (`DATABASE_URL`)
+    pub database_url: String,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ApiError> {
+        Ok(Self {
+            bind_addr: parse_or("BIND_ADDR", SocketAddr::from(([0, 0, 0, 0], 8080)))?,
+            database_url: required("DATABASE_URL")?,
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, ApiError> {
+    env::var(name).map_err(|_| ApiError::Config(format!("{name} is not set")))
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, ApiError> {
+    value
+        .parse()
+        .map_err(|_| ApiError::Config(format!("invalid {name}: {value}")))
+}
+
+fn parse_or<T: FromStr>(name: &str, default: T) -> Result<T, ApiError> {
+    match env::var(name) {
+        Ok(value) => parse(&value, name),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,335 @@
+//! Queries over the tables tsv-indexer writes (`packages/tsv-indexer/schema.sql`). The API only
+//! reads them.
+
+use tokio_postgres::{Client, Row};
+
+use crate::error::ApiError;
+
+/// Columns of a `share_prices` row, `block_time` as Unix seconds
+const PRICE_COLUMNS: &str = "sequence, EXTRACT(EPOCH FROM block_time)::BIGINT, total_assets, \
+     total_shares, exchange_rate";
+
+/// Columns of a `deposits` or `redemptions` row listed as a transaction
+const FLOW_COLUMNS: &str = "signature, event_index, slot, EXTRACT(EPOCH FROM block_time)::BIGINT, \
+     kind, owner, receiver, assets, shares, fee";
+
+/// Vault state after an event carrying a `VaultSnapshot`
+#[derive(Clone, Debug)]
+pub struct PricePoint {
+    pub sequence: i64,
+    /// Unix time of the block, when the indexer had it
+    pub block_time: Option<i64>,
+    pub total_assets: i64,
+    pub total_shares: i64,
+    /// Assets per `EXCHANGE_RATE_SCALE` shares
+    pub exchange_rate: i64,
+}
+
+impl From<&Row> for PricePoint {
+    fn from(row: &Row) -> Self {
+        Self {
+            sequence: row.get(0),
+            block_time: row.get(1),
+            total_assets: row.get(2),
+            total_shares: row.get(3),
+            exchange_rate: row.get(4),
+        }
+    }
+}
+
+/// Deposits, redemptions and fees of a vault over some period
+#[derive(Debug, Default)]
+pub struct FlowTotals {
+    pub deposits: i64,
+    pub deposited_assets: i64,
+    pub redemptions: i64,
+    pub redeemed_assets: i64,
+    /// USDC sent to the fee recipient
+    pub fees_collected: i64,
+    /// Shares minted to the treasury as management and performance fees
+    pub fee_shares: i64,
+}
+
+/// What one user put into and took out of a vault
+#[derive(Debug, Default)]
+pub struct Flows {
+    /// Shares minted to the user as receiver
+    pub shares_in: i64,
+    /// Shares burned from the user as owner
+    pub shares_out: i64,
+    pub deposited_assets: i64,
+    pub redeemed_assets: i64,
+}
+
+/// Position of a transaction in the history: its slot, signature and event index. History is
+/// listed newest first, and a page continues after its last entry's key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryKey {
+    pub slot: i64,
+    pub signature: String,
+    pub event_index: i32,
+}
+
+/// A deposit or redemption involving a user
+#[derive(Debug)]
+pub struct Transfer {
+    /// `deposit` or `redemption`
+    pub direction: String,
+    pub key: HistoryKey,
+    pub block_time: Option<i64>,
+    pub kind: String,
+    pub owner: String,
+    pub receiver: String,
+    pub assets: i64,
+    pub shares: i64,
+    pub fee: i64,
+}
+
+pub struct Db {
+    client: Client,
+}
+
+impl Db {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Vaults the indexer has indexed, base58
+    pub async fn vaults(&self) -> Result<Vec<String>, ApiError> {
+        let rows = self
+            .client
+            .query("SELECT vault FROM indexer_cursors ORDER BY vault", &[])
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    pub async fn is_indexed(&self, vault: &str) -> Result<bool, ApiError> {
+        let row = self
+            .client
+            .query_opt("SELECT 1 FROM indexer_cursors WHERE vault = $1", &[&vault])
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Newest share price, `None` before the first event carrying a snapshot
+    pub async fn latest_price(&self, vault: &str) -> Result<Option<PricePoint>, ApiError> {
+        let row = self
+            .client
+            .query_opt(
+                &format!(
+                    "SELECT {PRICE_COLUMNS} FROM share_prices WHERE vault = $1 \
+                     ORDER BY sequence DESC LIMIT 1"
+                ),
+                &[&vault],
+            )
+            .await?;
+        Ok(row.as_ref().map(PricePoint::from))
+    }
+
+    /// Newest share price at or before `time`, or the oldest one when the vault has none that
+    /// old. Rows without a block time are left out
+    pub async fn price_at(&self, vault: &str, time: i64) -> Result<Option<PricePoint>, ApiError> {
+        let before = self
+            .client
+            .query_opt(
+                &format!(
+                    "SELECT {PRICE_COLUMNS} FROM share_prices \
+                     WHERE vault = $1 AND block_time <= to_timestamp($2::BIGINT) \
+                     ORDER BY sequence DESC LIMIT 1"
+                ),
+                &[&vault, &time],
+            )
+            .await?;
+        if let Some(row) = before {
+            return Ok(Some(PricePoint::from(&row)));
+        }
+        let oldest = self
+            .client
+            .query_opt(
+                &format!(
+                    "SELECT {PRICE_COLUMNS} FROM share_prices \
+                     WHERE vault = $1 AND block_time IS NOT NULL \
+                     ORDER BY sequence LIMIT 1"
+                ),
+                &[&vault],
+            )
+            .await?;
+        Ok(oldest.as_ref().map(PricePoint::from))
+    }
+
+    /// Share prices from `from` to `to` (Unix seconds, both inclusive), oldest first
+    pub async fn prices(
+        &self,
+        vault: &str,
+        from: Option<i64>,
+        to: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<PricePoint>, ApiError> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {PRICE_COLUMNS} FROM share_prices \
+                     WHERE vault = $1 \
+                       AND ($2::BIGINT IS NULL OR block_time >= to_timestamp($2)) \
+                       AND ($3::BIGINT IS NULL OR block_time <= to_timestamp($3)) \
+                     ORDER BY sequence LIMIT $4"
+                ),
+                &[&vault, &from, &to, &limit],
+            )
+            .await?;
+        Ok(rows.iter().map(PricePoint::from).collect())
+    }
+
+    /// Last share price of each UTC day since `since` (Unix seconds), oldest first, with the
+    /// day as `YYYY-MM-DD`
+    pub async fn daily_prices(
+        &self,
+        vault: &str,
+        since: i64,
+    ) -> Result<Vec<(String, PricePoint)>, ApiError> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {PRICE_COLUMNS}, day FROM ( \
+                       SELECT DISTINCT ON (day) *, \
+                         to_char(block_time AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS day \
+                       FROM share_prices \
+                       WHERE vault = $1 AND block_time >= to_timestamp($2::BIGINT) \
+                       ORDER BY day, sequence DESC \
+                     ) daily ORDER BY day"
+                ),
+                &[&vault, &since],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| (row.get(5), PricePoint::from(row)))
+            .collect())
+    }
+
+    /// Deposits, redemptions and fees since `since` (Unix seconds), or ever
+    pub async fn totals(&self, vault: &str, since: Option<i64>) -> Result<FlowTotals, ApiError> {
+        let row = self
+            .client
+            .query_one(
+                "SELECT \
+                   (SELECT COUNT(*) FROM deposits d WHERE d.vault = $1 AND \
+                     ($2::BIGINT IS NULL OR d.block_time >= to_timestamp($2))), \
+                   (SELECT COALESCE(SUM(assets), 0)::BIGINT FROM deposits d WHERE d.vault = $1 \
+                     AND ($2::BIGINT IS NULL OR d.block_time >= to_timestamp($2))), \
+                   (SELECT COUNT(*) FROM redemptions r WHERE r.vault = $1 AND \
+                     ($2::BIGINT IS NULL OR r.block_time >= to_timestamp($2))), \
+                   (SELECT COALESCE(SUM(assets), 0)::BIGINT FROM redemptions r \
+                     WHERE r.vault = $1 \
+                     AND ($2::BIGINT IS NULL OR r.block_time >= to_timestamp($2))), \
+                   (SELECT COALESCE(SUM(amount), 0)::BIGINT FROM fee_collections f \
+                     WHERE f.vault = $1 AND f.kind = 'collected' \
+                     AND ($2::BIGINT IS NULL OR f.block_time >= to_timestamp($2))), \
+                   (SELECT COALESCE(SUM(shares), 0)::BIGINT FROM fee_collections f \
+                     WHERE f.vault = $1 AND f.kind <> 'collected' \
+                     AND ($2::BIGINT IS NULL OR f.block_time >= to_timestamp($2)))",
+                &[&vault, &since],
+            )
+            .await?;
+        Ok(FlowTotals {
+            deposits: row.get(0),
+            deposited_assets: row.get(1),
+            redemptions: row.get(2),
+            redeemed_assets: row.get(3),
+            fees_collected: row.get(4),
+            fee_shares: row.get(5),
+        })
+    }
+
+    /// Users who have received shares of the vault
+    pub async fn depositors(&self, vault: &str) -> Result<i64, ApiError> {
+        let row = self
+            .client
+            .query_one(
+                "SELECT COUNT(DISTINCT receiver) FROM deposits WHERE vault = $1",
+                &[&vault],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Shares and assets `user` received from deposits and paid into redemptions
+    pub async fn flows(&self, vault: &str, user: &str) -> Result<Flows, ApiError> {
+        let row = self
+            .client
+            .query_one(
+                "SELECT \
+                   (SELECT COALESCE(SUM(shares), 0)::BIGINT FROM deposits \
+                     WHERE vault = $1 AND receiver = $2), \
+                   (SELECT COALESCE(SUM(shares), 0)::BIGINT FROM redemptions \
+                     WHERE vault = $1 AND owner = $2), \
+                   (SELECT COALESCE(SUM(assets), 0)::BIGINT FROM deposits \
+                     WHERE vault = $1 AND receiver = $2), \
+                   (SELECT COALESCE(SUM(assets), 0)::BIGINT FROM redemptions \
+                     WHERE vault = $1 AND owner = $2)",
+                &[&vault, &user],
+            )
+            .await?;
+        Ok(Flows {
+            shares_in: row.get(0),
+            shares_out: row.get(1),
+            deposited_assets: row.get(2),
+            redeemed_assets: row.get(3),
+        })
+    }
+
+    /// Deposits and redemptions `user` owned or received, newest first, after `before`
+    pub async fn transfers(
+        &self,
+        vault: &str,
+        user: &str,
+        before: Option<&HistoryKey>,
+        limit: i64,
+    ) -> Result<Vec<Transfer>, ApiError> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT * FROM ( \
+                       SELECT 'deposit' AS direction, {FLOW_COLUMNS} FROM deposits \
+                       WHERE vault = $1 AND (owner = $2 OR receiver = $2) \
+                       UNION ALL \
+                       SELECT 'redemption' AS direction, {FLOW_COLUMNS} FROM redemptions \
+                       WHERE vault = $1 AND (owner = $2 OR receiver = $2) \
+                     ) transfers \
+                     WHERE $3::BIGINT IS NULL \
+                       OR (slot, signature, event_index) < ($3, $4::TEXT, $5::INTEGER) \
+                     ORDER BY slot DESC, signature DESC, event_index DESC LIMIT $6"
+                ),
+                &[
+                    &vault,
+                    &user,
+                    &before.map(|key| key.slot),
+                    &before.map(|key| key.signature.as_str()),
+                    &before.map(|key| key.event_index),
+                    &limit,
+                ],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Transfer {
+                direction: row.get(0),
+                key: HistoryKey {
+                    signature: row.get(1),
+                    event_index: row.get(2),
+                    slot: row.get(3),
+                },
+                block_time: row.get(4),
+                kind: row.get(5),
+                owner: row.get(6),
+                receiver: row.get(7),
+                assets: row.get(8),
+                shares: row.get(9),
+                fee: row.get(10),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,48 @@
+//! Read API for the Talken Stable Vault: serves user positions, historical APY, transaction
+//! history and vault stats from the database tsv-indexer writes, so apps and partners don't
+//! decode vault events themselves.
+
+mod api;
+mod config;
+mod db;
+mod error;
+mod returns;
+
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+use tokio_postgres::NoTls;
+use tracing_subscriber::EnvFilter;
+
+use crate::api::AppState;
+use crate::config::Config;
+use crate::db::Db;
+use crate::error::ApiError;
+
+#[tokio::main]
+async fn main() -> Result<(), ApiError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let (client, connection) = tokio_postgres::connect(&config.database_url, NoTls).await?;
+    let state = AppState {
+        db: Arc::new(Db::new(client)),
+    };
+
+    let listener = TcpListener::bind(config.bind_addr)
+        .await
+        .map_err(|err| ApiError::Config(format!("can't bind {}: {err}", config.bind_addr)))?;
+    tracing::info!(addr = %config.bind_addr, "listening");
+    // Exit when the database connection drops, for the supervisor to restart with a new one
+    tokio::select! {
+        result = connection => {
+            result?;
+            Err(ApiError::ConnectionClosed)
+        }
+        result = axum::serve(listener, api::router(state)) => {
+            result.map_err(|err| ApiError::Config(format!("server error: {err}")))
+        }
+    }
+}
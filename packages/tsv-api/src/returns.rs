@@ -0,0 +1,155 @@
+//! Returns and positions derived from the indexed share prices and flows.
+
+use tsv_usdc_vault::math::annualized_return_bps;
+use tsv_usdc_vault::EXCHANGE_RATE_SCALE;
+
+use crate::db::{Flows, PricePoint};
+
+/// Simple annualized return in bps from `start` to `end`, `None` when either lacks a block
+/// time or they're not apart in time
+pub fn apy_bps(start: &PricePoint, end: &PricePoint) -> Option<i64> {
+    let elapsed = end.block_time? - start.block_time?;
+    if elapsed <= 0 {
+        return None;
+    }
+    annualized_return_bps(
+        u64::try_from(start.exchange_rate).ok()?,
+        u64::try_from(end.exchange_rate).ok()?,
+        elapsed as u64,
+    )
+    .ok()
+}
+
+/// Closing share price of a day and its return over the previous closing price
+#[derive(Debug, PartialEq, Eq)]
+pub struct DailyReturn {
+    pub day: String,
+    pub exchange_rate: i64,
+    /// `None` for the first day
+    pub apy_bps: Option<i64>,
+}
+
+/// Daily returns of consecutive closing prices, oldest first. Days without events have no
+/// closing price; the next day's return then spans them
+pub fn daily_returns(closes: &[(String, PricePoint)]) -> Vec<DailyReturn> {
+    closes
+        .iter()
+        .enumerate()
+        .map(|(index, (day, close))| DailyReturn {
+            day: day.clone(),
+            exchange_rate: close.exchange_rate,
+            apy_bps: index
+                .checked_sub(1)
+                .and_then(|previous| apy_bps(&closes[previous].1, close)),
+        })
+        .collect()
+}
+
+/// A user's holding of a vault, as far as deposits and redemptions show it
+#[derive(Debug, PartialEq, Eq)]
+pub struct Position {
+    pub shares: u64,
+    /// Assets the shares redeem for at the latest exchange rate, before fees
+    pub value: u64,
+    pub deposited_assets: u64,
+    pub redeemed_assets: u64,
+    /// `value + redeemed_assets - deposited_assets`
+    pub earnings: i128,
+}
+
+/// Position of a user with `flows` at `exchange_rate` (assets per `EXCHANGE_RATE_SCALE`
+/// shares). Shares redeemed beyond those deposited came in by transfer and count as zero
+pub fn position(flows: &Flows, exchange_rate: i64) -> Position {
+    let shares = flows.shares_in.saturating_sub(flows.shares_out).max(0) as u64;
+    let value = (shares as u128 * exchange_rate.max(0) as u128 / EXCHANGE_RATE_SCALE as u128)
+        .min(u64::MAX as u128) as u64;
+    let deposited_assets = flows.deposited_assets.max(0) as u64;
+    let redeemed_assets = flows.redeemed_assets.max(0) as u64;
+    Position {
+        shares,
+        value,
+        deposited_assets,
+        redeemed_assets,
+        earnings: value as i128 + redeemed_assets as i128 - deposited_assets as i128,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn price(block_time: i64, exchange_rate: i64) -> PricePoint {
+        PricePoint {
+            sequence: 0,
+            block_time: Some(block_time),
+            total_assets: 0,
+            total_shares: 0,
+            exchange_rate,
+        }
+    }
+
+    #[test]
+    fn annualizes_over_the_elapsed_time() {
+        let rate = EXCHANGE_RATE_SCALE as i64;
+        // 0.1% over a day is 36.5% a year
+        assert_eq!(
+            apy_bps(&price(0, rate), &price(DAY, rate / 1_000 * 1_001)),
+            Some(3_650)
+        );
+        assert_eq!(apy_bps(&price(DAY, rate), &price(DAY, rate * 2)), None);
+        assert_eq!(
+            apy_bps(
+                &PricePoint {
+                    block_time: None,
+                    ..price(0, rate)
+                },
+                &price(DAY, rate)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn daily_returns_span_days_without_events() {
+        let closes = vec![
+            ("2026-01-01".to_string(), price(0, 1_000_000_000)),
+            ("2026-01-02".to_string(), price(DAY, 1_000_100_000)),
+            ("2026-01-04".to_string(), price(3 * DAY, 1_000_300_020)),
+        ];
+
+        let returns: Vec<Option<i64>> = daily_returns(&closes)
+            .into_iter()
+            .map(|daily| daily.apy_bps)
+            .collect();
+        assert_eq!(returns, [None, Some(365), Some(365)]);
+    }
+
+    #[test]
+    fn values_shares_at_the_exchange_rate() {
+        let flows = Flows {
+            shares_in: 3_000_000_000,
+            shares_out: 1_000_000_000,
+            deposited_assets: 3_000_000,
+            redeemed_assets: 1_050_000,
+        };
+
+        assert_eq!(
+            position(&flows, 1_100_000),
+            Position {
+                shares: 2_000_000_000,
+                value: 2_200_000,
+                deposited_assets: 3_000_000,
+                redeemed_assets: 1_050_000,
+                earnings: 250_000,
+            }
+        );
+
+        let transferred = Flows {
+            shares_out: 4_000_000_000,
+            ..flows
+        };
+        assert_eq!(position(&transferred, 1_100_000).shares, 0);
+    }
+}
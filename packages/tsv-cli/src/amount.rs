@@ -0,0 +1,71 @@
+//! Token amounts as typed and shown: decimal strings in whole tokens, e.g. `100.5` USDC.
+
+use crate::error::CliError;
+
+/// Base units of `amount` whole tokens of a mint with `decimals`
+pub fn parse(amount: &str, decimals: u8) -> Result<u64, CliError> {
+    let invalid = || CliError::InvalidArgument(format!("amount {amount}"));
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || fraction.len() > decimals as usize
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let scale = 10u64.checked_pow(decimals as u32).ok_or_else(invalid)?;
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let fraction: u64 = format!("{fraction:0<width$}", width = decimals as usize)
+        .parse()
+        .unwrap_or(0);
+    whole
+        .checked_mul(scale)
+        .and_then(|whole| whole.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+/// `amount` base units as whole tokens, without trailing zeros
+pub fn format(amount: u64, decimals: u8) -> String {
+    let scale = 10u128.pow(decimals as u32);
+    let whole = amount as u128 / scale;
+    let fraction = amount as u128 % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{fraction:0>width$}", width = decimals as usize);
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_whole_and_fractional_amounts() {
+        assert_eq!(parse("100", 6).unwrap(), 100_000_000);
+        assert_eq!(parse("100.5", 6).unwrap(), 100_500_000);
+        assert_eq!(parse("0.000001", 6).unwrap(), 1);
+        assert_eq!(parse(".5", 6).unwrap(), 500_000);
+        assert_eq!(parse("7", 0).unwrap(), 7);
+        assert!(parse("0.0000001", 6).is_err());
+        assert!(parse("1e6", 6).is_err());
+        assert!(parse("-1", 6).is_err());
+        assert!(parse(".", 6).is_err());
+        assert!(parse("18446744073709.551616", 6).is_err());
+    }
+
+    #[test]
+    fn formats_without_trailing_zeros() {
+        assert_eq!(format(100_000_000, 6), "100");
+        assert_eq!(format(100_500_000, 6), "100.5");
+        assert_eq!(format(1, 6), "0.000001");
+        assert_eq!(format(u64::MAX, 18), "18.446744073709551615");
+        assert_eq!(format(7, 0), "7");
+    }
+}
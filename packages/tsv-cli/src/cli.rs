@@ -0,0 +1,233 @@
+//! Command-line arguments.
+
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use solana_sdk::pubkey::Pubkey;
+use tsv_usdc_vault::RiskTier;
+
+#[derive(Parser)]
+#[command(name = "tsv", version, about = "Talken Stable Vault on Solana")]
+pub struct Cli {
+    /// Solana JSON-RPC endpoint
+    #[arg(
+        long,
+        short = 'u',
+        env = "RPC_URL",
+        default_value = "https://api.devnet.solana.com",
+        global = true
+    )]
+    pub url: String,
+    /// Signer and fee payer: a keypair file or a Ledger as usb://ledger[?key=N]
+    /// [default: ~/.config/solana/id.json]
+    #[arg(long, short = 'k', env = "TSV_KEYPAIR", global = true)]
+    pub keypair: Option<String>,
+    /// Asset mint of the vault [default: the legacy single vault]
+    #[arg(long, env = "VAULT_ASSET_MINT", global = true)]
+    pub asset_mint: Option<Pubkey>,
+    /// Simulate the transaction instead of sending it
+    #[arg(long, global = true)]
+    pub simulate: bool,
+    /// Print one JSON object, for scripts
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Create the vault of --asset-mint with a new share mint, governed by the signer
+    Init(InitArgs),
+    #[command(flatten)]
+    Vault(VaultCommand),
+    /// Squads v4 multisig workflows
+    Multisig {
+        #[command(subcommand)]
+        action: MultisigAction,
+    },
+    /// Air-gapped signing: build a transaction online, sign it offline, broadcast it later
+    Tx {
+        #[command(subcommand)]
+        action: TxAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MultisigAction {
+    /// Create a Squads proposal of a vault command, signed by the multisig's vault, instead of
+    /// sending it. The signer must be a member allowed to initiate
+    Propose(ProposeArgs),
+}
+
+#[derive(Args)]
+pub struct ProposeArgs {
+    /// Squads multisig account
+    #[arg(long)]
+    pub multisig: Pubkey,
+    /// Squads vault holding the vault role
+    #[arg(long, default_value_t = 0)]
+    pub vault_index: u8,
+    /// Also vote for the proposal; needs the member to be allowed to vote
+    #[arg(long)]
+    pub approve: bool,
+    #[command(subcommand)]
+    pub command: VaultCommand,
+}
+
+#[derive(Subcommand)]
+pub enum TxAction {
+    /// Write a vault command's transaction unsigned to --output instead of sending it.
+    /// --keypair and --guardian may be given as addresses, their keys staying offline
+    Build(BuildArgs),
+    /// Sign a built transaction with --keypair, without network access
+    Sign {
+        /// File written by `tx build`
+        file: PathBuf,
+        /// Write the signed transaction here [default: back to FILE]
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Send a transaction once every signer has signed it. With --simulate, simulate it however
+    /// far it's signed
+    Broadcast {
+        /// File signed by `tx sign`
+        file: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct BuildArgs {
+    /// File to write the transaction to
+    #[arg(long)]
+    pub output: PathBuf,
+    /// Nonce account whose durable nonce replaces the recent blockhash, so the transaction
+    /// stays valid until it's advanced
+    #[arg(long)]
+    pub nonce: Option<Pubkey>,
+    /// Authority of --nonce, another signer if not the fee payer [default: the signer]
+    #[arg(long, requires = "nonce")]
+    pub nonce_authority: Option<Pubkey>,
+    #[command(subcommand)]
+    pub command: VaultCommand,
+}
+
+/// Commands on an existing vault
+#[derive(Subcommand)]
+pub enum VaultCommand {
+    /// Deposit assets, minting shares
+    Deposit {
+        /// Assets in whole tokens, e.g. 100.5
+        amount: String,
+        /// Wallet receiving the shares [default: the signer]
+        #[arg(long)]
+        receiver: Option<Pubkey>,
+        /// Fewest shares to accept, in whole shares
+        #[arg(long)]
+        min_shares: Option<String>,
+    },
+    /// Redeem shares for the vault's idle assets
+    Redeem {
+        /// Shares in whole shares
+        shares: String,
+        /// Fewest assets to accept, in whole tokens
+        #[arg(long)]
+        min_assets: Option<String>,
+    },
+    /// Show the vault's state, and a wallet's shares with --owner
+    Status {
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Queue new fees, or apply the queued ones once their timelock has passed (fee manager)
+    SetFee {
+        /// New deposit fee [default: unchanged]
+        #[arg(long, conflicts_with = "execute")]
+        deposit_bps: Option<u16>,
+        /// New redeem fee [default: unchanged]
+        #[arg(long, conflicts_with = "execute")]
+        redeem_bps: Option<u16>,
+        /// Apply the queued fees
+        #[arg(long)]
+        execute: bool,
+    },
+    /// Pause deposits, redeems and meta-transactions, or only those given (pauser)
+    Pause {
+        #[arg(long)]
+        deposits: bool,
+        #[arg(long)]
+        redeems: bool,
+        #[arg(long)]
+        meta: bool,
+        /// Unpause instead
+        #[arg(long)]
+        resume: bool,
+    },
+    /// Queue a move of assets to the vault's recovery account behind the timelock, or execute
+    /// the queued one once its delay has passed (timelock admin)
+    EmergencyWithdraw {
+        /// Assets in whole tokens
+        #[arg(required_unless_present = "execute")]
+        amount: Option<String>,
+        /// Guardian co-signer, a keypair file or usb://ledger, or its address when queueing:
+        /// its signature is needed to execute. Without it a requested escape hatch must have
+        /// matured
+        #[arg(long)]
+        guardian: Option<String>,
+        /// Execute the queued action with this id
+        #[arg(long, value_name = "ACTION_ID", conflicts_with = "amount")]
+        execute: Option<u64>,
+    },
+}
+
+#[derive(Args)]
+pub struct InitArgs {
+    /// Share token name
+    #[arg(long)]
+    pub name: String,
+    /// Share token symbol
+    #[arg(long)]
+    pub symbol: String,
+    /// Share token metadata URI
+    #[arg(long, default_value = "")]
+    pub uri: String,
+    /// Vault description shown to depositors
+    #[arg(long, default_value = "")]
+    pub description: String,
+    /// Risk rating shown to depositors
+    #[arg(long, value_enum, default_value_t = Risk::Conservative)]
+    pub risk_tier: Risk,
+    /// Asset token account `emergency-withdraw` pays
+    #[arg(long)]
+    pub recovery_account: Pubkey,
+    #[arg(long, default_value_t = 0)]
+    pub deposit_fee_bps: u16,
+    #[arg(long, default_value_t = 0)]
+    pub redeem_fee_bps: u16,
+    /// Shares locked forever by the first deposit
+    #[arg(long, default_value_t = 1_000)]
+    pub dead_shares: u64,
+    /// Share mint decimals [default: the asset's plus the vault's virtual offset]
+    #[arg(long)]
+    pub share_decimals: Option<u8>,
+    /// Accept a Token-2022 asset mint with a permanent delegate or close authority
+    #[arg(long)]
+    pub allow_privileged_extensions: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Risk {
+    Conservative,
+    Moderate,
+    Aggressive,
+}
+
+impl From<Risk> for RiskTier {
+    fn from(risk: Risk) -> Self {
+        match risk {
+            Risk::Conservative => RiskTier::Conservative,
+            Risk::Moderate => RiskTier::Moderate,
+            Risk::Aggressive => RiskTier::Aggressive,
+        }
+    }
+}
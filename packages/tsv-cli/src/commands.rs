@@ -0,0 +1,532 @@
+//! The subcommands, each building its instructions with `tsv-client` and returning the
+//! `Report` that `main` prints.
+
+use std::str::FromStr;
+
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::signer::null_signer::NullSigner;
+use solana_sdk::system_instruction;
+use tsv_client::instructions::{self, NewVault};
+use tsv_client::{pda, Optionals, Vault, VaultClient};
+use tsv_usdc_vault::math::{Rounding, VIRTUAL_DECIMALS_OFFSET};
+use tsv_usdc_vault::{QueuedAction, Timelock, VaultInfo, MIN_TIMELOCK_DELAY_SECONDS};
+
+use crate::amount;
+use crate::cli::{InitArgs, VaultCommand};
+use crate::error::CliError;
+use crate::offline::OfflineBuild;
+use crate::output::Report;
+use crate::signer;
+use crate::squads::Multisig;
+use crate::submit::submit;
+
+/// A vault's client and how to sign and submit
+pub struct Context {
+    pub client: VaultClient,
+    /// Path of the signer, see `signer::load`
+    pub keypair: String,
+    pub simulate: bool,
+    /// Multisig the instructions are proposed to instead of sent (`multisig propose`)
+    pub proposal: Option<Proposal>,
+    /// File the transaction is written to unsigned instead of sent (`tx build`)
+    pub offline: Option<OfflineBuild>,
+}
+
+/// Where `multisig propose` creates its proposal
+pub struct Proposal {
+    pub multisig: Multisig,
+    /// Whether the signer, a member, also votes for it
+    pub approve: bool,
+}
+
+impl Context {
+    fn signer(&self) -> Result<Box<dyn Signer>, CliError> {
+        self.load(&self.keypair, "keypair")
+    }
+
+    /// Signer at `path`, see `signer::load`. When building for offline signing, `path` may be
+    /// the signer's address instead, the key itself staying on the offline machine
+    fn load(&self, path: &str, name: &str) -> Result<Box<dyn Signer>, CliError> {
+        if self.offline.is_some() {
+            if let Ok(pubkey) = Pubkey::from_str(path) {
+                return Ok(Box::new(NullSigner::new(&pubkey)));
+            }
+        }
+        signer::load(path, name)
+    }
+
+    /// Key signing a command's instructions: the signer's or, when proposing, the multisig
+    /// vault's
+    fn authority(&self, signer: &dyn Signer) -> Pubkey {
+        match &self.proposal {
+            Some(proposal) => proposal.multisig.vault(),
+            None => signer.pubkey(),
+        }
+    }
+
+    /// Send (or simulate) `instructions` signed by `signer` and `cosigners`, propose them to
+    /// the multisig with `signer` as the proposing member, or write them unsigned for offline
+    /// signing with `signer` paying, completing `report`
+    async fn submit(
+        &self,
+        report: Report,
+        signer: &dyn Signer,
+        cosigners: &[&dyn Signer],
+        instructions: &[Instruction],
+    ) -> Result<Report, CliError> {
+        let rpc = &self.client.rpc;
+        if let Some(offline) = &self.offline {
+            let outcome = offline
+                .write(rpc, &report, &signer.pubkey(), instructions)
+                .await?;
+            return Ok(report.outcome(outcome));
+        }
+        let Some(proposal) = &self.proposal else {
+            let mut signers = vec![signer];
+            signers.extend_from_slice(cosigners);
+            let outcome = submit(rpc, &signers, instructions, self.simulate).await?;
+            return Ok(report.outcome(outcome));
+        };
+        if !cosigners.is_empty() {
+            return Err(CliError::InvalidArgument(
+                "a multisig proposal can't carry other signers".into(),
+            ));
+        }
+
+        let multisig = &proposal.multisig;
+        let index = multisig.next_transaction_index(rpc).await?;
+        let proposed = multisig.propose(index, &signer.pubkey(), instructions, proposal.approve);
+        let outcome = submit(rpc, &[signer], &proposed, self.simulate).await?;
+        Ok(report
+            .with(
+                "proposal",
+                json!({
+                    "multisig": multisig.address.to_string(),
+                    "vault": multisig.vault().to_string(),
+                    "transaction_index": index,
+                    "address": multisig.proposal(index).to_string(),
+                    "approved": proposal.approve,
+                }),
+            )
+            .outcome(outcome))
+    }
+
+    /// Decimals of the vault's asset and share mints
+    async fn decimals(&self) -> Result<(u8, u8), CliError> {
+        let rpc = &self.client.rpc;
+        let asset: Mint = tsv_client::fetch(rpc, &self.client.vault.asset_mint).await?;
+        let share: Mint = tsv_client::fetch(rpc, &self.client.vault.share_mint).await?;
+        Ok((asset.decimals, share.decimals))
+    }
+}
+
+/// Create the vault of `asset_mint`: a share mint owned by the vault PDA, then `initialize`
+pub async fn init(
+    rpc: &RpcClient,
+    keypair: &str,
+    asset_mint: &Pubkey,
+    args: InitArgs,
+    simulate: bool,
+) -> Result<Report, CliError> {
+    let authority = signer::load(keypair, "keypair")?;
+    let token_program = rpc.get_account(asset_mint).await?.owner;
+    let asset: Mint = tsv_client::fetch(rpc, asset_mint).await?;
+    let share_decimals = args
+        .share_decimals
+        .unwrap_or(asset.decimals + VIRTUAL_DECIMALS_OFFSET as u8);
+    let vault = pda::vault(asset_mint);
+    let share_mint = Keypair::new();
+    let asset_vault = Keypair::new();
+
+    let mint_len = spl_token_2022::state::Mint::LEN;
+    let rent = rpc.get_minimum_balance_for_rent_exemption(mint_len).await?;
+    let create_share_mint = system_instruction::create_account(
+        &authority.pubkey(),
+        &share_mint.pubkey(),
+        rent,
+        mint_len as u64,
+        &token_program,
+    );
+    let initialize_share_mint = spl_token_2022::instruction::initialize_mint2(
+        &token_program,
+        &share_mint.pubkey(),
+        &vault,
+        None,
+        share_decimals,
+    )
+    .map_err(|err| CliError::Program(err.to_string()))?;
+
+    let accounts = NewVault {
+        authority: authority.pubkey(),
+        asset_mint: *asset_mint,
+        share_mint: share_mint.pubkey(),
+        asset_vault: asset_vault.pubkey(),
+        recovery_account: args.recovery_account,
+        token_program,
+    };
+    let initialize = instructions::initialize(
+        &accounts,
+        tsv_usdc_vault::instruction::Initialize {
+            deposit_fee_bps: args.deposit_fee_bps,
+            redeem_fee_bps: args.redeem_fee_bps,
+            dead_shares: args.dead_shares,
+            genesis_hash: rpc.get_genesis_hash().await?.to_bytes(),
+            allow_privileged_extensions: args.allow_privileged_extensions,
+            share_name: args.name.clone(),
+            share_symbol: args.symbol.clone(),
+            share_uri: args.uri.clone(),
+            vault_info: VaultInfo {
+                name: args.name,
+                description: args.description,
+                uri: args.uri,
+                risk_tier: args.risk_tier.into(),
+            },
+        },
+    );
+
+    let outcome = submit(
+        rpc,
+        &[&*authority, &share_mint, &asset_vault],
+        &[create_share_mint, initialize_share_mint, initialize],
+        simulate,
+    )
+    .await?;
+    Ok(Report::new("init")
+        .with("vault", vault.to_string())
+        .with("authority", authority.pubkey().to_string())
+        .with("share_mint", share_mint.pubkey().to_string())
+        .with("share_decimals", share_decimals)
+        .with("asset_vault", asset_vault.pubkey().to_string())
+        .outcome(outcome))
+}
+
+pub async fn run(ctx: &Context, command: VaultCommand) -> Result<Report, CliError> {
+    match command {
+        VaultCommand::Deposit {
+            amount,
+            receiver,
+            min_shares,
+        } => deposit(ctx, &amount, receiver, min_shares.as_deref()).await,
+        VaultCommand::Redeem { shares, min_assets } => {
+            redeem(ctx, &shares, min_assets.as_deref()).await
+        }
+        VaultCommand::Status { .. } if ctx.proposal.is_some() => Err(CliError::InvalidArgument(
+            "status only reads, there is nothing to propose".into(),
+        )),
+        VaultCommand::Status { .. } if ctx.offline.is_some() => Err(CliError::InvalidArgument(
+            "status only reads, there is nothing to sign".into(),
+        )),
+        VaultCommand::Status { owner } => status(ctx, owner).await,
+        VaultCommand::SetFee {
+            deposit_bps,
+            redeem_bps,
+            execute,
+        } => set_fee(ctx, deposit_bps, redeem_bps, execute).await,
+        VaultCommand::Pause {
+            deposits,
+            redeems,
+            meta,
+            resume,
+        } => pause(ctx, [deposits, redeems, meta], resume).await,
+        VaultCommand::EmergencyWithdraw {
+            amount,
+            guardian,
+            execute,
+        } => match execute {
+            Some(id) => execute_emergency_withdraw(ctx, id, guardian.as_deref()).await,
+            None => {
+                let amount = amount.ok_or_else(|| {
+                    CliError::InvalidArgument(
+                        "emergency-withdraw needs an amount or --execute".into(),
+                    )
+                })?;
+                emergency_withdraw(ctx, &amount, guardian.as_deref()).await
+            }
+        },
+    }
+}
+
+async fn deposit(
+    ctx: &Context,
+    amount: &str,
+    receiver: Option<Pubkey>,
+    min_shares: Option<&str>,
+) -> Result<Report, CliError> {
+    let signer = ctx.signer()?;
+    let user = ctx.authority(&*signer);
+    let receiver = receiver.unwrap_or(user);
+    let (asset_decimals, share_decimals) = ctx.decimals().await?;
+    let assets = amount::parse(amount, asset_decimals)?;
+    let min_shares_out = min_shares
+        .map(|min_shares| amount::parse(min_shares, share_decimals))
+        .transpose()?;
+
+    let vault = &ctx.client.vault;
+    let expected_shares = ctx.client.preview_deposit(assets).await?;
+    let optionals = Optionals::fetch(&ctx.client.rpc, &vault.address, &user, &receiver).await?;
+    let ix = instructions::deposit(vault, &user, &receiver, assets, min_shares_out, optionals);
+
+    let report = Report::new("deposit")
+        .with("vault", vault.address.to_string())
+        .with("receiver", receiver.to_string())
+        .with("assets", amount::format(assets, asset_decimals))
+        .with(
+            "expected_shares",
+            amount::format(expected_shares, share_decimals),
+        );
+    ctx.submit(report, &*signer, &[], &[ix]).await
+}
+
+async fn redeem(ctx: &Context, shares: &str, min_assets: Option<&str>) -> Result<Report, CliError> {
+    let signer = ctx.signer()?;
+    let user = ctx.authority(&*signer);
+    let (asset_decimals, share_decimals) = ctx.decimals().await?;
+    let shares = amount::parse(shares, share_decimals)?;
+    let min_assets_out = min_assets
+        .map(|min_assets| amount::parse(min_assets, asset_decimals))
+        .transpose()?;
+
+    let vault = &ctx.client.vault;
+    let expected_assets = ctx.client.preview_redeem(&user, shares).await?;
+    let optionals = Optionals::fetch(&ctx.client.rpc, &vault.address, &user, &user).await?;
+    let ix = instructions::redeem(vault, &user, None, shares, min_assets_out, optionals);
+
+    let report = Report::new("redeem")
+        .with("vault", vault.address.to_string())
+        .with("shares", amount::format(shares, share_decimals))
+        .with(
+            "expected_assets",
+            amount::format(expected_assets, asset_decimals),
+        );
+    ctx.submit(report, &*signer, &[], &[ix]).await
+}
+
+async fn status(ctx: &Context, owner: Option<Pubkey>) -> Result<Report, CliError> {
+    let rpc = &ctx.client.rpc;
+    let accounts = &ctx.client.vault;
+    let (vault, now) = ctx.client.state().await?;
+    let (asset_decimals, share_decimals) = ctx.decimals().await?;
+    let one_share = 10u64
+        .checked_pow(share_decimals as u32)
+        .ok_or_else(|| CliError::Program(format!("share mint has {share_decimals} decimals")))?;
+    let share_price = vault
+        .accrued(now)
+        .and_then(|vault| vault.convert_to_assets(one_share, Rounding::Floor))
+        .map_err(|err| CliError::Program(err.to_string()))?;
+    let asset_vault = rpc.get_token_account_balance(&accounts.asset_vault).await?;
+    let assets = |amount| amount::format(amount, asset_decimals);
+    let shares = |amount| amount::format(amount, share_decimals);
+
+    let pending_fees = (vault.fee_update_eta > 0).then(|| {
+        json!({
+            "deposit_bps": vault.pending_deposit_fee_bps,
+            "redeem_bps": vault.pending_redeem_fee_bps,
+            "eta": vault.fee_update_eta,
+        })
+    });
+    let mut report = Report::new("status")
+        .with("vault", accounts.address.to_string())
+        .with("asset_mint", accounts.asset_mint.to_string())
+        .with("share_mint", accounts.share_mint.to_string())
+        .with("total_assets", assets(vault.total_assets))
+        .with("total_shares", shares(vault.total_shares))
+        .with("share_price", assets(share_price))
+        .with("asset_vault_balance", asset_vault.ui_amount_string)
+        .with(
+            "fees",
+            json!({
+                "deposit_bps": vault.deposit_fee_bps,
+                "redeem_bps": vault.redeem_fee_bps,
+                "management_bps": vault.management_fee_bps,
+                "pending": pending_fees,
+            }),
+        )
+        .with(
+            "paused",
+            json!({
+                "deposits": vault.paused_deposits,
+                "redeems": vault.paused_redeems,
+                "meta": vault.paused_meta,
+            }),
+        )
+        .with(
+            "roles",
+            json!({
+                "authority": vault.authority.to_string(),
+                "pauser": vault.pauser.to_string(),
+                "fee_manager": vault.fee_manager.to_string(),
+                "guardian": vault.guardian.to_string(),
+                "keeper": vault.keeper.to_string(),
+            }),
+        )
+        .with("recovery_account", vault.recovery_account.to_string());
+
+    if let Some(owner) = owner {
+        let share_account: Option<TokenAccount> =
+            tsv_client::fetch_optional(rpc, &accounts.share_account(&owner)).await?;
+        let balance = share_account.map_or(0, |account| account.amount);
+        let value = ctx.client.preview_redeem(&owner, balance).await?;
+        report = report.with(
+            "position",
+            json!({
+                "owner": owner.to_string(),
+                "shares": shares(balance),
+                "redeem_value": assets(value),
+            }),
+        );
+    }
+    Ok(report)
+}
+
+async fn set_fee(
+    ctx: &Context,
+    deposit_bps: Option<u16>,
+    redeem_bps: Option<u16>,
+    execute: bool,
+) -> Result<Report, CliError> {
+    let signer = ctx.signer()?;
+    let fee_manager = ctx.authority(&*signer);
+    let address = ctx.client.vault.address;
+    let vault: Vault = tsv_client::fetch(&ctx.client.rpc, &address).await?;
+
+    let (ix, report) = if execute {
+        let report = Report::new("set-fee")
+            .with("deposit_bps", vault.pending_deposit_fee_bps)
+            .with("redeem_bps", vault.pending_redeem_fee_bps)
+            .with("executed", true);
+        (
+            instructions::execute_fee_update(&address, &fee_manager),
+            report,
+        )
+    } else {
+        if deposit_bps.is_none() && redeem_bps.is_none() {
+            return Err(CliError::InvalidArgument(
+                "set-fee needs --deposit-bps, --redeem-bps or --execute".into(),
+            ));
+        }
+        let deposit_bps = deposit_bps.unwrap_or(vault.deposit_fee_bps);
+        let redeem_bps = redeem_bps.unwrap_or(vault.redeem_fee_bps);
+        let report = Report::new("set-fee")
+            .with("deposit_bps", deposit_bps)
+            .with("redeem_bps", redeem_bps)
+            .with("timelock_seconds", vault.fee_timelock_seconds);
+        (
+            instructions::queue_fee_update(&address, &fee_manager, deposit_bps, redeem_bps),
+            report,
+        )
+    };
+
+    ctx.submit(report, &*signer, &[], &[ix]).await
+}
+
+/// Set the `selected` flags of deposits, redeems and meta-transactions, all of them when none
+/// is, to paused or, with `resume`, unpaused. The others keep their current state
+async fn pause(ctx: &Context, selected: [bool; 3], resume: bool) -> Result<Report, CliError> {
+    let signer = ctx.signer()?;
+    let pauser = ctx.authority(&*signer);
+    let address = ctx.client.vault.address;
+    let vault: Vault = tsv_client::fetch(&ctx.client.rpc, &address).await?;
+
+    let all = selected.iter().all(|selected| !selected);
+    let flag = |selected: bool, current: bool| {
+        if all || selected {
+            !resume
+        } else {
+            current
+        }
+    };
+    let paused_deposits = flag(selected[0], vault.paused_deposits);
+    let paused_redeems = flag(selected[1], vault.paused_redeems);
+    let paused_meta = flag(selected[2], vault.paused_meta);
+    let ix = instructions::set_pause_flags(
+        &address,
+        &pauser,
+        paused_deposits,
+        paused_redeems,
+        paused_meta,
+    );
+
+    let report = Report::new(if resume { "resume" } else { "pause" })
+        .with("deposits", paused_deposits)
+        .with("redeems", paused_redeems)
+        .with("meta", paused_meta);
+    ctx.submit(report, &*signer, &[], &[ix]).await
+}
+
+/// Queue an `emergency_withdraw` of `amount` as the timelock's next action, by the timelock
+/// admin. `guardian` is only named here, it signs `execute_emergency_withdraw`
+async fn emergency_withdraw(
+    ctx: &Context,
+    amount: &str,
+    guardian: Option<&str>,
+) -> Result<Report, CliError> {
+    let signer = ctx.signer()?;
+    let admin = ctx.authority(&*signer);
+    let guardian = match guardian {
+        Some(guardian) => Some(match Pubkey::from_str(guardian) {
+            Ok(pubkey) => pubkey,
+            Err(_) => ctx.load(guardian, "guardian")?.pubkey(),
+        }),
+        None => None,
+    };
+    let accounts = &ctx.client.vault;
+    let rpc = &ctx.client.rpc;
+    let vault: Vault = tsv_client::fetch(rpc, &accounts.address).await?;
+    let timelock: Timelock = tsv_client::fetch(rpc, &pda::timelock(&accounts.address)).await?;
+    let (asset_decimals, _) = ctx.decimals().await?;
+    let assets = amount::parse(amount, asset_decimals)?;
+
+    let withdraw = instructions::emergency_withdraw(
+        accounts,
+        &vault.recovery_account,
+        guardian.as_ref(),
+        assets,
+    );
+    let id = timelock.next_action_id;
+    let ix = instructions::queue_action(&accounts.address, &admin, id, &withdraw);
+
+    let report = Report::new("emergency-withdraw")
+        .with("assets", amount::format(assets, asset_decimals))
+        .with("recovery_account", vault.recovery_account.to_string())
+        .with("guardian", guardian.is_some())
+        .with("action_id", id)
+        .with(
+            "delay_seconds",
+            timelock.delay_seconds.max(MIN_TIMELOCK_DELAY_SECONDS),
+        );
+    ctx.submit(report, &*signer, &[], &[ix]).await
+}
+
+/// Execute the emergency withdrawal queued as action `id`, by the timelock admin and, if it
+/// was queued with one, co-signed by `guardian`
+async fn execute_emergency_withdraw(
+    ctx: &Context,
+    id: u64,
+    guardian: Option<&str>,
+) -> Result<Report, CliError> {
+    let signer = ctx.signer()?;
+    let admin = ctx.authority(&*signer);
+    let guardian = guardian
+        .map(|path| ctx.load(path, "guardian"))
+        .transpose()?;
+    let address = ctx.client.vault.address;
+    let action = pda::timelock_action(&pda::timelock(&address), id);
+    let queued: QueuedAction = tsv_client::fetch(&ctx.client.rpc, &action).await?;
+
+    let ix = instructions::execute_action(&address, &admin, id, &queued.accounts);
+    let cosigners: Vec<&dyn Signer> = guardian.as_deref().into_iter().collect();
+
+    let report = Report::new("emergency-withdraw")
+        .with("action_id", id)
+        .with("eta", queued.eta)
+        .with("guardian_signed", guardian.is_some())
+        .with("executed", true);
+    ctx.submit(report, &*signer, &cosigners, &[ix]).await
+}
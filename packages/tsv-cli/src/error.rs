@@ -0,0 +1,21 @@
+use solana_client::client_error::ClientError;
+use thiserror::Error;
+use tsv_client::VaultClientError;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("signer error: {0}")]
+    Signer(String),
+    #[error("RPC error: {0}")]
+    Rpc(#[from] ClientError),
+    #[error(transparent)]
+    Client(#[from] VaultClientError),
+    #[error("program error: {0}")]
+    Program(String),
+    #[error("transaction file {path}: {reason}")]
+    TxFile { path: String, reason: String },
+    #[error("nonce account {0}")]
+    Nonce(String),
+}
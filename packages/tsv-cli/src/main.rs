@@ -0,0 +1,121 @@
+//! `tsv`: command-line tool for Talken Stable Vault users and operators. Deposits, redeems and
+//! the vault's status for users; creating a vault, fees, pauses and emergency withdrawals for
+//! operators. Every transaction can be simulated first, and every result printed as JSON.
+//! Operator commands can instead be proposed to a Squads multisig holding the role, or built
+//! into a file to sign on an offline machine and broadcast later.
+
+mod amount;
+mod cli;
+mod commands;
+mod error;
+mod offline;
+mod output;
+mod signer;
+mod squads;
+mod submit;
+
+use std::process::ExitCode;
+
+use clap::Parser;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tsv_client::VaultClient;
+
+use crate::cli::{Cli, Command, MultisigAction, TxAction};
+use crate::commands::{Context, Proposal};
+use crate::error::CliError;
+use crate::offline::OfflineBuild;
+use crate::output::Report;
+use crate::squads::Multisig;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+    match run(cli).await {
+        Ok(report) => {
+            report.print(json);
+            if report.succeeded() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(err) => {
+            output::print_error(&err, json);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<Report, CliError> {
+    let rpc = RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed());
+    let keypair = cli.keypair.unwrap_or_else(signer::default_path);
+
+    match cli.command {
+        Command::Init(args) => {
+            let asset_mint = cli
+                .asset_mint
+                .ok_or_else(|| CliError::InvalidArgument("init needs --asset-mint".into()))?;
+            commands::init(&rpc, &keypair, &asset_mint, args, cli.simulate).await
+        }
+        Command::Vault(command) => {
+            let client = VaultClient::for_asset(rpc, cli.asset_mint.as_ref()).await?;
+            let ctx = Context {
+                client,
+                keypair,
+                simulate: cli.simulate,
+                proposal: None,
+                offline: None,
+            };
+            commands::run(&ctx, command).await
+        }
+        Command::Multisig {
+            action: MultisigAction::Propose(args),
+        } => {
+            let client = VaultClient::for_asset(rpc, cli.asset_mint.as_ref()).await?;
+            let ctx = Context {
+                client,
+                keypair,
+                simulate: cli.simulate,
+                proposal: Some(Proposal {
+                    multisig: Multisig {
+                        address: args.multisig,
+                        vault_index: args.vault_index,
+                    },
+                    approve: args.approve,
+                }),
+                offline: None,
+            };
+            commands::run(&ctx, args.command).await
+        }
+        Command::Tx {
+            action: TxAction::Build(args),
+        } => {
+            if cli.simulate {
+                return Err(CliError::InvalidArgument(
+                    "tx build sends nothing; simulate the file with tx broadcast --simulate".into(),
+                ));
+            }
+            let client = VaultClient::for_asset(rpc, cli.asset_mint.as_ref()).await?;
+            let ctx = Context {
+                client,
+                keypair,
+                simulate: false,
+                proposal: None,
+                offline: Some(OfflineBuild {
+                    output: args.output,
+                    nonce: args.nonce,
+                    nonce_authority: args.nonce_authority,
+                }),
+            };
+            commands::run(&ctx, args.command).await
+        }
+        Command::Tx {
+            action: TxAction::Sign { file, output },
+        } => offline::sign(&keypair, &file, output.as_deref()),
+        Command::Tx {
+            action: TxAction::Broadcast { file },
+        } => offline::broadcast(&rpc, &file, cli.simulate).await,
+    }
+}
@@ -0,0 +1,354 @@
+//! Air-gapped signing: `tx build` writes a vault command's transaction unsigned to a file on an
+//! online machine, `tx sign` adds signatures to it on offline ones, and `tx broadcast` sends it
+//! once every signer has. On a durable nonce the transaction stays valid until the nonce is
+//! advanced, instead of the minute or two a recent blockhash lasts.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::nonce_utils;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::error::CliError;
+use crate::output::Report;
+use crate::signer;
+use crate::submit::{simulate_transaction, Outcome};
+
+/// Version of the `TxFile` layout
+const FILE_VERSION: u8 = 1;
+
+/// Where `tx build` writes its transaction, and the durable nonce it uses
+pub struct OfflineBuild {
+    pub output: PathBuf,
+    pub nonce: Option<Pubkey>,
+    /// Authority of `nonce`, the fee payer without it
+    pub nonce_authority: Option<Pubkey>,
+}
+
+impl OfflineBuild {
+    /// Write the unsigned transaction of `instructions`, paid by `payer`, with `report`
+    /// describing it to the offline signers
+    pub async fn write(
+        &self,
+        rpc: &RpcClient,
+        report: &Report,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+    ) -> Result<Outcome, CliError> {
+        let transaction = match &self.nonce {
+            Some(nonce) => {
+                let authority = self.nonce_authority.unwrap_or(*payer);
+                let blockhash = nonce_blockhash(rpc, nonce, Some(&authority)).await?;
+                let mut message =
+                    Message::new_with_nonce(instructions.to_vec(), Some(payer), nonce, &authority);
+                message.recent_blockhash = blockhash;
+                Transaction::new_unsigned(message)
+            }
+            None => {
+                let blockhash = rpc.get_latest_blockhash().await?;
+                let message = Message::new_with_blockhash(instructions, Some(payer), &blockhash);
+                Transaction::new_unsigned(message)
+            }
+        };
+
+        let file = TxFile {
+            version: FILE_VERSION,
+            command: report.command.to_string(),
+            details: report.details.clone(),
+            nonce: self.nonce.map(|nonce| nonce.to_string()),
+            transaction: encode(&transaction)?,
+        };
+        file.write(&self.output)?;
+        Ok(Outcome::Built {
+            output: self.output.display().to_string(),
+            blockhash: transaction.message.recent_blockhash.to_string(),
+            nonce: file.nonce,
+            missing_signers: to_strings(&missing_signers(&transaction)),
+        })
+    }
+}
+
+/// A built transaction and what it does, as `tx build` writes it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TxFile {
+    pub version: u8,
+    /// Command the transaction was built by, and the details it reported
+    pub command: String,
+    pub details: Map<String, Value>,
+    /// Nonce account the transaction advances, if it's on a durable nonce
+    pub nonce: Option<String>,
+    /// The bincode-encoded `Transaction` in base64, with the signatures collected so far
+    pub transaction: String,
+}
+
+impl TxFile {
+    pub fn read(path: &Path) -> Result<Self, CliError> {
+        let text = fs::read_to_string(path).map_err(|err| file_error(path, err))?;
+        let file: Self = serde_json::from_str(&text).map_err(|err| file_error(path, err))?;
+        if file.version != FILE_VERSION {
+            return Err(file_error(
+                path,
+                format!("version {}, expected {FILE_VERSION}", file.version),
+            ));
+        }
+        Ok(file)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), CliError> {
+        let text = serde_json::to_string_pretty(self).expect("transaction files serialize");
+        fs::write(path, text + "\n").map_err(|err| file_error(path, err))
+    }
+
+    pub fn transaction(&self) -> Result<Transaction, CliError> {
+        let bytes = STANDARD
+            .decode(&self.transaction)
+            .map_err(|err| CliError::InvalidArgument(format!("transaction: {err}")))?;
+        bincode::deserialize(&bytes)
+            .map_err(|err| CliError::InvalidArgument(format!("transaction: {err}")))
+    }
+}
+
+/// `tx sign`: sign the transaction in `path` with the signer at `keypair`, writing it to
+/// `output` or back to `path`. Makes no network request
+pub fn sign(keypair: &str, path: &Path, output: Option<&Path>) -> Result<Report, CliError> {
+    let mut file = TxFile::read(path)?;
+    let mut transaction = file.transaction()?;
+    let signer = signer::load(keypair, "keypair")?;
+    sign_transaction(&mut transaction, &*signer)?;
+    file.transaction = encode(&transaction)?;
+    let output = output.unwrap_or(path);
+    file.write(output)?;
+
+    let programs: Vec<String> = transaction
+        .message
+        .instructions
+        .iter()
+        .map(|ix| transaction.message.account_keys[ix.program_id_index as usize].to_string())
+        .collect();
+    Ok(Report::new("tx-sign")
+        .with("file", output.display().to_string())
+        .with("built_command", file.command)
+        .with("details", Value::Object(file.details))
+        .with("fee_payer", transaction.message.account_keys[0].to_string())
+        .with("programs", programs)
+        .with("nonce", file.nonce)
+        .with("signed_by", signer.pubkey().to_string())
+        .with(
+            "missing_signers",
+            to_strings(&missing_signers(&transaction)),
+        ))
+}
+
+/// `tx broadcast`: send the transaction in `path` once every signer has signed it, or with
+/// `simulate`, simulate it however far it's signed
+pub async fn broadcast(rpc: &RpcClient, path: &Path, simulate: bool) -> Result<Report, CliError> {
+    let file = TxFile::read(path)?;
+    let transaction = file.transaction()?;
+    let missing = missing_signers(&transaction);
+    let report = Report::new("tx-broadcast")
+        .with("file", path.display().to_string())
+        .with("built_command", file.command)
+        .with("nonce", file.nonce.clone());
+
+    let nonce = file
+        .nonce
+        .as_deref()
+        .map(|nonce| {
+            nonce
+                .parse::<Pubkey>()
+                .map_err(|err| CliError::InvalidArgument(format!("nonce {nonce}: {err}")))
+        })
+        .transpose()?;
+    if let Some(nonce) = &nonce {
+        let current = nonce_blockhash(rpc, nonce, None).await?;
+        if current != transaction.message.recent_blockhash {
+            return Err(CliError::Nonce(format!(
+                "{nonce} was advanced since the transaction was built; build and sign it again"
+            )));
+        }
+    }
+
+    if simulate {
+        // A durable nonce's blockhash isn't recent, so it can't be replaced like `submit` does
+        let outcome = simulate_transaction(rpc, &transaction, missing.is_empty(), false).await?;
+        return Ok(report.outcome(outcome));
+    }
+    if !missing.is_empty() {
+        return Err(CliError::Signer(format!(
+            "not signed yet by {}",
+            to_strings(&missing).join(", ")
+        )));
+    }
+
+    let signature = match nonce {
+        // `send_and_confirm_transaction` gives up once the blockhash is no longer recent,
+        // which a nonce's never is
+        Some(_) => {
+            let signature = rpc.send_transaction(&transaction).await?;
+            rpc.poll_for_signature(&signature).await?;
+            signature
+        }
+        None => rpc.send_and_confirm_transaction(&transaction).await?,
+    };
+    Ok(report.outcome(Outcome::Confirmed {
+        signature: signature.to_string(),
+    }))
+}
+
+/// Add `signer`'s signature to `transaction`, keeping the others'
+fn sign_transaction(transaction: &mut Transaction, signer: &dyn Signer) -> Result<(), CliError> {
+    let pubkey = signer.pubkey();
+    if !transaction.message.signer_keys().contains(&&pubkey) {
+        return Err(CliError::Signer(format!(
+            "{pubkey} is not a signer of this transaction"
+        )));
+    }
+    let signers = [signer];
+    let blockhash = transaction.message.recent_blockhash;
+    transaction
+        .try_partial_sign(&signers[..], blockhash)
+        .map_err(|err| CliError::Signer(err.to_string()))
+}
+
+/// Signers of `transaction` that haven't signed it yet, in its order
+fn missing_signers(transaction: &Transaction) -> Vec<Pubkey> {
+    transaction
+        .message
+        .signer_keys()
+        .into_iter()
+        .zip(&transaction.signatures)
+        .filter(|(_, signature)| **signature == Signature::default())
+        .map(|(pubkey, _)| *pubkey)
+        .collect()
+}
+
+/// Blockhash `nonce` holds, checking that `authority`, if given, can advance it
+async fn nonce_blockhash(
+    rpc: &RpcClient,
+    nonce: &Pubkey,
+    authority: Option<&Pubkey>,
+) -> Result<Hash, CliError> {
+    let account =
+        nonce_utils::nonblocking::get_account_with_commitment(rpc, nonce, rpc.commitment())
+            .await
+            .map_err(|err| CliError::Nonce(format!("{nonce}: {err}")))?;
+    let data = nonce_utils::data_from_account(&account)
+        .map_err(|err| CliError::Nonce(format!("{nonce}: {err}")))?;
+    if let Some(authority) = authority {
+        if data.authority != *authority {
+            return Err(CliError::Nonce(format!(
+                "{nonce} is advanced by {}, not {authority}",
+                data.authority
+            )));
+        }
+    }
+    Ok(data.blockhash())
+}
+
+fn encode(transaction: &Transaction) -> Result<String, CliError> {
+    let bytes = bincode::serialize(transaction)
+        .map_err(|err| CliError::InvalidArgument(format!("transaction: {err}")))?;
+    Ok(STANDARD.encode(bytes))
+}
+
+fn to_strings(pubkeys: &[Pubkey]) -> Vec<String> {
+    pubkeys.iter().map(Pubkey::to_string).collect()
+}
+
+fn file_error(path: &Path, reason: impl ToString) -> CliError {
+    CliError::TxFile {
+        path: path.display().to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::system_instruction;
+
+    use super::*;
+
+    /// Unsigned transaction paid by `payer` on `nonce`, co-signed by `guardian`
+    fn built(payer: &Keypair, guardian: &Keypair, nonce: &Pubkey) -> Transaction {
+        let ix = Instruction {
+            program_id: tsv_usdc_vault::ID,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(guardian.pubkey(), true),
+            ],
+            data: vec![1, 2, 3],
+        };
+        let mut message =
+            Message::new_with_nonce(vec![ix], Some(&payer.pubkey()), nonce, &payer.pubkey());
+        message.recent_blockhash = Hash::new_unique();
+        Transaction::new_unsigned(message)
+    }
+
+    #[test]
+    fn signers_sign_in_any_order_on_the_nonce() {
+        let (payer, guardian) = (Keypair::new(), Keypair::new());
+        let nonce = Pubkey::new_unique();
+        let mut transaction = built(&payer, &guardian, &nonce);
+        let blockhash = transaction.message.recent_blockhash;
+        assert_eq!(
+            missing_signers(&transaction),
+            [payer.pubkey(), guardian.pubkey()]
+        );
+        // The advance comes first, as the runtime requires of a nonce transaction
+        let advance = system_instruction::advance_nonce_account(&nonce, &payer.pubkey());
+        assert_eq!(
+            transaction.message.account_keys
+                [transaction.message.instructions[0].program_id_index as usize],
+            advance.program_id
+        );
+
+        sign_transaction(&mut transaction, &guardian).unwrap();
+        assert_eq!(missing_signers(&transaction), [payer.pubkey()]);
+        sign_transaction(&mut transaction, &payer).unwrap();
+        assert!(missing_signers(&transaction).is_empty());
+        assert_eq!(transaction.message.recent_blockhash, blockhash);
+        transaction.verify().unwrap();
+
+        let stranger = Keypair::new();
+        assert!(matches!(
+            sign_transaction(&mut transaction, &stranger),
+            Err(CliError::Signer(_))
+        ));
+    }
+
+    #[test]
+    fn files_round_trip_partial_signatures() {
+        let (payer, guardian) = (Keypair::new(), Keypair::new());
+        let mut transaction = built(&payer, &guardian, &Pubkey::new_unique());
+        sign_transaction(&mut transaction, &payer).unwrap();
+        let file = TxFile {
+            version: FILE_VERSION,
+            command: "emergency-withdraw".into(),
+            details: Map::new(),
+            nonce: None,
+            transaction: encode(&transaction).unwrap(),
+        };
+
+        let path = std::env::temp_dir().join(format!("tsv-tx-{}.json", payer.pubkey()));
+        file.write(&path).unwrap();
+        let read = TxFile::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.transaction().unwrap(), transaction);
+        assert_eq!(
+            missing_signers(&read.transaction().unwrap()),
+            [guardian.pubkey()]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! What a command prints: `key: value` lines for people, one JSON object with `--json`.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::CliError;
+use crate::submit::Outcome;
+
+/// Result of a command
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub command: &'static str,
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+}
+
+impl Report {
+    pub fn new(command: &'static str) -> Self {
+        Self {
+            command,
+            details: Map::new(),
+            outcome: None,
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn outcome(mut self, outcome: Outcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+
+    /// Whether the command did what it was asked, false for a failing simulation
+    pub fn succeeded(&self) -> bool {
+        self.outcome.as_ref().map_or(true, Outcome::succeeded)
+    }
+
+    pub fn print(&self, json: bool) {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(self).expect("reports serialize")
+            );
+            return;
+        }
+        for (key, value) in &self.details {
+            print_value(key, value, 0);
+        }
+        match &self.outcome {
+            Some(Outcome::Confirmed { signature }) => println!("signature: {signature}"),
+            Some(Outcome::Simulated {
+                error,
+                units_consumed,
+                logs,
+            }) => {
+                match error {
+                    Some(error) => println!("simulation: failed: {error}"),
+                    None => println!("simulation: ok"),
+                }
+                if let Some(units) = units_consumed {
+                    println!("compute units: {units}");
+                }
+                for line in logs {
+                    println!("  {line}");
+                }
+            }
+            Some(Outcome::Built {
+                output,
+                blockhash,
+                nonce,
+                missing_signers,
+            }) => {
+                println!("written: {output}");
+                match nonce {
+                    Some(nonce) => println!("durable nonce: {nonce} ({blockhash})"),
+                    None => println!("blockhash: {blockhash} (expires in about a minute)"),
+                }
+                println!("to sign: {}", missing_signers.join(", "));
+            }
+            None => {}
+        }
+    }
+}
+
+/// Print `err`, as `{"error": ...}` with `--json` so scripts always get JSON
+pub fn print_error(err: &CliError, json: bool) {
+    if json {
+        println!("{}", serde_json::json!({ "error": err.to_string() }));
+    } else {
+        eprintln!("error: {err}");
+    }
+}
+
+fn print_value(key: &str, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(fields) => {
+            println!("{indent}{key}:");
+            for (key, value) in fields {
+                print_value(key, value, depth + 1);
+            }
+        }
+        Value::String(text) => println!("{indent}{key}: {text}"),
+        Value::Null => println!("{indent}{key}: -"),
+        other => println!("{indent}{key}: {other}"),
+    }
+}
@@ -0,0 +1,19 @@
+//! Signers named on the command line: a keypair file, or a Ledger account as
+//! `usb://ledger[?key=<account>[/<change>]]`, loaded by `tsv-client`.
+
+use std::env;
+
+use solana_sdk::signature::Signer;
+
+use crate::error::CliError;
+
+/// The Solana CLI's default keypair, used without `--keypair`
+pub fn default_path() -> String {
+    let home = env::var("HOME").unwrap_or_default();
+    format!("{home}/.config/solana/id.json")
+}
+
+/// Signer at `path`, as `name` in errors and Ledger prompts
+pub fn load(path: &str, name: &str) -> Result<Box<dyn Signer>, CliError> {
+    Ok(tsv_client::load_signer(path, name)?)
+}
@@ -0,0 +1,279 @@
+//! Squads v4 multisig proposals: instructions to run as a multisig vault, wrapped in a vault
+//! transaction with its proposal (and the proposer's approval) for the other members to vote on
+//! and execute in the Squads app.
+
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::AnchorSerialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey, system_program};
+
+use crate::error::CliError;
+
+/// Squads v4 program, the same on every cluster
+pub const PROGRAM_ID: Pubkey = pubkey!("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf");
+
+/// Offset of `transaction_index` in a `Multisig` account: discriminator, create key, config
+/// authority, threshold (u16) and time lock (u32)
+const TRANSACTION_INDEX_OFFSET: usize = 8 + 32 + 32 + 2 + 4;
+
+/// Multisig vault proposals are created for
+#[derive(Clone, Copy, Debug)]
+pub struct Multisig {
+    pub address: Pubkey,
+    pub vault_index: u8,
+}
+
+impl Multisig {
+    /// Vault PDA signing the proposed instructions, the key holding the vault's role
+    pub fn vault(&self) -> Pubkey {
+        pda(&[
+            b"multisig",
+            self.address.as_ref(),
+            b"vault",
+            &[self.vault_index],
+        ])
+    }
+
+    pub fn transaction(&self, index: u64) -> Pubkey {
+        pda(&[
+            b"multisig",
+            self.address.as_ref(),
+            b"transaction",
+            &index.to_le_bytes(),
+        ])
+    }
+
+    pub fn proposal(&self, index: u64) -> Pubkey {
+        pda(&[
+            b"multisig",
+            self.address.as_ref(),
+            b"transaction",
+            &index.to_le_bytes(),
+            b"proposal",
+        ])
+    }
+
+    /// Index the next vault transaction of the multisig gets
+    pub async fn next_transaction_index(&self, rpc: &RpcClient) -> Result<u64, CliError> {
+        let account = rpc.get_account(&self.address).await?;
+        if account.owner != PROGRAM_ID {
+            return Err(CliError::InvalidArgument(format!(
+                "{} is not a Squads v4 multisig",
+                self.address
+            )));
+        }
+        transaction_index(&account.data)
+            .map(|index| index + 1)
+            .ok_or_else(|| CliError::Program(format!("can't decode multisig {}", self.address)))
+    }
+
+    /// `vault_transaction_create` of `instructions` at `index` and its `proposal_create`, then
+    /// `proposal_approve` by `member` with `approve`. `member` creates both and pays their rent
+    pub fn propose(
+        &self,
+        index: u64,
+        member: &Pubkey,
+        instructions: &[Instruction],
+        approve: bool,
+    ) -> Vec<Instruction> {
+        let transaction = self.transaction(index);
+        let proposal = self.proposal(index);
+
+        let create_transaction = squads_instruction(
+            "vault_transaction_create",
+            vec![
+                AccountMeta::new(self.address, false),
+                AccountMeta::new(transaction, false),
+                AccountMeta::new_readonly(*member, true),
+                AccountMeta::new(*member, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            VaultTransactionCreateArgs {
+                vault_index: self.vault_index,
+                ephemeral_signers: 0,
+                transaction_message: transaction_message(&self.vault(), instructions),
+                memo: None,
+            },
+        );
+        let create_proposal = squads_instruction(
+            "proposal_create",
+            vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new(proposal, false),
+                AccountMeta::new_readonly(*member, true),
+                AccountMeta::new(*member, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            ProposalCreateArgs {
+                transaction_index: index,
+                draft: false,
+            },
+        );
+
+        let mut proposed = vec![create_transaction, create_proposal];
+        if approve {
+            proposed.push(squads_instruction(
+                "proposal_approve",
+                vec![
+                    AccountMeta::new_readonly(self.address, false),
+                    AccountMeta::new(*member, true),
+                    AccountMeta::new(proposal, false),
+                ],
+                ProposalVoteArgs { memo: None },
+            ));
+        }
+        proposed
+    }
+}
+
+#[derive(AnchorSerialize)]
+struct VaultTransactionCreateArgs {
+    vault_index: u8,
+    ephemeral_signers: u8,
+    transaction_message: Vec<u8>,
+    memo: Option<String>,
+}
+
+#[derive(AnchorSerialize)]
+struct ProposalCreateArgs {
+    transaction_index: u64,
+    draft: bool,
+}
+
+#[derive(AnchorSerialize)]
+struct ProposalVoteArgs {
+    memo: Option<String>,
+}
+
+/// `transaction_index` of a `Multisig` account's data
+fn transaction_index(data: &[u8]) -> Option<u64> {
+    let bytes = data.get(TRANSACTION_INDEX_OFFSET..TRANSACTION_INDEX_OFFSET + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// `instructions` compiled with `vault` as payer into Squads' `TransactionMessage`: the
+/// legacy message's header counts and keys, with `u8` lengths except the `u16` of instruction
+/// data, and no address lookup tables
+fn transaction_message(vault: &Pubkey, instructions: &[Instruction]) -> Vec<u8> {
+    let message = Message::new(instructions, Some(vault));
+    let header = message.header;
+    let keys = message.account_keys.len() as u8;
+
+    let mut bytes = vec![
+        header.num_required_signatures,
+        header.num_required_signatures - header.num_readonly_signed_accounts,
+        keys - header.num_required_signatures - header.num_readonly_unsigned_accounts,
+        keys,
+    ];
+    for key in &message.account_keys {
+        bytes.extend_from_slice(key.as_ref());
+    }
+    bytes.push(message.instructions.len() as u8);
+    for instruction in &message.instructions {
+        bytes.push(instruction.program_id_index);
+        bytes.push(instruction.accounts.len() as u8);
+        bytes.extend_from_slice(&instruction.accounts);
+        bytes.extend_from_slice(&(instruction.data.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&instruction.data);
+    }
+    // address_table_lookups
+    bytes.push(0);
+    bytes
+}
+
+fn squads_instruction(
+    name: &str,
+    accounts: Vec<AccountMeta>,
+    args: impl AnchorSerialize,
+) -> Instruction {
+    let mut data = hash(format!("global:{name}").as_bytes()).to_bytes()[..8].to_vec();
+    args.serialize(&mut data).expect("serializing to a Vec");
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data,
+    }
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &PROGRAM_ID).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multisig() -> Multisig {
+        Multisig {
+            address: Pubkey::new_unique(),
+            vault_index: 0,
+        }
+    }
+
+    #[test]
+    fn transaction_message_puts_the_vault_first_as_the_only_signer() {
+        let vault = Pubkey::new_unique();
+        let (writable, readonly, program) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let instruction = Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(vault, true),
+                AccountMeta::new_readonly(readonly, false),
+            ],
+            data: vec![7; 3],
+        };
+
+        let bytes = transaction_message(&vault, &[instruction]);
+
+        // 1 signer (writable, as payer), 1 writable non-signer, 4 keys
+        assert_eq!(&bytes[..4], &[1, 1, 1, 4]);
+        assert_eq!(&bytes[4..36], vault.as_ref());
+        assert_eq!(&bytes[36..68], writable.as_ref());
+        let instructions = 4 + 4 * 32;
+        // 1 instruction of program 3 over keys 1, 0, 2 and 3 bytes of data
+        assert_eq!(
+            &bytes[instructions..],
+            &[1, 3, 3, 1, 0, 2, 3, 0, 7, 7, 7, 0]
+        );
+    }
+
+    #[test]
+    fn propose_creates_then_approves_the_next_transaction() {
+        let multisig = multisig();
+        let member = Pubkey::new_unique();
+        let ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new_readonly(multisig.vault(), true)],
+            data: vec![],
+        };
+
+        let proposed = multisig.propose(4, &member, &[ix.clone()], true);
+        assert_eq!(proposed.len(), 3);
+        assert!(proposed.iter().all(|ix| ix.program_id == PROGRAM_ID));
+        assert_eq!(proposed[0].accounts[1].pubkey, multisig.transaction(4));
+        assert_eq!(proposed[1].accounts[1].pubkey, multisig.proposal(4));
+        assert_eq!(&proposed[1].data[8..16], &4u64.to_le_bytes());
+        assert_eq!(proposed[2].accounts[2].pubkey, multisig.proposal(4));
+        // vault_index, ephemeral_signers, then the message's length
+        assert_eq!(&proposed[0].data[8..10], &[0, 0]);
+
+        assert_eq!(multisig.propose(4, &member, &[ix], false).len(), 2);
+    }
+
+    #[test]
+    fn reads_the_transaction_index() {
+        let mut data = vec![0; TRANSACTION_INDEX_OFFSET + 8 + 16];
+        data[TRANSACTION_INDEX_OFFSET..TRANSACTION_INDEX_OFFSET + 8]
+            .copy_from_slice(&41u64.to_le_bytes());
+        assert_eq!(transaction_index(&data), Some(41));
+        assert_eq!(transaction_index(&data[..TRANSACTION_INDEX_OFFSET]), None);
+    }
+}
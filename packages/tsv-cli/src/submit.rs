@@ -0,0 +1,92 @@
+//! Sending a command's transaction, or simulating it for `--simulate`.
+
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::signature::Signer;
+use solana_sdk::transaction::Transaction;
+
+use crate::error::CliError;
+
+/// What became of a command's transaction
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Confirmed {
+        signature: String,
+    },
+    Simulated {
+        /// Why the transaction would fail, `None` if it would succeed
+        error: Option<String>,
+        units_consumed: Option<u64>,
+        logs: Vec<String>,
+    },
+    /// Written unsigned by `tx build`, for `tx sign` and `tx broadcast`
+    Built {
+        output: String,
+        /// Blockhash the transaction is valid on: the nonce's, or a recent one expiring after
+        /// 150 blocks
+        blockhash: String,
+        nonce: Option<String>,
+        /// Keys that still have to sign, the fee payer first
+        missing_signers: Vec<String>,
+    },
+}
+
+impl Outcome {
+    pub fn succeeded(&self) -> bool {
+        !matches!(self, Outcome::Simulated { error: Some(_), .. })
+    }
+}
+
+/// Send `instructions` signed by `signers`, the first paying the fee, and wait until confirmed.
+/// With `simulate`, simulate them unsigned instead, so a dry run never prompts a Ledger
+pub async fn submit(
+    rpc: &RpcClient,
+    signers: &[&dyn Signer],
+    instructions: &[Instruction],
+    simulate: bool,
+) -> Result<Outcome, CliError> {
+    let message = Message::new(instructions, Some(&signers[0].pubkey()));
+    let mut transaction = Transaction::new_unsigned(message);
+
+    if simulate {
+        return simulate_transaction(rpc, &transaction, false, true).await;
+    }
+
+    let blockhash = rpc.get_latest_blockhash().await?;
+    transaction
+        .try_sign(signers, blockhash)
+        .map_err(|err| CliError::Signer(err.to_string()))?;
+    let signature = rpc.send_and_confirm_transaction(&transaction).await?;
+    Ok(Outcome::Confirmed {
+        signature: signature.to_string(),
+    })
+}
+
+/// Simulate `transaction`, checking its signatures with `sig_verify` or else on the latest
+/// blockhash with `replace_recent_blockhash`
+pub async fn simulate_transaction(
+    rpc: &RpcClient,
+    transaction: &Transaction,
+    sig_verify: bool,
+    replace_recent_blockhash: bool,
+) -> Result<Outcome, CliError> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify,
+        replace_recent_blockhash,
+        commitment: Some(rpc.commitment()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc
+        .simulate_transaction_with_config(transaction, config)
+        .await?
+        .value;
+    Ok(Outcome::Simulated {
+        error: result.err.map(|err| err.to_string()),
+        units_consumed: result.units_consumed,
+        logs: result.logs.unwrap_or_default(),
+    })
+}
@@ -0,0 +1,128 @@
+//! Reading and deserializing the program's accounts.
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tsv_meta_message::Domain;
+use tsv_usdc_vault::Vault;
+
+use crate::error::VaultClientError;
+use crate::pda;
+
+/// The account at `address`, deserialized after checking its discriminator
+pub async fn fetch<T: AccountDeserialize>(
+    rpc: &RpcClient,
+    address: &Pubkey,
+) -> Result<T, VaultClientError> {
+    fetch_optional(rpc, address)
+        .await?
+        .ok_or(VaultClientError::AccountNotFound(*address))
+}
+
+/// The account at `address`, or `None` while it doesn't exist
+pub async fn fetch_optional<T: AccountDeserialize>(
+    rpc: &RpcClient,
+    address: &Pubkey,
+) -> Result<Option<T>, VaultClientError> {
+    let account = rpc
+        .get_account_with_commitment(address, rpc.commitment())
+        .await?
+        .value;
+    account
+        .map(|account| {
+            T::try_deserialize(&mut account.data.as_slice()).map_err(|err| {
+                VaultClientError::Decode {
+                    address: *address,
+                    reason: err.to_string(),
+                }
+            })
+        })
+        .transpose()
+}
+
+/// Current meta-transaction nonce of `owner`, 0 while its `UserNonce` doesn't exist
+pub async fn fetch_nonce(rpc: &RpcClient, owner: &Pubkey) -> Result<u64, VaultClientError> {
+    let user_nonce: Option<tsv_usdc_vault::UserNonce> =
+        fetch_optional(rpc, &pda::user_nonce(owner)).await?;
+    Ok(user_nonce.map_or(0, |user_nonce| user_nonce.nonce))
+}
+
+/// Accounts of a vault that don't change, read once and used to build its instructions
+#[derive(Clone, Debug)]
+pub struct VaultAccounts {
+    pub address: Pubkey,
+    pub asset_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub asset_vault: Pubkey,
+    pub dead_shares_account: Pubkey,
+    /// SPL Token or Token-2022, whichever owns the vault's mints
+    pub token_program: Pubkey,
+    /// Domain every meta-transaction message of this vault is bound to
+    pub domain: Domain,
+}
+
+impl VaultAccounts {
+    /// Read the vault at `address`
+    pub async fn fetch(rpc: &RpcClient, address: &Pubkey) -> Result<Self, VaultClientError> {
+        let vault: Vault = fetch(rpc, address).await?;
+        let token_program = rpc.get_account(&vault.asset_mint).await?.owner;
+        Ok(Self::new(*address, &vault, token_program))
+    }
+
+    pub fn new(address: Pubkey, vault: &Vault, token_program: Pubkey) -> Self {
+        Self {
+            address,
+            asset_mint: vault.asset_mint,
+            share_mint: vault.share_mint,
+            asset_vault: vault.asset_vault,
+            dead_shares_account: vault.dead_shares_account,
+            token_program,
+            domain: vault.domain(address),
+        }
+    }
+
+    /// Asset associated token account of `wallet`
+    pub fn asset_account(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.asset_mint, &self.token_program)
+    }
+
+    /// Share associated token account of `wallet`
+    pub fn share_account(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.share_mint, &self.token_program)
+    }
+}
+
+/// Optional accounts of a deposit or redeem, each passed only if its PDA exists
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Optionals {
+    /// Fee exemption of the wallet paying
+    pub fee_exemption: Option<Pubkey>,
+    /// Allowlist entry of the wallet holding the shares
+    pub allowlist_entry: Option<Pubkey>,
+    /// Cost basis of the wallet holding the shares, to keep it up to date
+    pub cost_basis: Option<Pubkey>,
+}
+
+impl Optionals {
+    /// Look up which of `payer`'s and `holder`'s optional accounts exist
+    pub async fn fetch(
+        rpc: &RpcClient,
+        vault: &Pubkey,
+        payer: &Pubkey,
+        holder: &Pubkey,
+    ) -> Result<Self, VaultClientError> {
+        let addresses = [
+            pda::fee_exemption(vault, payer),
+            pda::allowlist_entry(vault, holder),
+            pda::cost_basis(vault, holder),
+        ];
+        let accounts = rpc.get_multiple_accounts(&addresses).await?;
+        let exists = |index: usize| accounts[index].is_some().then_some(addresses[index]);
+        Ok(Self {
+            fee_exemption: exists(0),
+            allowlist_entry: exists(1),
+            cost_basis: exists(2),
+        })
+    }
+}
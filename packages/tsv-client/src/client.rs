@@ -0,0 +1,303 @@
+//! High-level flows: look up the optional accounts, build the instructions, sign, send and
+//! confirm.
+
+use anchor_spl::token_interface::TokenAccount;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::from_account;
+use solana_sdk::address_lookup_table::AddressLookupTableAccount;
+use solana_sdk::clock::Clock;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::signer::signers::Signers;
+use solana_sdk::signer::SignerError;
+use solana_sdk::sysvar;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use tsv_meta_message::{MetaAction, MetaMessage};
+use tsv_usdc_vault::{FeeExemption, UserPosition, Vault};
+
+use crate::accounts::{self, Optionals, VaultAccounts};
+use crate::error::VaultClientError;
+use crate::instructions::{self, Relayer};
+use crate::transaction::{self, SendOptions};
+use crate::{lookup_table, pda, preview};
+
+pub struct VaultClient {
+    pub rpc: RpcClient,
+    pub vault: VaultAccounts,
+}
+
+impl VaultClient {
+    /// Client of the vault at `address`, reading its accounts once
+    pub async fn new(rpc: RpcClient, address: &Pubkey) -> Result<Self, VaultClientError> {
+        let vault = VaultAccounts::fetch(&rpc, address).await?;
+        Ok(Self { rpc, vault })
+    }
+
+    /// Client of the vault of `asset_mint`, or the legacy single vault without it
+    pub async fn for_asset(
+        rpc: RpcClient,
+        asset_mint: Option<&Pubkey>,
+    ) -> Result<Self, VaultClientError> {
+        let address = asset_mint.map_or_else(pda::legacy_vault, pda::vault);
+        Self::new(rpc, &address).await
+    }
+
+    /// `owner`'s position, `None` before their first deposit
+    pub async fn position(&self, owner: &Pubkey) -> Result<Option<UserPosition>, VaultClientError> {
+        accounts::fetch_optional(&self.rpc, &pda::position(&self.vault.address, owner)).await
+    }
+
+    /// Shares a deposit of `assets` would mint now, net of the deposit fee
+    pub async fn preview_deposit(&self, assets: u64) -> Result<u64, VaultClientError> {
+        let (vault, now) = self.state().await?;
+        Ok(preview::preview_deposit(&vault, assets, now)?)
+    }
+
+    /// Assets a redeem of `owner`'s `shares` would pay now, net of the redeem fee at the
+    /// vault's current utilization and the owner's cooldown and early-exit penalties
+    pub async fn preview_redeem(
+        &self,
+        owner: &Pubkey,
+        shares: u64,
+    ) -> Result<u64, VaultClientError> {
+        let (vault, now) = self.state().await?;
+        let idle_assets = self.idle_assets(&vault).await?;
+        let position = self.position(owner).await?.unwrap_or_default();
+        let fee_exemption: Option<FeeExemption> =
+            accounts::fetch_optional(&self.rpc, &pda::fee_exemption(&self.vault.address, owner))
+                .await?;
+        preview::preview_redeem(
+            &vault,
+            shares,
+            &position,
+            fee_exemption.is_some(),
+            now,
+            idle_assets,
+        )
+    }
+
+    /// USDC in the asset vault available to redeem from, as the program counts it for the
+    /// utilization-scaled redeem fee
+    pub async fn idle_assets(&self, vault: &Vault) -> Result<u64, VaultClientError> {
+        let asset_vault: TokenAccount = accounts::fetch(&self.rpc, &self.vault.asset_vault).await?;
+        Ok(vault.idle_assets(&asset_vault))
+    }
+
+    /// The vault's current state and the cluster's Unix time
+    pub async fn state(&self) -> Result<(Vault, i64), VaultClientError> {
+        let vault = accounts::fetch(&self.rpc, &self.vault.address).await?;
+        let clock: Clock = from_account(&self.rpc.get_account(&sysvar::clock::ID).await?)
+            .ok_or(VaultClientError::Clock)?;
+        Ok((vault, clock.unix_timestamp))
+    }
+
+    /// Deposit `assets` of `user`'s, minting the shares to `receiver`
+    pub async fn deposit<S: Signer + ?Sized>(
+        &self,
+        user: &S,
+        receiver: &Pubkey,
+        assets: u64,
+        min_shares_out: Option<u64>,
+    ) -> Result<Signature, VaultClientError> {
+        let optionals =
+            Optionals::fetch(&self.rpc, &self.vault.address, &user.pubkey(), receiver).await?;
+        let ix = instructions::deposit(
+            &self.vault,
+            &user.pubkey(),
+            receiver,
+            assets,
+            min_shares_out,
+            optionals,
+        );
+        self.send(user, &[ix]).await
+    }
+
+    /// Redeem `shares` of `user`'s for the vault's idle assets, paid to `user`
+    pub async fn redeem<S: Signer + ?Sized>(
+        &self,
+        user: &S,
+        shares: u64,
+        min_assets_out: Option<u64>,
+    ) -> Result<Signature, VaultClientError> {
+        let user_key = user.pubkey();
+        let optionals =
+            Optionals::fetch(&self.rpc, &self.vault.address, &user_key, &user_key).await?;
+        let ix = instructions::redeem(
+            &self.vault,
+            &user_key,
+            None,
+            shares,
+            min_assets_out,
+            optionals,
+        );
+        self.send(user, &[ix]).await
+    }
+
+    /// Message for `owner` to sign authorizing `action` until `deadline`, at their current
+    /// nonce
+    pub async fn meta_message(
+        &self,
+        owner: &Pubkey,
+        action: MetaAction,
+        deadline: i64,
+    ) -> Result<MetaMessage, VaultClientError> {
+        Ok(MetaMessage {
+            domain: self.vault.domain.clone(),
+            owner: *owner,
+            nonce: accounts::fetch_nonce(&self.rpc, owner).await?,
+            deadline,
+            action,
+        })
+    }
+
+    /// Submit the owner-signed redeem `message` as `relayer`, taking `relayer_fee` into the
+    /// relayer's associated asset account. The owner must have approved the vault as delegate
+    /// of their shares
+    pub async fn meta_redeem<S: Signer + ?Sized>(
+        &self,
+        relayer: &S,
+        message: &MetaMessage,
+        signature: &[u8; 64],
+        relayer_fee: u64,
+    ) -> Result<Signature, VaultClientError> {
+        let relayer_key = relayer.pubkey();
+        let owner = message.owner;
+        let relayer_entry = pda::relayer_entry(&self.vault.address, &relayer_key);
+        let registered = self
+            .rpc
+            .get_account_with_commitment(&relayer_entry, self.rpc.commitment())
+            .await?
+            .value
+            .is_some();
+        let relayer_accounts = Relayer {
+            relayer: relayer_key,
+            relayer_asset: self.vault.asset_account(&relayer_key),
+            relayer_entry: registered.then_some(relayer_entry),
+            relayer_fee,
+        };
+        let optionals = Optionals::fetch(&self.rpc, &self.vault.address, &owner, &owner).await?;
+        let ixs = instructions::meta_redeem(
+            &self.vault,
+            &relayer_accounts,
+            message,
+            signature,
+            optionals,
+        )?;
+        self.send(relayer, &ixs).await
+    }
+
+    /// Send `instructions` paid and signed by `payer`, and wait until confirmed
+    pub async fn send<S: Signer + ?Sized>(
+        &self,
+        payer: &S,
+        instructions: &[Instruction],
+    ) -> Result<Signature, VaultClientError> {
+        self.send_signed(&payer.pubkey(), &[&SignerRef(payer)], instructions)
+            .await
+    }
+
+    /// Send `instructions` paid by `payer` and signed by all of `signers`, `payer` included,
+    /// and wait until confirmed. For admin instructions with a co-signer, e.g. an authority
+    /// on a Ledger and the guardian of `emergency_withdraw`
+    pub async fn send_signed<T: Signers + ?Sized>(
+        &self,
+        payer: &Pubkey,
+        signers: &T,
+        instructions: &[Instruction],
+    ) -> Result<Signature, VaultClientError> {
+        let blockhash = self.rpc.get_latest_blockhash().await?;
+        let mut transaction = Transaction::new_with_payer(instructions, Some(payer));
+        transaction
+            .try_sign(signers, blockhash)
+            .map_err(|err| VaultClientError::Signer(err.to_string()))?;
+        Ok(self.rpc.send_and_confirm_transaction(&transaction).await?)
+    }
+
+    /// Send `instructions` as a versioned transaction paid by `payer` and signed by all of
+    /// `signers`, with the compute budget, priority fee and lookup tables of `options`, and wait
+    /// until confirmed
+    pub async fn send_versioned<T: Signers + ?Sized>(
+        &self,
+        payer: &Pubkey,
+        signers: &T,
+        instructions: &[Instruction],
+        options: &SendOptions,
+    ) -> Result<Signature, VaultClientError> {
+        let priority_fee = match &options.priority_fee {
+            Some(fee) => Some(
+                transaction::estimate_priority_fee(
+                    &self.rpc,
+                    &transaction::writable_accounts(instructions),
+                    fee,
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        let mut all = transaction::compute_budget(options.compute_unit_limit, priority_fee);
+        all.extend_from_slice(instructions);
+
+        let blockhash = self.rpc.get_latest_blockhash().await?;
+        let message = transaction::compile(payer, &all, &options.lookup_tables, blockhash)?;
+        let transaction = VersionedTransaction::try_new(message, signers)
+            .map_err(|err| VaultClientError::Signer(err.to_string()))?;
+        Ok(self.rpc.send_and_confirm_transaction(&transaction).await?)
+    }
+
+    /// Make `table`, or a new table without it, hold `addresses` (e.g.
+    /// `lookup_table::vault_addresses`), with `authority` owning and paying for it. Returns the
+    /// table's address. Addresses added are only looked up from the next slot on
+    pub async fn sync_lookup_table<S: Signer + ?Sized>(
+        &self,
+        authority: &S,
+        table: Option<Pubkey>,
+        addresses: &[Pubkey],
+    ) -> Result<Pubkey, VaultClientError> {
+        let authority_key = authority.pubkey();
+        let (table, missing) = match table {
+            Some(table) => {
+                let current = lookup_table::fetch(&self.rpc, &table).await?;
+                (table, lookup_table::missing(&current, addresses))
+            }
+            None => {
+                // Finalized, so the slot is in every node's slot hashes when the table is created
+                let slot = self
+                    .rpc
+                    .get_slot_with_commitment(CommitmentConfig::finalized())
+                    .await?;
+                let (create, table) = lookup_table::create(&authority_key, &authority_key, slot);
+                self.send(authority, &[create]).await?;
+                let empty = AddressLookupTableAccount {
+                    key: table,
+                    addresses: Vec::new(),
+                };
+                (table, lookup_table::missing(&empty, addresses))
+            }
+        };
+
+        for extend in lookup_table::extend(&table, &authority_key, &authority_key, &missing) {
+            self.send(authority, &[extend]).await?;
+        }
+        Ok(table)
+    }
+}
+
+/// A borrowed signer, sized even when the one it borrows is a `dyn Signer` (e.g. a Ledger's),
+/// since `Signers` is only implemented for slices of sized ones
+struct SignerRef<'a, S: ?Sized>(&'a S);
+
+impl<S: Signer + ?Sized> Signer for SignerRef<'_, S> {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        self.0.try_pubkey()
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        self.0.try_sign_message(message)
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.0.is_interactive()
+    }
+}
@@ -0,0 +1,38 @@
+use solana_client::client_error::ClientError;
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+use tsv_math::MathError;
+
+#[derive(Debug, Error)]
+pub enum VaultClientError {
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("can't decode account {address}: {reason}")]
+    Decode { address: Pubkey, reason: String },
+    #[error("meta message authorizes a {0}, not a redeem")]
+    WrongAction(&'static str),
+    #[error("can't compile the transaction: {0}")]
+    Compile(String),
+    #[error("signer error: {0}")]
+    Signer(String),
+    #[error("preview failed: {0}")]
+    Math(MathError),
+    #[error("redemption within the deposit cooldown window")]
+    CooldownActive,
+    #[error("can't decode the clock sysvar")]
+    Clock,
+}
+
+impl From<ClientError> for VaultClientError {
+    fn from(err: ClientError) -> Self {
+        VaultClientError::Rpc(Box::new(err))
+    }
+}
+
+impl From<MathError> for VaultClientError {
+    fn from(err: MathError) -> Self {
+        VaultClientError::Math(err)
+    }
+}
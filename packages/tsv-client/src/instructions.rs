@@ -0,0 +1,660 @@
+//! Instruction builders: accounts in the program's order and Anchor-encoded arguments, with
+//! optional accounts left out as the program id.
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{ed25519_program, system_program, sysvar};
+use tsv_meta_message::{MetaAction, MetaMessage};
+use tsv_usdc_vault::ActionAccount;
+
+use crate::accounts::{Optionals, VaultAccounts};
+use crate::error::VaultClientError;
+use crate::pda;
+
+/// Size of the Ed25519 instruction header (signature count + padding) and its one offsets entry
+const ED25519_HEADER_LEN: u16 = 2 + 14;
+
+/// Offsets of an Ed25519 instruction pointing into its own data
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Relayer of a meta-transaction and what it charges
+#[derive(Clone, Copy, Debug)]
+pub struct Relayer {
+    pub relayer: Pubkey,
+    /// Asset account receiving the relayer fee
+    pub relayer_asset: Pubkey,
+    /// Registry PDA of the relayer, if registered
+    pub relayer_entry: Option<Pubkey>,
+    pub relayer_fee: u64,
+}
+
+/// Strategy a redeem may deallocate from, in the vault's strategy-item layout
+#[derive(Clone, Debug)]
+pub struct StrategyItem {
+    pub adapter_program: Pubkey,
+    /// Reserve the strategy lends into, as recorded in its `Strategy`
+    pub reserve: Pubkey,
+    /// The adapter's own accounts, after the item's `Strategy`, adapter program and reserve
+    pub accounts: Vec<AccountMeta>,
+}
+
+impl StrategyItem {
+    /// Remaining accounts of this item for the vault at `vault`
+    fn metas(&self, vault: &Pubkey) -> Vec<AccountMeta> {
+        let mut metas = vec![
+            AccountMeta::new(pda::strategy(vault, &self.adapter_program), false),
+            AccountMeta::new_readonly(self.adapter_program, false),
+            AccountMeta::new(self.reserve, false),
+        ];
+        metas.extend_from_slice(&self.accounts);
+        metas
+    }
+}
+
+/// Accounts of a vault created by `initialize`, outside the registry
+#[derive(Clone, Copy, Debug)]
+pub struct NewVault {
+    pub authority: Pubkey,
+    pub asset_mint: Pubkey,
+    /// Created beforehand, with the vault PDA as mint authority
+    pub share_mint: Pubkey,
+    /// Address of a fresh keypair, signing `initialize`, the asset account is created at
+    pub asset_vault: Pubkey,
+    /// Asset account `emergency_withdraw` pays, the only one it can
+    pub recovery_account: Pubkey,
+    /// SPL Token or Token-2022, whichever owns both mints
+    pub token_program: Pubkey,
+}
+
+/// `initialize` of the vault of `accounts.asset_mint` with the arguments in `args`
+pub fn initialize(
+    accounts: &NewVault,
+    args: tsv_usdc_vault::instruction::Initialize,
+) -> Instruction {
+    let vault = pda::vault(&accounts.asset_mint);
+    let metas = tsv_usdc_vault::accounts::Initialize {
+        vault,
+        authority: accounts.authority,
+        asset_mint: accounts.asset_mint,
+        share_mint: accounts.share_mint,
+        asset_vault: accounts.asset_vault,
+        dead_shares_account: pda::dead_shares(&vault),
+        recovery_account: accounts.recovery_account,
+        share_metadata: pda::share_metadata(&accounts.share_mint),
+        vault_metadata: pda::vault_metadata(&vault),
+        token_program: accounts.token_program,
+        token_metadata_program: anchor_spl::metadata::ID,
+        system_program: system_program::ID,
+        rent: sysvar::rent::ID,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: metas.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+/// `deposit` of `assets` from `user`, minting shares to `receiver`. `optionals` are
+/// `user`'s fee exemption and `receiver`'s allowlist entry and cost basis
+pub fn deposit(
+    vault: &VaultAccounts,
+    user: &Pubkey,
+    receiver: &Pubkey,
+    assets: u64,
+    min_shares_out: Option<u64>,
+    optionals: Optionals,
+) -> Instruction {
+    let accounts = tsv_usdc_vault::accounts::Deposit {
+        vault: vault.address,
+        user: *user,
+        user_asset: vault.asset_account(user),
+        receiver: *receiver,
+        share_mint: vault.share_mint,
+        receiver_shares: vault.share_account(receiver),
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        dead_shares_account: vault.dead_shares_account,
+        receiver_position: pda::position(&vault.address, receiver),
+        fee_exemption: optionals.fee_exemption,
+        allowlist_entry: optionals.allowlist_entry,
+        cost_basis: optionals.cost_basis,
+        user_blocklist: pda::blocklist_entry(&vault.address, user),
+        receiver_blocklist: pda::blocklist_entry(&vault.address, receiver),
+        token_program: vault.token_program,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::Deposit {
+        assets,
+        min_shares_out,
+    };
+
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// `redeem` of `user`'s `shares`, paying `user`'s asset account or `receiver`'s. Paid from idle
+/// assets only: no strategy accounts are passed, so the vault fails the redeem rather than
+/// deallocating
+pub fn redeem(
+    vault: &VaultAccounts,
+    user: &Pubkey,
+    receiver: Option<&Pubkey>,
+    shares: u64,
+    min_assets_out: Option<u64>,
+    optionals: Optionals,
+) -> Instruction {
+    redeem_with_strategies(
+        vault,
+        user,
+        receiver,
+        shares,
+        min_assets_out,
+        optionals,
+        &[],
+    )
+}
+
+/// `redeem` that deallocates from `strategies`, in order, when idle assets fall short. With a
+/// few strategies the accounts outgrow a legacy transaction; send it through a lookup table,
+/// see `lookup_table`
+pub fn redeem_with_strategies(
+    vault: &VaultAccounts,
+    user: &Pubkey,
+    receiver: Option<&Pubkey>,
+    shares: u64,
+    min_assets_out: Option<u64>,
+    optionals: Optionals,
+    strategies: &[StrategyItem],
+) -> Instruction {
+    let accounts = tsv_usdc_vault::accounts::Redeem {
+        vault: vault.address,
+        user: *user,
+        asset_mint: vault.asset_mint,
+        user_asset: vault.asset_account(user),
+        receiver_asset: receiver.map(|receiver| vault.asset_account(receiver)),
+        user_shares: vault.share_account(user),
+        asset_vault: vault.asset_vault,
+        share_mint: vault.share_mint,
+        user_position: pda::position(&vault.address, user),
+        fee_exemption: optionals.fee_exemption,
+        allowlist_entry: optionals.allowlist_entry,
+        cost_basis: optionals.cost_basis,
+        user_blocklist: pda::blocklist_entry(&vault.address, user),
+        receiver_blocklist: receiver.map(|receiver| pda::blocklist_entry(&vault.address, receiver)),
+        token_program: vault.token_program,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::Redeem {
+        shares,
+        min_assets_out,
+        adapter_accounts: strategies
+            .iter()
+            .map(|strategy| strategy.accounts.len() as u8)
+            .collect(),
+    };
+
+    let mut metas = accounts.to_account_metas(None);
+    for strategy in strategies {
+        metas.extend(strategy.metas(&vault.address));
+    }
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: metas,
+        data: data.data(),
+    }
+}
+
+/// `meta_redeem` of a redeem `message` the owner signed, after the Ed25519 instruction
+/// verifying `signature`. `optionals` are the owner's; the cost basis isn't tracked by meta
+/// redeems. No strategies are passed, so the vault pays it out of idle USDC
+pub fn meta_redeem(
+    vault: &VaultAccounts,
+    relayer: &Relayer,
+    message: &MetaMessage,
+    signature: &[u8; 64],
+    optionals: Optionals,
+) -> Result<Vec<Instruction>, VaultClientError> {
+    let MetaAction::Redeem {
+        shares,
+        receiver,
+        max_relayer_fee,
+    } = message.action
+    else {
+        return Err(VaultClientError::WrongAction("deposit"));
+    };
+    let owner = message.owner;
+    let accounts = tsv_usdc_vault::accounts::MetaRedeem {
+        vault: vault.address,
+        owner,
+        owner_shares: vault.share_account(&owner),
+        receiver_asset: vault.asset_account(&receiver),
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        share_mint: vault.share_mint,
+        owner_position: pda::position(&vault.address, &owner),
+        fee_exemption: optionals.fee_exemption,
+        allowlist_entry: optionals.allowlist_entry,
+        evm_owner: None,
+        relayer: relayer.relayer,
+        relayer_asset: relayer.relayer_asset,
+        relayer_entry: relayer.relayer_entry,
+        owner_blocklist: pda::blocklist_entry(&vault.address, &owner),
+        relayer_blocklist: pda::blocklist_entry(&vault.address, &relayer.relayer),
+        receiver_blocklist: pda::blocklist_entry(&vault.address, &receiver),
+        user_nonce: pda::user_nonce(&owner),
+        instructions: sysvar::instructions::ID,
+        token_program: vault.token_program,
+        system_program: system_program::ID,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::MetaRedeem {
+        shares,
+        receiver,
+        deadline: message.deadline,
+        max_relayer_fee,
+        relayer_fee: relayer.relayer_fee,
+        signature: *signature,
+        adapter_accounts: Vec::new(),
+    };
+
+    Ok(vec![
+        ed25519_verify(&owner, signature, &message.signing_bytes()),
+        Instruction {
+            program_id: tsv_usdc_vault::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        },
+    ])
+}
+
+/// `queue_fee_update` to `deposit_fee_bps` and `redeem_fee_bps`, applied by
+/// `execute_fee_update` once the vault's fee timelock has passed
+pub fn queue_fee_update(
+    vault: &Pubkey,
+    fee_manager: &Pubkey,
+    deposit_fee_bps: u16,
+    redeem_fee_bps: u16,
+) -> Instruction {
+    let data = tsv_usdc_vault::instruction::QueueFeeUpdate {
+        new_deposit_fee_bps: deposit_fee_bps,
+        new_redeem_fee_bps: redeem_fee_bps,
+    };
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: update_fee_accounts(vault, fee_manager),
+        data: data.data(),
+    }
+}
+
+/// `execute_fee_update` of the queued fees
+pub fn execute_fee_update(vault: &Pubkey, fee_manager: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: update_fee_accounts(vault, fee_manager),
+        data: tsv_usdc_vault::instruction::ExecuteFeeUpdate {}.data(),
+    }
+}
+
+fn update_fee_accounts(vault: &Pubkey, fee_manager: &Pubkey) -> Vec<AccountMeta> {
+    tsv_usdc_vault::accounts::UpdateFee {
+        vault: *vault,
+        fee_manager: *fee_manager,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    }
+    .to_account_metas(None)
+}
+
+/// `set_pause_flags` of deposits, redeems and meta-transactions
+pub fn set_pause_flags(
+    vault: &Pubkey,
+    pauser: &Pubkey,
+    paused_deposits: bool,
+    paused_redeems: bool,
+    paused_meta: bool,
+) -> Instruction {
+    let accounts = tsv_usdc_vault::accounts::SetPauseFlags {
+        vault: *vault,
+        pauser: *pauser,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::SetPauseFlags {
+        paused_deposits,
+        paused_redeems,
+        paused_meta,
+    };
+
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// `emergency_withdraw` of `amount` to the vault's `recovery_account`, co-signed by `guardian`
+/// unless a requested escape hatch has matured. Signed by the vault's timelock, so it only
+/// runs as a queued action, see `queue_action`
+pub fn emergency_withdraw(
+    vault: &VaultAccounts,
+    recovery_account: &Pubkey,
+    guardian: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let accounts = tsv_usdc_vault::accounts::EmergencyWithdraw {
+        vault: vault.address,
+        timelock: pda::timelock(&vault.address),
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        recovery_account: *recovery_account,
+        guardian: guardian.copied(),
+        token_program: vault.token_program,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::EmergencyWithdraw { amount };
+
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// `queue_action` by the timelock `admin` of `ix` as action `id`, the timelock's
+/// `next_action_id`, for the timelock of the vault at `vault` to sign once its delay has passed
+pub fn queue_action(vault: &Pubkey, admin: &Pubkey, id: u64, ix: &Instruction) -> Instruction {
+    let timelock = pda::timelock(vault);
+    let accounts = tsv_usdc_vault::accounts::QueueAction {
+        timelock,
+        admin: *admin,
+        action: pda::timelock_action(&timelock, id),
+        system_program: system_program::ID,
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::QueueAction {
+        action_accounts: ix
+            .accounts
+            .iter()
+            .map(|meta| ActionAccount {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: ix.data.clone(),
+    };
+
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// `execute_action` by the timelock `admin` of action `id` queued with `action_accounts`. Its
+/// signers other than the timelock, e.g. a guardian, must sign this instruction too
+pub fn execute_action(
+    vault: &Pubkey,
+    admin: &Pubkey,
+    id: u64,
+    action_accounts: &[ActionAccount],
+) -> Instruction {
+    let timelock = pda::timelock(vault);
+    let accounts = tsv_usdc_vault::accounts::ExecuteAction {
+        timelock,
+        admin: *admin,
+        action: pda::timelock_action(&timelock, id),
+        event_authority: pda::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(action_accounts.iter().map(|account| AccountMeta {
+        pubkey: account.pubkey,
+        is_signer: account.is_signer && account.pubkey != timelock,
+        is_writable: account.is_writable,
+    }));
+
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: metas,
+        data: tsv_usdc_vault::instruction::ExecuteAction {}.data(),
+    }
+}
+
+/// Ed25519 program instruction verifying `signature` by `signer` over `message`, in the
+/// single-signature layout the vault accepts: offsets, public key, signature, then message
+pub fn ed25519_verify(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let public_key_offset = ED25519_HEADER_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset,
+        CURRENT_INSTRUCTION,
+        public_key_offset,
+        CURRENT_INSTRUCTION,
+        message_offset,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::Discriminator;
+    use tsv_meta_message::{Domain, SCHEMA_VERSION};
+
+    use super::*;
+
+    fn vault() -> VaultAccounts {
+        let address = Pubkey::new_unique();
+        VaultAccounts {
+            address,
+            asset_mint: Pubkey::new_unique(),
+            share_mint: Pubkey::new_unique(),
+            asset_vault: Pubkey::new_unique(),
+            dead_shares_account: Pubkey::new_unique(),
+            token_program: anchor_spl::token::ID,
+            domain: Domain {
+                schema_version: SCHEMA_VERSION,
+                genesis_hash: [1; 32],
+                program_id: tsv_usdc_vault::ID,
+                vault: address,
+            },
+        }
+    }
+
+    fn message(vault: &VaultAccounts, action: MetaAction) -> MetaMessage {
+        MetaMessage {
+            domain: vault.domain.clone(),
+            owner: Pubkey::new_unique(),
+            nonce: 0,
+            deadline: 1_000,
+            action,
+        }
+    }
+
+    #[test]
+    fn deposit_encodes_its_arguments_and_leaves_absent_optionals_as_the_program() {
+        let vault = vault();
+        let user = Pubkey::new_unique();
+        let exemption = pda::fee_exemption(&vault.address, &user);
+        let optionals = Optionals {
+            fee_exemption: Some(exemption),
+            ..Optionals::default()
+        };
+
+        let ix = deposit(&vault, &user, &user, 1_000_000, Some(990_000), optionals);
+
+        assert_eq!(
+            &ix.data[..8],
+            &tsv_usdc_vault::instruction::Deposit::DISCRIMINATOR
+        );
+        // assets, then `Some(min_shares_out)`
+        assert_eq!(&ix.data[8..16], &1_000_000u64.to_le_bytes());
+        assert_eq!(ix.data[16], 1);
+        assert_eq!(ix.accounts[10].pubkey, exemption);
+        assert_eq!(ix.accounts[11].pubkey, tsv_usdc_vault::ID);
+        assert_eq!(ix.accounts[12].pubkey, tsv_usdc_vault::ID);
+        assert!(ix.accounts[1].is_signer);
+    }
+
+    #[test]
+    fn meta_redeem_verifies_the_owner_signature_first() {
+        let vault = vault();
+        let relayer = Relayer {
+            relayer: Pubkey::new_unique(),
+            relayer_asset: Pubkey::new_unique(),
+            relayer_entry: None,
+            relayer_fee: 10,
+        };
+        let redeem = message(
+            &vault,
+            MetaAction::Redeem {
+                shares: 500,
+                receiver: Pubkey::new_unique(),
+                max_relayer_fee: 20,
+            },
+        );
+        let deposit = message(
+            &vault,
+            MetaAction::Deposit {
+                assets: 500,
+                receiver: Pubkey::new_unique(),
+            },
+        );
+
+        let ixs = meta_redeem(&vault, &relayer, &redeem, &[9; 64], Optionals::default()).unwrap();
+
+        assert_eq!(ixs.len(), 2);
+        assert_eq!(ixs[0].program_id, ed25519_program::ID);
+        assert_eq!(&ixs[0].data[16..48], redeem.owner.as_ref());
+        assert_eq!(&ixs[0].data[112..], &redeem.signing_bytes()[..]);
+        assert_eq!(
+            &ixs[1].data[..8],
+            &tsv_usdc_vault::instruction::MetaRedeem::DISCRIMINATOR
+        );
+        assert!(meta_redeem(&vault, &relayer, &deposit, &[9; 64], Optionals::default()).is_err());
+    }
+
+    #[test]
+    fn redeem_appends_strategy_items_after_its_accounts() {
+        let vault = vault();
+        let user = Pubkey::new_unique();
+        let adapter_account = AccountMeta::new(Pubkey::new_unique(), false);
+        let strategy = StrategyItem {
+            adapter_program: Pubkey::new_unique(),
+            reserve: Pubkey::new_unique(),
+            accounts: vec![adapter_account.clone()],
+        };
+
+        let plain = redeem(&vault, &user, None, 5, None, Optionals::default());
+        let ix = redeem_with_strategies(
+            &vault,
+            &user,
+            None,
+            5,
+            None,
+            Optionals::default(),
+            std::slice::from_ref(&strategy),
+        );
+
+        let base = plain.accounts.len();
+        assert_eq!(ix.accounts.len(), base + 4);
+        assert_eq!(
+            ix.accounts[base].pubkey,
+            pda::strategy(&vault.address, &strategy.adapter_program)
+        );
+        assert_eq!(ix.accounts[base + 1].pubkey, strategy.adapter_program);
+        assert_eq!(ix.accounts[base + 2].pubkey, strategy.reserve);
+        assert_eq!(ix.accounts[base + 3], adapter_account);
+        // shares, `None`, then `adapter_accounts` as a one-entry vec
+        assert_eq!(&ix.data[8..16], &5u64.to_le_bytes());
+        assert_eq!(&ix.data[17..], &[1, 0, 0, 0, 1]);
+        assert_eq!(&plain.data[17..], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn emergency_withdraw_marks_the_guardian_as_a_signer_when_given() {
+        let vault = vault();
+        let (recovery, guardian) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let ix = emergency_withdraw(&vault, &recovery, Some(&guardian), 5);
+        assert_eq!(ix.accounts[1].pubkey, pda::timelock(&vault.address));
+        assert_eq!(ix.accounts[5].pubkey, guardian);
+        assert!(ix.accounts[5].is_signer);
+        assert_eq!(&ix.data[8..], &5u64.to_le_bytes());
+
+        let ix = emergency_withdraw(&vault, &recovery, None, 5);
+        assert_eq!(ix.accounts[5].pubkey, tsv_usdc_vault::ID);
+        assert!(!ix.accounts[5].is_signer);
+    }
+
+    #[test]
+    fn execute_action_leaves_the_timelock_signature_to_the_program() {
+        let vault = vault();
+        let (admin, guardian) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let withdraw = emergency_withdraw(&vault, &Pubkey::new_unique(), Some(&guardian), 5);
+
+        let queue = queue_action(&vault.address, &admin, 3, &withdraw);
+        let timelock = pda::timelock(&vault.address);
+        assert_eq!(queue.accounts[2].pubkey, pda::timelock_action(&timelock, 3));
+
+        let action_accounts: Vec<ActionAccount> = withdraw
+            .accounts
+            .iter()
+            .map(|meta| ActionAccount {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect();
+        let execute = execute_action(&vault.address, &admin, 3, &action_accounts);
+        let remaining = &execute.accounts[5..];
+        assert_eq!(remaining.len(), withdraw.accounts.len());
+        assert_eq!(remaining[1].pubkey, timelock);
+        assert!(!remaining[1].is_signer);
+        assert_eq!(remaining[5].pubkey, guardian);
+        assert!(remaining[5].is_signer);
+    }
+
+    #[test]
+    fn ed25519_verify_layout() {
+        let signer = Pubkey::new_unique();
+        let ix = ed25519_verify(&signer, &[9; 64], b"message");
+        assert_eq!(ix.program_id, ed25519_program::ID);
+        assert_eq!(&ix.data[..2], &[1, 0]);
+        assert_eq!(&ix.data[16..48], signer.as_ref());
+        assert_eq!(&ix.data[48..112], &[9; 64]);
+        assert_eq!(&ix.data[112..], b"message");
+        assert_eq!(u16::from_le_bytes([ix.data[12], ix.data[13]]), 7);
+    }
+}
@@ -0,0 +1,33 @@
+//! Typed client of the Talken Stable Vault program, for backend services that deposit, redeem
+//! or relay meta-transactions without hand-rolling accounts and Anchor encoding.
+//!
+//! - `pda`: addresses of the program's PDAs
+//! - `fetch`, `VaultAccounts`: reading its accounts, and the static accounts of a vault
+//! - `instructions`: instruction builders, accounts and arguments typed by the program crate,
+//!   for users' flows and the admin ones of `tsv-cli`
+//! - `preview`: deposit and redeem previews with the program's own math, computed locally
+//! - `load_signer`: keypair files and, with the `ledger` feature, Ledger accounts
+//! - `lookup_table`, `transaction`: lookup tables of a vault's accounts, priority fees from
+//!   recent fees and versioned messages, for instructions with many accounts
+//! - `VaultClient`: deposit, redeem, meta-redeem and preview flows over `solana-client`
+
+mod accounts;
+mod client;
+mod error;
+pub mod instructions;
+pub mod lookup_table;
+pub mod pda;
+pub mod preview;
+mod signer;
+pub mod transaction;
+
+pub use crate::accounts::{fetch, fetch_nonce, fetch_optional, Optionals, VaultAccounts};
+pub use crate::client::VaultClient;
+pub use crate::error::VaultClientError;
+pub use crate::preview::{preview_deposit, preview_redeem};
+#[cfg(feature = "ledger")]
+pub use crate::signer::ledger;
+pub use crate::signer::load_signer;
+pub use crate::transaction::{PriorityFee, SendOptions};
+pub use tsv_meta_message::{MetaAction, MetaMessage};
+pub use tsv_usdc_vault::{UserPosition, Vault};
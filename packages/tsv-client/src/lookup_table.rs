@@ -0,0 +1,160 @@
+//! Address lookup tables holding a vault's accounts, so instructions with many of them, such
+//! as redeems deallocating from strategies, fit in a versioned transaction.
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::address_lookup_table::instruction::{create_lookup_table, extend_lookup_table};
+use solana_sdk::address_lookup_table::state::AddressLookupTable;
+use solana_sdk::address_lookup_table::AddressLookupTableAccount;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_program, sysvar};
+
+use crate::accounts::VaultAccounts;
+use crate::error::VaultClientError;
+use crate::instructions::StrategyItem;
+use crate::pda;
+
+/// Addresses one `extend` instruction adds, small enough for its transaction with two signers
+pub const MAX_EXTEND_ADDRESSES: usize = 20;
+
+/// Accounts every user instruction of `vault` passes, and those of its `strategies`
+pub fn vault_addresses(vault: &VaultAccounts, strategies: &[StrategyItem]) -> Vec<Pubkey> {
+    let mut addresses = vec![
+        vault.address,
+        vault.asset_mint,
+        vault.share_mint,
+        vault.asset_vault,
+        vault.dead_shares_account,
+        vault.token_program,
+        anchor_spl::associated_token::ID,
+        system_program::ID,
+        sysvar::instructions::ID,
+        pda::event_authority(),
+    ];
+    for strategy in strategies {
+        addresses.push(pda::strategy(&vault.address, &strategy.adapter_program));
+        addresses.push(strategy.adapter_program);
+        addresses.push(strategy.reserve);
+        addresses.extend(strategy.accounts.iter().map(|meta| meta.pubkey));
+    }
+    dedup(addresses)
+}
+
+/// Lookup table owned by `authority`, at the address derived from `recent_slot`, which must
+/// still be in the cluster's slot hashes
+pub fn create(authority: &Pubkey, payer: &Pubkey, recent_slot: u64) -> (Instruction, Pubkey) {
+    create_lookup_table(*authority, *payer, recent_slot)
+}
+
+/// Instructions adding `addresses` to `table`, `MAX_EXTEND_ADDRESSES` each, one per
+/// transaction
+pub fn extend(
+    table: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Vec<Instruction> {
+    addresses
+        .chunks(MAX_EXTEND_ADDRESSES)
+        .map(|chunk| extend_lookup_table(*table, *authority, Some(*payer), chunk.to_vec()))
+        .collect()
+}
+
+/// The lookup table at `address`, ready to compile messages against
+pub async fn fetch(
+    rpc: &RpcClient,
+    address: &Pubkey,
+) -> Result<AddressLookupTableAccount, VaultClientError> {
+    let account = rpc
+        .get_account_with_commitment(address, rpc.commitment())
+        .await?
+        .value
+        .ok_or(VaultClientError::AccountNotFound(*address))?;
+    let table =
+        AddressLookupTable::deserialize(&account.data).map_err(|err| VaultClientError::Decode {
+            address: *address,
+            reason: err.to_string(),
+        })?;
+    Ok(AddressLookupTableAccount {
+        key: *address,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+/// `addresses` not in `table` yet, each once
+pub fn missing(table: &AddressLookupTableAccount, addresses: &[Pubkey]) -> Vec<Pubkey> {
+    dedup(
+        addresses
+            .iter()
+            .filter(|address| !table.addresses.contains(address))
+            .copied()
+            .collect(),
+    )
+}
+
+/// `addresses` with repeats dropped, in first-seen order
+fn dedup(addresses: Vec<Pubkey>) -> Vec<Pubkey> {
+    let mut unique = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        if !unique.contains(&address) {
+            unique.push(address);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+    use tsv_meta_message::{Domain, SCHEMA_VERSION};
+
+    use super::*;
+
+    #[test]
+    fn vault_addresses_cover_strategies_once() {
+        let address = Pubkey::new_unique();
+        let vault = VaultAccounts {
+            address,
+            asset_mint: Pubkey::new_unique(),
+            share_mint: Pubkey::new_unique(),
+            asset_vault: Pubkey::new_unique(),
+            dead_shares_account: Pubkey::new_unique(),
+            token_program: anchor_spl::token::ID,
+            domain: Domain {
+                schema_version: SCHEMA_VERSION,
+                genesis_hash: [1; 32],
+                program_id: tsv_usdc_vault::ID,
+                vault: address,
+            },
+        };
+        // Two strategies sharing a lending market
+        let market = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+        let strategies: Vec<StrategyItem> = (0..2)
+            .map(|_| StrategyItem {
+                adapter_program: Pubkey::new_unique(),
+                reserve: Pubkey::new_unique(),
+                accounts: vec![market.clone()],
+            })
+            .collect();
+
+        let addresses = vault_addresses(&vault, &strategies);
+        assert_eq!(addresses.len(), 10 + 2 * 3 + 1);
+        assert!(addresses.contains(&pda::strategy(&address, &strategies[1].adapter_program)));
+
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: addresses[..12].to_vec(),
+        };
+        assert_eq!(missing(&table, &addresses), &addresses[12..]);
+    }
+
+    #[test]
+    fn extend_splits_addresses() {
+        let addresses: Vec<Pubkey> = (0..MAX_EXTEND_ADDRESSES + 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        let (table, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(extend(&table, &authority, &authority, &addresses).len(), 2);
+        assert!(extend(&table, &authority, &authority, &[]).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+//! Program-derived addresses of the vault program, mirroring its seeds.
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Vault of `asset_mint`
+pub fn vault(asset_mint: &Pubkey) -> Pubkey {
+    pda(&[b"vault", asset_mint.as_ref()])
+}
+
+/// The single vault deployed before vaults were keyed by asset mint
+pub fn legacy_vault() -> Pubkey {
+    pda(&[b"vault"])
+}
+
+pub fn registry() -> Pubkey {
+    pda(&[b"registry"])
+}
+
+/// Token account holding the shares locked by the first deposit
+pub fn dead_shares(vault: &Pubkey) -> Pubkey {
+    pda(&[b"dead_shares", vault.as_ref()])
+}
+
+pub fn vault_metadata(vault: &Pubkey) -> Pubkey {
+    pda(&[b"vault_metadata", vault.as_ref()])
+}
+
+/// Metaplex metadata of `share_mint`, owned by the token metadata program
+pub fn share_metadata(share_mint: &Pubkey) -> Pubkey {
+    let program = anchor_spl::metadata::ID;
+    Pubkey::find_program_address(
+        &[b"metadata", program.as_ref(), share_mint.as_ref()],
+        &program,
+    )
+    .0
+}
+
+pub fn position(vault: &Pubkey, owner: &Pubkey) -> Pubkey {
+    pda(&[b"position", vault.as_ref(), owner.as_ref()])
+}
+
+pub fn fee_exemption(vault: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    pda(&[b"fee_exempt", vault.as_ref(), wallet.as_ref()])
+}
+
+pub fn allowlist_entry(vault: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    pda(&[b"allowlist", vault.as_ref(), wallet.as_ref()])
+}
+
+pub fn blocklist_entry(vault: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    pda(&[b"blocklist", vault.as_ref(), wallet.as_ref()])
+}
+
+pub fn cost_basis(vault: &Pubkey, owner: &Pubkey) -> Pubkey {
+    pda(&[b"cost_basis", vault.as_ref(), owner.as_ref()])
+}
+
+pub fn evm_owner(vault: &Pubkey, owner: &Pubkey) -> Pubkey {
+    pda(&[b"evm_owner", vault.as_ref(), owner.as_ref()])
+}
+
+pub fn relayer_entry(vault: &Pubkey, relayer: &Pubkey) -> Pubkey {
+    pda(&[b"relayer", vault.as_ref(), relayer.as_ref()])
+}
+
+/// `Strategy` of the vault at `vault` lending through `adapter_program`
+pub fn strategy(vault: &Pubkey, adapter_program: &Pubkey) -> Pubkey {
+    pda(&[b"strategy", vault.as_ref(), adapter_program.as_ref()])
+}
+
+/// Timelock controller of the vault at `vault`, the signer of its queued actions
+pub fn timelock(vault: &Pubkey) -> Pubkey {
+    pda(&[b"timelock", vault.as_ref()])
+}
+
+/// Action `id` queued on `timelock`
+pub fn timelock_action(timelock: &Pubkey, id: u64) -> Pubkey {
+    pda(&[b"action", timelock.as_ref(), &id.to_le_bytes()])
+}
+
+/// Meta-transaction nonce of `owner`, shared by every vault
+pub fn user_nonce(owner: &Pubkey) -> Pubkey {
+    pda(&[b"nonce", owner.as_ref()])
+}
+
+/// PDA signing the program's self-CPI events, an account of every instruction that emits one
+pub fn event_authority() -> Pubkey {
+    pda(&[b"__event_authority"])
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0
+}
@@ -0,0 +1,278 @@
+//! Previews of deposits and redeems computed locally from a fetched `Vault`, with the
+//! program's own `tsv-math` and rounding so they match what its preview instructions return.
+
+use tsv_math::{MathError, Rounding};
+use tsv_usdc_vault::{UserPosition, Vault};
+
+use crate::error::VaultClientError;
+
+/// Shares `deposit(assets)` mints at `now`, net of the deposit fee (rounds down). Like the
+/// program's `preview_deposit`, the dead shares locked by a vault's first deposit and any
+/// Token-2022 transfer fee are not subtracted
+pub fn preview_deposit(vault: &Vault, assets: u64, now: i64) -> Result<u64, MathError> {
+    let (free_assets, total_shares) = accrued_totals(vault, now)?;
+    let fee = tsv_math::fee_on_amount(assets, vault.deposit_fee_bps)?;
+    let net_assets = tsv_math::sub(assets, fee)?;
+    tsv_math::convert_to_shares(net_assets, free_assets, total_shares, Rounding::Floor)
+}
+
+/// Net assets `redeem(shares)` pays the owner of `position` at `now` with `idle_assets` on
+/// hand (`Vault::idle_assets` of the asset vault), rounding down. Like the program's
+/// `preview_redeem`, it charges the utilization-scaled fee plus the owner's cooldown and
+/// early-exit penalties, nothing if `fee_exempt`, and fails with `CooldownActive` where the
+/// cooldown rejects the redemption. An owner without a position passes the default one
+pub fn preview_redeem(
+    vault: &Vault,
+    shares: u64,
+    position: &UserPosition,
+    fee_exempt: bool,
+    now: i64,
+    idle_assets: u64,
+) -> Result<u64, VaultClientError> {
+    let (free_assets, total_shares) = accrued_totals(vault, now)?;
+    let gross_assets =
+        tsv_math::convert_to_assets(shares, free_assets, total_shares, Rounding::Floor)?;
+    let fee_bps = redeem_fee_bps(vault, position, fee_exempt, now, idle_assets)?;
+    let fee = tsv_math::fee_on_amount(gross_assets, fee_bps)?;
+    Ok(tsv_math::sub(gross_assets, fee)?)
+}
+
+/// Fee in bps `redeem` charges `position` at `now`, as `Vault::redeem_fee_bps` computes it
+fn redeem_fee_bps(
+    vault: &Vault,
+    position: &UserPosition,
+    fee_exempt: bool,
+    now: i64,
+    idle_assets: u64,
+) -> Result<u16, VaultClientError> {
+    if fee_exempt {
+        return Ok(0);
+    }
+    let mut fee_bps = tsv_math::utilization_fee_bps(
+        vault.redeem_fee_bps,
+        vault.max_redeem_fee_bps,
+        idle_assets,
+        vault.total_assets,
+    )?;
+
+    let cooldown_ends = position
+        .last_deposit_ts
+        .checked_add(vault.cooldown_seconds as i64)
+        .ok_or(MathError::Overflow)?;
+    if now < cooldown_ends {
+        if vault.cooldown_penalty_bps == 0 {
+            return Err(VaultClientError::CooldownActive);
+        }
+        fee_bps = fee_bps
+            .checked_add(vault.cooldown_penalty_bps)
+            .ok_or(MathError::Overflow)?;
+    }
+
+    let since_deposit = now.saturating_sub(position.last_deposit_ts).max(0) as u64;
+    let early_exit_bps = tsv_math::decayed_penalty_bps(
+        vault.early_exit_penalty_bps,
+        vault.early_exit_window_seconds,
+        since_deposit,
+    )?;
+    Ok(fee_bps
+        .checked_add(early_exit_bps)
+        .ok_or(MathError::Overflow)?)
+}
+
+/// Free assets and total shares of `vault` with the management fee accrued and profit
+/// unlocked up to `now`, as `Vault::accrued` books them
+fn accrued_totals(vault: &Vault, now: i64) -> Result<(u64, u64), MathError> {
+    let elapsed = now.saturating_sub(vault.last_accrual_ts).max(0) as u64;
+    let fee_shares =
+        tsv_math::management_fee_shares(vault.total_shares, vault.management_fee_bps, elapsed)?;
+    let total_shares = tsv_math::add(vault.total_shares, fee_shares)?;
+    let locked_profit = tsv_math::locked_profit_at(
+        vault.locked_profit,
+        vault.locked_profit_ts,
+        vault.profit_unlock_end_ts,
+        now,
+    )?;
+    let free_assets = vault
+        .total_assets
+        .saturating_sub(locked_profit)
+        .saturating_sub(vault.junior_assets);
+    Ok((free_assets, total_shares))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AccountDeserialize, Space};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const DAY: i64 = 24 * 3600;
+
+    fn vault(
+        (total_assets, total_shares): (u64, u64),
+        (locked_profit, junior_assets): (u64, u64),
+        (deposit_fee_bps, redeem_fee_bps, management_fee_bps): (u16, u16, u16),
+        (last_accrual_ts, locked_profit_ts, unlock_duration): (i64, i64, i64),
+    ) -> Vault {
+        let data = vec![0; 8 + Vault::INIT_SPACE];
+        let mut vault = Vault::try_deserialize_unchecked(&mut data.as_slice()).unwrap();
+        vault.total_assets = total_assets;
+        vault.total_shares = total_shares;
+        vault.locked_profit = locked_profit.min(total_assets);
+        vault.junior_assets = junior_assets.min(total_assets);
+        vault.deposit_fee_bps = deposit_fee_bps;
+        vault.redeem_fee_bps = redeem_fee_bps;
+        vault.management_fee_bps = management_fee_bps;
+        vault.last_accrual_ts = last_accrual_ts;
+        vault.locked_profit_ts = locked_profit_ts;
+        vault.profit_unlock_end_ts = locked_profit_ts + unlock_duration;
+        vault
+    }
+
+    #[test]
+    fn unlocking_profit_and_accrued_fees_move_the_previews() {
+        // 1,000 USDC backing 1,000,000 shares, 100 USDC of it locked over a day from 0
+        let vault = vault(
+            (1_000_000_000, 1_000_000_000_000),
+            (100_000_000, 0),
+            (0, 0, 0),
+            (0, 0, DAY),
+        );
+        let position = UserPosition::default();
+        let before = preview_redeem(&vault, 1_000_000_000, &position, false, 0, 0).unwrap();
+        let after = preview_redeem(&vault, 1_000_000_000, &position, false, DAY, 0).unwrap();
+        assert!(after > before);
+
+        let mut charged = vault.clone();
+        charged.management_fee_bps = 200;
+        assert!(
+            preview_deposit(&charged, 1_000_000, DAY).unwrap()
+                > preview_deposit(&vault, 1_000_000, DAY).unwrap()
+        );
+    }
+
+    #[test]
+    fn utilization_raises_the_redeem_fee() {
+        // 1,000 USDC with a 10 bps fee rising to 50 bps once all of it is deployed
+        let mut vault = vault(
+            (1_000_000_000, 1_000_000_000_000),
+            (0, 0),
+            (0, 10, 0),
+            (0, 0, 0),
+        );
+        vault.max_redeem_fee_bps = 50;
+        let position = UserPosition::default();
+        let preview = |idle_assets| {
+            preview_redeem(&vault, 1_000_000_000, &position, false, 0, idle_assets).unwrap()
+        };
+        assert_eq!(preview(1_000_000_000), 1_000_000 - 1_000);
+        assert_eq!(preview(500_000_000), 1_000_000 - 3_000);
+    }
+
+    #[test]
+    fn recent_deposits_pay_the_cooldown_and_early_exit_penalties() {
+        // 1,000 USDC with a 10 bps fee, a 50 bps penalty for a day and 100 bps decaying over
+        // ten days
+        let mut vault = vault(
+            (1_000_000_000, 1_000_000_000_000),
+            (0, 0),
+            (0, 10, 0),
+            (0, 0, 0),
+        );
+        vault.cooldown_seconds = DAY as u32;
+        vault.cooldown_penalty_bps = 50;
+        vault.early_exit_window_seconds = 10 * DAY as u32;
+        vault.early_exit_penalty_bps = 100;
+        let position = UserPosition {
+            last_deposit_ts: DAY,
+            ..UserPosition::default()
+        };
+        let preview = |fee_exempt, now| {
+            preview_redeem(
+                &vault,
+                1_000_000_000,
+                &position,
+                fee_exempt,
+                now,
+                1_000_000_000,
+            )
+        };
+
+        assert_eq!(preview(false, DAY).unwrap(), 1_000_000 - 16_000);
+        assert_eq!(preview(false, 6 * DAY).unwrap(), 1_000_000 - 6_000);
+        assert_eq!(preview(false, 11 * DAY).unwrap(), 1_000_000 - 1_000);
+        assert_eq!(preview(true, DAY).unwrap(), 1_000_000);
+
+        // Without a penalty the cooldown rejects the redemption, unless the owner is exempt
+        vault.cooldown_penalty_bps = 0;
+        let preview = |fee_exempt| {
+            preview_redeem(
+                &vault,
+                1_000_000_000,
+                &position,
+                fee_exempt,
+                DAY,
+                1_000_000_000,
+            )
+        };
+        assert!(matches!(
+            preview(false),
+            Err(VaultClientError::CooldownActive)
+        ));
+        assert_eq!(preview(true).unwrap(), 1_000_000);
+    }
+
+    // Bounds keep every intermediate result within u64, as in `tsv-math`'s own tests
+    fn totals() -> impl Strategy<Value = (u64, u64)> {
+        (0u64..1_000_000_000_000, 0u64..1_000_000_000_000)
+    }
+
+    fn times() -> impl Strategy<Value = (i64, i64, i64)> {
+        (0i64..30 * DAY, 0i64..30 * DAY, 0i64..7 * DAY)
+    }
+
+    proptest! {
+        /// The local preview equals the program's `accrued(now).preview_deposit`
+        #[test]
+        fn deposit_preview_matches_the_program(
+            amounts in totals(),
+            locked in totals(),
+            fees in (0u16..=100, 0u16..=100, 0u16..=500),
+            times in times(),
+            now in 0i64..60 * DAY,
+            assets in 0u64..1_000_000_000_000,
+        ) {
+            let vault = vault(amounts, locked, fees, times);
+            let program = vault.accrued(now).and_then(|vault| vault.preview_deposit(assets));
+            prop_assert_eq!(preview_deposit(&vault, assets, now).ok(), program.ok());
+        }
+
+        /// The local preview equals the program's `accrued(now).preview_redeem` at any
+        /// utilization and time since the owner's last deposit
+        #[test]
+        fn redeem_preview_matches_the_program(
+            amounts in totals(),
+            locked in totals(),
+            fees in (0u16..=100, 0u16..=100, 0u16..=500),
+            max_redeem_fee_bps in 0u16..=100,
+            cooldown in (0u32..2 * DAY as u32, 0u16..=500),
+            early_exit in (0u32..30 * DAY as u32, 0u16..=500),
+            times in times(),
+            (now, last_deposit_ts) in (0i64..60 * DAY, 0i64..60 * DAY),
+            fee_exempt in any::<bool>(),
+            shares in 0u64..1_000_000_000_000,
+            idle_assets in 0u64..1_000_000_000_000,
+        ) {
+            let mut vault = vault(amounts, locked, fees, times);
+            vault.max_redeem_fee_bps = max_redeem_fee_bps;
+            (vault.cooldown_seconds, vault.cooldown_penalty_bps) = cooldown;
+            (vault.early_exit_window_seconds, vault.early_exit_penalty_bps) = early_exit;
+            let position = UserPosition { last_deposit_ts, ..UserPosition::default() };
+            let program = vault.accrued(now).and_then(|vault| {
+                vault.preview_redeem(shares, &position, fee_exempt, now, idle_assets)
+            });
+            let local = preview_redeem(&vault, shares, &position, fee_exempt, now, idle_assets);
+            prop_assert_eq!(local.ok(), program.ok());
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Signers named by path, as the Solana CLI names them: a keypair file or, with the `ledger`
+//! feature, a Ledger as `usb://ledger`, optionally with its pubkey (`usb://ledger/<pubkey>`) and
+//! an account to derive (`?key=<account>[/<change>]`, by default the wallet's first).
+
+use solana_sdk::signature::{read_keypair_file, Signer};
+
+use crate::error::VaultClientError;
+
+const USB_SCHEME: &str = "usb://";
+
+/// Signer at `path`, called `name` in errors and on the Ledger's screen
+pub fn load_signer(path: &str, name: &str) -> Result<Box<dyn Signer>, VaultClientError> {
+    if path.starts_with(USB_SCHEME) {
+        return load_usb(path, name);
+    }
+    let keypair = read_keypair_file(path)
+        .map_err(|err| VaultClientError::Signer(format!("can't read {name} {path}: {err}")))?;
+    Ok(Box::new(keypair))
+}
+
+#[cfg(feature = "ledger")]
+fn load_usb(path: &str, name: &str) -> Result<Box<dyn Signer>, VaultClientError> {
+    Ok(Box::new(ledger::keypair(path, name)?))
+}
+
+#[cfg(not(feature = "ledger"))]
+fn load_usb(path: &str, name: &str) -> Result<Box<dyn Signer>, VaultClientError> {
+    Err(VaultClientError::Signer(format!(
+        "{name} {path}: hardware wallets need the `ledger` feature"
+    )))
+}
+
+/// Wallet locator and `key` derivation of a `usb://` path
+#[cfg_attr(not(feature = "ledger"), allow(dead_code))]
+fn split_usb_path(path: &str) -> Result<(&str, Option<&str>), VaultClientError> {
+    let (locator, query) = match path.split_once('?') {
+        Some((locator, query)) => (locator, Some(query)),
+        None => (path, None),
+    };
+    let key = query
+        .map(|query| {
+            query.strip_prefix("key=").ok_or_else(|| {
+                VaultClientError::Signer(format!("{path}: expected ?key=<account>[/<change>]"))
+            })
+        })
+        .transpose()?;
+    Ok((locator, key))
+}
+
+#[cfg(feature = "ledger")]
+pub mod ledger {
+    //! Ledger signing through `solana-remote-wallet`.
+
+    use solana_remote_wallet::locator::Locator;
+    use solana_remote_wallet::remote_keypair::{generate_remote_keypair, RemoteKeypair};
+    use solana_remote_wallet::remote_wallet::maybe_wallet_manager;
+    use solana_sdk::derivation_path::DerivationPath;
+
+    use super::split_usb_path;
+    use crate::error::VaultClientError;
+
+    /// Keypair of the Ledger account at the `usb://ledger` `path`, after the user confirms its
+    /// address on the device
+    pub fn keypair(path: &str, name: &str) -> Result<RemoteKeypair, VaultClientError> {
+        let signer_error =
+            |err: &dyn std::fmt::Display| VaultClientError::Signer(format!("{name} {path}: {err}"));
+        let (locator, key) = split_usb_path(path)?;
+        let locator = Locator::new_from_path(locator).map_err(|err| signer_error(&err))?;
+        let derivation_path = key
+            .map(DerivationPath::from_key_str)
+            .transpose()
+            .map_err(|err| signer_error(&err))?
+            .unwrap_or_default();
+        let wallet_manager = maybe_wallet_manager()
+            .map_err(|err| signer_error(&err))?
+            .ok_or_else(|| signer_error(&"no hardware wallet found"))?;
+        generate_remote_keypair(locator, derivation_path, &wallet_manager, true, name)
+            .map_err(|err| signer_error(&err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usb_paths_split_into_locator_and_key() {
+        assert_eq!(
+            split_usb_path("usb://ledger").unwrap(),
+            ("usb://ledger", None)
+        );
+        assert_eq!(
+            split_usb_path("usb://ledger?key=1/0").unwrap(),
+            ("usb://ledger", Some("1/0"))
+        );
+        assert_eq!(
+            split_usb_path("usb://ledger/9ZNTfG4NyQgxy2SWjSiQoUyBPEvXT2xo7fKc5hPYYJ7b?key=2")
+                .unwrap(),
+            (
+                "usb://ledger/9ZNTfG4NyQgxy2SWjSiQoUyBPEvXT2xo7fKc5hPYYJ7b",
+                Some("2")
+            )
+        );
+        assert!(split_usb_path("usb://ledger?account=1").is_err());
+    }
+
+    #[test]
+    fn missing_keypair_files_are_signer_errors() {
+        let err = load_signer("/nonexistent/id.json", "authority")
+            .err()
+            .unwrap();
+        assert!(matches!(err, VaultClientError::Signer(_)));
+    }
+}
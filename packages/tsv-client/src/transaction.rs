@@ -0,0 +1,153 @@
+//! Versioned transactions: compute budget instructions priced from recent prioritization fees,
+//! and messages compiled against lookup tables.
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::address_lookup_table::AddressLookupTableAccount;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::VaultClientError;
+
+/// How the priority fee is picked from the fees recently paid to write the same accounts, in
+/// micro-lamports per compute unit
+#[derive(Clone, Copy, Debug)]
+pub struct PriorityFee {
+    /// Percentile of the recent fees, 0 to 100
+    pub percentile: u8,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Default for PriorityFee {
+    /// The 75th percentile, at most 1 lamport per compute unit, as `tsv-keeper` defaults to
+    fn default() -> Self {
+        Self {
+            percentile: 75,
+            min: 0,
+            max: 1_000_000,
+        }
+    }
+}
+
+/// Options of `VaultClient::send_versioned`
+#[derive(Clone, Debug, Default)]
+pub struct SendOptions {
+    /// Compute unit limit, the runtime's default without it
+    pub compute_unit_limit: Option<u32>,
+    /// Priority fee to estimate, none without it
+    pub priority_fee: Option<PriorityFee>,
+    /// Tables the message looks accounts up in, see `lookup_table::fetch`
+    pub lookup_tables: Vec<AddressLookupTableAccount>,
+}
+
+/// `fee`'s percentile of the prioritization fees recently paid for transactions writing
+/// `writable`, within its bounds
+pub async fn estimate_priority_fee(
+    rpc: &RpcClient,
+    writable: &[Pubkey],
+    fee: &PriorityFee,
+) -> Result<u64, VaultClientError> {
+    let fees: Vec<u64> = rpc
+        .get_recent_prioritization_fees(writable)
+        .await?
+        .iter()
+        .map(|recent| recent.prioritization_fee)
+        .collect();
+    Ok(percentile(fees, fee.percentile).clamp(fee.min, fee.max))
+}
+
+/// Accounts `instructions` write, each once
+pub fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut writable = Vec::new();
+    for meta in instructions.iter().flat_map(|ix| ix.accounts.iter()) {
+        if meta.is_writable && !writable.contains(&meta.pubkey) {
+            writable.push(meta.pubkey);
+        }
+    }
+    writable
+}
+
+/// Compute budget instructions to put before a transaction's own
+pub fn compute_budget(unit_limit: Option<u32>, micro_lamports: Option<u64>) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    if let Some(units) = unit_limit {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+    }
+    if let Some(price) = micro_lamports {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    instructions
+}
+
+/// V0 message of `instructions` paid by `payer`, accounts found in `lookup_tables` referenced
+/// through them. Invoked programs and signers always stay in the message itself
+pub fn compile(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedMessage, VaultClientError> {
+    let message = v0::Message::try_compile(payer, instructions, lookup_tables, blockhash)
+        .map_err(|err| VaultClientError::Compile(err.to_string()))?;
+    Ok(VersionedMessage::V0(message))
+}
+
+/// The `percentile`th of `values`, 0 without any
+fn percentile(mut values: Vec<u64>, percentile: u8) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    let index = (values.len() - 1) * usize::from(percentile.min(100)) / 100;
+    values[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+
+    use super::*;
+
+    #[test]
+    fn picks_percentile() {
+        assert_eq!(percentile(vec![], 75), 0);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 0), 10);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 75), 40);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 100), 50);
+    }
+
+    #[test]
+    fn compiles_table_accounts_as_lookups() {
+        let payer = Pubkey::new_unique();
+        let (listed, unlisted) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = Instruction {
+            program_id: tsv_usdc_vault::ID,
+            accounts: vec![
+                AccountMeta::new(listed, false),
+                AccountMeta::new_readonly(unlisted, false),
+            ],
+            data: vec![],
+        };
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![listed],
+        };
+        let mut ixs = compute_budget(Some(200_000), Some(1_000));
+        ixs.push(ix);
+
+        let VersionedMessage::V0(message) =
+            compile(&payer, &ixs, std::slice::from_ref(&table), Hash::default()).unwrap()
+        else {
+            panic!("not a v0 message");
+        };
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(message.address_table_lookups[0].account_key, table.key);
+        assert_eq!(message.address_table_lookups[0].writable_indexes, [0]);
+        assert!(!message.account_keys.contains(&listed));
+        assert!(message.account_keys.contains(&unlisted));
+        assert_eq!(writable_accounts(&ixs), [listed]);
+    }
+}
@@ -0,0 +1,96 @@
+//! Admin actions the program records in `AdminActionEvent`.
+
+use tsv_usdc_vault::AdminAction;
+
+/// Name of the instruction that took an admin action, e.g. `set_pause_flags`
+pub fn action_name(action: &AdminAction) -> &'static str {
+    match action {
+        AdminAction::Initialize { .. } => "initialize",
+        AdminAction::QueueFeeUpdate { .. } => "queue_fee_update",
+        AdminAction::ExecuteFeeUpdate { .. } => "execute_fee_update",
+        AdminAction::SetFeeTimelock { .. } => "set_fee_timelock",
+        AdminAction::SetPauseFlags { .. } => "set_pause_flags",
+        AdminAction::ProposeAuthority { .. } => "propose_authority",
+        AdminAction::AcceptAuthority => "accept_authority",
+        AdminAction::GuardianPause => "guardian_pause",
+        AdminAction::SetDepegBreaker { .. } => "set_depeg_breaker",
+        AdminAction::ResetDepegBreaker => "reset_depeg_breaker",
+        AdminAction::RenounceAuthority => "renounce_authority",
+        AdminAction::SetRole { .. } => "set_role",
+        AdminAction::SetDepositCap { .. } => "set_deposit_cap",
+        AdminAction::SetMaxPerUser { .. } => "set_max_per_user",
+        AdminAction::SetMinAmounts { .. } => "set_min_amounts",
+        AdminAction::SetLockupSeconds { .. } => "set_lockup_seconds",
+        AdminAction::SetEarlyExitPenalty { .. } => "set_early_exit_penalty",
+        AdminAction::SetRedeemFeeCurve { .. } => "set_redeem_fee_curve",
+        AdminAction::SetCooldown { .. } => "set_cooldown",
+        AdminAction::SetManagementFee { .. } => "set_management_fee",
+        AdminAction::SetPerformanceFee { .. } => "set_performance_fee",
+        AdminAction::SetProfitUnlockDuration { .. } => "set_profit_unlock_duration",
+        AdminAction::SetBufferBps { .. } => "set_buffer_bps",
+        AdminAction::SetInstantRedeemPremium { .. } => "set_instant_redeem_premium",
+        AdminAction::SetFeeRecipient { .. } => "set_fee_recipient",
+        AdminAction::SetPermissioned { .. } => "set_permissioned",
+        AdminAction::AddToAllowlist { .. } => "add_to_allowlist",
+        AdminAction::RemoveFromAllowlist { .. } => "remove_from_allowlist",
+        AdminAction::SetRelayersRestricted { .. } => "set_relayers_restricted",
+        AdminAction::SetRelayer { .. } => "set_relayer",
+        AdminAction::RemoveRelayer { .. } => "remove_relayer",
+        AdminAction::UpdateShareMetadata { .. } => "update_share_metadata",
+        AdminAction::UpdateVaultMetadata { .. } => "update_vault_metadata",
+        AdminAction::BlockAddress { .. } => "block_address",
+        AdminAction::UnblockAddress { .. } => "unblock_address",
+        AdminAction::AddFeeExemption { .. } => "add_fee_exemption",
+        AdminAction::RemoveFeeExemption { .. } => "remove_fee_exemption",
+        AdminAction::SetSurplusPolicy { .. } => "set_surplus_policy",
+        AdminAction::Sync { .. } => "sync",
+        AdminAction::AddStrategy { .. } => "add_strategy",
+        AdminAction::UpdateStrategy { .. } => "update_strategy",
+        AdminAction::RemoveStrategy { .. } => "remove_strategy",
+        AdminAction::Allocate { .. } => "allocate",
+        AdminAction::Deallocate { .. } => "deallocate",
+        AdminAction::EmergencyExitStrategy { .. } => "emergency_exit_strategy",
+        AdminAction::Rebalance { .. } => "rebalance",
+        AdminAction::Harvest { .. } => "harvest",
+        AdminAction::ReportLoss { .. } => "report_loss",
+        AdminAction::ProcessEpoch { .. } => "process_epoch",
+        AdminAction::AddBasketAsset { .. } => "add_basket_asset",
+        AdminAction::UpdateBasketAsset { .. } => "update_basket_asset",
+        AdminAction::SetJuniorTranche { .. } => "set_junior_tranche",
+        AdminAction::SetMigrationTarget { .. } => "set_migration_target",
+        AdminAction::StartWindDown => "start_wind_down",
+        AdminAction::FreezeWindDown { .. } => "freeze_wind_down",
+        AdminAction::CloseVault { .. } => "close_vault",
+        AdminAction::SetLossPauseThreshold { .. } => "set_loss_pause_threshold",
+        AdminAction::InitTimelock { .. } => "init_timelock",
+        AdminAction::QueueAction { .. } => "queue_action",
+        AdminAction::CancelAction { .. } => "cancel_action",
+        AdminAction::ExecuteAction { .. } => "execute_action",
+        AdminAction::SetTimelockDelay { .. } => "set_timelock_delay",
+        AdminAction::SetTimelockAdmin { .. } => "set_timelock_admin",
+        AdminAction::SetRecoveryAccount { .. } => "set_recovery_account",
+        AdminAction::RequestEmergencyEscape { .. } => "request_emergency_escape",
+        AdminAction::CancelEmergencyEscape => "cancel_emergency_escape",
+        AdminAction::EmergencyWithdraw { .. } => "emergency_withdraw",
+        AdminAction::MigrateState { .. } => "migrate_state",
+        AdminAction::InitLegacyAccounts { .. } => "init_legacy_accounts",
+        AdminAction::ExecuteFeeTimelock { .. } => "execute_fee_timelock",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_admin_actions_after_their_instruction() {
+        let pause = AdminAction::SetPauseFlags {
+            paused_deposits: true,
+            paused_redeems: false,
+            paused_meta: false,
+        };
+
+        assert_eq!(action_name(&pause), "set_pause_flags");
+        assert_eq!(action_name(&AdminAction::GuardianPause), "guardian_pause");
+    }
+}
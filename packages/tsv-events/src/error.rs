@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("{event}: {reason}")]
+    Event { event: &'static str, reason: String },
+    #[error("transaction: {0}")]
+    Transaction(String),
+}
@@ -0,0 +1,246 @@
+//! `VaultEvent`: every event of the program in its current layout.
+
+pub use tsv_usdc_vault::{
+    ActionCancelledEvent, ActionExecutedEvent, ActionExpiredEvent, ActionQueuedEvent,
+    AdminActionEvent, AllocateEvent, AllowlistUpdatedEvent, AuthorityProposedEvent,
+    AuthorityRenouncedEvent, AuthorityTransferredEvent, BasketAssetAddedEvent,
+    BasketAssetRevaluedEvent, BasketAssetUpdatedEvent, BasketDepositEvent, BasketRedeemEvent,
+    BatchMetaRedeemEvent, BlocklistUpdatedEvent, BufferUpdatedEvent, CooldownUpdatedEvent,
+    CostBasisTrackingEvent, DeallocateEvent, DepegBreakerResetEvent, DepegBreakerTrippedEvent,
+    DepegBreakerUpdatedEvent, DepositCapUpdatedEvent, DepositEvent, EarlyExitPenaltyUpdatedEvent,
+    EmergencyEscapeCancelledEvent, EmergencyEscapeRequestedEvent, EmergencyWithdrawEvent,
+    EpochProcessedEvent, EvmAddressLinkedEvent, FeeExecutedEvent, FeeExemptionUpdatedEvent,
+    FeeQueuedEvent, FeeRecipientUpdatedEvent, FeeTimelockQueuedEvent, FeeTimelockUpdatedEvent,
+    FeesCollectedEvent, GuardianPauseEvent, HarvestEvent, InstantRedeemEvent,
+    InstantRedeemPremiumUpdatedEvent, InsuranceFundedEvent, JuniorDepositEvent, JuniorRedeemEvent,
+    JuniorTrancheUpdatedEvent, LockupUpdatedEvent, LossEvent, LossPauseThresholdUpdatedEvent,
+    ManagementFeeAccruedEvent, ManagementFeeUpdatedEvent, MaxPerUserUpdatedEvent, MetaDepositEvent,
+    MetaRedeemEvent, MigrationTargetUpdatedEvent, MinAmountsUpdatedEvent, NonceCancelledEvent,
+    PauseFlagsUpdatedEvent, PerformanceFeeUpdatedEvent, PermissionedUpdatedEvent,
+    PositionMigratedEvent, ProfitUnlockDurationUpdatedEvent, ProportionalRedeemEvent,
+    RateRecordedEvent, RecoveryAccountUpdatedEvent, RedeemEvent, RedeemFeeCurveUpdatedEvent,
+    RegistryCreatorUpdatedEvent, RelayerUpdatedEvent, RelayersRestrictedUpdatedEvent,
+    RoleUpdatedEvent, SessionKeyCreatedEvent, SessionKeyRevokedEvent, SessionRedeemEvent,
+    ShareMetadataUpdatedEvent, StateMigratedEvent, StrategyEmergencyExitEvent, StrategyQueuedEvent,
+    StrategyRemovedEvent, StrategyUpdatedEvent, SurplusPolicyUpdatedEvent, SyncEvent,
+    TimelockAdminUpdatedEvent, TimelockDelayUpdatedEvent, VaultClosedEvent,
+    VaultMetadataUpdatedEvent, VaultRegisteredEvent, WindDownClaimEvent, WindDownFrozenEvent,
+    WindDownStartedEvent, WithdrawClaimedEvent, WithdrawRequestCancelledEvent,
+    WithdrawRequestedEvent,
+};
+
+use crate::{v0, DecodeError, SCHEMA_VERSION};
+
+crate::event_enum! {
+    /// An event of the vault program. Holds the program's own structs, which don't implement
+    /// `Debug`; match on the variant to read one
+    pub enum VaultEvent {
+        AdminActionEvent,
+        DepositEvent,
+        RedeemEvent,
+        InstantRedeemEvent,
+        WithdrawRequestedEvent,
+        WithdrawRequestCancelledEvent,
+        EpochProcessedEvent,
+        WithdrawClaimedEvent,
+        BasketAssetAddedEvent,
+        BasketAssetUpdatedEvent,
+        BasketAssetRevaluedEvent,
+        BasketDepositEvent,
+        BasketRedeemEvent,
+        ProportionalRedeemEvent,
+        JuniorTrancheUpdatedEvent,
+        JuniorDepositEvent,
+        JuniorRedeemEvent,
+        MigrationTargetUpdatedEvent,
+        PositionMigratedEvent,
+        MetaDepositEvent,
+        MetaRedeemEvent,
+        BatchMetaRedeemEvent,
+        EvmAddressLinkedEvent,
+        CostBasisTrackingEvent,
+        SessionKeyCreatedEvent,
+        SessionKeyRevokedEvent,
+        SessionRedeemEvent,
+        NonceCancelledEvent,
+        FeeQueuedEvent,
+        FeeExecutedEvent,
+        FeeTimelockUpdatedEvent,
+        FeeTimelockQueuedEvent,
+        PauseFlagsUpdatedEvent,
+        AuthorityProposedEvent,
+        AuthorityTransferredEvent,
+        GuardianPauseEvent,
+        DepegBreakerUpdatedEvent,
+        DepegBreakerTrippedEvent,
+        DepegBreakerResetEvent,
+        AuthorityRenouncedEvent,
+        RoleUpdatedEvent,
+        DepositCapUpdatedEvent,
+        MaxPerUserUpdatedEvent,
+        MinAmountsUpdatedEvent,
+        SurplusPolicyUpdatedEvent,
+        LockupUpdatedEvent,
+        CooldownUpdatedEvent,
+        ManagementFeeUpdatedEvent,
+        ManagementFeeAccruedEvent,
+        PerformanceFeeUpdatedEvent,
+        BufferUpdatedEvent,
+        InstantRedeemPremiumUpdatedEvent,
+        ProfitUnlockDurationUpdatedEvent,
+        FeeRecipientUpdatedEvent,
+        FeesCollectedEvent,
+        PermissionedUpdatedEvent,
+        AllowlistUpdatedEvent,
+        RelayersRestrictedUpdatedEvent,
+        RelayerUpdatedEvent,
+        ShareMetadataUpdatedEvent,
+        VaultMetadataUpdatedEvent,
+        RateRecordedEvent,
+        BlocklistUpdatedEvent,
+        FeeExemptionUpdatedEvent,
+        RedeemFeeCurveUpdatedEvent,
+        EarlyExitPenaltyUpdatedEvent,
+        TimelockDelayUpdatedEvent,
+        TimelockAdminUpdatedEvent,
+        ActionQueuedEvent,
+        ActionCancelledEvent,
+        ActionExecutedEvent,
+        ActionExpiredEvent,
+        RecoveryAccountUpdatedEvent,
+        EmergencyEscapeRequestedEvent,
+        EmergencyEscapeCancelledEvent,
+        EmergencyWithdrawEvent,
+        WindDownStartedEvent,
+        WindDownFrozenEvent,
+        WindDownClaimEvent,
+        VaultClosedEvent,
+        RegistryCreatorUpdatedEvent,
+        VaultRegisteredEvent,
+        StateMigratedEvent,
+        SyncEvent,
+        StrategyQueuedEvent,
+        StrategyUpdatedEvent,
+        StrategyRemovedEvent,
+        AllocateEvent,
+        StrategyEmergencyExitEvent,
+        DeallocateEvent,
+        HarvestEvent,
+        LossEvent,
+        InsuranceFundedEvent,
+        LossPauseThresholdUpdatedEvent,
+    }
+    legacy V0(v0::Event)
+}
+
+impl VaultEvent {
+    /// Decode an event payload (8-byte discriminator, then Borsh data) in the current layout,
+    /// falling back to the version 0 layout. `None` for a discriminator of no known event, e.g.
+    /// one added to the program after this crate was built
+    pub fn decode(payload: &[u8]) -> Result<Option<Self>, DecodeError> {
+        if payload.len() < 8 {
+            return Ok(None);
+        }
+        let (discriminator, data) = payload.split_at(8);
+        match Self::parse(discriminator, data, false) {
+            // Too short for the current layout: written before fields were appended
+            Some(Err(err)) => match v0::Event::parse(discriminator, data, true) {
+                Some(Ok(event)) => Ok(Some(Self::V0(event))),
+                _ => Err(err),
+            },
+            parsed => parsed.transpose(),
+        }
+    }
+
+    /// `SCHEMA_VERSION` of the layout the event was written in
+    pub fn version(&self) -> u8 {
+        match self {
+            Self::V0(_) => 0,
+            _ => SCHEMA_VERSION,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::Event;
+
+    use super::*;
+    use crate::VaultSnapshot;
+
+    fn v0_deposit() -> v0::DepositEvent {
+        v0::DepositEvent {
+            user: Pubkey::new_unique(),
+            receiver: Pubkey::new_unique(),
+            assets: 1_000,
+            shares: 500,
+            fee: 1,
+        }
+    }
+
+    fn deposit() -> DepositEvent {
+        let event = v0_deposit();
+        DepositEvent {
+            user: event.user,
+            receiver: event.receiver,
+            assets: event.assets,
+            shares: event.shares,
+            fee: event.fee,
+            snapshot: VaultSnapshot {
+                sequence: 7,
+                total_assets: 2_000,
+                total_shares: 1_000,
+                exchange_rate: 2_000_000_000,
+            },
+        }
+    }
+
+    #[test]
+    fn decodes_the_current_layout() {
+        let event = VaultEvent::decode(&deposit().data()).unwrap().unwrap();
+
+        assert_eq!(event.version(), SCHEMA_VERSION);
+        assert_eq!(event.name(), "DepositEvent");
+        assert!(matches!(
+            event,
+            VaultEvent::DepositEvent(DepositEvent { assets: 1_000, ref snapshot, .. })
+                if snapshot.sequence == 7
+        ));
+    }
+
+    #[test]
+    fn decodes_events_written_before_snapshots_as_version_0() {
+        let written = v0_deposit();
+
+        let event = VaultEvent::decode(&written.data()).unwrap().unwrap();
+
+        assert_eq!(event.version(), 0);
+        assert_eq!(event.name(), "DepositEvent");
+        assert!(matches!(
+            event,
+            VaultEvent::V0(v0::Event::DepositEvent(v0::DepositEvent { user, fee: 1, .. }))
+                if user == written.user
+        ));
+    }
+
+    #[test]
+    fn ignores_fields_appended_after_the_current_layout() {
+        let mut data = deposit().data();
+        data.extend([0; 16]);
+
+        let event = VaultEvent::decode(&data).unwrap().unwrap();
+
+        assert_eq!(event.version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn skips_unknown_events_and_rejects_truncated_ones() {
+        let data = v0_deposit().data();
+        let unknown = [[0xff; 8].as_slice(), &data[8..]].concat();
+
+        assert!(VaultEvent::decode(&unknown).unwrap().is_none());
+        assert!(VaultEvent::decode(&[1, 2]).unwrap().is_none());
+        assert!(VaultEvent::decode(&data[..20]).is_err());
+    }
+}
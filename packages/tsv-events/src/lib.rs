@@ -0,0 +1,90 @@
+//! Events of the Talken Stable Vault program, shared by the off-chain services that read them.
+//!
+//! `VaultEvent` has a variant for every event the program emits, holding the program's own
+//! struct, so a field added to an event reaches every reader with the program crate. Events
+//! written in an older layout decode as `VaultEvent::V0`, with the structs of `v0`.
+//!
+//! Events are found in a confirmed transaction either as self-CPI inner instructions
+//! (`emit_cpi!`, what the program emits now) or as `Program data:` log lines (`emit!`, what it
+//! emitted before); `vault_events` reads both and keeps those of one vault.
+
+mod admin;
+mod error;
+mod events;
+mod transaction;
+pub mod v0;
+
+pub use crate::admin::action_name;
+pub use crate::error::DecodeError;
+pub use crate::events::*;
+pub use crate::transaction::{cpi_events, invocations, log_events, vault_events, Invocation};
+pub use tsv_usdc_vault::{AdminAction, VaultSnapshot};
+
+/// Layout version of the events `VaultEvent` decodes as current. Version 0 is the layout
+/// before deposit and redeem events carried a `VaultSnapshot`
+pub const SCHEMA_VERSION: u8 = 1;
+
+/// `DecodeError` naming `event`, for a payload whose data doesn't deserialize
+fn parse<E: anchor_lang::AnchorDeserialize>(
+    mut data: &[u8],
+    event: &'static str,
+    exact: bool,
+) -> Result<E, DecodeError> {
+    let error = |err: std::io::Error| DecodeError::Event {
+        event,
+        reason: err.to_string(),
+    };
+    if exact {
+        E::try_from_slice(data).map_err(error)
+    } else {
+        E::deserialize(&mut data).map_err(error)
+    }
+}
+
+/// An enum with a variant per event struct, each named after its struct, with the lookup of
+/// the variant a payload's discriminator stands for
+macro_rules! event_enum {
+    (
+        $(#[$attr:meta])*
+        pub enum $enum:ident { $($event:ident),* $(,)? }
+        $(legacy $legacy:ident($legacy_ty:ty))?
+    ) => {
+        $(#[$attr])*
+        pub enum $enum {
+            $($event($event),)*
+            $(
+                /// An event written in an older layout
+                $legacy($legacy_ty),
+            )?
+        }
+
+        impl $enum {
+            /// The event `discriminator` stands for, read from `data`; `None` for a
+            /// discriminator of no event here. Unless `exact`, bytes after the event's fields
+            /// (fields appended since) are ignored
+            pub(crate) fn parse(
+                discriminator: &[u8],
+                data: &[u8],
+                exact: bool,
+            ) -> Option<core::result::Result<Self, crate::DecodeError>> {
+                use anchor_lang::Discriminator;
+                $(
+                    if discriminator == $event::DISCRIMINATOR {
+                        return Some(crate::parse(data, stringify!($event), exact).map(Self::$event));
+                    }
+                )*
+                None
+            }
+
+            /// Name of the event's struct, e.g. `DepositEvent`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$event(_) => stringify!($event),)*
+                    $(Self::$legacy(event) => event.name(),)?
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use event_enum;
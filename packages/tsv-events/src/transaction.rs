@@ -0,0 +1,298 @@
+//! Event payloads of a confirmed transaction, from self-CPI inner instructions and from
+//! `Program data:` log lines, attributed to the vault of the instruction emitting them.
+
+use anchor_lang::event::EVENT_IX_TAG_LE;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiInnerInstructions, UiInstruction,
+    UiLoadedAddresses,
+};
+
+use crate::DecodeError;
+
+/// Instruction of a transaction, top-level or inner, with its accounts resolved
+pub struct Invocation {
+    pub program_id: Pubkey,
+    pub accounts: Vec<Pubkey>,
+    pub data: Vec<u8>,
+    /// 1 for a top-level instruction, one more for each level of CPI
+    pub stack_height: u32,
+}
+
+/// Every instruction the transaction ran, top-level ones each followed by their inner ones,
+/// or `None` if it failed
+pub fn invocations(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Option<Vec<Invocation>>, DecodeError> {
+    let Some(meta) = &transaction.transaction.meta else {
+        return Err(DecodeError::Transaction("no status".into()));
+    };
+    if meta.err.is_some() {
+        return Ok(None);
+    }
+    let decoded = transaction
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| DecodeError::Transaction("undecodable message".into()))?;
+
+    // Static keys, then the ones loaded from lookup tables, writable first
+    let mut keys = decoded.message.static_account_keys().to_vec();
+    if let Some(loaded) = Option::<&UiLoadedAddresses>::from(meta.loaded_addresses.as_ref()) {
+        for address in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(
+                address
+                    .parse()
+                    .map_err(|_| DecodeError::Transaction(format!("loaded address {address}")))?,
+            );
+        }
+    }
+    let key = |index: u8| {
+        keys.get(usize::from(index))
+            .copied()
+            .ok_or_else(|| DecodeError::Transaction(format!("account index {index}")))
+    };
+    let inner = Option::<&Vec<UiInnerInstructions>>::from(meta.inner_instructions.as_ref())
+        .map_or(&[][..], Vec::as_slice);
+
+    let mut invocations = Vec::new();
+    for (index, instruction) in decoded.message.instructions().iter().enumerate() {
+        invocations.push(Invocation {
+            program_id: key(instruction.program_id_index)?,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|&account| key(account))
+                .collect::<Result<_, _>>()?,
+            data: instruction.data.clone(),
+            stack_height: 1,
+        });
+        let group = inner
+            .iter()
+            .filter(|group| usize::from(group.index) == index);
+        for instruction in group.flat_map(|group| &group.instructions) {
+            let UiInstruction::Compiled(instruction) = instruction else {
+                return Err(DecodeError::Transaction("parsed inner instruction".into()));
+            };
+            invocations.push(Invocation {
+                program_id: key(instruction.program_id_index)?,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|&account| key(account))
+                    .collect::<Result<_, _>>()?,
+                data: bs58::decode(&instruction.data)
+                    .into_vec()
+                    .map_err(|err| DecodeError::Transaction(format!("inner instruction: {err}")))?,
+                // Nodes that predate the field only record a single level of CPI
+                stack_height: instruction.stack_height.unwrap_or(2),
+            });
+        }
+    }
+    Ok(Some(invocations))
+}
+
+/// Event payloads (discriminator and data) the program emitted through self-CPI from
+/// instructions that had `vault` among their accounts, in emission order
+pub fn cpi_events<'a>(invocations: &'a [Invocation], vault: &Pubkey) -> Vec<&'a [u8]> {
+    invocations
+        .iter()
+        .enumerate()
+        .filter_map(|(index, invocation)| {
+            if invocation.program_id != tsv_usdc_vault::ID {
+                return None;
+            }
+            let event = invocation.data.strip_prefix(&EVENT_IX_TAG_LE)?;
+            // The instruction emitting the event is the nearest one a level up: only the program
+            // itself can sign as the event authority
+            let emitter = invocations[..index]
+                .iter()
+                .rev()
+                .find(|parent| parent.stack_height < invocation.stack_height)?;
+            (emitter.program_id == tsv_usdc_vault::ID && emitter.accounts.contains(vault))
+                .then_some(event)
+        })
+        .collect()
+}
+
+/// Event payloads the program logged as `Program data:` lines from instructions that had
+/// `vault` among their accounts, in emission order. The logs are followed alongside
+/// `invocations` to know which instruction wrote each line
+pub fn log_events(invocations: &[Invocation], logs: &[String], vault: &Pubkey) -> Vec<Vec<u8>> {
+    let mut events = Vec::new();
+    // Indexes into `invocations` of the instructions running, innermost last
+    let mut stack: Vec<usize> = Vec::new();
+    let mut next = 0;
+    for log in logs {
+        let Some(line) = log.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(data) = line.strip_prefix("data: ") {
+            let Some(invocation) = stack.last().map(|&index| &invocations[index]) else {
+                continue;
+            };
+            // `emit!` logs a single chunk; lines of several are `sol_log_data` calls of
+            // something else
+            if invocation.program_id != tsv_usdc_vault::ID
+                || !invocation.accounts.contains(vault)
+                || data.contains(' ')
+            {
+                continue;
+            }
+            if let Ok(event) = STANDARD.decode(data) {
+                events.push(event);
+            }
+        } else if let Some((program_id, stack_height)) = invoke(line) {
+            // Instructions that don't log, e.g. precompiles, are stepped over
+            let Some(index) = invocations[next..]
+                .iter()
+                .position(|invocation| {
+                    invocation.stack_height == stack_height
+                        && invocation.program_id.to_string() == program_id
+                })
+                .map(|offset| next + offset)
+            else {
+                // Logs that don't follow the instructions, e.g. truncated ones: stop reading
+                break;
+            };
+            next = index + 1;
+            stack.truncate(stack_height.saturating_sub(1) as usize);
+            stack.push(index);
+        } else if line.ends_with(" success") || line.contains(" failed") {
+            stack.pop();
+        }
+    }
+    events
+}
+
+/// Program and stack height of a `Program <id> invoke [<n>]` line, without its `Program `
+fn invoke(line: &str) -> Option<(&str, u32)> {
+    let (program_id, rest) = line.split_once(' ')?;
+    let stack_height = rest.strip_prefix("invoke [")?.strip_suffix(']')?;
+    Some((program_id, stack_height.parse().ok()?))
+}
+
+/// Event payloads of `vault` in a confirmed transaction, self-CPI ones then logged ones, or
+/// `None` if it failed
+pub fn vault_events(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    vault: &Pubkey,
+) -> Result<Option<Vec<Vec<u8>>>, DecodeError> {
+    let Some(invocations) = invocations(transaction)? else {
+        return Ok(None);
+    };
+    let logs = transaction
+        .transaction
+        .meta
+        .as_ref()
+        .and_then(|meta| Option::<&Vec<String>>::from(meta.log_messages.as_ref()))
+        .map_or(&[][..], Vec::as_slice);
+
+    let mut events: Vec<Vec<u8>> = cpi_events(&invocations, vault)
+        .into_iter()
+        .map(<[u8]>::to_vec)
+        .collect();
+    events.extend(log_events(&invocations, logs, vault));
+    Ok(Some(events))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::Event;
+    use tsv_usdc_vault::{DepositEvent, VaultSnapshot};
+
+    use super::*;
+
+    fn cpi(program_id: Pubkey, event: &impl Event, stack_height: u32) -> Invocation {
+        Invocation {
+            program_id,
+            accounts: vec![],
+            data: [&EVENT_IX_TAG_LE[..], &event.data()].concat(),
+            stack_height,
+        }
+    }
+
+    fn instruction(program_id: Pubkey, accounts: Vec<Pubkey>, stack_height: u32) -> Invocation {
+        Invocation {
+            program_id,
+            accounts,
+            data: vec![1, 2, 3],
+            stack_height,
+        }
+    }
+
+    fn deposit_event(assets: u64) -> DepositEvent {
+        DepositEvent {
+            user: Pubkey::new_unique(),
+            receiver: Pubkey::new_unique(),
+            assets,
+            shares: assets / 2,
+            fee: 0,
+            snapshot: VaultSnapshot {
+                sequence: 1,
+                total_assets: assets,
+                total_shares: assets / 2,
+                exchange_rate: 2_000_000_000,
+            },
+        }
+    }
+
+    #[test]
+    fn keeps_cpi_events_emitted_by_instructions_of_the_vault() {
+        let vault = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let ours = deposit_event(1);
+        let theirs = deposit_event(2);
+        let invocations = vec![
+            instruction(tsv_usdc_vault::ID, vec![vault], 1),
+            cpi(tsv_usdc_vault::ID, &ours, 2),
+            instruction(tsv_usdc_vault::ID, vec![other], 1),
+            cpi(tsv_usdc_vault::ID, &theirs, 2),
+            // Not an event: a tagged instruction nothing above it could have signed
+            cpi(tsv_usdc_vault::ID, &theirs, 1),
+            // Not the vault program
+            cpi(Pubkey::new_unique(), &theirs, 2),
+        ];
+
+        let events = cpi_events(&invocations, &vault);
+
+        assert_eq!(events, vec![&ours.data()[..]]);
+    }
+
+    #[test]
+    fn keeps_logged_events_of_instructions_of_the_vault() {
+        let vault = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let token = Pubkey::new_unique();
+        let ours = deposit_event(1);
+        let theirs = deposit_event(2);
+        let invocations = vec![
+            instruction(tsv_usdc_vault::ID, vec![vault], 1),
+            instruction(token, vec![], 2),
+            // A precompile, which doesn't log
+            instruction(Pubkey::new_unique(), vec![], 1),
+            instruction(tsv_usdc_vault::ID, vec![other], 1),
+        ];
+        let program = tsv_usdc_vault::ID;
+        let data =
+            |event: &DepositEvent| format!("Program data: {}", STANDARD.encode(event.data()));
+        let logs = vec![
+            format!("Program {program} invoke [1]"),
+            format!("Program {token} invoke [2]"),
+            "Program data: AQID".to_string(),
+            format!("Program {token} success"),
+            data(&ours),
+            "Program data: AQID BAUG".to_string(),
+            format!("Program {program} success"),
+            format!("Program {program} invoke [1]"),
+            data(&theirs),
+            format!("Program {program} success"),
+        ];
+
+        let events = log_events(&invocations, &logs, &vault);
+
+        assert_eq!(events, vec![ours.data()]);
+    }
+}
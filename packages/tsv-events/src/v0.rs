@@ -0,0 +1,159 @@
+//! Version 0 layouts: the deposit and redeem events as the program emitted them before they
+//! carried a `VaultSnapshot`, through `emit!` log lines. Each is its current struct without the
+//! snapshot fields, and shares its discriminator.
+
+use anchor_lang::prelude::*;
+
+crate::event_enum! {
+    /// An event in the version 0 layout
+    pub enum Event {
+        DepositEvent,
+        RedeemEvent,
+        InstantRedeemEvent,
+        BasketDepositEvent,
+        BasketRedeemEvent,
+        ProportionalRedeemEvent,
+        JuniorDepositEvent,
+        JuniorRedeemEvent,
+        PositionMigratedEvent,
+        MetaDepositEvent,
+        MetaRedeemEvent,
+        SessionRedeemEvent,
+        WindDownClaimEvent,
+    }
+}
+
+#[event]
+pub struct DepositEvent {
+    pub user: Pubkey,
+    pub receiver: Pubkey,
+    pub assets: u64,
+    pub shares: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct RedeemEvent {
+    pub user: Pubkey,
+    pub receiver: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct InstantRedeemEvent {
+    pub user: Pubkey,
+    pub receiver: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    /// Redeem fee, set aside for `collect_fees`
+    pub fee: u64,
+    /// Premium left in `total_assets`
+    pub premium: u64,
+}
+
+#[event]
+pub struct BasketDepositEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// `amount`'s value in `asset_mint` base units, fee included
+    pub value: u64,
+    pub shares: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct BasketRedeemEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+    /// `amount`'s value in `asset_mint` base units, net of `fee`
+    pub value: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct ProportionalRedeemEvent {
+    pub user: Pubkey,
+    pub shares: u64,
+    /// USDC paid
+    pub assets: u64,
+    /// Amount of each basket asset paid, in `remaining_accounts` order
+    pub basket_amounts: Vec<u64>,
+    /// Value of everything paid in `asset_mint` base units, net of `fee`
+    pub value: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct JuniorDepositEvent {
+    pub user: Pubkey,
+    pub assets: u64,
+    pub shares: u64,
+    pub junior_assets: u64,
+}
+
+#[event]
+pub struct JuniorRedeemEvent {
+    pub user: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    pub junior_assets: u64,
+}
+
+#[event]
+pub struct PositionMigratedEvent {
+    pub user: Pubkey,
+    pub source_vault: Pubkey,
+    pub target_vault: Pubkey,
+    /// Source vault shares burned
+    pub shares: u64,
+    /// USDC moved between the vaults
+    pub assets: u64,
+    /// Target vault shares minted
+    pub target_shares: u64,
+}
+
+#[event]
+pub struct MetaDepositEvent {
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    pub relayer: Pubkey,
+    pub assets: u64,
+    pub shares: u64,
+    pub fee: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct MetaRedeemEvent {
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    pub relayer: Pubkey,
+    pub shares: u64,
+    /// USDC sent to the receiver, after the vault fee and the relayer fee
+    pub assets: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub relayer_fee: u64,
+}
+
+#[event]
+pub struct SessionRedeemEvent {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+    pub fee: u64,
+    pub remaining_assets: u64,
+}
+
+#[event]
+pub struct WindDownClaimEvent {
+    pub user: Pubkey,
+    pub shares: u64,
+    pub assets: u64,
+}
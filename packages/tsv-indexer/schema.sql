@@ -0,0 +1,73 @@
+-- Tables written by tsv-indexer, created on startup when missing. Rows are keyed by the
+-- transaction signature and the event's position among the vault's events in it, so indexing
+-- a transaction again changes nothing.
+
+CREATE TABLE IF NOT EXISTS deposits (
+  vault TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  event_index INTEGER NOT NULL,
+  slot BIGINT NOT NULL,
+  block_time TIMESTAMPTZ,
+  kind TEXT NOT NULL,
+  owner TEXT NOT NULL,
+  receiver TEXT NOT NULL,
+  assets BIGINT NOT NULL,
+  shares BIGINT NOT NULL,
+  fee BIGINT NOT NULL,
+  sequence BIGINT,
+  PRIMARY KEY (vault, signature, event_index)
+);
+
+CREATE TABLE IF NOT EXISTS redemptions (
+  vault TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  event_index INTEGER NOT NULL,
+  slot BIGINT NOT NULL,
+  block_time TIMESTAMPTZ,
+  kind TEXT NOT NULL,
+  owner TEXT NOT NULL,
+  receiver TEXT NOT NULL,
+  assets BIGINT NOT NULL,
+  shares BIGINT NOT NULL,
+  fee BIGINT NOT NULL,
+  sequence BIGINT,
+  PRIMARY KEY (vault, signature, event_index)
+);
+
+CREATE TABLE IF NOT EXISTS fee_collections (
+  vault TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  event_index INTEGER NOT NULL,
+  slot BIGINT NOT NULL,
+  block_time TIMESTAMPTZ,
+  kind TEXT NOT NULL,
+  recipient TEXT,
+  amount BIGINT NOT NULL,
+  shares BIGINT NOT NULL,
+  PRIMARY KEY (vault, signature, event_index)
+);
+
+CREATE TABLE IF NOT EXISTS share_prices (
+  vault TEXT NOT NULL,
+  sequence BIGINT NOT NULL,
+  signature TEXT NOT NULL,
+  slot BIGINT NOT NULL,
+  block_time TIMESTAMPTZ,
+  total_assets BIGINT NOT NULL,
+  total_shares BIGINT NOT NULL,
+  exchange_rate BIGINT NOT NULL,
+  PRIMARY KEY (vault, sequence)
+);
+
+CREATE INDEX IF NOT EXISTS deposits_owner ON deposits (vault, owner);
+CREATE INDEX IF NOT EXISTS redemptions_owner ON redemptions (vault, owner);
+CREATE INDEX IF NOT EXISTS deposits_receiver ON deposits (vault, receiver);
+CREATE INDEX IF NOT EXISTS redemptions_receiver ON redemptions (vault, receiver);
+CREATE INDEX IF NOT EXISTS share_prices_time ON share_prices (vault, block_time);
+
+-- Newest transaction indexed per vault, where catching up resumes
+CREATE TABLE IF NOT EXISTS indexer_cursors (
+  vault TEXT PRIMARY KEY,
+  signature TEXT NOT NULL,
+  slot BIGINT NOT NULL
+);
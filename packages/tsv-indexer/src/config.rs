@@ -0,0 +1,69 @@
+//! Indexer settings, read from the environment.
+
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::IndexerError;
+
+pub struct Config {
+    /// Solana JSON-RPC endpoint (`RPC_URL`)
+    pub rpc_url: String,
+    /// Solana websocket endpoint logs are subscribed on (`WS_URL`, default `RPC_URL` with its
+    /// scheme changed to `ws`/`wss`)
+    pub ws_url: String,
+    /// PostgreSQL connection string (`DATABASE_URL`)
+    pub database_url: String,
+    /// Asset mint of the vault to index (`VAULT_ASSET_MINT`, optional; without it the legacy
+    /// vault at the single-vault address)
+    pub vault_asset_mint: Option<Pubkey>,
+    /// Wait before resubscribing after the websocket drops (`RECONNECT_DELAY_SECS`, default 5)
+    pub reconnect_delay: Duration,
+    /// Attempts at reading a notified transaction before giving up on the subscription
+    /// (`FETCH_RETRIES`, default 5)
+    pub fetch_retries: u32,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, IndexerError> {
+        let rpc_url = required("RPC_URL")?;
+        let ws_url = env::var("WS_URL").unwrap_or_else(|_| rpc_url.replacen("http", "ws", 1));
+        let fetch_retries = parse_or("FETCH_RETRIES", 5)?;
+        if fetch_retries == 0 {
+            return Err(IndexerError::Config(
+                "FETCH_RETRIES must be positive".into(),
+            ));
+        }
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            database_url: required("DATABASE_URL")?,
+            vault_asset_mint: env::var("VAULT_ASSET_MINT")
+                .ok()
+                .map(|value| parse(&value, "VAULT_ASSET_MINT"))
+                .transpose()?,
+            reconnect_delay: Duration::from_secs(parse_or("RECONNECT_DELAY_SECS", 5)?),
+            fetch_retries,
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, IndexerError> {
+    env::var(name).map_err(|_| IndexerError::Config(format!("{name} is not set")))
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, IndexerError> {
+    value
+        .parse()
+        .map_err(|_| IndexerError::Config(format!("invalid {name}: {value}")))
+}
+
+fn parse_or<T: FromStr>(name: &str, default: T) -> Result<T, IndexerError> {
+    match env::var(name) {
+        Ok(value) => parse(&value, name),
+        Err(_) => Ok(default),
+    }
+}
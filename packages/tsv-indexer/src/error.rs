@@ -0,0 +1,33 @@
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::pubsub_client::PubsubClientError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum IndexerError {
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("subscription error: {0}")]
+    Pubsub(Box<PubsubClientError>),
+    #[error("subscription closed")]
+    SubscriptionClosed,
+    #[error("database error: {0}")]
+    Database(#[from] tokio_postgres::Error),
+    #[error("can't decode {0}")]
+    Decode(String),
+    #[error("can't decode {0}")]
+    Event(#[from] tsv_events::DecodeError),
+}
+
+impl From<ClientError> for IndexerError {
+    fn from(err: ClientError) -> Self {
+        IndexerError::Rpc(Box::new(err))
+    }
+}
+
+impl From<PubsubClientError> for IndexerError {
+    fn from(err: PubsubClientError) -> Self {
+        IndexerError::Pubsub(Box::new(err))
+    }
+}
@@ -0,0 +1,554 @@
+//! Vault events reduced to the rows the indexer stores.
+
+use solana_sdk::pubkey::Pubkey;
+use tsv_events::{v0, VaultEvent, VaultSnapshot};
+
+/// USDC moving into or out of the vault for shares
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Flow {
+    /// Instruction family, e.g. `deposit`, `meta`, `basket`, `migration`
+    pub kind: &'static str,
+    pub owner: Pubkey,
+    pub receiver: Pubkey,
+    /// USDC, or the USDC value of basket assets, net of `fee` for redemptions
+    pub assets: u64,
+    pub shares: u64,
+    pub fee: u64,
+    /// `VaultSnapshot::sequence` of the event, `None` for events without a snapshot
+    pub sequence: Option<u64>,
+}
+
+/// Fee taken by the vault, in USDC or in shares minted to the treasury
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fee {
+    /// `collected`, `management` or `performance`
+    pub kind: &'static str,
+    pub recipient: Option<Pubkey>,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+/// Vault totals after an event, from its `VaultSnapshot`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharePrice {
+    pub sequence: u64,
+    pub total_assets: u64,
+    pub total_shares: u64,
+    /// Assets redeemable for `EXCHANGE_RATE_SCALE` shares
+    pub exchange_rate: u64,
+}
+
+impl From<&VaultSnapshot> for SharePrice {
+    fn from(snapshot: &VaultSnapshot) -> Self {
+        Self {
+            sequence: snapshot.sequence,
+            total_assets: snapshot.total_assets,
+            total_shares: snapshot.total_shares,
+            exchange_rate: snapshot.exchange_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    Deposit(Flow),
+    Redemption(Flow),
+    Fee(Fee),
+    SharePrice(SharePrice),
+}
+
+/// Rows for one event of `vault`; events the indexer doesn't store give none
+pub fn decode(event: &VaultEvent, vault: &Pubkey) -> Vec<Record> {
+    match event {
+        VaultEvent::DepositEvent(event) => deposit(
+            "deposit",
+            event.user,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::MetaDepositEvent(event) => deposit(
+            "meta",
+            event.owner,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::BasketDepositEvent(event) => deposit(
+            "basket",
+            event.user,
+            event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::JuniorDepositEvent(event) => deposit(
+            "junior",
+            event.user,
+            event.user,
+            event.assets,
+            event.shares,
+            0,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::RedeemEvent(event) => redemption(
+            "redeem",
+            event.user,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::InstantRedeemEvent(event) => redemption(
+            "instant",
+            event.user,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::MetaRedeemEvent(event) => redemption(
+            "meta",
+            event.owner,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::SessionRedeemEvent(event) => redemption(
+            "session",
+            event.owner,
+            event.owner,
+            event.assets,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::BasketRedeemEvent(event) => redemption(
+            "basket",
+            event.user,
+            event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::ProportionalRedeemEvent(event) => redemption(
+            "proportional",
+            event.user,
+            event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::JuniorRedeemEvent(event) => redemption(
+            "junior",
+            event.user,
+            event.user,
+            event.assets,
+            event.shares,
+            0,
+            Some(&event.snapshot),
+        ),
+        VaultEvent::WindDownClaimEvent(event) => redemption(
+            "wind_down",
+            event.user,
+            event.user,
+            event.assets,
+            event.shares,
+            0,
+            Some(&event.snapshot),
+        ),
+        // The fee was taken when the epoch was processed
+        VaultEvent::WithdrawClaimedEvent(event) => redemption(
+            "queued",
+            event.owner,
+            event.owner,
+            event.assets,
+            event.shares,
+            0,
+            None,
+        ),
+        VaultEvent::PositionMigratedEvent(event) => migration(
+            vault,
+            event.user,
+            event.source_vault,
+            event.target_vault,
+            event.shares,
+            event.assets,
+            event.target_shares,
+            Some(&event.source_snapshot),
+            Some(&event.target_snapshot),
+        ),
+        VaultEvent::FeesCollectedEvent(event) => vec![Record::Fee(Fee {
+            kind: "collected",
+            recipient: Some(event.fee_recipient),
+            amount: event.amount,
+            shares: 0,
+        })],
+        VaultEvent::ManagementFeeAccruedEvent(event) => vec![Record::Fee(Fee {
+            kind: "management",
+            recipient: None,
+            amount: 0,
+            shares: event.shares,
+        })],
+        VaultEvent::HarvestEvent(event) if event.fee_shares > 0 => vec![Record::Fee(Fee {
+            kind: "performance",
+            recipient: None,
+            amount: event.performance_fee,
+            shares: event.fee_shares,
+        })],
+        VaultEvent::V0(event) => decode_v0(event, vault),
+        _ => Vec::new(),
+    }
+}
+
+/// Rows for an event written before events carried snapshots: its flow, without a sequence
+/// or share price
+fn decode_v0(event: &v0::Event, vault: &Pubkey) -> Vec<Record> {
+    match event {
+        v0::Event::DepositEvent(event) => deposit(
+            "deposit",
+            event.user,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::MetaDepositEvent(event) => deposit(
+            "meta",
+            event.owner,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::BasketDepositEvent(event) => deposit(
+            "basket",
+            event.user,
+            event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::JuniorDepositEvent(event) => deposit(
+            "junior",
+            event.user,
+            event.user,
+            event.assets,
+            event.shares,
+            0,
+            None,
+        ),
+        v0::Event::RedeemEvent(event) => redemption(
+            "redeem",
+            event.user,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::InstantRedeemEvent(event) => redemption(
+            "instant",
+            event.user,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::MetaRedeemEvent(event) => redemption(
+            "meta",
+            event.owner,
+            event.receiver,
+            event.assets,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::SessionRedeemEvent(event) => redemption(
+            "session",
+            event.owner,
+            event.owner,
+            event.assets,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::BasketRedeemEvent(event) => redemption(
+            "basket",
+            event.user,
+            event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::ProportionalRedeemEvent(event) => redemption(
+            "proportional",
+            event.user,
+            event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            None,
+        ),
+        v0::Event::JuniorRedeemEvent(event) => redemption(
+            "junior",
+            event.user,
+            event.user,
+            event.assets,
+            event.shares,
+            0,
+            None,
+        ),
+        v0::Event::WindDownClaimEvent(event) => redemption(
+            "wind_down",
+            event.user,
+            event.user,
+            event.assets,
+            event.shares,
+            0,
+            None,
+        ),
+        v0::Event::PositionMigratedEvent(event) => migration(
+            vault,
+            event.user,
+            event.source_vault,
+            event.target_vault,
+            event.shares,
+            event.assets,
+            event.target_shares,
+            None,
+            None,
+        ),
+    }
+}
+
+/// A redemption of the source vault and a deposit of the target, whichever is `vault`
+#[allow(clippy::too_many_arguments)]
+fn migration(
+    vault: &Pubkey,
+    user: Pubkey,
+    source_vault: Pubkey,
+    target_vault: Pubkey,
+    shares: u64,
+    assets: u64,
+    target_shares: u64,
+    source_snapshot: Option<&VaultSnapshot>,
+    target_snapshot: Option<&VaultSnapshot>,
+) -> Vec<Record> {
+    let mut records = Vec::new();
+    if source_vault == *vault {
+        records.extend(redemption(
+            "migration",
+            user,
+            user,
+            assets,
+            shares,
+            0,
+            source_snapshot,
+        ));
+    }
+    if target_vault == *vault {
+        records.extend(deposit(
+            "migration",
+            user,
+            user,
+            assets,
+            target_shares,
+            0,
+            target_snapshot,
+        ));
+    }
+    records
+}
+
+fn deposit(
+    kind: &'static str,
+    owner: Pubkey,
+    receiver: Pubkey,
+    assets: u64,
+    shares: u64,
+    fee: u64,
+    snapshot: Option<&VaultSnapshot>,
+) -> Vec<Record> {
+    let flow = Flow {
+        kind,
+        owner,
+        receiver,
+        assets,
+        shares,
+        fee,
+        sequence: snapshot.map(|snapshot| snapshot.sequence),
+    };
+    [Record::Deposit(flow)]
+        .into_iter()
+        .chain(snapshot.map(|snapshot| Record::SharePrice(snapshot.into())))
+        .collect()
+}
+
+fn redemption(
+    kind: &'static str,
+    owner: Pubkey,
+    receiver: Pubkey,
+    assets: u64,
+    shares: u64,
+    fee: u64,
+    snapshot: Option<&VaultSnapshot>,
+) -> Vec<Record> {
+    let flow = Flow {
+        kind,
+        owner,
+        receiver,
+        assets,
+        shares,
+        fee,
+        sequence: snapshot.map(|snapshot| snapshot.sequence),
+    };
+    [Record::Redemption(flow)]
+        .into_iter()
+        .chain(snapshot.map(|snapshot| Record::SharePrice(snapshot.into())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tsv_events::{DepositEvent, GuardianPauseEvent, PositionMigratedEvent};
+
+    use super::*;
+
+    fn snapshot(sequence: u64) -> VaultSnapshot {
+        VaultSnapshot {
+            sequence,
+            total_assets: 2_000_000,
+            total_shares: 1_000_000,
+            exchange_rate: 2_000_000_000,
+        }
+    }
+
+    #[test]
+    fn decodes_deposit_and_its_share_price() {
+        let vault = Pubkey::new_unique();
+        let event = DepositEvent {
+            user: Pubkey::new_unique(),
+            receiver: Pubkey::new_unique(),
+            assets: 1_000,
+            shares: 500,
+            fee: 0,
+            snapshot: snapshot(7),
+        };
+        let (user, receiver) = (event.user, event.receiver);
+
+        let records = decode(&VaultEvent::DepositEvent(event), &vault);
+
+        assert_eq!(
+            records,
+            vec![
+                Record::Deposit(Flow {
+                    kind: "deposit",
+                    owner: user,
+                    receiver,
+                    assets: 1_000,
+                    shares: 500,
+                    fee: 0,
+                    sequence: Some(7),
+                }),
+                Record::SharePrice((&snapshot(7)).into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_version_0_redemptions_without_a_share_price() {
+        let vault = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let event = v0::RedeemEvent {
+            user,
+            receiver: user,
+            shares: 500,
+            assets: 990,
+            fee: 10,
+        };
+
+        let records = decode(&VaultEvent::V0(v0::Event::RedeemEvent(event)), &vault);
+
+        assert_eq!(
+            records,
+            vec![Record::Redemption(Flow {
+                kind: "redeem",
+                owner: user,
+                receiver: user,
+                assets: 990,
+                shares: 500,
+                fee: 10,
+                sequence: None,
+            })]
+        );
+    }
+
+    #[test]
+    fn migration_is_a_redemption_of_the_source_and_a_deposit_of_the_target() {
+        let source = Pubkey::new_unique();
+        let target = Pubkey::new_unique();
+        let event = VaultEvent::PositionMigratedEvent(PositionMigratedEvent {
+            user: Pubkey::new_unique(),
+            source_vault: source,
+            target_vault: target,
+            shares: 100,
+            assets: 200,
+            target_shares: 150,
+            source_snapshot: snapshot(3),
+            target_snapshot: snapshot(9),
+        });
+
+        let source_records = decode(&event, &source);
+        let target_records = decode(&event, &target);
+
+        assert!(matches!(
+            &source_records[..],
+            [
+                Record::Redemption(Flow {
+                    shares: 100,
+                    sequence: Some(3),
+                    ..
+                }),
+                Record::SharePrice(_)
+            ]
+        ));
+        assert!(matches!(
+            &target_records[..],
+            [
+                Record::Deposit(Flow {
+                    shares: 150,
+                    sequence: Some(9),
+                    ..
+                }),
+                Record::SharePrice(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn ignores_events_it_does_not_store() {
+        let vault = Pubkey::new_unique();
+        let event = GuardianPauseEvent {
+            guardian: Pubkey::new_unique(),
+        };
+
+        assert!(decode(&VaultEvent::GuardianPauseEvent(event), &vault).is_empty());
+    }
+}
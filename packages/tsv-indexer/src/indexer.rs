@@ -0,0 +1,165 @@
+//! Reads the vault's transactions from the RPC, live over a logs subscription or from the
+//! signature history, and stores their events.
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{
+    RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use tsv_events::VaultEvent;
+
+use crate::error::IndexerError;
+use crate::events;
+use crate::store::{Store, TransactionRecords};
+
+/// Most signatures `getSignaturesForAddress` returns per call
+const SIGNATURE_PAGE: usize = 1_000;
+
+pub struct Indexer {
+    pub rpc: RpcClient,
+    pub store: Store,
+    pub vault: Pubkey,
+    pub fetch_retries: u32,
+}
+
+impl Indexer {
+    /// Index every successful transaction of the vault after `until`, or its whole history
+    /// without it, oldest first. Returns how many were indexed
+    pub async fn backfill(&mut self, until: Option<Signature>) -> Result<usize, IndexerError> {
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let page = self
+                .rpc
+                .get_signatures_for_address_with_config(
+                    &self.vault,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(SIGNATURE_PAGE),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                    },
+                )
+                .await?;
+            let Some(last) = page.last() else { break };
+            before = Some(parse_signature(&last.signature)?);
+            let full = page.len() == SIGNATURE_PAGE;
+            for status in page {
+                if status.err.is_none() {
+                    signatures.push(parse_signature(&status.signature)?);
+                }
+            }
+            if !full {
+                break;
+            }
+        }
+
+        let count = signatures.len();
+        for signature in signatures.iter().rev() {
+            self.index(signature).await?;
+        }
+        Ok(count)
+    }
+
+    /// Subscribe to the vault's transactions, catch up on those since the cursor, then index
+    /// each new one as it's confirmed. Returns when the subscription drops
+    pub async fn follow(&mut self, ws_url: &str) -> Result<(), IndexerError> {
+        let pubsub = PubsubClient::new(ws_url).await?;
+        // Subscribed before catching up so nothing lands in between; seeing a transaction twice
+        // is harmless
+        let (mut notifications, _unsubscribe) = pubsub
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![self.vault.to_string()]),
+                RpcTransactionLogsConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+
+        let cursor = self.store.cursor().await?;
+        let caught_up = self.backfill(cursor).await?;
+        tracing::info!(caught_up, "following");
+
+        while let Some(notification) = notifications.next().await {
+            if notification.value.err.is_some() {
+                continue;
+            }
+            self.index(&parse_signature(&notification.value.signature)?)
+                .await?;
+        }
+        Err(IndexerError::SubscriptionClosed)
+    }
+
+    /// Store the vault's events of a successful transaction
+    async fn index(&mut self, signature: &Signature) -> Result<(), IndexerError> {
+        let transaction = self.fetch(signature).await?;
+        let Some(payloads) = tsv_events::vault_events(&transaction, &self.vault)? else {
+            return Ok(());
+        };
+
+        let mut records = Vec::new();
+        for (index, payload) in payloads.iter().enumerate() {
+            match VaultEvent::decode(payload) {
+                Ok(event) => records
+                    .push(event.map_or_else(Vec::new, |event| events::decode(&event, &self.vault))),
+                // Kept in place so later events keep their index
+                Err(err) => {
+                    tracing::warn!(%signature, index, %err, "skipping event");
+                    records.push(Vec::new());
+                }
+            }
+        }
+        self.store
+            .save(&TransactionRecords {
+                signature: *signature,
+                slot: transaction.slot,
+                block_time: transaction.block_time,
+                events: records,
+            })
+            .await?;
+        tracing::debug!(%signature, slot = transaction.slot, "indexed");
+        Ok(())
+    }
+
+    /// Read a confirmed transaction, retrying while the node doesn't serve it yet
+    async fn fetch(
+        &self,
+        signature: &Signature,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta, IndexerError> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
+        let mut attempt = 1;
+        loop {
+            match self
+                .rpc
+                .get_transaction_with_config(signature, config)
+                .await
+            {
+                Ok(transaction) => return Ok(transaction),
+                Err(err) if attempt < self.fetch_retries => {
+                    tracing::debug!(%signature, attempt, %err, "transaction not available yet");
+                    tokio::time::sleep(Duration::from_millis(500 * u64::from(attempt))).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, IndexerError> {
+    signature
+        .parse()
+        .map_err(|_| IndexerError::Decode(format!("signature {signature}")))
+}
@@ -0,0 +1,56 @@
+//! Indexer for the Talken Stable Vault: follows the vault's transactions over an RPC websocket,
+//! decodes the events the program emits through self-CPI, and materializes deposits,
+//! redemptions, fees and share-price history into PostgreSQL. `tsv-indexer backfill` walks the
+//! vault's whole signature history once and exits.
+
+mod config;
+mod error;
+mod events;
+mod indexer;
+mod store;
+mod vault;
+
+use std::env;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::Config;
+use crate::error::IndexerError;
+use crate::indexer::Indexer;
+use crate::store::Store;
+
+#[tokio::main]
+async fn main() -> Result<(), IndexerError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let backfill = match env::args().nth(1).as_deref() {
+        None => false,
+        Some("backfill") => true,
+        Some(other) => return Err(IndexerError::Config(format!("unknown command {other}"))),
+    };
+    let config = Config::from_env()?;
+    let vault = vault::address(config.vault_asset_mint);
+    let mut indexer = Indexer {
+        rpc: RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::confirmed()),
+        store: Store::connect(&config.database_url, &vault).await?,
+        vault,
+        fetch_retries: config.fetch_retries,
+    };
+    tracing::info!(%vault, backfill, "starting");
+
+    if backfill {
+        let indexed = indexer.backfill(None).await?;
+        tracing::info!(indexed, "backfilled");
+        return Ok(());
+    }
+    loop {
+        if let Err(err) = indexer.follow(&config.ws_url).await {
+            tracing::warn!(%err, "subscription ended, resubscribing");
+        }
+        tokio::time::sleep(config.reconnect_delay).await;
+    }
+}
@@ -0,0 +1,191 @@
+//! PostgreSQL tables the indexer writes, see `schema.sql`.
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tokio_postgres::{Client, NoTls, Transaction};
+
+use crate::error::IndexerError;
+use crate::events::{Fee, Flow, Record, SharePrice};
+
+const SCHEMA: &str = include_str!("../schema.sql");
+
+/// Rows of one confirmed transaction, written together
+pub struct TransactionRecords {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Unix time of the block, when the node still has it
+    pub block_time: Option<i64>,
+    /// Rows of each of the vault's events, in emission order
+    pub events: Vec<Vec<Record>>,
+}
+
+pub struct Store {
+    client: Client,
+    /// Vault the rows belong to, base58
+    vault: String,
+}
+
+impl Store {
+    /// Connect and create the tables that are missing
+    pub async fn connect(url: &str, vault: &Pubkey) -> Result<Self, IndexerError> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::error!(%err, "database connection closed");
+            }
+        });
+        client.batch_execute(SCHEMA).await?;
+        Ok(Self {
+            client,
+            vault: vault.to_string(),
+        })
+    }
+
+    /// Newest transaction indexed, `None` before the first
+    pub async fn cursor(&self) -> Result<Option<Signature>, IndexerError> {
+        let row = self
+            .client
+            .query_opt(
+                "SELECT signature FROM indexer_cursors WHERE vault = $1",
+                &[&self.vault],
+            )
+            .await?;
+        row.map(|row| {
+            let signature: String = row.get(0);
+            signature
+                .parse()
+                .map_err(|_| IndexerError::Decode(format!("cursor signature {signature}")))
+        })
+        .transpose()
+    }
+
+    /// Write a transaction's rows and move the cursor to it unless it's older; rows already
+    /// written are left as they are
+    pub async fn save(&mut self, records: &TransactionRecords) -> Result<(), IndexerError> {
+        let signature = records.signature.to_string();
+        let slot = int(records.slot)?;
+        let tx = self.client.transaction().await?;
+        for (index, event) in records.events.iter().enumerate() {
+            let row = Row {
+                vault: &self.vault,
+                signature: &signature,
+                event_index: index as i32,
+                slot,
+                block_time: records.block_time,
+            };
+            for record in event {
+                match record {
+                    Record::Deposit(flow) => row.flow(&tx, "deposits", flow).await?,
+                    Record::Redemption(flow) => row.flow(&tx, "redemptions", flow).await?,
+                    Record::Fee(fee) => row.fee(&tx, fee).await?,
+                    Record::SharePrice(price) => row.share_price(&tx, price).await?,
+                }
+            }
+        }
+        tx.execute(
+            "INSERT INTO indexer_cursors (vault, signature, slot) VALUES ($1, $2, $3)
+             ON CONFLICT (vault) DO UPDATE SET signature = EXCLUDED.signature, slot = EXCLUDED.slot
+             WHERE indexer_cursors.slot <= EXCLUDED.slot",
+            &[&self.vault, &signature, &slot],
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Key and time columns shared by the rows of one event
+struct Row<'a> {
+    vault: &'a str,
+    signature: &'a str,
+    event_index: i32,
+    slot: i64,
+    block_time: Option<i64>,
+}
+
+impl Row<'_> {
+    async fn flow(
+        &self,
+        tx: &Transaction<'_>,
+        table: &str,
+        flow: &Flow,
+    ) -> Result<(), IndexerError> {
+        let sequence = flow.sequence.map(int).transpose()?;
+        tx.execute(
+            &format!(
+                "INSERT INTO {table} (vault, signature, event_index, slot, block_time, kind, owner,
+                   receiver, assets, shares, fee, sequence)
+                 VALUES ($1, $2, $3, $4, to_timestamp($5::BIGINT), $6, $7, $8, $9, $10, $11, $12)
+                 ON CONFLICT DO NOTHING"
+            ),
+            &[
+                &self.vault,
+                &self.signature,
+                &self.event_index,
+                &self.slot,
+                &self.block_time,
+                &flow.kind,
+                &flow.owner.to_string(),
+                &flow.receiver.to_string(),
+                &int(flow.assets)?,
+                &int(flow.shares)?,
+                &int(flow.fee)?,
+                &sequence,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn fee(&self, tx: &Transaction<'_>, fee: &Fee) -> Result<(), IndexerError> {
+        tx.execute(
+            "INSERT INTO fee_collections (vault, signature, event_index, slot, block_time, kind,
+               recipient, amount, shares)
+             VALUES ($1, $2, $3, $4, to_timestamp($5::BIGINT), $6, $7, $8, $9)
+             ON CONFLICT DO NOTHING",
+            &[
+                &self.vault,
+                &self.signature,
+                &self.event_index,
+                &self.slot,
+                &self.block_time,
+                &fee.kind,
+                &fee.recipient.map(|recipient| recipient.to_string()),
+                &int(fee.amount)?,
+                &int(fee.shares)?,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn share_price(
+        &self,
+        tx: &Transaction<'_>,
+        price: &SharePrice,
+    ) -> Result<(), IndexerError> {
+        tx.execute(
+            "INSERT INTO share_prices (vault, sequence, signature, slot, block_time, total_assets,
+               total_shares, exchange_rate)
+             VALUES ($1, $2, $3, $4, to_timestamp($5::BIGINT), $6, $7, $8)
+             ON CONFLICT DO NOTHING",
+            &[
+                &self.vault,
+                &int(price.sequence)?,
+                &self.signature,
+                &self.slot,
+                &self.block_time,
+                &int(price.total_assets)?,
+                &int(price.total_shares)?,
+                &int(price.exchange_rate)?,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// `BIGINT` column value; base units of any real USDC amount fit
+fn int(value: u64) -> Result<i64, IndexerError> {
+    i64::try_from(value).map_err(|_| IndexerError::Decode(format!("{value} as BIGINT")))
+}
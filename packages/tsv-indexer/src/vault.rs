@@ -0,0 +1,12 @@
+//! Address of the indexed vault, mirroring the program's seeds.
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Vault of `asset_mint`, or the legacy `[b"vault"]` one without it
+pub fn address(asset_mint: Option<Pubkey>) -> Pubkey {
+    let seeds: &[&[u8]] = match &asset_mint {
+        Some(asset_mint) => &[b"vault", asset_mint.as_ref()],
+        None => &[b"vault"],
+    };
+    Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0
+}
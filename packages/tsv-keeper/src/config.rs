@@ -0,0 +1,148 @@
+//! Keeper settings, read from the environment.
+
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+
+use crate::error::KeeperError;
+
+pub struct Config {
+    /// Solana JSON-RPC endpoint (`RPC_URL`)
+    pub rpc_url: String,
+    /// Fee payer holding the vault's keeper role (`KEEPER_KEYPAIR`, a keypair JSON file)
+    pub keeper: Keypair,
+    /// Asset mint of the vault to serve (`VAULT_ASSET_MINT`, optional; without it the legacy
+    /// vault at the single-vault address)
+    pub vault_asset_mint: Option<Pubkey>,
+    /// Adapter accounts of the vault's strategies (`STRATEGIES_FILE`, JSON, optional; without it
+    /// only fees are accrued)
+    pub strategies_file: Option<String>,
+    /// Prometheus `/metrics` listen address (`METRICS_ADDR`, default 0.0.0.0:9100)
+    pub metrics_addr: SocketAddr,
+    /// How often vault state is read into the metrics (`MONITOR_INTERVAL_SECS`, default 30)
+    pub monitor_interval: Duration,
+    /// How often each strategy is harvested (`HARVEST_INTERVAL_SECS`, default 3600, 0 disables)
+    pub harvest_interval: Option<Duration>,
+    /// How often allocations are moved toward their targets (`REBALANCE_INTERVAL_SECS`, default
+    /// 900, 0 disables)
+    pub rebalance_interval: Option<Duration>,
+    /// How often management fees are minted to the treasury (`ACCRUE_FEES_INTERVAL_SECS`,
+    /// default 86400, 0 disables)
+    pub accrue_fees_interval: Option<Duration>,
+    /// How often the USDC price is checked against the vault's depeg breaker
+    /// (`PRICE_CHECK_INTERVAL_SECS`, default 60, 0 disables)
+    pub price_check_interval: Option<Duration>,
+    /// How often the withdrawal queue's epoch is settled once it has requests
+    /// (`PROCESS_EPOCH_INTERVAL_SECS`, default 86400, 0 disables)
+    pub process_epoch_interval: Option<Duration>,
+    /// How often the vault's rate history is checked for a due daily snapshot
+    /// (`RECORD_RATE_INTERVAL_SECS`, default 3600, 0 disables)
+    pub record_rate_interval: Option<Duration>,
+    /// Smallest allocation change worth a transaction (`MIN_REBALANCE_AMOUNT`, USDC base units,
+    /// default 1000000000)
+    pub min_rebalance_amount: u64,
+    /// Address lookup table holding the vault's static accounts (`LOOKUP_TABLE`, optional)
+    pub lookup_table: Option<Pubkey>,
+    /// Compute unit limit set on every transaction (`COMPUTE_UNIT_LIMIT`, default 1000000)
+    pub compute_unit_limit: u32,
+    /// Bounds of the priority fee in micro-lamports per compute unit
+    /// (`MIN_PRIORITY_FEE`/`MAX_PRIORITY_FEE`, default 0/1000000)
+    pub min_priority_fee: u64,
+    pub max_priority_fee: u64,
+    /// Percentile of recent prioritization fees to pay (`PRIORITY_FEE_PERCENTILE`, default 75)
+    pub priority_fee_percentile: u8,
+    /// Resubmissions with a fresh blockhash and a higher fee before giving up (`MAX_RETRIES`,
+    /// default 3)
+    pub max_retries: u32,
+    /// Jito block engine transactions are sent to as bundles instead of through the RPC
+    /// (`JITO_URL`, optional)
+    pub jito_url: Option<String>,
+    /// Tip paid to Jito with each bundle (`JITO_TIP_LAMPORTS`, default 10000)
+    pub jito_tip_lamports: u64,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        let min_priority_fee = parse_or("MIN_PRIORITY_FEE", 0)?;
+        let max_priority_fee = parse_or("MAX_PRIORITY_FEE", 1_000_000)?;
+        if min_priority_fee > max_priority_fee {
+            return Err(KeeperError::Config(
+                "MIN_PRIORITY_FEE exceeds MAX_PRIORITY_FEE".into(),
+            ));
+        }
+        let priority_fee_percentile = parse_or("PRIORITY_FEE_PERCENTILE", 75)?;
+        if priority_fee_percentile > 100 {
+            return Err(KeeperError::Config(
+                "PRIORITY_FEE_PERCENTILE must be at most 100".into(),
+            ));
+        }
+        let monitor_interval = parse_or("MONITOR_INTERVAL_SECS", 30)?;
+        if monitor_interval == 0 {
+            return Err(KeeperError::Config(
+                "MONITOR_INTERVAL_SECS must be positive".into(),
+            ));
+        }
+
+        let keypair_path = required("KEEPER_KEYPAIR")?;
+        let keeper = read_keypair_file(&keypair_path).map_err(|err| {
+            KeeperError::Config(format!("can't read KEEPER_KEYPAIR {keypair_path}: {err}"))
+        })?;
+
+        Ok(Self {
+            rpc_url: required("RPC_URL")?,
+            keeper,
+            vault_asset_mint: env::var("VAULT_ASSET_MINT")
+                .ok()
+                .map(|value| parse(&value, "VAULT_ASSET_MINT"))
+                .transpose()?,
+            strategies_file: env::var("STRATEGIES_FILE").ok(),
+            metrics_addr: parse_or("METRICS_ADDR", SocketAddr::from(([0, 0, 0, 0], 9100)))?,
+            monitor_interval: Duration::from_secs(monitor_interval),
+            harvest_interval: interval_or("HARVEST_INTERVAL_SECS", 3_600)?,
+            rebalance_interval: interval_or("REBALANCE_INTERVAL_SECS", 900)?,
+            accrue_fees_interval: interval_or("ACCRUE_FEES_INTERVAL_SECS", 86_400)?,
+            price_check_interval: interval_or("PRICE_CHECK_INTERVAL_SECS", 60)?,
+            process_epoch_interval: interval_or("PROCESS_EPOCH_INTERVAL_SECS", 86_400)?,
+            record_rate_interval: interval_or("RECORD_RATE_INTERVAL_SECS", 3_600)?,
+            min_rebalance_amount: parse_or("MIN_REBALANCE_AMOUNT", 1_000_000_000)?,
+            lookup_table: env::var("LOOKUP_TABLE")
+                .ok()
+                .map(|value| parse(&value, "LOOKUP_TABLE"))
+                .transpose()?,
+            compute_unit_limit: parse_or("COMPUTE_UNIT_LIMIT", 1_000_000)?,
+            min_priority_fee,
+            max_priority_fee,
+            priority_fee_percentile,
+            max_retries: parse_or("MAX_RETRIES", 3)?,
+            jito_url: env::var("JITO_URL").ok(),
+            jito_tip_lamports: parse_or("JITO_TIP_LAMPORTS", 10_000)?,
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, KeeperError> {
+    env::var(name).map_err(|_| KeeperError::Config(format!("{name} is not set")))
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, KeeperError> {
+    value
+        .parse()
+        .map_err(|_| KeeperError::Config(format!("invalid {name}: {value}")))
+}
+
+fn parse_or<T: FromStr>(name: &str, default: T) -> Result<T, KeeperError> {
+    match env::var(name) {
+        Ok(value) => parse(&value, name),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Job schedule in seconds from `name`, `None` when set to 0
+fn interval_or(name: &str, default_secs: u64) -> Result<Option<Duration>, KeeperError> {
+    let secs = parse_or(name, default_secs)?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
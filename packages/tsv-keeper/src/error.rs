@@ -0,0 +1,24 @@
+use solana_client::client_error::ClientError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KeeperError {
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("account {0} not found")]
+    AccountNotFound(String),
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("transaction not confirmed after {0} attempts")]
+    NotConfirmed(u32),
+    #[error("transaction failed: {0}")]
+    TransactionFailed(String),
+    #[error("bundle error: {0}")]
+    Bundle(String),
+}
+
+impl From<ClientError> for KeeperError {
+    fn from(err: ClientError) -> Self {
+        KeeperError::Rpc(Box::new(err))
+    }
+}
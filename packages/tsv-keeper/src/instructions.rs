@@ -0,0 +1,320 @@
+//! Builds the vault's keeper instructions, each preceded by the pre-instructions of the
+//! strategies it reaches.
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+use tsv_usdc_vault::Strategy;
+
+use crate::strategies::StrategyConfig;
+use crate::vault::{self, VaultState};
+
+/// `harvest` of one strategy; strategies are harvested one per transaction so any adapter's
+/// accounts fit
+pub fn harvest(
+    vault: &VaultState,
+    keeper: &Pubkey,
+    config: &StrategyConfig,
+    strategy: &Strategy,
+) -> Vec<Instruction> {
+    let mut accounts = tsv_usdc_vault::accounts::Harvest {
+        vault: vault.address,
+        keeper: *keeper,
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        token_program: vault.token_program,
+        event_authority: vault::event_authority(),
+        program: tsv_usdc_vault::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(strategy_item(vault, config, strategy));
+
+    let mut instructions = config.pre_instructions.clone();
+    instructions.push(Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts,
+        data: tsv_usdc_vault::instruction::Harvest {
+            adapter_accounts: vec![config.accounts.len() as u8],
+        }
+        .data(),
+    });
+    instructions
+}
+
+/// `allocate` of `amount` idle USDC to a strategy
+pub fn allocate(
+    vault: &VaultState,
+    keeper: &Pubkey,
+    config: &StrategyConfig,
+    strategy: &Strategy,
+    amount: u64,
+) -> Vec<Instruction> {
+    strategy_transfer(
+        vault,
+        keeper,
+        config,
+        strategy,
+        tsv_usdc_vault::instruction::Allocate { amount }.data(),
+    )
+}
+
+/// `deallocate` of `amount` from a strategy back into the vault
+pub fn deallocate(
+    vault: &VaultState,
+    keeper: &Pubkey,
+    config: &StrategyConfig,
+    strategy: &Strategy,
+    amount: u64,
+) -> Vec<Instruction> {
+    strategy_transfer(
+        vault,
+        keeper,
+        config,
+        strategy,
+        tsv_usdc_vault::instruction::Deallocate { amount }.data(),
+    )
+}
+
+/// `rebalance` of `amount` from one strategy straight into another
+pub fn rebalance(
+    vault: &VaultState,
+    keeper: &Pubkey,
+    (from_config, from): (&StrategyConfig, &Strategy),
+    (to_config, to): (&StrategyConfig, &Strategy),
+    amount: u64,
+) -> Vec<Instruction> {
+    let mut accounts = tsv_usdc_vault::accounts::Rebalance {
+        vault: vault.address,
+        keeper: *keeper,
+        from_strategy: vault.strategy(&from.adapter_program),
+        from_adapter_program: from.adapter_program,
+        from_reserve: from.reserve,
+        to_strategy: vault.strategy(&to.adapter_program),
+        to_adapter_program: to.adapter_program,
+        to_reserve: to.reserve,
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        token_program: vault.token_program,
+        event_authority: vault::event_authority(),
+        program: tsv_usdc_vault::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend_from_slice(&from_config.accounts);
+    accounts.extend_from_slice(&to_config.accounts);
+
+    let mut instructions = from_config.pre_instructions.clone();
+    instructions.extend_from_slice(&to_config.pre_instructions);
+    instructions.push(Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts,
+        data: tsv_usdc_vault::instruction::Rebalance {
+            amount,
+            from_accounts: from_config.accounts.len() as u8,
+        }
+        .data(),
+    });
+    instructions
+}
+
+/// `accrue_fees`, minting the accrued management fee shares to the treasury
+pub fn accrue_fees(vault: &VaultState) -> Instruction {
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: tsv_usdc_vault::accounts::AccrueFees {
+            vault: vault.address,
+            share_mint: vault.share_mint,
+            treasury_shares: vault.treasury_shares,
+            token_program: vault.token_program,
+            event_authority: vault::event_authority(),
+            program: tsv_usdc_vault::ID,
+        }
+        .to_account_metas(None),
+        data: tsv_usdc_vault::instruction::AccrueFees {}.data(),
+    }
+}
+
+/// `record_rate`, snapshotting the exchange rate into the vault's `RateHistory`
+pub fn record_rate(vault: &VaultState) -> Instruction {
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: tsv_usdc_vault::accounts::RecordRate {
+            vault: vault.address,
+            rate_history: vault.rate_history(),
+            event_authority: vault::event_authority(),
+            program: tsv_usdc_vault::ID,
+        }
+        .to_account_metas(None),
+        data: tsv_usdc_vault::instruction::RecordRate {}.data(),
+    }
+}
+
+/// `process_epoch` of withdrawal epoch `epoch`, deallocating from `sources` in order where the
+/// idle USDC falls short
+pub fn process_epoch(
+    vault: &VaultState,
+    keeper: &Pubkey,
+    epoch: u64,
+    sources: &[(&StrategyConfig, &Strategy)],
+) -> Vec<Instruction> {
+    let mut accounts = tsv_usdc_vault::accounts::ProcessEpoch {
+        vault: vault.address,
+        keeper: *keeper,
+        withdraw_epoch: vault.withdraw_epoch(epoch),
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        token_program: vault.token_program,
+        system_program: system_program::ID,
+        event_authority: vault::event_authority(),
+        program: tsv_usdc_vault::ID,
+    }
+    .to_account_metas(None);
+    let mut instructions = Vec::new();
+    for (config, strategy) in sources {
+        accounts.extend(strategy_item(vault, config, strategy));
+        instructions.extend_from_slice(&config.pre_instructions);
+    }
+
+    instructions.push(Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts,
+        data: tsv_usdc_vault::instruction::ProcessEpoch {
+            adapter_accounts: sources
+                .iter()
+                .map(|(config, _)| config.accounts.len() as u8)
+                .collect(),
+        }
+        .data(),
+    });
+    instructions
+}
+
+/// `check_price` against the vault's configured price feed
+pub fn check_price(vault: &VaultState, price_feed: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts: tsv_usdc_vault::accounts::CheckPrice {
+            vault: vault.address,
+            price_update: *price_feed,
+            event_authority: vault::event_authority(),
+            program: tsv_usdc_vault::ID,
+        }
+        .to_account_metas(None),
+        data: tsv_usdc_vault::instruction::CheckPrice {}.data(),
+    }
+}
+
+fn strategy_transfer(
+    vault: &VaultState,
+    keeper: &Pubkey,
+    config: &StrategyConfig,
+    strategy: &Strategy,
+    data: Vec<u8>,
+) -> Vec<Instruction> {
+    let mut accounts = tsv_usdc_vault::accounts::StrategyTransfer {
+        vault: vault.address,
+        keeper: *keeper,
+        strategy: vault.strategy(&strategy.adapter_program),
+        adapter_program: strategy.adapter_program,
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        reserve: strategy.reserve,
+        token_program: vault.token_program,
+        event_authority: vault::event_authority(),
+        program: tsv_usdc_vault::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend_from_slice(&config.accounts);
+
+    let mut instructions = config.pre_instructions.clone();
+    instructions.push(Instruction {
+        program_id: tsv_usdc_vault::ID,
+        accounts,
+        data,
+    });
+    instructions
+}
+
+/// Remaining accounts of one strategy in the vault's strategy-item layout: `Strategy`, adapter
+/// program and reserve, then the adapter's own accounts
+fn strategy_item(
+    vault: &VaultState,
+    config: &StrategyConfig,
+    strategy: &Strategy,
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(vault.strategy(&strategy.adapter_program), false),
+        AccountMeta::new_readonly(strategy.adapter_program, false),
+        AccountMeta::new(strategy.reserve, false),
+    ];
+    accounts.extend_from_slice(&config.accounts);
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault() -> VaultState {
+        VaultState {
+            address: Pubkey::new_unique(),
+            asset_mint: Pubkey::new_unique(),
+            share_mint: Pubkey::new_unique(),
+            asset_vault: Pubkey::new_unique(),
+            treasury_shares: Pubkey::new_unique(),
+            token_program: Pubkey::new_unique(),
+        }
+    }
+
+    fn strategy(adapter_program: Pubkey) -> Strategy {
+        Strategy {
+            vault: Pubkey::default(),
+            adapter_program,
+            reserve: Pubkey::new_unique(),
+            allocated: 0,
+            max_debt: u64::MAX,
+            target_weight_bps: 0,
+            enabled: true,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn harvest_appends_strategy_item_after_pre_instructions() {
+        let vault = vault();
+        let keeper = Pubkey::new_unique();
+        let adapter = Pubkey::new_unique();
+        let extra = AccountMeta::new(Pubkey::new_unique(), false);
+        let refresh = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![],
+            data: vec![7],
+        };
+        let config = StrategyConfig {
+            adapter_program: adapter,
+            accounts: vec![extra.clone()],
+            pre_instructions: vec![refresh.clone()],
+        };
+        let strategy = strategy(adapter);
+
+        let instructions = harvest(&vault, &keeper, &config, &strategy);
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0], refresh);
+
+        let accounts = &instructions[1].accounts;
+        // Five `Harvest` accounts and the two event-CPI ones, then the item
+        assert_eq!(accounts[5].pubkey, vault::event_authority());
+        assert_eq!(accounts[6].pubkey, tsv_usdc_vault::ID);
+        assert_eq!(accounts[7].pubkey, vault.strategy(&adapter));
+        assert_eq!(accounts[8].pubkey, adapter);
+        assert_eq!(accounts[9].pubkey, strategy.reserve);
+        assert_eq!(accounts[10], extra);
+        assert_eq!(
+            instructions[1].data,
+            tsv_usdc_vault::instruction::Harvest {
+                adapter_accounts: vec![1]
+            }
+            .data()
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Scheduled keeper jobs. Each runs on its own interval; the jobs sending transactions take
+//! turns so they never race each other for the same strategy.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::Mutex;
+use tokio::time::{interval, MissedTickBehavior};
+use tsv_usdc_vault::{Strategy, RATE_SNAPSHOT_INTERVAL_SECONDS};
+
+use crate::error::KeeperError;
+use crate::instructions;
+use crate::metrics::Metrics;
+use crate::planner::{self, Allocation, Move};
+use crate::strategies::StrategyConfig;
+use crate::submitter::Submitter;
+use crate::vault::{Snapshot, VaultState};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Job {
+    /// Read vault state into the metrics
+    Monitor,
+    /// `harvest` each allocated strategy
+    Harvest,
+    /// Move one strategy allocation toward its target
+    Rebalance,
+    /// `accrue_fees`
+    AccrueFees,
+    /// `check_price`, tripping the depeg breaker if USDC is off its peg
+    CheckPrice,
+    /// `process_epoch`, settling the queued withdrawals
+    ProcessEpoch,
+    /// `record_rate`, the daily exchange-rate snapshot
+    RecordRate,
+}
+
+impl Job {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Monitor => "monitor",
+            Self::Harvest => "harvest",
+            Self::Rebalance => "rebalance",
+            Self::AccrueFees => "accrue_fees",
+            Self::CheckPrice => "check_price",
+            Self::ProcessEpoch => "process_epoch",
+            Self::RecordRate => "record_rate",
+        }
+    }
+}
+
+pub struct Keeper {
+    pub rpc: Arc<RpcClient>,
+    pub vault: VaultState,
+    pub keeper: Pubkey,
+    pub strategies: Vec<StrategyConfig>,
+    pub submitter: Submitter,
+    pub metrics: Arc<Metrics>,
+    /// Smallest allocation change worth a transaction
+    pub min_rebalance_amount: u64,
+    /// Held while a job sends transactions
+    pub sending: Mutex<()>,
+}
+
+impl Keeper {
+    /// Run `job` every `period`, forever. A failed run is logged and counted; the next one
+    /// starts from fresh state
+    pub async fn run(self: Arc<Self>, job: Job, period: Duration) {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let outcome = match self.run_once(job).await {
+                Ok(sent) => {
+                    self.metrics
+                        .job_last_success
+                        .with_label_values(&[job.name()])
+                        .set(unix_now());
+                    if sent {
+                        "success"
+                    } else {
+                        "skipped"
+                    }
+                }
+                Err(err) => {
+                    tracing::error!(job = job.name(), %err, "job failed");
+                    "error"
+                }
+            };
+            self.metrics
+                .job_runs
+                .with_label_values(&[job.name(), outcome])
+                .inc();
+        }
+    }
+
+    /// Run `job` once, returning whether it sent anything
+    async fn run_once(&self, job: Job) -> Result<bool, KeeperError> {
+        match job {
+            Job::Monitor => self.monitor().await.map(|()| false),
+            Job::Harvest => self.harvest().await,
+            Job::Rebalance => self.rebalance().await,
+            Job::AccrueFees => self.accrue_fees().await,
+            Job::CheckPrice => self.check_price().await,
+            Job::ProcessEpoch => self.process_epoch().await,
+            Job::RecordRate => self.record_rate().await,
+        }
+    }
+
+    async fn snapshot(&self) -> Result<Snapshot, KeeperError> {
+        let adapters: Vec<Pubkey> = self
+            .strategies
+            .iter()
+            .map(|strategy| strategy.adapter_program)
+            .collect();
+        self.vault.snapshot(&self.rpc, &adapters).await
+    }
+
+    /// Configured strategies registered with the vault, with their `Strategy` accounts
+    fn registered<'a>(
+        &'a self,
+        snapshot: &'a Snapshot,
+    ) -> impl Iterator<Item = (&'a StrategyConfig, &'a Strategy)> {
+        self.strategies
+            .iter()
+            .zip(&snapshot.strategies)
+            .filter_map(|(config, strategy)| Some((config, strategy.as_ref()?)))
+    }
+
+    async fn monitor(&self) -> Result<(), KeeperError> {
+        let snapshot = self.snapshot().await?;
+        let vault = &snapshot.vault;
+        let metrics = &self.metrics;
+        metrics.total_assets.set(vault.total_assets as i64);
+        metrics.total_shares.set(vault.total_shares as i64);
+        metrics.idle_assets.set(snapshot.idle_assets() as i64);
+        metrics.allocated_assets.set(vault.allocated_assets as i64);
+        metrics.locked_profit.set(vault.locked_profit as i64);
+        metrics.insurance_assets.set(vault.insurance_assets as i64);
+        metrics.depeg_tripped.set(i64::from(vault.depeg_tripped));
+        metrics
+            .queued_withdraw_shares
+            .set(vault.queued_withdraw_shares as i64);
+        metrics
+            .reserved_withdraw_assets
+            .set(vault.reserved_withdraw_assets as i64);
+        for (config, strategy) in self.registered(&snapshot) {
+            let label = config.adapter_program.to_string();
+            metrics
+                .strategy_allocated
+                .with_label_values(&[&label])
+                .set(strategy.allocated as i64);
+            metrics
+                .strategy_target
+                .with_label_values(&[&label])
+                .set(target(strategy, vault.total_assets) as i64);
+        }
+        let balance = self.rpc.get_balance(&self.keeper).await?;
+        metrics.keeper_balance.set(balance as i64);
+        Ok(())
+    }
+
+    /// Harvest every strategy with an allocation, one transaction each. A failing strategy
+    /// doesn't hold up the others; the run fails after trying them all
+    async fn harvest(&self) -> Result<bool, KeeperError> {
+        let _sending = self.sending.lock().await;
+        let snapshot = self.snapshot().await?;
+        let mut sent = false;
+        let mut failure = None;
+        for (config, strategy) in self.registered(&snapshot) {
+            if strategy.allocated == 0 {
+                continue;
+            }
+            let ixs = instructions::harvest(&self.vault, &self.keeper, config, strategy);
+            match self.submitter.submit(&ixs).await {
+                Ok(signature) => {
+                    tracing::info!(adapter = %config.adapter_program, %signature, "harvested");
+                    sent = true;
+                }
+                Err(err) => {
+                    tracing::warn!(adapter = %config.adapter_program, %err, "harvest failed");
+                    failure = Some(err);
+                }
+            }
+        }
+        failure.map_or(Ok(sent), Err)
+    }
+
+    /// Send the planner's next move, if any
+    async fn rebalance(&self) -> Result<bool, KeeperError> {
+        let _sending = self.sending.lock().await;
+        let snapshot = self.snapshot().await?;
+        let total_assets = snapshot.vault.total_assets;
+        let registered: Vec<_> = self.registered(&snapshot).collect();
+        let allocations: Vec<Allocation> = registered
+            .iter()
+            .map(|(_, strategy)| Allocation {
+                allocated: strategy.allocated,
+                target: target(strategy, total_assets),
+                max_debt: strategy.max_debt,
+                enabled: strategy.enabled,
+            })
+            .collect();
+
+        let Some(next) = planner::plan(
+            &allocations,
+            snapshot.idle_assets(),
+            snapshot.liquidity_buffer(),
+            self.min_rebalance_amount,
+        ) else {
+            return Ok(false);
+        };
+        let ixs = match next {
+            Move::Rebalance { from, to, amount } => instructions::rebalance(
+                &self.vault,
+                &self.keeper,
+                registered[from],
+                registered[to],
+                amount,
+            ),
+            Move::Allocate { to, amount } => {
+                let (config, strategy) = registered[to];
+                instructions::allocate(&self.vault, &self.keeper, config, strategy, amount)
+            }
+            Move::Deallocate { from, amount } => {
+                let (config, strategy) = registered[from];
+                instructions::deallocate(&self.vault, &self.keeper, config, strategy, amount)
+            }
+        };
+        let signature = self.submitter.submit(&ixs).await?;
+        tracing::info!(?next, %signature, "rebalanced");
+        Ok(true)
+    }
+
+    /// Mint accrued fee shares to the treasury, once one is set and there is a fee to mint
+    async fn accrue_fees(&self) -> Result<bool, KeeperError> {
+        let _sending = self.sending.lock().await;
+        let snapshot = self.snapshot().await?;
+        let vault = &snapshot.vault;
+        if vault.treasury_shares == Pubkey::default()
+            || (vault.management_fee_bps == 0 && vault.unminted_fee_shares == 0)
+        {
+            return Ok(false);
+        }
+        let signature = self
+            .submitter
+            .submit(&[instructions::accrue_fees(&self.vault)])
+            .await?;
+        tracing::info!(%signature, "accrued fees");
+        Ok(true)
+    }
+
+    /// Have the vault check the USDC price while its depeg breaker is armed. The vault decides
+    /// whether to trip, so the price isn't read here
+    async fn check_price(&self) -> Result<bool, KeeperError> {
+        let _sending = self.sending.lock().await;
+        let snapshot = self.snapshot().await?;
+        let vault = &snapshot.vault;
+        if vault.price_feed == Pubkey::default() || vault.depeg_tripped {
+            return Ok(false);
+        }
+        let signature = self
+            .submitter
+            .submit(&[instructions::check_price(&self.vault, &vault.price_feed)])
+            .await?;
+        tracing::info!(%signature, "checked price");
+        Ok(true)
+    }
+
+    /// Settle the withdrawal epoch once it has requests, deallocating from as many strategies
+    /// as the idle USDC needs
+    async fn process_epoch(&self) -> Result<bool, KeeperError> {
+        let _sending = self.sending.lock().await;
+        let snapshot = self.snapshot().await?;
+        let vault = &snapshot.vault;
+        if vault.queued_withdraw_shares == 0 || vault.paused_redeems {
+            return Ok(false);
+        }
+        let registered: Vec<_> = self.registered(&snapshot).collect();
+        let allocations: Vec<Allocation> = registered
+            .iter()
+            .map(|(_, strategy)| Allocation {
+                allocated: strategy.allocated,
+                target: target(strategy, vault.total_assets),
+                max_debt: strategy.max_debt,
+                enabled: strategy.enabled,
+            })
+            .collect();
+        let sources: Vec<_> = planner::sources(&allocations, snapshot.epoch_shortfall())
+            .into_iter()
+            .map(|index| registered[index])
+            .collect();
+
+        let ixs =
+            instructions::process_epoch(&self.vault, &self.keeper, vault.withdraw_epoch, &sources);
+        let signature = self.submitter.submit(&ixs).await?;
+        tracing::info!(
+            epoch = vault.withdraw_epoch,
+            shares = vault.queued_withdraw_shares,
+            %signature,
+            "processed withdrawal epoch"
+        );
+        Ok(true)
+    }
+
+    /// Snapshot the exchange rate once the vault's latest one is a day old. The history itself
+    /// is created with `init_rate_history`; until then there is nothing to do
+    async fn record_rate(&self) -> Result<bool, KeeperError> {
+        let _sending = self.sending.lock().await;
+        let Some(latest) = self.vault.latest_rate_ts(&self.rpc).await? else {
+            return Ok(false);
+        };
+        if unix_now() < latest.saturating_add(RATE_SNAPSHOT_INTERVAL_SECONDS) {
+            return Ok(false);
+        }
+        let signature = self
+            .submitter
+            .submit(&[instructions::record_rate(&self.vault)])
+            .await?;
+        tracing::info!(%signature, "recorded exchange rate");
+        Ok(true)
+    }
+}
+
+/// `Strategy::target_allocation` at `total_assets`, which can't overflow
+fn target(strategy: &Strategy, total_assets: u64) -> u64 {
+    strategy.target_allocation(total_assets).unwrap_or(u64::MAX)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
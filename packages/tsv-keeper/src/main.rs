@@ -0,0 +1,83 @@
+//! Keeper bot for the Talken Stable Vault: harvests strategies, moves allocations toward their
+//! target weights and accrues fees on schedules, and exports the vault's state as Prometheus
+//! metrics.
+
+mod config;
+mod error;
+mod instructions;
+mod jobs;
+mod metrics;
+mod planner;
+mod strategies;
+mod submitter;
+mod vault;
+
+use std::sync::Arc;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signer;
+use tokio::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::Config;
+use crate::error::KeeperError;
+use crate::jobs::{Job, Keeper};
+use crate::metrics::Metrics;
+use crate::submitter::Submitter;
+use crate::vault::VaultState;
+
+#[tokio::main]
+async fn main() -> Result<(), KeeperError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let rpc = Arc::new(RpcClient::new_with_commitment(
+        config.rpc_url.clone(),
+        CommitmentConfig::confirmed(),
+    ));
+    let vault = VaultState::fetch(&rpc, config.vault_asset_mint).await?;
+    let keeper = Arc::new(config.keeper.insecure_clone());
+    let strategies = match &config.strategies_file {
+        Some(path) => strategies::load(path)?,
+        None => Vec::new(),
+    };
+    tracing::info!(
+        keeper = %keeper.pubkey(),
+        vault = %vault.address,
+        strategies = strategies.len(),
+        jito = config.jito_url.is_some(),
+        "starting"
+    );
+
+    let metrics = Arc::new(Metrics::new()?);
+    let keeper = Arc::new(Keeper {
+        rpc: rpc.clone(),
+        vault,
+        keeper: keeper.pubkey(),
+        strategies,
+        submitter: Submitter::new(rpc, keeper, &config).await?,
+        metrics: metrics.clone(),
+        min_rebalance_amount: config.min_rebalance_amount,
+        sending: Mutex::new(()),
+    });
+
+    let schedules = [
+        (Job::Monitor, Some(config.monitor_interval)),
+        (Job::Harvest, config.harvest_interval),
+        (Job::Rebalance, config.rebalance_interval),
+        (Job::AccrueFees, config.accrue_fees_interval),
+        (Job::CheckPrice, config.price_check_interval),
+        (Job::ProcessEpoch, config.process_epoch_interval),
+        (Job::RecordRate, config.record_rate_interval),
+    ];
+    for (job, period) in schedules {
+        if let Some(period) = period {
+            tokio::spawn(keeper.clone().run(job, period));
+        }
+    }
+
+    metrics.serve(config.metrics_addr).await
+}
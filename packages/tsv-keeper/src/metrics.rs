@@ -0,0 +1,131 @@
+//! Prometheus metrics of the keeper's jobs and the vault state it monitors, served on
+//! `/metrics`.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{Encoder, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use tokio::net::TcpListener;
+
+use crate::error::KeeperError;
+
+pub struct Metrics {
+    registry: Registry,
+    /// Job runs by `job` and `outcome` (`success`, `skipped` or `error`)
+    pub job_runs: IntCounterVec,
+    /// Unix time of each job's last successful run
+    pub job_last_success: IntGaugeVec,
+    pub total_assets: IntGauge,
+    pub total_shares: IntGauge,
+    pub idle_assets: IntGauge,
+    pub allocated_assets: IntGauge,
+    pub locked_profit: IntGauge,
+    pub insurance_assets: IntGauge,
+    /// 1 while the depeg breaker has the vault paused
+    pub depeg_tripped: IntGauge,
+    pub queued_withdraw_shares: IntGauge,
+    pub reserved_withdraw_assets: IntGauge,
+    /// `Strategy::allocated` by adapter program
+    pub strategy_allocated: IntGaugeVec,
+    /// `Strategy::target_allocation` by adapter program
+    pub strategy_target: IntGaugeVec,
+    /// Keeper balance in lamports, to alert before it can't pay fees
+    pub keeper_balance: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, KeeperError> {
+        let registry = Registry::new_custom(Some("tsv_keeper".into()), None)
+            .map_err(|err| KeeperError::Config(format!("can't create registry: {err}")))?;
+        let gauge = |name: &str, help: &str| -> Result<IntGauge, KeeperError> {
+            let gauge = IntGauge::new(name, help).map_err(metric_error)?;
+            registry
+                .register(Box::new(gauge.clone()))
+                .map_err(metric_error)?;
+            Ok(gauge)
+        };
+        let gauge_vec = |name: &str, help: &str, labels: &[&str]| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), labels).map_err(metric_error)?;
+            registry
+                .register(Box::new(gauge.clone()))
+                .map_err(metric_error)?;
+            Ok::<_, KeeperError>(gauge)
+        };
+
+        let job_runs = IntCounterVec::new(
+            Opts::new("job_runs_total", "Job runs by outcome"),
+            &["job", "outcome"],
+        )
+        .map_err(metric_error)?;
+        registry
+            .register(Box::new(job_runs.clone()))
+            .map_err(metric_error)?;
+
+        Ok(Self {
+            job_runs,
+            job_last_success: gauge_vec(
+                "job_last_success_timestamp",
+                "Unix time of the job's last successful run",
+                &["job"],
+            )?,
+            total_assets: gauge("vault_total_assets", "Vault total assets, USDC base units")?,
+            total_shares: gauge("vault_total_shares", "Vault share supply")?,
+            idle_assets: gauge("vault_idle_assets", "USDC held in the asset vault")?,
+            allocated_assets: gauge("vault_allocated_assets", "USDC allocated to strategies")?,
+            locked_profit: gauge("vault_locked_profit", "Harvested profit still unlocking")?,
+            insurance_assets: gauge("vault_insurance_assets", "Insurance buffer")?,
+            depeg_tripped: gauge("vault_depeg_tripped", "Depeg breaker tripped")?,
+            queued_withdraw_shares: gauge(
+                "vault_queued_withdraw_shares",
+                "Shares queued for withdrawal in the current epoch",
+            )?,
+            reserved_withdraw_assets: gauge(
+                "vault_reserved_withdraw_assets",
+                "USDC reserved for unclaimed withdrawals",
+            )?,
+            strategy_allocated: gauge_vec(
+                "strategy_allocated",
+                "Principal allocated to the strategy",
+                &["adapter_program"],
+            )?,
+            strategy_target: gauge_vec(
+                "strategy_target",
+                "Target allocation of the strategy",
+                &["adapter_program"],
+            )?,
+            keeper_balance: gauge("keeper_balance_lamports", "Keeper SOL balance")?,
+            registry,
+        })
+    }
+
+    /// Serve `/metrics` on `addr` until the server fails
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<(), KeeperError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|err| KeeperError::Config(format!("can't bind {addr}: {err}")))?;
+        tracing::info!(%addr, "serving metrics");
+        let router = Router::new()
+            .route("/metrics", get(render))
+            .route("/health", get(|| async { "ok" }))
+            .with_state(self);
+        axum::serve(listener, router)
+            .await
+            .map_err(|err| KeeperError::Config(format!("metrics server error: {err}")))
+    }
+}
+
+async fn render(State(metrics): State<Arc<Metrics>>) -> Result<String, StatusCode> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&metrics.registry.gather(), &mut buffer)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    String::from_utf8(buffer).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn metric_error(err: prometheus::Error) -> KeeperError {
+    KeeperError::Config(format!("can't register metric: {err}"))
+}
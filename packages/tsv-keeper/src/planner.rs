@@ -0,0 +1,177 @@
+//! Decides the next allocation change moving strategies toward their target weights, and which
+//! strategies fund a withdrawal epoch.
+
+/// A strategy's allocation as the planner sees it
+#[derive(Clone, Copy, Debug)]
+pub struct Allocation {
+    pub allocated: u64,
+    /// `Strategy::target_allocation` at the vault's current total assets
+    pub target: u64,
+    pub max_debt: u64,
+    pub enabled: bool,
+}
+
+impl Allocation {
+    /// Allocated above target, what `rebalance`/`deallocate` may take out
+    fn surplus(&self) -> u64 {
+        self.allocated.saturating_sub(self.target)
+    }
+
+    /// Missing to reach target within the debt cap, what `rebalance`/`allocate` may add
+    fn deficit(&self) -> u64 {
+        if !self.enabled {
+            return 0;
+        }
+        self.target
+            .min(self.max_debt)
+            .saturating_sub(self.allocated)
+    }
+}
+
+/// An allocation change, by index into the planned allocations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Move {
+    Rebalance { from: usize, to: usize, amount: u64 },
+    Allocate { to: usize, amount: u64 },
+    Deallocate { from: usize, amount: u64 },
+}
+
+/// The largest move toward the targets, or `None` when every gap is below `min_amount`.
+/// Surplus goes straight to the most underweight strategy; otherwise idle USDC above `buffer`
+/// is allocated, or the surplus returned to the vault
+pub fn plan(allocations: &[Allocation], idle: u64, buffer: u64, min_amount: u64) -> Option<Move> {
+    let largest = |gap: fn(&Allocation) -> u64| {
+        allocations
+            .iter()
+            .enumerate()
+            .map(|(index, allocation)| (index, gap(allocation)))
+            .filter(|(_, amount)| *amount >= min_amount.max(1))
+            // The first strategy on ties, as `max_by_key` would pick the last
+            .max_by_key(|&(index, amount)| (amount, std::cmp::Reverse(index)))
+    };
+    let over = largest(Allocation::surplus);
+    let under = largest(Allocation::deficit);
+
+    match (over, under) {
+        (Some((from, surplus)), Some((to, deficit))) => Some(Move::Rebalance {
+            from,
+            to,
+            amount: surplus.min(deficit),
+        }),
+        (_, Some((to, deficit))) if idle.saturating_sub(buffer) >= min_amount.max(1) => {
+            Some(Move::Allocate {
+                to,
+                amount: deficit.min(idle - buffer),
+            })
+        }
+        (Some((from, surplus)), _) => Some(Move::Deallocate {
+            from,
+            amount: surplus,
+        }),
+        _ => None,
+    }
+}
+
+/// Strategies to deallocate `needed` from, most overweight first, until their allocations cover
+/// it (every allocated strategy if they can't)
+pub fn sources(allocations: &[Allocation], needed: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..allocations.len())
+        .filter(|&index| allocations[index].allocated > 0)
+        .collect();
+    order.sort_by_key(|&index| {
+        let allocation = &allocations[index];
+        std::cmp::Reverse((allocation.surplus(), allocation.allocated))
+    });
+
+    let mut covered = 0u64;
+    order
+        .into_iter()
+        .take_while(|&index| {
+            let take = covered < needed;
+            covered = covered.saturating_add(allocations[index].allocated);
+            take
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(allocated: u64, target: u64) -> Allocation {
+        Allocation {
+            allocated,
+            target,
+            max_debt: u64::MAX,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn moves_surplus_to_the_most_underweight() {
+        let allocations = [
+            allocation(700, 500),
+            allocation(300, 400),
+            allocation(0, 100),
+        ];
+        assert_eq!(
+            plan(&allocations, 0, 0, 10),
+            Some(Move::Rebalance {
+                from: 0,
+                to: 1,
+                amount: 100
+            })
+        );
+    }
+
+    #[test]
+    fn allocates_idle_above_the_buffer() {
+        let allocations = [allocation(500, 500), allocation(0, 400)];
+        assert_eq!(
+            plan(&allocations, 300, 100, 10),
+            Some(Move::Allocate { to: 1, amount: 200 })
+        );
+        // Nothing above the buffer
+        assert_eq!(plan(&allocations, 100, 100, 10), None);
+    }
+
+    #[test]
+    fn deallocates_surplus_nobody_takes() {
+        let mut disabled = allocation(0, 400);
+        disabled.enabled = false;
+        let allocations = [allocation(600, 500), disabled];
+        assert_eq!(
+            plan(&allocations, 0, 0, 10),
+            Some(Move::Deallocate {
+                from: 0,
+                amount: 100
+            })
+        );
+    }
+
+    #[test]
+    fn respects_max_debt_and_min_amount() {
+        let mut capped = allocation(0, 400);
+        capped.max_debt = 5;
+        assert_eq!(plan(&[capped], 1_000, 0, 10), None);
+        assert_eq!(plan(&[allocation(0, 9)], 1_000, 0, 10), None);
+        assert_eq!(
+            plan(&[allocation(0, 10)], 1_000, 0, 10),
+            Some(Move::Allocate { to: 0, amount: 10 })
+        );
+    }
+
+    #[test]
+    fn sources_overweight_strategies_first() {
+        let allocations = [
+            allocation(500, 500),
+            allocation(0, 100),
+            allocation(400, 200),
+            allocation(300, 300),
+        ];
+        assert_eq!(sources(&allocations, 0), Vec::<usize>::new());
+        assert_eq!(sources(&allocations, 400), vec![2]);
+        assert_eq!(sources(&allocations, 401), vec![2, 0]);
+        assert_eq!(sources(&allocations, 10_000), vec![2, 0, 3]);
+    }
+}
@@ -0,0 +1,172 @@
+//! Adapter accounts of the vault's strategies, read from the strategies file.
+//!
+//! The vault only records each strategy's adapter program and reserve; the adapter-specific
+//! accounts its calls take, and any instructions that must run first in the same transaction
+//! (such as klend's reserve and obligation refreshes), are configured here.
+
+use std::fs;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::KeeperError;
+
+/// One strategies file entry
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StrategyEntry {
+    adapter_program: String,
+    #[serde(default)]
+    accounts: Vec<AccountEntry>,
+    #[serde(default)]
+    pre_instructions: Vec<InstructionEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountEntry {
+    pubkey: String,
+    #[serde(default)]
+    writable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstructionEntry {
+    program_id: String,
+    #[serde(default)]
+    accounts: Vec<AccountEntry>,
+    /// Base64 instruction data
+    data: String,
+}
+
+/// A strategy the keeper manages
+#[derive(Clone, Debug)]
+pub struct StrategyConfig {
+    pub adapter_program: Pubkey,
+    /// Adapter-specific accounts following the interface accounts, in the adapter's order
+    pub accounts: Vec<AccountMeta>,
+    /// Instructions sent ahead of every call reaching the adapter
+    pub pre_instructions: Vec<Instruction>,
+}
+
+/// Read the strategies file at `path`: a JSON array of entries
+pub fn load(path: &str) -> Result<Vec<StrategyConfig>, KeeperError> {
+    let json = fs::read_to_string(path)
+        .map_err(|err| KeeperError::Config(format!("can't read STRATEGIES_FILE {path}: {err}")))?;
+    parse(&json)
+}
+
+fn parse(json: &str) -> Result<Vec<StrategyConfig>, KeeperError> {
+    let entries: Vec<StrategyEntry> = serde_json::from_str(json)
+        .map_err(|err| KeeperError::Config(format!("invalid strategies file: {err}")))?;
+    let strategies: Vec<StrategyConfig> = entries
+        .into_iter()
+        .map(StrategyConfig::try_from)
+        .collect::<Result<_, _>>()?;
+
+    for (index, strategy) in strategies.iter().enumerate() {
+        if strategies[..index]
+            .iter()
+            .any(|other| other.adapter_program == strategy.adapter_program)
+        {
+            return Err(KeeperError::Config(format!(
+                "strategy {} is listed twice",
+                strategy.adapter_program
+            )));
+        }
+        // The vault passes each strategy's account count as a u8
+        if strategy.accounts.len() > usize::from(u8::MAX) {
+            return Err(KeeperError::Config(format!(
+                "strategy {} has too many accounts",
+                strategy.adapter_program
+            )));
+        }
+    }
+    Ok(strategies)
+}
+
+impl TryFrom<StrategyEntry> for StrategyConfig {
+    type Error = KeeperError;
+
+    fn try_from(entry: StrategyEntry) -> Result<Self, KeeperError> {
+        Ok(Self {
+            adapter_program: parse_pubkey(&entry.adapter_program)?,
+            accounts: parse_accounts(entry.accounts)?,
+            pre_instructions: entry
+                .pre_instructions
+                .into_iter()
+                .map(|ix| {
+                    Ok(Instruction {
+                        program_id: parse_pubkey(&ix.program_id)?,
+                        accounts: parse_accounts(ix.accounts)?,
+                        data: STANDARD.decode(&ix.data).map_err(|_| {
+                            KeeperError::Config(format!("invalid instruction data: {}", ix.data))
+                        })?,
+                    })
+                })
+                .collect::<Result<_, KeeperError>>()?,
+        })
+    }
+}
+
+fn parse_accounts(entries: Vec<AccountEntry>) -> Result<Vec<AccountMeta>, KeeperError> {
+    entries
+        .into_iter()
+        .map(|entry| {
+            let pubkey = parse_pubkey(&entry.pubkey)?;
+            Ok(if entry.writable {
+                AccountMeta::new(pubkey, false)
+            } else {
+                AccountMeta::new_readonly(pubkey, false)
+            })
+        })
+        .collect()
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, KeeperError> {
+    value
+        .parse()
+        .map_err(|_| KeeperError::Config(format!("invalid pubkey in strategies file: {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries() {
+        let adapter = Pubkey::new_unique();
+        let market = Pubkey::new_unique();
+        let obligation = Pubkey::new_unique();
+        let json = format!(
+            r#"[{{
+                "adapterProgram": "{adapter}",
+                "accounts": [{{ "pubkey": "{market}" }}, {{ "pubkey": "{obligation}", "writable": true }}],
+                "preInstructions": [{{ "programId": "{market}", "accounts": [], "data": "AQI=" }}]
+            }}]"#
+        );
+        let strategies = parse(&json).unwrap();
+        assert_eq!(strategies.len(), 1);
+        assert_eq!(strategies[0].adapter_program, adapter);
+        assert_eq!(
+            strategies[0].accounts,
+            vec![
+                AccountMeta::new_readonly(market, false),
+                AccountMeta::new(obligation, false)
+            ]
+        );
+        assert_eq!(strategies[0].pre_instructions[0].data, vec![1, 2]);
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let adapter = Pubkey::new_unique();
+        let json = format!(
+            r#"[{{ "adapterProgram": "{adapter}" }}, {{ "adapterProgram": "{adapter}" }}]"#
+        );
+        assert!(parse(&json).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Sends transactions with a priority fee derived from recent fees, tracks their confirmation
+//! and resubmits with a fresh blockhash and a higher fee when the previous one expired. With a
+//! Jito block engine configured, each transaction carries a tip and is sent as a bundle.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::address_lookup_table::state::AddressLookupTable;
+use solana_sdk::address_lookup_table::AddressLookupTableAccount;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::VersionedTransaction;
+use tokio::time::sleep;
+
+use crate::config::Config;
+use crate::error::KeeperError;
+
+/// Interval between confirmation polls of a sent transaction
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Priority fee increase per resubmission, in percent of the sampled fee
+const RETRY_FEE_BUMP_PERCENT: u64 = 25;
+
+/// Jito's mainnet tip accounts; one is picked per attempt to spread write locks
+const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+/// Jito block engine bundles are sent to
+struct Jito {
+    http: reqwest::Client,
+    bundles_url: String,
+    tip_lamports: u64,
+}
+
+pub struct Submitter {
+    rpc: Arc<RpcClient>,
+    keeper: Arc<Keypair>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    compute_unit_limit: u32,
+    min_priority_fee: u64,
+    max_priority_fee: u64,
+    priority_fee_percentile: u8,
+    max_retries: u32,
+    jito: Option<Jito>,
+}
+
+impl Submitter {
+    pub async fn new(
+        rpc: Arc<RpcClient>,
+        keeper: Arc<Keypair>,
+        config: &Config,
+    ) -> Result<Self, KeeperError> {
+        let mut lookup_tables = Vec::new();
+        if let Some(key) = config.lookup_table {
+            let data = rpc.get_account_data(&key).await?;
+            let table = AddressLookupTable::deserialize(&data).map_err(|err| {
+                KeeperError::Config(format!("can't decode lookup table {key}: {err}"))
+            })?;
+            lookup_tables.push(AddressLookupTableAccount {
+                key,
+                addresses: table.addresses.to_vec(),
+            });
+        }
+
+        Ok(Self {
+            rpc,
+            keeper,
+            lookup_tables,
+            compute_unit_limit: config.compute_unit_limit,
+            min_priority_fee: config.min_priority_fee,
+            max_priority_fee: config.max_priority_fee,
+            priority_fee_percentile: config.priority_fee_percentile,
+            max_retries: config.max_retries,
+            jito: config.jito_url.as_ref().map(|url| Jito {
+                http: reqwest::Client::new(),
+                bundles_url: format!("{}/api/v1/bundles", url.trim_end_matches('/')),
+                tip_lamports: config.jito_tip_lamports,
+            }),
+        })
+    }
+
+    /// Send `instructions` in one transaction and wait until it is confirmed. Fails without
+    /// retrying if the transaction is rejected in preflight or on chain
+    pub async fn submit(&self, instructions: &[Instruction]) -> Result<Signature, KeeperError> {
+        let writable: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey)
+            .collect();
+        let sampled_fee = self.sample_priority_fee(&writable).await?;
+
+        for attempt in 0..=self.max_retries {
+            let priority_fee =
+                bump_fee(sampled_fee, attempt).clamp(self.min_priority_fee, self.max_priority_fee);
+            let (blockhash, last_valid_block_height) = self
+                .rpc
+                .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+                .await?;
+
+            let mut all = vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(self.compute_unit_limit),
+                ComputeBudgetInstruction::set_compute_unit_price(priority_fee),
+            ];
+            all.extend_from_slice(instructions);
+            if let Some(jito) = &self.jito {
+                all.push(system_instruction::transfer(
+                    &self.keeper.pubkey(),
+                    &tip_account(attempt),
+                    jito.tip_lamports,
+                ));
+            }
+            let message = v0::Message::try_compile(
+                &self.keeper.pubkey(),
+                &all,
+                &self.lookup_tables,
+                blockhash,
+            )
+            .map_err(|err| KeeperError::TransactionFailed(err.to_string()))?;
+            let transaction =
+                VersionedTransaction::try_new(VersionedMessage::V0(message), &[&*self.keeper])
+                    .map_err(|err| KeeperError::TransactionFailed(err.to_string()))?;
+
+            let signature = match &self.jito {
+                Some(jito) => jito.send_bundle(&transaction).await?,
+                None => self
+                    .rpc
+                    .send_transaction_with_config(
+                        &transaction,
+                        RpcSendTransactionConfig {
+                            preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+                            ..RpcSendTransactionConfig::default()
+                        },
+                    )
+                    .await
+                    .map_err(|err| KeeperError::TransactionFailed(err.to_string()))?,
+            };
+            tracing::info!(%signature, attempt, priority_fee, "sent");
+
+            if self
+                .await_confirmation(&signature, last_valid_block_height)
+                .await?
+            {
+                return Ok(signature);
+            }
+            tracing::warn!(%signature, attempt, "blockhash expired, resubmitting");
+        }
+
+        Err(KeeperError::NotConfirmed(self.max_retries + 1))
+    }
+
+    /// Poll `signature` until it is confirmed (`true`) or its blockhash expired (`false`)
+    async fn await_confirmation(
+        &self,
+        signature: &Signature,
+        last_valid_block_height: u64,
+    ) -> Result<bool, KeeperError> {
+        loop {
+            sleep(POLL_INTERVAL).await;
+            let status = self
+                .rpc
+                .get_signature_statuses(&[*signature])
+                .await?
+                .value
+                .remove(0);
+            if let Some(status) = status {
+                if let Some(err) = status.err {
+                    return Err(KeeperError::TransactionFailed(err.to_string()));
+                }
+                if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                    return Ok(true);
+                }
+            } else if self.rpc.get_block_height().await? > last_valid_block_height {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// The configured percentile of recent prioritization fees paid for `writable` accounts
+    async fn sample_priority_fee(&self, writable: &[Pubkey]) -> Result<u64, KeeperError> {
+        let fees: Vec<u64> = self
+            .rpc
+            .get_recent_prioritization_fees(writable)
+            .await?
+            .iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+        Ok(percentile(fees, self.priority_fee_percentile))
+    }
+}
+
+impl Jito {
+    /// Send `transaction` as a single-transaction bundle, returning its signature. A bundle
+    /// lands atomically or not at all, so it is tracked like any sent transaction
+    async fn send_bundle(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<Signature, KeeperError> {
+        let serialized =
+            bincode::serialize(transaction).map_err(|err| KeeperError::Bundle(err.to_string()))?;
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendBundle",
+            "params": [[STANDARD.encode(serialized)], { "encoding": "base64" }],
+        });
+        let response: Value = self
+            .http
+            .post(&self.bundles_url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| KeeperError::Bundle(err.to_string()))?
+            .json()
+            .await
+            .map_err(|err| KeeperError::Bundle(err.to_string()))?;
+        if let Some(error) = response.get("error") {
+            return Err(KeeperError::Bundle(error.to_string()));
+        }
+        let bundle_id = response.get("result").cloned().unwrap_or(Value::Null);
+        tracing::debug!(%bundle_id, "bundle accepted");
+        Ok(transaction.signatures[0])
+    }
+}
+
+/// Tip account used on `attempt`
+fn tip_account(attempt: u32) -> Pubkey {
+    JITO_TIP_ACCOUNTS[attempt as usize % JITO_TIP_ACCOUNTS.len()]
+        .parse()
+        .expect("valid tip account")
+}
+
+/// `percentile` (0-100) of `values`, 0 when empty
+fn percentile(mut values: Vec<u64>, percentile: u8) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    let index = (values.len() - 1) * usize::from(percentile) / 100;
+    values[index]
+}
+
+/// `fee` raised by `RETRY_FEE_BUMP_PERCENT` per retry
+fn bump_fee(fee: u64, attempt: u32) -> u64 {
+    let percent = 100 + RETRY_FEE_BUMP_PERCENT * u64::from(attempt);
+    fee.saturating_mul(percent) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_percentile() {
+        assert_eq!(percentile(vec![], 75), 0);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 0), 10);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 50), 30);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 75), 40);
+        assert_eq!(percentile(vec![30, 10, 20, 50, 40], 100), 50);
+    }
+
+    #[test]
+    fn bumps_fee_per_retry() {
+        assert_eq!(bump_fee(1_000, 0), 1_000);
+        assert_eq!(bump_fee(1_000, 2), 1_500);
+        assert_eq!(bump_fee(u64::MAX, 1), u64::MAX / 100);
+    }
+
+    #[test]
+    fn rotates_tip_accounts() {
+        assert_eq!(tip_account(0), tip_account(8));
+        assert_ne!(tip_account(0), tip_account(1));
+    }
+}
@@ -0,0 +1,163 @@
+//! Vault accounts the keeper reads and the PDAs it derives, mirroring the program's seeds.
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::token_interface::TokenAccount;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tsv_usdc_vault::math::Rounding;
+use tsv_usdc_vault::{RateHistory, Strategy, Vault};
+
+use crate::error::KeeperError;
+
+/// Static vault accounts, read once at startup
+pub struct VaultState {
+    pub address: Pubkey,
+    pub asset_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub asset_vault: Pubkey,
+    pub treasury_shares: Pubkey,
+    /// SPL Token or Token-2022, whichever owns the vault's mints
+    pub token_program: Pubkey,
+}
+
+/// Current vault state, read by each job run
+pub struct Snapshot {
+    pub vault: Vault,
+    pub asset_vault: TokenAccount,
+    /// `Strategy` account of each configured adapter, `None` while it isn't registered
+    pub strategies: Vec<Option<Strategy>>,
+}
+
+impl Snapshot {
+    /// USDC available to allocate or pay out
+    pub fn idle_assets(&self) -> u64 {
+        self.vault.idle_assets(&self.asset_vault)
+    }
+
+    /// Idle USDC the vault keeps for redemptions, which can't overflow
+    pub fn liquidity_buffer(&self) -> u64 {
+        self.vault.liquidity_buffer().unwrap_or(u64::MAX)
+    }
+
+    /// USDC `process_epoch` would have to deallocate to settle the queued withdrawals now: their
+    /// payout plus the buffer left afterwards, beyond the idle USDC
+    pub fn epoch_shortfall(&self) -> u64 {
+        let mut vault = self.vault.clone();
+        let gross = vault
+            .convert_to_assets(vault.queued_withdraw_shares, Rounding::Floor)
+            .unwrap_or(u64::MAX);
+        let payout = gross.saturating_sub(vault.withdraw_fee(gross).unwrap_or(0));
+        vault.total_assets = vault.total_assets.saturating_sub(gross);
+        let buffer = vault.liquidity_buffer().unwrap_or(u64::MAX);
+        payout
+            .saturating_add(buffer)
+            .saturating_sub(self.idle_assets())
+    }
+}
+
+impl VaultState {
+    /// Read the vault of `asset_mint`, or the legacy `[b"vault"]` one without it
+    pub async fn fetch(rpc: &RpcClient, asset_mint: Option<Pubkey>) -> Result<Self, KeeperError> {
+        let address = match asset_mint {
+            Some(asset_mint) => pda(&[b"vault", asset_mint.as_ref()]),
+            None => pda(&[b"vault"]),
+        };
+        let data = rpc.get_account_data(&address).await?;
+        let vault = Vault::try_deserialize(&mut data.as_slice())
+            .map_err(|err| KeeperError::Config(format!("can't decode vault {address}: {err}")))?;
+        let token_program = rpc.get_account(&vault.asset_mint).await?.owner;
+        Ok(Self {
+            address,
+            asset_mint: vault.asset_mint,
+            share_mint: vault.share_mint,
+            asset_vault: vault.asset_vault,
+            treasury_shares: vault.treasury_shares,
+            token_program,
+        })
+    }
+
+    /// Read the vault, its `asset_vault` and the strategies of `adapter_programs` in one call
+    pub async fn snapshot(
+        &self,
+        rpc: &RpcClient,
+        adapter_programs: &[Pubkey],
+    ) -> Result<Snapshot, KeeperError> {
+        let mut addresses = vec![self.address, self.asset_vault];
+        addresses.extend(
+            adapter_programs
+                .iter()
+                .map(|adapter| self.strategy(adapter)),
+        );
+        let mut accounts = rpc.get_multiple_accounts(&addresses).await?.into_iter();
+
+        let vault = accounts
+            .next()
+            .flatten()
+            .ok_or_else(|| KeeperError::AccountNotFound(self.address.to_string()))?;
+        let vault = Vault::try_deserialize(&mut vault.data.as_slice())
+            .map_err(|err| KeeperError::Config(format!("can't decode vault: {err}")))?;
+        let asset_vault = accounts
+            .next()
+            .flatten()
+            .ok_or_else(|| KeeperError::AccountNotFound(self.asset_vault.to_string()))?;
+        let asset_vault = TokenAccount::try_deserialize(&mut asset_vault.data.as_slice())
+            .map_err(|err| KeeperError::Config(format!("can't decode asset vault: {err}")))?;
+        let strategies = accounts
+            .map(|account| {
+                account
+                    .map(|account| {
+                        Strategy::try_deserialize(&mut account.data.as_slice()).map_err(|err| {
+                            KeeperError::Config(format!("can't decode strategy: {err}"))
+                        })
+                    })
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Snapshot {
+            vault,
+            asset_vault,
+            strategies,
+        })
+    }
+
+    pub fn strategy(&self, adapter_program: &Pubkey) -> Pubkey {
+        pda(&[b"strategy", self.address.as_ref(), adapter_program.as_ref()])
+    }
+
+    pub fn rate_history(&self) -> Pubkey {
+        pda(&[b"rate_history", self.address.as_ref()])
+    }
+
+    /// Time of the latest `RateHistory` snapshot, `None` while the vault has no history
+    pub async fn latest_rate_ts(&self, rpc: &RpcClient) -> Result<Option<i64>, KeeperError> {
+        let account = rpc
+            .get_account_with_commitment(&self.rate_history(), rpc.commitment())
+            .await?
+            .value;
+        account
+            .map(|account| {
+                RateHistory::try_deserialize(&mut account.data.as_slice())
+                    .map(|history| history.snapshot(0).map_or(0, |latest| latest.timestamp))
+                    .map_err(|err| KeeperError::Config(format!("can't decode rate history: {err}")))
+            })
+            .transpose()
+    }
+
+    pub fn withdraw_epoch(&self, epoch: u64) -> Pubkey {
+        pda(&[
+            b"withdraw_epoch",
+            self.address.as_ref(),
+            &epoch.to_le_bytes(),
+        ])
+    }
+}
+
+/// PDA signing the program's self-CPI events, an account of every instruction
+pub fn event_authority() -> Pubkey {
+    pda(&[b"__event_authority"])
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0
+}
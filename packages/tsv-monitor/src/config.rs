@@ -0,0 +1,69 @@
+//! Monitor settings, read from the environment.
+
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::MonitorError;
+
+pub struct Config {
+    /// Solana JSON-RPC endpoint (`RPC_URL`)
+    pub rpc_url: String,
+    /// Solana websocket endpoint admin actions are followed on (`WS_URL`, default `RPC_URL`
+    /// with its scheme changed to `ws`/`wss`)
+    pub ws_url: String,
+    /// Asset mint of the vault to monitor (`VAULT_ASSET_MINT`, optional; without it the legacy
+    /// vault at the single-vault address)
+    pub vault_asset_mint: Option<Pubkey>,
+    /// Prometheus `/metrics` listen address (`METRICS_ADDR`, default 0.0.0.0:9102)
+    pub metrics_addr: SocketAddr,
+    /// How often vault state is read into the metrics (`REFRESH_INTERVAL_SECS`, default 15)
+    pub refresh_interval: Duration,
+    /// Wait before resubscribing after the websocket drops (`RECONNECT_DELAY_SECS`, default 5)
+    pub reconnect_delay: Duration,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, MonitorError> {
+        let rpc_url = required("RPC_URL")?;
+        let ws_url = env::var("WS_URL").unwrap_or_else(|_| rpc_url.replacen("http", "ws", 1));
+        let refresh_interval = parse_or("REFRESH_INTERVAL_SECS", 15)?;
+        if refresh_interval == 0 {
+            return Err(MonitorError::Config(
+                "REFRESH_INTERVAL_SECS must be positive".into(),
+            ));
+        }
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            vault_asset_mint: env::var("VAULT_ASSET_MINT")
+                .ok()
+                .map(|value| parse(&value, "VAULT_ASSET_MINT"))
+                .transpose()?,
+            metrics_addr: parse_or("METRICS_ADDR", SocketAddr::from(([0, 0, 0, 0], 9102)))?,
+            refresh_interval: Duration::from_secs(refresh_interval),
+            reconnect_delay: Duration::from_secs(parse_or("RECONNECT_DELAY_SECS", 5)?),
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, MonitorError> {
+    env::var(name).map_err(|_| MonitorError::Config(format!("{name} is not set")))
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, MonitorError> {
+    value
+        .parse()
+        .map_err(|_| MonitorError::Config(format!("invalid {name}: {value}")))
+}
+
+fn parse_or<T: FromStr>(name: &str, default: T) -> Result<T, MonitorError> {
+    match env::var(name) {
+        Ok(value) => parse(&value, name),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,33 @@
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::pubsub_client::PubsubClientError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MonitorError {
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("account {0} not found")]
+    AccountNotFound(String),
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("subscription error: {0}")]
+    Pubsub(Box<PubsubClientError>),
+    #[error("subscription closed")]
+    SubscriptionClosed,
+    #[error("can't decode {0}")]
+    Decode(String),
+    #[error("can't decode {0}")]
+    Event(#[from] tsv_events::DecodeError),
+}
+
+impl From<ClientError> for MonitorError {
+    fn from(err: ClientError) -> Self {
+        MonitorError::Rpc(Box::new(err))
+    }
+}
+
+impl From<PubsubClientError> for MonitorError {
+    fn from(err: PubsubClientError) -> Self {
+        MonitorError::Pubsub(Box::new(err))
+    }
+}
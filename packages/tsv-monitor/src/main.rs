@@ -0,0 +1,54 @@
+//! Prometheus exporter for the health of a Talken Stable Vault: TVL, share price, idle buffer,
+//! strategy allocations, withdrawal queue, pause and oracle state, and counters of the admin
+//! actions confirmed while it runs.
+
+mod config;
+mod error;
+mod metrics;
+mod monitor;
+mod vault;
+
+use std::sync::Arc;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::Config;
+use crate::error::MonitorError;
+use crate::metrics::Metrics;
+use crate::monitor::Monitor;
+use crate::vault::VaultState;
+
+#[tokio::main]
+async fn main() -> Result<(), MonitorError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let rpc = Arc::new(RpcClient::new_with_commitment(
+        config.rpc_url.clone(),
+        CommitmentConfig::confirmed(),
+    ));
+    let vault = VaultState::fetch(&rpc, config.vault_asset_mint).await?;
+    tracing::info!(vault = %vault.address, "starting");
+
+    let metrics = Arc::new(Metrics::new()?);
+    let monitor = Arc::new(Monitor {
+        rpc,
+        vault,
+        metrics: metrics.clone(),
+    });
+    tokio::spawn(monitor.clone().run(config.refresh_interval));
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = monitor.follow_admin_actions(&config.ws_url).await {
+                tracing::warn!(%err, "subscription ended, resubscribing");
+            }
+            tokio::time::sleep(config.reconnect_delay).await;
+        }
+    });
+
+    metrics.serve(config.metrics_addr).await
+}
@@ -0,0 +1,185 @@
+//! Prometheus metrics of the vault's health, served on `/metrics`.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    Encoder, Gauge, GaugeVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use tokio::net::TcpListener;
+
+use crate::error::MonitorError;
+
+pub struct Metrics {
+    registry: Registry,
+    /// `Vault::total_assets`, the vault's TVL
+    pub total_assets: IntGauge,
+    pub total_shares: IntGauge,
+    /// Assets per share in base units, 1.0 until the vault earns
+    pub share_price: Gauge,
+    pub idle_assets: IntGauge,
+    /// Idle USDC as a share of `total_assets`
+    pub idle_ratio: Gauge,
+    /// Share of `total_assets` the vault keeps idle (`buffer_bps`)
+    pub buffer_target_ratio: Gauge,
+    pub allocated_assets: IntGauge,
+    /// `Strategy::allocated` by adapter program
+    pub strategy_allocated: IntGaugeVec,
+    /// `Strategy::allocated` as a share of `total_assets`, by adapter program
+    pub strategy_allocation_ratio: GaugeVec,
+    /// `target_weight_bps` as a share, by adapter program
+    pub strategy_target_ratio: GaugeVec,
+    pub queued_withdraw_shares: IntGauge,
+    pub reserved_withdraw_assets: IntGauge,
+    /// Withdrawal requests with unfilled shares
+    pub open_withdraw_requests: IntGauge,
+    /// Admin actions seen since the monitor started, by instruction
+    pub admin_actions: IntCounterVec,
+    /// 1 while `flow` (`deposits`, `redeems`, `meta`) is paused
+    pub paused: IntGaugeVec,
+    pub depeg_tripped: IntGauge,
+    /// 1 once a wind-down has started
+    pub winding_down: IntGauge,
+    /// 1 while the vault has a price feed set
+    pub oracle_configured: IntGauge,
+    /// Seconds since the feed's last publish
+    pub oracle_age: IntGauge,
+    /// 1 while the feed is older than `max_price_age` or can't be read
+    pub oracle_stale: IntGauge,
+    /// Distance of USDC from $1 in bps
+    pub oracle_deviation: IntGauge,
+    /// Unix time of the last refresh that read the vault
+    pub last_refresh: IntGauge,
+    pub refresh_errors: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, MonitorError> {
+        let registry = Registry::new_custom(Some("tsv_monitor".into()), None)
+            .map_err(|err| MonitorError::Config(format!("can't create registry: {err}")))?;
+        let register = |metric: Box<dyn prometheus::core::Collector>| {
+            registry.register(metric).map_err(metric_error)
+        };
+        let gauge = |name: &str, help: &str| -> Result<IntGauge, MonitorError> {
+            let gauge = IntGauge::new(name, help).map_err(metric_error)?;
+            register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let ratio = |name: &str, help: &str| -> Result<Gauge, MonitorError> {
+            let gauge = Gauge::new(name, help).map_err(metric_error)?;
+            register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let gauge_vec = |name: &str, help: &str, labels: &[&str]| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), labels).map_err(metric_error)?;
+            register(Box::new(gauge.clone()))?;
+            Ok::<_, MonitorError>(gauge)
+        };
+        let ratio_vec = |name: &str, help: &str| {
+            let gauge =
+                GaugeVec::new(Opts::new(name, help), &["adapter_program"]).map_err(metric_error)?;
+            register(Box::new(gauge.clone()))?;
+            Ok::<_, MonitorError>(gauge)
+        };
+
+        let admin_actions = IntCounterVec::new(
+            Opts::new("admin_actions_total", "Admin actions by instruction"),
+            &["action"],
+        )
+        .map_err(metric_error)?;
+        register(Box::new(admin_actions.clone()))?;
+        let refresh_errors =
+            IntCounter::new("refresh_errors_total", "Failed vault reads").map_err(metric_error)?;
+        register(Box::new(refresh_errors.clone()))?;
+
+        Ok(Self {
+            total_assets: gauge("vault_total_assets", "Vault total assets, USDC base units")?,
+            total_shares: gauge("vault_total_shares", "Vault share supply")?,
+            share_price: ratio("vault_share_price", "Assets per share")?,
+            idle_assets: gauge("vault_idle_assets", "USDC available in the asset vault")?,
+            idle_ratio: ratio("vault_idle_ratio", "Idle USDC over total assets")?,
+            buffer_target_ratio: ratio(
+                "vault_buffer_target_ratio",
+                "Share of total assets kept idle",
+            )?,
+            allocated_assets: gauge("vault_allocated_assets", "USDC allocated to strategies")?,
+            strategy_allocated: gauge_vec(
+                "strategy_allocated",
+                "Principal allocated to the strategy",
+                &["adapter_program"],
+            )?,
+            strategy_allocation_ratio: ratio_vec(
+                "strategy_allocation_ratio",
+                "Strategy allocation over total assets",
+            )?,
+            strategy_target_ratio: ratio_vec("strategy_target_ratio", "Strategy target weight")?,
+            queued_withdraw_shares: gauge(
+                "withdraw_queue_shares",
+                "Shares queued for withdrawal in the current epoch",
+            )?,
+            reserved_withdraw_assets: gauge(
+                "withdraw_reserved_assets",
+                "USDC reserved for unclaimed withdrawals",
+            )?,
+            open_withdraw_requests: gauge(
+                "withdraw_queue_requests",
+                "Withdrawal requests with unfilled shares",
+            )?,
+            admin_actions,
+            paused: gauge_vec("vault_paused", "Flow paused", &["flow"])?,
+            depeg_tripped: gauge("vault_depeg_tripped", "Depeg breaker tripped")?,
+            winding_down: gauge("vault_winding_down", "Wind-down started")?,
+            oracle_configured: gauge("oracle_configured", "Price feed set")?,
+            oracle_age: gauge(
+                "oracle_price_age_seconds",
+                "Seconds since the price feed's last publish",
+            )?,
+            oracle_stale: gauge(
+                "oracle_stale",
+                "Price feed older than max_price_age or unreadable",
+            )?,
+            oracle_deviation: gauge(
+                "oracle_price_deviation_bps",
+                "Distance of USDC from $1 in bps",
+            )?,
+            last_refresh: gauge(
+                "last_refresh_timestamp",
+                "Unix time of the last successful vault read",
+            )?,
+            refresh_errors,
+            registry,
+        })
+    }
+
+    /// Serve `/metrics` on `addr` until the server fails
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<(), MonitorError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|err| MonitorError::Config(format!("can't bind {addr}: {err}")))?;
+        tracing::info!(%addr, "serving metrics");
+        let router = Router::new()
+            .route("/metrics", get(render))
+            .route("/health", get(|| async { "ok" }))
+            .with_state(self);
+        axum::serve(listener, router)
+            .await
+            .map_err(|err| MonitorError::Config(format!("metrics server error: {err}")))
+    }
+}
+
+async fn render(State(metrics): State<Arc<Metrics>>) -> Result<String, StatusCode> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&metrics.registry.gather(), &mut buffer)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    String::from_utf8(buffer).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn metric_error(err: prometheus::Error) -> MonitorError {
+    MonitorError::Config(format!("can't register metric: {err}"))
+}
@@ -0,0 +1,223 @@
+//! Reads the vault into the metrics on a schedule, and counts admin actions as their
+//! transactions are confirmed.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::StreamExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{
+    RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use tokio::time::{interval, MissedTickBehavior};
+use tsv_events::VaultEvent;
+use tsv_usdc_vault::math::Rounding;
+use tsv_usdc_vault::EXCHANGE_RATE_SCALE;
+
+use crate::error::MonitorError;
+use crate::metrics::Metrics;
+use crate::vault::{Oracle, VaultState};
+
+/// Attempts at reading a notified transaction the node doesn't serve yet
+const FETCH_ATTEMPTS: u32 = 5;
+
+pub struct Monitor {
+    pub rpc: Arc<RpcClient>,
+    pub vault: VaultState,
+    pub metrics: Arc<Metrics>,
+}
+
+impl Monitor {
+    /// Refresh the vault metrics every `period`, forever. A failed read is logged and counted,
+    /// leaving the gauges at their last values
+    pub async fn run(self: Arc<Self>, period: Duration) {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = self.refresh().await {
+                tracing::error!(%err, "refresh failed");
+                self.metrics.refresh_errors.inc();
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<(), MonitorError> {
+        let reading = self.vault.read(&self.rpc).await?;
+        let vault = &reading.vault;
+        let metrics = &self.metrics;
+        let now = unix_now();
+
+        metrics.total_assets.set(vault.total_assets as i64);
+        metrics.total_shares.set(vault.total_shares as i64);
+        let rate = vault
+            .convert_to_assets(EXCHANGE_RATE_SCALE, Rounding::Floor)
+            .unwrap_or(0);
+        metrics
+            .share_price
+            .set(rate as f64 / EXCHANGE_RATE_SCALE as f64);
+        let idle_assets = vault.idle_assets(&reading.asset_vault);
+        metrics.idle_assets.set(idle_assets as i64);
+        metrics
+            .idle_ratio
+            .set(ratio(idle_assets, vault.total_assets));
+        metrics
+            .buffer_target_ratio
+            .set(f64::from(vault.buffer_bps) / 10_000.0);
+        metrics.allocated_assets.set(vault.allocated_assets as i64);
+
+        // Reset so removed strategies drop out
+        metrics.strategy_allocated.reset();
+        metrics.strategy_allocation_ratio.reset();
+        metrics.strategy_target_ratio.reset();
+        for strategy in &reading.strategies {
+            let label = strategy.adapter_program.to_string();
+            metrics
+                .strategy_allocated
+                .with_label_values(&[&label])
+                .set(strategy.allocated as i64);
+            metrics
+                .strategy_allocation_ratio
+                .with_label_values(&[&label])
+                .set(ratio(strategy.allocated, vault.total_assets));
+            metrics
+                .strategy_target_ratio
+                .with_label_values(&[&label])
+                .set(f64::from(strategy.target_weight_bps) / 10_000.0);
+        }
+
+        metrics
+            .queued_withdraw_shares
+            .set(vault.queued_withdraw_shares as i64);
+        metrics
+            .reserved_withdraw_assets
+            .set(vault.reserved_withdraw_assets as i64);
+        metrics
+            .open_withdraw_requests
+            .set(reading.open_requests as i64);
+
+        for (flow, paused) in [
+            ("deposits", vault.paused_deposits),
+            ("redeems", vault.paused_redeems),
+            ("meta", vault.paused_meta),
+        ] {
+            metrics
+                .paused
+                .with_label_values(&[flow])
+                .set(i64::from(paused));
+        }
+        metrics.depeg_tripped.set(i64::from(vault.depeg_tripped));
+        metrics.winding_down.set(i64::from(vault.wind_down_ts != 0));
+
+        match &reading.oracle {
+            Oracle::Unset => {
+                metrics.oracle_configured.set(0);
+                metrics.oracle_stale.set(0);
+            }
+            Oracle::Unreadable => {
+                metrics.oracle_configured.set(1);
+                metrics.oracle_stale.set(1);
+            }
+            Oracle::Price(price) => {
+                metrics.oracle_configured.set(1);
+                metrics
+                    .oracle_age
+                    .set(now.saturating_sub(price.publish_time));
+                metrics
+                    .oracle_stale
+                    .set(i64::from(price.is_stale(now, vault.max_price_age)));
+                if let Ok(deviation) = price.deviation_bps() {
+                    metrics.oracle_deviation.set(deviation as i64);
+                }
+            }
+        }
+
+        metrics.last_refresh.set(now);
+        Ok(())
+    }
+
+    /// Count the admin actions of each confirmed transaction of the vault. Returns when the
+    /// subscription drops
+    pub async fn follow_admin_actions(&self, ws_url: &str) -> Result<(), MonitorError> {
+        let pubsub = PubsubClient::new(ws_url).await?;
+        let (mut notifications, _unsubscribe) = pubsub
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![self.vault.address.to_string()]),
+                RpcTransactionLogsConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        tracing::info!("following admin actions");
+
+        while let Some(notification) = notifications.next().await {
+            if notification.value.err.is_some() {
+                continue;
+            }
+            let signature: Signature = notification.value.signature.parse().map_err(|_| {
+                MonitorError::Decode(format!("signature {}", notification.value.signature))
+            })?;
+            // A transaction that can't be read is skipped rather than ending the subscription
+            if let Err(err) = self.count_admin_actions(&signature).await {
+                tracing::warn!(%signature, %err, "can't read admin actions");
+            }
+        }
+        Err(MonitorError::SubscriptionClosed)
+    }
+
+    async fn count_admin_actions(&self, signature: &Signature) -> Result<(), MonitorError> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
+        let mut attempt = 1;
+        let transaction = loop {
+            match self
+                .rpc
+                .get_transaction_with_config(signature, config)
+                .await
+            {
+                Ok(transaction) => break transaction,
+                Err(_) if attempt < FETCH_ATTEMPTS => {
+                    tokio::time::sleep(Duration::from_millis(500 * u64::from(attempt))).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+        let Some(events) = tsv_events::vault_events(&transaction, &self.vault.address)? else {
+            return Ok(());
+        };
+        for event in events {
+            if let Some(VaultEvent::AdminActionEvent(event)) = VaultEvent::decode(&event)? {
+                let action = tsv_events::action_name(&event.action);
+                tracing::info!(%signature, action, "admin action");
+                self.metrics
+                    .admin_actions
+                    .with_label_values(&[action])
+                    .inc();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `part / whole`, 0 for an empty vault
+fn ratio(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
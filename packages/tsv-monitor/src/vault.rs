@@ -0,0 +1,147 @@
+//! Vault accounts the monitor reads, found by the program's seeds and account filters.
+
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anchor_spl::token_interface::TokenAccount;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::account_info::IntoAccountInfo;
+use solana_sdk::pubkey::Pubkey;
+use tsv_usdc_vault::oracle::UsdPrice;
+use tsv_usdc_vault::{Strategy, Vault, WithdrawRequest};
+
+use crate::error::MonitorError;
+
+pub struct VaultState {
+    pub address: Pubkey,
+    pub asset_vault: Pubkey,
+}
+
+/// The vault's depeg breaker feed as last read
+pub enum Oracle {
+    /// No feed set
+    Unset,
+    /// Feed set but missing or not a verified USDC/USD update
+    Unreadable,
+    Price(UsdPrice),
+}
+
+/// Vault state read by one refresh
+pub struct Reading {
+    pub vault: Vault,
+    pub asset_vault: TokenAccount,
+    /// Every `Strategy` of the vault
+    pub strategies: Vec<Strategy>,
+    /// Withdrawal requests with unfilled shares
+    pub open_requests: usize,
+    pub oracle: Oracle,
+}
+
+impl VaultState {
+    /// Read the vault of `asset_mint`, or the legacy `[b"vault"]` one without it
+    pub async fn fetch(rpc: &RpcClient, asset_mint: Option<Pubkey>) -> Result<Self, MonitorError> {
+        let address = match asset_mint {
+            Some(asset_mint) => pda(&[b"vault", asset_mint.as_ref()]),
+            None => pda(&[b"vault"]),
+        };
+        let vault: Vault = decode(&rpc.get_account(&address).await?, "vault")?;
+        Ok(Self {
+            address,
+            asset_vault: vault.asset_vault,
+        })
+    }
+
+    pub async fn read(&self, rpc: &RpcClient) -> Result<Reading, MonitorError> {
+        let mut accounts = rpc
+            .get_multiple_accounts(&[self.address, self.asset_vault])
+            .await?
+            .into_iter();
+        let vault = accounts
+            .next()
+            .flatten()
+            .ok_or_else(|| MonitorError::AccountNotFound(self.address.to_string()))?;
+        let vault = decode::<Vault>(&vault, "vault")?;
+        let asset_vault = accounts
+            .next()
+            .flatten()
+            .ok_or_else(|| MonitorError::AccountNotFound(self.asset_vault.to_string()))?;
+        let asset_vault = decode::<TokenAccount>(&asset_vault, "asset vault")?;
+
+        let strategies = self
+            .program_accounts::<Strategy>(rpc)
+            .await?
+            .iter()
+            .map(|account| decode::<Strategy>(account, "strategy"))
+            .collect::<Result<_, _>>()?;
+        let mut open_requests = 0;
+        for account in self.program_accounts::<WithdrawRequest>(rpc).await? {
+            if decode::<WithdrawRequest>(&account, "withdraw request")?.remaining_shares > 0 {
+                open_requests += 1;
+            }
+        }
+        let oracle = oracle(rpc, &vault.price_feed).await?;
+
+        Ok(Reading {
+            vault,
+            asset_vault,
+            strategies,
+            open_requests,
+            oracle,
+        })
+    }
+
+    /// Accounts of type `T` whose first field is this vault
+    async fn program_accounts<T: Discriminator>(
+        &self,
+        rpc: &RpcClient,
+    ) -> Result<Vec<Account>, MonitorError> {
+        let accounts = rpc
+            .get_program_accounts_with_config(
+                &tsv_usdc_vault::ID,
+                RpcProgramAccountsConfig {
+                    filters: Some(vec![
+                        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, T::DISCRIMINATOR.to_vec())),
+                        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                            8,
+                            self.address.to_bytes().to_vec(),
+                        )),
+                    ]),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        commitment: Some(rpc.commitment()),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                },
+            )
+            .await?;
+        Ok(accounts.into_iter().map(|(_, account)| account).collect())
+    }
+}
+
+/// Latest update of `price_feed`, read the way `check_price` reads it
+async fn oracle(rpc: &RpcClient, price_feed: &Pubkey) -> Result<Oracle, MonitorError> {
+    if *price_feed == Pubkey::default() {
+        return Ok(Oracle::Unset);
+    }
+    let account = rpc
+        .get_account_with_commitment(price_feed, rpc.commitment())
+        .await?
+        .value;
+    let Some(mut account) = account else {
+        return Ok(Oracle::Unreadable);
+    };
+    let info = (price_feed, &mut account).into_account_info();
+    Ok(UsdPrice::load(&info).map_or(Oracle::Unreadable, Oracle::Price))
+}
+
+fn decode<T: AccountDeserialize>(account: &Account, name: &str) -> Result<T, MonitorError> {
+    T::try_deserialize(&mut account.data.as_slice())
+        .map_err(|err| MonitorError::Decode(format!("{name}: {err}")))
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0
+}
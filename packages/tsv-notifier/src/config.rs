@@ -0,0 +1,71 @@
+//! Notifier settings, read from the environment.
+
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::NotifierError;
+
+pub struct Config {
+    /// Solana JSON-RPC endpoint (`RPC_URL`)
+    pub rpc_url: String,
+    /// Solana websocket endpoint the vault's transactions are followed on (`WS_URL`, default
+    /// `RPC_URL` with its scheme changed to `ws`/`wss`)
+    pub ws_url: String,
+    /// Asset mint of the vault to watch (`VAULT_ASSET_MINT`, optional; without it the legacy
+    /// vault at the single-vault address)
+    pub vault_asset_mint: Option<Pubkey>,
+    /// Webhooks and the rules routing notices to them (`RULES_FILE`, JSON)
+    pub rules_file: String,
+    /// Attempts at delivering a notice to a webhook (`WEBHOOK_ATTEMPTS`, default 3)
+    pub webhook_attempts: u32,
+    /// Timeout of each webhook request (`WEBHOOK_TIMEOUT_SECS`, default 10)
+    pub webhook_timeout: Duration,
+    /// Wait before resubscribing after the websocket drops (`RECONNECT_DELAY_SECS`, default 5)
+    pub reconnect_delay: Duration,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, NotifierError> {
+        let rpc_url = required("RPC_URL")?;
+        let ws_url = env::var("WS_URL").unwrap_or_else(|_| rpc_url.replacen("http", "ws", 1));
+        let webhook_attempts = parse_or("WEBHOOK_ATTEMPTS", 3)?;
+        if webhook_attempts == 0 {
+            return Err(NotifierError::Config(
+                "WEBHOOK_ATTEMPTS must be positive".into(),
+            ));
+        }
+
+        Ok(Self {
+            rpc_url,
+            ws_url,
+            vault_asset_mint: env::var("VAULT_ASSET_MINT")
+                .ok()
+                .map(|value| parse(&value, "VAULT_ASSET_MINT"))
+                .transpose()?,
+            rules_file: required("RULES_FILE")?,
+            webhook_attempts,
+            webhook_timeout: Duration::from_secs(parse_or("WEBHOOK_TIMEOUT_SECS", 10)?),
+            reconnect_delay: Duration::from_secs(parse_or("RECONNECT_DELAY_SECS", 5)?),
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, NotifierError> {
+    env::var(name).map_err(|_| NotifierError::Config(format!("{name} is not set")))
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, NotifierError> {
+    value
+        .parse()
+        .map_err(|_| NotifierError::Config(format!("invalid {name}: {value}")))
+}
+
+fn parse_or<T: FromStr>(name: &str, default: T) -> Result<T, NotifierError> {
+    match env::var(name) {
+        Ok(value) => parse(&value, name),
+        Err(_) => Ok(default),
+    }
+}
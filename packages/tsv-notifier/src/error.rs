@@ -0,0 +1,33 @@
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::pubsub_client::PubsubClientError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NotifierError {
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("subscription error: {0}")]
+    Pubsub(Box<PubsubClientError>),
+    #[error("subscription closed")]
+    SubscriptionClosed,
+    #[error("can't decode {0}")]
+    Decode(String),
+    #[error("can't decode {0}")]
+    Event(#[from] tsv_events::DecodeError),
+    #[error("webhook {0} failed: {1}")]
+    Webhook(String, String),
+}
+
+impl From<ClientError> for NotifierError {
+    fn from(err: ClientError) -> Self {
+        NotifierError::Rpc(Box::new(err))
+    }
+}
+
+impl From<PubsubClientError> for NotifierError {
+    fn from(err: PubsubClientError) -> Self {
+        NotifierError::Pubsub(Box::new(err))
+    }
+}
@@ -0,0 +1,410 @@
+//! Vault events turned into the notices rules can route to webhooks.
+
+use solana_sdk::pubkey::Pubkey;
+use tsv_events::VaultEvent;
+
+/// Something that happened to the vault, for rules to match and templates to render
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notice {
+    /// `redemption`, `fee_change`, `pause`, `emergency` or `admin_action`
+    pub topic: &'static str,
+    /// Event the notice comes from, e.g. `InstantRedeemEvent`
+    pub event: &'static str,
+    /// Base units of the vault's asset the event moved, compared with a rule's `minAmount`
+    pub amount: Option<u64>,
+    /// One line for humans
+    pub summary: String,
+    /// Event fields templates can use, by name
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Notice for an event of the vault, `None` for events no topic covers. Amounts in summaries
+/// are shown with the asset's `decimals`
+pub fn notice(event: &VaultEvent, decimals: u8) -> Option<Notice> {
+    let notice = match event {
+        VaultEvent::RedeemEvent(event) => redemption(
+            "RedeemEvent",
+            "redeem",
+            &event.user,
+            event.assets,
+            event.shares,
+            event.fee,
+            decimals,
+        ),
+        VaultEvent::InstantRedeemEvent(event) => redemption(
+            "InstantRedeemEvent",
+            "instant",
+            &event.user,
+            event.assets,
+            event.shares,
+            event.fee,
+            decimals,
+        ),
+        VaultEvent::MetaRedeemEvent(event) => redemption(
+            "MetaRedeemEvent",
+            "meta",
+            &event.owner,
+            event.assets,
+            event.shares,
+            event.fee,
+            decimals,
+        ),
+        VaultEvent::SessionRedeemEvent(event) => redemption(
+            "SessionRedeemEvent",
+            "session",
+            &event.owner,
+            event.assets,
+            event.shares,
+            event.fee,
+            decimals,
+        ),
+        VaultEvent::BasketRedeemEvent(event) => redemption(
+            "BasketRedeemEvent",
+            "basket",
+            &event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            decimals,
+        ),
+        VaultEvent::ProportionalRedeemEvent(event) => redemption(
+            "ProportionalRedeemEvent",
+            "proportional",
+            &event.user,
+            event.value,
+            event.shares,
+            event.fee,
+            decimals,
+        ),
+        VaultEvent::JuniorRedeemEvent(event) => redemption(
+            "JuniorRedeemEvent",
+            "junior",
+            &event.user,
+            event.assets,
+            event.shares,
+            0,
+            decimals,
+        ),
+        VaultEvent::WindDownClaimEvent(event) => redemption(
+            "WindDownClaimEvent",
+            "wind_down",
+            &event.user,
+            event.assets,
+            event.shares,
+            0,
+            decimals,
+        ),
+        VaultEvent::WithdrawClaimedEvent(event) => redemption(
+            "WithdrawClaimedEvent",
+            "queued",
+            &event.owner,
+            event.assets,
+            event.shares,
+            0,
+            decimals,
+        ),
+        VaultEvent::FeeQueuedEvent(event) => fee_change(
+            "FeeQueuedEvent",
+            format!(
+                "Fee update queued: deposit {} bps, redeem {} bps, executable at {}",
+                event.new_deposit_fee_bps, event.new_redeem_fee_bps, event.eta
+            ),
+            vec![
+                ("deposit_fee_bps", event.new_deposit_fee_bps.to_string()),
+                ("redeem_fee_bps", event.new_redeem_fee_bps.to_string()),
+                ("eta", event.eta.to_string()),
+            ],
+        ),
+        VaultEvent::FeeExecutedEvent(event) => fee_change(
+            "FeeExecutedEvent",
+            format!(
+                "Fees changed: deposit {} bps, redeem {} bps",
+                event.new_deposit_fee_bps, event.new_redeem_fee_bps
+            ),
+            vec![
+                ("deposit_fee_bps", event.new_deposit_fee_bps.to_string()),
+                ("redeem_fee_bps", event.new_redeem_fee_bps.to_string()),
+            ],
+        ),
+        VaultEvent::ManagementFeeUpdatedEvent(event) => fee_change(
+            "ManagementFeeUpdatedEvent",
+            format!("Management fee set to {} bps", event.management_fee_bps),
+            vec![("management_fee_bps", event.management_fee_bps.to_string())],
+        ),
+        VaultEvent::PerformanceFeeUpdatedEvent(event) => fee_change(
+            "PerformanceFeeUpdatedEvent",
+            format!("Performance fee set to {} bps", event.performance_fee_bps),
+            vec![("performance_fee_bps", event.performance_fee_bps.to_string())],
+        ),
+        VaultEvent::RedeemFeeCurveUpdatedEvent(event) => fee_change(
+            "RedeemFeeCurveUpdatedEvent",
+            format!("Maximum redeem fee set to {} bps", event.max_redeem_fee_bps),
+            vec![("max_redeem_fee_bps", event.max_redeem_fee_bps.to_string())],
+        ),
+        VaultEvent::EarlyExitPenaltyUpdatedEvent(event) => fee_change(
+            "EarlyExitPenaltyUpdatedEvent",
+            format!(
+                "Early exit penalty set to {} bps for {} s",
+                event.early_exit_penalty_bps, event.early_exit_window_seconds
+            ),
+            vec![
+                (
+                    "early_exit_penalty_bps",
+                    event.early_exit_penalty_bps.to_string(),
+                ),
+                (
+                    "early_exit_window_seconds",
+                    event.early_exit_window_seconds.to_string(),
+                ),
+            ],
+        ),
+        VaultEvent::InstantRedeemPremiumUpdatedEvent(event) => fee_change(
+            "InstantRedeemPremiumUpdatedEvent",
+            format!(
+                "Instant redeem premium set to {} bps",
+                event.instant_redeem_premium_bps
+            ),
+            vec![(
+                "instant_redeem_premium_bps",
+                event.instant_redeem_premium_bps.to_string(),
+            )],
+        ),
+        VaultEvent::FeeRecipientUpdatedEvent(event) => fee_change(
+            "FeeRecipientUpdatedEvent",
+            format!("Fee recipient set to {}", event.fee_recipient),
+            vec![("fee_recipient", event.fee_recipient.to_string())],
+        ),
+        VaultEvent::PauseFlagsUpdatedEvent(event) => Notice {
+            topic: "pause",
+            event: "PauseFlagsUpdatedEvent",
+            amount: None,
+            summary: format!(
+                "Pause flags set: deposits {}, redeems {}, meta {}",
+                event.paused_deposits, event.paused_redeems, event.paused_meta
+            ),
+            fields: vec![
+                ("paused_deposits", event.paused_deposits.to_string()),
+                ("paused_redeems", event.paused_redeems.to_string()),
+                ("paused_meta", event.paused_meta.to_string()),
+            ],
+        },
+        VaultEvent::GuardianPauseEvent(event) => Notice {
+            topic: "pause",
+            event: "GuardianPauseEvent",
+            amount: None,
+            summary: format!("Guardian {} paused the vault", event.guardian),
+            fields: vec![("guardian", event.guardian.to_string())],
+        },
+        VaultEvent::DepegBreakerTrippedEvent(event) => Notice {
+            topic: "pause",
+            event: "DepegBreakerTrippedEvent",
+            amount: None,
+            summary: format!(
+                "Depeg breaker tripped: USDC {} bps from $1{}",
+                event.deviation_bps,
+                if event.stale { ", price stale" } else { "" }
+            ),
+            fields: vec![
+                ("deviation_bps", event.deviation_bps.to_string()),
+                ("stale", event.stale.to_string()),
+                ("publish_time", event.publish_time.to_string()),
+            ],
+        },
+        VaultEvent::LossEvent(event) => {
+            if !event.deposits_paused {
+                return None;
+            }
+            Notice {
+                topic: "pause",
+                event: "LossEvent",
+                amount: Some(event.socialized),
+                summary: format!(
+                    "Deposits paused after a {} loss ({} bps) on {}",
+                    ui_amount(event.socialized, decimals),
+                    event.loss_bps,
+                    event.adapter_program
+                ),
+                fields: vec![
+                    ("adapter_program", event.adapter_program.to_string()),
+                    ("loss", event.loss.to_string()),
+                    ("socialized", event.socialized.to_string()),
+                    ("loss_bps", event.loss_bps.to_string()),
+                ],
+            }
+        }
+        VaultEvent::EmergencyWithdrawEvent(event) => Notice {
+            topic: "emergency",
+            event: "EmergencyWithdrawEvent",
+            amount: Some(event.amount),
+            summary: format!(
+                "Emergency withdrawal of {} to {}",
+                ui_amount(event.amount, decimals),
+                event.recovery_account
+            ),
+            fields: vec![
+                ("authority", event.authority.to_string()),
+                ("recovery_account", event.recovery_account.to_string()),
+                ("assets", event.amount.to_string()),
+            ],
+        },
+        VaultEvent::EmergencyEscapeRequestedEvent(event) => Notice {
+            topic: "emergency",
+            event: "EmergencyEscapeRequestedEvent",
+            amount: None,
+            summary: format!(
+                "Emergency escape requested, executable at {}",
+                event.escape_ts
+            ),
+            fields: vec![("escape_ts", event.escape_ts.to_string())],
+        },
+        VaultEvent::StrategyEmergencyExitEvent(event) => Notice {
+            topic: "emergency",
+            event: "StrategyEmergencyExitEvent",
+            amount: Some(event.value),
+            summary: format!(
+                "Emergency exit of {}: {} received of {}, {} unrecovered",
+                event.adapter_program,
+                ui_amount(event.received, decimals),
+                ui_amount(event.value, decimals),
+                ui_amount(event.allocated, decimals)
+            ),
+            fields: vec![
+                ("adapter_program", event.adapter_program.to_string()),
+                ("guardian", event.guardian.to_string()),
+                ("value", event.value.to_string()),
+                ("received", event.received.to_string()),
+                ("allocated", event.allocated.to_string()),
+            ],
+        },
+        VaultEvent::AdminActionEvent(event) => {
+            let action = tsv_events::action_name(&event.action);
+            Notice {
+                topic: "admin_action",
+                event: "AdminActionEvent",
+                amount: None,
+                summary: format!("Admin action {action} by {}", event.actor),
+                fields: vec![
+                    ("action", action.to_string()),
+                    ("actor", event.actor.to_string()),
+                ],
+            }
+        }
+        _ => return None,
+    };
+    Some(notice)
+}
+
+fn redemption(
+    event: &'static str,
+    kind: &str,
+    owner: &Pubkey,
+    assets: u64,
+    shares: u64,
+    fee: u64,
+    decimals: u8,
+) -> Notice {
+    Notice {
+        topic: "redemption",
+        event,
+        amount: Some(assets),
+        summary: format!(
+            "Redemption ({kind}) of {} by {owner}",
+            ui_amount(assets, decimals)
+        ),
+        fields: vec![
+            ("kind", kind.to_string()),
+            ("owner", owner.to_string()),
+            ("assets", assets.to_string()),
+            ("shares", shares.to_string()),
+            ("fee", fee.to_string()),
+        ],
+    }
+}
+
+fn fee_change(event: &'static str, summary: String, fields: Vec<(&'static str, String)>) -> Notice {
+    Notice {
+        topic: "fee_change",
+        event,
+        amount: None,
+        summary,
+        fields,
+    }
+}
+
+/// `amount` base units with `decimals` decimals, e.g. `1234.5` for 1234500000 at 6
+pub fn ui_amount(amount: u64, decimals: u8) -> String {
+    let scale = 10u64.pow(u32::from(decimals));
+    let fraction = format!("{:0width$}", amount % scale, width = usize::from(decimals));
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        (amount / scale).to_string()
+    } else {
+        format!("{}.{fraction}", amount / scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tsv_events::{
+        AdminAction, AdminActionEvent, FeesCollectedEvent, InstantRedeemEvent, VaultSnapshot,
+    };
+
+    use super::*;
+
+    #[test]
+    fn redemptions_carry_their_assets_for_thresholds() {
+        let owner = Pubkey::new_unique();
+        let event = InstantRedeemEvent {
+            user: owner,
+            receiver: owner,
+            shares: 900,
+            assets: 1_234_500_000,
+            fee: 10,
+            premium: 0,
+            snapshot: VaultSnapshot {
+                sequence: 1,
+                total_assets: 0,
+                total_shares: 0,
+                exchange_rate: 0,
+            },
+        };
+
+        let notice = notice(&VaultEvent::InstantRedeemEvent(event), 6).unwrap();
+
+        assert_eq!(notice.topic, "redemption");
+        assert_eq!(notice.amount, Some(1_234_500_000));
+        assert_eq!(
+            notice.summary,
+            format!("Redemption (instant) of 1234.5 by {owner}")
+        );
+    }
+
+    #[test]
+    fn names_admin_actions_and_skips_uncovered_events() {
+        let pause = AdminActionEvent {
+            actor: Pubkey::new_unique(),
+            action: AdminAction::GuardianPause,
+            timestamp: 1,
+        };
+        let fees = FeesCollectedEvent {
+            fee_recipient: Pubkey::new_unique(),
+            amount: 5,
+        };
+
+        let notice = notice(&VaultEvent::AdminActionEvent(pause), 6).unwrap();
+        assert_eq!(notice.topic, "admin_action");
+        assert!(notice.fields.contains(&("action", "guardian_pause".into())));
+        assert_eq!(
+            super::notice(&VaultEvent::FeesCollectedEvent(fees), 6),
+            None
+        );
+    }
+
+    #[test]
+    fn formats_amounts_with_the_asset_decimals() {
+        assert_eq!(ui_amount(1_000_000, 6), "1");
+        assert_eq!(ui_amount(1_050_000, 6), "1.05");
+        assert_eq!(ui_amount(7, 6), "0.000007");
+        assert_eq!(ui_amount(42, 0), "42");
+    }
+}
@@ -0,0 +1,68 @@
+//! Notifier for Talken Stable Vault events: follows the vault's transactions over an RPC
+//! websocket and posts templated webhooks (Slack, PagerDuty, ...) for large redemptions, fee
+//! changes, pauses and emergencies, as routed by the rules file.
+
+mod config;
+mod error;
+mod events;
+mod notifier;
+mod rules;
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::token_interface::Mint;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tracing_subscriber::EnvFilter;
+use tsv_usdc_vault::Vault;
+
+use crate::config::Config;
+use crate::error::NotifierError;
+use crate::notifier::Notifier;
+use crate::rules::Rules;
+
+#[tokio::main]
+async fn main() -> Result<(), NotifierError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let rules = Rules::load(&config.rules_file)?;
+    let rpc = RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::confirmed());
+
+    let seeds: &[&[u8]] = match &config.vault_asset_mint {
+        Some(asset_mint) => &[b"vault", asset_mint.as_ref()],
+        None => &[b"vault"],
+    };
+    let vault = Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0;
+    let data = rpc.get_account_data(&vault).await?;
+    let asset_mint = Vault::try_deserialize(&mut data.as_slice())
+        .map_err(|err| NotifierError::Config(format!("can't decode vault {vault}: {err}")))?
+        .asset_mint;
+    let data = rpc.get_account_data(&asset_mint).await?;
+    let decimals = Mint::try_deserialize(&mut data.as_slice())
+        .map_err(|err| NotifierError::Config(format!("can't decode mint {asset_mint}: {err}")))?
+        .decimals;
+
+    let http = reqwest::Client::builder()
+        .timeout(config.webhook_timeout)
+        .build()
+        .map_err(|err| NotifierError::Config(format!("can't build HTTP client: {err}")))?;
+    tracing::info!(%vault, webhooks = rules.webhooks.len(), "starting");
+    let notifier = Notifier {
+        rpc,
+        http,
+        rules,
+        vault,
+        decimals,
+        webhook_attempts: config.webhook_attempts,
+    };
+
+    loop {
+        if let Err(err) = notifier.follow(&config.ws_url).await {
+            tracing::warn!(%err, "subscription ended, resubscribing");
+        }
+        tokio::time::sleep(config.reconnect_delay).await;
+    }
+}
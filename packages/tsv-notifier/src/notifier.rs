@@ -0,0 +1,164 @@
+//! Follows the vault's confirmed transactions and posts a notice of each covered event to the
+//! webhooks its rules route it to.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{
+    RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use tsv_events::VaultEvent;
+
+use crate::error::NotifierError;
+use crate::events::{self, Notice};
+use crate::rules::{self, Rules, Webhook};
+
+/// Attempts at reading a notified transaction the node doesn't serve yet
+const FETCH_ATTEMPTS: u32 = 5;
+
+pub struct Notifier {
+    pub rpc: RpcClient,
+    pub http: reqwest::Client,
+    pub rules: Rules,
+    pub vault: Pubkey,
+    /// Decimals of the vault's asset, for amounts in summaries
+    pub decimals: u8,
+    pub webhook_attempts: u32,
+}
+
+impl Notifier {
+    /// Notify the covered events of each confirmed transaction of the vault. Returns when the
+    /// subscription drops
+    pub async fn follow(&self, ws_url: &str) -> Result<(), NotifierError> {
+        let pubsub = PubsubClient::new(ws_url).await?;
+        let (mut notifications, _unsubscribe) = pubsub
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![self.vault.to_string()]),
+                RpcTransactionLogsConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        tracing::info!("following");
+
+        while let Some(notification) = notifications.next().await {
+            if notification.value.err.is_some() {
+                continue;
+            }
+            let signature: Signature = notification.value.signature.parse().map_err(|_| {
+                NotifierError::Decode(format!("signature {}", notification.value.signature))
+            })?;
+            // A transaction that can't be read is skipped rather than ending the subscription
+            if let Err(err) = self.notify(&signature).await {
+                tracing::warn!(%signature, %err, "can't read events");
+            }
+        }
+        Err(NotifierError::SubscriptionClosed)
+    }
+
+    async fn notify(&self, signature: &Signature) -> Result<(), NotifierError> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
+        let mut attempt = 1;
+        let transaction = loop {
+            match self
+                .rpc
+                .get_transaction_with_config(signature, config)
+                .await
+            {
+                Ok(transaction) => break transaction,
+                Err(_) if attempt < FETCH_ATTEMPTS => {
+                    tokio::time::sleep(Duration::from_millis(500 * u64::from(attempt))).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+        let Some(events) = tsv_events::vault_events(&transaction, &self.vault)? else {
+            return Ok(());
+        };
+
+        for (index, event) in events.iter().enumerate() {
+            let Some(event) = VaultEvent::decode(event)? else {
+                continue;
+            };
+            let Some(notice) = events::notice(&event, self.decimals) else {
+                continue;
+            };
+            let fields = self.fields(&notice, signature, index, transaction.slot);
+            for webhook in self.rules.targets(&notice) {
+                // A webhook that stays down doesn't hold up the others
+                if let Err(err) = self.post(webhook, &fields).await {
+                    tracing::error!(%signature, %err, "notice not delivered");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Placeholders templates can use for `notice`: the event's fields and where it happened
+    fn fields(
+        &self,
+        notice: &Notice,
+        signature: &Signature,
+        index: usize,
+        slot: u64,
+    ) -> BTreeMap<&'static str, String> {
+        let mut fields = BTreeMap::from([
+            ("topic", notice.topic.to_string()),
+            ("event", notice.event.to_string()),
+            ("summary", notice.summary.clone()),
+            ("vault", self.vault.to_string()),
+            ("signature", signature.to_string()),
+            ("slot", slot.to_string()),
+            // Stable across redeliveries, e.g. for PagerDuty's `dedup_key`
+            ("id", format!("{signature}:{index}")),
+        ]);
+        if let Some(amount) = notice.amount {
+            fields.insert("amount", amount.to_string());
+            fields.insert("amount_ui", events::ui_amount(amount, self.decimals));
+        }
+        fields.extend(notice.fields.iter().cloned());
+        fields
+    }
+
+    /// POST the rendered template, retrying with a growing delay until a 2xx response
+    async fn post(
+        &self,
+        webhook: &Webhook,
+        fields: &BTreeMap<&str, String>,
+    ) -> Result<(), NotifierError> {
+        let body = rules::render(&webhook.template, fields);
+        let mut attempt = 1;
+        loop {
+            let mut request = self.http.post(&webhook.url).json(&body);
+            for (name, value) in &webhook.headers {
+                request = request.header(name, value);
+            }
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    tracing::info!(webhook = %webhook.name, "notice delivered");
+                    return Ok(());
+                }
+                Ok(response) => format!("status {}", response.status()),
+                Err(err) => err.to_string(),
+            };
+            if attempt >= self.webhook_attempts {
+                return Err(NotifierError::Webhook(webhook.name.clone(), error));
+            }
+            tracing::warn!(webhook = %webhook.name, attempt, %error, "retrying webhook");
+            tokio::time::sleep(Duration::from_secs(u64::from(attempt))).await;
+            attempt += 1;
+        }
+    }
+}
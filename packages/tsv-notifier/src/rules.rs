@@ -0,0 +1,285 @@
+//! Webhooks and the rules routing notices to them, read from the rules file.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::NotifierError;
+use crate::events::Notice;
+
+/// Topics `events::notice` gives notices
+const TOPICS: [&str; 5] = [
+    "redemption",
+    "fee_change",
+    "pause",
+    "emergency",
+    "admin_action",
+];
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    webhooks: BTreeMap<String, WebhookEntry>,
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookEntry {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// JSON body, `{{field}}` placeholders in its strings filled per notice
+    #[serde(default = "default_template")]
+    template: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleEntry {
+    topic: String,
+    /// Event names the rule is limited to, all of the topic's when empty
+    #[serde(default)]
+    events: Vec<String>,
+    /// Smallest `Notice::amount` matched; notices without an amount don't match
+    min_amount: Option<u64>,
+    webhooks: Vec<String>,
+}
+
+/// A Slack-compatible body: `{"text": "{{summary}}"}`
+fn default_template() -> Value {
+    json!({ "text": "{{summary}}" })
+}
+
+pub struct Webhook {
+    pub name: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub template: Value,
+}
+
+struct Rule {
+    topic: String,
+    events: Vec<String>,
+    min_amount: Option<u64>,
+    /// Indexes into `Rules::webhooks`
+    webhooks: Vec<usize>,
+}
+
+impl Rule {
+    fn matches(&self, notice: &Notice) -> bool {
+        self.topic == notice.topic
+            && (self.events.is_empty() || self.events.iter().any(|event| event == notice.event))
+            && self
+                .min_amount
+                .is_none_or(|min| notice.amount.is_some_and(|amount| amount >= min))
+    }
+}
+
+pub struct Rules {
+    pub webhooks: Vec<Webhook>,
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Read the rules file at `path`
+    pub fn load(path: &str) -> Result<Self, NotifierError> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| NotifierError::Config(format!("can't read RULES_FILE {path}: {err}")))?;
+        Self::parse(&json)
+    }
+
+    fn parse(json: &str) -> Result<Self, NotifierError> {
+        let file: RulesFile = serde_json::from_str(json)
+            .map_err(|err| NotifierError::Config(format!("invalid rules file: {err}")))?;
+        let names: Vec<&String> = file.webhooks.keys().collect();
+        let rules = file
+            .rules
+            .iter()
+            .map(|rule| {
+                if !TOPICS.contains(&rule.topic.as_str()) {
+                    return Err(NotifierError::Config(format!(
+                        "unknown topic {} in rules file",
+                        rule.topic
+                    )));
+                }
+                let webhooks = rule
+                    .webhooks
+                    .iter()
+                    .map(|name| {
+                        names
+                            .iter()
+                            .position(|known| *known == name)
+                            .ok_or_else(|| {
+                                NotifierError::Config(format!(
+                                    "unknown webhook {name} in rules file"
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Rule {
+                    topic: rule.topic.clone(),
+                    events: rule.events.clone(),
+                    min_amount: rule.min_amount,
+                    webhooks,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            webhooks: file
+                .webhooks
+                .into_iter()
+                .map(|(name, entry)| Webhook {
+                    name,
+                    url: entry.url,
+                    headers: entry.headers.into_iter().collect(),
+                    template: entry.template,
+                })
+                .collect(),
+            rules,
+        })
+    }
+
+    /// Webhooks `notice` goes to, each once, whatever number of rules match it
+    pub fn targets(&self, notice: &Notice) -> Vec<&Webhook> {
+        let mut targets: Vec<usize> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(notice))
+            .flat_map(|rule| rule.webhooks.iter().copied())
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+            .into_iter()
+            .map(|index| &self.webhooks[index])
+            .collect()
+    }
+}
+
+/// `template` with every `{{name}}` in its strings replaced by `fields[name]`; unknown names are
+/// left as they are
+pub fn render(template: &Value, fields: &BTreeMap<&str, String>) -> Value {
+    match template {
+        Value::String(text) => Value::String(fill(text, fields)),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| render(item, fields)).collect())
+        }
+        Value::Object(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), render(value, fields)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn fill(text: &str, fields: &BTreeMap<&str, String>) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                match fields.get(name) {
+                    Some(value) => filled.push_str(value),
+                    None => filled.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                filled.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"{
+        "webhooks": {
+            "slack": { "url": "https://hooks.slack.com/services/x" },
+            "pagerduty": {
+                "url": "https://events.pagerduty.com/v2/enqueue",
+                "template": { "event_action": "trigger", "dedup_key": "{{id}}" }
+            }
+        },
+        "rules": [
+            { "topic": "redemption", "minAmount": 1000, "webhooks": ["slack"] },
+            { "topic": "redemption", "minAmount": 5000, "webhooks": ["slack", "pagerduty"] },
+            { "topic": "pause", "events": ["GuardianPauseEvent"], "webhooks": ["pagerduty"] }
+        ]
+    }"#;
+
+    fn notice(topic: &'static str, event: &'static str, amount: Option<u64>) -> Notice {
+        Notice {
+            topic,
+            event,
+            amount,
+            summary: String::new(),
+            fields: vec![],
+        }
+    }
+
+    fn names<'a>(webhooks: &[&'a Webhook]) -> Vec<&'a str> {
+        webhooks
+            .iter()
+            .map(|webhook| webhook.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn routes_notices_by_topic_event_and_amount() {
+        let rules = Rules::parse(RULES).unwrap();
+
+        let small = notice("redemption", "RedeemEvent", Some(999));
+        let large = notice("redemption", "RedeemEvent", Some(5_000));
+        let guardian = notice("pause", "GuardianPauseEvent", None);
+        let flags = notice("pause", "PauseFlagsUpdatedEvent", None);
+
+        assert!(rules.targets(&small).is_empty());
+        assert_eq!(names(&rules.targets(&large)), ["pagerduty", "slack"]);
+        assert_eq!(names(&rules.targets(&guardian)), ["pagerduty"]);
+        assert!(rules.targets(&flags).is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_topics_and_webhooks() {
+        let topic = r#"{ "webhooks": {}, "rules": [{ "topic": "deposit", "webhooks": [] }] }"#;
+        let webhook = r#"{ "webhooks": {}, "rules": [{ "topic": "pause", "webhooks": ["ops"] }] }"#;
+
+        assert!(Rules::parse(topic).is_err());
+        assert!(Rules::parse(webhook).is_err());
+    }
+
+    #[test]
+    fn renders_placeholders_in_every_string() {
+        let template = json!({
+            "text": "{{summary}} ({{ signature }})",
+            "blocks": [{ "text": "{{missing}}" }],
+            "count": 1
+        });
+        let fields = BTreeMap::from([
+            ("summary", "Paused \"all\"".to_string()),
+            ("signature", "5xyz".to_string()),
+        ]);
+
+        assert_eq!(
+            render(&template, &fields),
+            json!({
+                "text": "Paused \"all\" (5xyz)",
+                "blocks": [{ "text": "{{missing}}" }],
+                "count": 1
+            })
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! HTTP API accepting signed meta-transactions and reporting their status.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::RelayerError;
+use crate::request::{MetaDepositPayload, MetaRedeemPayload, SignedRequest};
+use crate::store::Store;
+use crate::validate::Validator;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Arc<Store>,
+    pub validator: Arc<Validator>,
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/v1/meta-redeem", post(meta_redeem))
+        .route("/v1/meta-deposit", post(meta_deposit))
+        .route("/v1/requests/:id", get(request_status))
+        .with_state(state)
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn meta_redeem(
+    State(state): State<AppState>,
+    Json(payload): Json<MetaRedeemPayload>,
+) -> Result<Response, RelayerError> {
+    accept(&state, payload.try_into()?).await
+}
+
+async fn meta_deposit(
+    State(state): State<AppState>,
+    Json(payload): Json<MetaDepositPayload>,
+) -> Result<Response, RelayerError> {
+    accept(&state, payload.try_into()?).await
+}
+
+async fn request_status(State(state): State<AppState>, Path(id): Path<Uuid>) -> Response {
+    match state.store.status(&id).await {
+        Some(status) => Json(status).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "unknown request" })),
+        )
+            .into_response(),
+    }
+}
+
+/// Validate `request` and queue it. The pending map stays locked until the request is counted,
+/// so concurrent requests of one owner can't both claim the same nonce
+async fn accept(state: &AppState, request: SignedRequest) -> Result<Response, RelayerError> {
+    let mut pending = state.store.pending.lock().await;
+    let owner = request.owner;
+    let ahead = pending.get(&owner).copied().unwrap_or(0);
+    let validated = state.validator.validate(request, ahead).await?;
+    *pending.entry(owner).or_default() += 1;
+    let id = state.store.enqueue(validated).await;
+    drop(pending);
+
+    tracing::info!(%id, %owner, "queued");
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": id }))).into_response())
+}
@@ -0,0 +1,187 @@
+//! Flushes the queue on an interval: consecutive batchable meta-redeems share one
+//! `batch_meta_redeem` transaction, everything else is sent on its own. Transactions are sent
+//! one at a time and awaited, so each owner's requests land in nonce order.
+
+use std::sync::Arc;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use tokio::time::{interval, MissedTickBehavior};
+use tsv_meta_message::MetaAction;
+
+use crate::error::RelayerError;
+use crate::instructions::{self, OwnerOptionals, RelayerAccounts};
+use crate::store::{QueuedRequest, RequestStatus, Store};
+use crate::submitter::Submitter;
+use crate::vault::{self, VaultState};
+
+pub struct Batcher {
+    pub rpc: Arc<RpcClient>,
+    pub vault: Arc<VaultState>,
+    pub store: Arc<Store>,
+    pub submitter: Submitter,
+    pub relayer: RelayerAccounts,
+    pub max_batch_size: usize,
+}
+
+impl Batcher {
+    pub async fn run(self, period: std::time::Duration) {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let queued = self.store.drain().await;
+            for group in group(queued, self.max_batch_size) {
+                self.flush(group).await;
+            }
+        }
+    }
+
+    /// Submit one transaction for `group` and settle each of its requests with the outcome
+    async fn flush(&self, group: Vec<QueuedRequest>) {
+        let result = match self.build(&group).await {
+            Ok(ixs) => {
+                let ids: Vec<_> = group.iter().map(|queued| queued.id).collect();
+                let (store, ids) = (&self.store, &ids);
+                self.submitter
+                    .submit(&ixs, |signature, attempt| async move {
+                        let status = RequestStatus::Submitted {
+                            signature: signature.to_string(),
+                            attempt,
+                        };
+                        for id in ids {
+                            store.set_status(*id, status.clone()).await;
+                        }
+                    })
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+
+        let status = match result {
+            Ok(signature) => RequestStatus::Confirmed {
+                signature: signature.to_string(),
+            },
+            Err(err) => {
+                tracing::error!(count = group.len(), "submission failed: {err}");
+                RequestStatus::Failed {
+                    error: err.to_string(),
+                }
+            }
+        };
+        for queued in &group {
+            self.store
+                .settle(queued.id, &queued.validated.request.owner, status.clone())
+                .await;
+        }
+    }
+
+    /// Instructions of `group`, with optional accounts resolved against their current state
+    async fn build(&self, group: &[QueuedRequest]) -> Result<Vec<Instruction>, RelayerError> {
+        let mut candidates = Vec::with_capacity(group.len() * 2);
+        for queued in group {
+            let owner = &queued.validated.request.owner;
+            candidates.push(self.vault.fee_exemption(owner));
+            candidates.push(self.vault.allowlist_entry(owner));
+        }
+        let exist = vault::exist(&self.rpc, &candidates).await?;
+        let optionals: Vec<OwnerOptionals> = candidates
+            .chunks(2)
+            .zip(exist.chunks(2))
+            .map(|(keys, exist)| OwnerOptionals {
+                fee_exemption: exist[0].then_some(keys[0]),
+                allowlist_entry: exist[1].then_some(keys[1]),
+            })
+            .collect();
+
+        let first = &group[0].validated;
+        Ok(match first.request.action {
+            MetaAction::Deposit { .. } => {
+                instructions::meta_deposit(&self.vault, &self.relayer, first, optionals[0])
+            }
+            MetaAction::Redeem { .. } if group.len() == 1 => {
+                instructions::meta_redeem(&self.vault, &self.relayer, first, optionals[0])
+            }
+            MetaAction::Redeem { .. } => {
+                let requests: Vec<_> = group
+                    .iter()
+                    .map(|queued| &queued.validated)
+                    .zip(optionals)
+                    .collect();
+                instructions::batch_meta_redeem(&self.vault, &self.relayer, &requests)
+            }
+        })
+    }
+}
+
+/// Split `queued` into transactions, keeping arrival order: runs of batchable redeems of up to
+/// `max_batch_size` requests, and every other request alone
+fn group(queued: Vec<QueuedRequest>, max_batch_size: usize) -> Vec<Vec<QueuedRequest>> {
+    let mut groups: Vec<Vec<QueuedRequest>> = Vec::new();
+    let mut batch_open = false;
+    for request in queued {
+        let batchable = request.validated.batchable
+            && matches!(request.validated.request.action, MetaAction::Redeem { .. });
+        match groups.last_mut() {
+            Some(last) if batchable && batch_open && last.len() < max_batch_size => {
+                last.push(request)
+            }
+            _ => groups.push(vec![request]),
+        }
+        batch_open = batchable;
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{SignedRequest, ValidatedRequest};
+    use solana_sdk::pubkey::Pubkey;
+    use uuid::Uuid;
+
+    fn queued(redeem: bool, batchable: bool) -> QueuedRequest {
+        let receiver = Pubkey::new_unique();
+        let action = if redeem {
+            MetaAction::Redeem {
+                shares: 1,
+                receiver,
+                max_relayer_fee: 0,
+            }
+        } else {
+            MetaAction::Deposit {
+                assets: 1,
+                receiver,
+            }
+        };
+        QueuedRequest {
+            id: Uuid::new_v4(),
+            validated: ValidatedRequest {
+                request: SignedRequest {
+                    owner: Pubkey::new_unique(),
+                    nonce: 0,
+                    deadline: 0,
+                    action,
+                    signature: [0; 64],
+                },
+                message: vec![],
+                batchable,
+            },
+        }
+    }
+
+    #[test]
+    fn groups_consecutive_batchable_redeems() {
+        let queue = vec![
+            queued(true, true),
+            queued(true, true),
+            queued(true, true),
+            queued(false, true),
+            queued(true, true),
+            queued(true, false),
+            queued(true, true),
+        ];
+        let sizes: Vec<usize> = group(queue, 2).iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 1, 1, 1, 1, 1]);
+    }
+}
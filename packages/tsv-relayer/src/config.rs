@@ -0,0 +1,115 @@
+//! Relayer settings, read from the environment.
+
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+
+use crate::error::RelayerError;
+
+pub struct Config {
+    /// HTTP API listen address (`BIND_ADDR`, default 0.0.0.0:8080)
+    pub bind_addr: SocketAddr,
+    /// Solana JSON-RPC endpoint (`RPC_URL`)
+    pub rpc_url: String,
+    /// Fee payer and relayer signer (`RELAYER_KEYPAIR`, a keypair JSON file)
+    pub relayer: Keypair,
+    /// Asset mint of the vault to serve (`VAULT_ASSET_MINT`, optional; without it the legacy
+    /// vault at the single-vault address)
+    pub vault_asset_mint: Option<Pubkey>,
+    /// Relayer's USDC account receiving redeem relayer fees (`RELAYER_ASSET`)
+    pub relayer_asset: Pubkey,
+    /// Relayer fee taken from each meta-redeem, capped by the owner's signed maximum
+    /// (`RELAYER_FEE`, USDC base units, default 0)
+    pub relayer_fee: u64,
+    /// Most meta-redeems packed into one `batch_meta_redeem` (`MAX_BATCH_SIZE`, default 1;
+    /// larger batches need `LOOKUP_TABLE` to fit in a transaction)
+    pub max_batch_size: usize,
+    /// How often queued requests are flushed (`BATCH_INTERVAL_MS`, default 2000)
+    pub batch_interval: Duration,
+    /// Address lookup table holding the vault's static accounts (`LOOKUP_TABLE`, optional)
+    pub lookup_table: Option<Pubkey>,
+    /// Compute unit limit set on every transaction (`COMPUTE_UNIT_LIMIT`, default 400000)
+    pub compute_unit_limit: u32,
+    /// Bounds of the priority fee in micro-lamports per compute unit
+    /// (`MIN_PRIORITY_FEE`/`MAX_PRIORITY_FEE`, default 0/1000000)
+    pub min_priority_fee: u64,
+    pub max_priority_fee: u64,
+    /// Percentile of recent prioritization fees to pay (`PRIORITY_FEE_PERCENTILE`, default 75)
+    pub priority_fee_percentile: u8,
+    /// Resubmissions with a fresh blockhash and a higher fee before giving up (`MAX_RETRIES`,
+    /// default 3)
+    pub max_retries: u32,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, RelayerError> {
+        let min_priority_fee = parse_or("MIN_PRIORITY_FEE", 0)?;
+        let max_priority_fee = parse_or("MAX_PRIORITY_FEE", 1_000_000)?;
+        if min_priority_fee > max_priority_fee {
+            return Err(RelayerError::Config(
+                "MIN_PRIORITY_FEE exceeds MAX_PRIORITY_FEE".into(),
+            ));
+        }
+        let priority_fee_percentile = parse_or("PRIORITY_FEE_PERCENTILE", 75)?;
+        if priority_fee_percentile > 100 {
+            return Err(RelayerError::Config(
+                "PRIORITY_FEE_PERCENTILE must be at most 100".into(),
+            ));
+        }
+        let max_batch_size = parse_or("MAX_BATCH_SIZE", 1)?;
+        if max_batch_size == 0 {
+            return Err(RelayerError::Config(
+                "MAX_BATCH_SIZE must be positive".into(),
+            ));
+        }
+
+        let keypair_path = required("RELAYER_KEYPAIR")?;
+        let relayer = read_keypair_file(&keypair_path).map_err(|err| {
+            RelayerError::Config(format!("can't read RELAYER_KEYPAIR {keypair_path}: {err}"))
+        })?;
+
+        Ok(Self {
+            bind_addr: parse_or("BIND_ADDR", SocketAddr::from(([0, 0, 0, 0], 8080)))?,
+            rpc_url: required("RPC_URL")?,
+            relayer,
+            vault_asset_mint: env::var("VAULT_ASSET_MINT")
+                .ok()
+                .map(|value| parse(&value, "VAULT_ASSET_MINT"))
+                .transpose()?,
+            relayer_asset: parse(&required("RELAYER_ASSET")?, "RELAYER_ASSET")?,
+            relayer_fee: parse_or("RELAYER_FEE", 0)?,
+            max_batch_size,
+            batch_interval: Duration::from_millis(parse_or("BATCH_INTERVAL_MS", 2_000)?),
+            lookup_table: env::var("LOOKUP_TABLE")
+                .ok()
+                .map(|value| parse(&value, "LOOKUP_TABLE"))
+                .transpose()?,
+            compute_unit_limit: parse_or("COMPUTE_UNIT_LIMIT", 400_000)?,
+            min_priority_fee,
+            max_priority_fee,
+            priority_fee_percentile,
+            max_retries: parse_or("MAX_RETRIES", 3)?,
+        })
+    }
+}
+
+fn required(name: &str) -> Result<String, RelayerError> {
+    env::var(name).map_err(|_| RelayerError::Config(format!("{name} is not set")))
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, RelayerError> {
+    value
+        .parse()
+        .map_err(|_| RelayerError::Config(format!("invalid {name}: {value}")))
+}
+
+fn parse_or<T: FromStr>(name: &str, default: T) -> Result<T, RelayerError> {
+    match env::var(name) {
+        Ok(value) => parse(&value, name),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,51 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use solana_client::client_error::ClientError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RelayerError {
+    #[error("configuration error: {0}")]
+    Config(String),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("signature deadline expired")]
+    DeadlineExpired,
+    #[error("nonce {got} is not the next expected nonce {expected}")]
+    InvalidNonce { expected: u64, got: u64 },
+    #[error("signed max relayer fee {signed} is below the relayer fee {required}")]
+    RelayerFeeTooLow { signed: u64, required: u64 },
+    #[error("account {0} not found")]
+    AccountNotFound(String),
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("transaction not confirmed after {0} attempts")]
+    NotConfirmed(u32),
+    #[error("transaction failed: {0}")]
+    TransactionFailed(String),
+}
+
+impl From<ClientError> for RelayerError {
+    fn from(err: ClientError) -> Self {
+        RelayerError::Rpc(Box::new(err))
+    }
+}
+
+impl IntoResponse for RelayerError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidRequest(_)
+            | Self::InvalidSignature
+            | Self::DeadlineExpired
+            | Self::InvalidNonce { .. }
+            | Self::RelayerFeeTooLow { .. }
+            | Self::AccountNotFound(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
@@ -0,0 +1,277 @@
+//! Builds the vault's meta-transaction instructions, each preceded by the Ed25519 program
+//! instruction carrying the owner's signature.
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{ed25519_program, system_program, sysvar};
+use tsv_meta_message::MetaAction;
+use tsv_usdc_vault::MetaRedeemRequest;
+
+use crate::request::ValidatedRequest;
+use crate::vault::{self, VaultState};
+
+/// Size of the Ed25519 instruction header (signature count + padding) and its one offsets entry
+const ED25519_HEADER_LEN: u16 = 2 + 14;
+
+/// Offsets of an Ed25519 instruction pointing into its own data
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Optional accounts of one owner, present only if their PDA exists
+#[derive(Clone, Copy, Default)]
+pub struct OwnerOptionals {
+    pub fee_exemption: Option<Pubkey>,
+    pub allowlist_entry: Option<Pubkey>,
+}
+
+/// Relayer accounts shared by every instruction
+pub struct RelayerAccounts {
+    pub relayer: Pubkey,
+    /// USDC account receiving redeem relayer fees
+    pub relayer_asset: Pubkey,
+    /// Registry PDA of the relayer, if registered
+    pub relayer_entry: Option<Pubkey>,
+    pub relayer_fee: u64,
+}
+
+/// Ed25519 program instruction verifying `signature` by `signer` over `message`, in the
+/// single-signature layout the vault accepts: offsets, public key, signature, then message
+pub fn ed25519_verify(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let public_key_offset = ED25519_HEADER_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = vec![1, 0];
+    for value in [
+        signature_offset,
+        CURRENT_INSTRUCTION,
+        public_key_offset,
+        CURRENT_INSTRUCTION,
+        message_offset,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// `meta_deposit` of one request, after its verify instruction
+pub fn meta_deposit(
+    vault: &VaultState,
+    relayer: &RelayerAccounts,
+    validated: &ValidatedRequest,
+    optionals: OwnerOptionals,
+) -> Vec<Instruction> {
+    let request = &validated.request;
+    let MetaAction::Deposit { assets, receiver } = request.action else {
+        unreachable!("meta_deposit built for a redeem request");
+    };
+    let owner = request.owner;
+    let accounts = tsv_usdc_vault::accounts::MetaDeposit {
+        vault: vault.address,
+        owner,
+        owner_asset: vault.asset_account(&owner),
+        receiver,
+        share_mint: vault.share_mint,
+        receiver_shares: vault.share_account(&receiver),
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        dead_shares_account: vault.dead_shares_account,
+        receiver_position: vault.position(&receiver),
+        fee_exemption: optionals.fee_exemption,
+        allowlist_entry: optionals.allowlist_entry,
+        relayer: relayer.relayer,
+        relayer_entry: relayer.relayer_entry,
+        owner_blocklist: vault.blocklist_entry(&owner),
+        relayer_blocklist: vault.blocklist_entry(&relayer.relayer),
+        receiver_blocklist: vault.blocklist_entry(&receiver),
+        user_nonce: vault::user_nonce(&owner),
+        instructions: sysvar::instructions::ID,
+        token_program: vault.token_program,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+        event_authority: vault::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::MetaDeposit {
+        assets,
+        deadline: request.deadline,
+        signature: request.signature,
+    };
+
+    vec![
+        ed25519_verify(&owner, &request.signature, &validated.message),
+        Instruction {
+            program_id: tsv_usdc_vault::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        },
+    ]
+}
+
+/// `meta_redeem` of one request, after its verify instruction. Unlike `batch_meta_redeem`, it
+/// creates the owner's nonce and position accounts if missing
+pub fn meta_redeem(
+    vault: &VaultState,
+    relayer: &RelayerAccounts,
+    validated: &ValidatedRequest,
+    optionals: OwnerOptionals,
+) -> Vec<Instruction> {
+    let request = &validated.request;
+    let MetaAction::Redeem {
+        shares,
+        receiver,
+        max_relayer_fee,
+    } = request.action
+    else {
+        unreachable!("meta_redeem built for a deposit request");
+    };
+    let owner = request.owner;
+    let accounts = tsv_usdc_vault::accounts::MetaRedeem {
+        vault: vault.address,
+        owner,
+        owner_shares: vault.share_account(&owner),
+        receiver_asset: vault.asset_account(&receiver),
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        share_mint: vault.share_mint,
+        owner_position: vault.position(&owner),
+        fee_exemption: optionals.fee_exemption,
+        allowlist_entry: optionals.allowlist_entry,
+        evm_owner: None,
+        relayer: relayer.relayer,
+        relayer_asset: relayer.relayer_asset,
+        relayer_entry: relayer.relayer_entry,
+        owner_blocklist: vault.blocklist_entry(&owner),
+        relayer_blocklist: vault.blocklist_entry(&relayer.relayer),
+        receiver_blocklist: vault.blocklist_entry(&receiver),
+        user_nonce: vault::user_nonce(&owner),
+        instructions: sysvar::instructions::ID,
+        token_program: vault.token_program,
+        system_program: system_program::ID,
+        event_authority: vault::event_authority(),
+        program: tsv_usdc_vault::ID,
+    };
+    let data = tsv_usdc_vault::instruction::MetaRedeem {
+        shares,
+        receiver,
+        deadline: request.deadline,
+        max_relayer_fee,
+        relayer_fee: relayer.relayer_fee,
+        signature: request.signature,
+        adapter_accounts: Vec::new(),
+    };
+
+    vec![
+        ed25519_verify(&owner, &request.signature, &validated.message),
+        Instruction {
+            program_id: tsv_usdc_vault::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        },
+    ]
+}
+
+/// `batch_meta_redeem` of `requests` (all batchable redeems), after one verify instruction per
+/// request in the same order
+pub fn batch_meta_redeem(
+    vault: &VaultState,
+    relayer: &RelayerAccounts,
+    requests: &[(&ValidatedRequest, OwnerOptionals)],
+) -> Vec<Instruction> {
+    let mut ixs = Vec::with_capacity(requests.len() + 1);
+    let mut batch = Vec::with_capacity(requests.len());
+    let mut remaining_accounts =
+        Vec::with_capacity(requests.len() * tsv_usdc_vault::BATCH_ITEM_ACCOUNTS);
+    let program_id = tsv_usdc_vault::ID;
+
+    for (validated, optionals) in requests {
+        let request = &validated.request;
+        let MetaAction::Redeem {
+            shares,
+            receiver,
+            max_relayer_fee,
+        } = request.action
+        else {
+            unreachable!("batch_meta_redeem built for a deposit request");
+        };
+        let owner = request.owner;
+        ixs.push(ed25519_verify(
+            &owner,
+            &request.signature,
+            &validated.message,
+        ));
+        batch.push(MetaRedeemRequest {
+            shares,
+            receiver,
+            deadline: request.deadline,
+            max_relayer_fee,
+            relayer_fee: relayer.relayer_fee,
+            signature: request.signature,
+        });
+        // In `tsv_usdc_vault::BATCH_ITEM_ACCOUNTS` order, the program id standing for `None`
+        remaining_accounts.extend([
+            AccountMeta::new_readonly(owner, false),
+            AccountMeta::new(vault.share_account(&owner), false),
+            AccountMeta::new(vault.asset_account(&receiver), false),
+            AccountMeta::new_readonly(vault.position(&owner), false),
+            AccountMeta::new_readonly(optionals.fee_exemption.unwrap_or(program_id), false),
+            AccountMeta::new_readonly(optionals.allowlist_entry.unwrap_or(program_id), false),
+            AccountMeta::new_readonly(vault.blocklist_entry(&owner), false),
+            AccountMeta::new_readonly(vault.blocklist_entry(&receiver), false),
+            AccountMeta::new(vault::user_nonce(&owner), false),
+        ]);
+    }
+
+    let mut accounts = tsv_usdc_vault::accounts::BatchMetaRedeem {
+        vault: vault.address,
+        asset_vault: vault.asset_vault,
+        asset_mint: vault.asset_mint,
+        share_mint: vault.share_mint,
+        relayer: relayer.relayer,
+        relayer_asset: relayer.relayer_asset,
+        relayer_entry: relayer.relayer_entry,
+        relayer_blocklist: vault.blocklist_entry(&relayer.relayer),
+        instructions: sysvar::instructions::ID,
+        token_program: vault.token_program,
+        event_authority: vault::event_authority(),
+        program: program_id,
+    }
+    .to_account_metas(None);
+    accounts.extend(remaining_accounts);
+    let data = tsv_usdc_vault::instruction::BatchMetaRedeem { requests: batch };
+
+    ixs.push(Instruction {
+        program_id,
+        accounts,
+        data: data.data(),
+    });
+    ixs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ed25519_verify_layout() {
+        let signer = Pubkey::new_unique();
+        let ix = ed25519_verify(&signer, &[9; 64], b"message");
+        assert_eq!(ix.program_id, ed25519_program::ID);
+        assert_eq!(&ix.data[..2], &[1, 0]);
+        // Same slices the vault's parser reads (and the web3.js instruction uses)
+        assert_eq!(&ix.data[16..48], signer.as_ref());
+        assert_eq!(&ix.data[48..112], &[9; 64]);
+        assert_eq!(&ix.data[112..], b"message");
+        assert_eq!(u16::from_le_bytes([ix.data[12], ix.data[13]]), 7);
+    }
+}
@@ -0,0 +1,79 @@
+//! Relayer for Talken Stable Vault meta-transactions: accepts signed meta-redeem/meta-deposit
+//! requests over HTTP, checks them off-chain, and submits them on Solana as the fee payer.
+
+mod api;
+mod batcher;
+mod config;
+mod error;
+mod instructions;
+mod request;
+mod store;
+mod submitter;
+mod validate;
+mod vault;
+
+use std::sync::Arc;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signer;
+use tokio::net::TcpListener;
+use tracing_subscriber::EnvFilter;
+
+use crate::api::AppState;
+use crate::batcher::Batcher;
+use crate::config::Config;
+use crate::error::RelayerError;
+use crate::instructions::RelayerAccounts;
+use crate::store::Store;
+use crate::submitter::Submitter;
+use crate::validate::Validator;
+use crate::vault::VaultState;
+
+#[tokio::main]
+async fn main() -> Result<(), RelayerError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let rpc = Arc::new(RpcClient::new_with_commitment(
+        config.rpc_url.clone(),
+        CommitmentConfig::confirmed(),
+    ));
+    let vault = Arc::new(VaultState::fetch(&rpc, config.vault_asset_mint).await?);
+    let relayer = Arc::new(config.relayer.insecure_clone());
+
+    // Registration is checked once: restarting picks up a newly registered relayer
+    let relayer_entry = vault.relayer_entry(&relayer.pubkey());
+    let registered = vault::exist(&rpc, &[relayer_entry]).await?[0];
+    tracing::info!(relayer = %relayer.pubkey(), registered, vault = %vault.address, "starting");
+
+    let store = Arc::new(Store::default());
+    let batcher = Batcher {
+        rpc: rpc.clone(),
+        vault: vault.clone(),
+        store: store.clone(),
+        submitter: Submitter::new(rpc.clone(), relayer.clone(), &config).await?,
+        relayer: RelayerAccounts {
+            relayer: relayer.pubkey(),
+            relayer_asset: config.relayer_asset,
+            relayer_entry: registered.then_some(relayer_entry),
+            relayer_fee: config.relayer_fee,
+        },
+        max_batch_size: config.max_batch_size,
+    };
+    tokio::spawn(batcher.run(config.batch_interval));
+
+    let state = AppState {
+        store,
+        validator: Arc::new(Validator::new(rpc, vault, config.relayer_fee)),
+    };
+    let listener = TcpListener::bind(config.bind_addr)
+        .await
+        .map_err(|err| RelayerError::Config(format!("can't bind {}: {err}", config.bind_addr)))?;
+    tracing::info!(addr = %config.bind_addr, "listening");
+    axum::serve(listener, api::router(state))
+        .await
+        .map_err(|err| RelayerError::Config(format!("server error: {err}")))
+}
@@ -0,0 +1,105 @@
+//! Signed meta-transaction requests: JSON payloads accepted by the API and their validated form.
+
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tsv_meta_message::MetaAction;
+
+use crate::error::RelayerError;
+
+/// `POST /v1/meta-redeem` body: the fields of `MetaAction::Redeem` signed by `owner`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaRedeemPayload {
+    pub owner: String,
+    pub shares: u64,
+    pub receiver: String,
+    pub max_relayer_fee: u64,
+    pub nonce: u64,
+    pub deadline: i64,
+    /// Base58 ed25519 signature over the canonical message
+    pub signature: String,
+}
+
+/// `POST /v1/meta-deposit` body: the fields of `MetaAction::Deposit` signed by `owner`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaDepositPayload {
+    pub owner: String,
+    pub assets: u64,
+    pub receiver: String,
+    pub nonce: u64,
+    pub deadline: i64,
+    /// Base58 ed25519 signature over the canonical message
+    pub signature: String,
+}
+
+/// Request decoded from a payload, before its signature and nonce are checked
+#[derive(Clone, Debug)]
+pub struct SignedRequest {
+    pub owner: Pubkey,
+    pub nonce: u64,
+    pub deadline: i64,
+    pub action: MetaAction,
+    pub signature: [u8; 64],
+}
+
+impl TryFrom<MetaRedeemPayload> for SignedRequest {
+    type Error = RelayerError;
+
+    fn try_from(payload: MetaRedeemPayload) -> Result<Self, RelayerError> {
+        Ok(Self {
+            owner: parse_pubkey(&payload.owner, "owner")?,
+            nonce: payload.nonce,
+            deadline: payload.deadline,
+            action: MetaAction::Redeem {
+                shares: payload.shares,
+                receiver: parse_pubkey(&payload.receiver, "receiver")?,
+                max_relayer_fee: payload.max_relayer_fee,
+            },
+            signature: parse_signature(&payload.signature)?,
+        })
+    }
+}
+
+impl TryFrom<MetaDepositPayload> for SignedRequest {
+    type Error = RelayerError;
+
+    fn try_from(payload: MetaDepositPayload) -> Result<Self, RelayerError> {
+        Ok(Self {
+            owner: parse_pubkey(&payload.owner, "owner")?,
+            nonce: payload.nonce,
+            deadline: payload.deadline,
+            action: MetaAction::Deposit {
+                assets: payload.assets,
+                receiver: parse_pubkey(&payload.receiver, "receiver")?,
+            },
+            signature: parse_signature(&payload.signature)?,
+        })
+    }
+}
+
+/// A `SignedRequest` whose signature and nonce were checked, as queued for submission
+#[derive(Clone, Debug)]
+pub struct ValidatedRequest {
+    pub request: SignedRequest,
+    /// Exact bytes the owner signed, verified by the Ed25519 instruction
+    pub message: Vec<u8>,
+    /// The owner's `UserNonce` and `UserPosition` exist, as `batch_meta_redeem` requires
+    pub batchable: bool,
+}
+
+fn parse_pubkey(value: &str, field: &str) -> Result<Pubkey, RelayerError> {
+    value
+        .parse()
+        .map_err(|_| RelayerError::InvalidRequest(format!("invalid {field}: {value}")))
+}
+
+fn parse_signature(value: &str) -> Result<[u8; 64], RelayerError> {
+    let signature: Signature = value
+        .parse()
+        .map_err(|_| RelayerError::InvalidRequest(format!("invalid signature: {value}")))?;
+    let mut bytes = [0; 64];
+    bytes.copy_from_slice(signature.as_ref());
+    Ok(bytes)
+}
@@ -0,0 +1,82 @@
+//! In-memory request queue and status tracking.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::{Mutex, RwLock};
+use uuid::Uuid;
+
+use crate::request::ValidatedRequest;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum RequestStatus {
+    Queued,
+    /// Sent in `signature`; `attempt` counts resubmissions after an expired blockhash
+    Submitted {
+        signature: String,
+        attempt: u32,
+    },
+    Confirmed {
+        signature: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+pub struct QueuedRequest {
+    pub id: Uuid,
+    pub validated: ValidatedRequest,
+}
+
+#[derive(Default)]
+pub struct Store {
+    statuses: RwLock<HashMap<Uuid, RequestStatus>>,
+    queue: Mutex<VecDeque<QueuedRequest>>,
+    /// Requests per owner accepted but not yet settled, so new ones must use later nonces.
+    /// Held across validation so two requests can't claim the same nonce
+    pub pending: Mutex<HashMap<Pubkey, u64>>,
+}
+
+impl Store {
+    /// Queue a validated request; the caller holds `pending` and has counted it there
+    pub async fn enqueue(&self, validated: ValidatedRequest) -> Uuid {
+        let id = Uuid::new_v4();
+        self.statuses
+            .write()
+            .await
+            .insert(id, RequestStatus::Queued);
+        self.queue
+            .lock()
+            .await
+            .push_back(QueuedRequest { id, validated });
+        id
+    }
+
+    /// Take every queued request, in arrival order
+    pub async fn drain(&self) -> Vec<QueuedRequest> {
+        self.queue.lock().await.drain(..).collect()
+    }
+
+    pub async fn status(&self, id: &Uuid) -> Option<RequestStatus> {
+        self.statuses.read().await.get(id).cloned()
+    }
+
+    pub async fn set_status(&self, id: Uuid, status: RequestStatus) {
+        self.statuses.write().await.insert(id, status);
+    }
+
+    /// Record the final status of a request and release its owner's pending nonce
+    pub async fn settle(&self, id: Uuid, owner: &Pubkey, status: RequestStatus) {
+        self.set_status(id, status).await;
+        let mut pending = self.pending.lock().await;
+        if let Some(count) = pending.get_mut(owner) {
+            *count -= 1;
+            if *count == 0 {
+                pending.remove(owner);
+            }
+        }
+    }
+}
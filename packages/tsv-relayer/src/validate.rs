@@ -0,0 +1,140 @@
+//! Off-chain checks run before a request is queued, so invalid or stale signatures are rejected
+//! at the API instead of costing the relayer a failed transaction.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tsv_meta_message::{MetaAction, MetaMessage};
+
+use crate::error::RelayerError;
+use crate::request::{SignedRequest, ValidatedRequest};
+use crate::vault::{self, VaultState};
+
+pub struct Validator {
+    rpc: Arc<RpcClient>,
+    vault: Arc<VaultState>,
+    relayer_fee: u64,
+}
+
+impl Validator {
+    pub fn new(rpc: Arc<RpcClient>, vault: Arc<VaultState>, relayer_fee: u64) -> Self {
+        Self {
+            rpc,
+            vault,
+            relayer_fee,
+        }
+    }
+
+    /// Check `request` against the vault's domain and the owner's on-chain nonce, where
+    /// `pending` requests of the owner are already queued ahead of it
+    pub async fn validate(
+        &self,
+        request: SignedRequest,
+        pending: u64,
+    ) -> Result<ValidatedRequest, RelayerError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        if request.deadline < now {
+            return Err(RelayerError::DeadlineExpired);
+        }
+        if let MetaAction::Redeem {
+            max_relayer_fee, ..
+        } = request.action
+        {
+            if max_relayer_fee < self.relayer_fee {
+                return Err(RelayerError::RelayerFeeTooLow {
+                    signed: max_relayer_fee,
+                    required: self.relayer_fee,
+                });
+            }
+        }
+
+        let message = MetaMessage {
+            domain: self.vault.domain.clone(),
+            owner: request.owner,
+            nonce: request.nonce,
+            deadline: request.deadline,
+            action: request.action.clone(),
+        }
+        .signing_bytes();
+        verify_signature(&request.owner, &message, &request.signature)?;
+
+        let onchain_nonce = vault::fetch_nonce(&self.rpc, &request.owner).await?;
+        check_nonce(onchain_nonce.unwrap_or(0), pending, request.nonce)?;
+
+        // Token accounts the instruction needs but won't create
+        let token_account = match request.action {
+            MetaAction::Redeem { receiver, .. } => self.vault.asset_account(&receiver),
+            MetaAction::Deposit { .. } => self.vault.asset_account(&request.owner),
+        };
+        let exist = vault::exist(
+            &self.rpc,
+            &[token_account, self.vault.position(&request.owner)],
+        )
+        .await?;
+        if !exist[0] {
+            return Err(RelayerError::AccountNotFound(token_account.to_string()));
+        }
+
+        Ok(ValidatedRequest {
+            batchable: onchain_nonce.is_some() && exist[1],
+            request,
+            message,
+        })
+    }
+}
+
+fn verify_signature(
+    owner: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<(), RelayerError> {
+    if Signature::from(*signature).verify(owner.as_ref(), message) {
+        Ok(())
+    } else {
+        Err(RelayerError::InvalidSignature)
+    }
+}
+
+/// Requests of one owner must use consecutive nonces, starting at the on-chain one
+fn check_nonce(onchain: u64, pending: u64, got: u64) -> Result<(), RelayerError> {
+    let expected = onchain + pending;
+    if got == expected {
+        Ok(())
+    } else {
+        Err(RelayerError::InvalidNonce { expected, got })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    #[test]
+    fn verifies_owner_signature() {
+        let owner = Keypair::new();
+        let mut signature = [0; 64];
+        signature.copy_from_slice(owner.sign_message(b"message").as_ref());
+        assert!(verify_signature(&owner.pubkey(), b"message", &signature).is_ok());
+        assert!(verify_signature(&owner.pubkey(), b"other", &signature).is_err());
+        assert!(verify_signature(&Pubkey::new_unique(), b"message", &signature).is_err());
+    }
+
+    #[test]
+    fn expects_consecutive_nonces() {
+        assert!(check_nonce(4, 0, 4).is_ok());
+        assert!(check_nonce(4, 2, 6).is_ok());
+        assert!(matches!(
+            check_nonce(4, 1, 4),
+            Err(RelayerError::InvalidNonce {
+                expected: 5,
+                got: 4
+            })
+        ));
+    }
+}
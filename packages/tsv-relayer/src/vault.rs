@@ -0,0 +1,114 @@
+//! Vault accounts the relayer reads and the PDAs it derives, mirroring the program's seeds.
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tsv_meta_message::Domain;
+use tsv_usdc_vault::{UserNonce, Vault};
+
+use crate::error::RelayerError;
+
+/// Static vault accounts, read once at startup
+pub struct VaultState {
+    pub address: Pubkey,
+    pub asset_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub asset_vault: Pubkey,
+    pub dead_shares_account: Pubkey,
+    /// SPL Token or Token-2022, whichever owns the vault's mints
+    pub token_program: Pubkey,
+    /// Domain every meta-transaction message of this vault is bound to
+    pub domain: Domain,
+}
+
+impl VaultState {
+    /// Read the vault of `asset_mint`, or the legacy `[b"vault"]` one without it
+    pub async fn fetch(rpc: &RpcClient, asset_mint: Option<Pubkey>) -> Result<Self, RelayerError> {
+        let address = match asset_mint {
+            Some(asset_mint) => pda(&[b"vault", asset_mint.as_ref()]),
+            None => pda(&[b"vault"]),
+        };
+        let data = rpc.get_account_data(&address).await?;
+        let vault = Vault::try_deserialize(&mut data.as_slice())
+            .map_err(|err| RelayerError::Config(format!("can't decode vault {address}: {err}")))?;
+        let token_program = rpc.get_account(&vault.asset_mint).await?.owner;
+        Ok(Self {
+            address,
+            asset_mint: vault.asset_mint,
+            share_mint: vault.share_mint,
+            asset_vault: vault.asset_vault,
+            dead_shares_account: vault.dead_shares_account,
+            token_program,
+            domain: vault.domain(address),
+        })
+    }
+
+    /// USDC associated token account of `wallet`
+    pub fn asset_account(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.asset_mint, &self.token_program)
+    }
+
+    /// Share associated token account of `wallet`
+    pub fn share_account(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.share_mint, &self.token_program)
+    }
+
+    pub fn position(&self, owner: &Pubkey) -> Pubkey {
+        pda(&[b"position", self.address.as_ref(), owner.as_ref()])
+    }
+
+    pub fn fee_exemption(&self, wallet: &Pubkey) -> Pubkey {
+        pda(&[b"fee_exempt", self.address.as_ref(), wallet.as_ref()])
+    }
+
+    pub fn allowlist_entry(&self, wallet: &Pubkey) -> Pubkey {
+        pda(&[b"allowlist", self.address.as_ref(), wallet.as_ref()])
+    }
+
+    pub fn blocklist_entry(&self, wallet: &Pubkey) -> Pubkey {
+        pda(&[b"blocklist", self.address.as_ref(), wallet.as_ref()])
+    }
+
+    pub fn relayer_entry(&self, relayer: &Pubkey) -> Pubkey {
+        pda(&[b"relayer", self.address.as_ref(), relayer.as_ref()])
+    }
+}
+
+pub fn user_nonce(owner: &Pubkey) -> Pubkey {
+    pda(&[b"nonce", owner.as_ref()])
+}
+
+/// Current nonce of `owner`, or `None` while its `UserNonce` account doesn't exist (nonce 0)
+pub async fn fetch_nonce(rpc: &RpcClient, owner: &Pubkey) -> Result<Option<u64>, RelayerError> {
+    let account = rpc
+        .get_account_with_commitment(&user_nonce(owner), rpc.commitment())
+        .await?
+        .value;
+    account
+        .map(|account| {
+            UserNonce::try_deserialize(&mut account.data.as_slice())
+                .map(|user_nonce| user_nonce.nonce)
+                .map_err(|err| RelayerError::InvalidRequest(format!("bad nonce account: {err}")))
+        })
+        .transpose()
+}
+
+/// Whether each of `addresses` currently exists
+pub async fn exist(rpc: &RpcClient, addresses: &[Pubkey]) -> Result<Vec<bool>, RelayerError> {
+    Ok(rpc
+        .get_multiple_accounts(addresses)
+        .await?
+        .iter()
+        .map(Option::is_some)
+        .collect())
+}
+
+/// PDA signing the program's self-CPI events, an account of every instruction
+pub fn event_authority() -> Pubkey {
+    pda(&[b"__event_authority"])
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &tsv_usdc_vault::ID).0
+}
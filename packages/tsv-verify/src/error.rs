@@ -0,0 +1,22 @@
+use solana_client::client_error::ClientError;
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+use tsv_client::VaultClientError;
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    #[error(transparent)]
+    Client(#[from] VaultClientError),
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("can't decode account {address}: {reason}")]
+    Decode { address: Pubkey, reason: String },
+}
+
+impl From<ClientError> for VerifyError {
+    fn from(err: ClientError) -> Self {
+        VerifyError::Rpc(Box::new(err))
+    }
+}
@@ -0,0 +1,224 @@
+//! Invariants of a vault snapshot, as the program's instructions maintain them.
+
+use serde::Serialize;
+use tsv_usdc_vault::{
+    Vault, MAX_INSTANT_REDEEM_PREMIUM_BPS, MAX_MANAGEMENT_FEE_BPS, MAX_PENALTY_BPS,
+    MAX_PERFORMANCE_FEE_BPS,
+};
+
+use crate::snapshot::Snapshot;
+
+/// Cap on the deposit and redeem fees, as `initialize` and `queue_fee_update` require
+const MAX_FEE_BPS: u16 = 100;
+
+/// Outcome of one invariant
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    /// The figures compared
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, ok: bool, detail: String) -> Self {
+        Self { name, ok, detail }
+    }
+}
+
+/// Every invariant of `snapshot`, passing or not
+pub fn check(snapshot: &Snapshot) -> Vec<Check> {
+    let mut checks = vec![share_supply(snapshot), asset_backing(snapshot)];
+    checks.extend(junior_share_supply(snapshot));
+    checks.extend(fee_bounds(&snapshot.vault));
+    checks
+}
+
+/// The share mint's supply is `total_shares`, less the fee shares not minted yet and the
+/// shares burned by withdrawal requests that `process_epoch` hasn't filled
+fn share_supply(snapshot: &Snapshot) -> Check {
+    let vault = &snapshot.vault;
+    let expected = u128::from(vault.total_shares)
+        .checked_sub(u128::from(vault.unminted_fee_shares))
+        .and_then(|shares| shares.checked_sub(u128::from(vault.queued_withdraw_shares)));
+    Check::new(
+        "share_supply",
+        expected == Some(u128::from(snapshot.share_supply)),
+        format!(
+            "supply {} vs total_shares {} - unminted fee shares {} - queued withdraw shares {}",
+            snapshot.share_supply,
+            vault.total_shares,
+            vault.unminted_fee_shares,
+            vault.queued_withdraw_shares
+        ),
+    )
+}
+
+/// `asset_vault`, with what strategies and basket assets hold, covers `total_assets` and the
+/// USDC set aside outside it, the way `sync` reconciles them
+fn asset_backing(snapshot: &Snapshot) -> Check {
+    let vault = &snapshot.vault;
+    let held = u128::from(snapshot.asset_vault_balance)
+        + u128::from(vault.allocated_assets)
+        + u128::from(vault.basket_value);
+    let owed = u128::from(vault.total_assets)
+        + u128::from(vault.accrued_fees)
+        + u128::from(vault.insurance_assets)
+        + u128::from(vault.reserved_withdraw_assets);
+    Check::new(
+        "asset_backing",
+        held >= owed,
+        format!(
+            "balance {} + allocated {} + basket {} vs total_assets {} + fees {} + insurance {} \
+             + reserved withdrawals {}",
+            snapshot.asset_vault_balance,
+            vault.allocated_assets,
+            vault.basket_value,
+            vault.total_assets,
+            vault.accrued_fees,
+            vault.insurance_assets,
+            vault.reserved_withdraw_assets
+        ),
+    )
+}
+
+/// The junior share mint's supply is `junior_shares`, with a junior tranche
+fn junior_share_supply(snapshot: &Snapshot) -> Option<Check> {
+    let supply = snapshot.junior_share_supply?;
+    let junior_shares = snapshot.vault.junior_shares;
+    Some(Check::new(
+        "junior_share_supply",
+        supply == junior_shares,
+        format!("supply {supply} vs junior_shares {junior_shares}"),
+    ))
+}
+
+/// Every fee and penalty within the cap its setter enforces
+fn fee_bounds(vault: &Vault) -> Vec<Check> {
+    let mut bounds = vec![
+        ("deposit_fee", vault.deposit_fee_bps, MAX_FEE_BPS),
+        ("redeem_fee", vault.redeem_fee_bps, MAX_FEE_BPS),
+        ("max_redeem_fee", vault.max_redeem_fee_bps, MAX_FEE_BPS),
+        (
+            "management_fee",
+            vault.management_fee_bps,
+            MAX_MANAGEMENT_FEE_BPS,
+        ),
+        (
+            "performance_fee",
+            vault.performance_fee_bps,
+            MAX_PERFORMANCE_FEE_BPS,
+        ),
+        (
+            "instant_redeem_premium",
+            vault.instant_redeem_premium_bps,
+            MAX_INSTANT_REDEEM_PREMIUM_BPS,
+        ),
+        (
+            "cooldown_penalty",
+            vault.cooldown_penalty_bps,
+            MAX_PENALTY_BPS,
+        ),
+        (
+            "early_exit_penalty",
+            vault.early_exit_penalty_bps,
+            MAX_PENALTY_BPS,
+        ),
+    ];
+    if vault.fee_update_eta != 0 {
+        bounds.push((
+            "pending_deposit_fee",
+            vault.pending_deposit_fee_bps,
+            MAX_FEE_BPS,
+        ));
+        bounds.push((
+            "pending_redeem_fee",
+            vault.pending_redeem_fee_bps,
+            MAX_FEE_BPS,
+        ));
+    }
+
+    bounds
+        .into_iter()
+        .map(|(name, bps, max)| Check::new(name, bps <= max, format!("{bps} bps, at most {max}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AccountDeserialize, Space};
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    /// 1,000 USDC and 1,000,000 shares, all minted and held in `asset_vault`
+    fn snapshot() -> Snapshot {
+        let data = vec![0; 8 + Vault::INIT_SPACE];
+        let mut vault = Vault::try_deserialize_unchecked(&mut data.as_slice()).unwrap();
+        vault.total_assets = 1_000_000_000;
+        vault.total_shares = 1_000_000_000_000;
+        vault.deposit_fee_bps = 10;
+        vault.redeem_fee_bps = 10;
+        Snapshot {
+            address: Pubkey::new_unique(),
+            slot: 1,
+            vault,
+            share_supply: 1_000_000_000_000,
+            junior_share_supply: None,
+            asset_vault_balance: 1_000_000_000,
+        }
+    }
+
+    fn failed(snapshot: &Snapshot) -> Vec<&'static str> {
+        check(snapshot)
+            .into_iter()
+            .filter(|check| !check.ok)
+            .map(|check| check.name)
+            .collect()
+    }
+
+    #[test]
+    fn consistent_vault_passes() {
+        let mut snapshot = snapshot();
+        assert!(failed(&snapshot).is_empty());
+
+        // Shares owed but not minted, and USDC outside `asset_vault` or set aside
+        snapshot.vault.total_shares += 3_000;
+        snapshot.vault.unminted_fee_shares = 1_000;
+        snapshot.vault.queued_withdraw_shares = 2_000;
+        snapshot.vault.allocated_assets = 400_000_000;
+        snapshot.vault.total_assets += 400_000_000;
+        snapshot.vault.accrued_fees = 5_000;
+        snapshot.asset_vault_balance += 5_000;
+        assert!(failed(&snapshot).is_empty());
+    }
+
+    #[test]
+    fn broken_invariants_fail() {
+        let mut snapshot = snapshot();
+        snapshot.share_supply += 1;
+        snapshot.asset_vault_balance -= 1;
+        snapshot.vault.redeem_fee_bps = MAX_FEE_BPS + 1;
+        snapshot.vault.junior_shares = 5;
+        snapshot.junior_share_supply = Some(4);
+        assert_eq!(
+            failed(&snapshot),
+            [
+                "share_supply",
+                "asset_backing",
+                "junior_share_supply",
+                "redeem_fee"
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_fees_are_checked_while_queued() {
+        let mut snapshot = snapshot();
+        snapshot.vault.pending_deposit_fee_bps = MAX_FEE_BPS + 1;
+        assert!(failed(&snapshot).is_empty());
+
+        snapshot.vault.fee_update_eta = 1;
+        assert_eq!(failed(&snapshot), ["pending_deposit_fee"]);
+    }
+}
@@ -0,0 +1,110 @@
+//! `tsv-verify`: reads a live vault, its share mints and its asset vault at one slot and checks
+//! the accounting invariants between them, for cron jobs and CI. Exits 0 when every invariant
+//! holds, 1 when one is broken and 2 when the accounts can't be read.
+
+mod error;
+mod invariants;
+mod snapshot;
+
+use std::process::ExitCode;
+
+use clap::Parser;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tsv_client::pda;
+
+use crate::error::VerifyError;
+use crate::invariants::Check;
+use crate::snapshot::Snapshot;
+
+/// Exit code of a run that couldn't read the vault, told apart from a broken invariant
+const EXIT_UNREADABLE: u8 = 2;
+
+#[derive(Parser)]
+#[command(
+    name = "tsv-verify",
+    version,
+    about = "Check the invariants of a live Talken Stable Vault"
+)]
+struct Cli {
+    /// Solana JSON-RPC endpoint
+    #[arg(
+        long,
+        short = 'u',
+        env = "RPC_URL",
+        default_value = "https://api.mainnet-beta.solana.com"
+    )]
+    url: String,
+    /// Asset mint of the vault [default: the legacy single vault]
+    #[arg(long, env = "VAULT_ASSET_MINT")]
+    asset_mint: Option<Pubkey>,
+    /// Print one JSON object, for scripts
+    #[arg(long)]
+    json: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new_with_commitment(cli.url, CommitmentConfig::finalized());
+    let address = cli
+        .asset_mint
+        .as_ref()
+        .map_or_else(pda::legacy_vault, pda::vault);
+
+    match Snapshot::fetch(&rpc, address).await {
+        Ok(snapshot) => {
+            let checks = invariants::check(&snapshot);
+            print(&snapshot, &checks, cli.json);
+            if checks.iter().all(|check| check.ok) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(err) => {
+            print_error(&address, &err, cli.json);
+            ExitCode::from(EXIT_UNREADABLE)
+        }
+    }
+}
+
+fn print(snapshot: &Snapshot, checks: &[Check], json: bool) {
+    let ok = checks.iter().all(|check| check.ok);
+    if json {
+        let report = serde_json::json!({
+            "vault": snapshot.address.to_string(),
+            "slot": snapshot.slot,
+            "ok": ok,
+            "checks": checks,
+        });
+        println!("{report}");
+        return;
+    }
+    println!("vault: {}", snapshot.address);
+    println!("slot: {}", snapshot.slot);
+    for check in checks {
+        let status = if check.ok { "ok" } else { "FAILED" };
+        println!("{status} {}: {}", check.name, check.detail);
+    }
+    let failed = checks.iter().filter(|check| !check.ok).count();
+    if ok {
+        println!("all {} invariants hold", checks.len());
+    } else {
+        println!("{failed} of {} invariants broken", checks.len());
+    }
+}
+
+/// Print `err`, as `{"error": ...}` with `--json` so scripts always get JSON
+fn print_error(address: &Pubkey, err: &VerifyError, json: bool) {
+    if json {
+        let report = serde_json::json!({
+            "vault": address.to_string(),
+            "error": err.to_string(),
+        });
+        println!("{report}");
+    } else {
+        eprintln!("can't read vault {address}: {err}");
+    }
+}
@@ -0,0 +1,70 @@
+//! The vault account with its share mints and asset vault, read at one slot.
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use tsv_usdc_vault::Vault;
+
+use crate::error::VerifyError;
+
+pub struct Snapshot {
+    pub address: Pubkey,
+    /// Slot all the accounts were read at
+    pub slot: u64,
+    pub vault: Vault,
+    pub share_supply: u64,
+    /// Supply of the junior tranche's share mint, `None` without a junior tranche
+    pub junior_share_supply: Option<u64>,
+    pub asset_vault_balance: u64,
+}
+
+impl Snapshot {
+    /// Read the vault at `address`, then it again with its mints and asset vault in one
+    /// request, so the figures compared all come from the same slot
+    pub async fn fetch(rpc: &RpcClient, address: Pubkey) -> Result<Self, VerifyError> {
+        let vault: Vault = tsv_client::fetch(rpc, &address).await?;
+        let junior = vault.junior_share_mint != Pubkey::default();
+        let mut addresses = vec![address, vault.share_mint, vault.asset_vault];
+        if junior {
+            addresses.push(vault.junior_share_mint);
+        }
+
+        let response = rpc
+            .get_multiple_accounts_with_commitment(&addresses, rpc.commitment())
+            .await?;
+        let mut accounts = addresses.iter().zip(response.value);
+        let mut next = || {
+            let (address, account) = accounts.next().expect("one account per address");
+            account
+                .map(|account| (*address, account))
+                .ok_or(VerifyError::AccountNotFound(*address))
+        };
+
+        let vault: Vault = decode(next()?)?;
+        let share_supply = decode::<Mint>(next()?)?.supply;
+        let asset_vault_balance = decode::<TokenAccount>(next()?)?.amount;
+        let junior_share_supply = if junior {
+            Some(decode::<Mint>(next()?)?.supply)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            address,
+            slot: response.context.slot,
+            vault,
+            share_supply,
+            junior_share_supply,
+            asset_vault_balance,
+        })
+    }
+}
+
+fn decode<T: AccountDeserialize>((address, account): (Pubkey, Account)) -> Result<T, VerifyError> {
+    T::try_deserialize(&mut account.data.as_slice()).map_err(|err| VerifyError::Decode {
+        address,
+        reason: err.to_string(),
+    })
+}
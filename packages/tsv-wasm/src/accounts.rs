@@ -0,0 +1,119 @@
+//! A vault's fixed accounts and a wallet's optional ones, as the wallet read them with its own
+//! RPC connection.
+
+use solana_program::pubkey::Pubkey;
+use tsv_meta_message::{Domain, SCHEMA_VERSION};
+use wasm_bindgen::prelude::*;
+
+use crate::error::WasmError;
+use crate::{parse_address, pda, PROGRAM_ID};
+
+/// Accounts of a vault that don't change, used to build its instructions and messages
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct VaultAccounts {
+    pub(crate) address: Pubkey,
+    pub(crate) asset_mint: Pubkey,
+    pub(crate) share_mint: Pubkey,
+    pub(crate) asset_vault: Pubkey,
+    pub(crate) dead_shares_account: Pubkey,
+    pub(crate) token_program: Pubkey,
+    pub(crate) domain: Domain,
+}
+
+#[wasm_bindgen]
+impl VaultAccounts {
+    /// The vault of `asset_mint` (the legacy vault without it), with the `share_mint`,
+    /// `asset_vault` and `token_program` recorded in its `Vault` account, on the cluster of
+    /// `genesis_hash` (`connection.getGenesisHash()`, decoded from base58)
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        asset_mint: &str,
+        legacy: bool,
+        share_mint: &str,
+        asset_vault: &str,
+        token_program: &str,
+        genesis_hash: &[u8],
+    ) -> Result<VaultAccounts, WasmError> {
+        let asset_mint = parse_address("asset mint", asset_mint)?;
+        let address = if legacy {
+            pda::legacy_vault()
+        } else {
+            pda::vault(&asset_mint)
+        };
+        let genesis_hash = genesis_hash
+            .try_into()
+            .map_err(|_| WasmError::GenesisHash(genesis_hash.len()))?;
+        Ok(Self {
+            address,
+            asset_mint,
+            share_mint: parse_address("share mint", share_mint)?,
+            asset_vault: parse_address("asset vault", asset_vault)?,
+            dead_shares_account: pda::dead_shares(&address),
+            token_program: parse_address("token program", token_program)?,
+            domain: Domain {
+                schema_version: SCHEMA_VERSION,
+                genesis_hash,
+                program_id: PROGRAM_ID,
+                vault: address,
+            },
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    /// Asset associated token account of `wallet`
+    #[wasm_bindgen(js_name = assetAccount)]
+    pub fn asset_account_address(&self, wallet: &str) -> Result<String, WasmError> {
+        Ok(self
+            .asset_account(&parse_address("wallet", wallet)?)
+            .to_string())
+    }
+
+    /// Share associated token account of `wallet`
+    #[wasm_bindgen(js_name = shareAccount)]
+    pub fn share_account_address(&self, wallet: &str) -> Result<String, WasmError> {
+        Ok(self
+            .share_account(&parse_address("wallet", wallet)?)
+            .to_string())
+    }
+}
+
+impl VaultAccounts {
+    pub(crate) fn asset_account(&self, wallet: &Pubkey) -> Pubkey {
+        pda::associated_token(wallet, &self.asset_mint, &self.token_program)
+    }
+
+    pub(crate) fn share_account(&self, wallet: &Pubkey) -> Pubkey {
+        pda::associated_token(wallet, &self.share_mint, &self.token_program)
+    }
+}
+
+/// Optional accounts of a deposit or redeem, each passed only if its PDA exists. The wallet
+/// checks them with `getMultipleAccountsInfo` on the addresses from `feeExemptionAddress`,
+/// `allowlistEntryAddress` and `costBasisAddress`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Optionals {
+    /// Fee exemption of the wallet paying
+    #[wasm_bindgen(js_name = feeExemption)]
+    pub fee_exemption: bool,
+    /// Allowlist entry of the wallet holding the shares
+    #[wasm_bindgen(js_name = allowlistEntry)]
+    pub allowlist_entry: bool,
+    /// Cost basis of the wallet holding the shares, to keep it up to date
+    #[wasm_bindgen(js_name = costBasis)]
+    pub cost_basis: bool,
+}
+
+#[wasm_bindgen]
+impl Optionals {
+    /// None of the optional accounts
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Optionals {
+        Self::default()
+    }
+}
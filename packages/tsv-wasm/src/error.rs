@@ -0,0 +1,28 @@
+use thiserror::Error;
+use tsv_math::MathError;
+use wasm_bindgen::{JsError, JsValue};
+
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("invalid {name} address: {value}")]
+    Address { name: &'static str, value: String },
+    #[error("genesis hash must be 32 bytes, got {0}")]
+    GenesisHash(usize),
+    #[error("math error: {0}")]
+    Math(MathError),
+    #[error("redemption within the deposit cooldown window")]
+    CooldownActive,
+}
+
+impl From<MathError> for WasmError {
+    fn from(err: MathError) -> Self {
+        Self::Math(err)
+    }
+}
+
+/// Thrown to JavaScript as an `Error` with the message above
+impl From<WasmError> for JsValue {
+    fn from(err: WasmError) -> Self {
+        JsError::new(&err.to_string()).into()
+    }
+}
@@ -0,0 +1,420 @@
+//! `deposit` and `redeem` instructions, accounts in the program's order and arguments
+//! Anchor-encoded, and the meta-transaction messages owners sign for a relayer.
+
+use borsh::BorshSerialize;
+use solana_program::hash::hash;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+use tsv_meta_message::{MetaAction, MetaMessage};
+use wasm_bindgen::prelude::*;
+
+use crate::accounts::{Optionals, VaultAccounts};
+use crate::error::WasmError;
+use crate::{parse_address, pda, ASSOCIATED_TOKEN_PROGRAM_ID, PROGRAM_ID};
+
+/// An instruction in `@solana/web3.js`'s shape, addresses as base58 strings:
+/// `new TransactionInstruction({ programId: new PublicKey(ix.programId), keys: ix.keys.map(...),
+/// data: Buffer.from(ix.data) })`
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct TxInstruction(pub(crate) Instruction);
+
+#[wasm_bindgen]
+impl TxInstruction {
+    #[wasm_bindgen(getter, js_name = programId)]
+    pub fn program_id(&self) -> String {
+        self.0.program_id.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn keys(&self) -> Vec<AccountKey> {
+        self.0
+            .accounts
+            .iter()
+            .map(|meta| AccountKey {
+                pubkey: meta.pubkey.to_string(),
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.0.data.clone()
+    }
+}
+
+/// One account of a `TxInstruction`
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct AccountKey {
+    pub pubkey: String,
+    #[wasm_bindgen(js_name = isSigner)]
+    pub is_signer: bool,
+    #[wasm_bindgen(js_name = isWritable)]
+    pub is_writable: bool,
+}
+
+#[derive(BorshSerialize)]
+struct DepositArgs {
+    assets: u64,
+    min_shares_out: Option<u64>,
+}
+
+#[derive(BorshSerialize)]
+struct RedeemArgs {
+    shares: u64,
+    min_assets_out: Option<u64>,
+    adapter_accounts: Vec<u8>,
+}
+
+/// `deposit` of `assets` from `user`, minting shares to `receiver`. `optionals` are `user`'s
+/// fee exemption and `receiver`'s allowlist entry and cost basis
+#[wasm_bindgen]
+pub fn deposit(
+    vault: &VaultAccounts,
+    user: &str,
+    receiver: &str,
+    assets: u64,
+    min_shares_out: Option<u64>,
+    optionals: &Optionals,
+) -> Result<TxInstruction, WasmError> {
+    let user = parse_address("user", user)?;
+    let receiver = parse_address("receiver", receiver)?;
+    let address = vault.address;
+    let accounts = vec![
+        AccountMeta::new(address, false),
+        AccountMeta::new(user, true),
+        AccountMeta::new(vault.asset_account(&user), false),
+        AccountMeta::new_readonly(receiver, false),
+        AccountMeta::new(vault.share_mint, false),
+        AccountMeta::new(vault.share_account(&receiver), false),
+        AccountMeta::new(vault.asset_vault, false),
+        AccountMeta::new_readonly(vault.asset_mint, false),
+        AccountMeta::new(vault.dead_shares_account, false),
+        AccountMeta::new(pda::position(&address, &receiver), false),
+        optional(
+            optionals.fee_exemption,
+            b"fee_exempt",
+            &address,
+            &user,
+            false,
+        ),
+        optional(
+            optionals.allowlist_entry,
+            b"allowlist",
+            &address,
+            &receiver,
+            false,
+        ),
+        optional(
+            optionals.cost_basis,
+            b"cost_basis",
+            &address,
+            &receiver,
+            true,
+        ),
+        AccountMeta::new_readonly(pda::blocklist_entry(&address, &user), false),
+        AccountMeta::new_readonly(pda::blocklist_entry(&address, &receiver), false),
+        AccountMeta::new_readonly(vault.token_program, false),
+        AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(pda::event_authority(), false),
+        AccountMeta::new_readonly(PROGRAM_ID, false),
+    ];
+    let args = DepositArgs {
+        assets,
+        min_shares_out,
+    };
+    Ok(TxInstruction(Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: data("deposit", &args),
+    }))
+}
+
+/// `redeem` of `user`'s `shares`, paying `user`'s asset account or `receiver`'s. Paid from idle
+/// assets only: strategy accounts take a lookup table, which only `tsv-client` builds
+#[wasm_bindgen]
+pub fn redeem(
+    vault: &VaultAccounts,
+    user: &str,
+    receiver: Option<String>,
+    shares: u64,
+    min_assets_out: Option<u64>,
+    optionals: &Optionals,
+) -> Result<TxInstruction, WasmError> {
+    let user = parse_address("user", user)?;
+    let receiver = receiver
+        .map(|receiver| parse_address("receiver", &receiver))
+        .transpose()?;
+    let address = vault.address;
+    let accounts = vec![
+        AccountMeta::new(address, false),
+        AccountMeta::new(user, true),
+        AccountMeta::new_readonly(vault.asset_mint, false),
+        AccountMeta::new(vault.asset_account(&user), false),
+        receiver.map_or_else(absent, |receiver| {
+            AccountMeta::new(vault.asset_account(&receiver), false)
+        }),
+        AccountMeta::new(vault.share_account(&user), false),
+        AccountMeta::new(vault.asset_vault, false),
+        AccountMeta::new(vault.share_mint, false),
+        AccountMeta::new(pda::position(&address, &user), false),
+        optional(
+            optionals.fee_exemption,
+            b"fee_exempt",
+            &address,
+            &user,
+            false,
+        ),
+        optional(
+            optionals.allowlist_entry,
+            b"allowlist",
+            &address,
+            &user,
+            false,
+        ),
+        optional(optionals.cost_basis, b"cost_basis", &address, &user, true),
+        AccountMeta::new_readonly(pda::blocklist_entry(&address, &user), false),
+        receiver.map_or_else(absent, |receiver| {
+            AccountMeta::new_readonly(pda::blocklist_entry(&address, &receiver), false)
+        }),
+        AccountMeta::new_readonly(vault.token_program, false),
+        AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(pda::event_authority(), false),
+        AccountMeta::new_readonly(PROGRAM_ID, false),
+    ];
+    let args = RedeemArgs {
+        shares,
+        min_assets_out,
+        adapter_accounts: Vec::new(),
+    };
+    Ok(TxInstruction(Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: data("redeem", &args),
+    }))
+}
+
+/// Bytes `owner` signs with ed25519 to let a relayer redeem `shares` for them, paying
+/// `receiver` less at most `max_relayer_fee`. `nonce` is the current value of the owner's
+/// `UserNonce` (0 before it exists), `deadline` a Unix timestamp
+#[wasm_bindgen(js_name = metaRedeemMessage)]
+pub fn meta_redeem_message(
+    vault: &VaultAccounts,
+    owner: &str,
+    nonce: u64,
+    deadline: i64,
+    shares: u64,
+    receiver: &str,
+    max_relayer_fee: u64,
+) -> Result<Vec<u8>, WasmError> {
+    let action = MetaAction::Redeem {
+        shares,
+        receiver: parse_address("receiver", receiver)?,
+        max_relayer_fee,
+    };
+    meta_message(vault, owner, nonce, deadline, action)
+}
+
+/// Bytes `owner` signs with ed25519 to let a relayer deposit `assets` of theirs, minting shares
+/// to `receiver`
+#[wasm_bindgen(js_name = metaDepositMessage)]
+pub fn meta_deposit_message(
+    vault: &VaultAccounts,
+    owner: &str,
+    nonce: u64,
+    deadline: i64,
+    assets: u64,
+    receiver: &str,
+) -> Result<Vec<u8>, WasmError> {
+    let action = MetaAction::Deposit {
+        assets,
+        receiver: parse_address("receiver", receiver)?,
+    };
+    meta_message(vault, owner, nonce, deadline, action)
+}
+
+fn meta_message(
+    vault: &VaultAccounts,
+    owner: &str,
+    nonce: u64,
+    deadline: i64,
+    action: MetaAction,
+) -> Result<Vec<u8>, WasmError> {
+    let message = MetaMessage {
+        domain: vault.domain.clone(),
+        owner: parse_address("owner", owner)?,
+        nonce,
+        deadline,
+        action,
+    };
+    Ok(message.signing_bytes())
+}
+
+/// The `[seed, vault, wallet]` PDA if `present`, else the program id Anchor reads as `None`
+fn optional(
+    present: bool,
+    seed: &[u8],
+    vault: &Pubkey,
+    wallet: &Pubkey,
+    writable: bool,
+) -> AccountMeta {
+    if !present {
+        return absent();
+    }
+    let address =
+        Pubkey::find_program_address(&[seed, vault.as_ref(), wallet.as_ref()], &PROGRAM_ID).0;
+    if writable {
+        AccountMeta::new(address, false)
+    } else {
+        AccountMeta::new_readonly(address, false)
+    }
+}
+
+/// An optional account left out
+fn absent() -> AccountMeta {
+    AccountMeta::new_readonly(PROGRAM_ID, false)
+}
+
+/// Anchor's 8-byte discriminator of instruction `name`, then the Borsh-encoded `args`
+fn data(name: &str, args: &impl BorshSerialize) -> Vec<u8> {
+    let mut data = hash(format!("global:{name}").as_bytes()).to_bytes()[..8].to_vec();
+    args.serialize(&mut data)
+        .expect("writing to a Vec can't fail");
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use tsv_meta_message::{Domain, SCHEMA_VERSION};
+
+    use super::*;
+
+    fn accounts() -> (VaultAccounts, tsv_client::VaultAccounts) {
+        let asset_mint = Pubkey::new_unique();
+        let (share_mint, asset_vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token_program = Pubkey::new_unique();
+        let vault = VaultAccounts::new(
+            &asset_mint.to_string(),
+            false,
+            &share_mint.to_string(),
+            &asset_vault.to_string(),
+            &token_program.to_string(),
+            &[1; 32],
+        )
+        .unwrap();
+        let address = tsv_client::pda::vault(&asset_mint);
+        let client = tsv_client::VaultAccounts {
+            address,
+            asset_mint,
+            share_mint,
+            asset_vault,
+            dead_shares_account: tsv_client::pda::dead_shares(&address),
+            token_program,
+            domain: Domain {
+                schema_version: SCHEMA_VERSION,
+                genesis_hash: [1; 32],
+                program_id: tsv_usdc_vault::ID,
+                vault: address,
+            },
+        };
+        (vault, client)
+    }
+
+    fn optionals(vault: &Pubkey, payer: &Pubkey, holder: &Pubkey) -> tsv_client::Optionals {
+        tsv_client::Optionals {
+            fee_exemption: Some(tsv_client::pda::fee_exemption(vault, payer)),
+            allowlist_entry: None,
+            cost_basis: Some(tsv_client::pda::cost_basis(vault, holder)),
+        }
+    }
+
+    const SOME: Optionals = Optionals {
+        fee_exemption: true,
+        allowlist_entry: false,
+        cost_basis: true,
+    };
+
+    #[test]
+    fn deposit_matches_tsv_client() {
+        let (vault, client) = accounts();
+        let (user, receiver) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = deposit(
+            &vault,
+            &user.to_string(),
+            &receiver.to_string(),
+            1_000_000,
+            Some(990),
+            &SOME,
+        )
+        .unwrap();
+        let expected = tsv_client::instructions::deposit(
+            &client,
+            &user,
+            &receiver,
+            1_000_000,
+            Some(990),
+            optionals(&client.address, &user, &receiver),
+        );
+        assert_eq!(ix.0, expected);
+    }
+
+    #[test]
+    fn redeem_matches_tsv_client() {
+        let (vault, client) = accounts();
+        let (user, receiver) = (Pubkey::new_unique(), Pubkey::new_unique());
+        for to in [None, Some(receiver)] {
+            let ix = redeem(
+                &vault,
+                &user.to_string(),
+                to.map(|to| to.to_string()),
+                5_000,
+                None,
+                &SOME,
+            )
+            .unwrap();
+            let expected = tsv_client::instructions::redeem(
+                &client,
+                &user,
+                to.as_ref(),
+                5_000,
+                None,
+                optionals(&client.address, &user, &user),
+            );
+            assert_eq!(ix.0, expected);
+        }
+    }
+
+    #[test]
+    fn meta_redeem_message_is_the_signed_bytes() {
+        let (vault, client) = accounts();
+        let (owner, receiver) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let bytes = meta_redeem_message(
+            &vault,
+            &owner.to_string(),
+            3,
+            1_700_000_000,
+            5_000,
+            &receiver.to_string(),
+            50,
+        )
+        .unwrap();
+        let message = MetaMessage {
+            domain: client.domain,
+            owner,
+            nonce: 3,
+            deadline: 1_700_000_000,
+            action: MetaAction::Redeem {
+                shares: 5_000,
+                receiver,
+                max_relayer_fee: 50,
+            },
+        };
+        assert_eq!(bytes, message.signing_bytes());
+    }
+}
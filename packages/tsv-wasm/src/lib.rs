@@ -0,0 +1,36 @@
+//! `tsv-wasm`: a light client of the Talken Stable Vault program compiled to WebAssembly, for
+//! browsers and the React Native wallet. It derives the program's addresses, previews deposits
+//! and redeems with the program's own `tsv-math`, and encodes `deposit` and `redeem`
+//! instructions and the meta-transaction messages owners sign, all without an RPC client.
+//! Reading accounts and sending transactions stay with the wallet's own `@solana/web3.js`.
+
+mod accounts;
+mod error;
+mod instructions;
+mod pda;
+mod preview;
+
+use std::str::FromStr;
+
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub use accounts::{Optionals, VaultAccounts};
+pub use error::WasmError;
+pub use instructions::{AccountKey, TxInstruction};
+pub use preview::VaultTotals;
+
+/// Address the vault program is deployed at, its `declare_id!`
+pub const PROGRAM_ID: Pubkey = pubkey!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+
+/// The associated token account program, owning the wallets' token accounts
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// The base58 address in `value`, `name` naming it in the error
+fn parse_address(name: &'static str, value: &str) -> Result<Pubkey, WasmError> {
+    Pubkey::from_str(value).map_err(|_| WasmError::Address {
+        name,
+        value: value.to_string(),
+    })
+}
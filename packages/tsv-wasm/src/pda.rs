@@ -0,0 +1,161 @@
+//! Program-derived addresses of the vault program, mirroring its seeds. Every address is
+//! passed and returned as a base58 string.
+
+use solana_program::pubkey::Pubkey;
+use wasm_bindgen::prelude::*;
+
+use crate::error::WasmError;
+use crate::{parse_address, ASSOCIATED_TOKEN_PROGRAM_ID, PROGRAM_ID};
+
+/// Vault of `asset_mint`, or without it the single vault deployed before vaults were keyed by
+/// asset mint
+#[wasm_bindgen(js_name = vaultAddress)]
+pub fn vault_address(asset_mint: Option<String>) -> Result<String, WasmError> {
+    Ok(match asset_mint {
+        Some(asset_mint) => vault(&parse_address("asset mint", &asset_mint)?),
+        None => legacy_vault(),
+    }
+    .to_string())
+}
+
+#[wasm_bindgen(js_name = positionAddress)]
+pub fn position_address(vault: &str, owner: &str) -> Result<String, WasmError> {
+    seeded(b"position", vault, owner)
+}
+
+#[wasm_bindgen(js_name = feeExemptionAddress)]
+pub fn fee_exemption_address(vault: &str, wallet: &str) -> Result<String, WasmError> {
+    seeded(b"fee_exempt", vault, wallet)
+}
+
+#[wasm_bindgen(js_name = allowlistEntryAddress)]
+pub fn allowlist_entry_address(vault: &str, wallet: &str) -> Result<String, WasmError> {
+    seeded(b"allowlist", vault, wallet)
+}
+
+#[wasm_bindgen(js_name = blocklistEntryAddress)]
+pub fn blocklist_entry_address(vault: &str, wallet: &str) -> Result<String, WasmError> {
+    seeded(b"blocklist", vault, wallet)
+}
+
+#[wasm_bindgen(js_name = costBasisAddress)]
+pub fn cost_basis_address(vault: &str, owner: &str) -> Result<String, WasmError> {
+    seeded(b"cost_basis", vault, owner)
+}
+
+/// Meta-transaction nonce of `owner`, shared by every vault
+#[wasm_bindgen(js_name = userNonceAddress)]
+pub fn user_nonce_address(owner: &str) -> Result<String, WasmError> {
+    Ok(user_nonce(&parse_address("owner", owner)?).to_string())
+}
+
+/// Associated token account of `wallet` for `mint`, owned by `token_program` (SPL Token or
+/// Token-2022)
+#[wasm_bindgen(js_name = associatedTokenAddress)]
+pub fn associated_token_address(
+    wallet: &str,
+    mint: &str,
+    token_program: &str,
+) -> Result<String, WasmError> {
+    Ok(associated_token(
+        &parse_address("wallet", wallet)?,
+        &parse_address("mint", mint)?,
+        &parse_address("token program", token_program)?,
+    )
+    .to_string())
+}
+
+/// `[seed, vault, wallet]` PDA, from base58 addresses
+fn seeded(seed: &'static [u8], vault: &str, wallet: &str) -> Result<String, WasmError> {
+    let vault = parse_address("vault", vault)?;
+    let wallet = parse_address("wallet", wallet)?;
+    Ok(pda(&[seed, vault.as_ref(), wallet.as_ref()]).to_string())
+}
+
+pub(crate) fn vault(asset_mint: &Pubkey) -> Pubkey {
+    pda(&[b"vault", asset_mint.as_ref()])
+}
+
+pub(crate) fn legacy_vault() -> Pubkey {
+    pda(&[b"vault"])
+}
+
+/// Token account holding the shares locked by the first deposit
+pub(crate) fn dead_shares(vault: &Pubkey) -> Pubkey {
+    pda(&[b"dead_shares", vault.as_ref()])
+}
+
+pub(crate) fn position(vault: &Pubkey, owner: &Pubkey) -> Pubkey {
+    pda(&[b"position", vault.as_ref(), owner.as_ref()])
+}
+
+pub(crate) fn blocklist_entry(vault: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    pda(&[b"blocklist", vault.as_ref(), wallet.as_ref()])
+}
+
+pub(crate) fn user_nonce(owner: &Pubkey) -> Pubkey {
+    pda(&[b"nonce", owner.as_ref()])
+}
+
+/// PDA signing the program's self-CPI events, Anchor's `__event_authority`
+pub(crate) fn event_authority() -> Pubkey {
+    pda(&[b"__event_authority"])
+}
+
+pub(crate) fn associated_token(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &PROGRAM_ID).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_match_tsv_client() {
+        assert_eq!(PROGRAM_ID, tsv_usdc_vault::ID);
+        let (vault, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (vault_str, owner_str) = (vault.to_string(), owner.to_string());
+
+        assert_eq!(
+            vault_address(Some(owner_str.clone())).unwrap(),
+            tsv_client::pda::vault(&owner).to_string()
+        );
+        assert_eq!(
+            vault_address(None).unwrap(),
+            tsv_client::pda::legacy_vault().to_string()
+        );
+        assert_eq!(
+            position_address(&vault_str, &owner_str).unwrap(),
+            tsv_client::pda::position(&vault, &owner).to_string()
+        );
+        assert_eq!(
+            fee_exemption_address(&vault_str, &owner_str).unwrap(),
+            tsv_client::pda::fee_exemption(&vault, &owner).to_string()
+        );
+        assert_eq!(
+            allowlist_entry_address(&vault_str, &owner_str).unwrap(),
+            tsv_client::pda::allowlist_entry(&vault, &owner).to_string()
+        );
+        assert_eq!(
+            cost_basis_address(&vault_str, &owner_str).unwrap(),
+            tsv_client::pda::cost_basis(&vault, &owner).to_string()
+        );
+        assert_eq!(
+            user_nonce_address(&owner_str).unwrap(),
+            tsv_client::pda::user_nonce(&owner).to_string()
+        );
+        assert_eq!(event_authority(), tsv_client::pda::event_authority());
+        assert!(matches!(
+            position_address("not base58", &owner_str),
+            Err(WasmError::Address { name: "vault", .. })
+        ));
+    }
+}
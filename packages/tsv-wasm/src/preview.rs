@@ -0,0 +1,252 @@
+//! Previews of deposits and redeems from a vault's totals, the same math and rounding as
+//! `tsv-client`'s previews and the program's preview instructions.
+
+use tsv_math::{MathError, Rounding};
+use wasm_bindgen::prelude::*;
+
+use crate::error::WasmError;
+
+/// The fields of a fetched `Vault` the previews read, copied from the account the wallet
+/// decoded with the program's IDL. Amounts and timestamps are `bigint`s
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VaultTotals {
+    #[wasm_bindgen(js_name = totalAssets)]
+    pub total_assets: u64,
+    #[wasm_bindgen(js_name = totalShares)]
+    pub total_shares: u64,
+    #[wasm_bindgen(js_name = depositFeeBps)]
+    pub deposit_fee_bps: u16,
+    #[wasm_bindgen(js_name = redeemFeeBps)]
+    pub redeem_fee_bps: u16,
+    #[wasm_bindgen(js_name = maxRedeemFeeBps)]
+    pub max_redeem_fee_bps: u16,
+    #[wasm_bindgen(js_name = cooldownSeconds)]
+    pub cooldown_seconds: u32,
+    #[wasm_bindgen(js_name = cooldownPenaltyBps)]
+    pub cooldown_penalty_bps: u16,
+    #[wasm_bindgen(js_name = earlyExitWindowSeconds)]
+    pub early_exit_window_seconds: u32,
+    #[wasm_bindgen(js_name = earlyExitPenaltyBps)]
+    pub early_exit_penalty_bps: u16,
+    #[wasm_bindgen(js_name = managementFeeBps)]
+    pub management_fee_bps: u16,
+    #[wasm_bindgen(js_name = lastAccrualTs)]
+    pub last_accrual_ts: i64,
+    #[wasm_bindgen(js_name = lockedProfit)]
+    pub locked_profit: u64,
+    #[wasm_bindgen(js_name = lockedProfitTs)]
+    pub locked_profit_ts: i64,
+    #[wasm_bindgen(js_name = profitUnlockEndTs)]
+    pub profit_unlock_end_ts: i64,
+    #[wasm_bindgen(js_name = juniorAssets)]
+    pub junior_assets: u64,
+    #[wasm_bindgen(js_name = accruedFees)]
+    pub accrued_fees: u64,
+    #[wasm_bindgen(js_name = insuranceAssets)]
+    pub insurance_assets: u64,
+    #[wasm_bindgen(js_name = reservedWithdrawAssets)]
+    pub reserved_withdraw_assets: u64,
+}
+
+#[wasm_bindgen]
+impl VaultTotals {
+    /// An empty vault, to fill in field by field
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> VaultTotals {
+        Self::default()
+    }
+}
+
+/// Shares `deposit(assets)` mints at `now`, net of the deposit fee (rounds down). The dead
+/// shares of a vault's first deposit and Token-2022 transfer fees are not subtracted
+#[wasm_bindgen(js_name = previewDeposit)]
+pub fn preview_deposit(vault: &VaultTotals, assets: u64, now: i64) -> Result<u64, WasmError> {
+    let (free_assets, total_shares) = accrued_totals(vault, now)?;
+    let fee = tsv_math::fee_on_amount(assets, vault.deposit_fee_bps)?;
+    let net_assets = tsv_math::sub(assets, fee)?;
+    Ok(tsv_math::convert_to_shares(
+        net_assets,
+        free_assets,
+        total_shares,
+        Rounding::Floor,
+    )?)
+}
+
+/// Net assets `redeem(shares)` pays at `now` with `asset_vault_balance` USDC in the vault's
+/// asset account (rounds down), to an owner whose position's `lastDepositTs` is
+/// `last_deposit_ts` (0 without a position). It charges the utilization-scaled redeem fee plus
+/// the cooldown and early-exit penalties, nothing if `fee_exempt`, and throws where the
+/// cooldown rejects the redemption
+#[wasm_bindgen(js_name = previewRedeem)]
+pub fn preview_redeem(
+    vault: &VaultTotals,
+    shares: u64,
+    now: i64,
+    asset_vault_balance: u64,
+    last_deposit_ts: i64,
+    fee_exempt: bool,
+) -> Result<u64, WasmError> {
+    let (free_assets, total_shares) = accrued_totals(vault, now)?;
+    let gross_assets =
+        tsv_math::convert_to_assets(shares, free_assets, total_shares, Rounding::Floor)?;
+    let fee_bps = if fee_exempt {
+        0
+    } else {
+        redeem_fee_bps(vault, now, asset_vault_balance, last_deposit_ts)?
+    };
+    let fee = tsv_math::fee_on_amount(gross_assets, fee_bps)?;
+    Ok(tsv_math::sub(gross_assets, fee)?)
+}
+
+/// Fee in bps `redeem` charges a non-exempt owner, as `Vault::effective_redeem_fee_bps`
+/// computes it
+fn redeem_fee_bps(
+    vault: &VaultTotals,
+    now: i64,
+    asset_vault_balance: u64,
+    last_deposit_ts: i64,
+) -> Result<u16, WasmError> {
+    // Idle USDC as `Vault::idle_assets` counts it: fees, insurance and settled withdrawals
+    // aren't available to redeem from
+    let idle_assets = asset_vault_balance
+        .saturating_sub(vault.accrued_fees)
+        .saturating_sub(vault.insurance_assets)
+        .saturating_sub(vault.reserved_withdraw_assets);
+    let mut fee_bps = tsv_math::utilization_fee_bps(
+        vault.redeem_fee_bps,
+        vault.max_redeem_fee_bps,
+        idle_assets,
+        vault.total_assets,
+    )?;
+
+    let cooldown_ends = last_deposit_ts
+        .checked_add(vault.cooldown_seconds as i64)
+        .ok_or(MathError::Overflow)?;
+    if now < cooldown_ends {
+        if vault.cooldown_penalty_bps == 0 {
+            return Err(WasmError::CooldownActive);
+        }
+        fee_bps = fee_bps
+            .checked_add(vault.cooldown_penalty_bps)
+            .ok_or(MathError::Overflow)?;
+    }
+
+    let since_deposit = now.saturating_sub(last_deposit_ts).max(0) as u64;
+    let early_exit_bps = tsv_math::decayed_penalty_bps(
+        vault.early_exit_penalty_bps,
+        vault.early_exit_window_seconds,
+        since_deposit,
+    )?;
+    Ok(fee_bps
+        .checked_add(early_exit_bps)
+        .ok_or(MathError::Overflow)?)
+}
+
+/// Free assets and total shares with the management fee accrued and profit unlocked up to
+/// `now`, as `Vault::accrued` books them
+fn accrued_totals(vault: &VaultTotals, now: i64) -> Result<(u64, u64), WasmError> {
+    let elapsed = now.saturating_sub(vault.last_accrual_ts).max(0) as u64;
+    let fee_shares =
+        tsv_math::management_fee_shares(vault.total_shares, vault.management_fee_bps, elapsed)?;
+    let total_shares = tsv_math::add(vault.total_shares, fee_shares)?;
+    let locked_profit = tsv_math::locked_profit_at(
+        vault.locked_profit,
+        vault.locked_profit_ts,
+        vault.profit_unlock_end_ts,
+        now,
+    )?;
+    let free_assets = vault
+        .total_assets
+        .saturating_sub(locked_profit)
+        .saturating_sub(vault.junior_assets);
+    Ok((free_assets, total_shares))
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AccountDeserialize, Space};
+    use tsv_usdc_vault::{UserPosition, Vault};
+
+    use super::*;
+
+    const DAY: i64 = 24 * 3600;
+
+    #[test]
+    fn previews_match_tsv_client() {
+        let data = vec![0; 8 + Vault::INIT_SPACE];
+        let mut vault = Vault::try_deserialize_unchecked(&mut data.as_slice()).unwrap();
+        // 1,000 USDC backing 1,000,000 shares, 100 USDC of it locked over a day from 0
+        vault.total_assets = 1_000_000_000;
+        vault.total_shares = 1_000_000_000_000;
+        vault.locked_profit = 100_000_000;
+        vault.profit_unlock_end_ts = DAY;
+        vault.junior_assets = 50_000_000;
+        vault.deposit_fee_bps = 10;
+        vault.redeem_fee_bps = 20;
+        vault.max_redeem_fee_bps = 60;
+        vault.cooldown_seconds = DAY as u32;
+        vault.cooldown_penalty_bps = 50;
+        vault.early_exit_window_seconds = 10 * DAY as u32;
+        vault.early_exit_penalty_bps = 100;
+        vault.management_fee_bps = 200;
+        vault.accrued_fees = 1_000_000;
+        vault.insurance_assets = 2_000_000;
+        vault.reserved_withdraw_assets = 3_000_000;
+        let totals = VaultTotals {
+            total_assets: vault.total_assets,
+            total_shares: vault.total_shares,
+            deposit_fee_bps: vault.deposit_fee_bps,
+            redeem_fee_bps: vault.redeem_fee_bps,
+            max_redeem_fee_bps: vault.max_redeem_fee_bps,
+            cooldown_seconds: vault.cooldown_seconds,
+            cooldown_penalty_bps: vault.cooldown_penalty_bps,
+            early_exit_window_seconds: vault.early_exit_window_seconds,
+            early_exit_penalty_bps: vault.early_exit_penalty_bps,
+            management_fee_bps: vault.management_fee_bps,
+            last_accrual_ts: vault.last_accrual_ts,
+            locked_profit: vault.locked_profit,
+            locked_profit_ts: vault.locked_profit_ts,
+            profit_unlock_end_ts: vault.profit_unlock_end_ts,
+            junior_assets: vault.junior_assets,
+            accrued_fees: vault.accrued_fees,
+            insurance_assets: vault.insurance_assets,
+            reserved_withdraw_assets: vault.reserved_withdraw_assets,
+        };
+        // 400 USDC in the asset vault, 394 of it idle once the fees, insurance and settled
+        // withdrawals are set aside
+        let asset_vault_balance = 400_000_000;
+        let idle_assets = 394_000_000;
+        // Last deposit at 0: inside the cooldown, then only the early-exit window, then past both
+        let position = UserPosition::default();
+
+        for now in [0, DAY / 2, 5 * DAY, 30 * DAY] {
+            assert_eq!(
+                preview_deposit(&totals, 1_000_000, now).unwrap(),
+                tsv_client::preview_deposit(&vault, 1_000_000, now).unwrap()
+            );
+            for fee_exempt in [false, true] {
+                assert_eq!(
+                    preview_redeem(
+                        &totals,
+                        1_000_000_000,
+                        now,
+                        asset_vault_balance,
+                        0,
+                        fee_exempt
+                    )
+                    .unwrap(),
+                    tsv_client::preview_redeem(
+                        &vault,
+                        1_000_000_000,
+                        &position,
+                        fee_exempt,
+                        now,
+                        idle_assets
+                    )
+                    .unwrap()
+                );
+            }
+        }
+    }
+}